@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use mp_block::DeoxysBlock;
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::WriteBatchWithTransaction;
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::read_options;
+use crate::{cold_storage, Column, DatabaseExt, DbError, DB};
+
+/// The historical blocks backfilled so far, below the block the node started syncing from.
+///
+/// Blocks are backfilled from the most recent to the oldest, so the backfilled blocks always form
+/// the contiguous range `lowest..end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+pub struct BackfillRange {
+    /// The oldest backfilled block.
+    pub lowest: u64,
+    /// The block the node started syncing from, which the backfill stops short of.
+    pub end: u64,
+}
+
+impl BackfillRange {
+    /// Whether `block_number` has been backfilled.
+    pub fn contains(&self, block_number: u64) -> bool {
+        (self.lowest..self.end).contains(&block_number)
+    }
+
+    /// Whether `block_number` is a historical block that has not been backfilled yet.
+    pub fn is_missing(&self, block_number: u64) -> bool {
+        block_number < self.lowest
+    }
+
+    /// Whether every block down to the genesis block has been backfilled.
+    pub fn is_complete(&self) -> bool {
+        self.lowest == 0
+    }
+}
+
+/// Allow interaction with the backfill db
+///
+/// The backfill db stores the historical blocks retrieved in the background when the node starts
+/// syncing mid-chain.
+pub struct BackfillDb {
+    db: Arc<DB>,
+}
+
+impl BackfillDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Retrieve the range of backfilled blocks, if a backfill was ever started
+    pub fn range(&self) -> Result<Option<BackfillRange>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf_opt(&column, crate::static_keys::BACKFILL_RANGE, &read_options())? {
+            Some(raw) => Ok(Some(BackfillRange::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Start backfilling the blocks below `end`, or resume the backfill already started
+    pub fn start(&self, end: u64) -> Result<BackfillRange, DbError> {
+        if let Some(range) = self.range()? {
+            return Ok(range);
+        }
+
+        let range = BackfillRange { lowest: end, end };
+        let column = self.db.get_column(Column::Meta);
+        self.db.put_cf(&column, crate::static_keys::BACKFILL_RANGE, range.encode())?;
+        Ok(range)
+    }
+
+    /// Store the block right below the backfilled range, extending the range down to it
+    pub fn store_block(&self, block: &DeoxysBlock) -> Result<BackfillRange, DbError> {
+        let block_number = block.header().block_number;
+        let mut range = self.range()?.ok_or_else(|| {
+            let key = String::from_utf8_lossy(crate::static_keys::BACKFILL_RANGE).into_owned();
+            DbError::ValueNotInitialized(Column::Meta, key)
+        })?;
+        // blocks are backfilled from the most recent to the oldest
+        if block_number + 1 != range.lowest {
+            return Err(DbError::BackfillOutOfOrder { block_number, lowest: range.lowest });
+        }
+        range.lowest = block_number;
+
+        let blocks_col = self.db.get_column(Column::BackfilledBlocks);
+        let meta_col = self.db.get_column(Column::Meta);
+
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+        transaction.put_cf(&blocks_col, block_number.to_be_bytes(), block.encode());
+        transaction.put_cf(&meta_col, crate::static_keys::BACKFILL_RANGE, range.encode());
+        self.db.write(transaction)?;
+
+        Ok(range)
+    }
+
+    /// Retrieve a backfilled block
+    pub fn get_block(&self, block_number: u64) -> Result<Option<DeoxysBlock>, DbError> {
+        let column = self.db.get_column(Column::BackfilledBlocks);
+
+        let key = block_number.to_be_bytes();
+        let raw = match self.db.get_cf_opt(&column, key, &read_options())? {
+            Some(raw) => Some(raw),
+            None => cold_storage::get_cf(Column::BackfilledBlocks, &key)?,
+        };
+        match raw {
+            Some(raw) => Ok(Some(DeoxysBlock::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backfill_range() {
+        let range = BackfillRange { lowest: 10, end: 20 };
+
+        assert!(range.contains(10));
+        assert!(range.contains(19));
+        assert!(!range.contains(20));
+        assert!(range.is_missing(9));
+        assert!(!range.is_missing(10));
+        assert!(!range.is_complete());
+        assert!(BackfillRange { lowest: 0, end: 20 }.is_complete());
+    }
+}
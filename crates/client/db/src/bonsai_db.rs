@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use std::sync::Arc;
 
 use bonsai_trie::id::BasicId;
 use bonsai_trie::{BonsaiDatabase, BonsaiPersistentDatabase, DatabaseKey};
@@ -7,6 +8,7 @@ use rocksdb::{
     WriteBatchWithTransaction, WriteOptions,
 };
 
+use crate::snapshot::{DbSnapshot, SnapshotReadOptions};
 use crate::{BonsaiDbError, Column, DatabaseExt, DB};
 
 pub type RocksDBTransaction = WriteBatchWithTransaction<true>;
@@ -19,6 +21,22 @@ pub(crate) struct DatabaseKeyMapping {
 }
 
 impl DatabaseKeyMapping {
+    pub(crate) fn contracts() -> Self {
+        Self { flat: Column::BonsaiContractsFlat, trie: Column::BonsaiContractsTrie, log: Column::BonsaiContractsLog }
+    }
+
+    pub(crate) fn contracts_storage() -> Self {
+        Self {
+            flat: Column::BonsaiContractsStorageFlat,
+            trie: Column::BonsaiContractsStorageTrie,
+            log: Column::BonsaiContractsStorageLog,
+        }
+    }
+
+    pub(crate) fn classes() -> Self {
+        Self { flat: Column::BonsaiClassesFlat, trie: Column::BonsaiClassesTrie, log: Column::BonsaiClassesLog }
+    }
+
     pub(crate) fn map(&self, key: &DatabaseKey) -> Column {
         match key {
             DatabaseKey::Trie(_) => self.trie,
@@ -34,11 +52,23 @@ pub struct BonsaiDb<'db> {
     /// Mapping from `DatabaseKey` => rocksdb column name
     column_mapping: DatabaseKeyMapping,
     snapshots: BTreeMap<BasicId, SnapshotWithThreadMode<'db, DB>>,
+    /// The snapshot reads are served from, for the read-only tries opened on a snapshot.
+    read_snapshot: Option<Arc<DbSnapshot>>,
 }
 
 impl<'db> BonsaiDb<'db> {
     pub(crate) fn new(db: &'db DB, column_mapping: DatabaseKeyMapping) -> Self {
-        Self { db, column_mapping, snapshots: BTreeMap::new() }
+        Self { db, column_mapping, snapshots: BTreeMap::new(), read_snapshot: None }
+    }
+
+    /// A database serving every read from `snapshot`, to open a trie as it was when the snapshot
+    /// was taken. Nothing should be written through it.
+    pub(crate) fn at_snapshot(db: &'db DB, column_mapping: DatabaseKeyMapping, snapshot: Arc<DbSnapshot>) -> Self {
+        Self { db, column_mapping, snapshots: BTreeMap::new(), read_snapshot: Some(snapshot) }
+    }
+
+    fn read_options(&self) -> SnapshotReadOptions {
+        SnapshotReadOptions::new(self.read_snapshot.clone())
     }
 }
 
@@ -53,13 +83,14 @@ impl BonsaiDatabase for BonsaiDb<'_> {
     fn get(&self, key: &DatabaseKey) -> Result<Option<Vec<u8>>, Self::DatabaseError> {
         log::trace!("Getting from RocksDB: {:?}", key);
         let handle = self.db.get_column(self.column_mapping.map(key));
-        Ok(self.db.get_cf(&handle, key.as_slice())?)
+        Ok(self.db.get_cf_opt(&handle, key.as_slice(), &self.read_options())?)
     }
 
     fn get_by_prefix(&self, prefix: &DatabaseKey) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::DatabaseError> {
         log::trace!("Getting from RocksDB: {:?}", prefix);
         let handle = self.db.get_column(self.column_mapping.map(prefix));
-        let iter = self.db.iterator_cf(&handle, IteratorMode::From(prefix.as_slice(), Direction::Forward));
+        let mode = IteratorMode::From(prefix.as_slice(), Direction::Forward);
+        let iter = self.read_options().iterator_cf(self.db, &handle, mode);
         Ok(iter
             .map_while(|kv| {
                 if let Ok((key, value)) = kv {
@@ -74,7 +105,7 @@ impl BonsaiDatabase for BonsaiDb<'_> {
     fn contains(&self, key: &DatabaseKey) -> Result<bool, Self::DatabaseError> {
         log::trace!("Checking if RocksDB contains: {:?}", key);
         let handle = self.db.get_column(self.column_mapping.map(key));
-        Ok(self.db.get_cf(&handle, key.as_slice()).map(|value| value.is_some())?)
+        Ok(self.db.get_cf_opt(&handle, key.as_slice(), &self.read_options()).map(|value| value.is_some())?)
     }
 
     fn insert(
@@ -0,0 +1,90 @@
+//! Backfill of the index of contracts by class hash.
+//!
+//! The index is written along with the contract data of every stored block, but databases synced
+//! before it was introduced hold contracts which are not indexed. These are indexed from their
+//! class hash histories on a background thread, which records the last contract it indexed so that
+//! a restarted node resumes where it stopped. The classes a contract held before its history was
+//! pruned are not indexed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rocksdb::{Direction, IteratorMode, WriteBatchWithTransaction};
+use starknet_api::core::ContractAddress;
+
+use crate::meta_db::ClassIndexBackfill;
+use crate::storage_handler::contract_data::class_deployments;
+use crate::storage_handler::primitives::contract::StorageContractData;
+use crate::storage_handler::{DeoxysStorageError, StorageType};
+use crate::{Column, DatabaseExt, DeoxysBackend};
+
+/// Number of contracts indexed in a single write.
+const BACKFILL_BATCH: u64 = 4096;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Starts indexing the contracts stored before the class index on a background thread, if they
+/// are not all indexed yet.
+///
+/// The database must be open. The backfill is only started once, later calls are ignored.
+pub fn start() {
+    if STARTED.swap(true, Ordering::Relaxed) || is_complete() {
+        return;
+    }
+    std::thread::Builder::new()
+        .name("db-class-index".into())
+        .spawn(|| match backfill() {
+            Ok(indexed) => log::info!("🗃️ Indexed {indexed} contracts by class hash"),
+            Err(e) => log::error!("❗ Failed to index the contracts by class hash: {e}"),
+        })
+        .expect("Failed to spawn the class index thread");
+}
+
+/// Whether every stored contract is indexed by class hash.
+pub fn is_complete() -> bool {
+    matches!(DeoxysBackend::meta().class_index_backfill(), Ok(Some(ClassIndexBackfill::Done)))
+}
+
+/// Indexes every stored contract by class hash, resuming from the last contract indexed.
+///
+/// Returns the number of contracts indexed.
+fn backfill() -> Result<u64, DeoxysStorageError> {
+    let db = DeoxysBackend::expose_db();
+    let meta = DeoxysBackend::meta();
+    let column = db.get_column(Column::ContractData);
+    let deployments_column = db.get_column(Column::ClassDeployments);
+
+    let start = match meta.class_index_backfill() {
+        Ok(Some(ClassIndexBackfill::Indexing(cursor))) => cursor,
+        Ok(Some(ClassIndexBackfill::Done)) => return Ok(0),
+        Ok(None) => Vec::new(),
+        Err(_) => return Err(DeoxysStorageError::StorageRetrievalError(StorageType::ContractData)),
+    };
+
+    let mut batch = WriteBatchWithTransaction::<true>::default();
+    let mut indexed = 0;
+    for entry in db.iterator_cf(&column, IteratorMode::From(&start, Direction::Forward)) {
+        let (key, value) = entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?;
+        let contract_address: ContractAddress = bincode::deserialize(&key)
+            .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData))?;
+        let contract_data: StorageContractData = bincode::deserialize(&value)
+            .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData))?;
+
+        // reindexing a contract already indexed by the sync rewrites the same entries
+        for (index_key, block_number) in class_deployments(&contract_address, &contract_data) {
+            batch.put_cf(&deployments_column, index_key, block_number);
+        }
+        indexed += 1;
+
+        if indexed % BACKFILL_BATCH == 0 {
+            db.write(std::mem::take(&mut batch))
+                .map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::ContractData))?;
+            meta.write_class_index_backfill(&ClassIndexBackfill::Indexing(key.to_vec()))
+                .map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::ContractData))?;
+        }
+    }
+    db.write(batch).map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::ContractData))?;
+    meta.write_class_index_backfill(&ClassIndexBackfill::Done)
+        .map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::ContractData))?;
+
+    Ok(indexed)
+}
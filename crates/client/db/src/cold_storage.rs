@@ -0,0 +1,145 @@
+//! Tiering of the data of the oldest blocks to a secondary database.
+//!
+//! The state diffs and the backfilled blocks are written to the main database. Once a block is
+//! older than the number of blocks kept hot, its data is moved on a background thread, woken up
+//! after every applied block, to a cold database which may be on cheaper and slower storage. Reads
+//! of these columns fall back to the cold database transparently. The blocks moved so far are
+//! recorded, so a restarted node resumes moving them where it stopped.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::thread::Thread;
+
+use anyhow::Result;
+use parity_scale_codec::Encode;
+use rocksdb::WriteBatchWithTransaction;
+
+use crate::storage_handler::{DeoxysStorageError, StorageType};
+use crate::{open_rocksdb, ColdStorage, Column, DatabaseExt, DeoxysBackend, DB};
+
+/// The number of blocks moved to the cold database in a single write.
+const TIERING_BATCH: u64 = 256;
+
+static COLD_DB: OnceLock<DB> = OnceLock::new();
+static TIERING: OnceLock<Tiering> = OnceLock::new();
+
+struct Tiering {
+    after_blocks: u64,
+    last_applied: AtomicU64,
+    thread: Thread,
+}
+
+/// Opens the cold database, which the reads of the tiered columns then fall back to.
+pub(crate) fn open(cold_storage: &ColdStorage) -> Result<()> {
+    let _ = COLD_DB.set(open_rocksdb(&cold_storage.path, true)?);
+    Ok(())
+}
+
+/// Starts moving the data of the blocks older than the last `after_blocks` to the cold database on
+/// a background thread.
+///
+/// The database must be open with a cold database. The tiering is only started once, later calls
+/// are ignored.
+pub fn start(after_blocks: u64) {
+    if COLD_DB.get().is_none() || TIERING.get().is_some() {
+        return;
+    }
+
+    let thread = std::thread::Builder::new()
+        .name("db-tiering".into())
+        .spawn(|| {
+            loop {
+                std::thread::park();
+                if let Some(tiering) = TIERING.get() {
+                    tiering.move_cold_blocks();
+                }
+            }
+        })
+        .expect("Failed to spawn the tiering thread");
+    let _ = TIERING.set(Tiering { after_blocks, last_applied: AtomicU64::new(0), thread: thread.thread().clone() });
+}
+
+/// Schedules the move of the blocks falling out of the hot blocks once `block_number` is applied.
+pub fn block_applied(block_number: u64) {
+    if let Some(tiering) = TIERING.get() {
+        tiering.last_applied.fetch_max(block_number, Ordering::Relaxed);
+        tiering.thread.unpark();
+    }
+}
+
+/// Reads `key` from `column` in the cold database, if there is one.
+pub(crate) fn get_cf(column: Column, key: &[u8]) -> Result<Option<Vec<u8>>, rocksdb::Error> {
+    match COLD_DB.get() {
+        Some(cold_db) => cold_db.get_cf(&cold_db.get_column(column), key),
+        None => Ok(None),
+    }
+}
+
+/// Deletes the state diffs of `blocks` from the cold database, if there is one.
+pub(crate) fn delete_state_diffs(blocks: std::ops::Range<u64>) -> Result<(), rocksdb::Error> {
+    let Some(cold_db) = COLD_DB.get() else {
+        return Ok(());
+    };
+
+    let column = cold_db.get_column(Column::BlockStateDiff);
+    let mut batch = WriteBatchWithTransaction::<true>::default();
+    for block_number in blocks {
+        batch.delete_cf(&column, bincode::serialize(&block_number).unwrap());
+    }
+    cold_db.write(batch)
+}
+
+impl Tiering {
+    fn move_cold_blocks(&self) {
+        let hot_start = (self.last_applied.load(Ordering::Relaxed) + 1).saturating_sub(self.after_blocks);
+        let mut next = match DeoxysBackend::meta().cold_up_to() {
+            Ok(cold_up_to) => cold_up_to.unwrap_or_default(),
+            Err(e) => {
+                log::error!("❗ Failed to read the blocks moved to cold storage: {e}");
+                return;
+            }
+        };
+
+        while next < hot_start {
+            let end = (next + TIERING_BATCH).min(hot_start);
+            if let Err(e) = move_blocks(next, end) {
+                log::error!("❗ Failed to move blocks #{next}..#{end} to cold storage: {e}");
+                return;
+            }
+            next = end;
+        }
+    }
+}
+
+/// The keys of the data of `block_number` in the tiered columns.
+fn block_keys(block_number: u64) -> [(Column, Vec<u8>); 2] {
+    [
+        (Column::BlockStateDiff, bincode::serialize(&block_number).unwrap()),
+        (Column::BackfilledBlocks, block_number.to_be_bytes().to_vec()),
+    ]
+}
+
+/// Moves the data of blocks `start..end` to the cold database.
+fn move_blocks(start: u64, end: u64) -> Result<(), DeoxysStorageError> {
+    let db = DeoxysBackend::expose_db();
+    let cold_db = COLD_DB.get().expect("Cold database not opened");
+
+    let mut cold_batch = WriteBatchWithTransaction::<true>::default();
+    let mut batch = WriteBatchWithTransaction::<true>::default();
+    for (column, key) in (start..end).flat_map(block_keys) {
+        let value = db
+            .get_cf(&db.get_column(column), &key)
+            .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::Block))?;
+        let Some(value) = value else {
+            continue;
+        };
+        cold_batch.put_cf(&cold_db.get_column(column), &key, value);
+        batch.delete_cf(&db.get_column(column), key);
+    }
+    batch.put_cf(&db.get_column(Column::Meta), crate::static_keys::COLD_UP_TO, end.encode());
+
+    // the blocks are written to the cold database before being deleted from the main one, so that
+    // they can always be read from either
+    cold_db.write(cold_batch).map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::Block))?;
+    db.write(batch).map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::Block))
+}
@@ -0,0 +1,88 @@
+//! Compaction of the database, scheduled from the shape of its levels.
+//!
+//! RocksDB compacts the database in the background on its own, but while the sync writes faster
+//! than it compacts, files pile up in level 0 and the levels grow past their target size, which
+//! slows the reads down. Rather than compacting the whole database at fixed intervals, which stalls
+//! the sync, a background thread checks the columns of the database and only compacts the columns
+//! going over one of the thresholds of the [CompactionPolicy].
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{Column, DatabaseExt, DeoxysBackend};
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// When the columns of the database are compacted.
+#[derive(Debug, Clone)]
+pub struct CompactionPolicy {
+    /// The number of files in level 0 of a column above which it is compacted.
+    pub max_l0_files: u64,
+    /// The estimated bytes to rewrite to bring the levels of a column back to their target size,
+    /// above which it is compacted.
+    pub max_pending_bytes: u64,
+    /// How often the columns are checked.
+    pub check_interval: Duration,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self { max_l0_files: 20, max_pending_bytes: 64 * 1024 * 1024 * 1024, check_interval: Duration::from_secs(60) }
+    }
+}
+
+impl CompactionPolicy {
+    fn is_exceeded(&self, l0_files: u64, pending_bytes: u64) -> bool {
+        l0_files > self.max_l0_files || pending_bytes > self.max_pending_bytes
+    }
+}
+
+/// Starts checking the columns of the database against `policy` on a background thread.
+///
+/// The database must be open. The scheduler is only started once, later calls are ignored.
+pub fn start(policy: CompactionPolicy) {
+    if STARTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    std::thread::Builder::new()
+        .name("db-compaction".into())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(policy.check_interval);
+                compact_over_thresholds(&policy);
+            }
+        })
+        .expect("Failed to spawn the compaction thread");
+}
+
+fn compact_over_thresholds(policy: &CompactionPolicy) {
+    let db = DeoxysBackend::expose_db();
+    for column in Column::ALL {
+        let handle = db.get_column(*column);
+        let property = |name: &str| db.property_int_value_cf(&handle, name).ok().flatten().unwrap_or_default();
+        let l0_files = property("rocksdb.num-files-at-level0");
+        let pending_bytes = property("rocksdb.estimate-pending-compaction-bytes");
+        if !policy.is_exceeded(l0_files, pending_bytes) {
+            continue;
+        }
+
+        let name = column.rocksdb_name();
+        log::debug!("Compacting column {name}: {l0_files} files in level 0, {pending_bytes} bytes pending compaction");
+        let start = Instant::now();
+        db.compact_range_cf(&handle, None::<&[u8]>, None::<&[u8]>);
+        log::debug!("Compacted column {name} in {:?}", start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_are_compacted_over_either_threshold() {
+        let policy = CompactionPolicy { max_l0_files: 8, max_pending_bytes: 1024, ..Default::default() };
+        assert!(!policy.is_exceeded(8, 1024));
+        assert!(policy.is_exceeded(9, 0));
+        assert!(policy.is_exceeded(0, 1025));
+    }
+}
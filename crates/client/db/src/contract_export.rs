@@ -0,0 +1,168 @@
+//! Export of the full state of a single contract at a block.
+//!
+//! A contract export holds the class hash, nonce and storage of a contract, along with its class,
+//! as JSON, so that the state of a real contract can seed the fixtures of a devnet or a fork. Every
+//! read is served from a single snapshot of the database, and of the state at the exported block,
+//! so the export is consistent with itself.
+
+use mp_convert::field_element::FromFieldElement;
+use mp_felt::Felt252Wrapper;
+use serde::Serialize;
+use starknet_api::core::ContractAddress;
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+use starknet_core::types::{ContractClass, FieldElement, StorageEntry};
+use thiserror::Error;
+
+use crate::storage_handler::primitives::contract_class::{ContractClassWrapper, StorageContractClassData};
+use crate::storage_handler::{self, DeoxysStorageError, StorageView};
+use crate::{DbError, DeoxysBackend};
+
+/// The number of storage entries read from the database at once.
+const STORAGE_PAGE: usize = 1024;
+
+#[derive(Error, Debug)]
+pub enum ContractExportError {
+    #[error("block {block_number} is not synced yet, the node is at block {synced}")]
+    NotSynced { block_number: u64, synced: u64 },
+    #[error("contract {0:#x} is not deployed at block {1}")]
+    NotDeployed(FieldElement, u64),
+    #[error("class {0:#x} of the contract not found")]
+    ClassNotFound(FieldElement),
+    #[error("failed to convert class {0:#x}: {1}")]
+    Class(FieldElement, anyhow::Error),
+    #[error(transparent)]
+    Storage(#[from] DeoxysStorageError),
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+/// The state of a contract at a block.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContractExport {
+    pub block_number: u64,
+    pub block_hash: FieldElement,
+    pub address: FieldElement,
+    pub class_hash: FieldElement,
+    /// The compiled class hash of the class, for Sierra classes.
+    pub compiled_class_hash: Option<FieldElement>,
+    pub nonce: FieldElement,
+    /// The non-zero storage entries of the contract, in key order.
+    pub storage: Vec<StorageEntry>,
+    pub class: ContractClass,
+}
+
+/// Reads the state of the contract at `address` at `block_number`.
+pub fn export_contract(address: FieldElement, block_number: u64) -> Result<ContractExport, ContractExportError> {
+    let synced = DeoxysBackend::meta().sync_checkpoint()?.map(|checkpoint| checkpoint.block_number);
+    match synced {
+        Some(synced) if synced >= block_number => {}
+        synced => return Err(ContractExportError::NotSynced { block_number, synced: synced.unwrap_or_default() }),
+    }
+
+    DeoxysBackend::snapshot().pin(|| {
+        let contract_address = ContractAddress::from_field_element(address);
+        let contract_data = storage_handler::contract_data();
+        let class_hash = contract_data
+            .get_class_hash_at(&contract_address, block_number)?
+            .ok_or(ContractExportError::NotDeployed(address, block_number))?;
+        let nonce = contract_data.get_nonce_at(&contract_address, block_number)?.unwrap_or_default();
+
+        let storage = storage_at(&contract_address, block_number, STORAGE_PAGE)?;
+
+        let class_hash_felt = felt(class_hash.0);
+        let StorageContractClassData { contract_class, abi, sierra_program_length, abi_length } =
+            storage_handler::contract_class_data()
+                .get(&class_hash)?
+                .ok_or(ContractExportError::ClassNotFound(class_hash_felt))?;
+        let class = ContractClassWrapper { contract: contract_class, abi, sierra_program_length, abi_length }
+            .try_into()
+            .map_err(|e| ContractExportError::Class(class_hash_felt, e))?;
+        let compiled_class_hash = storage_handler::contract_class_hashes().get(&class_hash)?;
+
+        let block_hash = storage_handler::block_hash().get(block_number)?.map_or(FieldElement::ZERO, |hash| hash.0);
+
+        Ok(ContractExport {
+            block_number,
+            block_hash,
+            address,
+            class_hash: class_hash_felt,
+            compiled_class_hash: compiled_class_hash.map(|hash| felt(hash.0)),
+            nonce: felt(nonce.0),
+            storage,
+            class,
+        })
+    })
+}
+
+/// Reads the non-zero storage of a contract at `block_number`, `page` entries at a time.
+fn storage_at(
+    address: &ContractAddress,
+    block_number: u64,
+    page: usize,
+) -> Result<Vec<StorageEntry>, DeoxysStorageError> {
+    let contract_storage = storage_handler::contract_storage();
+    let mut storage = Vec::new();
+    let mut start_key = StorageKey::default();
+
+    loop {
+        let entries = contract_storage.get_contract_storage_at(address, &start_key, block_number, page)?;
+        let last_page = entries.len() < page;
+        storage.extend(
+            entries.into_iter().map(|(key, value)| StorageEntry { key: felt(*key.0.key()), value: felt(value) }),
+        );
+
+        // the next page starts right after the last key read
+        match storage.last() {
+            Some(last) if !last_page && last.key != FieldElement::MAX => {
+                start_key = StorageKey::from_field_element(last.key + FieldElement::ONE);
+            }
+            _ => return Ok(storage),
+        }
+    }
+}
+
+fn felt(felt: StarkFelt) -> FieldElement {
+    Felt252Wrapper::from(felt).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::PatriciaKey;
+
+    use super::*;
+    use crate::storage_handler::StorageViewMut;
+
+    #[test]
+    fn storage_is_read_in_pages() {
+        let _db = DeoxysBackend::open_for_testing();
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(0x266u64)));
+        let key = |n: u64| StorageKey(PatriciaKey(StarkFelt::from(n)));
+
+        // 5 entries at block 20, one of them cleared at block 21
+        let contract_storage = storage_handler::contract_storage_mut();
+        for n in 1..=5 {
+            contract_storage.insert((address, key(n)), StarkFelt::from(n + 10)).unwrap();
+        }
+        contract_storage.commit(20).unwrap();
+        let contract_storage = storage_handler::contract_storage_mut();
+        contract_storage.insert((address, key(3)), StarkFelt::ZERO).unwrap();
+        contract_storage.commit(21).unwrap();
+
+        let keys = |storage: Vec<StorageEntry>| storage.into_iter().map(|entry| entry.key).collect::<Vec<_>>();
+        let expected = (1..=5).map(FieldElement::from).collect::<Vec<_>>();
+        for page in [1, 2, 5, 6, STORAGE_PAGE] {
+            assert_eq!(keys(storage_at(&address, 20, page).unwrap()), expected);
+        }
+        let expected = [1u64, 2, 4, 5].map(FieldElement::from);
+        assert_eq!(keys(storage_at(&address, 21, 2).unwrap()), expected);
+        assert!(storage_at(&address, 19, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn blocks_not_synced_are_not_exported() {
+        let _db = DeoxysBackend::open_for_testing();
+        let export = export_contract(FieldElement::from(0x266u64), u64::MAX);
+        assert!(matches!(export, Err(ContractExportError::NotSynced { block_number: u64::MAX, .. })));
+    }
+}
@@ -9,10 +9,14 @@ pub enum DbError {
     RocksDB(#[from] rocksdb::Error),
     #[error("Failed to deserialize DB Data: `{0}`")]
     DeserializeError(#[from] parity_scale_codec::Error),
+    #[error("Failed to deserialize DB Data: `{0}`")]
+    Json(#[from] serde_json::Error),
     #[error("Failed to build Uuid: `{0}`")]
     Uuid(#[from] uuid::Error),
     #[error("A value was queryied that was not initialized at column: `{0}` key: `{1}`")]
     ValueNotInitialized(Column, String),
+    #[error("Block {block_number} is not the block right below the backfilled blocks, starting at {lowest}")]
+    BackfillOutOfOrder { block_number: u64, lowest: u64 },
 }
 
 #[derive(Debug, Error)]
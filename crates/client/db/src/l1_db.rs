@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::{Direction, IteratorMode};
+use starknet_api::hash::StarkHash;
+
+use crate::snapshot::{iterator_cf, read_options};
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// A state update posted to the Starknet core contract on L1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct L1Confirmation {
+    /// The Ethereum block in which the state update was posted, unknown for the state read from the
+    /// core contract rather than from its events.
+    pub l1_block_number: Option<u64>,
+    pub block_hash: StarkHash,
+    pub global_root: StarkHash,
+}
+
+/// Allow interaction with the l1 db
+///
+/// The l1 db maps the Starknet blocks whose state update was posted to L1 to their confirmation,
+/// so that the blocks accepted on L1 are known as soon as the node restarts.
+pub struct L1Db {
+    db: Arc<DB>,
+}
+
+impl L1Db {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Store the state update of `block_number` posted to L1
+    ///
+    /// A confirmation whose Ethereum block is known is never replaced by one whose block is not.
+    pub fn store_state_update(&self, block_number: u64, confirmation: &L1Confirmation) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::L1StateUpdates);
+        let key = block_number.to_be_bytes();
+
+        if confirmation.l1_block_number.is_none() {
+            if let Some(raw) = self.db.get_cf_opt(&column, key, &read_options())? {
+                if L1Confirmation::decode(&mut &raw[..])?.l1_block_number.is_some() {
+                    return Ok(());
+                }
+            }
+        }
+        self.db.put_cf(&column, key, confirmation.encode())?;
+        Ok(())
+    }
+
+    /// Retrieve the last block confirmed on L1, along with its confirmation
+    pub fn last_confirmed(&self) -> Result<Option<(u64, L1Confirmation)>, DbError> {
+        let column = self.db.get_column(Column::L1StateUpdates);
+        let mut iter = iterator_cf(&self.db, &column, IteratorMode::End);
+        iter.next().transpose()?.map(|(key, value)| decode_entry(&key, &value)).transpose()
+    }
+
+    /// Retrieve the state update confirming `block_number` on L1, which is the first one posted at
+    /// or after it
+    pub fn confirmation_of(&self, block_number: u64) -> Result<Option<(u64, L1Confirmation)>, DbError> {
+        let column = self.db.get_column(Column::L1StateUpdates);
+        let start = block_number.to_be_bytes();
+        let mut iter = iterator_cf(&self.db, &column, IteratorMode::From(&start, Direction::Forward));
+        iter.next().transpose()?.map(|(key, value)| decode_entry(&key, &value)).transpose()
+    }
+}
+
+/// Decodes an entry of the [Column::L1StateUpdates] column, keyed by the big endian block number so
+/// that the entries are in chain order.
+fn decode_entry(key: &[u8], value: &[u8]) -> Result<(u64, L1Confirmation), DbError> {
+    let key: [u8; 8] = key.try_into().map_err(|_| parity_scale_codec::Error::from("invalid block number key"))?;
+    Ok((u64::from_be_bytes(key), L1Confirmation::decode(&mut &value[..])?))
+}
@@ -4,6 +4,7 @@ use parity_scale_codec::Encode;
 use starknet_api::hash::StarkFelt;
 use starknet_api::transaction::Fee;
 
+use crate::snapshot::read_options;
 use crate::{Column, DatabaseExt, DbError, DB};
 
 pub struct L1HandlerTxFeeDb {
@@ -27,7 +28,7 @@ impl L1HandlerTxFeeDb {
     pub fn get_fee_paid_for_l1_handler_tx(&self, tx_hash: StarkFelt) -> Result<Fee, DbError> {
         let column = self.db.get_column(Column::L1HandlerPaidFee);
 
-        if let Some(bytes) = self.db.get_cf(&column, tx_hash.encode())? {
+        if let Some(bytes) = self.db.get_cf_opt(&column, tx_hash.encode(), &read_options())? {
             let mut buff = [0u8; 16];
 
             buff.copy_from_slice(&bytes);
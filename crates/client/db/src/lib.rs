@@ -16,38 +16,110 @@ use std::path::{Path, PathBuf};
 use std::sync::{Arc, OnceLock, RwLock};
 
 use anyhow::{bail, Context, Result};
+use backfill_db::BackfillDb;
 use bonsai_db::{BonsaiDb, DatabaseKeyMapping};
 use bonsai_trie::id::BasicId;
 use bonsai_trie::{BonsaiStorage, BonsaiStorageConfig};
+use l1_db::L1Db;
 use l1_handler_tx_fee::L1HandlerTxFeeDb;
 use mapping_db::MappingDb;
 use meta_db::MetaDb;
+use pending_db::PendingDb;
 use sc_client_db::DatabaseSource;
+use transfer_db::TransferDb;
 
 mod error;
 mod mapping_db;
 use rocksdb::{
     BoundColumnFamily, ColumnFamilyDescriptor, DBCompressionType, MultiThreaded, OptimisticTransactionDB, Options,
 };
+use serde::{Deserialize, Serialize};
 use starknet_api::hash::StarkHash;
-use starknet_types_core::hash::{Pedersen, Poseidon};
+use starknet_types_core::hash::{Pedersen, Poseidon, StarkHash as StarkHashTrait};
+mod backfill_db;
 pub mod bonsai_db;
+pub mod class_index;
+pub mod cold_storage;
+pub mod compaction;
+pub mod contract_export;
+mod l1_db;
 mod l1_handler_tx_fee;
 mod meta_db;
+mod pending_db;
+pub mod perf;
+pub mod pruning;
+mod schema_version;
+pub mod snapshot;
+pub mod state_snapshot;
 pub mod storage_handler;
 pub mod storage_updates;
+mod transfer_db;
 
+pub use backfill_db::BackfillRange;
 pub use error::{BonsaiDbError, DbError};
+pub use l1_db::L1Confirmation;
 pub use mapping_db::MappingCommitment;
-use storage_handler::bonsai_identifier;
+pub use meta_db::{ApplyJournal, ClassIndexBackfill, SyncCheckpoint};
+pub use schema_version::{read_schema_version, DB_SCHEMA_VERSION};
+use snapshot::TrieSnapshots;
+pub use snapshot::{DbSnapshot, TRIE_SNAPSHOTS_KEPT};
+use storage_handler::{bonsai_identifier, DeoxysStorageError, StorageType, TrieType};
+pub use transfer_db::TokenTransfer;
 
 const DB_HASH_LEN: usize = 32;
 /// Hash type that this backend uses for the database.
 pub type DbHash = [u8; DB_HASH_LEN];
 
+/// A secondary database, on cheaper and slower storage, holding the data of the oldest blocks.
+///
+/// The state diffs and backfilled blocks older than `after_blocks` are moved from the main
+/// database to the cold database. Reads go through both databases transparently. Once blocks have
+/// been moved to a cold database, the node must always be started with it.
+#[derive(Debug, Clone)]
+pub struct ColdStorage {
+    pub path: PathBuf,
+    /// The number of last blocks whose data is kept in the main database.
+    pub after_blocks: u64,
+}
+
+/// The memory held by the database, as estimated by RocksDB, in bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryUsage {
+    /// The memtables, holding the writes not flushed to disk yet.
+    pub mem_tables: u64,
+    /// The indexes and filters of the open files.
+    pub table_readers: u64,
+    /// The blocks cached in memory.
+    pub block_cache: u64,
+}
+
+/// The statistics of a column of the database, as estimated by RocksDB.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnStats {
+    pub column: String,
+    pub estimated_keys: u64,
+    /// The size of the files of the column on disk, once compressed.
+    pub disk_bytes: u64,
+    /// The size of the writes to the column not flushed to disk yet.
+    pub mem_table_bytes: u64,
+    pub l0_files: u64,
+    /// The bytes to rewrite to bring the levels of the column back to their target size.
+    pub pending_compaction_bytes: u64,
+}
+
+/// The statistics of the database, to spot the columns growing before the disk fills up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbStats {
+    pub columns: Vec<ColumnStats>,
+    /// The share of the blocks read which were found in the block cache, `None` unless the
+    /// statistics of the database are enabled.
+    pub block_cache_hit_rate: Option<f64>,
+}
+
 struct DatabaseSettings {
     /// Where to find the database.
     pub source: DatabaseSource,
+    pub cold_storage: Option<ColdStorage>,
     pub max_saved_trie_logs: Option<usize>,
     pub max_saved_snapshots: Option<usize>,
     pub snapshot_interval: u64,
@@ -65,6 +137,20 @@ impl From<&DatabaseSettings> for BonsaiStorageConfig {
 
 pub type DB = OptimisticTransactionDB<MultiThreaded>;
 
+/// The number of blocks the tries can be reverted by, as only the logs of their last commits are
+/// kept. This bounds the depth of the reorgs handled and of the blocks reverted by the `revert`
+/// command.
+pub const TRIE_LOG_DEPTH: usize = 64;
+
+/// The configuration of the tries, whether opened for the sync or on a snapshot.
+fn bonsai_config() -> BonsaiStorageConfig {
+    BonsaiStorageConfig {
+        max_saved_trie_logs: Some(TRIE_LOG_DEPTH),
+        max_saved_snapshots: Some(0),
+        snapshot_interval: u64::MAX,
+    }
+}
+
 pub(crate) fn open_database(config: &DatabaseSettings) -> Result<DB> {
     Ok(match &config.source {
         DatabaseSource::RocksDb { path, .. } => open_rocksdb(path, true)?,
@@ -84,6 +170,7 @@ pub(crate) fn open_rocksdb(path: &Path, create: bool) -> Result<OptimisticTransa
     opts.set_compression_type(DBCompressionType::Zstd);
     let cores = std::thread::available_parallelism().map(|e| e.get() as i32).unwrap_or(1);
     opts.increase_parallelism(i32::max(cores / 2, 1));
+    perf::configure(&mut opts);
 
     let db = OptimisticTransactionDB::<MultiThreaded>::open_cf_descriptors(
         &opts,
@@ -94,6 +181,21 @@ pub(crate) fn open_rocksdb(path: &Path, create: bool) -> Result<OptimisticTransa
     Ok(db)
 }
 
+/// Creates a checkpoint of the RocksDB database in `path` at `out`: a consistent copy of the
+/// database, whose immutable files are hard linked when `out` is on the same filesystem.
+///
+/// The database is opened to create the checkpoint, which thus fails while a node is running on it.
+pub fn create_checkpoint(path: &Path, out: &Path) -> Result<()> {
+    let opts = Options::default();
+    let columns = rocksdb::DB::list_cf(&opts, path)?;
+    let db = rocksdb::DB::open_cf(&opts, path, columns)?;
+    if let Some(parent) = out.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    rocksdb::checkpoint::Checkpoint::new(&db)?.create_checkpoint(out)?;
+    Ok(())
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Column {
     Meta,
@@ -134,6 +236,69 @@ pub enum Column {
     BonsaiClassesTrie,
     BonsaiClassesFlat,
     BonsaiClassesLog,
+
+    /// This column is used to store the historical blocks backfilled below the block the node
+    /// started syncing from.
+    BackfilledBlocks,
+
+    /// This column is used to map substrate block hashes to the number of transactions of the
+    /// starknet block they contain, so that it can be read without decoding the block.
+    BlockTransactionCount,
+
+    /// This column is used to index contracts by class hash, mapping each `(class_hash,
+    /// contract_address)` pair to the block at which the contract got this class, whether it was
+    /// deployed with it or replaced its class with it.
+    ClassDeployments,
+
+    /// This column is used to index transactions by sender, mapping each `(sender_address,
+    /// block_number, transaction_index)` triple to the hash of the transaction.
+    ///
+    /// This column should only be accessed if the `--cache` flag is enabled.
+    SenderTransactions,
+
+    /// This column maps each block number to the keys it wrote in [Column::SenderTransactions], so
+    /// that they are removed when the block is reverted.
+    ///
+    /// This column should only be accessed if the `--cache` flag is enabled.
+    BlockSenderTransactions,
+
+    /// This column maps each starknet block number to the hash of the substrate block wrapping it,
+    /// its hash and the hashes of its transactions, so that their mapping is removed when the block
+    /// is reverted.
+    StarknetBlockMapping,
+
+    /// This column is used to index ERC-20 transfers by account, mapping each `(account,
+    /// block_number, transaction_index, event_index)` tuple to the transfer, for both the sender
+    /// and the recipient.
+    ///
+    /// This column is only written to if the `--index-transfers` flag is enabled.
+    TokenTransfers,
+
+    /// This column is used to index ERC-20 transfers by account and token, mapping each `(account,
+    /// token_address, block_number, transaction_index, event_index)` tuple to the transfer, for
+    /// both the sender and the recipient.
+    ///
+    /// This column is only written to if the `--index-transfers` flag is enabled.
+    AccountTokenTransfers,
+
+    /// This column maps each block number to the ERC-20 transfers indexed for it, so that they are
+    /// removed from the index when the block is reverted.
+    ///
+    /// This column is only written to if the `--index-transfers` flag is enabled.
+    BlockTokenTransfers,
+
+    /// This column is used to map the starknet blocks whose state update was posted to L1 to the
+    /// Ethereum block it was posted in.
+    L1StateUpdates,
+
+    /// This column holds the last pending block along with its state update, so that it is served
+    /// as soon as the node restarts.
+    PendingBlock,
+
+    /// This column is used to store every value taken by the storage keys of contracts, mapping
+    /// each `(contract_address, storage_key, block_number)` triple to the value set at that block,
+    /// so that the storage is read at any block with a single seek.
+    ContractStorageVersions,
 }
 
 impl fmt::Debug for Column {
@@ -175,6 +340,18 @@ impl Column {
             BonsaiClassesTrie,
             BonsaiClassesFlat,
             BonsaiClassesLog,
+            BackfilledBlocks,
+            BlockTransactionCount,
+            ClassDeployments,
+            SenderTransactions,
+            BlockSenderTransactions,
+            StarknetBlockMapping,
+            TokenTransfers,
+            AccountTokenTransfers,
+            BlockTokenTransfers,
+            L1StateUpdates,
+            PendingBlock,
+            ContractStorageVersions,
         ]
     };
     pub const NUM_COLUMNS: usize = Self::ALL.len();
@@ -204,6 +381,18 @@ impl Column {
             Column::ContractData => "contract_data",
             Column::ContractClassHashes => "contract_class_hashes",
             Column::ContractStorage => "contrac_storage",
+            Column::BackfilledBlocks => "backfilled_blocks",
+            Column::BlockTransactionCount => "block_transaction_count",
+            Column::ClassDeployments => "class_deployments",
+            Column::SenderTransactions => "sender_transactions",
+            Column::BlockSenderTransactions => "block_sender_transactions",
+            Column::StarknetBlockMapping => "starknet_block_mapping",
+            Column::TokenTransfers => "token_transfers",
+            Column::AccountTokenTransfers => "account_token_transfers",
+            Column::BlockTokenTransfers => "block_token_transfers",
+            Column::L1StateUpdates => "l1_state_updates",
+            Column::PendingBlock => "pending_block",
+            Column::ContractStorageVersions => "contract_storage_versions",
         }
     }
 
@@ -235,6 +424,18 @@ pub mod static_keys {
     pub const CURRENT_SYNCING_TIPS: &[u8] = b"CURRENT_SYNCING_TIPS";
     pub const LAST_PROVED_BLOCK: &[u8] = b"LAST_PROVED_BLOCK";
     pub const LAST_SYNCED_L1_EVENT_BLOCK: &[u8] = b"LAST_SYNCED_L1_EVENT_BLOCK";
+    pub const APPLY_JOURNAL: &[u8] = b"APPLY_JOURNAL";
+    pub const BACKFILL_RANGE: &[u8] = b"BACKFILL_RANGE";
+    pub const SYNC_CHECKPOINT: &[u8] = b"SYNC_CHECKPOINT";
+    pub const CLASS_BACKFILL_CURSOR: &[u8] = b"CLASS_BACKFILL_CURSOR";
+    pub const SCHEMA_VERSION: &[u8] = b"SCHEMA_VERSION";
+    pub const PRUNED_UP_TO: &[u8] = b"PRUNED_UP_TO";
+    pub const STATE_PRUNED_BEFORE: &[u8] = b"STATE_PRUNED_BEFORE";
+    pub const VERSIONS_MIGRATION: &[u8] = b"VERSIONS_MIGRATION";
+    pub const CLASS_INDEX_BACKFILL: &[u8] = b"CLASS_INDEX_BACKFILL";
+    pub const COLD_UP_TO: &[u8] = b"COLD_UP_TO";
+    pub const TRIES_BLOCK: &[u8] = b"TRIES_BLOCK";
+    pub const EXECUTION_POLICY: &[u8] = b"EXECUTION_POLICY";
 }
 
 /// Returns the Starknet database directory.
@@ -250,6 +451,9 @@ pub fn starknet_database_dir(db_config_dir: &Path, db_path: &str) -> PathBuf {
 ///
 /// * `meta`: stores data aboud the current state of the chain.
 /// * `mapping`: maps Starknet blocks to Substrate blocks.
+/// * `backfill`: stores the historical blocks backfilled when syncing from a trusted root.
+/// * `transfers`: indexes the ERC-20 transfers by account.
+/// * `pending`: stores the last pending block.
 /// * `da`: store Data Availability info that needs to be written to the Ethereum L1.
 /// * `messaging`: Stores Ethereum L1 messaging data.
 /// * `sierra_classes`: @antyro what is this for?
@@ -260,10 +464,15 @@ pub fn starknet_database_dir(db_config_dir: &Path, db_path: &str) -> PathBuf {
 pub struct DeoxysBackend {
     meta: Arc<MetaDb>,
     mapping: Arc<MappingDb>,
+    backfill: Arc<BackfillDb>,
+    transfers: Arc<TransferDb>,
+    l1: Arc<L1Db>,
+    pending: Arc<PendingDb>,
     l1_handler_paid_fee: Arc<L1HandlerTxFeeDb>,
     bonsai_contract: RwLock<BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>,
     bonsai_storage: RwLock<BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>,
     bonsai_class: RwLock<BonsaiStorage<BasicId, BonsaiDb<'static>, Poseidon>>,
+    trie_snapshots: RwLock<TrieSnapshots>,
 }
 
 // Singleton backing instance for `DeoxysBackend`
@@ -280,16 +489,42 @@ impl DeoxysBackend {
         database: &DatabaseSource,
         db_config_dir: &Path,
         cache_more_things: bool,
+        cold_storage: Option<ColdStorage>,
     ) -> Result<&'static Arc<DeoxysBackend>> {
         BACKEND_SINGLETON
-            .set(Arc::new(Self::init(database, db_config_dir, cache_more_things).unwrap()))
+            .set(Arc::new(Self::init(database, db_config_dir, cache_more_things, cold_storage).unwrap()))
             .ok()
             .context("Backend already initialized")?;
+        storage_handler::versioned_storage::resume_migration()?;
 
         Ok(BACKEND_SINGLETON.get().unwrap())
     }
 
-    fn init(database: &DatabaseSource, db_config_dir: &Path, cache_more_things: bool) -> Result<Self> {
+    /// Opens a database in a temporary directory, once per process, for the tests.
+    ///
+    /// The database is shared by all the tests of the process, which hold the returned guard so
+    /// that they do not write to it concurrently.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn open_for_testing() -> std::sync::MutexGuard<'static, ()> {
+        static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        static OPEN: std::sync::Once = std::sync::Once::new();
+
+        OPEN.call_once(|| {
+            let dir = std::env::temp_dir().join(format!("deoxys-db-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            let database = DatabaseSource::RocksDb { path: dir.clone(), cache_size: 0 };
+            Self::open(&database, &dir, false, None).expect("Failed to open the test database");
+        });
+        // a failed test must not fail the tests which follow it
+        LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn init(
+        database: &DatabaseSource,
+        db_config_dir: &Path,
+        cache_more_things: bool,
+        cold_storage: Option<ColdStorage>,
+    ) -> Result<Self> {
         Self::new(
             &DatabaseSettings {
                 source: match database {
@@ -306,7 +541,8 @@ impl DeoxysBackend {
                     },
                     _ => bail!("Supported db sources: `rocksdb` | `paritydb` | `auto`"),
                 },
-                max_saved_trie_logs: Some(0),
+                cold_storage,
+                max_saved_trie_logs: Some(TRIE_LOG_DEPTH),
                 max_saved_snapshots: Some(0),
                 snapshot_interval: u64::MAX,
             },
@@ -316,61 +552,36 @@ impl DeoxysBackend {
 
     fn new(config: &DatabaseSettings, cache_more_things: bool) -> Result<Self> {
         DB_SINGLETON.set(Arc::new(open_database(config)?)).unwrap();
+        if let Some(cold_storage) = &config.cold_storage {
+            cold_storage::open(cold_storage)?;
+        }
         let db = DB_SINGLETON.get().unwrap();
-        let bonsai_config = BonsaiStorageConfig {
-            max_saved_trie_logs: Some(0),
-            max_saved_snapshots: Some(0),
-            snapshot_interval: u64::MAX,
-        };
-
-        let mut bonsai_contract = BonsaiStorage::new(
-            BonsaiDb::new(
-                db,
-                DatabaseKeyMapping {
-                    flat: Column::BonsaiContractsFlat,
-                    trie: Column::BonsaiContractsTrie,
-                    log: Column::BonsaiContractsLog,
-                },
-            ),
-            bonsai_config.clone(),
-        )
-        .unwrap();
+        let mut bonsai_contract =
+            BonsaiStorage::new(BonsaiDb::new(db, DatabaseKeyMapping::contracts()), bonsai_config()).unwrap();
         bonsai_contract.init_tree(bonsai_identifier::CONTRACT).unwrap();
 
-        let bonsai_contract_storage = BonsaiStorage::new(
-            BonsaiDb::new(
-                db,
-                DatabaseKeyMapping {
-                    flat: Column::BonsaiContractsStorageFlat,
-                    trie: Column::BonsaiContractsStorageTrie,
-                    log: Column::BonsaiContractsStorageLog,
-                },
-            ),
-            bonsai_config.clone(),
-        )
-        .unwrap();
-
-        let mut bonsai_classes = BonsaiStorage::new(
-            BonsaiDb::new(
-                db,
-                DatabaseKeyMapping {
-                    flat: Column::BonsaiClassesFlat,
-                    trie: Column::BonsaiClassesTrie,
-                    log: Column::BonsaiClassesLog,
-                },
-            ),
-            bonsai_config.clone(),
-        )
-        .unwrap();
+        let bonsai_contract_storage =
+            BonsaiStorage::new(BonsaiDb::new(db, DatabaseKeyMapping::contracts_storage()), bonsai_config()).unwrap();
+
+        let mut bonsai_classes =
+            BonsaiStorage::new(BonsaiDb::new(db, DatabaseKeyMapping::classes()), bonsai_config()).unwrap();
         bonsai_classes.init_tree(bonsai_identifier::CLASS).unwrap();
 
+        let meta = MetaDb::new(Arc::clone(db));
+        schema_version::upgrade(&meta)?;
+
         Ok(Self {
             mapping: Arc::new(MappingDb::new(Arc::clone(db), cache_more_things)),
-            meta: Arc::new(MetaDb::new(Arc::clone(db))),
+            meta: Arc::new(meta),
+            backfill: Arc::new(BackfillDb::new(Arc::clone(db))),
+            transfers: Arc::new(TransferDb::new(Arc::clone(db))),
+            l1: Arc::new(L1Db::new(Arc::clone(db))),
+            pending: Arc::new(PendingDb::new(Arc::clone(db))),
             l1_handler_paid_fee: Arc::new(L1HandlerTxFeeDb::new(Arc::clone(db))),
             bonsai_contract: RwLock::new(bonsai_contract),
             bonsai_storage: RwLock::new(bonsai_contract_storage),
             bonsai_class: RwLock::new(bonsai_classes),
+            trie_snapshots: RwLock::new(TrieSnapshots::new(db)),
         })
     }
 
@@ -384,6 +595,26 @@ impl DeoxysBackend {
         BACKEND_SINGLETON.get().map(|backend| &backend.meta).expect("Backend not initialized")
     }
 
+    /// Return the backfill database manager
+    pub fn backfill() -> &'static Arc<BackfillDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.backfill).expect("Backend not initialized")
+    }
+
+    /// Return the transfer database manager
+    pub fn transfers() -> &'static Arc<TransferDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.transfers).expect("Backend not initialized")
+    }
+
+    /// Return the l1 database manager
+    pub fn l1() -> &'static Arc<L1Db> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.l1).expect("Backend not initialized")
+    }
+
+    /// Return the pending block database manager
+    pub fn pending() -> &'static Arc<PendingDb> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.pending).expect("Backend not initialized")
+    }
+
     pub(crate) fn bonsai_contract() -> &'static RwLock<BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>> {
         BACKEND_SINGLETON.get().map(|backend| &backend.bonsai_contract).expect("Backend not initialized")
     }
@@ -396,6 +627,20 @@ impl DeoxysBackend {
         BACKEND_SINGLETON.get().map(|backend| &backend.bonsai_class).expect("Backend not initialized")
     }
 
+    pub(crate) fn trie_snapshots() -> &'static RwLock<TrieSnapshots> {
+        BACKEND_SINGLETON.get().map(|backend| &backend.trie_snapshots).expect("Backend not initialized")
+    }
+
+    /// Opens a read-only trie on `snapshot`, as it was when the snapshot was taken.
+    pub(crate) fn open_trie<H: StarkHashTrait + Send + Sync>(
+        column_mapping: DatabaseKeyMapping,
+        snapshot: Arc<DbSnapshot>,
+        trie_type: TrieType,
+    ) -> Result<BonsaiStorage<BasicId, BonsaiDb<'static>, H>, DeoxysStorageError> {
+        BonsaiStorage::new(BonsaiDb::at_snapshot(Self::expose_db(), column_mapping, snapshot), bonsai_config())
+            .map_err(|_| DeoxysStorageError::TrieInitError(trie_type))
+    }
+
     pub(crate) fn expose_db() -> &'static Arc<DB> {
         DB_SINGLETON.get().expect("Databsae not initialized")
     }
@@ -404,11 +649,70 @@ impl DeoxysBackend {
         Self::expose_db().compact_range(None::<&[u8]>, None::<&[u8]>);
     }
 
+    /// Estimates the memory held by the database, over all of its columns.
+    pub fn memory_usage() -> MemoryUsage {
+        let db = Self::expose_db();
+        let mut usage = MemoryUsage::default();
+        for column in Column::ALL {
+            let handle = db.get_column(*column);
+            let property = |name: &str| db.property_int_value_cf(&handle, name).ok().flatten().unwrap_or_default();
+            usage.mem_tables += property("rocksdb.cur-size-all-mem-tables");
+            usage.table_readers += property("rocksdb.estimate-table-readers-mem");
+            usage.block_cache += property("rocksdb.block-cache-usage");
+        }
+        usage
+    }
+
+    /// Estimates the size, number of keys and compaction backlog of every column of the database.
+    pub fn db_stats() -> DbStats {
+        let db = Self::expose_db();
+        let columns = Column::ALL
+            .iter()
+            .map(|column| {
+                let handle = db.get_column(*column);
+                let property = |name: &str| db.property_int_value_cf(&handle, name).ok().flatten().unwrap_or_default();
+                ColumnStats {
+                    column: column.rocksdb_name().to_string(),
+                    estimated_keys: property("rocksdb.estimate-num-keys"),
+                    disk_bytes: property("rocksdb.total-sst-files-size"),
+                    mem_table_bytes: property("rocksdb.cur-size-all-mem-tables"),
+                    l0_files: property("rocksdb.num-files-at-level0"),
+                    pending_compaction_bytes: property("rocksdb.estimate-pending-compaction-bytes"),
+                }
+            })
+            .collect();
+        DbStats { columns, block_cache_hit_rate: perf::block_cache_hit_rate() }
+    }
+
+    /// Syncs the write-ahead log to disk, so that every write made so far survives the node
+    /// stopping.
+    pub fn flush() -> Result<(), DbError> {
+        Self::expose_db().flush_wal(true)?;
+        Ok(())
+    }
+
     /// Return l1 handler tx paid fee database manager
     pub fn l1_handler_paid_fee() -> &'static Arc<L1HandlerTxFeeDb> {
         BACKEND_SINGLETON.get().map(|backend| &backend.l1_handler_paid_fee).expect("Backend not initialized")
     }
 
+    /// Reverts the chain to `block_number`, undoing the state and class updates of every block
+    /// stored after it, along with their mapping and indexes.
+    ///
+    /// The tries are reverted too when they committed blocks after `block_number`, whether or not
+    /// state roots are still verified. This is used to handle reorgs and the `revert` command,
+    /// after which the chain is synced again from `block_number`.
+    pub async fn revert_to(block_number: u64) -> Result<(), DeoxysStorageError> {
+        let revert_error = |_| DeoxysStorageError::StorageRevertError(StorageType::Block, block_number);
+
+        storage_updates::revert_state_updates_to(block_number).await?;
+        if Self::meta().tries_block().map_err(revert_error)?.is_some_and(|tries_block| tries_block > block_number) {
+            storage_updates::revert_tries_to(block_number)?;
+        }
+        Self::mapping().revert_to(block_number).map_err(revert_error)?;
+        Self::transfers().revert_transfers(block_number).map_err(revert_error)
+    }
+
     /// In the future, we will compute the block global state root asynchronously in the client,
     /// using the Starknet-Bonzai-trie.
     /// That what replaces it for now :)
@@ -3,10 +3,12 @@ use std::sync::Arc;
 use mp_types::block::{DBlockT, DHashT};
 // Substrate
 use parity_scale_codec::{Decode, Encode};
-use rocksdb::WriteBatchWithTransaction;
+use rocksdb::{Direction, IteratorMode, WriteBatchWithTransaction};
 use sp_runtime::traits::Block as BlockT;
+use starknet_api::core::ContractAddress;
 use starknet_api::hash::StarkHash;
 
+use crate::snapshot::{iterator_cf, read_options};
 use crate::{Column, DatabaseExt, DbError, DB};
 
 /// The mapping to write in db
@@ -16,6 +18,23 @@ pub struct MappingCommitment<B: BlockT> {
     pub block_hash: B::Hash,
     pub starknet_block_hash: StarkHash,
     pub starknet_transaction_hashes: Vec<StarkHash>,
+    /// The sender of each transaction, if it has one.
+    pub starknet_transaction_senders: Vec<Option<ContractAddress>>,
+}
+
+/// Length of the keys of the [Column::SenderTransactions] column.
+const SENDER_TRANSACTION_KEY_LEN: usize = 48;
+
+/// Returns the key of a transaction in the [Column::SenderTransactions] column.
+///
+/// Numbers are written big endian so that the transactions of a sender are ordered by block and by
+/// index in the block.
+fn sender_transaction_key(sender_address: &ContractAddress, block_number: u64, transaction_index: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(SENDER_TRANSACTION_KEY_LEN);
+    key.extend_from_slice(&sender_address.0.0.0);
+    key.extend_from_slice(&block_number.to_be_bytes());
+    key.extend_from_slice(&transaction_index.to_be_bytes());
+    key
 }
 
 /// Allow interaction with the mapping db
@@ -35,7 +54,7 @@ impl MappingDb {
     pub fn is_synced(&self, block_hash: &DHashT) -> Result<bool, DbError> {
         let synced_mapping_col = self.db.get_column(Column::SyncedMapping);
 
-        match self.db.get_cf(&synced_mapping_col, block_hash.encode())? {
+        match self.db.get_cf_opt(&synced_mapping_col, block_hash.encode(), &read_options())? {
             Some(raw) => Ok(bool::decode(&mut &raw[..])?),
             None => Ok(false),
         }
@@ -48,7 +67,7 @@ impl MappingDb {
     pub fn block_hash(&self, starknet_block_hash: StarkHash) -> Result<Option<Vec<DHashT>>, DbError> {
         let block_mapping_col = self.db.get_column(Column::BlockMapping);
 
-        match self.db.get_cf(&block_mapping_col, starknet_block_hash.encode())? {
+        match self.db.get_cf_opt(&block_mapping_col, starknet_block_hash.encode(), &read_options())? {
             Some(raw) => Ok(Some(Vec::<DHashT>::decode(&mut &raw[..])?)),
             None => Ok(None),
         }
@@ -69,6 +88,10 @@ impl MappingDb {
         let transaction_mapping_col = self.db.get_column(Column::TransactionMapping);
         let starknet_tx_hashes_col = self.db.get_column(Column::StarknetTransactionHashesCache);
         let starknet_block_hashes_col = self.db.get_column(Column::StarknetBlockHashesCache);
+        let transaction_count_col = self.db.get_column(Column::BlockTransactionCount);
+        let sender_transactions_col = self.db.get_column(Column::SenderTransactions);
+        let block_sender_transactions_col = self.db.get_column(Column::BlockSenderTransactions);
+        let starknet_block_mapping_col = self.db.get_column(Column::StarknetBlockMapping);
 
         let mut transaction: WriteBatchWithTransaction<true> = Default::default();
 
@@ -90,10 +113,23 @@ impl MappingDb {
 
         transaction.put_cf(&synced_mapping_col, &commitment.block_hash.encode(), &true.encode());
 
+        transaction.put_cf(
+            &transaction_count_col,
+            &commitment.block_hash.encode(),
+            &(commitment.starknet_transaction_hashes.len() as u128).encode(),
+        );
+
         for transaction_hash in commitment.starknet_transaction_hashes.iter() {
             transaction.put_cf(&transaction_mapping_col, &transaction_hash.encode(), &commitment.block_hash.encode());
         }
 
+        // block numbers are written big endian, so that the blocks after a reverted one follow it
+        transaction.put_cf(
+            &starknet_block_mapping_col,
+            commitment.block_number.to_be_bytes(),
+            (commitment.block_hash, commitment.starknet_block_hash, &commitment.starknet_transaction_hashes).encode(),
+        );
+
         if self.cache_more_things {
             transaction.put_cf(
                 &starknet_tx_hashes_col,
@@ -106,6 +142,21 @@ impl MappingDb {
                 &commitment.block_number.encode(),
                 &commitment.starknet_block_hash.encode(),
             );
+
+            let senders = commitment.starknet_transaction_hashes.iter().zip(&commitment.starknet_transaction_senders);
+            let mut sender_keys = Vec::new();
+            for (transaction_index, (transaction_hash, sender_address)) in senders.enumerate() {
+                if let Some(sender_address) = sender_address {
+                    let key = sender_transaction_key(sender_address, commitment.block_number, transaction_index as u64);
+                    transaction.put_cf(&sender_transactions_col, &key, transaction_hash.encode());
+                    sender_keys.push(key);
+                }
+            }
+            transaction.put_cf(
+                &block_sender_transactions_col,
+                commitment.block_number.to_be_bytes(),
+                sender_keys.encode(),
+            );
         }
 
         self.db.write(transaction)?;
@@ -124,12 +175,26 @@ impl MappingDb {
     pub fn block_hash_from_transaction_hash(&self, transaction_hash: StarkHash) -> Result<Option<DHashT>, DbError> {
         let transaction_mapping_col = self.db.get_column(Column::TransactionMapping);
 
-        match self.db.get_cf(&transaction_mapping_col, transaction_hash.encode())? {
+        match self.db.get_cf_opt(&transaction_mapping_col, transaction_hash.encode(), &read_options())? {
             Some(raw) => Ok(Some(<DHashT>::decode(&mut &raw[..])?)),
             None => Ok(None),
         }
     }
 
+    /// Returns the number of transactions of the starknet block contained in the given substrate
+    /// block.
+    ///
+    /// This function may return `None` if the block was imported before transaction counts were
+    /// recorded.
+    pub fn transaction_count(&self, block_hash: DHashT) -> Result<Option<u128>, DbError> {
+        let transaction_count_col = self.db.get_column(Column::BlockTransactionCount);
+
+        match self.db.get_cf_opt(&transaction_count_col, block_hash.encode(), &read_options())? {
+            Some(raw) => Ok(Some(u128::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
     /// Returns the list of transaction hashes for the given block hash.
     ///
     /// # Arguments
@@ -155,7 +220,7 @@ impl MappingDb {
             return Ok(None);
         }
 
-        match self.db.get_cf(&starknet_tx_hashes_col, starknet_block_hash.encode())? {
+        match self.db.get_cf_opt(&starknet_tx_hashes_col, starknet_block_hash.encode(), &read_options())? {
             Some(raw) => Ok(Some(Vec::<StarkHash>::decode(&mut &raw[..])?)),
             None => Ok(None),
         }
@@ -186,9 +251,255 @@ impl MappingDb {
             return Ok(None);
         }
 
-        match self.db.get_cf(&starknet_block_hashes_col, starknet_block_number.encode())? {
+        match self.db.get_cf_opt(&starknet_block_hashes_col, starknet_block_number.encode(), &read_options())? {
             Some(raw) => Ok(Some(<StarkHash>::decode(&mut &raw[..])?)),
             None => Ok(None),
         }
     }
+
+    /// Returns the transactions sent by the given account, along with the block they are included
+    /// in and their index in this block.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_address` - the account which sent the transactions.
+    /// * `start` - the block number and transaction index to start from, included.
+    /// * `to_block` - the last block to search, included.
+    /// * `limit` - the maximum number of transactions to return.
+    ///
+    /// # Returns
+    ///
+    /// The transactions ordered by block and by index in the block.
+    ///
+    /// This function returns `None` if the cache is disabled, as the transactions are not indexed
+    /// by sender then.
+    pub fn transactions_by_sender(
+        &self,
+        sender_address: &ContractAddress,
+        start: (u64, u64),
+        to_block: u64,
+        limit: usize,
+    ) -> Result<Option<Vec<(u64, u64, StarkHash)>>, DbError> {
+        let sender_transactions_col = self.db.get_column(Column::SenderTransactions);
+
+        if !self.cache_more_things {
+            // The index is not maintained, no need to even touch the database.
+            return Ok(None);
+        }
+
+        let prefix = &sender_address.0.0.0;
+        let start = sender_transaction_key(sender_address, start.0, start.1);
+        let iter = iterator_cf(&self.db, &sender_transactions_col, IteratorMode::From(&start, Direction::Forward));
+
+        let mut transactions = Vec::with_capacity(limit);
+        for entry in iter.take(limit) {
+            let (key, value) = entry?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if key.len() != SENDER_TRANSACTION_KEY_LEN {
+                return Err(DbError::DeserializeError("Invalid sender transaction key".into()));
+            }
+            let block_number = u64::from_be_bytes(key[32..40].try_into().unwrap());
+            let transaction_index = u64::from_be_bytes(key[40..].try_into().unwrap());
+            if block_number > to_block {
+                break;
+            }
+
+            let transaction_hash = StarkHash::decode(&mut &value[..])?;
+            transactions.push((block_number, transaction_index, transaction_hash));
+        }
+
+        Ok(Some(transactions))
+    }
+
+    /// Counts the transactions sent by the given account from `from_block` to `to_block`, included.
+    ///
+    /// This function returns `None` if the cache is disabled, as the transactions are not indexed
+    /// by sender then.
+    pub fn count_transactions_by_sender(
+        &self,
+        sender_address: &ContractAddress,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Option<u64>, DbError> {
+        let sender_transactions_col = self.db.get_column(Column::SenderTransactions);
+
+        if !self.cache_more_things {
+            return Ok(None);
+        }
+
+        let prefix = &sender_address.0.0.0;
+        let start = sender_transaction_key(sender_address, from_block, 0);
+        let iter = iterator_cf(&self.db, &sender_transactions_col, IteratorMode::From(&start, Direction::Forward));
+
+        // only the keys are read, the transactions are not collected
+        let mut count = 0;
+        for entry in iter {
+            let (key, _) = entry?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if key.len() != SENDER_TRANSACTION_KEY_LEN {
+                return Err(DbError::DeserializeError("Invalid sender transaction key".into()));
+            }
+            if u64::from_be_bytes(key[32..40].try_into().unwrap()) > to_block {
+                break;
+            }
+            count += 1;
+        }
+
+        Ok(Some(count))
+    }
+
+    /// Removes the mapping of the starknet blocks after `block_number`, along with their
+    /// transactions in the index of transactions by sender, once these blocks are reverted.
+    ///
+    /// The substrate blocks wrapping them are no longer considered synced, so they are mapped again
+    /// if they are imported again.
+    pub fn revert_to(&self, block_number: u64) -> Result<(), DbError> {
+        let synced_mapping_col = self.db.get_column(Column::SyncedMapping);
+        let block_mapping_col = self.db.get_column(Column::BlockMapping);
+        let transaction_mapping_col = self.db.get_column(Column::TransactionMapping);
+        let starknet_tx_hashes_col = self.db.get_column(Column::StarknetTransactionHashesCache);
+        let starknet_block_hashes_col = self.db.get_column(Column::StarknetBlockHashesCache);
+        let transaction_count_col = self.db.get_column(Column::BlockTransactionCount);
+        let sender_transactions_col = self.db.get_column(Column::SenderTransactions);
+        let block_sender_transactions_col = self.db.get_column(Column::BlockSenderTransactions);
+        let starknet_block_mapping_col = self.db.get_column(Column::StarknetBlockMapping);
+
+        // the reverted blocks are the ones after the start, as block numbers are written big endian
+        let start = (block_number + 1).to_be_bytes();
+        let mut transaction: WriteBatchWithTransaction<true> = Default::default();
+
+        let iter = self.db.iterator_cf(&starknet_block_mapping_col, IteratorMode::From(&start, Direction::Forward));
+        for entry in iter {
+            let (block_key, mapping) = entry?;
+            let reverted_block = <[u8; 8]>::try_from(&block_key[..])
+                .map(u64::from_be_bytes)
+                .map_err(|_| DbError::DeserializeError("Invalid block number key".into()))?;
+            let (block_hash, starknet_block_hash, starknet_transaction_hashes) =
+                <(DHashT, StarkHash, Vec<StarkHash>)>::decode(&mut &mapping[..])?;
+
+            let substrate_hashes = self.block_hash(starknet_block_hash)?.unwrap_or_default();
+            let substrate_hashes: Vec<_> = substrate_hashes.into_iter().filter(|hash| *hash != block_hash).collect();
+            if substrate_hashes.is_empty() {
+                transaction.delete_cf(&block_mapping_col, starknet_block_hash.encode());
+            } else {
+                transaction.put_cf(&block_mapping_col, starknet_block_hash.encode(), substrate_hashes.encode());
+            }
+            transaction.delete_cf(&synced_mapping_col, block_hash.encode());
+            transaction.delete_cf(&transaction_count_col, block_hash.encode());
+            for transaction_hash in starknet_transaction_hashes {
+                transaction.delete_cf(&transaction_mapping_col, transaction_hash.encode());
+            }
+            transaction.delete_cf(&starknet_tx_hashes_col, starknet_block_hash.encode());
+            transaction.delete_cf(&starknet_block_hashes_col, reverted_block.encode());
+            transaction.delete_cf(&starknet_block_mapping_col, block_key);
+        }
+
+        let iter = self.db.iterator_cf(&block_sender_transactions_col, IteratorMode::From(&start, Direction::Forward));
+        for entry in iter {
+            let (block_key, sender_keys) = entry?;
+            for sender_key in Vec::<Vec<u8>>::decode(&mut &sender_keys[..])? {
+                transaction.delete_cf(&sender_transactions_col, sender_key);
+            }
+            transaction.delete_cf(&block_sender_transactions_col, block_key);
+        }
+
+        self.db.write(transaction)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sp_core::H256;
+    use starknet_api::core::PatriciaKey;
+
+    use super::*;
+    use crate::open_rocksdb;
+
+    fn contract_address(address: u64) -> ContractAddress {
+        ContractAddress(PatriciaKey::try_from(StarkHash::from(address)).unwrap())
+    }
+
+    /// The hash of the transaction at `index` in `block_number`.
+    fn transaction_hash(block_number: u64, index: u64) -> StarkHash {
+        StarkHash::from(block_number * 100 + index)
+    }
+
+    fn commitment(block_number: u64, senders: Vec<Option<ContractAddress>>) -> MappingCommitment<DBlockT> {
+        MappingCommitment {
+            block_number,
+            block_hash: H256::from_low_u64_be(block_number),
+            starknet_block_hash: StarkHash::from(block_number),
+            starknet_transaction_hashes: (0..senders.len() as u64).map(|i| transaction_hash(block_number, i)).collect(),
+            starknet_transaction_senders: senders,
+        }
+    }
+
+    #[test]
+    fn test_transactions_by_sender() {
+        let path = std::env::temp_dir().join(format!("deoxys-mapping-db-{}", std::process::id()));
+        let mapping = MappingDb::new(Arc::new(open_rocksdb(&path, true).unwrap()), true);
+        let (alice, bob) = (contract_address(1), contract_address(2));
+
+        mapping.write_hashes(commitment(1, vec![Some(alice), None, Some(bob), Some(alice)])).unwrap();
+        mapping.write_hashes(commitment(2, vec![Some(bob), Some(alice)])).unwrap();
+        mapping.write_hashes(commitment(3, vec![Some(alice)])).unwrap();
+
+        let sent_by = |sender, start, to_block, limit| {
+            mapping.transactions_by_sender(&sender, start, to_block, limit).unwrap().unwrap()
+        };
+        let expected = |transactions: &[(u64, u64)]| {
+            let transaction = |&(block, index): &(u64, u64)| (block, index, transaction_hash(block, index));
+            transactions.iter().map(transaction).collect::<Vec<_>>()
+        };
+        assert_eq!(sent_by(alice, (0, 0), u64::MAX, 10), expected(&[(1, 0), (1, 3), (2, 1), (3, 0)]));
+        assert_eq!(sent_by(alice, (1, 1), u64::MAX, 2), expected(&[(1, 3), (2, 1)]));
+        assert_eq!(sent_by(alice, (0, 0), 1, 10), expected(&[(1, 0), (1, 3)]));
+        assert_eq!(sent_by(contract_address(3), (0, 0), u64::MAX, 10), expected(&[]));
+        assert_eq!(mapping.count_transactions_by_sender(&alice, 2, 3).unwrap(), Some(2));
+        assert_eq!(mapping.count_transactions_by_sender(&alice, 0, u64::MAX).unwrap(), Some(4));
+
+        // a reorg reverts the blocks after block 1, and their transactions with them
+        mapping.revert_to(1).unwrap();
+        assert_eq!(sent_by(alice, (0, 0), u64::MAX, 10), expected(&[(1, 0), (1, 3)]));
+        assert_eq!(sent_by(bob, (0, 0), u64::MAX, 10), expected(&[(1, 2)]));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_revert_to_removes_the_mapping_of_reverted_blocks() {
+        let path = std::env::temp_dir().join(format!("deoxys-mapping-db-revert-{}", std::process::id()));
+        let mapping = MappingDb::new(Arc::new(open_rocksdb(&path, true).unwrap()), true);
+        for block_number in 1..=3 {
+            mapping.write_hashes(commitment(block_number, vec![None, None])).unwrap();
+        }
+
+        mapping.revert_to(1).unwrap();
+
+        let kept = H256::from_low_u64_be(1);
+        assert!(mapping.is_synced(&kept).unwrap());
+        assert_eq!(mapping.block_hash(StarkHash::from(1u64)).unwrap(), Some(vec![kept]));
+        assert_eq!(mapping.block_hash_from_transaction_hash(transaction_hash(1, 1)).unwrap(), Some(kept));
+        assert_eq!(mapping.cached_block_hash_from_block_number(1).unwrap(), Some(StarkHash::from(1u64)));
+
+        for block_number in 2..=3 {
+            let reverted = H256::from_low_u64_be(block_number);
+            assert!(!mapping.is_synced(&reverted).unwrap());
+            assert_eq!(mapping.block_hash(StarkHash::from(block_number)).unwrap(), None);
+            assert_eq!(mapping.block_hash_from_transaction_hash(transaction_hash(block_number, 0)).unwrap(), None);
+            assert_eq!(mapping.transaction_count(reverted).unwrap(), None);
+            assert_eq!(mapping.cached_block_hash_from_block_number(block_number).unwrap(), None);
+        }
+
+        // the blocks synced again are mapped as usual
+        mapping.write_hashes(commitment(2, vec![None])).unwrap();
+        assert_eq!(mapping.block_hash(StarkHash::from(2u64)).unwrap(), Some(vec![H256::from_low_u64_be(2)]));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
 }
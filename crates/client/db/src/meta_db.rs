@@ -3,9 +3,39 @@ use std::sync::Arc;
 use mp_types::block::DHashT;
 // Substrate
 use parity_scale_codec::{Decode, Encode};
+use rocksdb::WriteBatchWithTransaction;
+use starknet_api::hash::StarkHash;
 
+use crate::snapshot::read_options;
 use crate::{Column, DatabaseExt, DbError, DB};
 
+/// Journal record of the last block going through the apply phase of the sync pipeline.
+///
+/// A `Started` record found on startup means the node stopped in the middle of applying that
+/// block, and its partially written state must be rolled back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum ApplyJournal {
+    Started(u64),
+    Applied(u64),
+}
+
+/// The last block fully verified and applied by the sync pipeline, which the sync resumes after on
+/// restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct SyncCheckpoint {
+    pub block_number: u64,
+    pub block_hash: StarkHash,
+    pub global_state_root: StarkHash,
+}
+
+/// Progress of the backfill of the index of contracts by class hash.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum ClassIndexBackfill {
+    /// The contracts are indexed in address order, resuming from this contract key.
+    Indexing(Vec<u8>),
+    Done,
+}
+
 /// Allow interaction with the meta db
 ///
 /// The meta db store the tips of the synced chain.
@@ -36,4 +66,180 @@ impl MetaDb {
         self.db.put_cf(&column, crate::static_keys::CURRENT_SYNCING_TIPS, tips.encode())?;
         Ok(())
     }
+
+    /// Retrieve the last apply journal record
+    pub fn apply_journal(&self) -> Result<Option<ApplyJournal>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::APPLY_JOURNAL)? {
+            Some(raw) => Ok(Some(ApplyJournal::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store an apply journal record, replacing the previous one
+    pub fn write_apply_journal(&self, record: ApplyJournal) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::APPLY_JOURNAL, record.encode())?;
+        Ok(())
+    }
+
+    /// Retrieve the sync checkpoint
+    pub fn sync_checkpoint(&self) -> Result<Option<SyncCheckpoint>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::SYNC_CHECKPOINT)? {
+            Some(raw) => Ok(Some(SyncCheckpoint::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the sync checkpoint, replacing the previous one
+    ///
+    /// The block of the checkpoint is recorded as applied in the apply journal in the same write,
+    /// so that the two cannot disagree after a crash.
+    pub fn write_sync_checkpoint(&self, checkpoint: SyncCheckpoint) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        let mut batch: WriteBatchWithTransaction<true> = Default::default();
+        let record = ApplyJournal::Applied(checkpoint.block_number);
+        batch.put_cf(&column, crate::static_keys::APPLY_JOURNAL, record.encode());
+        batch.put_cf(&column, crate::static_keys::SYNC_CHECKPOINT, checkpoint.encode());
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Retrieve the next block whose classes are to be checked by the class backfill
+    pub fn class_backfill_cursor(&self) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::CLASS_BACKFILL_CURSOR)? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the next block whose classes are to be checked by the class backfill
+    pub fn write_class_backfill_cursor(&self, block_number: u64) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::CLASS_BACKFILL_CURSOR, block_number.encode())?;
+        Ok(())
+    }
+
+    /// Retrieve the first block whose state history has not been pruned
+    pub fn pruned_up_to(&self) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf_opt(&column, crate::static_keys::PRUNED_UP_TO, &read_options())? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve the first block the state is readable at, if the state history has been pruned
+    pub fn state_pruned_before(&self) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf_opt(&column, crate::static_keys::STATE_PRUNED_BEFORE, &read_options())? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve the storage key the storage versions are built from next, if they are still being
+    /// built from the storage histories
+    pub fn versions_migration(&self) -> Result<Option<Vec<u8>>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        Ok(self.db.get_cf(&column, crate::static_keys::VERSIONS_MIGRATION)?)
+    }
+
+    /// Store the storage key the storage versions are built from next
+    pub fn write_versions_migration(&self, storage_key: &[u8]) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::VERSIONS_MIGRATION, storage_key)?;
+        Ok(())
+    }
+
+    /// Retrieve the first block whose data has not been moved to the cold database
+    pub fn cold_up_to(&self) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::COLD_UP_TO)? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve the last block the tries committed, if it was recorded
+    pub fn tries_block(&self) -> Result<Option<u64>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::TRIES_BLOCK)? {
+            Some(raw) => Ok(Some(u64::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the last block the tries committed
+    pub fn write_tries_block(&self, block_number: u64) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::TRIES_BLOCK, block_number.encode())?;
+        Ok(())
+    }
+
+    /// Retrieve the version of the layout of the database
+    pub fn schema_version(&self) -> Result<Option<u32>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::SCHEMA_VERSION)? {
+            Some(raw) => Ok(Some(u32::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the version of the layout of the database
+    pub fn write_schema_version(&self, version: u32) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::SCHEMA_VERSION, version.encode())?;
+        Ok(())
+    }
+
+    /// Retrieve the progress of the backfill of the class index
+    pub fn class_index_backfill(&self) -> Result<Option<ClassIndexBackfill>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        match self.db.get_cf(&column, crate::static_keys::CLASS_INDEX_BACKFILL)? {
+            Some(raw) => Ok(Some(ClassIndexBackfill::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Store the progress of the backfill of the class index
+    pub fn write_class_index_backfill(&self, progress: &ClassIndexBackfill) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::CLASS_INDEX_BACKFILL, progress.encode())?;
+        Ok(())
+    }
+
+    /// Retrieve the rules of the execution policy of the rpc, as serialized by the rpc
+    pub fn execution_policy(&self) -> Result<Option<Vec<u8>>, DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        Ok(self.db.get_cf(&column, crate::static_keys::EXECUTION_POLICY)?)
+    }
+
+    /// Store the rules of the execution policy of the rpc
+    pub fn write_execution_policy(&self, rules: &[u8]) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::Meta);
+
+        self.db.put_cf(&column, crate::static_keys::EXECUTION_POLICY, rules)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use mp_block::DeoxysBlock;
+use parity_scale_codec::{Decode, Encode};
+use starknet_core::types::PendingStateUpdate;
+use starknet_ff::FieldElement;
+
+use crate::snapshot::read_options;
+use crate::{Column, DatabaseExt, DbError, DB};
+
+const PENDING_KEY: &[u8] = b"pending";
+
+#[derive(Encode, Decode)]
+struct StoredPending {
+    parent_hash: [u8; 32],
+    block: DeoxysBlock,
+    /// The state update, as JSON since the rpc types have no SCALE encoding.
+    state_update: Vec<u8>,
+}
+
+/// Allow interaction with the pending block db
+///
+/// The pending block db holds the last pending block downloaded by the node, so that the rpc can
+/// serve it right after a restart, before the pending block is polled again.
+pub struct PendingDb {
+    db: Arc<DB>,
+}
+
+impl PendingDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Store the pending block built on top of `parent_hash`, replacing the previous one
+    pub fn store(
+        &self,
+        parent_hash: FieldElement,
+        block: &DeoxysBlock,
+        state_update: &PendingStateUpdate,
+    ) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::PendingBlock);
+        let stored = StoredPending {
+            parent_hash: parent_hash.to_bytes_be(),
+            block: block.clone(),
+            state_update: serde_json::to_vec(state_update)?,
+        };
+        self.db.put_cf(&column, PENDING_KEY, stored.encode())?;
+        Ok(())
+    }
+
+    /// Retrieve the stored pending block, along with the hash of its parent and its state update
+    pub fn get(&self) -> Result<Option<(FieldElement, DeoxysBlock, PendingStateUpdate)>, DbError> {
+        let column = self.db.get_column(Column::PendingBlock);
+        let Some(raw) = self.db.get_cf_opt(&column, PENDING_KEY, &read_options())? else {
+            return Ok(None);
+        };
+        let StoredPending { parent_hash, block, state_update } = StoredPending::decode(&mut &raw[..])?;
+        let parent_hash = FieldElement::from_bytes_be(&parent_hash)
+            .map_err(|_| parity_scale_codec::Error::from("invalid pending block parent hash"))?;
+        Ok(Some((parent_hash, block, serde_json::from_slice(&state_update)?)))
+    }
+
+    /// Remove the stored pending block, once it has been superseded
+    pub fn clear(&self) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::PendingBlock);
+        self.db.delete_cf(&column, PENDING_KEY)?;
+        Ok(())
+    }
+}
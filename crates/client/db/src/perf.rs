@@ -0,0 +1,168 @@
+//! Read amplification of the database, to measure what a query costs to the storage.
+//!
+//! The reads of the current thread are counted with the perf context of RocksDB. The levels the
+//! values are found at are only counted by the statistics of the database, which must be enabled
+//! with [enable_statistics] before the backend is opened, as they slow down every read.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use rocksdb::perf::{set_perf_stats, PerfContext, PerfMetric, PerfStatsLevel};
+use rocksdb::Options;
+
+static ENABLE_STATISTICS: AtomicBool = AtomicBool::new(false);
+
+/// The options the database was opened with, when its statistics are enabled.
+static STATISTICS: OnceLock<Options> = OnceLock::new();
+
+/// The tickers of the statistics counting the point lookups served by each level.
+const LEVEL_TICKERS: [&str; 4] = ["rocksdb.memtable.hit", "rocksdb.l0.hit", "rocksdb.l1.hit", "rocksdb.l2andup.hit"];
+
+/// Enable the statistics of the database opened from now on.
+pub fn enable_statistics() {
+    ENABLE_STATISTICS.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn configure(opts: &mut Options) {
+    if ENABLE_STATISTICS.load(Ordering::Relaxed) {
+        opts.enable_statistics();
+        // the copy shares the statistics of the database
+        let _ = STATISTICS.set(opts.clone());
+    }
+}
+
+fn level_hits() -> Option<[u64; 4]> {
+    let tickers = parse_tickers(&STATISTICS.get()?.get_statistics()?);
+    Some(LEVEL_TICKERS.map(|ticker| tickers.get(ticker).copied().unwrap_or_default()))
+}
+
+/// The share of the blocks read which were found in the block cache since the database was opened,
+/// if its statistics are enabled and a block was read.
+pub(crate) fn block_cache_hit_rate() -> Option<f64> {
+    let tickers = parse_tickers(&STATISTICS.get()?.get_statistics()?);
+    let hits = tickers.get("rocksdb.block.cache.hit").copied().unwrap_or_default();
+    let misses = tickers.get("rocksdb.block.cache.miss").copied().unwrap_or_default();
+    (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64)
+}
+
+/// Parses the tickers of a statistics dump, such as `rocksdb.l0.hit COUNT : 12`.
+fn parse_tickers(statistics: &str) -> HashMap<&str, u64> {
+    statistics
+        .lines()
+        .filter_map(|line| {
+            let (name, count) = line.split_once(" COUNT : ")?;
+            Some((name.trim(), count.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Counts the reads of the current thread, from [ReadProfiler::start] to [ReadProfiler::finish].
+///
+/// Reads made on other threads, such as by the tasks a query spawns, are not counted, except by
+/// the level counts which cover the whole database.
+pub struct ReadProfiler {
+    context: PerfContext,
+    level_hits: Option<[u64; 4]>,
+}
+
+impl ReadProfiler {
+    pub fn start() -> Self {
+        set_perf_stats(PerfStatsLevel::EnableCount);
+        let mut context = PerfContext::default();
+        context.reset();
+        Self { context, level_hits: level_hits() }
+    }
+
+    pub fn finish(self) -> ReadProfile {
+        let Self { context, level_hits: before } = self;
+        set_perf_stats(PerfStatsLevel::Disable);
+
+        let level_hits = before
+            .zip(level_hits())
+            .map(|(before, after)| std::array::from_fn(|level| after[level].saturating_sub(before[level])));
+
+        ReadProfile {
+            level_hits,
+            block_reads: context.metric(PerfMetric::BlockReadCount),
+            block_cache_hits: context.metric(PerfMetric::BlockCacheHitCount),
+            block_bytes_read: context.metric(PerfMetric::BlockReadByte),
+            bytes_returned: context.metric(PerfMetric::GetReadBytes)
+                + context.metric(PerfMetric::MultigetReadBytes)
+                + context.metric(PerfMetric::IterReadBytes),
+            bloom_positives: context.metric(PerfMetric::BloomSstHitCount),
+            bloom_negatives: context.metric(PerfMetric::BloomSstMissCount),
+            keys_skipped: context.metric(PerfMetric::InternalKeySkippedCount)
+                + context.metric(PerfMetric::InternalDeleteSkippedCount),
+        }
+    }
+}
+
+/// The reads made by a query.
+#[derive(Debug, Clone, Default)]
+pub struct ReadProfile {
+    /// The point lookups served by the memtables, level 0, level 1 and the levels below, if the
+    /// statistics of the database are enabled.
+    pub level_hits: Option<[u64; 4]>,
+    /// The blocks read from sst files, from the disk or the page cache.
+    pub block_reads: u64,
+    pub block_cache_hits: u64,
+    pub block_bytes_read: u64,
+    /// The size of the values returned to the query.
+    pub bytes_returned: u64,
+    /// The sst files the bloom filters could not exclude.
+    pub bloom_positives: u64,
+    /// The sst files skipped thanks to their bloom filter.
+    pub bloom_negatives: u64,
+    /// The deleted and overwritten entries iterated over.
+    pub keys_skipped: u64,
+}
+
+impl ReadProfile {
+    /// The share of the bloom filter checks which spared an sst file read.
+    pub fn bloom_hit_rate(&self) -> Option<f64> {
+        let checks = self.bloom_positives + self.bloom_negatives;
+        (checks > 0).then(|| self.bloom_negatives as f64 / checks as f64)
+    }
+}
+
+impl fmt::Display for ReadProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level_hits {
+            Some([memtable, l0, l1, l2_and_up]) => {
+                writeln!(f, "lookups per level:   memtable {memtable}, L0 {l0}, L1 {l1}, L2+ {l2_and_up}")?
+            }
+            None => writeln!(f, "lookups per level:   unknown, the statistics of the database are disabled")?,
+        }
+        writeln!(f, "blocks read:         {} ({} from the block cache)", self.block_reads, self.block_cache_hits)?;
+        writeln!(f, "bytes read:          {}", self.block_bytes_read)?;
+        writeln!(f, "bytes returned:      {}", self.bytes_returned)?;
+        match self.bloom_hit_rate() {
+            Some(rate) => writeln!(
+                f,
+                "bloom filter:        {:.1}% of {} checks skipped a file",
+                rate * 100.0,
+                self.bloom_positives + self.bloom_negatives
+            )?,
+            None => writeln!(f, "bloom filter:        no checks")?,
+        }
+        write!(f, "entries skipped:     {}", self.keys_skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tickers_are_parsed_from_statistics() {
+        let statistics = "rocksdb.block.cache.miss COUNT : 3\nrocksdb.l0.hit COUNT : 12\nrocksdb.db.get.micros P50 : \
+                          1.000000 P95 : 2.000000 COUNT : 4 SUM : 5\n";
+        let tickers = parse_tickers(statistics);
+        assert_eq!(tickers.get("rocksdb.l0.hit"), Some(&12));
+        assert_eq!(tickers.get("rocksdb.block.cache.miss"), Some(&3));
+        // histograms are not tickers
+        assert_eq!(tickers.len(), 2);
+    }
+}
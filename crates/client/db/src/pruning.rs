@@ -0,0 +1,323 @@
+//! Pruning of the state history older than a retention window.
+//!
+//! An archive node keeps the value of every storage key, and the class hash and nonce of every
+//! contract, as of every block. When pruning the state to the last `n` blocks, the values
+//! superseded before the window are deleted from these histories, along with the state diffs of
+//! the blocks before it, so the state stays readable at the blocks of the window only. The tries,
+//! which the proofs of the latest state are built from, are left untouched.
+//!
+//! Pruning runs on a background thread, woken up after every applied block. The blocks pruned so
+//! far are recorded, so a restarted node resumes pruning where it stopped, along with the first
+//! block the state is still readable at, below which the RPC rejects state reads.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::thread::Thread;
+
+use mp_convert::field_element::FromFieldElement;
+use parity_scale_codec::Encode;
+use rocksdb::ErrorKind;
+use starknet_api::core::ContractAddress;
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+use starknet_core::types::{
+    ContractStorageDiffItem, DeployedContractItem, NonceUpdate, ReplacedClassItem, StorageEntry,
+};
+
+use crate::storage_handler::history::History;
+use crate::storage_handler::primitives::contract::StorageContractData;
+use crate::storage_handler::versioned_storage::version_keys;
+use crate::storage_handler::{self, DeoxysStorageError, StorageType};
+use crate::{cold_storage, Column, DatabaseExt, DeoxysBackend};
+
+/// The number of blocks pruned in a single transaction.
+const PRUNING_BATCH: u64 = 64;
+
+static PRUNER: OnceLock<Pruner> = OnceLock::new();
+
+/// How much of the state history is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatePruning {
+    /// The state is kept readable at every block.
+    #[default]
+    Archive,
+    /// The state is only kept readable at the given number of last blocks.
+    Blocks(u64),
+}
+
+impl FromStr for StatePruning {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "archive" => Ok(StatePruning::Archive),
+            _ => match s.parse::<u64>() {
+                Ok(0) => Err("the retention window must hold at least one block".to_string()),
+                Ok(blocks) => Ok(StatePruning::Blocks(blocks)),
+                Err(_) => Err(format!("invalid state pruning {s:?}, expected `archive` or a number of blocks")),
+            },
+        }
+    }
+}
+
+impl fmt::Display for StatePruning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatePruning::Archive => write!(f, "archive"),
+            StatePruning::Blocks(blocks) => write!(f, "{blocks}"),
+        }
+    }
+}
+
+struct Pruner {
+    retention: u64,
+    last_applied: AtomicU64,
+    thread: Thread,
+}
+
+/// Starts pruning the state history on a background thread, following `pruning`.
+///
+/// The database must be open. The pruner is only started once, later calls are ignored.
+pub fn start(pruning: StatePruning) {
+    let StatePruning::Blocks(retention) = pruning else {
+        return;
+    };
+    if PRUNER.get().is_some() {
+        return;
+    }
+
+    let thread = std::thread::Builder::new()
+        .name("db-pruning".into())
+        .spawn(|| {
+            loop {
+                std::thread::park();
+                if let Some(pruner) = PRUNER.get() {
+                    pruner.prune();
+                }
+            }
+        })
+        .expect("Failed to spawn the pruning thread");
+    let _ = PRUNER.set(Pruner { retention, last_applied: AtomicU64::new(0), thread: thread.thread().clone() });
+}
+
+/// Schedules the pruning of the blocks falling out of the retention window once `block_number` is
+/// applied.
+pub fn block_applied(block_number: u64) {
+    if let Some(pruner) = PRUNER.get() {
+        pruner.last_applied.fetch_max(block_number, Ordering::Relaxed);
+        pruner.thread.unpark();
+    }
+}
+
+impl Pruner {
+    fn prune(&self) {
+        // the state stays readable from the first block of the window
+        let window_start = (self.last_applied.load(Ordering::Relaxed) + 1).saturating_sub(self.retention);
+        let mut next = match DeoxysBackend::meta().pruned_up_to() {
+            Ok(pruned_up_to) => pruned_up_to.unwrap_or_default(),
+            Err(e) => {
+                log::error!("❗ Failed to read the pruned blocks: {e}");
+                return;
+            }
+        };
+
+        while next < window_start {
+            let end = (next + PRUNING_BATCH).min(window_start);
+            match prune_blocks(next, end, window_start) {
+                Ok(Pruned::Blocks) => next = end,
+                // a block applied meanwhile conflicts with the pruning of the same keys, which is
+                // retried once the next block is applied
+                Ok(Pruned::Conflict) => {
+                    log::debug!("Pruning blocks #{next}..#{end} conflicted with a block being applied");
+                    return;
+                }
+                Err(e) => {
+                    log::warn!("❗ Failed to prune blocks #{next}..#{end}: {e}");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Whether a batch of blocks was pruned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pruned {
+    Blocks,
+    /// The pruning conflicted with a block applied meanwhile, and was rolled back.
+    Conflict,
+}
+
+/// Prunes the history of the state changed in blocks `start..end`, keeping the state readable
+/// from `window_start` on.
+fn prune_blocks(start: u64, end: u64, window_start: u64) -> Result<Pruned, DeoxysStorageError> {
+    let mut storage_keys = HashSet::new();
+    let mut contracts = HashSet::new();
+    for block_number in start..end {
+        // blocks preceding a trusted root have no state diff
+        let Some(state_diff) = storage_handler::block_state_diff().get(block_number)? else {
+            continue;
+        };
+
+        for ContractStorageDiffItem { address, storage_entries } in &state_diff.storage_diffs {
+            let address = ContractAddress::from_field_element(address);
+            storage_keys.extend(
+                storage_entries.iter().map(|StorageEntry { key, .. }| (address, StorageKey::from_field_element(key))),
+            );
+        }
+        contracts.extend(state_diff.deployed_contracts.iter().map(|DeployedContractItem { address, .. }| address));
+        contracts.extend(
+            state_diff.replaced_classes.iter().map(|ReplacedClassItem { contract_address, .. }| contract_address),
+        );
+        contracts.extend(state_diff.nonces.iter().map(|NonceUpdate { contract_address, .. }| contract_address));
+    }
+
+    let db = DeoxysBackend::expose_db();
+    let transaction = db.transaction();
+
+    let column = db.get_column(Column::ContractStorage);
+    let versions_column = db.get_column(Column::ContractStorageVersions);
+    for key in storage_keys {
+        let key = bincode::serialize(&key).unwrap();
+        let Some(raw) = transaction
+            .get_for_update_cf(&column, &key, true)
+            .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?
+        else {
+            continue;
+        };
+        let mut history: History<StarkFelt> = bincode::deserialize(&raw)
+            .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractStorage))?;
+        if history.prune_before(window_start) {
+            transaction
+                .put_cf(&column, &key, bincode::serialize(&history).unwrap())
+                .map_err(|_| DeoxysStorageError::StoragePruneError(StorageType::ContractStorage, window_start))?;
+
+            // the versions are pruned down to the first one kept in the history
+            let first_kept = history.0.first().map(|(block_number, _)| *block_number).unwrap_or(window_start);
+            for version in version_keys(db, &key, 0..first_kept)? {
+                transaction
+                    .delete_cf(&versions_column, version)
+                    .map_err(|_| DeoxysStorageError::StoragePruneError(StorageType::ContractStorage, window_start))?;
+            }
+        }
+    }
+
+    let column = db.get_column(Column::ContractData);
+    for contract_address in contracts {
+        let key = bincode::serialize(&ContractAddress::from_field_element(contract_address)).unwrap();
+        let Some(raw) = transaction
+            .get_for_update_cf(&column, &key, true)
+            .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?
+        else {
+            continue;
+        };
+        let mut contract_data: StorageContractData = bincode::deserialize(&raw)
+            .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData))?;
+        let class_hash_pruned = contract_data.class_hash.prune_before(window_start);
+        let nonce_pruned = contract_data.nonce.prune_before(window_start);
+        if class_hash_pruned || nonce_pruned {
+            transaction
+                .put_cf(&column, &key, bincode::serialize(&contract_data).unwrap())
+                .map_err(|_| DeoxysStorageError::StoragePruneError(StorageType::ContractData, window_start))?;
+        }
+    }
+
+    let column = db.get_column(Column::BlockStateDiff);
+    for block_number in start..end {
+        transaction
+            .delete_cf(&column, bincode::serialize(&block_number).unwrap())
+            .map_err(|_| DeoxysStorageError::StoragePruneError(StorageType::BlockStateDiff, window_start))?;
+    }
+
+    let column = db.get_column(Column::Meta);
+    transaction
+        .put_cf(&column, crate::static_keys::PRUNED_UP_TO, end.encode())
+        .and_then(|()| transaction.put_cf(&column, crate::static_keys::STATE_PRUNED_BEFORE, window_start.encode()))
+        .map_err(|_| DeoxysStorageError::StoragePruneError(StorageType::Block, window_start))?;
+
+    match transaction.commit() {
+        Ok(()) => {}
+        Err(e) if matches!(e.kind(), ErrorKind::Busy | ErrorKind::TryAgain) => return Ok(Pruned::Conflict),
+        Err(_) => return Err(DeoxysStorageError::StoragePruneError(StorageType::Block, window_start)),
+    }
+    // the state diffs older than the hot blocks were moved to the cold database
+    cold_storage::delete_state_diffs(start..end)
+        .map_err(|_| DeoxysStorageError::StoragePruneError(StorageType::BlockStateDiff, window_start))?;
+    Ok(Pruned::Blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::{ClassHash, Nonce, PatriciaKey};
+    use starknet_core::types::{FieldElement, StateDiff};
+
+    use super::*;
+    use crate::storage_handler::{StorageView, StorageViewMut};
+
+    #[test]
+    fn pruned_state_is_readable_from_the_window() {
+        let _db = DeoxysBackend::open_for_testing();
+        let felt = |n: u64| StarkFelt::from(n);
+        let address = ContractAddress(PatriciaKey(felt(0x274)));
+        let key = StorageKey(PatriciaKey(felt(1)));
+
+        // the contract is deployed at block 100, and its storage and nonce updated at blocks 100 to 103
+        for block_number in 100..104 {
+            let contract_data = storage_handler::contract_data_mut();
+            let class_hash = (block_number == 100).then_some(ClassHash(felt(0x2740)));
+            contract_data.insert(address, (class_hash, Some(Nonce(felt(block_number))))).unwrap();
+            contract_data.commit(block_number).unwrap();
+            let contract_storage = storage_handler::contract_storage_mut();
+            contract_storage.insert((address, key), felt(block_number)).unwrap();
+            contract_storage.commit(block_number).unwrap();
+
+            let state_diff = StateDiff {
+                storage_diffs: vec![ContractStorageDiffItem {
+                    address: FieldElement::from(0x274u64),
+                    storage_entries: vec![StorageEntry { key: FieldElement::ONE, value: block_number.into() }],
+                }],
+                deprecated_declared_classes: vec![],
+                declared_classes: vec![],
+                deployed_contracts: vec![],
+                replaced_classes: vec![],
+                nonces: vec![NonceUpdate {
+                    contract_address: FieldElement::from(0x274u64),
+                    nonce: block_number.into(),
+                }],
+            };
+            storage_handler::block_state_diff().insert(block_number, state_diff).unwrap();
+        }
+
+        assert_eq!(prune_blocks(100, 102, 102).unwrap(), Pruned::Blocks);
+        assert_eq!(DeoxysBackend::meta().pruned_up_to().unwrap(), Some(102));
+        assert_eq!(DeoxysBackend::meta().state_pruned_before().unwrap(), Some(102));
+
+        // the state is unchanged from the window on, and the state diffs before it are deleted
+        let contract_storage = storage_handler::contract_storage();
+        assert_eq!(contract_storage.get_at(&(address, key), 102).unwrap(), Some(felt(102)));
+        assert_eq!(contract_storage.get_at(&(address, key), 103).unwrap(), Some(felt(103)));
+        let contract_data = storage_handler::contract_data();
+        assert_eq!(contract_data.get_nonce_at(&address, 102).unwrap(), Some(Nonce(felt(102))));
+        assert_eq!(contract_data.get_class_hash_at(&address, 103).unwrap(), Some(ClassHash(felt(0x2740))));
+        assert!(storage_handler::block_state_diff().get(100).unwrap().is_none());
+        assert!(storage_handler::block_state_diff().get(102).unwrap().is_some());
+
+        // the values superseded before the window are gone
+        assert_eq!(contract_storage.get_at(&(address, key), 100).unwrap(), None);
+        assert_eq!(contract_data.get_nonce_at(&address, 100).unwrap(), None);
+        let versioned_storage = storage_handler::versioned_storage();
+        assert_eq!(versioned_storage.get_at(&(address, key), 101).unwrap(), None);
+        assert_eq!(versioned_storage.get_at(&(address, key), 102).unwrap(), Some(felt(102)));
+    }
+
+    #[test]
+    fn state_pruning_is_parsed_from_archive_or_blocks() {
+        assert_eq!("archive".parse(), Ok(StatePruning::Archive));
+        assert_eq!("1000".parse(), Ok(StatePruning::Blocks(1000)));
+        assert!("0".parse::<StatePruning>().is_err());
+        assert!("full".parse::<StatePruning>().is_err());
+    }
+}
@@ -0,0 +1,53 @@
+//! Versioning of the layout of the Starknet database.
+//!
+//! The version of the layout is recorded in the meta column. Databases with an older layout are
+//! upgraded when they are opened, and databases with a newer one are refused.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use parity_scale_codec::Decode;
+use rocksdb::Options;
+
+use crate::meta_db::MetaDb;
+use crate::{starknet_database_dir, static_keys, Column};
+
+/// The version of the layout of the Starknet database. It is bumped whenever the layout changes in
+/// a way which older databases cannot be opened with.
+pub const DB_SCHEMA_VERSION: u32 = 2;
+
+/// Reads the schema version of the Starknet database in `db_config_dir`, without opening the
+/// backend.
+///
+/// The database is opened read-only, so it can be checked while the node is running.
+pub fn read_schema_version(db_config_dir: &Path) -> Result<Option<u32>> {
+    let path = starknet_database_dir(db_config_dir, "rockdb");
+    let opts = Options::default();
+
+    let meta = Column::Meta.rocksdb_name();
+    let db = rocksdb::DB::open_cf_for_read_only(&opts, &path, [meta], false)?;
+    let column = db.cf_handle(meta).context("Missing meta column")?;
+    match db.get_cf(column, static_keys::SCHEMA_VERSION)? {
+        Some(raw) => Ok(Some(u32::decode(&mut &raw[..])?)),
+        None => Ok(None),
+    }
+}
+
+/// Upgrades the layout of the database to [`DB_SCHEMA_VERSION`], failing on layouts newer than it.
+///
+/// The storage versions of the databases created before they were stored are built in the
+/// background, so the database can be used right away.
+pub(crate) fn upgrade(meta: &MetaDb) -> Result<()> {
+    match meta.schema_version()? {
+        // databases created before the schema version was recorded share the first layout, which
+        // only stored the storage versions within their histories
+        None | Some(1) => {
+            meta.write_versions_migration(&[])?;
+            meta.write_schema_version(DB_SCHEMA_VERSION)?;
+            log::info!("🗃️ Migrated the database to schema {DB_SCHEMA_VERSION}");
+        }
+        Some(DB_SCHEMA_VERSION) => {}
+        Some(version) => bail!("Unsupported database schema version {version}, expected {DB_SCHEMA_VERSION}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! Point-in-time views of the database.
+//!
+//! A [DbSnapshot] can be pinned to the current thread for the duration of a closure: every read
+//! made by the storage views and the mapping database during that time is then served from the
+//! snapshot, so that blocks committed concurrently by the sync are not observed halfway through.
+//! Outside of a pinned scope, reads are served from the live database as before.
+//!
+//! The snapshot is pinned per thread: work handed to other threads, such as rayon tasks, must pin
+//! [DbSnapshot::pinned] again to be served from it. The tries are read from the [TrieSnapshots]
+//! instead, taken as the sync commits blocks, which are consistent with the blocks they are opened
+//! at whether or not a snapshot is pinned.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use rocksdb::{AsColumnFamilyRef, DBIteratorWithThreadMode, IteratorMode, ReadOptions, SnapshotWithThreadMode};
+
+use crate::{DeoxysBackend, DB};
+
+/// A point-in-time view of the database, unaffected by the writes made after it was taken.
+pub struct DbSnapshot(SnapshotWithThreadMode<'static, DB>);
+
+thread_local! {
+    static PINNED_SNAPSHOT: RefCell<Option<Arc<DbSnapshot>>> = const { RefCell::new(None) };
+}
+
+/// Restores the previously pinned snapshot when dropped, even if the pinned closure panics.
+struct PinGuard(Option<Arc<DbSnapshot>>);
+
+impl Drop for PinGuard {
+    fn drop(&mut self) {
+        PINNED_SNAPSHOT.with(|pinned| *pinned.borrow_mut() = self.0.take());
+    }
+}
+
+impl DbSnapshot {
+    /// Runs `f`, serving every database read made from this thread in the meantime from this
+    /// snapshot.
+    pub fn pin<R>(self: &Arc<Self>, f: impl FnOnce() -> R) -> R {
+        let _guard = PinGuard(PINNED_SNAPSHOT.with(|pinned| pinned.replace(Some(Arc::clone(self)))));
+        f()
+    }
+}
+
+impl DbSnapshot {
+    /// The snapshot pinned on this thread, if any, to pin it again on the threads a pinned request
+    /// hands its reads to.
+    pub fn pinned() -> Option<Arc<DbSnapshot>> {
+        PINNED_SNAPSHOT.with(|pinned| pinned.borrow().clone())
+    }
+
+    /// Read options serving reads from this snapshot, which they keep alive.
+    pub(crate) fn read_options(self: &Arc<Self>) -> SnapshotReadOptions {
+        SnapshotReadOptions::new(Some(Arc::clone(self)))
+    }
+}
+
+/// Read options serving reads from a snapshot, if any.
+///
+/// RocksDB read options only point to their snapshot, so they hold a reference to it: the snapshot
+/// is released once the options, and the iterators built on them, are dropped.
+pub(crate) struct SnapshotReadOptions {
+    // declared before the snapshot, so that it is dropped first
+    options: ReadOptions,
+    snapshot: Option<Arc<DbSnapshot>>,
+}
+
+impl SnapshotReadOptions {
+    pub(crate) fn new(snapshot: Option<Arc<DbSnapshot>>) -> Self {
+        let mut options = ReadOptions::default();
+        if let Some(snapshot) = &snapshot {
+            options.set_snapshot(&snapshot.0);
+        }
+        Self { options, snapshot }
+    }
+
+    /// Iterates over `column` with these options, keeping the snapshot alive along with the
+    /// iterator.
+    pub(crate) fn iterator_cf<'a>(
+        self,
+        db: &'a DB,
+        column: &impl AsColumnFamilyRef,
+        mode: IteratorMode,
+    ) -> SnapshotIterator<'a> {
+        let Self { options, snapshot } = self;
+        SnapshotIterator { inner: db.iterator_cf_opt(column, options, mode), _snapshot: snapshot }
+    }
+}
+
+impl Deref for SnapshotReadOptions {
+    type Target = ReadOptions;
+
+    fn deref(&self) -> &ReadOptions {
+        &self.options
+    }
+}
+
+/// An iterator over a column, served from the snapshot it keeps alive, if any.
+pub(crate) struct SnapshotIterator<'a> {
+    // declared before the snapshot, so that it is dropped first
+    inner: DBIteratorWithThreadMode<'a, DB>,
+    _snapshot: Option<Arc<DbSnapshot>>,
+}
+
+impl Iterator for SnapshotIterator<'_> {
+    type Item = Result<(Box<[u8]>, Box<[u8]>), rocksdb::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// The number of blocks whose tries stay readable once the sync committed the next ones.
+pub const TRIE_SNAPSHOTS_KEPT: usize = 16;
+
+/// Snapshots of the database taken right after the tries committed a block.
+///
+/// The contract, contract storage and class tries commit a block one after the other, and keep
+/// committing the next blocks while they are read. Reading them from a snapshot taken once they
+/// all committed a block gives a consistent view of the state at that block, without holding the
+/// locks of the sync.
+pub(crate) struct TrieSnapshots {
+    /// The tries as they were when the database was opened, until this run commits a block. It is
+    /// dropped then, as it would keep every value overwritten since from being compacted away.
+    opened: Option<Arc<DbSnapshot>>,
+    by_block: BTreeMap<u64, Arc<DbSnapshot>>,
+}
+
+impl TrieSnapshots {
+    pub(crate) fn new(db: &'static DB) -> Self {
+        Self { opened: Some(Arc::new(DbSnapshot(db.snapshot()))), by_block: BTreeMap::new() }
+    }
+}
+
+impl DeoxysBackend {
+    /// Takes a snapshot of the current state of the database.
+    pub fn snapshot() -> Arc<DbSnapshot> {
+        Arc::new(DbSnapshot(Self::expose_db().snapshot()))
+    }
+
+    /// Records the state of the tries once they all committed, or were reverted to, `block_number`.
+    ///
+    /// The snapshots of the blocks after `block_number` are dropped, as they no longer belong to
+    /// the chain, and so is the oldest snapshot past the last [TRIE_SNAPSHOTS_KEPT] blocks.
+    pub fn snapshot_tries(block_number: u64) {
+        let snapshot = Self::snapshot();
+        let mut snapshots = Self::trie_snapshots().write().expect("Failed to acquire lock on the trie snapshots");
+        snapshots.by_block.split_off(&block_number);
+        snapshots.by_block.insert(block_number, snapshot);
+        snapshots.opened = None;
+        while snapshots.by_block.len() > TRIE_SNAPSHOTS_KEPT {
+            snapshots.by_block.pop_first();
+        }
+    }
+
+    /// The snapshot of the tries at the last block they committed, along with that block, which is
+    /// `None` if they did not commit any block since the database was opened.
+    pub(crate) fn latest_trie_snapshot() -> (Option<u64>, Arc<DbSnapshot>) {
+        let snapshots = Self::trie_snapshots().read().expect("Failed to acquire lock on the trie snapshots");
+        match snapshots.by_block.last_key_value() {
+            Some((block_number, snapshot)) => (Some(*block_number), Arc::clone(snapshot)),
+            // the snapshot taken when the database was opened is only dropped once a block is committed
+            None => (None, snapshots.opened.clone().unwrap_or_else(Self::snapshot)),
+        }
+    }
+
+    /// The snapshot of the tries at `block_number`, if it is one of the last blocks they committed.
+    pub(crate) fn trie_snapshot_at(block_number: u64) -> Option<Arc<DbSnapshot>> {
+        let snapshots = Self::trie_snapshots().read().expect("Failed to acquire lock on the trie snapshots");
+        snapshots.by_block.get(&block_number).cloned()
+    }
+}
+
+/// Read options serving reads from the snapshot pinned on this thread, if any.
+pub(crate) fn read_options() -> SnapshotReadOptions {
+    SnapshotReadOptions::new(DbSnapshot::pinned())
+}
+
+/// Iterates over `column`, from the snapshot pinned on this thread if any.
+pub(crate) fn iterator_cf<'a>(db: &'a DB, column: &impl AsColumnFamilyRef, mode: IteratorMode) -> SnapshotIterator<'a> {
+    read_options().iterator_cf(db, column, mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use rocksdb::{Options, DEFAULT_COLUMN_FAMILY_NAME};
+    use starknet_api::core::{ContractAddress, PatriciaKey};
+    use starknet_api::hash::StarkFelt;
+    use starknet_api::state::StorageKey;
+    use starknet_types_core::felt::Felt;
+
+    use super::*;
+    use crate::storage_handler;
+
+    #[test]
+    fn test_pinned_reads_outlive_the_pinned_scope() {
+        let path = std::env::temp_dir().join(format!("deoxys-db-snapshot-{}", std::process::id()));
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        let db: &'static DB = Box::leak(Box::new(DB::open(&options, &path).unwrap()));
+        let column = db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+
+        db.put(b"key", b"before").unwrap();
+        let snapshot = Arc::new(DbSnapshot(db.snapshot()));
+        db.put(b"key", b"after").unwrap();
+
+        let (value, iter) = snapshot.pin(|| {
+            let value = db.get_cf_opt(&column, b"key", &read_options()).unwrap();
+            (value, iterator_cf(db, &column, IteratorMode::Start))
+        });
+        assert_eq!(value.as_deref(), Some(&b"before"[..]));
+
+        // the iterator keeps the snapshot alive once it is no longer pinned nor referenced
+        drop(snapshot);
+        let entries: Vec<_> = iter.map(|entry| entry.unwrap()).collect();
+        assert_eq!(entries, vec![(Box::from(&b"key"[..]), Box::from(&b"before"[..]))]);
+
+        // outside of a pinned scope, reads are served from the live database
+        assert_eq!(db.get_cf_opt(&column, b"key", &read_options()).unwrap().as_deref(), Some(&b"after"[..]));
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_tries_are_read_at_the_block_of_their_snapshot() {
+        let _db = DeoxysBackend::open_for_testing();
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(0x277u64)));
+        let key = StorageKey(PatriciaKey(StarkFelt::from(1u64)));
+        let value_at =
+            |tries: storage_handler::TriesView| tries.contract_storage_trie().unwrap().get(&address, &key).unwrap();
+
+        let mut trie = storage_handler::contract_storage_trie_mut();
+        trie.insert(address, key, StarkFelt::from(2770u64)).unwrap();
+        trie.commit(2770).unwrap();
+        drop(trie);
+        DeoxysBackend::snapshot_tries(2770);
+        assert!(DeoxysBackend::trie_snapshots().read().unwrap().opened.is_none());
+
+        // the next block is committed by the storage trie, but not yet by the others
+        let mut trie = storage_handler::contract_storage_trie_mut();
+        trie.insert(address, key, StarkFelt::from(2771u64)).unwrap();
+        trie.commit(2771).unwrap();
+        drop(trie);
+        assert_eq!(storage_handler::tries().block_number(), Some(2770));
+        assert_eq!(value_at(storage_handler::tries()), Some(Felt::from(2770u64)));
+        assert!(storage_handler::tries_at(2771).is_none());
+
+        DeoxysBackend::snapshot_tries(2771);
+        assert_eq!(value_at(storage_handler::tries()), Some(Felt::from(2771u64)));
+        assert_eq!(value_at(storage_handler::tries_at(2770).unwrap()), Some(Felt::from(2770u64)));
+
+        // reverting the tries drops the snapshots of the blocks reverted
+        let mut trie = storage_handler::contract_storage_trie_mut();
+        trie.revert_to(2770).unwrap();
+        drop(trie);
+        DeoxysBackend::snapshot_tries(2770);
+        assert!(storage_handler::tries_at(2771).is_none());
+        assert_eq!(value_at(storage_handler::tries()), Some(Felt::from(2770u64)));
+    }
+}
@@ -0,0 +1,563 @@
+//! Portable snapshots of the Starknet state at a block.
+//!
+//! Unlike the database copies shared by `deoxys snapshot publish`, a state snapshot only holds the
+//! state of the chain at one block: the class and nonce of every contract, their storage, and the
+//! declared classes. It does not depend on the layout of the database, and importing it rebuilds
+//! the tries, so that the state root it yields can be checked before syncing on top of it.
+//!
+//! A snapshot is a directory holding:
+//!
+//! - `manifest.json`: the [StateSnapshotHeader] of the snapshot, with the expected state root of
+//!   its block, and the list of its chunks along with their sha3-256 checksums. It is written last,
+//!   so that a snapshot without one is incomplete.
+//! - `chunk-<n>.gz`: gzip streams of SCALE-encoded [StateSnapshotEntry] records, in any order, each
+//!   terminated by [StateSnapshotEntry::End] so that truncated chunks are detected.
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use mp_convert::field_element::FromFieldElement;
+use parity_scale_codec::{Decode, Encode, IoReader};
+use rocksdb::IteratorMode;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce};
+use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::state::StorageKey;
+use starknet_core::types::DeclaredClassItem;
+use thiserror::Error;
+
+use crate::snapshot::iterator_cf;
+use crate::storage_handler::history::History;
+use crate::storage_handler::primitives::contract::StorageContractData;
+use crate::storage_handler::primitives::contract_class::{
+    ContractClassData, ContractClassWrapper, StorageContractClassData,
+};
+use crate::storage_handler::{self, DeoxysStorageError, StorageType, StorageView};
+use crate::{Column, DatabaseExt, DbError, DeoxysBackend};
+
+/// The version of the snapshot format written by [StateSnapshotWriter].
+pub const STATE_SNAPSHOT_VERSION: u32 = 3;
+
+/// The number of entries after which [StateSnapshotWriter] starts a new chunk.
+pub const STATE_SNAPSHOT_CHUNK_ENTRIES: usize = 1_000_000;
+
+const MANIFEST: &str = "manifest.json";
+
+#[derive(Error, Debug)]
+pub enum StateSnapshotError {
+    #[error("failed to read state snapshot: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid state snapshot: {0}")]
+    Codec(#[from] parity_scale_codec::Error),
+    #[error("invalid state snapshot manifest: {0}")]
+    Manifest(#[from] serde_json::Error),
+    #[error("unsupported state snapshot version {0}, expected {STATE_SNAPSHOT_VERSION}")]
+    Version(u32),
+    #[error("invalid state snapshot chunk name {0}")]
+    ChunkName(String),
+    #[error("state snapshot chunk {0} does not match its checksum")]
+    Checksum(String),
+    #[error("{0} already exists")]
+    AlreadyExists(PathBuf),
+    #[error("cannot export the state of block {block_number}, the database holds the state up to block {synced}")]
+    NotSynced { block_number: u64, synced: u64 },
+    #[error("failed to read the state from the database: {0}")]
+    Storage(#[from] DeoxysStorageError),
+    #[error("failed to read sync metadata: {0}")]
+    Db(#[from] DbError),
+}
+
+/// The block a state snapshot was taken at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSnapshotHeader {
+    pub version: u32,
+    pub block_number: u64,
+    pub block_hash: StarkHash,
+    /// The state root of the block, which the root of the tries rebuilt from the snapshot must
+    /// match.
+    pub global_state_root: StarkHash,
+    /// The Ethereum block in which the state update of `block_number` was posted to the Starknet
+    /// core contract, which the state root is checked against.
+    pub l1_block_number: u64,
+}
+
+/// The content of `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSnapshotManifest {
+    #[serde(flatten)]
+    pub header: StateSnapshotHeader,
+    /// The chunk files of the snapshot, relative to its directory.
+    pub chunks: Vec<StateSnapshotChunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSnapshotChunk {
+    pub name: String,
+    /// The sha3-256 checksum of the chunk file, in hex.
+    pub sha3_256: String,
+}
+
+/// A reader or writer computing the sha3-256 checksum of the bytes going through it.
+struct Checksummed<T> {
+    inner: T,
+    hasher: Sha3_256,
+}
+
+impl<T> Checksummed<T> {
+    fn new(inner: T) -> Self {
+        Self { inner, hasher: Sha3_256::new() }
+    }
+
+    fn checksum(self) -> (T, String) {
+        (self.inner, hex::encode(self.hasher.finalize()))
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+pub enum StateSnapshotEntry {
+    /// A contract with a class. Contracts without one, such as the block hash contract `0x1`, only
+    /// have storage entries.
+    Contract {
+        address: ContractAddress,
+        class_hash: ClassHash,
+        nonce: Nonce,
+    },
+    Storage {
+        address: ContractAddress,
+        key: StorageKey,
+        value: StarkFelt,
+    },
+    /// A declared class, along with its compiled class hash if it is a Sierra class.
+    Class {
+        class: ContractClassData,
+        compiled_class_hash: Option<CompiledClassHash>,
+    },
+    End,
+}
+
+/// Reads the entries of a state snapshot, chunk by chunk, as an iterator which fails if one of them
+/// is truncated.
+pub struct StateSnapshotReader {
+    dir: PathBuf,
+    manifest: StateSnapshotManifest,
+    next_chunk: usize,
+    chunk: Option<GzDecoder<BufReader<Checksummed<File>>>>,
+    done: bool,
+}
+
+impl StateSnapshotReader {
+    /// Reads the manifest of the snapshot in `dir`, failing if its format is not supported.
+    pub fn open(dir: &Path) -> Result<Self, StateSnapshotError> {
+        let file = BufReader::new(File::open(dir.join(MANIFEST))?);
+        let manifest: StateSnapshotManifest = serde_json::from_reader(file)?;
+        if manifest.header.version != STATE_SNAPSHOT_VERSION {
+            return Err(StateSnapshotError::Version(manifest.header.version));
+        }
+        // chunks are plain file names, which cannot point outside of the snapshot
+        let invalid_name = |name: &String| Path::new(name).file_name() != Some(OsStr::new(name));
+        if let Some(chunk) = manifest.chunks.iter().find(|chunk| invalid_name(&chunk.name)) {
+            return Err(StateSnapshotError::ChunkName(chunk.name.clone()));
+        }
+        Ok(Self { dir: dir.to_owned(), manifest, next_chunk: 0, chunk: None, done: false })
+    }
+
+    pub fn header(&self) -> &StateSnapshotHeader {
+        &self.manifest.header
+    }
+
+    /// Checks the checksum of the chunk read until its end marker.
+    fn finish_chunk(&mut self) -> Result<(), StateSnapshotError> {
+        let Some(decoder) = self.chunk.take() else {
+            return Ok(());
+        };
+        // the rest of the file, such as the gzip trailer, is part of the checksum
+        let mut file = decoder.into_inner();
+        std::io::copy(&mut file, &mut std::io::sink())?;
+        let (_, checksum) = file.into_inner().checksum();
+
+        let chunk = &self.manifest.chunks[self.next_chunk - 1];
+        if checksum != chunk.sha3_256 {
+            return Err(StateSnapshotError::Checksum(chunk.name.clone()));
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, error: StateSnapshotError) -> Option<Result<StateSnapshotEntry, StateSnapshotError>> {
+        self.done = true;
+        Some(Err(error))
+    }
+}
+
+impl Iterator for StateSnapshotReader {
+    type Item = Result<StateSnapshotEntry, StateSnapshotError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let decoder = match &mut self.chunk {
+                Some(decoder) => decoder,
+                None if self.next_chunk == self.manifest.chunks.len() => {
+                    self.done = true;
+                    return None;
+                }
+                None => {
+                    let path = self.dir.join(&self.manifest.chunks[self.next_chunk].name);
+                    self.next_chunk += 1;
+                    match File::open(path) {
+                        Ok(file) => self.chunk.insert(GzDecoder::new(BufReader::new(Checksummed::new(file)))),
+                        Err(e) => return self.fail(e.into()),
+                    }
+                }
+            };
+
+            match StateSnapshotEntry::decode(&mut IoReader(decoder)) {
+                Ok(StateSnapshotEntry::End) => {
+                    if let Err(e) = self.finish_chunk() {
+                        return self.fail(e);
+                    }
+                }
+                Ok(entry) => return Some(Ok(entry)),
+                Err(e) => return self.fail(e.into()),
+            }
+        }
+        None
+    }
+}
+
+/// Writes a state snapshot, which is only complete once [StateSnapshotWriter::finish] is called.
+pub struct StateSnapshotWriter {
+    dir: PathBuf,
+    manifest: StateSnapshotManifest,
+    chunk: Option<(String, GzEncoder<Checksummed<BufWriter<File>>>)>,
+    chunk_entries: usize,
+}
+
+impl StateSnapshotWriter {
+    /// Starts a snapshot in `dir`, which must not already hold one.
+    pub fn create(dir: &Path, header: StateSnapshotHeader) -> Result<Self, StateSnapshotError> {
+        if dir.join(MANIFEST).exists() {
+            return Err(StateSnapshotError::AlreadyExists(dir.join(MANIFEST)));
+        }
+        std::fs::create_dir_all(dir)?;
+        let manifest = StateSnapshotManifest { header, chunks: Vec::new() };
+        Ok(Self { dir: dir.to_owned(), manifest, chunk: None, chunk_entries: 0 })
+    }
+
+    pub fn write(&mut self, entry: &StateSnapshotEntry) -> Result<(), StateSnapshotError> {
+        let (_, encoder) = match &mut self.chunk {
+            Some(chunk) => chunk,
+            None => {
+                let name = format!("chunk-{:05}.gz", self.manifest.chunks.len());
+                let file = Checksummed::new(BufWriter::new(File::create(self.dir.join(&name))?));
+                self.chunk_entries = 0;
+                self.chunk.insert((name, GzEncoder::new(file, flate2::Compression::default())))
+            }
+        };
+        encoder.write_all(&entry.encode())?;
+
+        self.chunk_entries += 1;
+        if self.chunk_entries == STATE_SNAPSHOT_CHUNK_ENTRIES {
+            self.finish_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the manifest of the snapshot, and returns it.
+    pub fn finish(mut self) -> Result<StateSnapshotManifest, StateSnapshotError> {
+        self.finish_chunk()?;
+        let mut file = BufWriter::new(File::create(self.dir.join(MANIFEST))?);
+        serde_json::to_writer_pretty(&mut file, &self.manifest)?;
+        file.flush()?;
+        Ok(self.manifest)
+    }
+
+    fn finish_chunk(&mut self) -> Result<(), StateSnapshotError> {
+        if let Some((name, mut encoder)) = self.chunk.take() {
+            encoder.write_all(&StateSnapshotEntry::End.encode())?;
+            let (mut file, sha3_256) = encoder.finish()?.checksum();
+            file.flush()?;
+            self.manifest.chunks.push(StateSnapshotChunk { name, sha3_256 });
+        }
+        Ok(())
+    }
+}
+
+/// The number of entries of each kind written by [export_state_snapshot].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StateSnapshotCounts {
+    pub contracts: u64,
+    pub storage_entries: u64,
+    pub classes: u64,
+}
+
+/// Exports the state of the database at the block of `header` as a snapshot in `dir`.
+///
+/// Contracts and their storage are read at that block from their history, including the storage of
+/// the contracts without a class, such as the block hash contract `0x1`. Classes have no history,
+/// so the ones declared by the blocks synced after it are left out using their state diffs. The
+/// node must be stopped, so that the state does not change while it is exported.
+pub fn export_state_snapshot(
+    dir: &Path,
+    header: StateSnapshotHeader,
+) -> Result<StateSnapshotCounts, StateSnapshotError> {
+    let block_number = header.block_number;
+    let synced = DeoxysBackend::meta().sync_checkpoint()?.map(|checkpoint| checkpoint.block_number);
+    match synced {
+        Some(synced) if synced >= block_number => {}
+        synced => return Err(StateSnapshotError::NotSynced { block_number, synced: synced.unwrap_or_default() }),
+    }
+
+    let db = DeoxysBackend::expose_db();
+    let mut writer = StateSnapshotWriter::create(dir, header)?;
+    let mut counts = StateSnapshotCounts::default();
+
+    for entry in iterator_cf(&db, &db.get_column(Column::ContractData), IteratorMode::Start) {
+        let (key, value) = entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?;
+        let decode_error = |_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData);
+        let address: ContractAddress = bincode::deserialize(&key).map_err(decode_error)?;
+        let contract_data: StorageContractData = bincode::deserialize(&value).map_err(decode_error)?;
+
+        let Some(class_hash) = contract_data.class_hash.get_at(block_number) else {
+            continue;
+        };
+        let nonce = contract_data.nonce.get_at(block_number).copied().unwrap_or_default();
+        writer.write(&StateSnapshotEntry::Contract { address, class_hash: *class_hash, nonce })?;
+        counts.contracts += 1;
+    }
+
+    for entry in iterator_cf(&db, &db.get_column(Column::ContractStorage), IteratorMode::Start) {
+        let (key, value) =
+            entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?;
+        let decode_error = |_| DeoxysStorageError::StorageDecodeError(StorageType::ContractStorage);
+        let (address, key): (ContractAddress, StorageKey) = bincode::deserialize(&key).map_err(decode_error)?;
+        let history: History<StarkFelt> = bincode::deserialize(&value).map_err(decode_error)?;
+
+        // cleared entries are absent from the storage tries, and the entries of the contracts
+        // deployed after the block have no value at it
+        match history.get_at(block_number) {
+            Some(value) if *value != StarkFelt::ZERO => {
+                writer.write(&StateSnapshotEntry::Storage { address, key, value: *value })?;
+                counts.storage_entries += 1;
+            }
+            _ => continue,
+        }
+    }
+
+    let declared_after = declared_after(block_number)?;
+    for entry in iterator_cf(&db, &db.get_column(Column::ContractClassData), IteratorMode::Start) {
+        let (key, value) =
+            entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractClassData))?;
+        let class_hash: ClassHash = bincode::deserialize(&key)
+            .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractClassData))?;
+        if declared_after.contains(&class_hash) {
+            continue;
+        }
+        let StorageContractClassData { contract_class, abi, sierra_program_length, abi_length } =
+            StorageContractClassData::decode(&mut &value[..])
+                .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::Class))?;
+
+        let compiled_class_hash = storage_handler::contract_class_hashes().get(&class_hash)?;
+        let class = ContractClassData {
+            hash: class_hash,
+            contract_class: ContractClassWrapper { contract: contract_class, abi, sierra_program_length, abi_length },
+        };
+        writer.write(&StateSnapshotEntry::Class { class, compiled_class_hash })?;
+        counts.classes += 1;
+    }
+
+    writer.finish()?;
+    Ok(counts)
+}
+
+/// Calls `f` with every contract of the state at `block_number`, along with its class hash and
+/// nonce.
+///
+/// The contracts are read from the database one at a time. The contracts which only hold storage,
+/// such as the block hash contract `0x1`, have a zero class hash and nonce.
+pub fn for_each_contract<E>(
+    block_number: u64,
+    mut f: impl FnMut(ContractAddress, ClassHash, Nonce) -> Result<(), E>,
+) -> Result<(), E>
+where
+    E: From<DeoxysStorageError>,
+{
+    let db = DeoxysBackend::expose_db();
+
+    for entry in iterator_cf(&db, &db.get_column(Column::ContractData), IteratorMode::Start) {
+        let (key, value) = entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?;
+        let decode_error = |_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData);
+        let address: ContractAddress = bincode::deserialize(&key).map_err(decode_error)?;
+        let contract_data: StorageContractData = bincode::deserialize(&value).map_err(decode_error)?;
+
+        let class_hash = contract_data.class_hash.get_at(block_number).copied();
+        let nonce = contract_data.nonce.get_at(block_number).copied();
+        if class_hash.is_none() && nonce.is_none() {
+            continue;
+        }
+        f(address, class_hash.unwrap_or_default(), nonce.unwrap_or_default())?;
+    }
+
+    // the storage entries of a contract share the prefix of its address, so they are contiguous
+    let mut last_address = None;
+    for entry in iterator_cf(&db, &db.get_column(Column::ContractStorage), IteratorMode::Start) {
+        let (key, _) = entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?;
+        let (address, _): (ContractAddress, StorageKey) = bincode::deserialize(&key)
+            .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractStorage))?;
+        if last_address.replace(address) == Some(address) {
+            continue;
+        }
+        if storage_handler::contract_data().get(&address)?.is_none() {
+            f(address, ClassHash::default(), Nonce::default())?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the classes declared by the blocks stored after `block_number`.
+fn declared_after(block_number: u64) -> Result<HashSet<ClassHash>, DeoxysStorageError> {
+    let mut classes = HashSet::new();
+    // a state diff is stored along with every block, so the stored blocks end at the first one
+    // without a state diff
+    let mut block = block_number + 1;
+    while let Some(state_diff) = storage_handler::block_state_diff().get(block)? {
+        let declared_classes = state_diff
+            .declared_classes
+            .iter()
+            .map(|DeclaredClassItem { class_hash, .. }| class_hash)
+            .chain(state_diff.deprecated_declared_classes.iter());
+        classes.extend(declared_classes.map(ClassHash::from_field_element));
+        block += 1;
+    }
+    Ok(classes)
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::PatriciaKey;
+
+    use super::*;
+    use crate::storage_handler::StorageViewMut;
+    use crate::SyncCheckpoint;
+
+    fn header() -> StateSnapshotHeader {
+        StateSnapshotHeader {
+            version: STATE_SNAPSHOT_VERSION,
+            block_number: 10,
+            block_hash: StarkHash::from(1u64),
+            global_state_root: StarkHash::from(2u64),
+            l1_block_number: 100,
+        }
+    }
+
+    #[test]
+    fn truncated_snapshots_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("deoxys-state-snapshot-{}", std::process::id()));
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(3u64)));
+        let (class_hash, nonce) = (ClassHash::default(), Nonce::default());
+        let contract = StateSnapshotEntry::Contract { address, class_hash, nonce };
+        let key = StorageKey(PatriciaKey(StarkFelt::from(4u64)));
+        let storage = StateSnapshotEntry::Storage { address, key, value: StarkFelt::from(5u64) };
+
+        let mut writer = StateSnapshotWriter::create(&dir, header()).unwrap();
+        writer.write(&contract).unwrap();
+        writer.write(&storage).unwrap();
+        let manifest = writer.finish().unwrap();
+        assert!(StateSnapshotWriter::create(&dir, header()).is_err());
+
+        let reader = StateSnapshotReader::open(&dir).unwrap();
+        assert_eq!(*reader.header(), header());
+        let entries = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(matches!(entries[..], [StateSnapshotEntry::Contract { .. }, StateSnapshotEntry::Storage { .. }]));
+
+        // the same chunk, without its end marker
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&contract.encode()).unwrap();
+        std::fs::write(dir.join(&manifest.chunks[0].name), encoder.finish().unwrap()).unwrap();
+        let entries = StateSnapshotReader::open(&dir).unwrap().collect::<Result<Vec<_>, _>>();
+        assert!(matches!(entries, Err(StateSnapshotError::Codec(_))));
+
+        // the same chunk, compressed differently
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&contract.encode()).unwrap();
+        encoder.write_all(&storage.encode()).unwrap();
+        encoder.write_all(&StateSnapshotEntry::End.encode()).unwrap();
+        std::fs::write(dir.join(&manifest.chunks[0].name), encoder.finish().unwrap()).unwrap();
+        let entries = StateSnapshotReader::open(&dir).unwrap().collect::<Result<Vec<_>, _>>();
+        assert!(matches!(entries, Err(StateSnapshotError::Checksum(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exports_the_state_at_the_block() {
+        let _db = DeoxysBackend::open_for_testing();
+        let dir = std::env::temp_dir().join(format!("deoxys-state-snapshot-export-{}", std::process::id()));
+        let felt = |n: u64| StarkFelt::from(n);
+        let address = |n: u64| ContractAddress(PatriciaKey(felt(n)));
+        let key = |n: u64| StorageKey(PatriciaKey(felt(n)));
+
+        // a contract deployed at block 1, with the storage of the block hash contract, which has no
+        // class, then a contract deployed at block 2
+        let blocks = [(1, 0x100, [(0x100, 1, 5), (1, 1, 0x11)]), (2, 0x200, [(0x100, 1, 6), (0x200, 1, 7)])];
+        for (block_number, contract, storage) in blocks {
+            let contract_data = storage_handler::contract_data_mut();
+            contract_data.insert(address(contract), (Some(ClassHash(felt(contract))), None)).unwrap();
+            contract_data.commit(block_number).unwrap();
+            let contract_storage = storage_handler::contract_storage_mut();
+            for (contract, storage_key, value) in storage {
+                contract_storage.insert((address(contract), key(storage_key)), felt(value)).unwrap();
+            }
+            contract_storage.commit(block_number).unwrap();
+        }
+        let checkpoint = SyncCheckpoint { block_number: 2, block_hash: felt(2), global_state_root: felt(3) };
+        DeoxysBackend::meta().write_sync_checkpoint(checkpoint).unwrap();
+
+        let header = StateSnapshotHeader { block_number: 1, ..header() };
+        let counts = export_state_snapshot(&dir, header).unwrap();
+        assert_eq!((counts.contracts, counts.storage_entries, counts.classes), (1, 2, 0));
+
+        let mut entries = StateSnapshotReader::open(&dir)
+            .unwrap()
+            .map(|entry| match entry.unwrap() {
+                StateSnapshotEntry::Contract { address, class_hash, .. } => (address, class_hash.0, None),
+                StateSnapshotEntry::Storage { address, key, value } => (address, value, Some(key)),
+                entry => panic!("unexpected entry {entry:?}"),
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+        let mut expected = vec![
+            (address(0x100), felt(0x100), None),
+            (address(0x100), felt(5), Some(key(1))),
+            (address(1), felt(0x11), Some(key(1))),
+        ];
+        expected.sort();
+        assert_eq!(entries, expected);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
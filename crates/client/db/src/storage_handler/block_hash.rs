@@ -1,6 +1,7 @@
 use mp_felt::Felt252Wrapper;
 
 use super::{DeoxysStorageError, StorageType};
+use crate::snapshot::read_options;
 use crate::{Column, DatabaseExt, DeoxysBackend};
 
 pub struct BlockHashView;
@@ -13,11 +14,19 @@ impl BlockHashView {
             .map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::BlockHash))
     }
 
+    pub fn remove(&mut self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::BlockNumberToHash);
+
+        db.delete_cf(&column, bincode::serialize(&block_number).unwrap())
+            .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::BlockHash, block_number))
+    }
+
     pub fn get(&self, block_number: u64) -> Result<Option<Felt252Wrapper>, DeoxysStorageError> {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::BlockNumberToHash);
         let block_hash = db
-            .get_cf(&column, bincode::serialize(&block_number).unwrap())
+            .get_cf_opt(&column, bincode::serialize(&block_number).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::BlockHash))?
             .map(|bytes| bincode::deserialize::<Felt252Wrapper>(&bytes[..]));
 
@@ -32,7 +41,7 @@ impl BlockHashView {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::BlockNumberToHash);
 
-        match db.key_may_exist_cf(&column, bincode::serialize(&block_number).unwrap()) {
+        match db.key_may_exist_cf_opt(&column, bincode::serialize(&block_number).unwrap(), &read_options()) {
             true => Ok(self.get(block_number)?.is_some()),
             false => Ok(false),
         }
@@ -1,6 +1,7 @@
 use mp_felt::Felt252Wrapper;
 
 use super::{DeoxysStorageError, StorageType};
+use crate::snapshot::read_options;
 use crate::{Column, DatabaseExt, DeoxysBackend};
 
 pub struct BlockNumberView;
@@ -13,11 +14,19 @@ impl BlockNumberView {
         db.put_cf(&column, bincode::serialize(&block_hash).unwrap(), bincode::serialize(&block_number).unwrap())
             .map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::BlockNumber))
     }
+    pub fn remove(&mut self, block_hash: &Felt252Wrapper, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::BlockHashToNumber);
+
+        db.delete_cf(&column, bincode::serialize(&block_hash).unwrap())
+            .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::BlockNumber, block_number))
+    }
+
     pub fn get(&self, block_hash: &Felt252Wrapper) -> Result<Option<u64>, DeoxysStorageError> {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::BlockHashToNumber);
         let block_number = db
-            .get_cf(&column, bincode::serialize(&block_hash).unwrap())
+            .get_cf_opt(&column, bincode::serialize(&block_hash).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::BlockNumber))?
             .map(|bytes| bincode::deserialize::<u64>(&bytes[..]));
 
@@ -32,7 +41,7 @@ impl BlockNumberView {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::BlockHashToNumber);
 
-        match db.key_may_exist_cf(&column, bincode::serialize(&block_hash).unwrap()) {
+        match db.key_may_exist_cf_opt(&column, bincode::serialize(&block_hash).unwrap(), &read_options()) {
             true => Ok(self.get(block_hash)?.is_some()),
             false => Ok(false),
         }
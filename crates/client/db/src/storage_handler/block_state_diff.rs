@@ -1,7 +1,9 @@
+use rocksdb::WriteBatchWithTransaction;
 use starknet_core::types::StateDiff;
 
 use super::{DeoxysStorageError, StorageType};
-use crate::{Column, DatabaseExt, DeoxysBackend};
+use crate::snapshot::read_options;
+use crate::{cold_storage, Column, DatabaseExt, DeoxysBackend};
 
 pub struct BlockStateDiffView;
 
@@ -14,14 +16,43 @@ impl BlockStateDiffView {
             .map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::BlockStateDiff))
     }
 
+    /// Adds the state diff of `block_number` to `batch`, so that it is stored along with the other
+    /// writes of the batch.
+    pub fn insert_to(
+        &mut self,
+        batch: &mut WriteBatchWithTransaction<true>,
+        block_number: u64,
+        state_diff: &StateDiff,
+    ) -> Result<(), DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::BlockStateDiff);
+
+        let state_diff = bincode::serialize(state_diff)
+            .map_err(|_| DeoxysStorageError::StorageEncodeError(StorageType::BlockStateDiff))?;
+        batch.put_cf(&column, bincode::serialize(&block_number).unwrap(), state_diff);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::BlockStateDiff);
+
+        db.delete_cf(&column, bincode::serialize(&block_number).unwrap())
+            .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::BlockStateDiff, block_number))
+    }
+
     pub fn get(&self, block_number: u64) -> Result<Option<StateDiff>, DeoxysStorageError> {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::BlockStateDiff);
 
-        let state_diff = db
-            .get_cf(&column, bincode::serialize(&block_number).unwrap())
-            .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::BlockStateDiff))?
-            .map(|bytes| bincode::deserialize::<StateDiff>(&bytes[..]));
+        let key = bincode::serialize(&block_number).unwrap();
+        let state_diff = match db.get_cf_opt(&column, &key, &read_options()) {
+            Ok(Some(bytes)) => Ok(Some(bytes)),
+            Ok(None) => cold_storage::get_cf(Column::BlockStateDiff, &key),
+            Err(e) => Err(e),
+        }
+        .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::BlockStateDiff))?
+        .map(|bytes| bincode::deserialize::<StateDiff>(&bytes[..]));
 
         match state_diff {
             Some(Ok(state_diff)) => Ok(Some(state_diff)),
@@ -34,9 +65,12 @@ impl BlockStateDiffView {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::BlockStateDiff);
 
-        match db.key_may_exist_cf(&column, bincode::serialize(&block_number).unwrap()) {
+        let key = bincode::serialize(&block_number).unwrap();
+        match db.key_may_exist_cf_opt(&column, &key, &read_options()) {
             true => Ok(self.get(block_number)?.is_some()),
-            false => Ok(false),
+            false => cold_storage::get_cf(Column::BlockStateDiff, &key)
+                .map(|bytes| bytes.is_some())
+                .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::BlockStateDiff)),
         }
     }
 }
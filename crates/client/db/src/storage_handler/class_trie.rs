@@ -1,4 +1,4 @@
-use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+use std::sync::RwLockWriteGuard;
 
 use bonsai_trie::id::BasicId;
 use bonsai_trie::BonsaiStorage;
@@ -10,10 +10,10 @@ use starknet_types_core::hash::Poseidon;
 use super::{bonsai_identifier, conv_class_key, DeoxysStorageError, StorageType, StorageView, TrieType};
 use crate::bonsai_db::BonsaiDb;
 
-pub struct ClassTrieView<'a>(pub(crate) RwLockReadGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Poseidon>>);
+pub struct ClassTrieView(pub(crate) BonsaiStorage<BasicId, BonsaiDb<'static>, Poseidon>);
 pub struct ClassTrieViewMut<'a>(pub(crate) RwLockWriteGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Poseidon>>);
 
-impl StorageView for ClassTrieView<'_> {
+impl StorageView for ClassTrieView {
     type KEY = ClassHash;
 
     type VALUE = Felt;
@@ -31,7 +31,7 @@ impl StorageView for ClassTrieView<'_> {
     }
 }
 
-impl ClassTrieView<'_> {
+impl ClassTrieView {
     pub fn root(&self) -> Result<Felt, DeoxysStorageError> {
         self.0.root_hash(bonsai_identifier::CLASS).map_err(|_| DeoxysStorageError::TrieRootError(TrieType::Class))
     }
@@ -5,6 +5,7 @@ use starknet_api::core::ClassHash;
 
 use super::primitives::contract_class::StorageContractClassData;
 use super::{DeoxysStorageError, StorageType, StorageView, StorageViewMut};
+use crate::snapshot::read_options;
 use crate::{Column, DatabaseExt, DeoxysBackend};
 
 #[derive(Default, Debug)]
@@ -20,7 +21,7 @@ impl StorageView for ContractClassDataView {
         let column = db.get_column(Column::ContractClassData);
 
         let contract_class_data = db
-            .get_cf(&column, bincode::serialize(&class_hash).unwrap())
+            .get_cf_opt(&column, bincode::serialize(&class_hash).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractClassData))?
             .map(|bytes| StorageContractClassData::decode(&mut &bytes[..]));
 
@@ -35,13 +36,49 @@ impl StorageView for ContractClassDataView {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::ContractClassData);
 
-        match db.key_may_exist_cf(&column, bincode::serialize(&class_hash).unwrap()) {
+        match db.key_may_exist_cf_opt(&column, bincode::serialize(&class_hash).unwrap(), &read_options()) {
             true => Ok(self.get(class_hash)?.is_some()),
             false => Ok(false),
         }
     }
 }
 
+/// Estimates of the classes stored, as maintained by the database.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassDataStats {
+    /// The number of classes stored.
+    pub classes: u64,
+    /// The size of the classes on disk, once compressed.
+    pub disk_bytes: u64,
+}
+
+impl ContractClassDataView {
+    /// Estimates the number and size of the classes stored, without reading them.
+    pub fn stats(&self) -> Result<ClassDataStats, DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::ContractClassData);
+
+        let property = |name: &str| {
+            db.property_int_value_cf(&column, name)
+                .map(Option::unwrap_or_default)
+                .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractClassData))
+        };
+        Ok(ClassDataStats {
+            classes: property("rocksdb.estimate-num-keys")?,
+            disk_bytes: property("rocksdb.total-sst-files-size")?,
+        })
+    }
+
+    /// Removes a class declared in `block_number`, when that block is reverted.
+    pub fn remove(&self, class_hash: &ClassHash, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::ContractClassData);
+
+        db.delete_cf(&column, bincode::serialize(&class_hash).unwrap())
+            .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::ContractClassData, block_number))
+    }
+}
+
 impl StorageViewMut for ContractClassDataViewMut {
     type KEY = ClassHash;
     type VALUE = StorageContractClassData;
@@ -51,14 +88,25 @@ impl StorageViewMut for ContractClassDataViewMut {
         Ok(())
     }
 
-    fn commit(self, _block_number: u64) -> Result<(), DeoxysStorageError> {
+    fn commit(self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        self.commit_to(&mut batch, block_number)?;
+        DeoxysBackend::expose_db()
+            .write(batch)
+            .map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::ContractClassData))
+    }
+
+    fn commit_to(
+        self,
+        batch: &mut WriteBatchWithTransaction<true>,
+        _block_number: u64,
+    ) -> Result<(), DeoxysStorageError> {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::ContractClassData);
 
-        let mut batch = WriteBatchWithTransaction::<true>::default();
         for (key, value) in self.0.into_iter() {
             batch.put_cf(&column, bincode::serialize(&key).unwrap(), value.encode());
         }
-        db.write(batch).map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::ContractClassData))
+        Ok(())
     }
 }
@@ -3,6 +3,7 @@ use rocksdb::WriteBatchWithTransaction;
 use starknet_api::core::{ClassHash, CompiledClassHash};
 
 use super::{DeoxysStorageError, StorageType, StorageView, StorageViewMut};
+use crate::snapshot::read_options;
 use crate::{Column, DatabaseExt, DeoxysBackend};
 
 #[derive(Default, Debug)]
@@ -18,7 +19,7 @@ impl StorageView for ContractClassHashesView {
         let column = db.get_column(Column::ContractClassHashes);
 
         let compiled_class_hash = db
-            .get_cf(&column, bincode::serialize(&class_hash).unwrap())
+            .get_cf_opt(&column, bincode::serialize(&class_hash).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractClassHashes))?
             .map(|bytes| bincode::deserialize::<CompiledClassHash>(&bytes[..]));
 
@@ -33,13 +34,24 @@ impl StorageView for ContractClassHashesView {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::ContractClassHashes);
 
-        match db.key_may_exist_cf(&column, bincode::serialize(&class_hash).unwrap()) {
+        match db.key_may_exist_cf_opt(&column, bincode::serialize(&class_hash).unwrap(), &read_options()) {
             true => Ok(self.get(class_hash)?.is_some()),
             false => Ok(false),
         }
     }
 }
 
+impl ContractClassHashesView {
+    /// Removes a class declared in `block_number`, when that block is reverted.
+    pub fn remove(&self, class_hash: &ClassHash, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::ContractClassHashes);
+
+        db.delete_cf(&column, bincode::serialize(&class_hash).unwrap())
+            .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::ContractClassHashes, block_number))
+    }
+}
+
 impl StorageViewMut for ContractClassHashesViewMut {
     type KEY = ClassHash;
     type VALUE = CompiledClassHash;
@@ -49,14 +61,25 @@ impl StorageViewMut for ContractClassHashesViewMut {
         Ok(())
     }
 
-    fn commit(self, _block_number: u64) -> Result<(), DeoxysStorageError> {
+    fn commit(self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        self.commit_to(&mut batch, block_number)?;
+        DeoxysBackend::expose_db()
+            .write(batch)
+            .map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::ContractClassHashes))
+    }
+
+    fn commit_to(
+        self,
+        batch: &mut WriteBatchWithTransaction<true>,
+        _block_number: u64,
+    ) -> Result<(), DeoxysStorageError> {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::ContractClassHashes);
 
-        let mut batch = WriteBatchWithTransaction::<true>::default();
         for (key, value) in self.0.into_iter() {
             batch.put_cf(&column, bincode::serialize(&key).unwrap(), bincode::serialize(&value).unwrap());
         }
-        db.write(batch).map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::ContractClassHashes))
+        Ok(())
     }
 }
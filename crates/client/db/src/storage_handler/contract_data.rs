@@ -1,11 +1,14 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use crossbeam_skiplist::SkipMap;
 use itertools::izip;
-use rocksdb::{IteratorMode, WriteBatchWithTransaction};
+use rocksdb::{Direction, IteratorMode, WriteBatchWithTransaction};
 use starknet_api::core::{ClassHash, ContractAddress, Nonce};
 
 use super::primitives::contract::StorageContractData;
 use super::{DeoxysStorageError, StorageType, StorageView, StorageViewMut, StorageViewRevetible};
+use crate::snapshot::{iterator_cf, read_options};
 use crate::{Column, DatabaseExt, DeoxysBackend};
 
 #[derive(Default, Debug)]
@@ -21,7 +24,7 @@ impl StorageView for ContractDataView {
         let column = db.get_column(Column::ContractData);
 
         match db
-            .get_cf(&column, bincode::serialize(&contract_address).unwrap())
+            .get_cf_opt(&column, bincode::serialize(&contract_address).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?
         {
             Some(bytes) => Ok(Some(
@@ -36,7 +39,7 @@ impl StorageView for ContractDataView {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::ContractData);
 
-        match db.key_may_exist_cf(&column, bincode::serialize(&contract_address).unwrap()) {
+        match db.key_may_exist_cf_opt(&column, bincode::serialize(&contract_address).unwrap(), &read_options()) {
             true => Ok(self.get(contract_address)?.is_some()),
             false => Ok(false),
         }
@@ -63,7 +66,7 @@ impl ContractDataView {
         let column = db.get_column(Column::ContractData);
 
         let contract_data = match db
-            .get_cf(&column, bincode::serialize(&contract_address).unwrap())
+            .get_cf_opt(&column, bincode::serialize(&contract_address).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?
         {
             Some(bytes) => bincode::deserialize::<StorageContractData>(&bytes[..])
@@ -83,7 +86,7 @@ impl ContractDataView {
         let column = db.get_column(Column::ContractData);
 
         let contract_data = match db
-            .get_cf(&column, bincode::serialize(&contract_address).unwrap())
+            .get_cf_opt(&column, bincode::serialize(&contract_address).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?
         {
             Some(bytes) => bincode::deserialize::<StorageContractData>(&bytes[..])
@@ -93,6 +96,42 @@ impl ContractDataView {
 
         Ok(contract_data.class_hash.get_at(block_number).cloned())
     }
+
+    /// Returns the contracts which got `class_hash`, by deployment or class replacement, along with
+    /// the block at which they got it.
+    ///
+    /// Contracts are ordered by address, starting from `start_address` and up to `limit` of them.
+    /// Contracts which later replaced their class are still returned.
+    pub fn get_contracts_by_class(
+        &self,
+        class_hash: &ClassHash,
+        start_address: &ContractAddress,
+        limit: usize,
+    ) -> Result<Vec<(ContractAddress, u64)>, DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::ClassDeployments);
+
+        let prefix = bincode::serialize(class_hash).unwrap();
+        let start = class_deployment_key(class_hash, start_address);
+        let iter = iterator_cf(&db, &column, IteratorMode::From(&start, Direction::Forward));
+
+        let mut contracts = Vec::with_capacity(limit);
+        for entry in iter.take(limit) {
+            let (key, value) =
+                entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+
+            let (_, contract_address): (ClassHash, ContractAddress) = bincode::deserialize(&key)
+                .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData))?;
+            let block_number: u64 = bincode::deserialize(&value)
+                .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData))?;
+            contracts.push((contract_address, block_number));
+        }
+
+        Ok(contracts)
+    }
 }
 
 impl StorageViewMut for ContractDataViewMut {
@@ -105,6 +144,18 @@ impl StorageViewMut for ContractDataViewMut {
     }
 
     fn commit(self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        self.commit_to(&mut batch, block_number)?;
+        DeoxysBackend::expose_db()
+            .write(batch)
+            .map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::ContractData))
+    }
+
+    fn commit_to(
+        self,
+        batch: &mut WriteBatchWithTransaction<true>,
+        block_number: u64,
+    ) -> Result<(), DeoxysStorageError> {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::ContractData);
         let (keys, values): (Vec<_>, Vec<_>) = self.0.into_iter().unzip();
@@ -123,10 +174,15 @@ impl StorageViewMut for ContractDataViewMut {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        let mut batch = WriteBatchWithTransaction::<true>::default();
+        let deployments_column = db.get_column(Column::ClassDeployments);
         for (key, mut contract_data, (class_hash, nonce)) in izip!(keys, histories, values) {
             if let Some(class_hash) = class_hash {
                 contract_data.class_hash.push(block_number, class_hash).unwrap();
+                batch.put_cf(
+                    &deployments_column,
+                    class_deployment_key(&class_hash, &key),
+                    bincode::serialize(&block_number).unwrap(),
+                );
             }
 
             if let Some(nonce) = nonce {
@@ -135,7 +191,7 @@ impl StorageViewMut for ContractDataViewMut {
 
             batch.put_cf(&column, bincode::serialize(&key).unwrap(), bincode::serialize(&contract_data).unwrap());
         }
-        db.write(batch).map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::ContractData))
+        Ok(())
     }
 }
 
@@ -176,31 +232,76 @@ impl StorageViewRevetible for ContractDataViewMut {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::ContractData);
 
+        let deployments_column = db.get_column(Column::ClassDeployments);
         let iterator = db.iterator_cf(&column, IteratorMode::Start);
 
+        // the contract data and the class index are reverted together
+        let mut batch = WriteBatchWithTransaction::<true>::default();
         for data in iterator {
             let (key, value) =
                 data.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractData))?;
             let mut contract_data = bincode::deserialize::<StorageContractData>(&value[..])
                 .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData))?;
 
+            let is_reverted = |last_block: Option<&u64>| last_block.is_some_and(|block| *block > block_number);
+            if !is_reverted(contract_data.class_hash.0.last().map(|(block, _)| block))
+                && !is_reverted(contract_data.nonce.0.last().map(|(block, _)| block))
+            {
+                continue;
+            }
+
+            let contract_address: ContractAddress = bincode::deserialize(&key)
+                .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractData))?;
+            let reverted_class_hashes: Vec<_> = contract_data
+                .class_hash
+                .0
+                .iter()
+                .filter(|(block, _)| *block > block_number)
+                .map(|(_, class_hash)| class_deployment_key(class_hash, &contract_address))
+                .collect();
             contract_data.class_hash.revert_to(block_number);
             contract_data.nonce.revert_to(block_number);
 
+            // the contracts which held a reverted class before the revert point stay indexed, with the
+            // last block at which they got it
+            for index_key in reverted_class_hashes {
+                batch.delete_cf(&deployments_column, index_key);
+            }
+            for (index_key, block_number) in class_deployments(&contract_address, &contract_data) {
+                batch.put_cf(&deployments_column, index_key, block_number);
+            }
+
             match (contract_data.class_hash.is_empty(), contract_data.nonce.is_empty()) {
-                (true, true) => db
-                    .delete(key)
-                    .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::ContractData, block_number))?,
-                _ => db
-                    .put_cf(&column, key, bincode::serialize(&contract_data).unwrap())
-                    .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::ContractData, block_number))?,
+                (true, true) => batch.delete_cf(&column, key),
+                _ => batch.put_cf(&column, key, bincode::serialize(&contract_data).unwrap()),
             }
         }
 
-        Ok(())
+        db.write(batch).map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::ContractData, block_number))
     }
 }
 
+/// Key of the class index entry of a contract which got `class_hash`.
+fn class_deployment_key(class_hash: &ClassHash, contract_address: &ContractAddress) -> Vec<u8> {
+    bincode::serialize(&(class_hash, contract_address)).unwrap()
+}
+
+/// Class index entries of the class hashes held by a contract, each mapped to the last block at
+/// which the contract got it.
+pub(crate) fn class_deployments(
+    contract_address: &ContractAddress,
+    contract_data: &StorageContractData,
+) -> HashMap<Vec<u8>, Vec<u8>> {
+    contract_data
+        .class_hash
+        .0
+        .iter()
+        .map(|(block_number, class_hash)| {
+            (class_deployment_key(class_hash, contract_address), bincode::serialize(block_number).unwrap())
+        })
+        .collect()
+}
+
 impl ContractDataViewMut {
     pub fn insert_nonce(&self, contract_address: ContractAddress, nonce: Nonce) -> Result<(), DeoxysStorageError> {
         self.insert(contract_address, (None, Some(nonce)))
@@ -214,3 +315,26 @@ impl ContractDataViewMut {
         self.insert(contract_address, (Some(class_hash), None))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::hash::StarkFelt;
+
+    use super::*;
+
+    #[test]
+    fn test_class_deployments_keep_the_last_block_of_each_class() {
+        let contract_address = ContractAddress::default();
+        let class_hash = |n: u64| ClassHash(StarkFelt::from(n));
+        let mut contract_data = StorageContractData::default();
+        contract_data.class_hash.push(5, class_hash(1)).unwrap();
+        contract_data.class_hash.push(10, class_hash(2)).unwrap();
+        contract_data.class_hash.push(20, class_hash(1)).unwrap();
+
+        let deployments = class_deployments(&contract_address, &contract_data);
+        assert_eq!(deployments.len(), 2);
+        let block = |n: u64| deployments[&class_deployment_key(&class_hash(n), &contract_address)].clone();
+        assert_eq!(bincode::deserialize::<u64>(&block(1)).unwrap(), 20);
+        assert_eq!(bincode::deserialize::<u64>(&block(2)).unwrap(), 10);
+    }
+}
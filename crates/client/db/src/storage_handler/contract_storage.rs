@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use crossbeam_skiplist::{SkipMap, SkipSet};
 use itertools::izip;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use rocksdb::{IteratorMode, ReadOptions, WriteBatchWithTransaction};
+use rocksdb::{Direction, IteratorMode, ReadOptions, WriteBatchWithTransaction};
 use starknet_api::core::ContractAddress;
 use starknet_api::hash::StarkFelt;
 use starknet_api::state::StorageKey;
@@ -12,8 +12,10 @@ use starknet_core::types::{ContractStorageDiffItem, StateDiff, StorageEntry};
 use tokio::task::{spawn_blocking, JoinSet};
 
 use super::history::History;
+use super::versioned_storage::{version_key, version_keys};
 use super::{DeoxysStorageError, StorageType, StorageView, StorageViewMut, StorageViewRevetible};
-use crate::{Column, DatabaseExt, DeoxysBackend};
+use crate::snapshot::{iterator_cf, read_options};
+use crate::{cold_storage, Column, DatabaseExt, DeoxysBackend};
 
 #[derive(Default, Debug)]
 pub struct ContractStorageViewMut(SkipMap<(ContractAddress, StorageKey), StarkFelt>);
@@ -38,6 +40,22 @@ impl StorageViewMut for ContractStorageViewMut {
     /// * `block_number`: point in the chain at which to apply the new changes. Must be
     /// incremental
     fn commit(self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        self.commit_to(&mut batch, block_number)?;
+        DeoxysBackend::expose_db()
+            .write(batch)
+            .map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::ContractStorage))
+    }
+
+    /// Adds all changes up to this point to `batch`.
+    ///
+    /// * `block_number`: point in the chain at which to apply the new changes. Must be
+    /// incremental
+    fn commit_to(
+        self,
+        batch: &mut WriteBatchWithTransaction<true>,
+        block_number: u64,
+    ) -> Result<(), DeoxysStorageError> {
         let db = Arc::new(DeoxysBackend::expose_db());
         let column = db.get_column(Column::ContractStorage);
 
@@ -57,12 +75,14 @@ impl StorageViewMut for ContractStorageViewMut {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        let mut batch = WriteBatchWithTransaction::<true>::default();
+        let versions_column = db.get_column(Column::ContractStorageVersions);
         for (key, mut history, value) in izip!(keys, histories, values) {
+            let key = bincode::serialize(&key).unwrap();
             history.push(block_number, value).unwrap();
-            batch.put_cf(&column, bincode::serialize(&key).unwrap(), bincode::serialize(&history).unwrap());
+            batch.put_cf(&versions_column, version_key(&key, block_number), bincode::serialize(&value).unwrap());
+            batch.put_cf(&column, key, bincode::serialize(&history).unwrap());
         }
-        db.write(batch).map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::ContractStorage))
+        Ok(())
     }
 }
 
@@ -111,8 +131,8 @@ impl StorageViewRevetible for ContractStorageViewMut {
         read_options.set_iterate_lower_bound(bincode::serialize(&block_number).unwrap());
 
         // Currently we only use this iterator to retrieve the latest block number. A better way to
-        // do this would be to decouple the highest block lazy_static in `l2.rs`, but in the
-        // meantime this works as a workaround.
+        // do this would be to read the highest block from the sync state, but the database has no
+        // access to it, so in the meantime this works as a workaround.
         let mut iter = db.iterator_cf_opt(&db.get_column(Column::BlockStateDiff), read_options, IteratorMode::End);
         let block_number_max = match iter.next() {
             Some(Ok((bytes, _))) => bincode::deserialize(&bytes)
@@ -125,13 +145,20 @@ impl StorageViewRevetible for ContractStorageViewMut {
         // First, we aggregate all storage changes from the latest [StateDiff]
         // up to the target block number. We use a non-blocking set and perform this asychronously.
         // TODO: buffer this
-        let keys = (block_number..block_number_max).map(|key| bincode::serialize(&key).unwrap());
+        let keys: Vec<_> = (block_number..block_number_max).map(|key| bincode::serialize(&key).unwrap()).collect();
         let change_set = Arc::new(SkipSet::new());
-        for entry in db.batched_multi_get_cf(&db.get_column(Column::BlockStateDiff), keys, true) {
-            let state_diff = match entry {
-                Ok(Some(bytes)) => bincode::deserialize::<StateDiff>(&bytes)
+        let entries = db.batched_multi_get_cf(&db.get_column(Column::BlockStateDiff), &keys, true);
+        for (key, entry) in keys.iter().zip(entries) {
+            // the state diffs of the blocks older than the hot blocks are in the cold database
+            let bytes = match entry {
+                Ok(Some(bytes)) => Some(bytes.to_vec()),
+                Ok(None) => cold_storage::get_cf(Column::BlockStateDiff, key).ok().flatten(),
+                Err(_) => None,
+            };
+            let state_diff = match bytes {
+                Some(bytes) => bincode::deserialize::<StateDiff>(&bytes)
                     .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::BlockStateDiff))?,
-                _ => return Err(DeoxysStorageError::StorageRetrievalError(StorageType::BlockStateDiff)),
+                None => return Err(DeoxysStorageError::StorageRetrievalError(StorageType::BlockStateDiff)),
             };
 
             let change_set = Arc::clone(&change_set);
@@ -165,26 +192,23 @@ impl StorageViewRevetible for ContractStorageViewMut {
                     None => unreachable!("Reverting contract storage should only use existing contract addresses"),
                 };
 
+                // the versions set after the block are dropped along with the history entries, in
+                // the same batch so that they stay in sync
+                let mut batch = WriteBatchWithTransaction::<true>::default();
+                let versions_column = db.get_column(Column::ContractStorageVersions);
+                for version in version_keys(&db, &key, block_number + 1..u64::MAX)? {
+                    batch.delete_cf(&versions_column, version);
+                }
+
                 // history is updated and re-inserted into the db
                 // or deleted if reverting leaves it empty
                 history.revert_to(block_number);
                 match history.is_empty() {
-                    true => {
-                        if db.delete(key.clone()).is_err() {
-                            return Err(DeoxysStorageError::StorageRevertError(
-                                StorageType::ContractStorage,
-                                block_number,
-                            ));
-                        }
-                    }
-                    false => {
-                        if db.put_cf(&column, key.clone(), bincode::serialize(&history).unwrap()).is_err() {
-                            return Err(DeoxysStorageError::StorageRevertError(
-                                StorageType::ContractStorage,
-                                block_number,
-                            ));
-                        }
-                    }
+                    true => batch.delete_cf(&column, &key),
+                    false => batch.put_cf(&column, &key, bincode::serialize(&history).unwrap()),
+                }
+                if db.write(batch).is_err() {
+                    return Err(DeoxysStorageError::StorageRevertError(StorageType::ContractStorage, block_number));
                 }
 
                 Ok(())
@@ -199,6 +223,47 @@ impl StorageViewRevetible for ContractStorageViewMut {
     }
 }
 
+impl ContractStorageViewMut {
+    /// Reverts the history of the given storage keys to their state at `block_number`.
+    ///
+    /// Unlike [StorageViewRevetible::revert_to], this does not need to look up which keys changed
+    /// and is meant for rolling back a single known block.
+    pub fn revert_keys_to(
+        &self,
+        keys: impl IntoIterator<Item = (ContractAddress, StorageKey)>,
+        block_number: u64,
+    ) -> Result<(), DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::ContractStorage);
+        let versions_column = db.get_column(Column::ContractStorageVersions);
+
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        for key in keys {
+            let key = bincode::serialize(&key).unwrap();
+            for version in version_keys(db, &key, block_number + 1..u64::MAX)? {
+                batch.delete_cf(&versions_column, version);
+            }
+            let mut history: History<StarkFelt> = match db
+                .get_cf(&column, &key)
+                .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?
+                .map(|bytes| bincode::deserialize(&bytes))
+            {
+                Some(Ok(history)) => history,
+                Some(Err(_)) => return Err(DeoxysStorageError::StorageDecodeError(StorageType::ContractStorage)),
+                None => continue,
+            };
+
+            history.revert_to(block_number);
+            match history.is_empty() {
+                true => batch.delete_cf(&column, key),
+                false => batch.put_cf(&column, key, bincode::serialize(&history).unwrap()),
+            }
+        }
+
+        db.write(batch).map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::ContractStorage, block_number))
+    }
+}
+
 impl ContractStorageView {
     pub fn get_at(
         &self,
@@ -209,7 +274,7 @@ impl ContractStorageView {
         let column = db.get_column(Column::ContractStorage);
 
         let history: History<StarkFelt> = match db
-            .get_cf(&column, bincode::serialize(key).unwrap())
+            .get_cf_opt(&column, bincode::serialize(key).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?
             .map(|bytes| bincode::deserialize(&bytes))
         {
@@ -220,6 +285,50 @@ impl ContractStorageView {
 
         Ok(history.get_at(block_number).copied())
     }
+
+    /// Returns the storage entries of a contract at `block_number`, in key order, starting from
+    /// `start_key` and up to `limit` entries.
+    ///
+    /// Entries which were not yet set at `block_number`, or which were cleared to zero, are
+    /// skipped.
+    pub fn get_contract_storage_at(
+        &self,
+        contract_address: &ContractAddress,
+        start_key: &StorageKey,
+        block_number: u64,
+        limit: usize,
+    ) -> Result<Vec<(StorageKey, StarkFelt)>, DeoxysStorageError> {
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::ContractStorage);
+
+        // keys are serialized as the contract address followed by the storage key, both fixed size
+        let prefix = bincode::serialize(contract_address).unwrap();
+        let start = bincode::serialize(&(contract_address, start_key)).unwrap();
+        let iter = iterator_cf(&db, &column, IteratorMode::From(&start, Direction::Forward));
+
+        let mut entries = Vec::with_capacity(limit);
+        for entry in iter {
+            if entries.len() >= limit {
+                break;
+            }
+            let (key, value) =
+                entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+
+            let (_, storage_key): (ContractAddress, StorageKey) = bincode::deserialize(&key)
+                .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractStorage))?;
+            let history: History<StarkFelt> = bincode::deserialize(&value)
+                .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractStorage))?;
+            match history.get_at(block_number) {
+                Some(value) if *value != StarkFelt::ZERO => entries.push((storage_key, *value)),
+                _ => continue,
+            }
+        }
+
+        Ok(entries)
+    }
 }
 
 impl StorageView for ContractStorageView {
@@ -231,7 +340,7 @@ impl StorageView for ContractStorageView {
         let column = db.get_column(Column::ContractStorage);
 
         let history: History<StarkFelt> = match db
-            .get_cf(&column, bincode::serialize(key).unwrap())
+            .get_cf_opt(&column, bincode::serialize(key).unwrap(), &read_options())
             .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?
             .map(|bytes| bincode::deserialize(&bytes))
         {
@@ -247,7 +356,7 @@ impl StorageView for ContractStorageView {
         let db = DeoxysBackend::expose_db();
         let column = db.get_column(Column::ContractStorage);
 
-        match db.key_may_exist_cf(&column, bincode::serialize(&key).unwrap()) {
+        match db.key_may_exist_cf_opt(&column, bincode::serialize(&key).unwrap(), &read_options()) {
             true => Ok(self.get(key)?.is_some()),
             false => Ok(false),
         }
@@ -1,7 +1,7 @@
-use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+use std::sync::RwLockWriteGuard;
 
 use bonsai_trie::id::BasicId;
-use bonsai_trie::BonsaiStorage;
+use bonsai_trie::{BonsaiStorage, ProofNode};
 use starknet_api::core::{ContractAddress, PatriciaKey};
 use starknet_api::hash::StarkFelt;
 use starknet_api::state::StorageKey;
@@ -13,14 +13,12 @@ use super::{
 };
 use crate::bonsai_db::BonsaiDb;
 
-pub struct ContractStorageTrieView<'a>(
-    pub(crate) RwLockReadGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>,
-);
+pub struct ContractStorageTrieView(pub(crate) BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>);
 pub struct ContractStorageTrieViewMut<'a>(
     pub(crate) RwLockWriteGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>,
 );
 
-impl ContractStorageTrieView<'_> {
+impl ContractStorageTrieView {
     pub fn get(&self, identifier: &ContractAddress, key: &StorageKey) -> Result<Option<Felt>, DeoxysStorageError> {
         let identifier = conv_contract_identifier(identifier);
         let key = conv_contract_storage_key(key);
@@ -71,6 +69,19 @@ impl ContractStorageTrieView<'_> {
             .root_hash(conv_contract_identifier(identifier))
             .map_err(|_| DeoxysStorageError::TrieRootError(TrieType::ContractStorage))
     }
+
+    /// Returns the nodes from the root of the storage trie of a contract down to the leaf of `key`,
+    /// or down to where its path leaves the trie if the key is not set.
+    pub fn get_proof(
+        &self,
+        identifier: &ContractAddress,
+        key: &StorageKey,
+    ) -> Result<Vec<ProofNode>, DeoxysStorageError> {
+        let identifier = conv_contract_identifier(identifier);
+        let key = conv_contract_storage_key(key);
+
+        self.0.get_proof(identifier, &key).map_err(|_| DeoxysStorageError::TrieProofError(TrieType::ContractStorage))
+    }
 }
 
 impl ContractStorageTrieViewMut<'_> {
@@ -1,7 +1,7 @@
-use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+use std::sync::RwLockWriteGuard;
 
 use bonsai_trie::id::BasicId;
-use bonsai_trie::BonsaiStorage;
+use bonsai_trie::{BonsaiStorage, ProofNode};
 use starknet_api::core::ContractAddress;
 use starknet_types_core::felt::Felt;
 use starknet_types_core::hash::Pedersen;
@@ -9,12 +9,12 @@ use starknet_types_core::hash::Pedersen;
 use super::{bonsai_identifier, conv_contract_key, DeoxysStorageError, StorageType, StorageView, TrieType};
 use crate::bonsai_db::BonsaiDb;
 
-pub struct ContractTrieView<'a>(pub(crate) RwLockReadGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>);
+pub struct ContractTrieView(pub(crate) BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>);
 pub struct ContractTrieViewMut<'a>(
     pub(crate) RwLockWriteGuard<'a, BonsaiStorage<BasicId, BonsaiDb<'static>, Pedersen>>,
 );
 
-impl StorageView for ContractTrieView<'_> {
+impl StorageView for ContractTrieView {
     type KEY = ContractAddress;
 
     type VALUE = Felt;
@@ -32,10 +32,18 @@ impl StorageView for ContractTrieView<'_> {
     }
 }
 
-impl ContractTrieView<'_> {
+impl ContractTrieView {
     pub fn root(&self) -> Result<Felt, DeoxysStorageError> {
         self.0.root_hash(bonsai_identifier::CONTRACT).map_err(|_| DeoxysStorageError::TrieRootError(TrieType::Contract))
     }
+
+    /// Returns the nodes from the root of the trie down to the leaf of `contract_address`, or down
+    /// to where its path leaves the trie if the contract is not deployed.
+    pub fn get_proof(&self, contract_address: &ContractAddress) -> Result<Vec<ProofNode>, DeoxysStorageError> {
+        self.0
+            .get_proof(bonsai_identifier::CONTRACT, &conv_contract_key(contract_address))
+            .map_err(|_| DeoxysStorageError::TrieProofError(TrieType::Contract))
+    }
 }
 
 impl ContractTrieViewMut<'_> {
@@ -67,6 +75,12 @@ impl ContractTrieViewMut<'_> {
         self.0.root_hash(bonsai_identifier::CONTRACT).map_err(|_| DeoxysStorageError::TrieRootError(TrieType::Contract))
     }
 
+    pub fn revert_to(&mut self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        self.0
+            .revert_to(BasicId::new(block_number))
+            .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::Contract, block_number))
+    }
+
     pub fn update(&mut self, updates: Vec<(&ContractAddress, Felt)>) -> Result<(), DeoxysStorageError> {
         for (key, value) in updates {
             let key = conv_contract_key(key);
@@ -54,6 +54,18 @@ where
         }
     }
 
+    /// Drop the values superseded before a given index, keeping the value at that index.
+    /// Returns whether any value was dropped.
+    pub fn prune_before(&mut self, index: u64) -> bool {
+        let keep_from = match self.0.binary_search_by_key(&index, |&(i, _)| i) {
+            Ok(i) => i,
+            Err(0) => 0,
+            Err(i) => i - 1,
+        };
+        self.0.drain(..keep_from);
+        keep_from > 0
+    }
+
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
@@ -114,4 +126,21 @@ mod tests {
         assert_eq!(history.get_at(1), Some(&1));
         assert_eq!(history.get_at(2), Some(&1));
     }
+
+    #[test]
+    fn test_history_prune() {
+        let mut history = History::<u64>(vec![(0, 0), (2, 2), (4, 4)]);
+
+        assert!(!history.prune_before(1));
+        assert_eq!(history.get_at(1), Some(&0));
+
+        assert!(history.prune_before(3));
+        assert_eq!(history.get_at(3), Some(&2));
+        assert_eq!(history.get_at(4), Some(&4));
+        assert_eq!(history.get_at(1), None);
+
+        assert!(history.prune_before(4));
+        assert_eq!(history.get(), Some(&4));
+        assert!(!history.prune_before(5));
+    }
 }
@@ -1,9 +1,11 @@
 use std::fmt::Display;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use bitvec::prelude::Msb0;
 use bitvec::vec::BitVec;
 use bitvec::view::AsBits;
+use rocksdb::WriteBatchWithTransaction;
 use sp_core::hexdisplay::AsBytesRef;
 use starknet_api::core::{ClassHash, ContractAddress};
 use starknet_api::hash::StarkFelt;
@@ -15,13 +17,16 @@ use self::block_hash::BlockHashView;
 use self::block_number::BlockNumberView;
 use self::block_state_diff::BlockStateDiffView;
 use self::class_trie::{ClassTrieView, ClassTrieViewMut};
+pub use self::contract_class_data::ClassDataStats;
 use self::contract_class_data::{ContractClassDataView, ContractClassDataViewMut};
 use self::contract_class_hashes::{ContractClassHashesView, ContractClassHashesViewMut};
 use self::contract_data::{ContractDataView, ContractDataViewMut};
 use self::contract_storage::{ContractStorageView, ContractStorageViewMut};
 use self::contract_storage_trie::{ContractStorageTrieView, ContractStorageTrieViewMut};
 use self::contract_trie::{ContractTrieView, ContractTrieViewMut};
-use crate::DeoxysBackend;
+use self::versioned_storage::VersionedStorageView;
+use crate::bonsai_db::DatabaseKeyMapping;
+use crate::{DbSnapshot, DeoxysBackend, TRIE_SNAPSHOTS_KEPT};
 
 pub mod benchmark;
 pub mod block_hash;
@@ -31,13 +36,14 @@ mod class_trie;
 mod codec;
 mod contract_class_data;
 mod contract_class_hashes;
-mod contract_data;
+pub(crate) mod contract_data;
 mod contract_storage;
 mod contract_storage_trie;
 mod contract_trie;
-mod history;
+pub(crate) mod history;
 pub mod primitives;
 pub mod query;
+pub(crate) mod versioned_storage;
 
 pub mod bonsai_identifier {
     pub const CONTRACT: &[u8] = "0xcontract".as_bytes();
@@ -52,6 +58,8 @@ pub enum DeoxysStorageError {
     TrieInitError(TrieType),
     #[error("failed to compute trie root for {0}")]
     TrieRootError(TrieType),
+    #[error("failed to compute proof in {0}")]
+    TrieProofError(TrieType),
     #[error("failed to merge transactional state back into {0}")]
     TrieMergeError(TrieType),
     #[error("failed to retrieve latest id for {0}")]
@@ -70,6 +78,8 @@ pub enum DeoxysStorageError {
     StorageDecodeError(StorageType),
     #[error("failed to revert {0} to block {1}")]
     StorageRevertError(StorageType, u64),
+    #[error("failed to prune {0} up to block {1}")]
+    StoragePruneError(StorageType, u64),
 }
 
 #[derive(Debug)]
@@ -91,6 +101,7 @@ pub enum StorageType {
     BlockNumber,
     BlockHash,
     BlockStateDiff,
+    Block,
 }
 
 impl Display for TrieType {
@@ -118,6 +129,7 @@ impl Display for StorageType {
             StorageType::BlockStateDiff => "block state diff storage",
             StorageType::ContractClassHashes => "contract class hashes storage",
             StorageType::ContractData => "contract class data storage",
+            StorageType::Block => "block storage",
         };
 
         write!(f, "{storage_type}")
@@ -165,6 +177,17 @@ pub trait StorageViewMut {
     /// * `block_number`: point in the chain at which to apply the new changes. Must be
     /// incremental
     fn commit(self, block_number: u64) -> Result<(), DeoxysStorageError>;
+
+    /// Adds all changes up to this point to `batch`, so that they are applied along with the other
+    /// writes of the batch.
+    ///
+    /// * `block_number`: point in the chain at which to apply the new changes. Must be
+    /// incremental
+    fn commit_to(
+        self,
+        batch: &mut WriteBatchWithTransaction<true>,
+        block_number: u64,
+    ) -> Result<(), DeoxysStorageError>;
 }
 
 /// A mutable view on a backend storage interface, marking it as revertible in the chain.
@@ -183,16 +206,18 @@ pub fn contract_trie_mut<'a>() -> ContractTrieViewMut<'a> {
     ContractTrieViewMut(DeoxysBackend::bonsai_contract().write().unwrap())
 }
 
-pub fn contract_trie<'a>() -> ContractTrieView<'a> {
-    ContractTrieView(DeoxysBackend::bonsai_contract().read().unwrap())
+/// The contract trie at the last block committed by every trie, see [tries].
+pub fn contract_trie() -> Result<ContractTrieView, DeoxysStorageError> {
+    tries().contract_trie()
 }
 
 pub fn contract_storage_trie_mut<'a>() -> ContractStorageTrieViewMut<'a> {
     ContractStorageTrieViewMut(DeoxysBackend::bonsai_storage().write().unwrap())
 }
 
-pub fn contract_storage_trie<'a>() -> ContractStorageTrieView<'a> {
-    ContractStorageTrieView(DeoxysBackend::bonsai_storage().read().unwrap())
+/// The contract storage trie at the last block committed by every trie, see [tries].
+pub fn contract_storage_trie() -> Result<ContractStorageTrieView, DeoxysStorageError> {
+    tries().contract_storage_trie()
 }
 
 pub fn contract_storage_mut() -> ContractStorageViewMut {
@@ -203,12 +228,63 @@ pub fn contract_storage() -> ContractStorageView {
     ContractStorageView
 }
 
+pub fn versioned_storage() -> VersionedStorageView {
+    VersionedStorageView
+}
+
 pub fn class_trie_mut<'a>() -> ClassTrieViewMut<'a> {
     ClassTrieViewMut(DeoxysBackend::bonsai_class().write().unwrap())
 }
 
-pub fn class_trie<'a>() -> ClassTrieView<'a> {
-    ClassTrieView(DeoxysBackend::bonsai_class().read().unwrap())
+/// The class trie at the last block committed by every trie, see [tries].
+pub fn class_trie() -> Result<ClassTrieView, DeoxysStorageError> {
+    tries().class_trie()
+}
+
+/// Read-only views of the tries, all at the same block.
+///
+/// The views are opened on a snapshot taken once every trie committed that block, so that they
+/// neither observe the blocks committed by the sync in the meantime nor hold it back.
+pub struct TriesView {
+    block_number: Option<u64>,
+    snapshot: Arc<DbSnapshot>,
+}
+
+impl TriesView {
+    /// The block the tries are at, `None` if they did not commit any block since the database was
+    /// opened.
+    pub fn block_number(&self) -> Option<u64> {
+        self.block_number
+    }
+
+    pub fn contract_trie(&self) -> Result<ContractTrieView, DeoxysStorageError> {
+        let snapshot = Arc::clone(&self.snapshot);
+        Ok(ContractTrieView(DeoxysBackend::open_trie(DatabaseKeyMapping::contracts(), snapshot, TrieType::Contract)?))
+    }
+
+    pub fn contract_storage_trie(&self) -> Result<ContractStorageTrieView, DeoxysStorageError> {
+        let snapshot = Arc::clone(&self.snapshot);
+        let mapping = DatabaseKeyMapping::contracts_storage();
+        Ok(ContractStorageTrieView(DeoxysBackend::open_trie(mapping, snapshot, TrieType::ContractStorage)?))
+    }
+
+    pub fn class_trie(&self) -> Result<ClassTrieView, DeoxysStorageError> {
+        let snapshot = Arc::clone(&self.snapshot);
+        Ok(ClassTrieView(DeoxysBackend::open_trie(DatabaseKeyMapping::classes(), snapshot, TrieType::Class)?))
+    }
+}
+
+/// The tries at the last block they all committed.
+pub fn tries() -> TriesView {
+    let (block_number, snapshot) = DeoxysBackend::latest_trie_snapshot();
+    TriesView { block_number, snapshot }
+}
+
+/// The tries at `block_number`, if it is one of the last [TRIE_SNAPSHOTS_KEPT] blocks they
+/// committed.
+pub fn tries_at(block_number: u64) -> Option<TriesView> {
+    DeoxysBackend::trie_snapshot_at(block_number)
+        .map(|snapshot| TriesView { block_number: Some(block_number), snapshot })
 }
 
 pub fn contract_class_data_mut() -> ContractClassDataViewMut {
@@ -0,0 +1,230 @@
+//! Point-in-time reads of the contract storage.
+//!
+//! Every value taken by a storage key is stored under the key followed by the block it was set at,
+//! in big endian so that the versions of a key are sorted by block. The value of a key at a block
+//! is then the first version found seeking backward from it, without reading the whole history of
+//! the key.
+//!
+//! Databases created before the versions were stored have them built from the histories on a
+//! background thread, which records the last storage key migrated so that it resumes there after a
+//! restart. Reads are served from the histories until it is done.
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rocksdb::{Direction, IteratorMode, WriteBatchWithTransaction};
+use starknet_api::core::ContractAddress;
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+
+use super::history::History;
+use super::{DeoxysStorageError, StorageType};
+use crate::snapshot::iterator_cf;
+use crate::{Column, DatabaseExt, DeoxysBackend, DB};
+
+/// The number of storage keys whose versions are written in a single batch when building the
+/// versions from the storage histories.
+const MIGRATION_BATCH: usize = 100_000;
+
+/// Whether the versions are still being built from the histories.
+static MIGRATING: AtomicBool = AtomicBool::new(false);
+
+pub struct VersionedStorageView;
+
+/// The key of the value taken by a storage key at `block_number`, `storage_key` being the
+/// serialized `(contract_address, storage_key)` pair.
+pub(crate) fn version_key(storage_key: &[u8], block_number: u64) -> Vec<u8> {
+    [storage_key, &block_number.to_be_bytes()].concat()
+}
+
+/// The keys of the versions of a storage key set within `blocks`, `storage_key` being the
+/// serialized `(contract_address, storage_key)` pair.
+pub(crate) fn version_keys(
+    db: &DB,
+    storage_key: &[u8],
+    blocks: Range<u64>,
+) -> Result<Vec<Box<[u8]>>, DeoxysStorageError> {
+    let column = db.get_column(Column::ContractStorageVersions);
+    let (start, end) = (version_key(storage_key, blocks.start), version_key(storage_key, blocks.end));
+
+    let mut keys = Vec::new();
+    for entry in db.iterator_cf(&column, IteratorMode::From(&start, Direction::Forward)) {
+        let (key, _) = entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?;
+        if key.as_ref() >= end.as_slice() {
+            break;
+        }
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+impl VersionedStorageView {
+    /// Returns the value of a storage key at `block_number`, which is the latest value set at or
+    /// before it.
+    pub fn get_at(
+        &self,
+        key: &(ContractAddress, StorageKey),
+        block_number: u64,
+    ) -> Result<Option<StarkFelt>, DeoxysStorageError> {
+        if MIGRATING.load(Ordering::Acquire) {
+            return super::contract_storage().get_at(key, block_number);
+        }
+
+        let db = DeoxysBackend::expose_db();
+        let column = db.get_column(Column::ContractStorageVersions);
+
+        let prefix = bincode::serialize(key).unwrap();
+        let start = version_key(&prefix, block_number);
+        let mut iter = iterator_cf(&db, &column, IteratorMode::From(&start, Direction::Reverse));
+
+        match iter.next() {
+            Some(Ok((version, value))) if version.starts_with(&prefix) => Ok(Some(
+                bincode::deserialize(&value)
+                    .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractStorage))?,
+            )),
+            Some(Err(_)) => Err(DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Builds the versions of the storage keys from their histories on a background thread, from the
+/// storage key the migration stopped at, if the migration is not done.
+pub(crate) fn resume_migration() -> Result<(), DeoxysStorageError> {
+    let Some(cursor) = DeoxysBackend::meta()
+        .versions_migration()
+        .map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?
+    else {
+        return Ok(());
+    };
+
+    MIGRATING.store(true, Ordering::Release);
+    std::thread::Builder::new()
+        .name("db-versions-migration".into())
+        .spawn(move || {
+            log::info!("🗃️ Building the storage versions from the storage histories in the background");
+            let db = DeoxysBackend::expose_db();
+            let mut cursor = Some(cursor);
+            let mut written = 0;
+            while let Some(from) = cursor {
+                match migrate_batch(db, &from, MIGRATION_BATCH) {
+                    Ok((versions, next)) => {
+                        written += versions;
+                        cursor = next;
+                        log::info!("🗃️ Built {written} storage versions");
+                    }
+                    Err(e) => {
+                        log::error!("❗ Failed to build the storage versions, resuming on the next start: {e}");
+                        return;
+                    }
+                }
+            }
+            MIGRATING.store(false, Ordering::Release);
+            log::info!("🗃️ Storage versions built ({written} versions)");
+        })
+        .map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::ContractStorage))?;
+    Ok(())
+}
+
+/// Writes the versions of the `keys` storage keys from `from` on, along with the storage key to
+/// resume from, which is `None` once every storage key is migrated.
+///
+/// Returns the number of versions written and the storage key to resume from.
+fn migrate_batch(db: &DB, from: &[u8], keys: usize) -> Result<(u64, Option<Vec<u8>>), DeoxysStorageError> {
+    let column = db.get_column(Column::ContractStorage);
+    let versions_column = db.get_column(Column::ContractStorageVersions);
+
+    let mut batch = WriteBatchWithTransaction::<true>::default();
+    let mut written = 0;
+    let mut next = None;
+    for (migrated, entry) in db.iterator_cf(&column, IteratorMode::From(from, Direction::Forward)).enumerate() {
+        let (key, value) =
+            entry.map_err(|_| DeoxysStorageError::StorageRetrievalError(StorageType::ContractStorage))?;
+        if migrated == keys {
+            next = Some(key.to_vec());
+            break;
+        }
+        let history: History<StarkFelt> = bincode::deserialize(&value)
+            .map_err(|_| DeoxysStorageError::StorageDecodeError(StorageType::ContractStorage))?;
+
+        for (block_number, value) in history.0 {
+            batch.put_cf(&versions_column, version_key(&key, block_number), bincode::serialize(&value).unwrap());
+            written += 1;
+        }
+    }
+
+    // the progress is recorded along with the versions
+    let meta_column = db.get_column(Column::Meta);
+    match &next {
+        Some(next) => batch.put_cf(&meta_column, crate::static_keys::VERSIONS_MIGRATION, next),
+        None => batch.delete_cf(&meta_column, crate::static_keys::VERSIONS_MIGRATION),
+    }
+    db.write(batch).map_err(|_| DeoxysStorageError::StorageInsertionError(StorageType::ContractStorage))?;
+
+    Ok((written, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::PatriciaKey;
+
+    use super::*;
+    use crate::storage_handler::{self, StorageViewMut};
+
+    #[test]
+    fn values_are_read_at_past_blocks() {
+        let _db = DeoxysBackend::open_for_testing();
+        // the versions of the test database are built in the background when it is opened
+        while MIGRATING.load(Ordering::Acquire) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(0x275u64)));
+        let key = (address, StorageKey(PatriciaKey(StarkFelt::from(1u64))));
+        let value = |block_number: u64| Some(StarkFelt::from(block_number));
+
+        for block_number in [300, 302, 303] {
+            let contract_storage = storage_handler::contract_storage_mut();
+            contract_storage.insert(key, StarkFelt::from(block_number)).unwrap();
+            contract_storage.commit(block_number).unwrap();
+        }
+        let versioned_storage = storage_handler::versioned_storage();
+        assert_eq!(versioned_storage.get_at(&key, 299).unwrap(), None);
+        assert_eq!(versioned_storage.get_at(&key, 300).unwrap(), value(300));
+        assert_eq!(versioned_storage.get_at(&key, 301).unwrap(), value(300));
+        assert_eq!(versioned_storage.get_at(&key, 303).unwrap(), value(303));
+        assert_eq!(versioned_storage.get_at(&key, u64::MAX).unwrap(), value(303));
+
+        // the versions after the block are reverted along with the history
+        storage_handler::contract_storage_mut().revert_keys_to([key], 302).unwrap();
+        assert_eq!(versioned_storage.get_at(&key, 303).unwrap(), value(302));
+        assert_eq!(storage_handler::contract_storage().get_at(&key, 303).unwrap(), value(302));
+
+        // the versions are built again from the histories, in batches
+        let db = DeoxysBackend::expose_db();
+        let prefix = bincode::serialize(&key).unwrap();
+        for version in version_keys(db, &prefix, 0..u64::MAX).unwrap() {
+            db.delete_cf(&db.get_column(Column::ContractStorageVersions), version).unwrap();
+        }
+        assert_eq!(versioned_storage.get_at(&key, 303).unwrap(), None);
+        let (written, next) = migrate_batch(db, &prefix, 1).unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(DeoxysBackend::meta().versions_migration().unwrap(), next);
+        assert_eq!(versioned_storage.get_at(&key, 301).unwrap(), value(300));
+        assert_eq!(versioned_storage.get_at(&key, 303).unwrap(), value(302));
+        if let Some(next) = next {
+            db.delete_cf(&db.get_column(Column::Meta), crate::static_keys::VERSIONS_MIGRATION).unwrap();
+            assert!(next > prefix);
+        }
+    }
+
+    #[test]
+    fn test_version_keys_are_sorted_by_block() {
+        let storage_key = [7u8; 64];
+        let versions = [0, 1, 255, 256, 65_536, u64::MAX].map(|block_number| version_key(&storage_key, block_number));
+
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+        // the versions of the next storage key come after every version of this one
+        let next_storage_key = [[7u8; 63].as_slice(), &[8]].concat();
+        assert!(version_key(&next_storage_key, 0) > versions[5]);
+    }
+}
@@ -1,94 +1,22 @@
 use std::collections::HashMap;
 
 use mp_convert::field_element::FromFieldElement;
-use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey};
+use rocksdb::WriteBatchWithTransaction;
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce};
 use starknet_api::hash::StarkFelt;
-use starknet_core::types::{DeclaredClassItem, DeployedContractItem, NonceUpdate, ReplacedClassItem, StateUpdate};
+use starknet_api::state::StorageKey;
+use starknet_core::types::{
+    ContractStorageDiffItem, DeclaredClassItem, DeployedContractItem, NonceUpdate, ReplacedClassItem, StateUpdate,
+    StorageEntry,
+};
 use storage_handler::primitives::contract_class::{
     ClassUpdateWrapper, ContractClassData, ContractClassWrapper, StorageContractClassData,
 };
 
-use crate::storage_handler::{self, DeoxysStorageError, StorageView, StorageViewMut};
-
-pub async fn store_state_update(block_number: u64, state_update: StateUpdate) -> Result<(), DeoxysStorageError> {
-    let state_diff = state_update.state_diff.clone();
-    let nonce_map: HashMap<ContractAddress, Nonce> = state_update
-        .state_diff
-        .nonces
-        .into_iter()
-        .map(|NonceUpdate { contract_address, nonce }| {
-            (
-                ContractAddress(PatriciaKey(StarkFelt::new_unchecked(contract_address.to_bytes_be()))),
-                Nonce(StarkFelt::new_unchecked(nonce.to_bytes_be())),
-            )
-        })
-        .collect();
-
-    log::debug!("💾 update state: block_number: {}", block_number);
-
-    let (result1, result2, result3, result4) = tokio::join!(
-        // Contract address to class hash and nonce update
-        async move {
-            let handler_contract_data = storage_handler::contract_data_mut();
-
-            let iter_depoyed = state_update.state_diff.deployed_contracts.into_iter().map(
-                |DeployedContractItem { address, class_hash }| {
-                    (ContractAddress::from_field_element(address), ClassHash::from_field_element(class_hash))
-                },
-            );
-            let iter_replaced = state_update.state_diff.replaced_classes.into_iter().map(
-                |ReplacedClassItem { contract_address, class_hash }| {
-                    (ContractAddress::from_field_element(contract_address), ClassHash::from_field_element(class_hash))
-                },
-            );
-
-            iter_depoyed.chain(iter_replaced).for_each(|(contract_address, class_hash)| {
-                let class_hash = Some(class_hash);
-                let previous_nonce = handler_contract_data.get(&contract_address).unwrap().map(|data| data.nonce);
-                let nonce = match previous_nonce.unwrap_or_default().get().copied() {
-                    Some(nonce) => Some(nonce),
-                    None => nonce_map.get(&contract_address).copied(),
-                };
-
-                handler_contract_data.insert(contract_address, (class_hash, nonce)).unwrap()
-            });
-
-            handler_contract_data.commit(block_number)
-        },
-        // Class hash to compiled class hash update
-        async move {
-            let handler_contract_class_hashes = storage_handler::contract_class_hashes_mut();
-
-            state_update
-                .state_diff
-                .declared_classes
-                .into_iter()
-                .map(|DeclaredClassItem { class_hash, compiled_class_hash }| {
-                    (
-                        ClassHash(StarkFelt::new_unchecked(class_hash.to_bytes_be())),
-                        CompiledClassHash(StarkFelt::new_unchecked(compiled_class_hash.to_bytes_be())),
-                    )
-                })
-                .for_each(|(class_hash, compiled_class_hash)| {
-                    handler_contract_class_hashes.insert(class_hash, compiled_class_hash).unwrap();
-                });
-
-            handler_contract_class_hashes.commit(block_number)
-        },
-        // Block number to state diff update
-        async move { storage_handler::block_state_diff().insert(block_number, state_diff) },
-        // Contract address to contract storage update
-        async move { storage_handler::contract_storage_mut().commit(block_number) }
-    );
-
-    match (result1, result2, result3, result4) {
-        (Err(err), _, _, _) => Err(err),
-        (_, Err(err), _, _) => Err(err),
-        (_, _, Err(err), _) => Err(err),
-        (_, _, _, Err(err)) => Err(err),
-        _ => Ok(()),
-    }
-}
+use crate::storage_handler::{
+    self, DeoxysStorageError, StorageType, StorageView, StorageViewMut, StorageViewRevetible,
+};
+use crate::DeoxysBackend;
 
 pub async fn store_class_update(block_number: u64, class_update: ClassUpdateWrapper) -> Result<(), DeoxysStorageError> {
     let handler_contract_class_data_mut = storage_handler::contract_class_data_mut();
@@ -106,3 +34,216 @@ pub async fn store_class_update(block_number: u64, class_update: ClassUpdateWrap
 
     handler_contract_class_data_mut.commit(block_number)
 }
+
+/// Stores the state and class updates of `block_number` in a single write batch.
+///
+/// Unlike [store_class_update], either all the writes of the block are applied or none of them
+/// are, so an interrupted node never restarts with a block half stored.
+/// The tries are committed separately and are not part of the batch.
+pub async fn store_block_artifacts(
+    block_number: u64,
+    state_update: StateUpdate,
+    class_update: ClassUpdateWrapper,
+) -> Result<(), DeoxysStorageError> {
+    let StateUpdate { state_diff, .. } = state_update;
+    let mut nonce_map: HashMap<ContractAddress, Nonce> = state_diff
+        .nonces
+        .iter()
+        .map(|NonceUpdate { contract_address, nonce }| {
+            (ContractAddress::from_field_element(contract_address), Nonce::from_field_element(nonce))
+        })
+        .collect();
+
+    log::debug!("💾 update block: block_number: {}", block_number);
+
+    let mut batch = WriteBatchWithTransaction::<true>::default();
+
+    // Contract address to class hash and nonce update
+    let handler_contract_data = storage_handler::contract_data_mut();
+    let iter_deployed = state_diff.deployed_contracts.iter().map(|DeployedContractItem { address, class_hash }| {
+        (ContractAddress::from_field_element(address), ClassHash::from_field_element(class_hash))
+    });
+    let iter_replaced = state_diff.replaced_classes.iter().map(|ReplacedClassItem { contract_address, class_hash }| {
+        (ContractAddress::from_field_element(contract_address), ClassHash::from_field_element(class_hash))
+    });
+    for (contract_address, class_hash) in iter_deployed.chain(iter_replaced) {
+        let nonce = nonce_map.remove(&contract_address);
+        handler_contract_data.insert(contract_address, (Some(class_hash), nonce))?;
+    }
+    for (contract_address, nonce) in nonce_map {
+        handler_contract_data.insert_nonce(contract_address, nonce)?;
+    }
+    handler_contract_data.commit_to(&mut batch, block_number)?;
+
+    // Class hash to compiled class hash update
+    let handler_contract_class_hashes = storage_handler::contract_class_hashes_mut();
+    for DeclaredClassItem { class_hash, compiled_class_hash } in state_diff.declared_classes.iter() {
+        handler_contract_class_hashes.insert(
+            ClassHash::from_field_element(class_hash),
+            CompiledClassHash::from_field_element(compiled_class_hash),
+        )?;
+    }
+    handler_contract_class_hashes.commit_to(&mut batch, block_number)?;
+
+    // Contract address to contract storage update
+    let handler_contract_storage = storage_handler::contract_storage_mut();
+    for ContractStorageDiffItem { address, storage_entries } in state_diff.storage_diffs.iter() {
+        for StorageEntry { key, value } in storage_entries {
+            handler_contract_storage.insert(
+                (ContractAddress::from_field_element(address), StorageKey::from_field_element(key)),
+                StarkFelt::from_field_element(value),
+            )?;
+        }
+    }
+    handler_contract_storage.commit_to(&mut batch, block_number)?;
+
+    // Class hash to class definition update
+    let handler_contract_class_data = storage_handler::contract_class_data_mut();
+    for ContractClassData { hash: class_hash, contract_class: contract_class_wrapper } in class_update.0 {
+        let ContractClassWrapper { contract: contract_class, abi, sierra_program_length, abi_length } =
+            contract_class_wrapper;
+        handler_contract_class_data
+            .insert(class_hash, StorageContractClassData { contract_class, abi, sierra_program_length, abi_length })?;
+    }
+    handler_contract_class_data.commit_to(&mut batch, block_number)?;
+
+    // Block number to state diff update
+    storage_handler::block_state_diff().insert_to(&mut batch, block_number, &state_diff)?;
+
+    DeoxysBackend::expose_db().write(batch).map_err(|_| DeoxysStorageError::StorageCommitError(StorageType::Block))
+}
+
+/// Rolls back the state changes stored for `block_number`, leaving the state as it was at the
+/// previous block.
+pub async fn revert_state_update(block_number: u64) -> Result<(), DeoxysStorageError> {
+    let previous_block = block_number.saturating_sub(1);
+
+    storage_handler::contract_data_mut().revert_to(previous_block).await?;
+
+    if let Some(state_diff) = storage_handler::block_state_diff().get(block_number)? {
+        let keys =
+            state_diff.storage_diffs.into_iter().flat_map(|ContractStorageDiffItem { address, storage_entries }| {
+                storage_entries.into_iter().map(move |StorageEntry { key, value: _ }| {
+                    (ContractAddress::from_field_element(address), StorageKey::from_field_element(key))
+                })
+            });
+        storage_handler::contract_storage_mut().revert_keys_to(keys, previous_block)?;
+        storage_handler::block_state_diff().remove(block_number)?;
+    }
+
+    Ok(())
+}
+
+/// Rolls back the state changes stored for `block_number` and the classes it declared.
+///
+/// This is used to undo a block whose apply phase was interrupted, so it can be applied again. The
+/// tries are left untouched, see [revert_tries_to].
+pub async fn revert_block(block_number: u64) -> Result<(), DeoxysStorageError> {
+    revert_declared_classes(block_number)?;
+    revert_state_update(block_number).await
+}
+
+/// Removes the classes declared by `block_number`.
+fn revert_declared_classes(block_number: u64) -> Result<(), DeoxysStorageError> {
+    if let Some(state_diff) = storage_handler::block_state_diff().get(block_number)? {
+        let declared_classes = state_diff
+            .declared_classes
+            .iter()
+            .map(|DeclaredClassItem { class_hash, .. }| class_hash)
+            .chain(state_diff.deprecated_declared_classes.iter());
+        for class_hash in declared_classes {
+            let class_hash = ClassHash::from_field_element(class_hash);
+            storage_handler::contract_class_data().remove(&class_hash, block_number)?;
+            storage_handler::contract_class_hashes().remove(&class_hash, block_number)?;
+        }
+    }
+    Ok(())
+}
+
+/// Rolls back every block stored after `block_number`, leaving the state as it was at that block.
+///
+/// Blocks are undone from the most recent one: the state they changed, the classes they declared
+/// and their hashes are removed. The tries are left untouched, see [revert_tries_to].
+pub async fn revert_state_updates_to(block_number: u64) -> Result<(), DeoxysStorageError> {
+    // a state diff is stored along with every block, so the stored blocks end at the first one
+    // without a state diff
+    let mut last_block = block_number;
+    while storage_handler::block_state_diff().contains(last_block + 1)? {
+        last_block += 1;
+    }
+
+    for reverted_block in (block_number + 1..=last_block).rev() {
+        log::debug!("⏪ revert state: block_number: {}", reverted_block);
+
+        if let Some(block_hash) = storage_handler::block_hash().get(reverted_block)? {
+            storage_handler::block_number().remove(&block_hash, reverted_block)?;
+            storage_handler::block_hash().remove(reverted_block)?;
+        }
+
+        revert_block(reverted_block).await?;
+    }
+
+    Ok(())
+}
+
+/// Reverts the contract, contract storage and class tries to their commit at `block_number`.
+///
+/// The tries only keep the logs of their last [crate::TRIE_LOG_DEPTH] commits, so this fails if
+/// `block_number` is further in the past, or if the tries were not committed at that block because
+/// state roots are not verified.
+pub fn revert_tries_to(block_number: u64) -> Result<(), DeoxysStorageError> {
+    storage_handler::contract_storage_trie_mut().revert_to(block_number)?;
+    storage_handler::contract_trie_mut().revert_to(block_number)?;
+    storage_handler::class_trie_mut().revert_to(block_number)?;
+
+    DeoxysBackend::snapshot_tries(block_number);
+    DeoxysBackend::meta()
+        .write_tries_block(block_number)
+        .map_err(|_| DeoxysStorageError::StorageRevertError(StorageType::Block, block_number))
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::PatriciaKey;
+    use starknet_types_core::felt::Felt;
+
+    use super::*;
+
+    #[test]
+    fn test_tries_are_reverted_by_a_block() {
+        let _db = DeoxysBackend::open_for_testing();
+        let address = ContractAddress(PatriciaKey(StarkFelt::from(0x268u64)));
+        let key = StorageKey(PatriciaKey(StarkFelt::from(1u64)));
+        let class_hash = ClassHash(StarkFelt::from(0x268u64));
+        let commit = |block_number: u64| {
+            let mut trie = storage_handler::contract_storage_trie_mut();
+            trie.insert(address, key, StarkFelt::from(block_number)).unwrap();
+            trie.commit(block_number).unwrap();
+            drop(trie);
+            let mut trie = storage_handler::contract_trie_mut();
+            trie.insert(address, Felt::from(block_number)).unwrap();
+            trie.commit(block_number).unwrap();
+            drop(trie);
+            let mut trie = storage_handler::class_trie_mut();
+            trie.insert(class_hash, Felt::from(block_number)).unwrap();
+            trie.commit(block_number).unwrap();
+        };
+        let state = || {
+            (
+                storage_handler::contract_storage_trie_mut().get(&address, &key).unwrap(),
+                storage_handler::contract_trie_mut().root().unwrap(),
+                storage_handler::class_trie_mut().root().unwrap(),
+            )
+        };
+
+        commit(2680);
+        let committed = state();
+        commit(2681);
+        assert_ne!(state(), committed);
+
+        revert_tries_to(2680).unwrap();
+        assert_eq!(state(), committed);
+        assert_eq!(committed.0, Some(Felt::from(2680u64)));
+        assert_eq!(DeoxysBackend::meta().tries_block().unwrap(), Some(2680));
+    }
+}
@@ -0,0 +1,319 @@
+use std::sync::Arc;
+
+use mp_block::OrderedEvents;
+use parity_scale_codec::{Decode, Encode};
+use rocksdb::{Direction, IteratorMode, WriteBatchWithTransaction};
+use starknet_api::core::ContractAddress;
+use starknet_api::hash::StarkFelt;
+use starknet_api::transaction::Event;
+
+use crate::snapshot::iterator_cf;
+use crate::{Column, DatabaseExt, DbError, DB};
+
+/// Key of the `Transfer` event, `starknet_keccak("Transfer")`.
+const TRANSFER_SELECTOR: &str = "0x99cd8bde557814842a3121e8ddfd433a539b8c9f14bf31ebf108d12e6196e9";
+
+/// Length of the keys of the [Column::TokenTransfers] column.
+const TOKEN_TRANSFER_KEY_LEN: usize = 56;
+
+/// Length of the keys of the [Column::AccountTokenTransfers] column.
+const ACCOUNT_TOKEN_TRANSFER_KEY_LEN: usize = 88;
+
+/// A transfer of ERC-20 tokens, decoded from a `Transfer` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct TokenTransfer {
+    pub block_number: u64,
+    /// The index of the transaction which emitted the event in its block.
+    pub transaction_index: u64,
+    /// The index of the event among the events of its transaction.
+    pub event_index: u64,
+    pub token_address: StarkFelt,
+    pub from: StarkFelt,
+    pub to: StarkFelt,
+    /// The transferred amount, a `u256` split in its low and high 128 bits.
+    pub amount: (StarkFelt, StarkFelt),
+}
+
+/// Decodes a `Transfer` event emitted by an ERC-20 token.
+///
+/// Both the legacy layout, where every field is in the data, and the Cairo 1 layout, where the
+/// sender and the recipient are keys, are supported. Events of other standards emitting a
+/// `Transfer` event with the same layout, such as legacy ERC-721 tokens, cannot be told apart.
+fn decode_transfer(event: &Event, selector: StarkFelt) -> Option<(StarkFelt, StarkFelt, (StarkFelt, StarkFelt))> {
+    let keys = &event.content.keys;
+    let data = &event.content.data.0;
+    if keys.first()?.0 != selector {
+        return None;
+    }
+
+    match (&keys[1..], &data[..]) {
+        ([], [from, to, low, high]) => Some((*from, *to, (*low, *high))),
+        ([from, to], [low, high]) => Some((from.0, to.0, (*low, *high))),
+        _ => None,
+    }
+}
+
+/// Returns the key of a transfer in the [Column::TokenTransfers] column, as seen from `account`.
+///
+/// Numbers are written big endian so that the transfers of an account are in chain order.
+fn token_transfer_key(account: &StarkFelt, block_number: u64, transaction_index: u64, event_index: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(TOKEN_TRANSFER_KEY_LEN);
+    key.extend_from_slice(account.bytes());
+    key.extend_from_slice(&block_number.to_be_bytes());
+    key.extend_from_slice(&transaction_index.to_be_bytes());
+    key.extend_from_slice(&event_index.to_be_bytes());
+    key
+}
+
+/// Returns the key of a transfer in the [Column::AccountTokenTransfers] column, as seen from
+/// `account`.
+///
+/// The token follows the account, so that the transfers of an account in a token are in chain order
+/// too.
+fn account_token_transfer_key(
+    account: &StarkFelt,
+    token_address: &StarkFelt,
+    block_number: u64,
+    transaction_index: u64,
+    event_index: u64,
+) -> Vec<u8> {
+    let mut key = Vec::with_capacity(ACCOUNT_TOKEN_TRANSFER_KEY_LEN);
+    key.extend_from_slice(account.bytes());
+    key.extend_from_slice(token_address.bytes());
+    key.extend_from_slice(&block_number.to_be_bytes());
+    key.extend_from_slice(&transaction_index.to_be_bytes());
+    key.extend_from_slice(&event_index.to_be_bytes());
+    key
+}
+
+/// Returns the keys of a transfer in the [Column::TokenTransfers] and
+/// [Column::AccountTokenTransfers] columns, for the sender and for the recipient.
+fn transfer_keys(transfer: &TokenTransfer) -> [(Vec<u8>, Vec<u8>); 2] {
+    let TokenTransfer { block_number, transaction_index, event_index, token_address, .. } = *transfer;
+    [transfer.from, transfer.to].map(|account| {
+        (
+            token_transfer_key(&account, block_number, transaction_index, event_index),
+            account_token_transfer_key(&account, &token_address, block_number, transaction_index, event_index),
+        )
+    })
+}
+
+/// Allow interaction with the transfer db
+///
+/// The transfer db indexes the ERC-20 transfers by account, for both the sender and the recipient.
+/// It is only written to when the node runs with the `--index-transfers` flag.
+pub struct TransferDb {
+    db: Arc<DB>,
+}
+
+impl TransferDb {
+    pub(crate) fn new(db: Arc<DB>) -> Self {
+        Self { db }
+    }
+
+    /// Index the ERC-20 transfers emitted in a block
+    ///
+    /// Each transfer is indexed by account, and by account and token, for both the sender and the
+    /// recipient. The transfers of the block are also recorded by block number, so that they are
+    /// removed from the index when the block is reverted.
+    pub fn store_block_transfers(&self, block_number: u64, events: &[OrderedEvents]) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::TokenTransfers);
+        let account_token_column = self.db.get_column(Column::AccountTokenTransfers);
+        let block_column = self.db.get_column(Column::BlockTokenTransfers);
+        let selector = StarkFelt::try_from(TRANSFER_SELECTOR).expect("valid transfer selector");
+
+        let mut batch: WriteBatchWithTransaction<true> = Default::default();
+        let mut block_transfers = Vec::new();
+        for transaction_events in events {
+            let transaction_index = transaction_events.index() as u64;
+            for (event_index, event) in transaction_events.events().iter().enumerate() {
+                let Some((from, to, amount)) = decode_transfer(event, selector) else {
+                    continue;
+                };
+                let transfer = TokenTransfer {
+                    block_number,
+                    transaction_index,
+                    event_index: event_index as u64,
+                    token_address: *event.from_address.0.key(),
+                    from,
+                    to,
+                    amount,
+                };
+
+                for (key, account_token_key) in transfer_keys(&transfer) {
+                    batch.put_cf(&column, key, transfer.encode());
+                    batch.put_cf(&account_token_column, account_token_key, transfer.encode());
+                }
+                block_transfers.push(transfer);
+            }
+        }
+        batch.put_cf(&block_column, block_number.to_be_bytes(), block_transfers.encode());
+
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Removes the transfers of the blocks after `block_number` from the index, once these blocks
+    /// are reverted.
+    pub fn revert_transfers(&self, block_number: u64) -> Result<(), DbError> {
+        let column = self.db.get_column(Column::TokenTransfers);
+        let account_token_column = self.db.get_column(Column::AccountTokenTransfers);
+        let block_column = self.db.get_column(Column::BlockTokenTransfers);
+
+        // block numbers are written big endian, so the reverted blocks are the ones after the start
+        let start = (block_number + 1).to_be_bytes();
+        let iter = self.db.iterator_cf(&block_column, IteratorMode::From(&start, Direction::Forward));
+
+        let mut batch: WriteBatchWithTransaction<true> = Default::default();
+        for entry in iter {
+            let (block_key, transfers) = entry?;
+            for transfer in Vec::<TokenTransfer>::decode(&mut &transfers[..])? {
+                for (key, account_token_key) in transfer_keys(&transfer) {
+                    batch.delete_cf(&column, key);
+                    batch.delete_cf(&account_token_column, account_token_key);
+                }
+            }
+            batch.delete_cf(&block_column, block_key);
+        }
+
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Returns the ERC-20 transfers sent or received by `account`, in chain order.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - the account whose transfers are returned.
+    /// * `token_address` - the token to return the transfers of, or `None` for every token.
+    /// * `start` - the block number, transaction index and event index to start from, included.
+    /// * `to_block` - the last block to search, included.
+    /// * `limit` - the maximum number of transfers to return.
+    pub fn transfers(
+        &self,
+        account: &ContractAddress,
+        token_address: Option<&ContractAddress>,
+        start: (u64, u64, u64),
+        to_block: u64,
+        limit: usize,
+    ) -> Result<Vec<TokenTransfer>, DbError> {
+        let account = account.0.key();
+        // the transfers of a token are read from their own keys, rather than filtered out of every
+        // transfer of the account
+        let (column, prefix, start, key_len) = match token_address {
+            Some(token_address) => {
+                let token_address = token_address.0.key();
+                let prefix = [account.bytes(), token_address.bytes()].concat();
+                let start = account_token_transfer_key(account, token_address, start.0, start.1, start.2);
+                (Column::AccountTokenTransfers, prefix, start, ACCOUNT_TOKEN_TRANSFER_KEY_LEN)
+            }
+            None => {
+                let start = token_transfer_key(account, start.0, start.1, start.2);
+                (Column::TokenTransfers, account.bytes().to_vec(), start, TOKEN_TRANSFER_KEY_LEN)
+            }
+        };
+        let column = self.db.get_column(column);
+        let iter = iterator_cf(&self.db, &column, IteratorMode::From(&start, Direction::Forward));
+
+        let mut transfers = Vec::new();
+        for entry in iter {
+            if transfers.len() == limit {
+                break;
+            }
+            let (key, value) = entry?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            if key.len() != key_len {
+                return Err(DbError::DeserializeError("Invalid token transfer key".into()));
+            }
+
+            let transfer = TokenTransfer::decode(&mut &value[..])?;
+            if transfer.block_number > to_block {
+                break;
+            }
+            transfers.push(transfer);
+        }
+
+        Ok(transfers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_api::core::PatriciaKey;
+    use starknet_api::transaction::{EventContent, EventData, EventKey};
+
+    use super::*;
+
+    fn event(keys: Vec<StarkFelt>, data: Vec<StarkFelt>) -> Event {
+        Event {
+            from_address: ContractAddress::default(),
+            content: EventContent { keys: keys.into_iter().map(EventKey).collect(), data: EventData(data) },
+        }
+    }
+
+    #[test]
+    fn test_decode_transfer() {
+        let selector = StarkFelt::try_from(TRANSFER_SELECTOR).unwrap();
+        let [from, to, low, high] = [1u64, 2, 3, 4].map(StarkFelt::from);
+
+        let legacy = event(vec![selector], vec![from, to, low, high]);
+        assert_eq!(decode_transfer(&legacy, selector), Some((from, to, (low, high))));
+
+        let cairo_1 = event(vec![selector, from, to], vec![low, high]);
+        assert_eq!(decode_transfer(&cairo_1, selector), Some((from, to, (low, high))));
+
+        // ERC-721 transfers have their token id as keys
+        let erc_721 = event(vec![selector, from, to, low, high], vec![]);
+        assert_eq!(decode_transfer(&erc_721, selector), None);
+
+        let other = event(vec![StarkFelt::from(5u64)], vec![from, to, low, high]);
+        assert_eq!(decode_transfer(&other, selector), None);
+    }
+
+    #[test]
+    fn test_transfers_by_token_and_revert() {
+        let path = std::env::temp_dir().join(format!("deoxys-transfer-db-{}", std::process::id()));
+        let transfers = TransferDb::new(Arc::new(crate::open_rocksdb(&path, true).unwrap()));
+        let selector = StarkFelt::try_from(TRANSFER_SELECTOR).unwrap();
+        let address = |n: u64| ContractAddress(PatriciaKey::try_from(StarkFelt::from(n)).unwrap());
+        let (eth, strk, alice, bob) = (address(10), address(11), address(1), address(2));
+
+        // one transfer of eth then one of strk from alice to bob in each block
+        let block_events = |block_number: u64| {
+            let events = [eth, strk].map(|token| Event {
+                from_address: token,
+                content: EventContent {
+                    keys: vec![EventKey(selector)],
+                    data: EventData(vec![
+                        *alice.0.key(),
+                        *bob.0.key(),
+                        StarkFelt::from(block_number),
+                        StarkFelt::default(),
+                    ]),
+                },
+            });
+            vec![OrderedEvents::new(0, events.to_vec())]
+        };
+        for block_number in 1..=3 {
+            transfers.store_block_transfers(block_number, &block_events(block_number)).unwrap();
+        }
+
+        let positions = |account, token, start| {
+            let found = transfers.transfers(&account, token, start, u64::MAX, 10).unwrap();
+            found.into_iter().map(|transfer| (transfer.block_number, transfer.event_index)).collect::<Vec<_>>()
+        };
+        assert_eq!(positions(bob, None, (0, 0, 0)), vec![(1, 0), (1, 1), (2, 0), (2, 1), (3, 0), (3, 1)]);
+        assert_eq!(positions(alice, Some(&strk), (0, 0, 0)), vec![(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(positions(bob, Some(&eth), (2, 0, 0)), vec![(2, 0), (3, 0)]);
+        assert_eq!(positions(bob, Some(&address(12)), (0, 0, 0)), vec![]);
+
+        // a reorg reverts the blocks after block 1, and their transfers with them
+        transfers.revert_transfers(1).unwrap();
+        assert_eq!(positions(bob, None, (0, 0, 0)), vec![(1, 0), (1, 1)]);
+        assert_eq!(positions(alice, Some(&eth), (0, 0, 0)), vec![(1, 0)]);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
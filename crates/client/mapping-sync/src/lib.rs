@@ -46,6 +46,7 @@ pub struct MappingSyncWorker<C, BE, H> {
     retry_times: usize,
     sync_from: <DHeaderT as HeaderT>::Number,
     block_metrics: Option<BlockMetrics>,
+    index_transfers: bool,
 }
 
 impl<C, BE, H> Unpin for MappingSyncWorker<C, BE, H> {}
@@ -60,6 +61,7 @@ impl<C, BE, H> MappingSyncWorker<C, BE, H> {
         retry_times: usize,
         sync_from: <DHeaderT as HeaderT>::Number,
         prometheus_registry: Option<prometheus::Registry>,
+        index_transfers: bool,
     ) -> Self {
         let block_metrics =
             prometheus_registry.and_then(|registry| block_metrics::BlockMetrics::register(&registry).ok());
@@ -77,6 +79,7 @@ impl<C, BE, H> MappingSyncWorker<C, BE, H> {
             retry_times,
             sync_from,
             block_metrics,
+            index_transfers,
         }
     }
 }
@@ -127,6 +130,7 @@ where
                 self.retry_times,
                 self.sync_from,
                 self.block_metrics.as_ref(),
+                self.index_transfers,
             ) {
                 Ok(have_next) => {
                     self.have_next = have_next;
@@ -15,10 +15,35 @@ use sc_client_api::backend::{Backend, StorageProvider};
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::{Backend as _, HeaderBackend};
 use sp_runtime::traits::Header as HeaderT;
+use starknet_api::core::{calculate_contract_address, ContractAddress};
+use starknet_api::transaction::Transaction;
 
 use crate::block_metrics::BlockMetrics;
 
-fn sync_block<C, BE, H>(client: &C, header: &DHeaderT, block_metrics: Option<&BlockMetrics>) -> anyhow::Result<()>
+/// Returns the account which sent a transaction, or `None` for transactions not sent by an account.
+///
+/// Account deployments are sent by the account they deploy.
+fn transaction_sender(transaction: &Transaction) -> Option<ContractAddress> {
+    match transaction {
+        Transaction::Declare(tx) => Some(tx.sender_address()),
+        Transaction::Invoke(tx) => Some(tx.sender_address()),
+        Transaction::DeployAccount(tx) => calculate_contract_address(
+            tx.contract_address_salt(),
+            tx.class_hash(),
+            &tx.constructor_calldata(),
+            ContractAddress::default(),
+        )
+        .ok(),
+        Transaction::Deploy(_) | Transaction::L1Handler(_) => None,
+    }
+}
+
+fn sync_block<C, BE, H>(
+    client: &C,
+    header: &DHeaderT,
+    block_metrics: Option<&BlockMetrics>,
+    index_transfers: bool,
+) -> anyhow::Result<()>
 where
     // TODO: refactor this!
     C: HeaderBackend<DBlockT> + StorageProvider<DBlockT, BE>,
@@ -38,8 +63,9 @@ where
             let opt_storage_starknet_block = get_block_by_block_hash(client, substrate_block_hash);
             match opt_storage_starknet_block {
                 Ok(storage_starknet_block) => {
-                    let digest_starknet_block_hash = digest_starknet_block.header().hash::<H>();
-                    let storage_starknet_block_hash = storage_starknet_block.header().hash::<H>();
+                    let chain_id = client.runtime_api().chain_id(substrate_block_hash)?;
+                    let digest_starknet_block_hash = digest_starknet_block.header().hash::<H>(chain_id);
+                    let storage_starknet_block_hash = storage_starknet_block.header().hash::<H>(chain_id);
                     // Ensure the two blocks sources (chain storage and block digest) agree on the block content
                     if digest_starknet_block_hash != storage_starknet_block_hash {
                         Err(anyhow::anyhow!(
@@ -47,8 +73,6 @@ where
                              db state ({storage_starknet_block_hash:?})"
                         ))
                     } else {
-                        let chain_id = client.runtime_api().chain_id(substrate_block_hash)?;
-
                         // Success, we write the Starknet to Substate hashes mapping to db
                         let mapping_commitment = mc_db::MappingCommitment {
                             block_number: digest_starknet_block.header().block_number,
@@ -66,6 +90,11 @@ where
                                     .into()
                                 })
                                 .collect(),
+                            starknet_transaction_senders: digest_starknet_block
+                                .transactions()
+                                .iter()
+                                .map(transaction_sender)
+                                .collect(),
                         };
 
                         if let Some(block_metrics) = block_metrics {
@@ -95,6 +124,15 @@ where
                                 .set(f64::from_u128(l1_gas_price.strk_l1_gas_price.into()).unwrap_or(f64::MIN))
                         }
 
+                        if index_transfers {
+                            DeoxysBackend::transfers()
+                                .store_block_transfers(
+                                    digest_starknet_block.header().block_number,
+                                    digest_starknet_block.events(),
+                                )
+                                .map_err(|e| anyhow::anyhow!(e))?;
+                        }
+
                         DeoxysBackend::mapping().write_hashes(mapping_commitment).map_err(|e| anyhow::anyhow!(e))
                     }
                 }
@@ -110,9 +148,11 @@ where
     }
 }
 
-fn sync_genesis_block<C, H>(_client: &C, header: &DHeaderT) -> anyhow::Result<()>
+fn sync_genesis_block<C, H>(client: &C, header: &DHeaderT) -> anyhow::Result<()>
 where
     C: HeaderBackend<DBlockT>,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT>,
     H: HasherT,
 {
     let substrate_block_hash = header.hash();
@@ -124,12 +164,14 @@ where
         }
         Err(FindLogError::MultipleLogs) => return Err(anyhow::anyhow!("Multiple logs found")),
     };
-    let block_hash = block.header().hash::<H>();
+    let chain_id = client.runtime_api().chain_id(substrate_block_hash)?;
+    let block_hash = block.header().hash::<H>(chain_id);
     let mapping_commitment = mc_db::MappingCommitment::<DBlockT> {
         block_number: block.header().block_number,
         block_hash: substrate_block_hash,
         starknet_block_hash: block_hash.into(),
         starknet_transaction_hashes: Vec::new(),
+        starknet_transaction_senders: Vec::new(),
     };
 
     DeoxysBackend::mapping().write_hashes(mapping_commitment)?;
@@ -142,6 +184,7 @@ fn sync_one_block<C, BE, H>(
     substrate_backend: &BE,
     sync_from: <DHeaderT as HeaderT>::Number,
     block_metrics: Option<&BlockMetrics>,
+    index_transfers: bool,
 ) -> anyhow::Result<bool>
 where
     C: ProvideRuntimeApi<DBlockT>,
@@ -181,7 +224,7 @@ where
         DeoxysBackend::meta().write_current_syncing_tips(current_syncing_tips)?;
         Ok(true)
     } else {
-        sync_block::<_, _, H>(client, &operating_header, block_metrics)?;
+        sync_block::<_, _, H>(client, &operating_header, block_metrics, index_transfers)?;
 
         current_syncing_tips.push(*operating_header.parent_hash());
         DeoxysBackend::meta().write_current_syncing_tips(current_syncing_tips)?;
@@ -195,6 +238,7 @@ pub fn sync_blocks<C, BE, H>(
     limit: usize,
     sync_from: <DHeaderT as HeaderT>::Number,
     block_metrics: Option<&BlockMetrics>,
+    index_transfers: bool,
 ) -> anyhow::Result<bool>
 where
     C: ProvideRuntimeApi<DBlockT>,
@@ -206,7 +250,8 @@ where
     let mut synced_any = false;
 
     for _ in 0..limit {
-        synced_any = synced_any || sync_one_block::<_, _, H>(client, substrate_backend, sync_from, block_metrics)?;
+        synced_any = synced_any
+            || sync_one_block::<_, _, H>(client, substrate_backend, sync_from, block_metrics, index_transfers)?;
     }
 
     Ok(synced_any)
@@ -0,0 +1,218 @@
+//! Typed Rust client for the rpc interface of a Deoxys node
+//!
+//! The methods are generated by `jsonrpsee` from the same traits and types the server of the node
+//! implements, shared through `mc-rpc-core`, so requests and responses always match what the node
+//! serves. They are brought in scope by the `*Client` traits re-exported here, and available on any
+//! [DeoxysClient]:
+//!
+//! ```no_run
+//! use deoxys_rpc_client::{DeoxysClient, StarknetReadRpcApiClient};
+//!
+//! # async fn block_number() -> Result<u64, deoxys_rpc_client::Error> {
+//! let client = DeoxysClient::http("http://localhost:9944")?;
+//! client.block_number().await
+//! # }
+//! ```
+
+mod retry;
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use jsonrpsee::core::client::{BatchResponse, ClientT, Subscription, SubscriptionClientT};
+use jsonrpsee::core::params::BatchRequestBuilder;
+use jsonrpsee::core::traits::ToRpcParams;
+pub use jsonrpsee::core::Error;
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee::ws_client::{WsClient, WsClientBuilder};
+pub use mc_rpc_core::{
+    AccountSummary, BackfillRange, BlockContextError, BlockContextRequest, BlockContextResponse, BlockContextResult,
+    BlockHeader, BlockRange, CairoProfilerTransactionTrace, CallTrace, ClassInstance, ColumnStats, ContractData,
+    ContractStoragePage, ContractsByClassPage, ConversionError, DataAvailability, DbStats, DecodedEvent,
+    DecodedRevertReason, DeniedEntryPoint, DeoxysAdminRpcApiClient, DeoxysRpcApiClient, EdgePath, EventProof,
+    EventsSource, ExecutionPolicyRules, ExtendedBlockId, FeeTokenBalances, Felt, GetProofOutput, MerkleNode,
+    PathfinderRpcApiClient, SenderTransaction, StarknetReadRpcApiClient, StarknetTraceRpcApiClient,
+    StarknetWriteRpcApiClient, SyncProgress, SyncStatus, TokenTransferEntry, TokenTransfersPage, TraceFormat,
+    TransactionProof, TransactionTraceOutput, TransactionsBySenderPage, TrieNode, U256Balance, WithDecodedRevertReason,
+    WithDecodedRevertReasons,
+};
+pub use retry::RetryPolicy;
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+pub use starknet_core::types;
+
+/// How long a request may take before it fails with [Error::RequestTimeout], by default.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A client to the rpc interface of a Deoxys node, over HTTP or WebSocket.
+///
+/// Requests which fail because of the transport or time out are retried according to the
+/// [RetryPolicy] of the client. Errors returned by the node itself are never retried.
+#[derive(Clone, Debug)]
+pub struct DeoxysClient<C> {
+    inner: C,
+    retry_policy: RetryPolicy,
+}
+
+impl DeoxysClient<HttpClient> {
+    /// Creates a client sending its requests over HTTP, with the default settings.
+    pub fn http(url: impl AsRef<str>) -> Result<Self, Error> {
+        DeoxysClientBuilder::default().build_http(url)
+    }
+}
+
+impl DeoxysClient<WsClient> {
+    /// Connects a client over WebSocket, with the default settings. Subscriptions are only
+    /// available over WebSocket.
+    pub async fn ws(url: impl AsRef<str>) -> Result<Self, Error> {
+        DeoxysClientBuilder::default().build_ws(url).await
+    }
+}
+
+impl<C> DeoxysClient<C> {
+    pub fn builder() -> DeoxysClientBuilder {
+        DeoxysClientBuilder::default()
+    }
+
+    /// The underlying `jsonrpsee` client, which sends requests without retrying them.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Runs a request until it succeeds, fails with an error which is not retryable, or runs out
+    /// of retries.
+    async fn with_retries<T, F, Fut>(&self, method: &str, mut request: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let err = match request().await {
+                Err(err) if attempt < self.retry_policy.max_retries && retry::is_retryable(&err) => err,
+                result => return result,
+            };
+
+            let backoff = self.retry_policy.backoff(attempt);
+            log::debug!("Retrying {method} in {backoff:?} after error: {err}");
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl<C> ClientT for DeoxysClient<C>
+where
+    C: ClientT + Send + Sync,
+{
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), Error>
+    where
+        Params: ToRpcParams + Send,
+    {
+        let params = RawParams::new(params)?;
+        self.with_retries(method, move || self.inner.notification(method, params.clone())).await
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        let params = RawParams::new(params)?;
+        self.with_retries(method, move || self.inner.request(method, params.clone())).await
+    }
+
+    /// Batches are sent once: the node may have executed part of a batch which failed midway.
+    async fn batch_request<'a, R>(&self, batch: BatchRequestBuilder<'a>) -> Result<BatchResponse<'a, R>, Error>
+    where
+        R: DeserializeOwned + fmt::Debug + 'a,
+    {
+        self.inner.batch_request(batch).await
+    }
+}
+
+/// Subscriptions are not retried, a subscription closed by the node has to be opened again.
+#[async_trait]
+impl<C> SubscriptionClientT for DeoxysClient<C>
+where
+    C: SubscriptionClientT + Send + Sync,
+{
+    async fn subscribe<'a, Notif, Params>(
+        &self,
+        subscribe_method: &'a str,
+        params: Params,
+        unsubscribe_method: &'a str,
+    ) -> Result<Subscription<Notif>, Error>
+    where
+        Params: ToRpcParams + Send,
+        Notif: DeserializeOwned,
+    {
+        self.inner.subscribe(subscribe_method, params, unsubscribe_method).await
+    }
+
+    async fn subscribe_to_method<'a, Notif>(&self, method: &'a str) -> Result<Subscription<Notif>, Error>
+    where
+        Notif: DeserializeOwned,
+    {
+        self.inner.subscribe_to_method(method).await
+    }
+}
+
+/// Builds a [DeoxysClient] over HTTP or WebSocket.
+#[derive(Clone, Debug)]
+pub struct DeoxysClientBuilder {
+    request_timeout: Duration,
+    retry_policy: RetryPolicy,
+}
+
+impl Default for DeoxysClientBuilder {
+    fn default() -> Self {
+        Self { request_timeout: DEFAULT_REQUEST_TIMEOUT, retry_policy: RetryPolicy::default() }
+    }
+}
+
+impl DeoxysClientBuilder {
+    /// How long each attempt of a request may take.
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn build_http(self, url: impl AsRef<str>) -> Result<DeoxysClient<HttpClient>, Error> {
+        let inner = HttpClientBuilder::default().request_timeout(self.request_timeout).build(url)?;
+        Ok(DeoxysClient { inner, retry_policy: self.retry_policy })
+    }
+
+    pub async fn build_ws(self, url: impl AsRef<str>) -> Result<DeoxysClient<WsClient>, Error> {
+        let inner = WsClientBuilder::default().request_timeout(self.request_timeout).build(url).await?;
+        Ok(DeoxysClient { inner, retry_policy: self.retry_policy })
+    }
+}
+
+/// Request parameters serialized once, so that they can be sent again on retries.
+#[derive(Clone)]
+struct RawParams(Option<Box<RawValue>>);
+
+impl RawParams {
+    fn new(params: impl ToRpcParams) -> Result<Self, Error> {
+        Ok(Self(params.to_rpc_params()?))
+    }
+}
+
+impl ToRpcParams for RawParams {
+    fn to_rpc_params(self) -> Result<Option<Box<RawValue>>, serde_json::Error> {
+        Ok(self.0)
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use jsonrpsee::core::Error;
+
+/// How the requests failing because of the transport are retried.
+///
+/// The delay between two attempts doubles after each of them, starting from `initial_backoff` and
+/// up to `max_backoff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of times a request is sent again after its first attempt.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 3, initial_backoff: Duration::from_millis(500), max_backoff: Duration::from_secs(10) }
+    }
+}
+
+impl RetryPolicy {
+    /// Requests are sent only once.
+    pub const NONE: Self = Self { max_retries: 0, initial_backoff: Duration::ZERO, max_backoff: Duration::ZERO };
+
+    /// The delay before the retry following the failed attempt `attempt`, counted from 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2u32.saturating_pow(attempt)).min(self.max_backoff)
+    }
+}
+
+/// Whether a request may succeed when sent again: the node could not be reached or did not answer
+/// in time. Errors returned by the node are final.
+pub(crate) fn is_retryable(err: &Error) -> bool {
+    matches!(err, Error::Transport(_) | Error::RequestTimeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_max() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(800));
+        assert_eq!(policy.backoff(4), Duration::from_secs(1));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn only_transport_errors_are_retried() {
+        assert!(is_retryable(&Error::RequestTimeout));
+        assert!(is_retryable(&Error::Transport(anyhow::anyhow!("connection reset"))));
+        assert!(!is_retryable(&Error::Custom("unknown block".to_string())));
+    }
+}
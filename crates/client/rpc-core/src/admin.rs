@@ -0,0 +1,60 @@
+//! Types of the `deoxysAdmin` namespace.
+
+use serde::{Deserialize, Serialize};
+use starknet_core::types::FieldElement;
+
+/// The rules of the execution policy.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionPolicyRules {
+    /// When set, only these classes can be executed. Note that this includes the account classes
+    /// validating the transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_classes: Option<Vec<FieldElement>>,
+    /// Classes which cannot be executed.
+    #[serde(default)]
+    pub denied_classes: Vec<FieldElement>,
+    /// Entry points which cannot be called.
+    #[serde(default)]
+    pub denied_entry_points: Vec<DeniedEntryPoint>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeniedEntryPoint {
+    pub class_hash: FieldElement,
+    pub entry_point_selector: FieldElement,
+}
+
+/// A block served by the gateway which could not be converted, located down to the offending
+/// field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversionError {
+    /// `None` if the block number itself is missing.
+    pub block_number: Option<u64>,
+    /// The index of the offending transaction in the block, if the field belongs to a transaction.
+    pub transaction_index: Option<usize>,
+    pub field: String,
+    pub reason: String,
+}
+
+/// The statistics of a column of the database, as estimated by RocksDB.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnStats {
+    pub column: String,
+    pub estimated_keys: u64,
+    /// The size of the files of the column on disk, once compressed.
+    pub disk_bytes: u64,
+    /// The size of the writes to the column not flushed to disk yet.
+    pub mem_table_bytes: u64,
+    pub l0_files: u64,
+    /// The bytes to rewrite to bring the levels of the column back to their target size.
+    pub pending_compaction_bytes: u64,
+}
+
+/// The statistics of the database, to spot the columns growing before the disk fills up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbStats {
+    pub columns: Vec<ColumnStats>,
+    /// The share of the blocks read which were found in the block cache, `None` unless the
+    /// statistics of the database are enabled.
+    pub block_cache_hit_rate: Option<f64>,
+}
@@ -0,0 +1,259 @@
+//! Types of the `deoxys` namespace.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::serde_as;
+use starknet_core::serde::unsigned_field_element::UfeHex;
+use starknet_core::types::{
+    BlockId, BroadcastedTransaction, EmittedEvent, FeeEstimate, FieldElement, FunctionCall, FunctionInvocation,
+    MsgFromL1, SimulationFlagForEstimateFee, StorageEntry,
+};
+
+/// A read request executed against a pinned block context.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BlockContextRequest {
+    Call { request: FunctionCall },
+    EstimateFee { request: Vec<BroadcastedTransaction>, simulation_flags: Vec<SimulationFlagForEstimateFee> },
+    EstimateMessageFee { message: MsgFromL1 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum BlockContextResponse {
+    Call(Vec<String>),
+    EstimateFee(Vec<FeeEstimate>),
+    EstimateMessageFee(FeeEstimate),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockContextError {
+    pub code: i32,
+    pub message: String,
+}
+
+/// The outcome of a single request of the batch: a failing request does not abort the others.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockContextResult {
+    Result(BlockContextResponse),
+    Error(BlockContextError),
+}
+
+/// The execution trace of a call.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CallTrace {
+    /// The call tree, along with the events and messages emitted by each call.
+    pub function_invocation: FunctionInvocation,
+    /// The panic data of the call, if it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    /// The readable form of `revert_reason`, if the node decodes revert reasons.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_reason_decoded: Option<String>,
+}
+
+/// A page of the storage entries of a contract.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContractStoragePage {
+    pub entries: Vec<StorageEntry>,
+    /// The key to start the next page from, if there are more entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_key: Option<FieldElement>,
+}
+
+/// A contract which got the requested class.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClassInstance {
+    pub contract_address: FieldElement,
+    /// The block at which the contract got the class, by deployment or class replacement.
+    pub block_number: u64,
+}
+
+/// A page of the contracts which got a class.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContractsByClassPage {
+    pub contracts: Vec<ClassInstance>,
+    /// The address to start the next page from, if there are more contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_address: Option<FieldElement>,
+}
+
+/// The events to decode: those of a block, or those of a transaction.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EventsSource {
+    BlockId(BlockId),
+    TransactionHash(FieldElement),
+}
+
+/// An emitted event, along with its name and fields decoded with the ABI of its emitter.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DecodedEvent {
+    #[serde(flatten)]
+    pub event: EmittedEvent,
+    /// The name of the event, if the ABI of its emitter describes it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The fields of the event by name, if they could be decoded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Value>,
+}
+
+/// A `u256` balance, split in its low and high 128 bits as it is stored by the token contracts.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct U256Balance {
+    pub low: FieldElement,
+    pub high: FieldElement,
+}
+
+/// The balances of an account in the fee tokens.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeeTokenBalances {
+    pub eth: U256Balance,
+    pub strk: U256Balance,
+}
+
+/// An overview of the state and activity of an account.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AccountSummary {
+    /// The block the summary was assembled at.
+    pub block_number: u64,
+    pub nonce: FieldElement,
+    pub class_hash: FieldElement,
+    pub balances: FeeTokenBalances,
+    /// The block at which the contract was deployed, unknown once the state history of the
+    /// contract has been pruned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_seen_block: Option<u64>,
+    /// The number of transactions sent by the account over the last blocks, if transactions are
+    /// indexed by sender.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recent_transaction_count: Option<u64>,
+}
+
+/// An ERC-20 transfer sent or received by the requested account.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TokenTransferEntry {
+    pub block_number: u64,
+    /// The index of the transaction which emitted the transfer in its block.
+    pub transaction_index: u64,
+    /// The index of the transfer event among the events of its transaction.
+    pub event_index: u64,
+    pub token_address: FieldElement,
+    pub from: FieldElement,
+    pub to: FieldElement,
+    /// The low 128 bits of the transferred amount.
+    pub amount_low: FieldElement,
+    /// The high 128 bits of the transferred amount.
+    pub amount_high: FieldElement,
+}
+
+/// A page of the ERC-20 transfers of an account.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TokenTransfersPage {
+    pub transfers: Vec<TokenTransferEntry>,
+    /// The token to pass to get the next page, if there are more transfers in the range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<String>,
+}
+
+/// A transaction sent by the requested account.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SenderTransaction {
+    pub transaction_hash: FieldElement,
+    pub block_number: u64,
+    /// The index of the transaction in its block.
+    pub transaction_index: u64,
+}
+
+/// A page of the transactions sent by an account.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TransactionsBySenderPage {
+    pub transactions: Vec<SenderTransaction>,
+    /// The token to pass to get the next page, if there are more transactions in the range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_token: Option<String>,
+}
+
+/// A node of a Merkle-Patricia proof.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MerkleNode {
+    Binary {
+        #[serde_as(as = "UfeHex")]
+        left: FieldElement,
+        #[serde_as(as = "UfeHex")]
+        right: FieldElement,
+    },
+    Edge {
+        #[serde_as(as = "UfeHex")]
+        child: FieldElement,
+        #[serde_as(as = "UfeHex")]
+        path: FieldElement,
+        length: usize,
+    },
+}
+
+/// The inclusion proof of a transaction in the transaction commitment of its block.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TransactionProof {
+    #[serde_as(as = "UfeHex")]
+    pub block_hash: FieldElement,
+    pub block_number: u64,
+    pub transaction_index: u64,
+    #[serde_as(as = "UfeHex")]
+    pub transaction_commitment: FieldElement,
+    /// The committed leaf: the hash of the transaction hash and of its signature.
+    #[serde_as(as = "UfeHex")]
+    pub leaf: FieldElement,
+    /// The proof nodes, from the root of the transaction trie down to the leaf.
+    pub proof: Vec<MerkleNode>,
+}
+
+/// The inclusion proof of an event in the event commitment of its block.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EventProof {
+    #[serde_as(as = "UfeHex")]
+    pub block_hash: FieldElement,
+    pub block_number: u64,
+    /// The index of the event among all the events emitted in the block.
+    pub event_index: u64,
+    #[serde_as(as = "UfeHex")]
+    pub event_commitment: FieldElement,
+    /// The committed leaf: the hash of the event.
+    #[serde_as(as = "UfeHex")]
+    pub leaf: FieldElement,
+    /// The proof nodes, from the root of the event trie down to the leaf.
+    pub proof: Vec<MerkleNode>,
+}
+
+/// An inclusive range of block numbers.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockRange {
+    pub first: u64,
+    pub last: u64,
+}
+
+impl BlockRange {
+    /// The range from `first` to `last`, `None` if it is empty.
+    pub fn new(first: u64, last: u64) -> Option<Self> {
+        (first <= last).then_some(Self { first, last })
+    }
+}
+
+/// The block ranges for which each kind of data is held locally.
+///
+/// Blocks synced from the starting block hold all of their data, while historical blocks
+/// backfilled below a trusted root only hold their header and body: their state was never
+/// replayed and their receipts cannot be served.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DataAvailability {
+    pub headers: Vec<BlockRange>,
+    pub bodies: Vec<BlockRange>,
+    pub state: Vec<BlockRange>,
+    pub receipts: Vec<BlockRange>,
+}
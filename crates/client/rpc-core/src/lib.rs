@@ -0,0 +1,361 @@
+//! Interface and types of the rpc of Deoxys
+//!
+//! The rpc interfaces are defined here as `jsonrpsee` traits, along with the types of their
+//! requests and responses, so that the node implementing them in `mc-rpc` and the typed client of
+//! `deoxys-rpc-client` share them without the client depending on the node.
+//!
+//! Starkware maintains [a description of the Starknet API](https://github.com/starkware-libs/starknet-specs/blob/master/api/starknet_api_openrpc.json)
+//! using the openRPC specification. With the `client` feature, the matching typed clients are
+//! generated along with the servers.
+
+mod admin;
+mod deoxys;
+mod pathfinder;
+mod sync;
+mod trace;
+mod types;
+
+use jsonrpsee::core::RpcResult;
+use jsonrpsee::proc_macros::rpc;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use starknet_core::serde::unsigned_field_element::UfeHex;
+use starknet_core::types::{
+    BlockHashAndNumber, BroadcastedDeclareTransaction, BroadcastedDeployAccountTransaction,
+    BroadcastedInvokeTransaction, BroadcastedTransaction, ContractClass, DeclareTransactionResult,
+    DeployAccountTransactionResult, EventFilterWithPage, EventsPage, FeeEstimate, FieldElement, FunctionCall,
+    InvokeTransactionResult, MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs,
+    MaybePendingStateUpdate, MsgFromL1, SimulatedTransaction, SimulationFlag, SimulationFlagForEstimateFee,
+    SyncStatusType, Transaction, TransactionReceiptWithBlockInfo, TransactionStatus, TransactionTraceWithHash,
+};
+
+pub use crate::admin::{ColumnStats, ConversionError, DbStats, DeniedEntryPoint, ExecutionPolicyRules};
+pub use crate::deoxys::{
+    AccountSummary, BlockContextError, BlockContextRequest, BlockContextResponse, BlockContextResult, BlockRange,
+    CallTrace, ClassInstance, ContractStoragePage, ContractsByClassPage, DataAvailability, DecodedEvent, EventProof,
+    EventsSource, FeeTokenBalances, MerkleNode, SenderTransaction, TokenTransferEntry, TokenTransfersPage,
+    TransactionProof, TransactionsBySenderPage, U256Balance,
+};
+pub use crate::pathfinder::{ContractData, EdgePath, GetProofOutput, TrieNode};
+pub use crate::sync::{BackfillRange, BlockHeader, SyncProgress, SyncStatus};
+pub use crate::trace::{
+    CairoProfilerCallTrace, CairoProfilerCallTraceNode, CairoProfilerCallType, CairoProfilerEntryPoint,
+    CairoProfilerEntryPointType, CairoProfilerExecutionResources, CairoProfilerL1Resources,
+    CairoProfilerTransactionTrace, CairoProfilerVmResources, TraceFormat, TransactionTraceOutput,
+};
+pub use crate::types::{DecodedRevertReason, ExtendedBlockId, WithDecodedRevertReason, WithDecodedRevertReasons};
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Felt(#[serde_as(as = "UfeHex")] pub FieldElement);
+
+/// Starknet write rpc interface.
+#[cfg_attr(not(feature = "client"), rpc(server, namespace = "starknet"))]
+#[cfg_attr(feature = "client", rpc(server, client, namespace = "starknet"))]
+pub trait StarknetWriteRpcApi {
+    /// Submit a new transaction to be added to the chain
+    #[method(name = "addInvokeTransaction")]
+    async fn add_invoke_transaction(
+        &self,
+        invoke_transaction: BroadcastedInvokeTransaction,
+    ) -> RpcResult<InvokeTransactionResult>;
+
+    /// Submit a new class declaration transaction
+    #[method(name = "addDeployAccountTransaction")]
+    async fn add_deploy_account_transaction(
+        &self,
+        deploy_account_transaction: BroadcastedDeployAccountTransaction,
+    ) -> RpcResult<DeployAccountTransactionResult>;
+
+    /// Submit a new deploy account transaction
+    #[method(name = "addDeclareTransaction")]
+    async fn add_declare_transaction(
+        &self,
+        declare_transaction: BroadcastedDeclareTransaction,
+    ) -> RpcResult<DeclareTransactionResult>;
+}
+
+#[cfg_attr(not(feature = "client"), rpc(server, namespace = "starknet"))]
+#[cfg_attr(feature = "client", rpc(server, client, namespace = "starknet"))]
+pub trait StarknetReadRpcApi {
+    /// Get the Version of the StarkNet JSON-RPC Specification Being Used
+    #[method(name = "specVersion")]
+    fn spec_version(&self) -> RpcResult<String>;
+
+    /// Get the most recent accepted block number
+    #[method(name = "blockNumber")]
+    fn block_number(&self) -> RpcResult<u64>;
+
+    // Get the most recent accepted block hash and number
+    #[method(name = "blockHashAndNumber")]
+    fn block_hash_and_number(&self) -> RpcResult<BlockHashAndNumber>;
+
+    /// Call a contract function at a given block id
+    #[method(name = "call")]
+    fn call(&self, request: FunctionCall, block_id: ExtendedBlockId) -> RpcResult<Vec<String>>;
+
+    /// Get the chain id
+    #[method(name = "chainId")]
+    fn chain_id(&self) -> RpcResult<Felt>;
+
+    /// Get the number of transactions in a block given a block id
+    #[method(name = "getBlockTransactionCount")]
+    fn get_block_transaction_count(&self, block_id: ExtendedBlockId) -> RpcResult<u128>;
+
+    /// Estimate the fee associated with transaction
+    #[method(name = "estimateFee")]
+    async fn estimate_fee(
+        &self,
+        request: Vec<BroadcastedTransaction>,
+        simulation_flags: Vec<SimulationFlagForEstimateFee>,
+        block_id: ExtendedBlockId,
+    ) -> RpcResult<Vec<FeeEstimate>>;
+
+    /// Estimate the L2 fee of a message sent on L1
+    #[method(name = "estimateMessageFee")]
+    async fn estimate_message_fee(&self, message: MsgFromL1, block_id: ExtendedBlockId) -> RpcResult<FeeEstimate>;
+
+    /// Get block information with full transactions and receipts given the block id
+    #[method(name = "getBlockWithReceipts")]
+    async fn get_block_with_receipts(
+        &self,
+        block_id: ExtendedBlockId,
+    ) -> RpcResult<WithDecodedRevertReasons<MaybePendingBlockWithReceipts>>;
+
+    /// Get block information with transaction hashes given the block id
+    #[method(name = "getBlockWithTxHashes")]
+    fn get_block_with_tx_hashes(&self, block_id: ExtendedBlockId) -> RpcResult<MaybePendingBlockWithTxHashes>;
+
+    /// Get block information with full transactions given the block id
+    #[method(name = "getBlockWithTxs")]
+    fn get_block_with_txs(&self, block_id: ExtendedBlockId) -> RpcResult<MaybePendingBlockWithTxs>;
+
+    /// Get the contract class at a given contract address for a given block id
+    #[method(name = "getClassAt")]
+    fn get_class_at(&self, block_id: ExtendedBlockId, contract_address: FieldElement) -> RpcResult<ContractClass>;
+
+    /// Get the contract class hash in the given block for the contract deployed at the given
+    /// address
+    #[method(name = "getClassHashAt")]
+    fn get_class_hash_at(&self, block_id: ExtendedBlockId, contract_address: FieldElement) -> RpcResult<Felt>;
+
+    /// Get the contract class definition in the given block associated with the given hash
+    #[method(name = "getClass")]
+    fn get_class(&self, block_id: ExtendedBlockId, class_hash: FieldElement) -> RpcResult<ContractClass>;
+
+    /// Returns all events matching the given filter
+    #[method(name = "getEvents")]
+    async fn get_events(&self, filter: EventFilterWithPage) -> RpcResult<EventsPage>;
+
+    /// Get the nonce associated with the given address at the given block
+    #[method(name = "getNonce")]
+    fn get_nonce(&self, block_id: ExtendedBlockId, contract_address: FieldElement) -> RpcResult<Felt>;
+
+    /// Get the value of the storage at the given address and key, at the given block id
+    #[method(name = "getStorageAt")]
+    fn get_storage_at(
+        &self,
+        contract_address: FieldElement,
+        key: FieldElement,
+        block_id: ExtendedBlockId,
+    ) -> RpcResult<Felt>;
+
+    /// Get the details of a transaction by a given block id and index
+    #[method(name = "getTransactionByBlockIdAndIndex")]
+    fn get_transaction_by_block_id_and_index(&self, block_id: ExtendedBlockId, index: u64) -> RpcResult<Transaction>;
+
+    /// Returns the information about a transaction by transaction hash.
+    #[method(name = "getTransactionByHash")]
+    fn get_transaction_by_hash(&self, transaction_hash: FieldElement) -> RpcResult<Transaction>;
+
+    /// Returns the receipt of a transaction by transaction hash.
+    #[method(name = "getTransactionReceipt")]
+    async fn get_transaction_receipt(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> RpcResult<WithDecodedRevertReason<TransactionReceiptWithBlockInfo>>;
+
+    /// Gets the Transaction Status, Including Mempool Status and Execution Details
+    #[method(name = "getTransactionStatus")]
+    fn get_transaction_status(&self, transaction_hash: FieldElement) -> RpcResult<TransactionStatus>;
+
+    /// Get an object about the sync status, or false if the node is not syncing
+    #[method(name = "syncing")]
+    async fn syncing(&self) -> RpcResult<SyncStatusType>;
+
+    /// Get the information about the result of executing the requested block
+    #[method(name = "getStateUpdate")]
+    fn get_state_update(&self, block_id: ExtendedBlockId) -> RpcResult<MaybePendingStateUpdate>;
+}
+
+#[cfg_attr(not(feature = "client"), rpc(server, namespace = "starknet"))]
+#[cfg_attr(feature = "client", rpc(server, client, namespace = "starknet"))]
+pub trait StarknetTraceRpcApi {
+    /// Returns the execution trace of a transaction by simulating it in the runtime.
+    #[method(name = "simulateTransactions")]
+    async fn simulate_transactions(
+        &self,
+        block_id: ExtendedBlockId,
+        transactions: Vec<BroadcastedTransaction>,
+        simulation_flags: Vec<SimulationFlag>,
+    ) -> RpcResult<Vec<WithDecodedRevertReason<SimulatedTransaction>>>;
+
+    #[method(name = "traceBlockTransactions")]
+    /// Returns the execution traces of all transactions included in the given block
+    async fn trace_block_transactions(
+        &self,
+        block_id: ExtendedBlockId,
+    ) -> RpcResult<Vec<WithDecodedRevertReason<TransactionTraceWithHash>>>;
+
+    #[method(name = "traceTransaction")]
+    /// Returns the execution trace of a transaction, in the format of the specification unless
+    /// another one is requested
+    async fn trace_transaction(
+        &self,
+        transaction_hash: FieldElement,
+        format: Option<TraceFormat>,
+    ) -> RpcResult<TransactionTraceOutput>;
+}
+
+/// Deoxys-specific rpc interface, extending the Starknet specification.
+#[cfg_attr(not(feature = "client"), rpc(server, namespace = "deoxys"))]
+#[cfg_attr(feature = "client", rpc(server, client, namespace = "deoxys"))]
+pub trait DeoxysRpcApi {
+    /// Run a batch of calls and fee estimations against a single resolved block context
+    #[method(name = "withBlockContext")]
+    fn with_block_context(
+        &self,
+        block_id: ExtendedBlockId,
+        requests: Vec<BlockContextRequest>,
+    ) -> RpcResult<Vec<BlockContextResult>>;
+
+    /// Estimate the fees of a sequence of transactions from one account, each one executed on top
+    /// of the previous ones with auto-incremented nonces
+    #[method(name = "estimateFeeBulk")]
+    fn estimate_fee_bulk(
+        &self,
+        block_id: ExtendedBlockId,
+        sender_address: FieldElement,
+        transactions: Vec<BroadcastedTransaction>,
+        simulation_flags: Vec<SimulationFlagForEstimateFee>,
+    ) -> RpcResult<Vec<FeeEstimate>>;
+
+    /// Trace a call without creating a transaction, returning its whole call tree and events
+    #[method(name = "traceCall")]
+    fn trace_call(&self, request: FunctionCall, block_id: ExtendedBlockId) -> RpcResult<CallTrace>;
+
+    /// Iterate over the storage entries of a contract, in key order, one page at a time
+    #[method(name = "getContractStorage")]
+    fn get_contract_storage(
+        &self,
+        contract_address: FieldElement,
+        block_id: ExtendedBlockId,
+        start_key: Option<FieldElement>,
+        limit: u64,
+    ) -> RpcResult<ContractStoragePage>;
+
+    /// Enumerate the contracts deployed with a class or which replaced their class with it, one
+    /// page at a time
+    #[method(name = "getContractsByClass")]
+    fn get_contracts_by_class(
+        &self,
+        class_hash: FieldElement,
+        start_address: Option<FieldElement>,
+        limit: u64,
+    ) -> RpcResult<ContractsByClassPage>;
+
+    /// Decode the events of a block or of a transaction with the ABI of the contracts which emitted
+    /// them
+    #[method(name = "decodeEvents")]
+    fn decode_events(&self, source: EventsSource) -> RpcResult<Vec<DecodedEvent>>;
+
+    /// Get the nonce, class hash, fee token balances and activity of an account in a single request
+    #[method(name = "getAccountSummary")]
+    fn get_account_summary(&self, contract_address: FieldElement) -> RpcResult<AccountSummary>;
+
+    /// Get the ERC-20 transfers sent or received by an account within a range of blocks, one page
+    /// at a time
+    #[method(name = "getTokenTransfers")]
+    fn get_token_transfers(
+        &self,
+        account: FieldElement,
+        token_address: Option<FieldElement>,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        continuation_token: Option<String>,
+        limit: u64,
+    ) -> RpcResult<TokenTransfersPage>;
+
+    /// Enumerate the transactions sent by an account within a range of blocks, one page at a time
+    #[method(name = "getTransactionsBySender")]
+    fn get_transactions_by_sender(
+        &self,
+        sender_address: FieldElement,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        continuation_token: Option<String>,
+        limit: u64,
+    ) -> RpcResult<TransactionsBySenderPage>;
+
+    /// Get the inclusion proof of a transaction against the transaction commitment of its block
+    #[method(name = "getReceiptProof")]
+    fn get_receipt_proof(&self, transaction_hash: FieldElement) -> RpcResult<TransactionProof>;
+
+    /// Get the inclusion proof of an event against the event commitment of its block
+    #[method(name = "getEventProof")]
+    fn get_event_proof(&self, transaction_hash: FieldElement, event_index: u64) -> RpcResult<EventProof>;
+
+    /// Get the number and hash of a block, including the blocks of the header chain fetched ahead
+    /// of the full blocks in headers-first sync
+    #[method(name = "getBlockHeader")]
+    fn get_block_header(&self, block_number: u64) -> RpcResult<BlockHeader>;
+
+    /// Get the range of historical blocks backfilled so far, when syncing from a trusted root
+    #[method(name = "getBackfillRange")]
+    fn get_backfill_range(&self) -> RpcResult<Option<BackfillRange>>;
+
+    /// Get the block ranges for which headers, bodies, state and receipts are held locally
+    #[method(name = "getDataAvailability")]
+    fn get_data_availability(&self) -> RpcResult<DataAvailability>;
+
+    /// Get the progress of the sync: the last applied and highest known blocks, the speed of the
+    /// sync and the estimated time left to catch up
+    #[method(name = "getSyncProgress")]
+    fn get_sync_progress(&self) -> RpcResult<SyncProgress>;
+}
+
+/// Deoxys administration rpc interface, only served when unsafe rpc methods are enabled.
+#[cfg_attr(not(feature = "client"), rpc(server, namespace = "deoxysAdmin"))]
+#[cfg_attr(feature = "client", rpc(server, client, namespace = "deoxysAdmin"))]
+pub trait DeoxysAdminRpcApi {
+    /// Get the classes and entry points the node refuses to execute
+    #[method(name = "getExecutionPolicy")]
+    fn get_execution_policy(&self) -> RpcResult<ExecutionPolicyRules>;
+
+    /// Replace the classes and entry points the node refuses to execute, for the executions started
+    /// from now on. The policy is kept across restarts
+    #[method(name = "setExecutionPolicy")]
+    fn set_execution_policy(&self, rules: ExecutionPolicyRules) -> RpcResult<()>;
+
+    /// Get the last blocks the sync failed to convert, located down to the offending field
+    #[method(name = "getConversionErrors")]
+    fn get_conversion_errors(&self) -> RpcResult<Vec<ConversionError>>;
+
+    /// Get the size, number of keys and compaction backlog of every column of the database
+    #[method(name = "dbStats")]
+    fn db_stats(&self) -> RpcResult<DbStats>;
+}
+
+/// Pathfinder compatible rpc interface, for the clients built against the extensions of pathfinder.
+#[cfg_attr(not(feature = "client"), rpc(server, namespace = "pathfinder"))]
+#[cfg_attr(feature = "client", rpc(server, client, namespace = "pathfinder"))]
+pub trait PathfinderRpcApi {
+    /// Get the Merkle-Patricia proof of storage values of a contract against the global state root
+    #[method(name = "getProof")]
+    fn get_proof(
+        &self,
+        block_id: ExtendedBlockId,
+        contract_address: FieldElement,
+        keys: Vec<FieldElement>,
+    ) -> RpcResult<GetProofOutput>;
+}
@@ -0,0 +1,66 @@
+//! Types of the `pathfinder` namespace.
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use starknet_core::serde::unsigned_field_element::UfeHex;
+use starknet_core::types::FieldElement;
+
+/// The path of an edge node, `len` bits long.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EdgePath {
+    #[serde_as(as = "UfeHex")]
+    pub value: FieldElement,
+    pub len: usize,
+}
+
+/// A node of a Merkle-Patricia proof, as served by pathfinder.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TrieNode {
+    Binary {
+        #[serde_as(as = "UfeHex")]
+        left: FieldElement,
+        #[serde_as(as = "UfeHex")]
+        right: FieldElement,
+    },
+    Edge {
+        #[serde_as(as = "UfeHex")]
+        child: FieldElement,
+        path: EdgePath,
+    },
+}
+
+/// The contract leaf of the contracts trie and the proofs of the requested storage keys.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContractData {
+    #[serde_as(as = "UfeHex")]
+    pub class_hash: FieldElement,
+    #[serde_as(as = "UfeHex")]
+    pub nonce: FieldElement,
+    /// The root of the storage trie of the contract.
+    #[serde_as(as = "UfeHex")]
+    pub root: FieldElement,
+    #[serde_as(as = "UfeHex")]
+    pub contract_state_hash_version: FieldElement,
+    /// The proof nodes of every requested key, in the order of the request.
+    pub storage_proofs: Vec<Vec<TrieNode>>,
+}
+
+/// The proof of the storage values of a contract against the global state root of a block.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetProofOutput {
+    #[serde_as(as = "UfeHex")]
+    pub state_commitment: FieldElement,
+    /// The root of the classes trie, hashed with the root of the contracts trie into the state
+    /// commitment.
+    #[serde_as(as = "UfeHex")]
+    pub class_commitment: FieldElement,
+    /// The proof nodes of the contract, from the root of the contracts trie.
+    pub contract_proof: Vec<TrieNode>,
+    /// `None` if the contract is not deployed, in which case `contract_proof` proves its absence.
+    pub contract_data: Option<ContractData>,
+}
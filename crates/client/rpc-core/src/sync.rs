@@ -0,0 +1,47 @@
+//! Types reporting the state of the sync.
+
+use serde::{Deserialize, Serialize};
+use starknet_core::types::FieldElement;
+
+/// The header of a block, as returned by the feeder gateway.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub block_number: u64,
+    pub block_hash: FieldElement,
+}
+
+/// The historical blocks backfilled so far, below the block the node started syncing from.
+///
+/// Blocks are backfilled from the most recent to the oldest, so the backfilled blocks always form
+/// the contiguous range `lowest..end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackfillRange {
+    /// The oldest backfilled block.
+    pub lowest: u64,
+    /// The block the node started syncing from, which the backfill stops short of.
+    pub end: u64,
+}
+
+/// Whether the state synced so far is verified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    SyncVerifiedState,
+    SyncUnverifiedState,
+    SyncPendingState,
+}
+
+/// A snapshot of the progress of the sync.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SyncProgress {
+    /// The last block applied by the sync.
+    pub current_block: u64,
+    /// The highest block known to the feeder gateway.
+    pub highest_block: u64,
+    /// The number of blocks applied per second, averaged over the last minute.
+    pub blocks_per_second: f64,
+    /// The estimated number of seconds left to reach the highest block, unknown while no block is
+    /// being applied.
+    pub eta_seconds: Option<u64>,
+    pub status: SyncStatus,
+}
@@ -0,0 +1,106 @@
+//! Types of the traces, in the format of the specification or in the one read by `cairo-profiler`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use starknet_core::types::{FieldElement, TransactionTraceWithHash};
+
+use crate::WithDecodedRevertReason;
+
+/// The format of a transaction trace.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceFormat {
+    /// The trace defined by the Starknet specification.
+    #[default]
+    Starknet,
+    /// The call traces read by `cairo-profiler`, with the resources used by every call.
+    CairoProfiler,
+}
+
+/// A transaction trace, in the requested [TraceFormat].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum TransactionTraceOutput {
+    Starknet(WithDecodedRevertReason<TransactionTraceWithHash>),
+    CairoProfiler(CairoProfilerTransactionTrace),
+}
+
+/// The trace of a transaction, with one `cairo-profiler` trace per invocation.
+///
+/// Each invocation can be written to its own file and given as is to `cairo-profiler`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CairoProfilerTransactionTrace {
+    pub transaction_hash: FieldElement,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validate_invocation: Option<CairoProfilerCallTrace>,
+    /// The execution of the transaction: the `__execute__` call, the constructor of a deployed
+    /// account or the L1 handler. Absent for declare transactions and reverted transactions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execute_invocation: Option<CairoProfilerCallTrace>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_transfer_invocation: Option<CairoProfilerCallTrace>,
+}
+
+/// A call and its nested calls, along with the resources they used.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CairoProfilerCallTrace {
+    pub entry_point: CairoProfilerEntryPoint,
+    /// The resources used by the call, nested calls included.
+    pub cumulative_resources: CairoProfilerExecutionResources,
+    pub used_l1_resources: CairoProfilerL1Resources,
+    pub nested_calls: Vec<CairoProfilerCallTraceNode>,
+    /// The VM trace is not recorded by the node, so this is always `null`.
+    pub vm_trace: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum CairoProfilerCallTraceNode {
+    EntryPointCall(Box<CairoProfilerCallTrace>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CairoProfilerEntryPoint {
+    pub class_hash: Option<FieldElement>,
+    pub entry_point_type: CairoProfilerEntryPointType,
+    pub entry_point_selector: FieldElement,
+    pub contract_address: FieldElement,
+    pub call_type: CairoProfilerCallType,
+    /// Contract and function names are left to the profiler, which resolves them from the ABI.
+    pub contract_name: Option<String>,
+    pub function_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum CairoProfilerEntryPointType {
+    Constructor,
+    External,
+    L1Handler,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum CairoProfilerCallType {
+    Call,
+    Delegate,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CairoProfilerExecutionResources {
+    pub vm_resources: CairoProfilerVmResources,
+    pub gas_consumed: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CairoProfilerVmResources {
+    pub n_steps: usize,
+    pub n_memory_holes: usize,
+    pub builtin_instance_counter: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CairoProfilerL1Resources {
+    /// The payload lengths of the messages sent to L1 by the call and its nested calls, in call
+    /// order.
+    pub l2_l1_message_sizes: Vec<usize>,
+}
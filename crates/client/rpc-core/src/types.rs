@@ -0,0 +1,98 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use starknet_core::types::{BlockId, FieldElement};
+
+/// A [BlockId] which may also be the `l1_accepted` tag, designating the last block covered by a
+/// state update verified on L1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtendedBlockId {
+    L1Accepted,
+    Starknet(BlockId),
+}
+
+impl From<BlockId> for ExtendedBlockId {
+    fn from(block_id: BlockId) -> Self {
+        ExtendedBlockId::Starknet(block_id)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExtendedBlockId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        enum L1AcceptedTag {
+            L1Accepted,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            L1Accepted(L1AcceptedTag),
+            Starknet(BlockId),
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::L1Accepted(_) => ExtendedBlockId::L1Accepted,
+            Repr::Starknet(block_id) => ExtendedBlockId::Starknet(block_id),
+        })
+    }
+}
+
+impl Serialize for ExtendedBlockId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ExtendedBlockId::L1Accepted => serializer.serialize_str("l1_accepted"),
+            ExtendedBlockId::Starknet(block_id) => block_id.serialize(serializer),
+        }
+    }
+}
+
+/// A response of the RPC specification, along with the readable form of the revert reason it holds.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WithDecodedRevertReason<T> {
+    #[serde(flatten)]
+    pub inner: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_reason_decoded: Option<String>,
+}
+
+/// The readable form of the revert reason of a transaction of a block.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DecodedRevertReason {
+    pub transaction_hash: FieldElement,
+    pub revert_reason_decoded: String,
+}
+
+/// A response of the RPC specification, along with the readable form of the revert reasons of the
+/// transactions it holds.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WithDecodedRevertReasons<T> {
+    #[serde(flatten)]
+    pub inner: T,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revert_reasons_decoded: Vec<DecodedRevertReason>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use starknet_core::types::BlockTag;
+
+    use super::*;
+
+    #[rstest]
+    #[case(r#""l1_accepted""#, ExtendedBlockId::L1Accepted)]
+    #[case(r#""latest""#, ExtendedBlockId::Starknet(BlockId::Tag(BlockTag::Latest)))]
+    #[case(r#"{"block_number":3}"#, ExtendedBlockId::Starknet(BlockId::Number(3)))]
+    fn extended_block_id_deserializes(#[case] json: &str, #[case] expected: ExtendedBlockId) {
+        assert_eq!(expected, serde_json::from_str::<ExtendedBlockId>(json).unwrap());
+    }
+
+    #[rstest]
+    #[case(ExtendedBlockId::L1Accepted)]
+    #[case(ExtendedBlockId::Starknet(BlockId::Tag(BlockTag::Pending)))]
+    #[case(ExtendedBlockId::Starknet(BlockId::Number(3)))]
+    fn extended_block_id_roundtrips(#[case] block_id: ExtendedBlockId) {
+        let json = serde_json::to_string(&block_id).unwrap();
+        assert_eq!(block_id, serde_json::from_str::<ExtendedBlockId>(&json).unwrap());
+    }
+}
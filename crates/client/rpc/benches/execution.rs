@@ -0,0 +1,94 @@
+//! Contract execution benchmarks.
+//!
+//! Executes the entry points of the test contract bundled with blockifier, the way the RPC executes
+//! calls, against an in-memory state: the database of the node is not needed, so that the cost of
+//! the execution itself is what gets timed.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use blockifier::context::{BlockContext, TransactionContext};
+use blockifier::execution::contract_class::{ContractClass, ContractClassV0};
+use blockifier::execution::entry_point::{CallEntryPoint, CallType, EntryPointExecutionContext};
+use blockifier::state::cached_state::{CachedState, GlobalContractCache};
+use blockifier::test_utils::dict_state_reader::DictStateReader;
+use blockifier::test_utils::TEST_CONTRACT_CAIRO0_PATH;
+use blockifier::transaction::objects::{DeprecatedTransactionInfo, TransactionInfo};
+use blockifier::versioned_constants::VersionedConstants;
+use cairo_vm::vm::runners::cairo_runner::ExecutionResources;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use mp_felt::Felt252Wrapper;
+use starknet_api::core::{ClassHash, ContractAddress, EntryPointSelector, PatriciaKey};
+use starknet_api::deprecated_contract_class::EntryPointType;
+use starknet_api::hash::StarkFelt;
+use starknet_api::transaction::Calldata;
+use starknet_core::utils::get_selector_from_name;
+
+const TEST_CLASS_HASH: u64 = 0x110;
+const TEST_CONTRACT_ADDRESS: u64 = 0x100;
+
+fn contract_address(address: u64) -> ContractAddress {
+    ContractAddress(PatriciaKey::try_from(StarkFelt::from(address)).expect("Failed to build the contract address"))
+}
+
+/// A state with the test contract deployed.
+fn test_state() -> CachedState<DictStateReader> {
+    let class_hash = ClassHash(StarkFelt::from(TEST_CLASS_HASH));
+    let class = ContractClassV0::from_file(TEST_CONTRACT_CAIRO0_PATH);
+    let state_reader = DictStateReader {
+        address_to_class_hash: HashMap::from([(contract_address(TEST_CONTRACT_ADDRESS), class_hash)]),
+        class_hash_to_class: HashMap::from([(class_hash, ContractClass::V0(class))]),
+        ..Default::default()
+    };
+    CachedState::new(state_reader, GlobalContractCache::new(10))
+}
+
+fn entry_point(name: &str, calldata: &[u64]) -> CallEntryPoint {
+    let selector = get_selector_from_name(name).expect("Failed to compute the entry point selector");
+    CallEntryPoint {
+        class_hash: None,
+        code_address: None,
+        entry_point_type: EntryPointType::External,
+        entry_point_selector: EntryPointSelector(Felt252Wrapper::from(selector).into()),
+        calldata: Calldata(Arc::new(calldata.iter().copied().map(StarkFelt::from).collect())),
+        storage_address: contract_address(TEST_CONTRACT_ADDRESS),
+        caller_address: ContractAddress::default(),
+        call_type: CallType::Call,
+        initial_gas: VersionedConstants::latest_constants().tx_initial_gas(),
+    }
+}
+
+/// Executes `entry_point` the way the RPC executes calls.
+fn execute(entry_point: CallEntryPoint, state: &mut CachedState<DictStateReader>, block_context: &BlockContext) {
+    let mut resources = ExecutionResources::default();
+    let mut context = EntryPointExecutionContext::new_invoke(
+        Arc::new(TransactionContext {
+            block_context: block_context.clone(),
+            tx_info: TransactionInfo::Deprecated(DeprecatedTransactionInfo::default()),
+        }),
+        false,
+    )
+    .expect("Failed to create the execution context");
+    let call_info = entry_point.execute(state, &mut resources, &mut context).expect("Failed to execute the call");
+    assert!(!call_info.execution.failed, "the benched call failed");
+}
+
+fn execution(c: &mut Criterion) {
+    let block_context = BlockContext::create_for_account_testing();
+    let mut group = c.benchmark_group("execution");
+
+    for (name, calldata) in [("return_result", &[42][..]), ("test_storage_read_write", &[0x5, 42][..])] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                test_state,
+                |mut state| execute(entry_point(name, calldata), &mut state, &block_context),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, execution);
+criterion_main!(benches);
@@ -1,5 +1,6 @@
 use jsonrpsee::types::error::{CallError, ErrorObject};
 use pallet_starknet_runtime_api::StarknetTransactionExecutionError;
+use serde_json::Value;
 use starknet_core::types::StarknetError;
 
 // Comes from the RPC Spec:
@@ -70,6 +71,88 @@ pub enum StarknetRpcApiError {
     UnimplementedMethod = 501,
     #[error("Too many storage keys requested")]
     ProofLimitExceeded = 10000,
+    #[error("Historical data not yet backfilled")]
+    HistoricalDataNotBackfilled = 10001,
+    #[error("The transactions of the sequence are not all sent by the same account")]
+    MixedSenders = 10002,
+    #[error("Execution denied by the execution policy of the node")]
+    ExecutionDenied = 10003,
+    #[error("Proof is not available at the requested block")]
+    ProofMissing = 10004,
+    #[error("The state at the requested block has been pruned")]
+    StatePruned = 10005,
+}
+
+impl StarknetRpcApiError {
+    /// The name of the error in the specification, sent in the `data` field of the errors so that
+    /// clients can match on it without relying on the message.
+    pub fn name(self) -> &'static str {
+        match self {
+            StarknetRpcApiError::FailedToReceiveTxn => "FAILED_TO_RECEIVE_TXN",
+            StarknetRpcApiError::ContractNotFound => "CONTRACT_NOT_FOUND",
+            StarknetRpcApiError::BlockNotFound => "BLOCK_NOT_FOUND",
+            StarknetRpcApiError::InvalidTxnHash => "INVALID_TXN_HASH",
+            StarknetRpcApiError::InvalidBlockHash => "INVALID_BLOCK_HASH",
+            StarknetRpcApiError::InvalidTxnIndex => "INVALID_TXN_INDEX",
+            StarknetRpcApiError::ClassHashNotFound => "CLASS_HASH_NOT_FOUND",
+            StarknetRpcApiError::TxnHashNotFound => "TXN_HASH_NOT_FOUND",
+            StarknetRpcApiError::PageSizeTooBig => "PAGE_SIZE_TOO_BIG",
+            StarknetRpcApiError::NoBlocks => "NO_BLOCKS",
+            StarknetRpcApiError::InvalidContinuationToken => "INVALID_CONTINUATION_TOKEN",
+            StarknetRpcApiError::TooManyKeysInFilter => "TOO_MANY_KEYS_IN_FILTER",
+            StarknetRpcApiError::FailedToFetchPendingTransactions => "FAILED_TO_FETCH_PENDING_TRANSACTIONS",
+            StarknetRpcApiError::ContractError => "CONTRACT_ERROR",
+            StarknetRpcApiError::TxnExecutionError => "TRANSACTION_EXECUTION_ERROR",
+            StarknetRpcApiError::InvalidContractClass => "INVALID_CONTRACT_CLASS",
+            StarknetRpcApiError::ClassAlreadyDeclared => "CLASS_ALREADY_DECLARED",
+            StarknetRpcApiError::InvalidTxnNonce => "INVALID_TRANSACTION_NONCE",
+            StarknetRpcApiError::InsufficientMaxFee => "INSUFFICIENT_MAX_FEE",
+            StarknetRpcApiError::InsufficientAccountBalance => "INSUFFICIENT_ACCOUNT_BALANCE",
+            StarknetRpcApiError::ValidationFailure => "VALIDATION_FAILURE",
+            StarknetRpcApiError::CompilationFailed => "COMPILATION_FAILED",
+            StarknetRpcApiError::ContractClassSizeTooLarge => "CONTRACT_CLASS_SIZE_IS_TOO_LARGE",
+            StarknetRpcApiError::NonAccount => "NON_ACCOUNT",
+            StarknetRpcApiError::DuplicateTxn => "DUPLICATE_TX",
+            StarknetRpcApiError::CompiledClassHashMismatch => "COMPILED_CLASS_HASH_MISMATCH",
+            StarknetRpcApiError::UnsupportedTxnVersion => "UNSUPPORTED_TX_VERSION",
+            StarknetRpcApiError::UnsupportedContractClassVersion => "UNSUPPORTED_CONTRACT_CLASS_VERSION",
+            StarknetRpcApiError::ErrUnexpectedError => "UNEXPECTED_ERROR",
+            StarknetRpcApiError::InternalServerError => "INTERNAL_SERVER_ERROR",
+            StarknetRpcApiError::UnimplementedMethod => "UNIMPLEMENTED_METHOD",
+            StarknetRpcApiError::ProofLimitExceeded => "PROOF_LIMIT_EXCEEDED",
+            StarknetRpcApiError::HistoricalDataNotBackfilled => "HISTORICAL_DATA_NOT_BACKFILLED",
+            StarknetRpcApiError::MixedSenders => "MIXED_SENDERS",
+            StarknetRpcApiError::ExecutionDenied => "EXECUTION_DENIED",
+            StarknetRpcApiError::ProofMissing => "PROOF_MISSING",
+            StarknetRpcApiError::StatePruned => "STATE_PRUNED",
+        }
+    }
+
+    /// Builds the rpc error, with `context` added to the `data` field next to the name of the
+    /// error.
+    ///
+    /// `context` should be an object, such as `json!({ "contract_address": address })`: it is
+    /// otherwise sent under a `details` key. The errors whose data is defined by the specification
+    /// (`CONTRACT_ERROR`, `TRANSACTION_EXECUTION_ERROR`) have `context` sent as is instead.
+    pub fn with_data(self, context: Value) -> jsonrpsee::core::Error {
+        let data = match (self, context) {
+            (StarknetRpcApiError::ContractError | StarknetRpcApiError::TxnExecutionError, Value::Null) => None,
+            (StarknetRpcApiError::ContractError | StarknetRpcApiError::TxnExecutionError, context) => Some(context),
+            (_, context) => {
+                let mut data = serde_json::Map::new();
+                data.insert("error".to_string(), self.name().into());
+                match context {
+                    Value::Object(context) => data.extend(context),
+                    Value::Null => {}
+                    context => {
+                        data.insert("details".to_string(), context);
+                    }
+                }
+                Some(Value::Object(data))
+            }
+        };
+        jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(self as i32, self.to_string(), data)))
+    }
 }
 
 impl From<StarknetTransactionExecutionError> for StarknetRpcApiError {
@@ -86,7 +169,7 @@ impl From<StarknetTransactionExecutionError> for StarknetRpcApiError {
 
 impl From<StarknetRpcApiError> for jsonrpsee::core::Error {
     fn from(err: StarknetRpcApiError) -> Self {
-        jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(err as i32, err.to_string(), None::<()>)))
+        err.with_data(Value::Null)
     }
 }
 
@@ -122,3 +205,48 @@ impl From<StarknetError> for StarknetRpcApiError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_with_data() {
+        let data = |error: jsonrpsee::core::Error| match error {
+            jsonrpsee::core::Error::Call(CallError::Custom(error)) => {
+                serde_json::from_str::<Value>(error.data().expect("error data").get()).unwrap()
+            }
+            error => panic!("unexpected error {error:?}"),
+        };
+
+        assert_eq!(data(StarknetRpcApiError::BlockNotFound.into()), json!({ "error": "BLOCK_NOT_FOUND" }));
+        assert_eq!(
+            data(StarknetRpcApiError::ContractNotFound.with_data(json!({ "contract_address": "0x1" }))),
+            json!({ "error": "CONTRACT_NOT_FOUND", "contract_address": "0x1" })
+        );
+        assert_eq!(
+            data(StarknetRpcApiError::BlockNotFound.with_data(json!("pruned"))),
+            json!({ "error": "BLOCK_NOT_FOUND", "details": "pruned" })
+        );
+    }
+
+    #[test]
+    fn test_with_data_keeps_spec_data() {
+        let error = |error: jsonrpsee::core::Error| match error {
+            jsonrpsee::core::Error::Call(CallError::Custom(error)) => error,
+            error => panic!("unexpected error {error:?}"),
+        };
+        let data = |error: ErrorObject| error.data().map(|data| serde_json::from_str::<Value>(data.get()).unwrap());
+
+        let revert_error = json!({ "revert_error": "reverted" });
+        assert_eq!(data(error(StarknetRpcApiError::ContractError.with_data(revert_error.clone()))), Some(revert_error));
+        let execution_error = json!({ "transaction_index": 1, "execution_error": "reverted" });
+        assert_eq!(
+            data(error(StarknetRpcApiError::TxnExecutionError.with_data(execution_error.clone()))),
+            Some(execution_error)
+        );
+        assert_eq!(data(error(StarknetRpcApiError::ContractError.into())), None);
+    }
+}
@@ -1,4 +1,3 @@
-use mc_sync::l2::get_pending_block;
 use mp_block::DeoxysBlock;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
@@ -58,7 +57,8 @@ where
         let (block_hash, block_number) = if block_id == BlockId::Tag(BlockTag::Pending) {
             (None, None)
         } else {
-            (Some(starknet_block.header().hash::<H>().0), Some(starknet_block.header().block_number))
+            let chain_id = self.chain_id().map_err(|_| StarknetRpcApiError::InternalServerError)?;
+            (Some(starknet_block.header().hash::<H>(chain_id.0.into()).0), Some(starknet_block.header().block_number))
         };
 
         let emitted_events = tx_hash_and_events
@@ -76,8 +76,8 @@ where
     }
 
     fn get_block_txs_hashes(&self, starknet_block: &DeoxysBlock) -> Result<Vec<FieldElement>, StarknetRpcApiError> {
-        let block_hash = starknet_block.header().hash::<H>();
         let chain_id = self.chain_id().unwrap();
+        let block_hash = starknet_block.header().hash::<H>(chain_id.0.into());
 
         // get txs hashes from cache or compute them
         let block_txs_hashes: Vec<_> = if let Some(tx_hashes) = self.get_cached_transaction_hashes(block_hash.into()) {
@@ -148,7 +148,7 @@ where
                 })?,
             )
         } else {
-            match get_pending_block() {
+            match self.pending_block() {
                 Some(block) => Ok(block),
                 _ => Err(StarknetRpcApiError::BlockNotFound),
             }
@@ -0,0 +1,199 @@
+//! Execution limits of the RPC, which can be overridden at runtime.
+//!
+//! The limits used to execute calls, fee estimations, simulations and traces default to the
+//! versioned constants of the block they are executed at. Operators may override some of them in a
+//! toml file, such as:
+//!
+//! ```toml
+//! invoke_tx_max_n_steps = 1000000
+//! validate_max_n_steps = 100000
+//! ```
+//!
+//! The file is polled for changes, so that the limits can be tightened in response to expensive
+//! requests without restarting the node. The transactions of the chain are always executed with the
+//! constants of their block, so that their status and fees do not depend on the overrides.
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use blockifier::context::BlockContext;
+use blockifier::versioned_constants::VersionedConstants;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// How often the override file is checked for changes.
+pub const CONSTANTS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Error, Debug)]
+pub enum ConstantsError {
+    #[error("failed to read execution constants file {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("invalid execution constants file {0}: {1}")]
+    Parse(PathBuf, toml::de::Error),
+}
+
+/// The execution limits overridden by the operator, the others keep their default value.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConstantsOverrides {
+    /// The maximum number of steps of the execution of a transaction or a call.
+    pub invoke_tx_max_n_steps: Option<u32>,
+    /// The maximum number of steps of the validation of a transaction.
+    pub validate_max_n_steps: Option<u32>,
+    /// The maximum depth of nested calls.
+    pub max_recursion_depth: Option<usize>,
+}
+
+impl ConstantsOverrides {
+    fn apply(&self, base: &VersionedConstants) -> VersionedConstants {
+        let mut constants = base.clone();
+        if let Some(max_n_steps) = self.invoke_tx_max_n_steps {
+            constants.invoke_tx_max_n_steps = max_n_steps;
+        }
+        if let Some(max_n_steps) = self.validate_max_n_steps {
+            constants.validate_max_n_steps = max_n_steps;
+        }
+        if let Some(max_depth) = self.max_recursion_depth {
+            constants.max_recursion_depth = max_depth;
+        }
+        constants
+    }
+}
+
+/// The execution constants of the RPC, shared by all its handlers.
+#[derive(Default)]
+pub struct ExecutionConstants {
+    /// The override file, if any.
+    path: Option<PathBuf>,
+    loaded: RwLock<LoadedConstants>,
+}
+
+#[derive(Default)]
+struct LoadedConstants {
+    /// The modification time of the override file when it was last read, `None` until it is.
+    modified: Option<Option<SystemTime>>,
+    /// The overrides read from the file, `None` when the defaults are used.
+    overrides: Option<ConstantsOverrides>,
+}
+
+impl ExecutionConstants {
+    /// Loads the overrides from `path`, failing if the file cannot be read or is invalid.
+    pub fn from_file(path: PathBuf) -> Result<Self, ConstantsError> {
+        let constants = Self { path: Some(path), loaded: Default::default() };
+        constants.reload()?;
+        Ok(constants)
+    }
+
+    /// Reads the override file again if it has been modified since it was last read, and returns
+    /// whether it was. The previous constants are kept if the file is invalid.
+    pub fn reload(&self) -> Result<bool, ConstantsError> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        {
+            let mut loaded = self.loaded.write().expect("Failed to acquire write lock on execution constants");
+            if loaded.modified == Some(modified) {
+                return Ok(false);
+            }
+            // an invalid or missing file is only reported once, until it is modified again
+            loaded.modified = Some(modified);
+        }
+
+        let overrides = read_overrides(path)?;
+        log::debug!("Execution constants overrides: {overrides:?}");
+        self.loaded.write().expect("Failed to acquire write lock on execution constants").overrides = Some(overrides);
+
+        Ok(true)
+    }
+
+    /// Checks the override file for changes every `interval`, until the node stops.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        loop {
+            tokio::time::sleep(interval).await;
+            match self.reload() {
+                Ok(true) => log::info!("⚙️  Execution constants reloaded from {}", path.display()),
+                Ok(false) => {}
+                Err(e) => log::warn!("❗ {e}, keeping the previous execution constants"),
+            }
+        }
+    }
+
+    /// Returns `block_context` with the overrides applied to its versioned constants.
+    pub(crate) fn apply(&self, block_context: BlockContext) -> BlockContext {
+        let loaded = self.loaded.read().expect("Failed to acquire read lock on execution constants");
+        match &loaded.overrides {
+            Some(overrides) => {
+                let constants = overrides.apply(block_context.versioned_constants());
+                BlockContext::new_unchecked(block_context.block_info(), block_context.chain_info(), &constants)
+            }
+            None => block_context,
+        }
+    }
+}
+
+fn read_overrides(path: &Path) -> Result<ConstantsOverrides, ConstantsError> {
+    let content = std::fs::read_to_string(path).map_err(|e| ConstantsError::Io(path.to_owned(), e))?;
+    toml::from_str(&content).map_err(|e| ConstantsError::Parse(path.to_owned(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_only_the_given_limits() {
+        let overrides: ConstantsOverrides = toml::from_str("validate_max_n_steps = 1000").unwrap();
+        let base = VersionedConstants::latest_constants();
+        let constants = overrides.apply(base);
+
+        assert_eq!(constants.validate_max_n_steps, 1000);
+        assert_eq!(constants.invoke_tx_max_n_steps, base.invoke_tx_max_n_steps);
+        assert_eq!(constants.max_recursion_depth, base.max_recursion_depth);
+
+        assert!(toml::from_str::<ConstantsOverrides>("max_steps = 1000").is_err());
+    }
+
+    #[test]
+    fn invalid_file_keeps_the_previous_constants() {
+        let path = std::env::temp_dir().join(format!("deoxys-execution-constants-{}.toml", std::process::id()));
+        std::fs::write(&path, "invoke_tx_max_n_steps = 1000").unwrap();
+        let constants = ExecutionConstants::from_file(path.clone()).unwrap();
+        assert!(!constants.reload().unwrap());
+
+        std::fs::write(&path, "invoke_tx_max_n_steps = ").unwrap();
+        // the modification time may not change within the resolution of the filesystem
+        constants.loaded.write().unwrap().modified = None;
+        assert!(constants.reload().is_err());
+        assert!(!constants.reload().unwrap());
+
+        let loaded = constants.loaded.read().unwrap();
+        assert_eq!(loaded.overrides.as_ref().unwrap().invoke_tx_max_n_steps, Some(1000));
+        drop(loaded);
+
+        // a missing file is only reported once as well
+        std::fs::remove_file(&path).unwrap();
+        assert!(constants.reload().is_err());
+        assert!(!constants.reload().unwrap());
+    }
+
+    #[test]
+    fn overrides_apply_to_the_constants_of_the_block() {
+        let path = std::env::temp_dir().join(format!("deoxys-execution-constants-block-{}.toml", std::process::id()));
+        std::fs::write(&path, "max_recursion_depth = 7").unwrap();
+        let constants = ExecutionConstants::from_file(path.clone()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let block_context = BlockContext::create_for_account_testing();
+        let base = block_context.versioned_constants().clone();
+        let block_context = constants.apply(block_context);
+
+        assert_eq!(block_context.versioned_constants().max_recursion_depth, 7);
+        assert_eq!(block_context.versioned_constants().invoke_tx_max_n_steps, base.invoke_tx_max_n_steps);
+    }
+}
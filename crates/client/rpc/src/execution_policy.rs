@@ -0,0 +1,307 @@
+//! Execution policy of the RPC, refusing to execute classes known to be pathological.
+//!
+//! Calls, fee estimations and simulations involving a denied class, or a class missing from the
+//! allowlist when one is set, fail with `EXECUTION_DENIED`. Classes are checked as they are loaded
+//! for execution, including by nested and library calls, so a denied class is never run at all.
+//!
+//! The entry point of a call is checked before it is executed. The classes with denied entry points
+//! are loaded without them, so that nested calls of these entry points abort the execution, which
+//! is then reported as denied. Cairo 0 classes with a default entry point run it instead, so the
+//! call tree of each execution is still checked before its result is returned.
+//!
+//! The policy is replaced at runtime through `deoxysAdmin_setExecutionPolicy`, and persisted in the
+//! database so that it is kept across restarts. Re-executions of blocks, for traces and receipts,
+//! are not subject to it.
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use blockifier::execution::call_info::CallInfo;
+use blockifier::execution::contract_class::{ContractClass, ContractClassV0, ContractClassV1};
+use blockifier::state::errors::StateError;
+use blockifier::transaction::objects::TransactionExecutionInfo;
+use mc_db::DeoxysBackend;
+use mc_rpc_core::ExecutionPolicyRules;
+use mp_felt::Felt252Wrapper;
+use starknet_api::core::{ClassHash, EntryPointSelector};
+use starknet_ff::FieldElement;
+
+use crate::errors::StarknetRpcApiError;
+
+/// The rules, indexed for the lookups done during execution.
+#[derive(Default)]
+struct CompiledRules {
+    rules: ExecutionPolicyRules,
+    allowed_classes: Option<HashSet<ClassHash>>,
+    denied_classes: HashSet<ClassHash>,
+    denied_entry_points: HashMap<ClassHash, HashSet<EntryPointSelector>>,
+}
+
+impl From<ExecutionPolicyRules> for CompiledRules {
+    fn from(rules: ExecutionPolicyRules) -> Self {
+        let class_hash = |class_hash: &FieldElement| ClassHash(Felt252Wrapper::from(*class_hash).into());
+        Self {
+            allowed_classes: rules.allowed_classes.as_ref().map(|classes| classes.iter().map(class_hash).collect()),
+            denied_classes: rules.denied_classes.iter().map(class_hash).collect(),
+            denied_entry_points: rules.denied_entry_points.iter().fold(HashMap::new(), |mut denied, entry_point| {
+                let selector = EntryPointSelector(Felt252Wrapper::from(entry_point.entry_point_selector).into());
+                denied.entry(class_hash(&entry_point.class_hash)).or_insert_with(HashSet::new).insert(selector);
+                denied
+            }),
+            rules,
+        }
+    }
+}
+
+impl CompiledRules {
+    fn allows_class(&self, class_hash: &ClassHash) -> bool {
+        !self.denied_classes.contains(class_hash)
+            && self.allowed_classes.as_ref().map_or(true, |allowed| allowed.contains(class_hash))
+    }
+
+    fn denies_entry_point(&self, class_hash: &ClassHash, selector: &EntryPointSelector) -> bool {
+        self.denied_entry_points.get(class_hash).is_some_and(|selectors| selectors.contains(selector))
+    }
+}
+
+/// The execution policy of the RPC, shared by all its handlers.
+#[derive(Default)]
+pub struct ExecutionPolicy {
+    rules: RwLock<Arc<CompiledRules>>,
+    /// Whether the rules are persisted in the database when they are replaced.
+    persistent: bool,
+}
+
+impl ExecutionPolicy {
+    /// Restores the rules persisted in the database, which must be open. The rules set from then on
+    /// are persisted as well.
+    pub fn restore() -> Result<Self, StarknetRpcApiError> {
+        let rules = match DeoxysBackend::meta().execution_policy() {
+            Ok(Some(raw)) => serde_json::from_slice(&raw).map_err(|e| {
+                log::error!("Failed to decode the persisted execution policy: {e}");
+                StarknetRpcApiError::InternalServerError
+            })?,
+            Ok(None) => ExecutionPolicyRules::default(),
+            Err(e) => {
+                log::error!("Failed to read the persisted execution policy: {e}");
+                return Err(StarknetRpcApiError::InternalServerError);
+            }
+        };
+        Ok(Self { rules: RwLock::new(Arc::new(rules.into())), persistent: true })
+    }
+
+    pub fn rules(&self) -> ExecutionPolicyRules {
+        self.rules.read().expect("Failed to acquire read lock on execution policy").rules.clone()
+    }
+
+    /// Replaces the rules of the policy, executions already running keep the previous ones.
+    pub fn set_rules(&self, rules: ExecutionPolicyRules) -> Result<(), StarknetRpcApiError> {
+        if self.persistent {
+            let raw = serde_json::to_vec(&rules).map_err(|_| StarknetRpcApiError::InternalServerError)?;
+            DeoxysBackend::meta().write_execution_policy(&raw).map_err(|e| {
+                log::error!("Failed to persist the execution policy: {e}");
+                StarknetRpcApiError::InternalServerError
+            })?;
+        }
+        *self.rules.write().expect("Failed to acquire write lock on execution policy") = Arc::new(rules.into());
+        Ok(())
+    }
+
+    /// Returns a guard enforcing the current rules over one execution.
+    pub(crate) fn guard(&self) -> Arc<PolicyGuard> {
+        let rules = Arc::clone(&self.rules.read().expect("Failed to acquire read lock on execution policy"));
+        Arc::new(PolicyGuard { rules, violation: Mutex::new(None), restricted: AtomicBool::new(false) })
+    }
+}
+
+/// Enforces the policy over one execution, recording the first rule it violates.
+pub(crate) struct PolicyGuard {
+    rules: Arc<CompiledRules>,
+    violation: Mutex<Option<String>>,
+    /// Whether a class was loaded without its denied entry points.
+    restricted: AtomicBool,
+}
+
+impl PolicyGuard {
+    /// Checks that a class may be executed, before it is loaded.
+    pub(crate) fn check_class(&self, class_hash: ClassHash) -> Result<(), StateError> {
+        if self.rules.allows_class(&class_hash) {
+            return Ok(());
+        }
+        let violation = format!("class {} is not allowed to be executed", class_hash.0);
+        self.record(violation.clone());
+        Err(StateError::StateReadError(violation))
+    }
+
+    /// Checks the entry point of a call before it is executed.
+    pub(crate) fn check_entry_point(
+        &self,
+        class_hash: ClassHash,
+        selector: EntryPointSelector,
+    ) -> Result<(), StarknetRpcApiError> {
+        self.inspect_entry_point(class_hash, selector);
+        self.check()
+    }
+
+    /// Returns a class loaded for execution without its denied entry points, so that calling one of
+    /// them aborts the execution.
+    pub(crate) fn restrict_class(&self, class_hash: ClassHash, class: ContractClass) -> ContractClass {
+        let Some(denied) = self.rules.denied_entry_points.get(&class_hash) else {
+            return class;
+        };
+        self.restricted.store(true, Ordering::Relaxed);
+
+        match class {
+            ContractClass::V0(class) => {
+                let mut inner = (*class.0).clone();
+                for entry_points in inner.entry_points_by_type.values_mut() {
+                    entry_points.retain(|entry_point| !denied.contains(&entry_point.selector));
+                }
+                ContractClass::V0(ContractClassV0(Arc::new(inner)))
+            }
+            ContractClass::V1(class) => {
+                let mut inner = (*class.0).clone();
+                for entry_points in inner.entry_points_by_type.values_mut() {
+                    entry_points.retain(|entry_point| !denied.contains(&entry_point.selector));
+                }
+                ContractClass::V1(ContractClassV1(Arc::new(inner)))
+            }
+        }
+    }
+
+    /// Records that an execution failed or reverted. The call of a denied entry point fails like
+    /// the call of any missing entry point, so the failure of an execution which loaded a class
+    /// without its denied entry points is attributed to the policy.
+    pub(crate) fn inspect_failure(&self) {
+        if self.restricted.load(Ordering::Relaxed) {
+            self.record("a denied entry point was called".to_string());
+        }
+    }
+
+    /// Checks the classes and entry points of a call tree.
+    pub(crate) fn inspect_call(&self, call_info: &CallInfo) {
+        if let Some(class_hash) = call_info.call.class_hash {
+            self.inspect_entry_point(class_hash, call_info.call.entry_point_selector);
+        }
+        call_info.inner_calls.iter().for_each(|call_info| self.inspect_call(call_info));
+    }
+
+    /// Checks the entry points executed by a call served from the cache.
+    pub(crate) fn inspect_entry_points(&self, entry_points: &[(ClassHash, EntryPointSelector)]) {
+        entry_points.iter().for_each(|(class_hash, selector)| self.inspect_entry_point(*class_hash, *selector));
+    }
+
+    fn inspect_entry_point(&self, class_hash: ClassHash, selector: EntryPointSelector) {
+        if !self.rules.allows_class(&class_hash) {
+            self.record(format!("class {} is not allowed to be executed", class_hash.0));
+        } else if self.rules.denies_entry_point(&class_hash, &selector) {
+            self.record(format!("entry point {} of class {} is not allowed to be called", selector.0, class_hash.0));
+        }
+    }
+
+    pub(crate) fn inspect_transaction(&self, execution_info: &TransactionExecutionInfo) {
+        if execution_info.revert_error.is_some() {
+            self.inspect_failure();
+        }
+        [&execution_info.validate_call_info, &execution_info.execute_call_info, &execution_info.fee_transfer_call_info]
+            .into_iter()
+            .flatten()
+            .for_each(|call_info| self.inspect_call(call_info));
+    }
+
+    /// Fails with [StarknetRpcApiError::ExecutionDenied] if the execution violated the policy, in
+    /// which case its result must not be returned.
+    pub(crate) fn check(&self) -> Result<(), StarknetRpcApiError> {
+        match &*self.violation.lock().expect("Failed to acquire lock on policy violation") {
+            Some(violation) => {
+                log::debug!("Execution denied by the execution policy: {violation}");
+                Err(StarknetRpcApiError::ExecutionDenied)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn record(&self, violation: String) {
+        self.violation.lock().expect("Failed to acquire lock on policy violation").get_or_insert(violation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mc_rpc_core::DeniedEntryPoint;
+    use starknet_api::deprecated_contract_class::{EntryPoint, EntryPointOffset, EntryPointType};
+    use starknet_api::hash::StarkFelt;
+
+    use super::*;
+
+    #[test]
+    fn denied_classes_take_precedence_over_the_allowlist() {
+        let policy = ExecutionPolicy::default();
+        assert!(policy.guard().check_class(ClassHash(StarkFelt::from(1u64))).is_ok());
+
+        policy
+            .set_rules(ExecutionPolicyRules {
+                allowed_classes: Some(vec![FieldElement::ONE, FieldElement::TWO]),
+                denied_classes: vec![FieldElement::TWO],
+                denied_entry_points: vec![],
+            })
+            .unwrap();
+        let guard = policy.guard();
+        assert!(guard.check_class(ClassHash(StarkFelt::from(1u64))).is_ok());
+        assert!(guard.check().is_ok());
+        assert!(guard.check_class(ClassHash(StarkFelt::from(2u64))).is_err());
+        assert!(guard.check_class(ClassHash(StarkFelt::from(3u64))).is_err());
+        assert!(matches!(guard.check(), Err(StarknetRpcApiError::ExecutionDenied)));
+    }
+
+    #[test]
+    fn denied_entry_points_are_checked_before_and_during_execution() {
+        let policy = ExecutionPolicy::default();
+        policy
+            .set_rules(ExecutionPolicyRules {
+                denied_entry_points: vec![DeniedEntryPoint {
+                    class_hash: FieldElement::ONE,
+                    entry_point_selector: FieldElement::TWO,
+                }],
+                ..Default::default()
+            })
+            .unwrap();
+        let class_hash = ClassHash(StarkFelt::from(1u64));
+        let selector = |selector: u64| EntryPointSelector(StarkFelt::from(selector));
+
+        let guard = policy.guard();
+        assert!(guard.check_entry_point(class_hash, selector(3)).is_ok());
+        assert!(matches!(guard.check_entry_point(class_hash, selector(2)), Err(StarknetRpcApiError::ExecutionDenied)));
+
+        // the entry points of a call served from the cache are checked as well
+        let guard = policy.guard();
+        guard.inspect_entry_points(&[(class_hash, selector(3))]);
+        assert!(guard.check().is_ok());
+        guard.inspect_entry_points(&[(class_hash, selector(3)), (class_hash, selector(2))]);
+        assert!(matches!(guard.check(), Err(StarknetRpcApiError::ExecutionDenied)));
+
+        // the denied entry point is removed from the class as it is loaded
+        let entry_point = |n: u64| EntryPoint { selector: selector(n), offset: EntryPointOffset(0) };
+        let mut class = ContractClassV0::default();
+        Arc::make_mut(&mut class.0)
+            .entry_points_by_type
+            .insert(EntryPointType::External, vec![entry_point(2), entry_point(3)]);
+        let guard = policy.guard();
+        let ContractClass::V0(restricted) = guard.restrict_class(class_hash, ContractClass::V0(class.clone())) else {
+            panic!("the class changed version");
+        };
+        assert_eq!(restricted.0.entry_points_by_type[&EntryPointType::External], vec![entry_point(3)]);
+        assert!(guard.check().is_ok());
+
+        // an execution failing after loading the class is attributed to the policy
+        guard.inspect_failure();
+        assert!(matches!(guard.check(), Err(StarknetRpcApiError::ExecutionDenied)));
+
+        // classes without denied entry points are left untouched
+        let guard = policy.guard();
+        let unrestricted = guard.restrict_class(ClassHash(StarkFelt::from(3u64)), ContractClass::V0(class.clone()));
+        assert_eq!(unrestricted, ContractClass::V0(class));
+        guard.inspect_failure();
+        assert!(guard.check().is_ok());
+    }
+}
@@ -6,7 +6,10 @@ mod constants;
 pub mod deoxys_backend_client;
 mod errors;
 mod events;
+pub mod execution_constants;
+pub mod execution_policy;
 mod methods;
+pub mod spec_audit;
 mod types;
 pub mod utils;
 
@@ -15,201 +18,73 @@ use std::sync::Arc;
 
 use errors::StarknetRpcApiError;
 use jsonrpsee::core::RpcResult;
-use jsonrpsee::proc_macros::rpc;
 use mc_db::DeoxysBackend;
+pub use mc_rpc_core::{
+    AccountSummary, BackfillRange, BlockContextError, BlockContextRequest, BlockContextResponse, BlockContextResult,
+    BlockHeader, BlockRange, CairoProfilerTransactionTrace, CallTrace, ClassInstance, ColumnStats, ContractData,
+    ContractStoragePage, ContractsByClassPage, ConversionError, DataAvailability, DbStats, DecodedEvent,
+    DecodedRevertReason, DeniedEntryPoint, DeoxysAdminRpcApi, DeoxysAdminRpcApiServer, DeoxysRpcApi,
+    DeoxysRpcApiServer, EdgePath, EventProof, EventsSource, ExecutionPolicyRules, ExtendedBlockId, FeeTokenBalances,
+    Felt, GetProofOutput, MerkleNode, PathfinderRpcApi, PathfinderRpcApiServer, SenderTransaction, StarknetReadRpcApi,
+    StarknetReadRpcApiServer, StarknetTraceRpcApi, StarknetTraceRpcApiServer, StarknetWriteRpcApi,
+    StarknetWriteRpcApiServer, SyncProgress, SyncStatus, TokenTransferEntry, TokenTransfersPage, TraceFormat,
+    TransactionProof, TransactionTraceOutput, TransactionsBySenderPage, TrieNode, U256Balance, WithDecodedRevertReason,
+    WithDecodedRevertReasons,
+};
+use mc_sync::l1::l1_head;
+use mc_sync::pending::PendingSubscription;
+use mc_sync::state::SyncState;
+use mp_block::DeoxysBlock;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_types::block::{DBlockT, DHashT, DHeaderT};
 use pallet_starknet_runtime_api::StarknetRuntimeApi;
 use sc_network_sync::SyncingService;
-use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
 use sp_api::ProvideRuntimeApi;
 use sp_arithmetic::traits::UniqueSaturatedInto;
 use sp_blockchain::HeaderBackend;
 use sp_core::H256;
 use sp_runtime::traits::Header as HeaderT;
 use starknet_api::hash::StarkHash;
-use starknet_core::serde::unsigned_field_element::UfeHex;
-use starknet_core::types::{
-    BlockHashAndNumber, BlockId, BroadcastedDeclareTransaction, BroadcastedDeployAccountTransaction,
-    BroadcastedInvokeTransaction, BroadcastedTransaction, ContractClass, DeclareTransactionResult,
-    DeployAccountTransactionResult, EventFilterWithPage, EventsPage, FeeEstimate, FieldElement, FunctionCall,
-    InvokeTransactionResult, MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs,
-    MaybePendingStateUpdate, MsgFromL1, SimulatedTransaction, SimulationFlag, SimulationFlagForEstimateFee,
-    SyncStatusType, Transaction, TransactionReceiptWithBlockInfo, TransactionStatus, TransactionTraceWithHash,
-};
+use starknet_core::types::{BlockId, BlockTag, PendingStateUpdate};
 
 use crate::deoxys_backend_client::get_block_by_block_hash;
+use crate::execution_constants::ExecutionConstants;
+use crate::execution_policy::ExecutionPolicy;
 use crate::methods::get_block::{
     get_block_with_tx_hashes_finalized, get_block_with_tx_hashes_pending, get_block_with_txs_finalized,
     get_block_with_txs_pending,
 };
+use crate::types::resolve_extended_block_id;
+use crate::utils::cache::{BlockContextCache, CallCache, BLOCK_CONTEXT_CACHE_SIZE, CALL_CACHE_SIZE};
+use crate::utils::snapshot::{get_or_pin, PinnedBlock};
+pub use crate::utils::snapshot::{pin_request, PinRequest, PinRequestLayer};
 
-// Starknet RPC API trait and types
-//
-// Starkware maintains [a description of the Starknet API](https://github.com/starkware-libs/starknet-specs/blob/master/api/starknet_api_openrpc.json)
-// using the openRPC specification.
-// This crate uses `jsonrpsee` to define such an API in Rust terms.
-
-#[serde_as]
-#[derive(Serialize, Deserialize, Clone, Copy)]
-pub struct Felt(#[serde_as(as = "UfeHex")] pub FieldElement);
-
-/// Starknet write rpc interface.
-#[rpc(server, namespace = "starknet")]
-pub trait StarknetWriteRpcApi {
-    /// Submit a new transaction to be added to the chain
-    #[method(name = "addInvokeTransaction")]
-    async fn add_invoke_transaction(
-        &self,
-        invoke_transaction: BroadcastedInvokeTransaction,
-    ) -> RpcResult<InvokeTransactionResult>;
-
-    /// Submit a new class declaration transaction
-    #[method(name = "addDeployAccountTransaction")]
-    async fn add_deploy_account_transaction(
-        &self,
-        deploy_account_transaction: BroadcastedDeployAccountTransaction,
-    ) -> RpcResult<DeployAccountTransactionResult>;
-
-    /// Submit a new deploy account transaction
-    #[method(name = "addDeclareTransaction")]
-    async fn add_declare_transaction(
-        &self,
-        declare_transaction: BroadcastedDeclareTransaction,
-    ) -> RpcResult<DeclareTransactionResult>;
-}
-
-#[rpc(server, namespace = "starknet")]
-pub trait StarknetReadRpcApi {
-    /// Get the Version of the StarkNet JSON-RPC Specification Being Used
-    #[method(name = "specVersion")]
-    fn spec_version(&self) -> RpcResult<String>;
-
-    /// Get the most recent accepted block number
-    #[method(name = "blockNumber")]
-    fn block_number(&self) -> RpcResult<u64>;
-
-    // Get the most recent accepted block hash and number
-    #[method(name = "blockHashAndNumber")]
-    fn block_hash_and_number(&self) -> RpcResult<BlockHashAndNumber>;
-
-    /// Call a contract function at a given block id
-    #[method(name = "call")]
-    fn call(&self, request: FunctionCall, block_id: BlockId) -> RpcResult<Vec<String>>;
-
-    /// Get the chain id
-    #[method(name = "chainId")]
-    fn chain_id(&self) -> RpcResult<Felt>;
-
-    /// Get the number of transactions in a block given a block id
-    #[method(name = "getBlockTransactionCount")]
-    fn get_block_transaction_count(&self, block_id: BlockId) -> RpcResult<u128>;
-
-    /// Estimate the fee associated with transaction
-    #[method(name = "estimateFee")]
-    async fn estimate_fee(
-        &self,
-        request: Vec<BroadcastedTransaction>,
-        simulation_flags: Vec<SimulationFlagForEstimateFee>,
-        block_id: BlockId,
-    ) -> RpcResult<Vec<FeeEstimate>>;
-
-    /// Estimate the L2 fee of a message sent on L1
-    #[method(name = "estimateMessageFee")]
-    async fn estimate_message_fee(&self, message: MsgFromL1, block_id: BlockId) -> RpcResult<FeeEstimate>;
-
-    /// Get block information with full transactions and receipts given the block id
-    #[method(name = "getBlockWithReceipts")]
-    async fn get_block_with_receipts(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithReceipts>;
-
-    /// Get block information with transaction hashes given the block id
-    #[method(name = "getBlockWithTxHashes")]
-    fn get_block_with_tx_hashes(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithTxHashes>;
-
-    /// Get block information with full transactions given the block id
-    #[method(name = "getBlockWithTxs")]
-    fn get_block_with_txs(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithTxs>;
-
-    /// Get the contract class at a given contract address for a given block id
-    #[method(name = "getClassAt")]
-    fn get_class_at(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<ContractClass>;
-
-    /// Get the contract class hash in the given block for the contract deployed at the given
-    /// address
-    #[method(name = "getClassHashAt")]
-    fn get_class_hash_at(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt>;
-
-    /// Get the contract class definition in the given block associated with the given hash
-    #[method(name = "getClass")]
-    fn get_class(&self, block_id: BlockId, class_hash: FieldElement) -> RpcResult<ContractClass>;
-
-    /// Returns all events matching the given filter
-    #[method(name = "getEvents")]
-    async fn get_events(&self, filter: EventFilterWithPage) -> RpcResult<EventsPage>;
-
-    /// Get the nonce associated with the given address at the given block
-    #[method(name = "getNonce")]
-    fn get_nonce(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt>;
-
-    /// Get the value of the storage at the given address and key, at the given block id
-    #[method(name = "getStorageAt")]
-    fn get_storage_at(&self, contract_address: FieldElement, key: FieldElement, block_id: BlockId) -> RpcResult<Felt>;
-
-    /// Get the details of a transaction by a given block id and index
-    #[method(name = "getTransactionByBlockIdAndIndex")]
-    fn get_transaction_by_block_id_and_index(&self, block_id: BlockId, index: u64) -> RpcResult<Transaction>;
-
-    /// Returns the information about a transaction by transaction hash.
-    #[method(name = "getTransactionByHash")]
-    fn get_transaction_by_hash(&self, transaction_hash: FieldElement) -> RpcResult<Transaction>;
-
-    /// Returns the receipt of a transaction by transaction hash.
-    #[method(name = "getTransactionReceipt")]
-    async fn get_transaction_receipt(
-        &self,
-        transaction_hash: FieldElement,
-    ) -> RpcResult<TransactionReceiptWithBlockInfo>;
-
-    /// Gets the Transaction Status, Including Mempool Status and Execution Details
-    #[method(name = "getTransactionStatus")]
-    fn get_transaction_status(&self, transaction_hash: FieldElement) -> RpcResult<TransactionStatus>;
-
-    /// Get an object about the sync status, or false if the node is not syncing
-    #[method(name = "syncing")]
-    async fn syncing(&self) -> RpcResult<SyncStatusType>;
-
-    /// Get the information about the result of executing the requested block
-    #[method(name = "getStateUpdate")]
-    fn get_state_update(&self, block_id: BlockId) -> RpcResult<MaybePendingStateUpdate>;
-}
-
-#[rpc(server, namespace = "starknet")]
-pub trait StarknetTraceRpcApi {
-    /// Returns the execution trace of a transaction by simulating it in the runtime.
-    #[method(name = "simulateTransactions")]
-    async fn simulate_transactions(
-        &self,
-        block_id: BlockId,
-        transactions: Vec<BroadcastedTransaction>,
-        simulation_flags: Vec<SimulationFlag>,
-    ) -> RpcResult<Vec<SimulatedTransaction>>;
-
-    #[method(name = "traceBlockTransactions")]
-    /// Returns the execution traces of all transactions included in the given block
-    async fn trace_block_transactions(&self, block_id: BlockId) -> RpcResult<Vec<TransactionTraceWithHash>>;
-
-    #[method(name = "traceTransaction")]
-    /// Returns the execution trace of a transaction
-    async fn trace_transaction(&self, transaction_hash: FieldElement) -> RpcResult<TransactionTraceWithHash>;
-}
+/// The version of the Starknet RPC specification implemented by the node, served unless the chain
+/// spec overrides it.
+pub const SPEC_VERSION: &str = "0.7.1";
 
 /// A Starknet RPC server for Deoxys
 pub struct Starknet<BE, C, H> {
     client: Arc<C>,
-    sync_service: Arc<SyncingService<DBlockT>>,
+    /// The network sync of the node, `None` when replaying requests against the database only.
+    sync_service: Option<Arc<SyncingService<DBlockT>>>,
     starting_block: <DHeaderT as HeaderT>::Number,
+    /// Only serve blocks covered by a state update verified on L1.
+    l1_accepted_only: bool,
+    /// The version of the Starknet RPC specification reported by the node.
+    spec_version: String,
+    /// Attach a readable form of the revert reasons to traces and receipts, which is an extension
+    /// to the specification.
+    decode_revert_reasons: bool,
+    block_context_cache: Arc<BlockContextCache>,
+    call_cache: Arc<CallCache>,
+    /// The limits used to execute transactions and calls, which may be changed at runtime.
+    execution_constants: Arc<ExecutionConstants>,
+    /// The classes and entry points which may not be executed, set at runtime.
+    execution_policy: Arc<ExecutionPolicy>,
+    sync_state: Arc<SyncState>,
+    pending: PendingSubscription,
     _marker: PhantomData<(DBlockT, BE, H)>,
 }
 
@@ -219,8 +94,54 @@ impl<BE, C, H> Starknet<BE, C, H> {
         client: Arc<C>,
         sync_service: Arc<SyncingService<DBlockT>>,
         starting_block: <DHeaderT as HeaderT>::Number,
+        l1_accepted_only: bool,
+        spec_version: String,
+        decode_revert_reasons: bool,
+        sync_state: Arc<SyncState>,
+        execution_constants: Arc<ExecutionConstants>,
+        execution_policy: Arc<ExecutionPolicy>,
     ) -> Self {
-        Self { client, sync_service, starting_block, _marker: PhantomData }
+        Self {
+            client,
+            sync_service: Some(sync_service),
+            starting_block,
+            l1_accepted_only,
+            spec_version,
+            decode_revert_reasons,
+            block_context_cache: Arc::new(BlockContextCache::new(BLOCK_CONTEXT_CACHE_SIZE)),
+            call_cache: Arc::new(CallCache::new(CALL_CACHE_SIZE)),
+            execution_constants,
+            execution_policy,
+            pending: sync_state.subscribe_pending(),
+            sync_state,
+            _marker: PhantomData,
+        }
+    }
+
+    /// A server over the local database only, without the network of the node, used to replay
+    /// requests offline.
+    pub fn offline(
+        client: Arc<C>,
+        spec_version: String,
+        sync_state: Arc<SyncState>,
+        execution_constants: Arc<ExecutionConstants>,
+        execution_policy: Arc<ExecutionPolicy>,
+    ) -> Self {
+        Self {
+            client,
+            sync_service: None,
+            starting_block: Default::default(),
+            l1_accepted_only: false,
+            spec_version,
+            decode_revert_reasons: false,
+            block_context_cache: Arc::new(BlockContextCache::new(BLOCK_CONTEXT_CACHE_SIZE)),
+            call_cache: Arc::new(CallCache::new(CALL_CACHE_SIZE)),
+            execution_constants,
+            execution_policy,
+            pending: sync_state.subscribe_pending(),
+            sync_state,
+            _marker: PhantomData,
+        }
     }
 }
 
@@ -228,6 +149,23 @@ impl<BE, C, H> Starknet<BE, C, H> {
     fn chain_id(&self) -> RpcResult<Felt> {
         methods::read::chain_id::chain_id()
     }
+
+    /// The pending block, as last broadcast by the pending block tracker.
+    ///
+    /// It is never served when only blocks accepted on L1 are served.
+    fn pending_block(&self) -> Option<DeoxysBlock> {
+        if self.l1_accepted_only {
+            return None;
+        }
+        self.pending.borrow().as_ref().map(|pending| pending.block.clone())
+    }
+
+    fn pending_state_update(&self) -> Option<PendingStateUpdate> {
+        if self.l1_accepted_only {
+            return None;
+        }
+        self.pending.borrow().as_ref().map(|pending| pending.state_update.clone())
+    }
 }
 
 impl<BE, C, H> Starknet<BE, C, H>
@@ -235,7 +173,96 @@ where
     C: HeaderBackend<DBlockT> + 'static,
 {
     pub fn current_block_number(&self) -> RpcResult<u64> {
-        Ok(UniqueSaturatedInto::<u64>::unique_saturated_into(self.client.info().best_number))
+        let best_number = UniqueSaturatedInto::<u64>::unique_saturated_into(self.client.info().best_number);
+        let l1_head = self.l1_accepted_head().map_err(|_| StarknetRpcApiError::NoBlocks)?;
+        Ok(l1_head.map_or(best_number, |l1_head| best_number.min(l1_head)))
+    }
+
+    /// Resolves a block id and pins a database snapshot to serve it from.
+    ///
+    /// The snapshot and the resolution of the `latest` tag are shared with the other calls of the
+    /// request made on the same block id, so that the calls of a batch served within
+    /// [pin_request] are all served from the same state even if a new block lands in the middle of
+    /// it.
+    fn pin_block(&self, block_id: ExtendedBlockId) -> RpcResult<PinnedBlock> {
+        let block_id = self.resolve_block_id(block_id)?;
+        if let BlockId::Number(block_number) = block_id {
+            self.ensure_backfilled(block_number)?;
+        }
+        get_or_pin(block_id, || match block_id {
+            BlockId::Tag(BlockTag::Latest) => self.current_block_number().map(BlockId::Number),
+            block_id => Ok(block_id),
+        })
+    }
+
+    /// Same as [Starknet::pin_block], for the requests reading the state at the block, which fail
+    /// with [StarknetRpcApiError::StatePruned] if the state at the block has been pruned.
+    fn pin_state(&self, block_id: ExtendedBlockId) -> RpcResult<PinnedBlock> {
+        let pinned = self.pin_block(block_id)?;
+        if let BlockId::Number(_) | BlockId::Hash(_) = pinned.block_id {
+            self.ensure_state_available(self.substrate_block_number_from_starknet_block(pinned.block_id)?)?;
+        }
+        Ok(pinned)
+    }
+
+    /// Resolves the tags of `block_id` which do not designate a block of the chain.
+    ///
+    /// When only blocks accepted on L1 are served, the pending block is never served and the
+    /// `pending` tag resolves to the latest block, as when pending block tracking is disabled.
+    fn resolve_block_id(&self, block_id: ExtendedBlockId) -> Result<BlockId, StarknetRpcApiError> {
+        Ok(match resolve_extended_block_id(block_id)? {
+            BlockId::Tag(BlockTag::Pending) if self.l1_accepted_only => BlockId::Tag(BlockTag::Latest),
+            block_id => block_id,
+        })
+    }
+
+    /// Returns the highest block which may be served, when only blocks accepted on L1 are served.
+    ///
+    /// No block may be served until the first state update verified on L1 is known.
+    fn l1_accepted_head(&self) -> Result<Option<u64>, StarknetRpcApiError> {
+        if !self.l1_accepted_only {
+            return Ok(None);
+        }
+        Ok(Some(l1_head().ok_or(StarknetRpcApiError::BlockNotFound)?.block_number))
+    }
+
+    /// Fails with [StarknetRpcApiError::BlockNotFound] if `block_number` may not be served.
+    fn ensure_queryable(&self, block_number: u64) -> Result<(), StarknetRpcApiError> {
+        match self.l1_accepted_head()? {
+            Some(l1_head) if block_number > l1_head => Err(StarknetRpcApiError::BlockNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails with [StarknetRpcApiError::HistoricalDataNotBackfilled] if `block_number` precedes the
+    /// blocks backfilled so far.
+    fn ensure_backfilled(&self, block_number: u64) -> Result<(), StarknetRpcApiError> {
+        match DeoxysBackend::backfill().range() {
+            Ok(Some(range)) if range.is_missing(block_number) => Err(StarknetRpcApiError::HistoricalDataNotBackfilled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails with [StarknetRpcApiError::StatePruned] if the state history at `block_number` has
+    /// been pruned.
+    fn ensure_state_available(&self, block_number: u64) -> Result<(), StarknetRpcApiError> {
+        match DeoxysBackend::meta().state_pruned_before() {
+            Ok(Some(pruned_before)) if block_number < pruned_before => Err(StarknetRpcApiError::StatePruned),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the block for `block_id` if it is a historical block served from the backfilled
+    /// blocks.
+    pub(crate) fn backfilled_block(&self, block_id: BlockId) -> Option<DeoxysBlock> {
+        let BlockId::Number(block_number) = block_id else {
+            return None;
+        };
+        let range = DeoxysBackend::backfill().range().ok()??;
+        if !range.contains(block_number) {
+            return None;
+        }
+        DeoxysBackend::backfill().get_block(block_number).ok()?
     }
 }
 
@@ -244,7 +271,7 @@ where
     C: HeaderBackend<DBlockT> + 'static,
 {
     pub fn current_spec_version(&self) -> RpcResult<String> {
-        Ok("0.7.1".to_string())
+        Ok(self.spec_version.clone())
     }
 }
 
@@ -256,18 +283,24 @@ where
     H: HasherT + Send + Sync + 'static,
 {
     pub fn current_block_hash(&self) -> Result<H256, StarknetRpcApiError> {
-        let substrate_block_hash = self.client.info().best_hash;
+        // the headers of the last applied blocks are kept in memory by the sync
+        if let Some(cached) = self.current_block_number().ok().and_then(|n| self.sync_state.cached_header(n)) {
+            return Ok(H256::from(cached.block_hash.to_bytes_be()));
+        }
+
+        let substrate_block_hash = self.substrate_block_hash_from_starknet_block(BlockId::Tag(BlockTag::Latest))?;
 
         let starknet_block = match get_block_by_block_hash(self.client.as_ref(), substrate_block_hash) {
             Ok(block) => block,
             Err(_) => return Err(StarknetRpcApiError::BlockNotFound),
         };
-        Ok(starknet_block.header().hash::<H>().into())
+        let chain_id = self.chain_id().map_err(|_| StarknetRpcApiError::InternalServerError)?.0.into();
+        Ok(starknet_block.header().hash::<H>(chain_id).into())
     }
 
     /// Returns the substrate block hash corresponding to the given Starknet block id
     fn substrate_block_hash_from_starknet_block(&self, block_id: BlockId) -> Result<DHashT, StarknetRpcApiError> {
-        match block_id {
+        let substrate_block_hash = match block_id {
             BlockId::Hash(h) => deoxys_backend_client::load_hash(self.client.as_ref(), Felt252Wrapper::from(h).into())
                 .map_err(|e| {
                     log::error!("Failed to load Starknet block hash for Substrate block with hash '{h}': {e}");
@@ -277,9 +310,29 @@ where
                 .client
                 .hash(UniqueSaturatedInto::unique_saturated_into(n))
                 .map_err(|_| StarknetRpcApiError::BlockNotFound)?,
-            BlockId::Tag(_) => Some(self.client.info().best_hash),
+            // with only L1 accepted blocks being served, the latest block is the L1 head
+            BlockId::Tag(_) => match self.l1_accepted_head()? {
+                Some(l1_head) => {
+                    let best_number = UniqueSaturatedInto::<u64>::unique_saturated_into(self.client.info().best_number);
+                    self.client
+                        .hash(UniqueSaturatedInto::unique_saturated_into(best_number.min(l1_head)))
+                        .map_err(|_| StarknetRpcApiError::BlockNotFound)?
+                }
+                None => Some(self.client.info().best_hash),
+            },
+        }
+        .ok_or(StarknetRpcApiError::BlockNotFound)?;
+
+        if self.l1_accepted_only {
+            let block_number = self
+                .client
+                .number(substrate_block_hash)
+                .map_err(|_| StarknetRpcApiError::BlockNotFound)?
+                .ok_or(StarknetRpcApiError::BlockNotFound)?;
+            self.ensure_queryable(UniqueSaturatedInto::<u64>::unique_saturated_into(block_number))?;
         }
-        .ok_or(StarknetRpcApiError::BlockNotFound)
+
+        Ok(substrate_block_hash)
     }
 
     /// Helper function to get the substrate block number from a Starknet block id
@@ -294,8 +347,17 @@ where
     fn substrate_block_number_from_starknet_block(&self, block_id: BlockId) -> Result<u64, StarknetRpcApiError> {
         // Short circuit on block number
         if let BlockId::Number(x) = block_id {
+            self.ensure_queryable(x)?;
             return Ok(x);
         }
+        let cached = match block_id {
+            BlockId::Hash(block_hash) => self.sync_state.cached_header_by_hash(block_hash),
+            _ => None,
+        };
+        if let Some(cached) = cached {
+            self.ensure_queryable(cached.header.block_number)?;
+            return Ok(cached.header.block_number);
+        }
 
         let substrate_block_hash = self.substrate_block_hash_from_starknet_block(block_id)?;
 
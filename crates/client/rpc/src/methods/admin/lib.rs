@@ -0,0 +1,69 @@
+use jsonrpsee::core::{async_trait, RpcResult};
+use mc_db::DeoxysBackend;
+use mc_rpc_core::{ColumnStats, ConversionError, DbStats, ExecutionPolicyRules};
+use mc_sync::convert::recent_conversion_errors;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+
+use crate::{DeoxysAdminRpcApiServer, Starknet};
+
+#[async_trait]
+impl<BE, C, H> DeoxysAdminRpcApiServer for Starknet<BE, C, H>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    fn get_execution_policy(&self) -> RpcResult<ExecutionPolicyRules> {
+        Ok(self.execution_policy.rules())
+    }
+
+    fn set_execution_policy(&self, rules: ExecutionPolicyRules) -> RpcResult<()> {
+        self.execution_policy.set_rules(rules.clone())?;
+        log::info!(
+            "⚙️  Execution policy updated: {} denied classes, {} denied entry points, allowlist {}",
+            rules.denied_classes.len(),
+            rules.denied_entry_points.len(),
+            rules.allowed_classes.as_ref().map_or("disabled".to_string(), |classes| format!("of {}", classes.len()))
+        );
+        Ok(())
+    }
+
+    fn get_conversion_errors(&self) -> RpcResult<Vec<ConversionError>> {
+        Ok(recent_conversion_errors()
+            .into_iter()
+            .map(|error| ConversionError {
+                block_number: error.block_number,
+                transaction_index: error.transaction_index,
+                field: error.field,
+                reason: error.reason,
+            })
+            .collect())
+    }
+
+    fn db_stats(&self) -> RpcResult<DbStats> {
+        let stats = DeoxysBackend::db_stats();
+        Ok(DbStats {
+            columns: stats
+                .columns
+                .into_iter()
+                .map(|column| ColumnStats {
+                    column: column.column,
+                    estimated_keys: column.estimated_keys,
+                    disk_bytes: column.disk_bytes,
+                    mem_table_bytes: column.mem_table_bytes,
+                    l0_files: column.l0_files,
+                    pending_compaction_bytes: column.pending_compaction_bytes,
+                })
+                .collect(),
+            block_cache_hit_rate: stats.block_cache_hit_rate,
+        })
+    }
+}
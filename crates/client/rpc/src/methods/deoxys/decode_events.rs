@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use jsonrpsee::core::RpcResult;
+use mc_db::storage_handler::{self, StorageView};
+use mc_db::DeoxysBackend;
+use mc_rpc_core::{DecodedEvent, EventsSource};
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::{BlockId, EmittedEvent, FieldElement};
+
+use crate::errors::StarknetRpcApiError;
+use crate::utils::event_decoding::EventAbi;
+use crate::{get_block_by_block_hash, Starknet};
+
+/// Decode Events with the ABI of their Emitter
+///
+/// The class of the contract which emitted each event is looked up at the block of the event, and
+/// its ABI is used to decode the event. Events which the ABI does not describe, or whose keys and
+/// data do not match their description, are returned without a name and fields.
+///
+/// ### Arguments
+///
+/// * `source` - Either `{"block_id": ...}` to decode the events of a block, or
+///   `{"transaction_hash": ...}` to decode the events of a transaction.
+///
+/// ### Returns
+///
+/// The events in emission order, along with their decoded name and fields.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the specified block does not exist in the blockchain.
+/// * `TXN_HASH_NOT_FOUND` - If the specified transaction is not found.
+pub fn decode_events<BE, C, H>(starknet: &Starknet<BE, C, H>, source: EventsSource) -> RpcResult<Vec<DecodedEvent>>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    let events = match source {
+        EventsSource::BlockId(block_id) => starknet.get_block_events(block_id)?,
+        EventsSource::TransactionHash(transaction_hash) => {
+            let substrate_block_hash = DeoxysBackend::mapping()
+                .block_hash_from_transaction_hash(Felt252Wrapper::from(transaction_hash).into())
+                .map_err(|e| {
+                    log::error!("Failed to get transaction's substrate block hash from mapping_db: {e}");
+                    StarknetRpcApiError::InternalServerError
+                })?
+                .ok_or_else(|| {
+                    StarknetRpcApiError::TxnHashNotFound.with_data(json!({ "transaction_hash": transaction_hash }))
+                })?;
+            let block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+
+            let mut events = starknet.get_block_events(BlockId::Number(block.header().block_number))?;
+            events.retain(|event| event.transaction_hash == transaction_hash);
+            events
+        }
+    };
+
+    // pending events are decoded with the classes of the latest block
+    let latest_block = starknet.current_block_number()?;
+
+    let mut abis = HashMap::<(FieldElement, u64), Option<EventAbi>>::new();
+    let mut decoded_events = Vec::with_capacity(events.len());
+    for event in events {
+        let block_number = event.block_number.unwrap_or(latest_block);
+        let abi = abis
+            .entry((event.from_address, block_number))
+            .or_insert_with(|| emitter_abi(event.from_address, block_number));
+
+        let (name, fields) = match abi.as_ref().and_then(|abi| abi.decode(&event.keys, &event.data)) {
+            Some((name, fields)) => (Some(name), Some(fields)),
+            None => (None, None),
+        };
+        decoded_events.push(DecodedEvent { event, name, fields });
+    }
+
+    Ok(decoded_events)
+}
+
+/// Returns the ABI of the class of `contract_address` at `block_number`, if it can be retrieved.
+fn emitter_abi(contract_address: FieldElement, block_number: u64) -> Option<EventAbi> {
+    let address = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
+    let class_hash = storage_handler::contract_data()
+        .get_class_hash_at(&address, block_number)
+        .map_err(|e| log::error!("Failed to get the class hash of '{contract_address:#x}': {e}"))
+        .ok()??;
+    let class = storage_handler::contract_class_data()
+        .get(&class_hash)
+        .map_err(|e| log::error!("Failed to get the class of '{contract_address:#x}': {e}"))
+        .ok()??;
+
+    EventAbi::new(class.abi)
+}
@@ -0,0 +1,213 @@
+use blockifier::transaction::account_transaction::AccountTransaction;
+use jsonrpsee::core::RpcResult;
+use mc_db::storage_handler;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_simulations::SimulationFlagForEstimateFee;
+use mp_transactions::from_broadcasted_transactions::ToAccountTransaction;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::{
+    BlockId, BroadcastedDeclareTransaction, BroadcastedDeployAccountTransaction, BroadcastedInvokeTransaction,
+    BroadcastedTransaction, FeeEstimate, FieldElement, SimulationFlagForEstimateFee as EstimateFeeFlag,
+};
+use starknet_core::utils::get_contract_address;
+
+use crate::errors::StarknetRpcApiError;
+use crate::utils::execution::estimate_fee_sequence;
+use crate::utils::helpers::request_block_context;
+use crate::Starknet;
+
+/// Maximum number of transactions accepted in a single `deoxys_estimateFeeBulk` request.
+pub const MAX_BULK_TRANSACTIONS: usize = 100;
+
+/// Returns the sender of a transaction, which for an account deployment is the address of the
+/// deployed account, derived from its deployment parameters.
+fn sender_address(transaction: &BroadcastedTransaction) -> FieldElement {
+    match transaction {
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(tx)) => tx.sender_address,
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V3(tx)) => tx.sender_address,
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V1(tx)) => tx.sender_address,
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V2(tx)) => tx.sender_address,
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V3(tx)) => tx.sender_address,
+        BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction::V1(tx)) => {
+            get_contract_address(tx.contract_address_salt, tx.class_hash, &tx.constructor_calldata, FieldElement::ZERO)
+        }
+        BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction::V3(tx)) => {
+            get_contract_address(tx.contract_address_salt, tx.class_hash, &tx.constructor_calldata, FieldElement::ZERO)
+        }
+    }
+}
+
+/// Whether every transaction is sent by `account`, an account deployment only being allowed to open
+/// the sequence.
+fn is_sent_by(transactions: &[BroadcastedTransaction], account: FieldElement) -> bool {
+    transactions.iter().enumerate().all(|(index, tx)| {
+        let is_deployment = matches!(tx, BroadcastedTransaction::DeployAccount(_));
+        sender_address(tx) == account && (index == 0 || !is_deployment)
+    })
+}
+
+fn set_nonce(transaction: &mut BroadcastedTransaction, nonce: FieldElement) {
+    match transaction {
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(tx)) => tx.nonce = nonce,
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V3(tx)) => tx.nonce = nonce,
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V1(tx)) => tx.nonce = nonce,
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V2(tx)) => tx.nonce = nonce,
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V3(tx)) => tx.nonce = nonce,
+        BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction::V1(tx)) => tx.nonce = nonce,
+        BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction::V3(tx)) => tx.nonce = nonce,
+    }
+}
+
+/// Estimate the Fees of a Dependent Sequence of Transactions From One Account
+///
+/// Unlike `starknet_estimateFee`, which estimates each transaction against the state of the block
+/// on its own, the transactions are executed one after the other, each one seeing the state changes
+/// of the ones before it. Their nonces are assigned in order starting from the nonce of the account
+/// at the block, and the nonces of the request are ignored.
+///
+/// ### Arguments
+///
+/// * `block_id` - The identifier of the block to execute the transactions against. This can be the
+///   hash of the block, its number (height), or a specific block tag.
+/// * `sender_address` - The account sending every transaction of the sequence. An account
+///   deployment may open the sequence, in which case it must deploy this account.
+/// * `transactions` - The transactions to estimate, in execution order.
+/// * `simulation_flags` - Flags applied to every transaction of the sequence.
+///
+/// ### Returns
+///
+/// One fee estimate per transaction, in the same order.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the specified block does not exist in the blockchain.
+/// * `PAGE_SIZE_TOO_BIG` - If more than [MAX_BULK_TRANSACTIONS] transactions are sent at once.
+/// * `MIXED_SENDERS` - If a transaction is not sent by `sender_address`, or if an account
+///   deployment does not deploy it or does not open the sequence.
+/// * `CONTRACT_ERROR` - If a transaction of the sequence fails.
+/// * `EXECUTION_DENIED` - If the sequence involves a class or entry point denied by the execution
+///   policy of the node.
+pub fn estimate_fee_bulk<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    block_id: BlockId,
+    sender_address: FieldElement,
+    mut transactions: Vec<BroadcastedTransaction>,
+    simulation_flags: Vec<EstimateFeeFlag>,
+) -> RpcResult<Vec<FeeEstimate>>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    if transactions.len() > MAX_BULK_TRANSACTIONS {
+        return Err(StarknetRpcApiError::PageSizeTooBig.into());
+    }
+    if !is_sent_by(&transactions, sender_address) {
+        return Err(StarknetRpcApiError::MixedSenders.with_data(json!({ "sender_address": sender_address })));
+    }
+
+    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
+        log::error!("'{e}'");
+        StarknetRpcApiError::BlockNotFound
+    })?;
+    let block_context = request_block_context(starknet, substrate_block_hash)?;
+
+    // the nonce of an account which is not deployed yet is zero
+    let address = ContractAddress(PatriciaKey(StarkFelt(sender_address.to_bytes_be())));
+    let nonce = storage_handler::contract_data()
+        .get_nonce_at(&address, block_context.block_info().block_number.0)
+        .map_err(|e| {
+            log::error!("Failed to get nonce at '{sender_address:?}': {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .map_or(FieldElement::ZERO, |nonce| Felt252Wrapper::from(nonce).into());
+
+    for (index, transaction) in transactions.iter_mut().enumerate() {
+        set_nonce(transaction, nonce + FieldElement::from(index));
+    }
+
+    let account_transactions = transactions
+        .into_iter()
+        .map(|tx| tx.to_account_transaction())
+        .collect::<Result<Vec<AccountTransaction>, _>>()
+        .map_err(|e| {
+            log::error!("Failed to convert BroadcastedTransaction to AccountTransaction: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+
+    let simulation_flags =
+        SimulationFlagForEstimateFee { skip_validate: simulation_flags.contains(&EstimateFeeFlag::SkipValidate) };
+
+    let policy = starknet.execution_policy.guard();
+    let fee_estimates = estimate_fee_sequence(account_transactions, simulation_flags, &block_context, &policy);
+    policy.check()?;
+    let fee_estimates = fee_estimates.map_err(|(index, e)| {
+        log::error!("Failed to estimate the fee of transaction {index} of the sequence: {e:#?}");
+        let data = json!({ "transaction_index": index, "revert_error": e.to_string() });
+        StarknetRpcApiError::ContractError.with_data(data)
+    })?;
+
+    Ok(fee_estimates)
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_core::types::{BroadcastedDeployAccountTransactionV1, BroadcastedInvokeTransactionV1};
+
+    use super::*;
+
+    fn invoke(sender_address: FieldElement) -> BroadcastedTransaction {
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(BroadcastedInvokeTransactionV1 {
+            sender_address,
+            calldata: vec![],
+            max_fee: FieldElement::ZERO,
+            signature: vec![],
+            nonce: FieldElement::ZERO,
+            is_query: true,
+        }))
+    }
+
+    fn deploy_account(class_hash: FieldElement) -> BroadcastedTransaction {
+        BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction::V1(
+            BroadcastedDeployAccountTransactionV1 {
+                max_fee: FieldElement::ZERO,
+                signature: vec![],
+                nonce: FieldElement::ZERO,
+                contract_address_salt: FieldElement::ONE,
+                constructor_calldata: vec![FieldElement::TWO],
+                class_hash,
+                is_query: true,
+            },
+        ))
+    }
+
+    #[test]
+    fn test_is_sent_by() {
+        let account = FieldElement::from(0x1234u64);
+        assert!(is_sent_by(&[invoke(account), invoke(account)], account));
+        assert!(!is_sent_by(&[invoke(account), invoke(FieldElement::ONE)], account));
+    }
+
+    #[test]
+    fn test_is_sent_by_with_account_deployment() {
+        let class_hash = FieldElement::from(0x5678u64);
+        let account = get_contract_address(FieldElement::ONE, class_hash, &[FieldElement::TWO], FieldElement::ZERO);
+
+        assert!(is_sent_by(&[deploy_account(class_hash), invoke(account)], account));
+        // the deployment must deploy the sender
+        assert!(!is_sent_by(&[deploy_account(FieldElement::ONE), invoke(account)], account));
+        // and open the sequence
+        assert!(!is_sent_by(&[invoke(account), deploy_account(class_hash)], account));
+    }
+}
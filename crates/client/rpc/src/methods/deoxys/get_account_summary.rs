@@ -0,0 +1,127 @@
+use blockifier::abi::abi_utils::get_fee_token_var_address;
+use jsonrpsee::core::RpcResult;
+use mc_db::storage_handler::{self, StorageView};
+use mc_db::DeoxysBackend;
+use mc_rpc_core::{AccountSummary, FeeTokenBalances, U256Balance};
+use mp_felt::Felt252Wrapper;
+use mp_genesis_config::{ETH_TOKEN_ADDR, STRK_TOKEN_ADDR};
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::FieldElement;
+
+use crate::errors::StarknetRpcApiError;
+use crate::Starknet;
+
+/// Number of blocks, up to the latest one, over which the recent transactions of an account are
+/// counted.
+pub const RECENT_TRANSACTIONS_BLOCKS: u64 = 1000;
+
+/// Summarize the State and Activity of an Account
+///
+/// Assembles, at the latest block and in a single request, what wallet dashboards usually fetch
+/// with one call each.
+///
+/// ### Arguments
+///
+/// * `contract_address` - The address of the account to summarize.
+///
+/// ### Returns
+///
+/// The nonce, class hash and fee token balances of the account, the block at which it was
+/// deployed and, when the node indexes transactions by sender, the number of transactions it sent
+/// over the last [RECENT_TRANSACTIONS_BLOCKS] blocks.
+///
+/// ### Errors
+///
+/// * `CONTRACT_NOT_FOUND` - If the contract is not deployed at the latest block.
+pub fn get_account_summary<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    contract_address: FieldElement,
+) -> RpcResult<AccountSummary>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    let block_number = starknet.current_block_number()?;
+    let address = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
+
+    let contract_data = storage_handler::contract_data().get(&address).map_err(|e| {
+        log::error!("Failed to get the data of contract '{contract_address:#x}': {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let (class_hash, first_seen_block) = contract_data
+        .as_ref()
+        .and_then(|contract_data| {
+            let class_hash = contract_data.class_hash.get_at(block_number)?;
+            let first_seen_block = contract_data.class_hash.0.first().map(|(block_number, _)| *block_number)?;
+            Some((*class_hash, first_seen_block))
+        })
+        .ok_or_else(|| {
+            StarknetRpcApiError::ContractNotFound.with_data(json!({ "contract_address": contract_address }))
+        })?;
+    // the class hashes set before the pruned blocks were dropped, but for the last one
+    let pruned_before = DeoxysBackend::meta().state_pruned_before().unwrap_or_default();
+    let first_seen_block = Some(first_seen_block).filter(|block| pruned_before.map_or(true, |pruned| *block >= pruned));
+    let nonce = contract_data
+        .and_then(|contract_data| contract_data.nonce.get_at(block_number).copied())
+        .map_or(FieldElement::ZERO, |nonce| Felt252Wrapper::from(nonce).into());
+
+    let balances = FeeTokenBalances {
+        eth: fee_token_balance(ETH_TOKEN_ADDR.0, address, block_number)?,
+        strk: fee_token_balance(STRK_TOKEN_ADDR.0, address, block_number)?,
+    };
+
+    let from_block = block_number.saturating_sub(RECENT_TRANSACTIONS_BLOCKS - 1);
+    let recent_transaction_count =
+        DeoxysBackend::mapping().count_transactions_by_sender(&address, from_block, block_number).map_err(|e| {
+            log::error!("Failed to count the transactions of '{contract_address:#x}': {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+
+    Ok(AccountSummary {
+        block_number,
+        nonce,
+        class_hash: Felt252Wrapper::from(class_hash).into(),
+        balances,
+        first_seen_block,
+        recent_transaction_count,
+    })
+}
+
+/// Reads the balance of `account` in a fee token, which is stored as a `u256` split in two felts.
+///
+/// The limbs are returned as they are stored, as their sum may not fit in a felt.
+fn fee_token_balance(
+    fee_token_address: FieldElement,
+    account: ContractAddress,
+    block_number: u64,
+) -> Result<U256Balance, StarknetRpcApiError> {
+    let fee_token_address = ContractAddress(PatriciaKey(StarkFelt(fee_token_address.to_bytes_be())));
+    let low_key = get_fee_token_var_address(account);
+    let high_key = low_key.next_storage_key().map_err(|e| {
+        log::error!("Failed to compute the fee token balance key of '{account:?}': {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    let read = |key| -> Result<FieldElement, StarknetRpcApiError> {
+        let value =
+            storage_handler::versioned_storage().get_at(&(fee_token_address, key), block_number).map_err(|e| {
+                log::error!("Failed to get the fee token balance of '{account:?}': {e}");
+                StarknetRpcApiError::InternalServerError
+            })?;
+        Ok(value.map_or(FieldElement::ZERO, |value| Felt252Wrapper::from(value).into()))
+    };
+
+    Ok(U256Balance { low: read(low_key)?, high: read(high_key)? })
+}
@@ -0,0 +1,99 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::storage_handler;
+use mc_rpc_core::ContractStoragePage;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+use starknet_core::types::{BlockId, FieldElement, StorageEntry};
+
+use crate::errors::StarknetRpcApiError;
+use crate::Starknet;
+
+/// Maximum number of storage entries returned by a single `deoxys_getContractStorage` request.
+pub const MAX_CONTRACT_STORAGE_PAGE_SIZE: u64 = 1000;
+
+/// Iterate Over the Storage of a Contract
+///
+/// ### Arguments
+///
+/// * `contract_address` - The address of the contract whose storage is iterated.
+/// * `block_id` - The identifier of the block at which the storage is read. This can be the hash of
+///   the block, its number (height), or a specific block tag.
+/// * `start_key` - The storage key to start from, included. Iteration starts from the lowest key
+///   when omitted.
+/// * `limit` - The maximum number of entries to return.
+///
+/// ### Returns
+///
+/// The non-zero storage entries of the contract, ordered by key, along with the key to pass as
+/// `start_key` to get the next page when there are more entries.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the specified block does not exist in the blockchain.
+/// * `CONTRACT_NOT_FOUND` - If the contract is not deployed at the specified block.
+/// * `PAGE_SIZE_TOO_BIG` - If `limit` exceeds [MAX_CONTRACT_STORAGE_PAGE_SIZE].
+pub fn get_contract_storage<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    contract_address: FieldElement,
+    block_id: BlockId,
+    start_key: Option<FieldElement>,
+    limit: u64,
+) -> RpcResult<ContractStoragePage>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    if limit > MAX_CONTRACT_STORAGE_PAGE_SIZE {
+        let data = json!({ "max_page_size": MAX_CONTRACT_STORAGE_PAGE_SIZE });
+        return Err(StarknetRpcApiError::PageSizeTooBig.with_data(data));
+    }
+
+    let block_number = starknet.substrate_block_number_from_starknet_block(block_id).map_err(|e| {
+        log::error!("'{e}'");
+        StarknetRpcApiError::BlockNotFound
+    })?;
+
+    let address = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
+    let is_deployed = storage_handler::contract_data()
+        .get_class_hash_at(&address, block_number)
+        .map_err(|e| {
+            log::error!("Failed to get class hash at '{contract_address:?}': {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .is_some();
+    if !is_deployed {
+        return Err(StarknetRpcApiError::ContractNotFound.with_data(json!({ "contract_address": contract_address })));
+    }
+
+    let start_key = StorageKey(PatriciaKey(StarkFelt(start_key.unwrap_or(FieldElement::ZERO).to_bytes_be())));
+    // one more entry is read to know whether there is a next page
+    let mut entries = storage_handler::contract_storage()
+        .get_contract_storage_at(&address, &start_key, block_number, limit as usize + 1)
+        .map_err(|e| {
+            log::error!("Failed to iterate the storage of '{contract_address:?}': {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .into_iter()
+        .map(|(key, value)| StorageEntry {
+            key: Felt252Wrapper::from(key.0.0).into(),
+            value: Felt252Wrapper::from(value).into(),
+        })
+        .collect::<Vec<_>>();
+
+    let continuation_key = if entries.len() as u64 > limit { entries.pop().map(|entry| entry.key) } else { None };
+
+    Ok(ContractStoragePage { entries, continuation_key })
+}
@@ -0,0 +1,73 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::{class_index, storage_handler};
+use mc_rpc_core::{ClassInstance, ContractsByClassPage};
+use mp_felt::Felt252Wrapper;
+use serde_json::json;
+use starknet_api::core::{ClassHash, ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::FieldElement;
+
+use crate::errors::StarknetRpcApiError;
+
+/// Maximum number of contracts returned by a single `deoxys_getContractsByClass` request.
+pub const MAX_CONTRACTS_BY_CLASS_PAGE_SIZE: u64 = 1000;
+
+/// Enumerate the Contracts of a Class
+///
+/// Contracts are indexed by class hash as their state updates are stored. The contracts stored
+/// before the index was introduced are indexed in the background when the node starts.
+///
+/// ### Arguments
+///
+/// * `class_hash` - The hash of the class whose contracts are enumerated.
+/// * `start_address` - The contract address to start from, included. Enumeration starts from the
+///   lowest address when omitted.
+/// * `limit` - The maximum number of contracts to return.
+///
+/// ### Returns
+///
+/// The contracts which were deployed with the class or replaced their class with it, ordered by
+/// address, along with the address to pass as `start_address` to get the next page when there are
+/// more contracts. Contracts which later replaced their class are still returned.
+///
+/// ### Errors
+///
+/// * `PAGE_SIZE_TOO_BIG` - If `limit` exceeds [MAX_CONTRACTS_BY_CLASS_PAGE_SIZE].
+/// * `HISTORICAL_DATA_NOT_BACKFILLED` - If the contracts stored before the index was introduced are
+///   still being indexed.
+pub fn get_contracts_by_class(
+    class_hash: FieldElement,
+    start_address: Option<FieldElement>,
+    limit: u64,
+) -> RpcResult<ContractsByClassPage> {
+    if limit > MAX_CONTRACTS_BY_CLASS_PAGE_SIZE {
+        let data = json!({ "max_page_size": MAX_CONTRACTS_BY_CLASS_PAGE_SIZE });
+        return Err(StarknetRpcApiError::PageSizeTooBig.with_data(data));
+    }
+    if !class_index::is_complete() {
+        return Err(StarknetRpcApiError::HistoricalDataNotBackfilled.into());
+    }
+
+    let class_hash_key = ClassHash(StarkFelt(class_hash.to_bytes_be()));
+    let start_address =
+        ContractAddress(PatriciaKey(StarkFelt(start_address.unwrap_or(FieldElement::ZERO).to_bytes_be())));
+
+    // one more contract is read to know whether there is a next page
+    let mut contracts = storage_handler::contract_data()
+        .get_contracts_by_class(&class_hash_key, &start_address, limit as usize + 1)
+        .map_err(|e| {
+            log::error!("Failed to enumerate the contracts of class '{class_hash:#x}': {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .into_iter()
+        .map(|(contract_address, block_number)| ClassInstance {
+            contract_address: Felt252Wrapper::from(contract_address.0.0).into(),
+            block_number,
+        })
+        .collect::<Vec<_>>();
+
+    let continuation_address =
+        if contracts.len() as u64 > limit { contracts.pop().map(|contract| contract.contract_address) } else { None };
+
+    Ok(ContractsByClassPage { contracts, continuation_address })
+}
@@ -0,0 +1,62 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+use mc_rpc_core::{BlockRange, DataAvailability};
+use mp_types::block::DBlockT;
+use sp_arithmetic::traits::UniqueSaturatedInto;
+use sp_blockchain::HeaderBackend;
+
+use crate::errors::StarknetRpcApiError;
+use crate::Starknet;
+
+/// Returns which block ranges have their headers, bodies, state and receipts held locally, so that
+/// historical queries can be routed to nodes which actually hold the data.
+pub fn get_data_availability<BE, C, H>(starknet: &Starknet<BE, C, H>) -> RpcResult<DataAvailability>
+where
+    C: HeaderBackend<DBlockT> + 'static,
+{
+    let backfill = DeoxysBackend::backfill().range().map_err(|e| {
+        log::error!("Failed to read backfill range: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    let latest = UniqueSaturatedInto::<u64>::unique_saturated_into(starknet.client.info().best_number);
+    // with a trusted root, the node holds the full data of the blocks synced after it only
+    let synced = BlockRange::new(backfill.map_or(0, |range| range.end), latest);
+    let backfilled = backfill.and_then(|range| BlockRange::new(range.lowest, range.end.checked_sub(1)?));
+
+    let bodies = merge(backfilled.into_iter().chain(synced));
+    let header_chain = starknet.sync_state.header_range().and_then(|(first, last)| BlockRange::new(first, last));
+    let headers = merge(bodies.iter().copied().chain(header_chain));
+    let state: Vec<_> = synced.into_iter().collect();
+
+    Ok(DataAvailability { headers, bodies, receipts: state.clone(), state })
+}
+
+/// Sorts block ranges and merges those which overlap or are adjacent.
+fn merge(ranges: impl IntoIterator<Item = BlockRange>) -> Vec<BlockRange> {
+    let mut ranges: Vec<_> = ranges.into_iter().collect();
+    ranges.sort_by_key(|range| range.first);
+
+    let mut merged: Vec<BlockRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.first <= last.last.saturating_add(1) => last.last = last.last.max(range.last),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let range = |first, last| BlockRange { first, last };
+
+        assert_eq!(merge([range(10, 20), range(0, 9), range(30, 40)]), vec![range(0, 20), range(30, 40)]);
+        assert_eq!(merge([range(0, 20), range(5, 10)]), vec![range(0, 20)]);
+        assert_eq!(merge([]), vec![]);
+    }
+}
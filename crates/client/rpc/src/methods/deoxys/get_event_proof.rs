@@ -0,0 +1,110 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+use mc_rpc_core::EventProof;
+use mc_sync::commitments::events::memory_event_proof;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::FieldElement;
+
+use super::get_receipt_proof::merkle_node;
+use crate::deoxys_backend_client::get_block_by_block_hash;
+use crate::errors::StarknetRpcApiError;
+use crate::utils::helpers::{tx_hash_compute, tx_hash_retrieve};
+use crate::Starknet;
+
+/// Get the Inclusion Proof of an Event in its Block
+///
+/// The proof is given against the event commitment of the block header, which is itself part of
+/// the block hash: once that block hash is anchored on L1, the event can be verified without
+/// trusting this node.
+///
+/// ### Arguments
+///
+/// * `transaction_hash` - The hash of the transaction which emitted the event.
+/// * `event_index` - The index of the event among the events emitted by that transaction.
+///
+/// ### Returns
+///
+/// The block containing the event, the leaf committed for the event and the Merkle-Patricia proof
+/// of that leaf against the event commitment.
+///
+/// ### Errors
+///
+/// * `TXN_HASH_NOT_FOUND` - If the transaction is not part of a finalized block.
+/// * `INVALID_TXN_INDEX` - If the transaction did not emit an event at `event_index`.
+pub fn get_event_proof<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    transaction_hash: FieldElement,
+    event_index: u64,
+) -> RpcResult<EventProof>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    let substrate_block_hash = DeoxysBackend::mapping()
+        .block_hash_from_transaction_hash(Felt252Wrapper::from(transaction_hash).into())
+        .map_err(|e| {
+            log::error!("Failed to get transaction's substrate block hash from mapping_db: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .ok_or_else(|| {
+            StarknetRpcApiError::TxnHashNotFound.with_data(json!({ "transaction_hash": transaction_hash }))
+        })?;
+
+    let block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+    let block_header = block.header();
+    let block_number = block_header.block_number;
+    let chain_id = starknet.chain_id()?;
+    let block_hash: Felt252Wrapper = block_header.hash::<H>(chain_id.0.into());
+
+    let block_txs_hashes = if let Some(tx_hashes) = starknet.get_cached_transaction_hashes(block_hash.into()) {
+        tx_hash_retrieve(tx_hashes)
+    } else {
+        tx_hash_compute::<H>(&block, chain_id)
+    };
+
+    let tx_index = block_txs_hashes.iter().position(|hash| hash == &transaction_hash).ok_or_else(|| {
+        log::error!("Failed to retrieve transaction index from block with hash {block_hash:?}");
+        StarknetRpcApiError::InternalServerError
+    })? as u128;
+
+    // events are committed in emission order, which is the order of their transactions
+    let preceding_events: usize =
+        block.events().iter().filter(|ordered| ordered.index() < tx_index).map(|ordered| ordered.events().len()).sum();
+    let tx_event_count =
+        block.events().iter().find(|ordered| ordered.index() == tx_index).map_or(0, |ordered| ordered.events().len());
+    if event_index as usize >= tx_event_count {
+        return Err(StarknetRpcApiError::InvalidTxnIndex.into());
+    }
+    let block_event_index = preceding_events + event_index as usize;
+
+    let events: Vec<_> = block.events().iter().flat_map(|ordered| ordered.events().iter().cloned()).collect();
+    let (event_commitment, leaf, proof) = memory_event_proof(&events, block_event_index).map_err(|e| {
+        log::error!("Failed to compute event proof: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    if Felt252Wrapper::from(block_header.event_commitment) != event_commitment {
+        log::error!("Recomputed event commitment doesn't match the one of block #{block_number}");
+        return Err(StarknetRpcApiError::InternalServerError.into());
+    }
+
+    Ok(EventProof {
+        block_hash: block_hash.into(),
+        block_number,
+        event_index: block_event_index as u64,
+        event_commitment: event_commitment.into(),
+        leaf,
+        proof: proof.into_iter().map(merkle_node).collect(),
+    })
+}
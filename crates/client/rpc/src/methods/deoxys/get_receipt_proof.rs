@@ -0,0 +1,108 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+use mc_rpc_core::{MerkleNode, TransactionProof};
+use mc_sync::commitments::lib::ProofNode;
+use mc_sync::commitments::transactions::memory_transaction_proof;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::FieldElement;
+
+use crate::deoxys_backend_client::get_block_by_block_hash;
+use crate::errors::StarknetRpcApiError;
+use crate::utils::helpers::{tx_hash_compute, tx_hash_retrieve};
+use crate::Starknet;
+
+/// Converts a node of a proof computed by the sync to its rpc form.
+pub(crate) fn merkle_node(node: ProofNode) -> MerkleNode {
+    match node {
+        ProofNode::Binary { left, right } => MerkleNode::Binary { left, right },
+        ProofNode::Edge { child, path, length } => MerkleNode::Edge { child, path, length },
+    }
+}
+
+/// Get the Inclusion Proof of a Transaction in its Block
+///
+/// The proof is given against the transaction commitment of the block header, which commits to
+/// the transactions and their signatures. Block headers do not commit to receipts in the
+/// supported Starknet versions, so the receipt itself should be obtained through
+/// `starknet_getTransactionReceipt` and checked against the proven transaction.
+///
+/// ### Arguments
+///
+/// * `transaction_hash` - The hash of the transaction to prove.
+///
+/// ### Returns
+///
+/// The block containing the transaction, the leaf committed for the transaction and the
+/// Merkle-Patricia proof of that leaf against the transaction commitment.
+///
+/// ### Errors
+///
+/// * `TXN_HASH_NOT_FOUND` - If the transaction is not part of a finalized block.
+pub fn get_receipt_proof<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    transaction_hash: FieldElement,
+) -> RpcResult<TransactionProof>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    let substrate_block_hash = DeoxysBackend::mapping()
+        .block_hash_from_transaction_hash(Felt252Wrapper::from(transaction_hash).into())
+        .map_err(|e| {
+            log::error!("Failed to get transaction's substrate block hash from mapping_db: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .ok_or_else(|| {
+            StarknetRpcApiError::TxnHashNotFound.with_data(json!({ "transaction_hash": transaction_hash }))
+        })?;
+
+    let block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+    let block_header = block.header();
+    let block_number = block_header.block_number;
+    let chain_id = starknet.chain_id()?;
+    let block_hash: Felt252Wrapper = block_header.hash::<H>(chain_id.0.into());
+
+    let block_txs_hashes = if let Some(tx_hashes) = starknet.get_cached_transaction_hashes(block_hash.into()) {
+        tx_hash_retrieve(tx_hashes)
+    } else {
+        tx_hash_compute::<H>(&block, chain_id)
+    };
+
+    let tx_index = block_txs_hashes.iter().position(|hash| hash == &transaction_hash).ok_or_else(|| {
+        log::error!("Failed to retrieve transaction index from block with hash {block_hash:?}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    let (transaction_commitment, leaf, proof) =
+        memory_transaction_proof(block.transactions(), Felt252Wrapper(chain_id.0), block_number, tx_index).map_err(
+            |e| {
+                log::error!("Failed to compute transaction proof: {e}");
+                StarknetRpcApiError::InternalServerError
+            },
+        )?;
+
+    if Felt252Wrapper::from(block_header.transaction_commitment) != transaction_commitment {
+        log::error!("Recomputed transaction commitment doesn't match the one of block #{block_number}");
+        return Err(StarknetRpcApiError::InternalServerError.into());
+    }
+
+    Ok(TransactionProof {
+        block_hash: block_hash.into(),
+        block_number,
+        transaction_index: tx_index as u64,
+        transaction_commitment: transaction_commitment.into(),
+        leaf,
+        proof: proof.into_iter().map(merkle_node).collect(),
+    })
+}
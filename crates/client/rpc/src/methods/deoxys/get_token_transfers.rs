@@ -0,0 +1,116 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::{DeoxysBackend, TokenTransfer};
+use mc_rpc_core::{TokenTransferEntry, TokenTransfersPage};
+use mp_felt::Felt252Wrapper;
+use serde_json::json;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::FieldElement;
+
+use crate::errors::StarknetRpcApiError;
+
+/// Maximum number of transfers returned by a single `deoxys_getTokenTransfers` request.
+pub const MAX_TOKEN_TRANSFERS_PAGE_SIZE: u64 = 1000;
+
+/// Converts a transfer indexed by the database to its rpc form.
+fn token_transfer_entry(transfer: TokenTransfer) -> TokenTransferEntry {
+    TokenTransferEntry {
+        block_number: transfer.block_number,
+        transaction_index: transfer.transaction_index,
+        event_index: transfer.event_index,
+        token_address: Felt252Wrapper::from(transfer.token_address).into(),
+        from: Felt252Wrapper::from(transfer.from).into(),
+        to: Felt252Wrapper::from(transfer.to).into(),
+        amount_low: Felt252Wrapper::from(transfer.amount.0).into(),
+        amount_high: Felt252Wrapper::from(transfer.amount.1).into(),
+    }
+}
+
+/// Formats the position of a transfer as a continuation token.
+fn format_continuation_token(transfer: &TokenTransferEntry) -> String {
+    format!("{}-{}-{}", transfer.block_number, transfer.transaction_index, transfer.event_index)
+}
+
+/// Parses a continuation token into the position of the transfer it designates.
+fn parse_continuation_token(token: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = token.split('-').map(str::parse::<u64>);
+    let position = (parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?);
+    parts.next().is_none().then_some(position)
+}
+
+/// Get the Balance Change History of an Account
+///
+/// ERC-20 transfers are decoded from the `Transfer` events as blocks are stored, only when the
+/// node runs with the `--index-transfers` flag: blocks stored before are not indexed.
+///
+/// ### Arguments
+///
+/// * `account` - The account whose transfers are returned, as the sender or the recipient.
+/// * `token_address` - The token to return the transfers of. Every token when omitted.
+/// * `from_block` - The number of the first block to search, included. Defaults to the genesis.
+/// * `to_block` - The number of the last block to search, included. Defaults to the latest block.
+/// * `continuation_token` - The token returned with the previous page, to get the next one.
+/// * `limit` - The maximum number of transfers to return.
+///
+/// ### Returns
+///
+/// The transfers in chain order, along with the token to pass to get the next page when there are
+/// more transfers in the range.
+///
+/// ### Errors
+///
+/// * `PAGE_SIZE_TOO_BIG` - If `limit` exceeds [MAX_TOKEN_TRANSFERS_PAGE_SIZE].
+/// * `INVALID_CONTINUATION_TOKEN` - If the continuation token cannot be parsed.
+pub fn get_token_transfers(
+    account: FieldElement,
+    token_address: Option<FieldElement>,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    continuation_token: Option<String>,
+    limit: u64,
+) -> RpcResult<TokenTransfersPage> {
+    if limit > MAX_TOKEN_TRANSFERS_PAGE_SIZE {
+        let data = json!({ "max_page_size": MAX_TOKEN_TRANSFERS_PAGE_SIZE });
+        return Err(StarknetRpcApiError::PageSizeTooBig.with_data(data));
+    }
+
+    let start = match continuation_token {
+        Some(token) => parse_continuation_token(&token).ok_or_else(|| {
+            log::error!("Failed to parse continuation token: {token}");
+            StarknetRpcApiError::InvalidContinuationToken
+        })?,
+        None => (from_block.unwrap_or(0), 0, 0),
+    };
+
+    let address = ContractAddress(PatriciaKey(StarkFelt(account.to_bytes_be())));
+    let token_address = token_address.map(|token| ContractAddress(PatriciaKey(StarkFelt(token.to_bytes_be()))));
+
+    // one more transfer is read to know whether there is a next page
+    let mut transfers = DeoxysBackend::transfers()
+        .transfers(&address, token_address.as_ref(), start, to_block.unwrap_or(u64::MAX), limit as usize + 1)
+        .map_err(|e| {
+            log::error!("Failed to get the transfers of '{account:#x}': {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .into_iter()
+        .map(token_transfer_entry)
+        .collect::<Vec<_>>();
+
+    let continuation_token =
+        if transfers.len() as u64 > limit { transfers.pop().as_ref().map(format_continuation_token) } else { None };
+
+    Ok(TokenTransfersPage { transfers, continuation_token })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_continuation_token() {
+        assert_eq!(parse_continuation_token("12-3-4"), Some((12, 3, 4)));
+        assert_eq!(parse_continuation_token("12-3"), None);
+        assert_eq!(parse_continuation_token("12-3-4-5"), None);
+        assert_eq!(parse_continuation_token("12-a-4"), None);
+    }
+}
@@ -0,0 +1,91 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
+use mc_rpc_core::{SenderTransaction, TransactionsBySenderPage};
+use mp_felt::Felt252Wrapper;
+use serde_json::json;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::FieldElement;
+
+use crate::errors::StarknetRpcApiError;
+use crate::types::ContinuationToken;
+
+/// Maximum number of transactions returned by a single `deoxys_getTransactionsBySender` request.
+pub const MAX_TRANSACTIONS_BY_SENDER_PAGE_SIZE: u64 = 1000;
+
+/// Enumerate the Transactions Sent by an Account
+///
+/// Transactions are indexed by sender as their blocks are stored, only when the node runs with the
+/// `--cache` flag: blocks stored before are not indexed. Account deployments are sent by the
+/// account they deploy, while L1 handler and legacy deploy transactions have no sender.
+///
+/// ### Arguments
+///
+/// * `sender_address` - The account whose transactions are enumerated.
+/// * `from_block` - The number of the first block to search, included. Defaults to the genesis.
+/// * `to_block` - The number of the last block to search, included. Defaults to the latest block.
+/// * `continuation_token` - The token returned with the previous page, to get the next one.
+/// * `limit` - The maximum number of transactions to return.
+///
+/// ### Returns
+///
+/// The transactions ordered by block and by index in the block, along with the token to pass to
+/// get the next page when there are more transactions in the range.
+///
+/// ### Errors
+///
+/// * `PAGE_SIZE_TOO_BIG` - If `limit` exceeds [MAX_TRANSACTIONS_BY_SENDER_PAGE_SIZE].
+/// * `INVALID_CONTINUATION_TOKEN` - If the continuation token cannot be parsed.
+/// * `UNIMPLEMENTED_METHOD` - If transactions are not indexed by sender.
+pub fn get_transactions_by_sender(
+    sender_address: FieldElement,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    continuation_token: Option<String>,
+    limit: u64,
+) -> RpcResult<TransactionsBySenderPage> {
+    if limit > MAX_TRANSACTIONS_BY_SENDER_PAGE_SIZE {
+        let data = json!({ "max_page_size": MAX_TRANSACTIONS_BY_SENDER_PAGE_SIZE });
+        return Err(StarknetRpcApiError::PageSizeTooBig.with_data(data));
+    }
+
+    let start = match continuation_token {
+        Some(token) => ContinuationToken::parse(token).map_err(|e| {
+            log::error!("Failed to parse continuation token: {:?}", e);
+            StarknetRpcApiError::InvalidContinuationToken
+        })?,
+        None => ContinuationToken { block_n: from_block.unwrap_or(0), event_n: 0 },
+    };
+
+    let address = ContractAddress(PatriciaKey(StarkFelt(sender_address.to_bytes_be())));
+    let to_block = to_block.unwrap_or(u64::MAX);
+
+    // one more transaction is read to know whether there is a next page
+    let mut transactions = DeoxysBackend::mapping()
+        .transactions_by_sender(&address, (start.block_n, start.event_n), to_block, limit as usize + 1)
+        .map_err(|e| {
+            log::error!("Failed to enumerate the transactions of '{sender_address:#x}': {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .ok_or_else(|| {
+            let data = json!({ "reason": "transactions are only indexed by sender when the node runs with --cache" });
+            StarknetRpcApiError::UnimplementedMethod.with_data(data)
+        })?
+        .into_iter()
+        .map(|(block_number, transaction_index, transaction_hash)| SenderTransaction {
+            transaction_hash: Felt252Wrapper::from(transaction_hash).into(),
+            block_number,
+            transaction_index,
+        })
+        .collect::<Vec<_>>();
+
+    let continuation_token = if transactions.len() as u64 > limit {
+        transactions
+            .pop()
+            .map(|next| ContinuationToken { block_n: next.block_number, event_n: next.transaction_index }.to_string())
+    } else {
+        None
+    };
+
+    Ok(TransactionsBySenderPage { transactions, continuation_token })
+}
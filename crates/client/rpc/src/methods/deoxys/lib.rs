@@ -0,0 +1,166 @@
+use jsonrpsee::core::{async_trait, RpcResult};
+use mc_db::DeoxysBackend;
+use mc_rpc_core::{
+    AccountSummary, BackfillRange, BlockContextRequest, BlockContextResult, BlockHeader, CallTrace,
+    ContractStoragePage, ContractsByClassPage, DataAvailability, DecodedEvent, EventProof, EventsSource, SyncProgress,
+    SyncStatus, TokenTransfersPage, TransactionProof, TransactionsBySenderPage,
+};
+use mc_sync::l2;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::{
+    BlockId, BroadcastedTransaction, FeeEstimate, FieldElement, FunctionCall, SimulationFlagForEstimateFee,
+};
+
+use super::decode_events::*;
+use super::estimate_fee_bulk::*;
+use super::get_account_summary::*;
+use super::get_contract_storage::*;
+use super::get_contracts_by_class::*;
+use super::get_data_availability::*;
+use super::get_event_proof::*;
+use super::get_receipt_proof::*;
+use super::get_token_transfers::*;
+use super::get_transactions_by_sender::*;
+use super::trace_call::*;
+use super::with_block_context::*;
+use crate::deoxys_backend_client::get_block_by_block_hash;
+use crate::errors::StarknetRpcApiError;
+use crate::{DeoxysRpcApiServer, ExtendedBlockId, Starknet};
+
+#[async_trait]
+impl<BE, C, H> DeoxysRpcApiServer for Starknet<BE, C, H>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    fn with_block_context(
+        &self,
+        block_id: ExtendedBlockId,
+        requests: Vec<BlockContextRequest>,
+    ) -> RpcResult<Vec<BlockContextResult>> {
+        self.pin_state(block_id)?.run(|block_id| with_block_context(self, block_id, requests))
+    }
+
+    fn estimate_fee_bulk(
+        &self,
+        block_id: ExtendedBlockId,
+        sender_address: FieldElement,
+        transactions: Vec<BroadcastedTransaction>,
+        simulation_flags: Vec<SimulationFlagForEstimateFee>,
+    ) -> RpcResult<Vec<FeeEstimate>> {
+        self.pin_state(block_id)?
+            .run(|block_id| estimate_fee_bulk(self, block_id, sender_address, transactions, simulation_flags))
+    }
+
+    fn trace_call(&self, request: FunctionCall, block_id: ExtendedBlockId) -> RpcResult<CallTrace> {
+        self.pin_state(block_id)?.run(|block_id| trace_call(self, request, block_id))
+    }
+
+    fn get_contract_storage(
+        &self,
+        contract_address: FieldElement,
+        block_id: ExtendedBlockId,
+        start_key: Option<FieldElement>,
+        limit: u64,
+    ) -> RpcResult<ContractStoragePage> {
+        self.pin_state(block_id)?
+            .run(|block_id| get_contract_storage(self, contract_address, block_id, start_key, limit))
+    }
+
+    fn get_contracts_by_class(
+        &self,
+        class_hash: FieldElement,
+        start_address: Option<FieldElement>,
+        limit: u64,
+    ) -> RpcResult<ContractsByClassPage> {
+        get_contracts_by_class(class_hash, start_address, limit)
+    }
+
+    fn decode_events(&self, source: EventsSource) -> RpcResult<Vec<DecodedEvent>> {
+        decode_events(self, source)
+    }
+
+    fn get_account_summary(&self, contract_address: FieldElement) -> RpcResult<AccountSummary> {
+        get_account_summary(self, contract_address)
+    }
+
+    fn get_token_transfers(
+        &self,
+        account: FieldElement,
+        token_address: Option<FieldElement>,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        continuation_token: Option<String>,
+        limit: u64,
+    ) -> RpcResult<TokenTransfersPage> {
+        get_token_transfers(account, token_address, from_block, to_block, continuation_token, limit)
+    }
+
+    fn get_transactions_by_sender(
+        &self,
+        sender_address: FieldElement,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        continuation_token: Option<String>,
+        limit: u64,
+    ) -> RpcResult<TransactionsBySenderPage> {
+        get_transactions_by_sender(sender_address, from_block, to_block, continuation_token, limit)
+    }
+
+    fn get_receipt_proof(&self, transaction_hash: FieldElement) -> RpcResult<TransactionProof> {
+        get_receipt_proof(self, transaction_hash)
+    }
+
+    fn get_event_proof(&self, transaction_hash: FieldElement, event_index: u64) -> RpcResult<EventProof> {
+        get_event_proof(self, transaction_hash, event_index)
+    }
+
+    fn get_block_header(&self, block_number: u64) -> RpcResult<BlockHeader> {
+        if let Some(header) = self.sync_state.header(block_number) {
+            return Ok(BlockHeader { block_number: header.block_number, block_hash: header.block_hash });
+        }
+        // the headers of the applied blocks are no longer held by the header chain
+        let substrate_block_hash = self.substrate_block_hash_from_starknet_block(BlockId::Number(block_number))?;
+        let block = get_block_by_block_hash(self.client.as_ref(), substrate_block_hash)
+            .map_err(|_| StarknetRpcApiError::BlockNotFound)?;
+        let chain_id = self.chain_id()?.0.into();
+        Ok(BlockHeader { block_number, block_hash: block.header().hash::<H>(chain_id).into() })
+    }
+
+    fn get_data_availability(&self) -> RpcResult<DataAvailability> {
+        get_data_availability(self)
+    }
+
+    fn get_backfill_range(&self) -> RpcResult<Option<BackfillRange>> {
+        let range = DeoxysBackend::backfill().range().map_err(|_| StarknetRpcApiError::InternalServerError)?;
+        Ok(range.map(|range| BackfillRange { lowest: range.lowest, end: range.end }))
+    }
+
+    fn get_sync_progress(&self) -> RpcResult<SyncProgress> {
+        let progress = self.sync_state.sync_progress();
+        Ok(SyncProgress {
+            current_block: progress.current_block,
+            highest_block: progress.highest_block,
+            blocks_per_second: progress.blocks_per_second,
+            eta_seconds: progress.eta_seconds,
+            status: sync_status(progress.status),
+        })
+    }
+}
+
+fn sync_status(status: l2::SyncStatus) -> SyncStatus {
+    match status {
+        l2::SyncStatus::SyncVerifiedState => SyncStatus::SyncVerifiedState,
+        l2::SyncStatus::SyncUnverifiedState => SyncStatus::SyncUnverifiedState,
+        l2::SyncStatus::SyncPendingState => SyncStatus::SyncPendingState,
+    }
+}
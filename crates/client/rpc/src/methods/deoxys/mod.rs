@@ -0,0 +1,13 @@
+pub mod decode_events;
+pub mod estimate_fee_bulk;
+pub mod get_account_summary;
+pub mod get_contract_storage;
+pub mod get_contracts_by_class;
+pub mod get_data_availability;
+pub mod get_event_proof;
+pub mod get_receipt_proof;
+pub mod get_token_transfers;
+pub mod get_transactions_by_sender;
+pub mod lib;
+pub mod trace_call;
+pub mod with_block_context;
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use jsonrpsee::core::RpcResult;
+use mc_db::storage_handler;
+use mc_rpc_core::CallTrace;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_api::transaction::Calldata;
+use starknet_core::types::{BlockId, FunctionCall};
+
+use crate::errors::StarknetRpcApiError;
+use crate::methods::trace::utils::try_get_funtion_invocation_from_call_info;
+use crate::utils::execution::trace_call as execute_traced_call;
+use crate::utils::helpers::request_block_context;
+use crate::utils::revert_reason::decode_revert_reason;
+use crate::Starknet;
+
+/// Trace a Function Call Without Creating a Transaction
+///
+/// Executes the call like `starknet_call`, but returns its whole call tree instead of its return
+/// value alone, which helps debugging view functions failing deep inside library calls.
+///
+/// ### Arguments
+///
+/// * `request` - The details of the function call to trace.
+/// * `block_id` - The identifier of the block used to reference the state to call against. This can
+///   be the hash of the block, its number (height), or a specific block tag.
+///
+/// ### Returns
+///
+/// The trace of the call. A call which panicked is still traced, with its panic data as result
+/// and as `revert_reason`.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the specified block does not exist in the blockchain.
+/// * `CONTRACT_NOT_FOUND` - If the specified contract address does not exist.
+/// * `CONTRACT_ERROR` - If the call could not be executed to completion, in which case the error
+///   chain is returned as `revert_error`.
+/// * `EXECUTION_DENIED` - If the call involves a class or entry point denied by the execution
+///   policy of the node.
+pub fn trace_call<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    request: FunctionCall,
+    block_id: BlockId,
+) -> RpcResult<CallTrace>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
+        log::error!("'{e}'");
+        StarknetRpcApiError::BlockNotFound
+    })?;
+    let block_context = request_block_context(starknet, substrate_block_hash)?;
+    let block_number = block_context.block_info().block_number.0;
+
+    let contract_address = ContractAddress(PatriciaKey(StarkFelt(request.contract_address.to_bytes_be())));
+    let class_hash = storage_handler::contract_data()
+        .get_class_hash_at(&contract_address, block_number)
+        .map_err(|e| {
+            log::error!("Failed to get class hash at '{contract_address:?}': {e}");
+            StarknetRpcApiError::InternalServerError
+        })?
+        .ok_or_else(|| {
+            StarknetRpcApiError::ContractNotFound.with_data(json!({ "contract_address": request.contract_address }))
+        })?;
+
+    let calldata = Calldata(Arc::new(request.calldata.iter().map(|x| Felt252Wrapper::from(*x).into()).collect()));
+    let policy = starknet.execution_policy.guard();
+    let call_info = execute_traced_call(
+        contract_address,
+        class_hash,
+        Felt252Wrapper(request.entry_point_selector).into(),
+        calldata,
+        &block_context,
+        &policy,
+    );
+    policy.check()?;
+    let call_info = call_info.map_err(|e| {
+        log::debug!("Failed to trace call: {e:#?}");
+        StarknetRpcApiError::ContractError.with_data(json!({ "revert_error": e.to_string() }))
+    })?;
+
+    let revert_reason = call_info.execution.failed.then(|| {
+        let panic_data = call_info.execution.retdata.0.iter().map(|x| format!("{:#x}", Felt252Wrapper::from(*x).0));
+        format!("({})", panic_data.collect::<Vec<_>>().join(", "))
+    });
+    let revert_reason_decoded =
+        revert_reason.as_deref().filter(|_| starknet.decode_revert_reasons).and_then(decode_revert_reason);
+
+    let function_invocation = try_get_funtion_invocation_from_call_info(&call_info, &mut HashMap::new(), block_number)
+        .map_err(|e| {
+            log::error!("Failed to convert the call info of the traced call: {e}");
+            StarknetRpcApiError::InternalServerError
+        })?;
+
+    Ok(CallTrace { function_invocation, revert_reason, revert_reason_decoded })
+}
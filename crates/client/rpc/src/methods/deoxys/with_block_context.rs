@@ -0,0 +1,107 @@
+use jsonrpsee::core::RpcResult;
+use mc_rpc_core::{BlockContextError, BlockContextRequest, BlockContextResponse, BlockContextResult};
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::BlockId;
+
+use crate::errors::StarknetRpcApiError;
+use crate::methods::read::call::call_with_context;
+use crate::methods::read::estimate_fee::estimate_fee_with_context;
+use crate::methods::read::estimate_message_fee::estimate_message_fee_with_context;
+use crate::utils::helpers::request_block_context;
+use crate::{Starknet, StarknetReadRpcApiServer};
+
+/// Maximum number of requests accepted in a single `deoxys_withBlockContext` batch.
+pub const MAX_BLOCK_CONTEXT_REQUESTS: usize = 1000;
+
+/// The outcome of a single request of the batch: a failing request does not abort the others.
+fn block_context_result(result: Result<BlockContextResponse, StarknetRpcApiError>) -> BlockContextResult {
+    match result {
+        Ok(response) => BlockContextResult::Result(response),
+        Err(err) => BlockContextResult::Error(BlockContextError { code: err as i32, message: err.to_string() }),
+    }
+}
+
+/// Run a Batch of Calls and Fee Estimations Against a Single Block
+///
+/// The execution context of the block is resolved once and shared by every request of the batch,
+/// which makes replaying many reads at one height much cheaper than issuing them one by one.
+///
+/// ### Arguments
+///
+/// * `block_id` - The identifier of the block to execute the requests against. This can be the hash
+///   of the block, its number (height), or a specific block tag.
+/// * `requests` - The calls, fee estimates and message fee estimates to run, in order.
+///
+/// ### Returns
+///
+/// One result per request, in the same order. A request failing does not affect the others.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the specified block does not exist in the blockchain.
+/// * `PAGE_SIZE_TOO_BIG` - If more than [MAX_BLOCK_CONTEXT_REQUESTS] requests are sent at once.
+pub fn with_block_context<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    block_id: BlockId,
+    requests: Vec<BlockContextRequest>,
+) -> RpcResult<Vec<BlockContextResult>>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    if requests.len() > MAX_BLOCK_CONTEXT_REQUESTS {
+        return Err(StarknetRpcApiError::PageSizeTooBig.into());
+    }
+
+    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
+        log::error!("'{e}'");
+        StarknetRpcApiError::BlockNotFound
+    })?;
+    let block_context = request_block_context(starknet, substrate_block_hash)?;
+
+    // the message is handled as part of the requested block, not of the latest one
+    let block_number = starknet.substrate_block_number_from_starknet_block(block_id).map_err(|e| {
+        log::error!("'{e}'");
+        StarknetRpcApiError::BlockNotFound
+    })?;
+    let chain_id = Felt252Wrapper(starknet.chain_id()?.0);
+
+    let results = requests
+        .into_iter()
+        .map(|request| match request {
+            BlockContextRequest::Call { request } => call_with_context(
+                request,
+                substrate_block_hash,
+                &block_context,
+                &starknet.execution_policy,
+                &starknet.call_cache,
+            )
+            .map(BlockContextResponse::Call),
+            BlockContextRequest::EstimateFee { request, simulation_flags } => {
+                estimate_fee_with_context(request, simulation_flags, &block_context, &starknet.execution_policy)
+                    .map(BlockContextResponse::EstimateFee)
+            }
+            BlockContextRequest::EstimateMessageFee { message } => estimate_message_fee_with_context::<H>(
+                message,
+                chain_id,
+                block_number,
+                &block_context,
+                &starknet.execution_policy,
+            )
+            .map(BlockContextResponse::EstimateMessageFee),
+        })
+        .map(block_context_result)
+        .collect();
+
+    Ok(results)
+}
@@ -1,8 +1,8 @@
 use jsonrpsee::core::error::Error;
 use jsonrpsee::core::RpcResult;
-use mc_sync::l2::get_pending_block;
+use mp_block::DeoxysBlock;
 use mp_hashers::HasherT;
-use mp_types::block::{DBlockT, DHashT};
+use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
@@ -13,7 +13,6 @@ use starknet_core::types::{
     PendingBlockWithTxs,
 };
 
-use crate::deoxys_backend_client::get_block_by_block_hash;
 use crate::utils::block::{
     l1_da_mode, l1_data_gas_price, l1_gas_price, new_root, parent_hash, sequencer_address, starknet_version, timestamp,
 };
@@ -23,7 +22,7 @@ use crate::{Felt, Starknet};
 pub(crate) fn get_block_with_tx_hashes_finalized<BE, C, H>(
     server: &Starknet<BE, C, H>,
     chain_id: Felt,
-    substrate_block_hash: DHashT,
+    starknet_block: DeoxysBlock,
 ) -> RpcResult<MaybePendingBlockWithTxHashes>
 where
     BE: Backend<DBlockT> + 'static,
@@ -32,9 +31,7 @@ where
     C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
     H: HasherT + Send + Sync + 'static,
 {
-    let starknet_block = get_block_by_block_hash(server.client.as_ref(), substrate_block_hash)?;
-
-    let block_hash = starknet_block.header().hash::<H>();
+    let block_hash = starknet_block.header().hash::<H>(chain_id.0.into());
     let transactions = if let Some(tx_hashes) = server.get_cached_transaction_hashes(block_hash.into()) {
         tx_hash_retrieve(tx_hashes)
     } else {
@@ -70,11 +67,14 @@ where
     Ok(MaybePendingBlockWithTxHashes::Block(block_with_tx_hashes))
 }
 
-pub(crate) fn get_block_with_tx_hashes_pending<H>(chain_id: Felt) -> RpcResult<MaybePendingBlockWithTxHashes>
+pub(crate) fn get_block_with_tx_hashes_pending<H>(
+    pending_block: Option<DeoxysBlock>,
+    chain_id: Felt,
+) -> RpcResult<MaybePendingBlockWithTxHashes>
 where
     H: HasherT + Send + Sync + 'static,
 {
-    let starknet_block = get_pending_block()
+    let starknet_block = pending_block
         .ok_or(Error::Custom("Failed to retrieve pending block, node not yet synchronized".to_string()))?;
 
     let transactions = tx_hash_compute::<H>(&starknet_block, chain_id);
@@ -103,7 +103,7 @@ where
 pub(crate) fn get_block_with_txs_finalized<BE, C, H>(
     server: &Starknet<BE, C, H>,
     chain_id: Felt,
-    substrate_block_hash: DHashT,
+    starknet_block: DeoxysBlock,
 ) -> RpcResult<MaybePendingBlockWithTxs>
 where
     BE: Backend<DBlockT> + 'static,
@@ -112,9 +112,7 @@ where
     C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
     H: HasherT + Send + Sync + 'static,
 {
-    let starknet_block = get_block_by_block_hash(server.client.as_ref(), substrate_block_hash)?;
-
-    let block_hash = starknet_block.header().hash::<H>();
+    let block_hash = starknet_block.header().hash::<H>(chain_id.0.into());
     let tx_hashes = if let Some(tx_hashes) = server.get_cached_transaction_hashes(block_hash.into()) {
         tx_hash_retrieve(tx_hashes)
     } else {
@@ -151,11 +149,14 @@ where
     Ok(MaybePendingBlockWithTxs::Block(block_with_txs))
 }
 
-pub(crate) fn get_block_with_txs_pending<H>(chain_id: Felt) -> RpcResult<MaybePendingBlockWithTxs>
+pub(crate) fn get_block_with_txs_pending<H>(
+    pending_block: Option<DeoxysBlock>,
+    chain_id: Felt,
+) -> RpcResult<MaybePendingBlockWithTxs>
 where
     H: HasherT + Send + Sync + 'static,
 {
-    let starknet_block = get_pending_block()
+    let starknet_block = pending_block
         .ok_or(Error::Custom("Failed to retrieve pending block, node not yet synchronized".to_string()))?;
 
     let tx_hashes = tx_hash_compute::<H>(&starknet_block, chain_id);
@@ -1,4 +1,7 @@
+pub mod admin;
+pub mod deoxys;
 pub mod get_block;
+pub mod pathfinder;
 pub mod read;
 pub mod trace;
 pub mod write;
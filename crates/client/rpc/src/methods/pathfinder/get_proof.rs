@@ -0,0 +1,264 @@
+use jsonrpsee::core::RpcResult;
+use mc_db::storage_handler;
+use mc_rpc_core::{ContractData, EdgePath, GetProofOutput, TrieNode};
+use mc_sync::commitments::lib::ProofNode;
+use mc_sync::commitments::storage_proof::{get_storage_proof, StorageProof};
+use mp_felt::Felt252Wrapper;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkFelt;
+use starknet_api::state::StorageKey;
+use starknet_core::types::{BlockId, FieldElement};
+
+use crate::deoxys_backend_client::get_block_by_block_hash;
+use crate::errors::StarknetRpcApiError;
+use crate::utils::block::new_root;
+use crate::Starknet;
+
+/// Maximum number of storage keys proven by a single `pathfinder_getProof` request.
+pub const MAX_PROOF_KEYS: usize = 100;
+
+/// Converts a node of a proof computed by the sync to the form served by pathfinder.
+fn trie_node(node: ProofNode) -> TrieNode {
+    match node {
+        ProofNode::Binary { left, right } => TrieNode::Binary { left, right },
+        ProofNode::Edge { child, path, length } => {
+            TrieNode::Edge { child, path: EdgePath { value: path, len: length } }
+        }
+    }
+}
+
+/// Get the Merkle-Patricia Proof of Storage Values of a Contract
+///
+/// Compatible with `pathfinder_getProof`, so that light clients can verify storage values against
+/// the global state root of a block header.
+///
+/// ### Arguments
+///
+/// * `block_id` - The identifier of the block at which the storage is proven. This can be the hash
+///   of the block, its number (height), or a specific block tag.
+/// * `contract_address` - The address of the contract whose storage is proven.
+/// * `keys` - The storage keys to prove, at most [MAX_PROOF_KEYS].
+///
+/// ### Returns
+///
+/// The state commitment, the proof of the contract leaf in the contracts trie and, if the contract
+/// is deployed, the fields hashed into its leaf along with the proof of every key in its storage
+/// trie.
+///
+/// ### Errors
+///
+/// * `BLOCK_NOT_FOUND` - If the specified block does not exist in the blockchain.
+/// * `PROOF_LIMIT_EXCEEDED` - If more than [MAX_PROOF_KEYS] keys are requested.
+/// * `PROOF_MISSING` - If the state tries are no longer available at the specified block: they are
+///   only kept for the last [TRIE_SNAPSHOTS_KEPT](mc_db::TRIE_SNAPSHOTS_KEPT) blocks.
+pub fn get_proof<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    block_id: BlockId,
+    contract_address: FieldElement,
+    keys: Vec<FieldElement>,
+) -> RpcResult<GetProofOutput>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    if keys.len() > MAX_PROOF_KEYS {
+        return Err(StarknetRpcApiError::ProofLimitExceeded.with_data(json!({ "max_keys": MAX_PROOF_KEYS })));
+    }
+
+    let block_number = starknet.substrate_block_number_from_starknet_block(block_id).map_err(|e| {
+        log::error!("'{e}'");
+        StarknetRpcApiError::BlockNotFound
+    })?;
+    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(BlockId::Number(block_number))?;
+    let block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+
+    let address = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
+    let storage_keys = keys.iter().map(|key| StorageKey(PatriciaKey(StarkFelt(key.to_bytes_be())))).collect::<Vec<_>>();
+    let proof = get_storage_proof(block_number, &address, &storage_keys).map_err(|e| {
+        log::error!("Failed to compute the storage proof of '{contract_address:#x}': {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+
+    // the tries are only kept for the last blocks, and only match the header if the state root was
+    // verified when syncing the block
+    let proof = match proof {
+        Some(proof) if proof.state_commitment == new_root(&block) => proof,
+        _ => return Err(StarknetRpcApiError::ProofMissing.with_data(json!({ "block_number": block_number }))),
+    };
+
+    proof_output(proof, contract_address, block_number)
+}
+
+/// Serves a proof computed by the sync, along with the fields hashed into the contract leaf.
+fn proof_output(proof: StorageProof, contract_address: FieldElement, block_number: u64) -> RpcResult<GetProofOutput> {
+    let address = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
+    let contract_data = storage_handler::contract_data();
+    let class_hash = contract_data.get_class_hash_at(&address, block_number).map_err(|e| {
+        log::error!("Failed to get class hash at '{contract_address:#x}': {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let contract_data = match class_hash {
+        Some(class_hash) => {
+            let nonce = contract_data.get_nonce_at(&address, block_number).map_err(|e| {
+                log::error!("Failed to get nonce at '{contract_address:#x}': {e}");
+                StarknetRpcApiError::InternalServerError
+            })?;
+
+            Some(ContractData {
+                class_hash: Felt252Wrapper::from(class_hash).into(),
+                nonce: nonce.map_or(FieldElement::ZERO, |nonce| Felt252Wrapper::from(nonce).into()),
+                root: proof.storage_root,
+                contract_state_hash_version: FieldElement::ZERO,
+                storage_proofs: proof
+                    .storage_proofs
+                    .into_iter()
+                    .map(|nodes| nodes.into_iter().map(trie_node).collect())
+                    .collect(),
+            })
+        }
+        None => None,
+    };
+
+    Ok(GetProofOutput {
+        state_commitment: proof.state_commitment,
+        class_commitment: proof.classes_trie_root,
+        contract_proof: proof.contract_proof.into_iter().map(trie_node).collect(),
+        contract_data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::state::cached_state::CommitmentStateDiff;
+    use mc_db::storage_handler::StorageViewMut;
+    use mc_db::DeoxysBackend;
+    use mc_sync::commitments::lib::{calculate_state_root, update_state_root};
+    use mp_hashers::pedersen::PedersenHasher;
+    use mp_hashers::poseidon::PoseidonHasher;
+    use starknet_api::core::{ClassHash, CompiledClassHash, Nonce};
+
+    use super::*;
+
+    /// The hash of a node of a Merkle-Patricia trie.
+    fn node_hash(node: &TrieNode) -> FieldElement {
+        match node {
+            TrieNode::Binary { left, right } => PedersenHasher::hash_elements(*left, *right),
+            TrieNode::Edge { child, path } => {
+                PedersenHasher::hash_elements(*child, path.value) + FieldElement::from(path.len as u64)
+            }
+        }
+    }
+
+    /// Verifies that `proof` leads from the node hashing to `root` down to `leaf` at `key`.
+    fn verify(root: FieldElement, key: FieldElement, leaf: FieldElement, proof: &[TrieNode]) -> bool {
+        // the 251 bits of the key, from the root of the trie down
+        let bits = key.to_bits_le();
+        let mut bits = bits[..251].iter().rev().copied();
+        let mut expected = root;
+        for node in proof {
+            if node_hash(node) != expected {
+                return false;
+            }
+            expected = match node {
+                TrieNode::Binary { left, right } => {
+                    if bits.next() == Some(true) {
+                        *right
+                    } else {
+                        *left
+                    }
+                }
+                TrieNode::Edge { child, path } => {
+                    let walked = bits.by_ref().take(path.len).fold(FieldElement::ZERO, |acc, bit| {
+                        acc + acc + if bit { FieldElement::ONE } else { FieldElement::ZERO }
+                    });
+                    if walked != path.value {
+                        return false;
+                    }
+                    *child
+                }
+            };
+        }
+        bits.next().is_none() && expected == leaf
+    }
+
+    #[test]
+    fn trie_nodes_are_serialized_as_by_pathfinder() {
+        let binary = trie_node(ProofNode::Binary { left: FieldElement::ONE, right: FieldElement::TWO });
+        let edge = trie_node(ProofNode::Edge { child: FieldElement::THREE, path: FieldElement::ONE, length: 2 });
+
+        assert_eq!(serde_json::to_value(binary).unwrap(), json!({ "binary": { "left": "0x1", "right": "0x2" } }));
+        assert_eq!(
+            serde_json::to_value(edge).unwrap(),
+            json!({ "edge": { "child": "0x3", "path": { "value": "0x1", "len": 2 } } })
+        );
+    }
+
+    #[test]
+    fn proofs_are_verified_against_the_state_root() {
+        let _db = DeoxysBackend::open_for_testing();
+        let felt = |n: u64| StarkFelt::from(n);
+        let address = ContractAddress(PatriciaKey(felt(0x2762)));
+        let other = ContractAddress(PatriciaKey(felt(0x2763)));
+        let key = |n: u64| StorageKey(PatriciaKey(felt(n)));
+        let block_number = 2760;
+
+        let contract_data = storage_handler::contract_data_mut();
+        contract_data.insert(address, (Some(ClassHash(felt(0x2761))), Some(Nonce(felt(2))))).unwrap();
+        contract_data.insert(other, (Some(ClassHash(felt(0x2761))), None)).unwrap();
+        contract_data.commit(block_number).unwrap();
+
+        let csd = CommitmentStateDiff {
+            address_to_class_hash: [(address, ClassHash(felt(0x2761))), (other, ClassHash(felt(0x2761)))]
+                .into_iter()
+                .collect(),
+            address_to_nonce: [(address, Nonce(felt(2)))].into_iter().collect(),
+            storage_updates: [
+                (address, [(key(1), felt(0x10)), (key(2), felt(0x20))].into_iter().collect()),
+                (other, [(key(1), felt(0x30))].into_iter().collect()),
+            ]
+            .into_iter()
+            .collect(),
+            class_hash_to_compiled_class_hash: [(ClassHash(felt(0x2761)), CompiledClassHash(felt(0x2764)))]
+                .into_iter()
+                .collect(),
+        };
+        let state_root: FieldElement = update_state_root(csd, block_number).into();
+
+        let contract_address = FieldElement::from(0x2762u64);
+        let keys = [FieldElement::ONE, FieldElement::TWO];
+        let storage_keys = [key(1), key(2)];
+        let proof = get_storage_proof(block_number, &address, &storage_keys).unwrap().unwrap();
+        let output = proof_output(proof, contract_address, block_number).unwrap();
+        assert_eq!(output.state_commitment, state_root);
+
+        // the root of the contracts trie is the hash of the first node of the contract proof
+        let contracts_root = node_hash(&output.contract_proof[0]);
+        let state_commitment =
+            calculate_state_root::<PoseidonHasher>(contracts_root.into(), output.class_commitment.into());
+        assert_eq!(FieldElement::from(state_commitment), output.state_commitment);
+
+        let contract_data = output.contract_data.unwrap();
+        let leaf = PedersenHasher::hash_elements(contract_data.class_hash, contract_data.root);
+        let leaf = PedersenHasher::hash_elements(leaf, contract_data.nonce);
+        let leaf = PedersenHasher::hash_elements(leaf, contract_data.contract_state_hash_version);
+        assert!(verify(contracts_root, contract_address, leaf, &output.contract_proof));
+
+        for (key, value, proof) in
+            [(keys[0], 0x10u64, &contract_data.storage_proofs[0]), (keys[1], 0x20, &contract_data.storage_proofs[1])]
+        {
+            assert!(verify(contract_data.root, key, FieldElement::from(value), proof));
+            assert!(!verify(contract_data.root, key, FieldElement::from(value + 1), proof));
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use jsonrpsee::core::{async_trait, RpcResult};
+use mc_rpc_core::GetProofOutput;
+use mp_hashers::HasherT;
+use mp_types::block::DBlockT;
+use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
+use sc_client_api::backend::{Backend, StorageProvider};
+use sc_client_api::BlockBackend;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::FieldElement;
+
+use super::get_proof::*;
+use crate::{ExtendedBlockId, PathfinderRpcApiServer, Starknet};
+
+#[async_trait]
+impl<BE, C, H> PathfinderRpcApiServer for Starknet<BE, C, H>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    fn get_proof(
+        &self,
+        block_id: ExtendedBlockId,
+        contract_address: FieldElement,
+        keys: Vec<FieldElement>,
+    ) -> RpcResult<GetProofOutput> {
+        self.pin_block(block_id)?.run(|block_id| get_proof(self, block_id, contract_address, keys))
+    }
+}
@@ -30,13 +30,17 @@ where
     H: HasherT + Send + Sync + 'static,
 {
     let block_number = starknet.current_block_number()?;
-    let block_hash = starknet.current_block_hash().map_err(|e| {
-        log::error!("Failed to retrieve the current block hash: {}", e);
-        StarknetRpcApiError::NoBlocks
-    })?;
+    // the hash is looked up for the same block number, even if a block is applied in the meantime
+    let block_hash = match starknet.sync_state.cached_header(block_number) {
+        Some(cached) => cached.block_hash,
+        None => {
+            let block_hash = starknet.current_block_hash().map_err(|e| {
+                log::error!("Failed to retrieve the current block hash: {}", e);
+                StarknetRpcApiError::NoBlocks
+            })?;
+            FieldElement::from_byte_slice_be(block_hash.as_bytes()).unwrap()
+        }
+    };
 
-    Ok(BlockHashAndNumber {
-        block_hash: FieldElement::from_byte_slice_be(block_hash.as_bytes()).unwrap(),
-        block_number,
-    })
+    Ok(BlockHashAndNumber { block_hash, block_number })
 }
@@ -1,7 +1,9 @@
+use blockifier::context::BlockContext;
 use jsonrpsee::core::RpcResult;
+use mc_db::storage_handler;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
-use mp_types::block::DBlockT;
+use mp_types::block::{DBlockT, DHashT};
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
@@ -11,8 +13,9 @@ use starknet_api::transaction::Calldata;
 use starknet_core::types::{BlockId, FunctionCall};
 
 use crate::errors::StarknetRpcApiError;
-use crate::utils::execution::block_context;
-use crate::utils::helpers::previous_substrate_block_hash;
+use crate::execution_policy::ExecutionPolicy;
+use crate::utils::cache::{CachedCall, CallCache, CallKey};
+use crate::utils::helpers::request_block_context;
 use crate::{utils, Arc, Starknet};
 
 /// Call a Function in a Contract Without Creating a Transaction
@@ -35,6 +38,8 @@ use crate::{utils, Arc, Starknet};
 /// * `CONTRACT_NOT_FOUND` - If the specified contract address does not exist.
 /// * `CONTRACT_ERROR` - If there is an error with the contract or the function call.
 /// * `BLOCK_NOT_FOUND` - If the specified block does not exist in the blockchain.
+/// * `EXECUTION_DENIED` - If the call involves a class or entry point denied by the execution
+///   policy of the node.
 pub fn call<BE, C, H>(starknet: &Starknet<BE, C, H>, request: FunctionCall, block_id: BlockId) -> RpcResult<Vec<String>>
 where
     BE: Backend<DBlockT> + 'static,
@@ -48,23 +53,66 @@ where
         StarknetRpcApiError::BlockNotFound
     })?;
 
-    let previous_substrate_block_hash = previous_substrate_block_hash(starknet, substrate_block_hash)?;
-    let block_context = block_context(starknet.client.as_ref(), previous_substrate_block_hash)?;
+    let block_context = request_block_context(starknet, substrate_block_hash)?;
 
+    Ok(call_with_context(
+        request,
+        substrate_block_hash,
+        &block_context,
+        &starknet.execution_policy,
+        &starknet.call_cache,
+    )?)
+}
+
+/// Calls a contract function against an already resolved block context.
+///
+/// The calls made at a block are memoized in `call_cache`, so that repeating a call at the same
+/// block does not execute it again.
+pub(crate) fn call_with_context(
+    request: FunctionCall,
+    substrate_block_hash: DHashT,
+    block_context: &BlockContext,
+    execution_policy: &ExecutionPolicy,
+    call_cache: &CallCache,
+) -> Result<Vec<String>, StarknetRpcApiError> {
+    let contract_address = Felt252Wrapper(request.contract_address).into();
+    let entry_point_selector = Felt252Wrapper(request.entry_point_selector).into();
     let calldata = Calldata(Arc::new(request.calldata.iter().map(|x| Felt252Wrapper::from(*x).into()).collect()));
+    // the class is resolved the way the call resolves it, so that replacing the class of the contract
+    // does not serve the results of its former class
+    let class_hash = storage_handler::contract_data().get_class_hash(&contract_address).map_err(|e| {
+        log::error!("Failed to retrieve the class hash of the contract: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let key = CallKey::new(
+        substrate_block_hash,
+        block_context,
+        class_hash,
+        contract_address,
+        entry_point_selector,
+        calldata.clone(),
+    );
 
-    let result = utils::execution::call_contract(
-        Felt252Wrapper(request.contract_address).into(),
-        Felt252Wrapper(request.entry_point_selector).into(),
-        calldata,
-        &block_context,
-    )
-    .map_err(|_| {
+    let policy = execution_policy.guard();
+    let call = match call_cache.get(&key) {
+        Some(call) => {
+            policy.inspect_entry_points(&call.entry_points);
+            Ok(call)
+        }
+        None => {
+            utils::execution::call_contract(contract_address, entry_point_selector, calldata, block_context, &policy)
+                .map(|call_info| {
+                    let call = CachedCall::new(&call_info);
+                    call_cache.insert(key, call.clone());
+                    call
+                })
+        }
+    };
+    policy.check()?;
+    let call = call.map_err(|_| {
         log::error!("Request parameters error");
         StarknetRpcApiError::InternalServerError
     })?;
 
-    // let result = convert_error(starknet.client.clone(), substrate_block_hash, result)?;
-
-    Ok(result.iter().map(|x| format!("{:#x}", x.0)).collect())
+    Ok(call.retdata.iter().map(|x| format!("{:#x}", Felt252Wrapper::from(*x).0)).collect())
 }
@@ -1,3 +1,4 @@
+use blockifier::context::BlockContext;
 use blockifier::transaction::account_transaction::AccountTransaction;
 use jsonrpsee::core::RpcResult;
 use mp_hashers::HasherT;
@@ -7,6 +8,7 @@ use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
+use serde_json::json;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{
@@ -14,8 +16,8 @@ use starknet_core::types::{
 };
 
 use crate::errors::StarknetRpcApiError;
-use crate::utils::execution::block_context;
-use crate::utils::helpers::previous_substrate_block_hash;
+use crate::execution_policy::ExecutionPolicy;
+use crate::utils::helpers::request_block_context;
 use crate::{utils, Starknet};
 
 /// Estimate the fee associated with transaction
@@ -46,9 +48,18 @@ where
         StarknetRpcApiError::BlockNotFound
     })?;
 
-    let previous_substrate_block_hash = previous_substrate_block_hash(starknet, substrate_block_hash)?;
-    let block_context = block_context(starknet.client.as_ref(), previous_substrate_block_hash)?;
+    let block_context = request_block_context(starknet, substrate_block_hash)?;
 
+    Ok(estimate_fee_with_context(request, simulation_flags, &block_context, &starknet.execution_policy)?)
+}
+
+/// Estimates the fee of the given transactions against an already resolved block context.
+pub(crate) fn estimate_fee_with_context(
+    request: Vec<BroadcastedTransaction>,
+    simulation_flags: Vec<EstimateFeeFlag>,
+    block_context: &BlockContext,
+    execution_policy: &ExecutionPolicy,
+) -> Result<Vec<FeeEstimate>, StarknetRpcApiError> {
     let transactions = request
         .into_iter()
         .map(|tx| tx.to_account_transaction())
@@ -63,11 +74,13 @@ where
 
     let simulation_flags = convert_flags(simulation_flags);
 
-    let fee_estimates = utils::execution::estimate_fee(account_transactions, &simulation_flags, &block_context)
-        .map_err(|e| {
-            log::error!("Failed to call function: {:#?}", e);
-            StarknetRpcApiError::ContractError
-        })?;
+    let policy = execution_policy.guard();
+    let fee_estimates = utils::execution::estimate_fee(account_transactions, &simulation_flags, block_context, &policy);
+    policy.check()?;
+    let fee_estimates = fee_estimates.map_err(|e| {
+        log::error!("Failed to call function: {:#?}", e);
+        StarknetRpcApiError::ContractError.with_data(json!({ "revert_error": e.to_string() }))
+    })?;
 
     let estimates = fee_estimates
         .into_iter()
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use blockifier::context::BlockContext;
 use blockifier::transaction::transactions::L1HandlerTransaction;
 use jsonrpsee::core::RpcResult;
 use log::error;
@@ -18,8 +19,8 @@ use starknet_api::transaction::{Calldata, Fee, TransactionVersion};
 use starknet_core::types::{BlockId, FeeEstimate, MsgFromL1};
 
 use crate::errors::StarknetRpcApiError;
-use crate::utils::execution::block_context;
-use crate::utils::helpers::previous_substrate_block_hash;
+use crate::execution_policy::ExecutionPolicy;
+use crate::utils::helpers::request_block_context;
 use crate::{utils, Starknet, StarknetReadRpcApiServer};
 
 /// Estimate the L2 fee of a message sent on L1
@@ -38,6 +39,7 @@ use crate::{utils, Starknet, StarknetReadRpcApiServer};
 /// BlockNotFound : If the specified block does not exist.
 /// ContractNotFound : If the specified contract address does not exist.
 /// ContractError : If there is an error with the contract.
+/// ExecutionDenied : If the message handler is denied by the execution policy of the node.
 pub async fn estimate_message_fee<BE, C, H>(
     starknet: &Starknet<BE, C, H>,
     message: MsgFromL1,
@@ -54,18 +56,38 @@ where
         log::error!("'{e}'");
         StarknetRpcApiError::BlockNotFound
     })?;
-    let previous_substrate_block_hash = previous_substrate_block_hash(starknet, substrate_block_hash)?;
-    let block_context = block_context(starknet.client.as_ref(), previous_substrate_block_hash)?;
+    let block_context = request_block_context(starknet, substrate_block_hash)?;
 
-    let block_number = starknet.block_number().map_err(|e| {
+    // the message is handled as part of the requested block, not of the latest one
+    let block_number = starknet.substrate_block_number_from_starknet_block(block_id).map_err(|e| {
         log::error!("'{e}'");
         StarknetRpcApiError::BlockNotFound
     })?;
     let chain_id = Felt252Wrapper(starknet.chain_id()?.0);
 
+    Ok(estimate_message_fee_with_context::<H>(
+        message,
+        chain_id,
+        block_number,
+        &block_context,
+        &starknet.execution_policy,
+    )?)
+}
+
+/// Estimates the L2 fee of an L1 message against an already resolved block context.
+pub(crate) fn estimate_message_fee_with_context<H: HasherT + Send + Sync + 'static>(
+    message: MsgFromL1,
+    chain_id: Felt252Wrapper,
+    block_number: u64,
+    block_context: &BlockContext,
+    execution_policy: &ExecutionPolicy,
+) -> Result<FeeEstimate, StarknetRpcApiError> {
     let transaction = convert_message_into_tx::<H>(message, chain_id, Some(block_number));
 
-    let message_fee = utils::execution::estimate_message_fee(transaction, &block_context).map_err(|e| {
+    let policy = execution_policy.guard();
+    let message_fee = utils::execution::estimate_message_fee(transaction, block_context, &policy);
+    policy.check()?;
+    let message_fee = message_fee.map_err(|e| {
         error!("Function execution failed: {:#?}", e);
         StarknetRpcApiError::ContractError
     })?;
@@ -1,4 +1,5 @@
 use jsonrpsee::core::RpcResult;
+use mc_db::DeoxysBackend;
 use mp_hashers::HasherT;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
@@ -6,7 +7,7 @@ use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::BlockId;
+use starknet_core::types::{BlockId, BlockTag};
 
 use crate::deoxys_backend_client::get_block_by_block_hash;
 use crate::errors::StarknetRpcApiError;
@@ -21,7 +22,8 @@ use crate::Starknet;
 ///
 /// ### Returns
 ///
-/// * `transaction_count` - The number of transactions in the specified block.
+/// * `transaction_count` - The number of transactions in the specified block, or in the pending
+///   block for the `pending` tag.
 ///
 /// ### Errors
 ///
@@ -35,12 +37,29 @@ where
     C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
     H: HasherT + Send + Sync + 'static,
 {
+    if let BlockId::Tag(BlockTag::Pending) = block_id {
+        let pending_block = starknet.pending_block().ok_or(StarknetRpcApiError::BlockNotFound)?;
+        return Ok(pending_block.transactions().len() as u128);
+    }
+    if let Some(starknet_block) = starknet.backfilled_block(block_id) {
+        return Ok(starknet_block.header().transaction_count);
+    }
+
     let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
         log::error!("'{e}'");
         StarknetRpcApiError::BlockNotFound
     })?;
 
-    let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
-
-    Ok(starknet_block.header().transaction_count)
+    // the count is recorded at import, older blocks have to be decoded
+    match DeoxysBackend::mapping().transaction_count(substrate_block_hash) {
+        Ok(Some(transaction_count)) => Ok(transaction_count),
+        Ok(None) => {
+            let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+            Ok(starknet_block.header().transaction_count)
+        }
+        Err(e) => {
+            log::error!("Failed to read transaction count: {e}");
+            Err(StarknetRpcApiError::InternalServerError.into())
+        }
+    }
 }
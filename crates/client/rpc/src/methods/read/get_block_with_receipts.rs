@@ -1,4 +1,5 @@
 use jsonrpsee::core::RpcResult;
+use mc_rpc_core::{DecodedRevertReason, WithDecodedRevertReasons};
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::to_starknet_core_transaction::to_starknet_core_tx;
@@ -20,15 +21,16 @@ use crate::errors::StarknetRpcApiError;
 use crate::utils::block::{
     l1_da_mode, l1_data_gas_price, l1_gas_price, new_root, parent_hash, sequencer_address, starknet_version, timestamp,
 };
-use crate::utils::execution::{block_context, re_execute_transactions};
-use crate::utils::helpers::{previous_substrate_block_hash, status, tx_hash_compute, tx_hash_retrieve};
+use crate::utils::execution::re_execute_transactions;
+use crate::utils::helpers::{previous_block_context, status, tx_hash_compute, tx_hash_retrieve};
+use crate::utils::revert_reason::decode_revert_reason;
 use crate::utils::transaction::blockifier_transactions;
 use crate::Starknet;
 
 pub fn get_block_with_receipts<BE, C, H>(
     starknet: &Starknet<BE, C, H>,
     block_id: BlockId,
-) -> RpcResult<MaybePendingBlockWithReceipts>
+) -> RpcResult<WithDecodedRevertReasons<MaybePendingBlockWithReceipts>>
 where
     BE: Backend<DBlockT> + 'static,
     C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
@@ -43,12 +45,11 @@ where
     let block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
     let block_header = block.header();
     let block_number = block_header.block_number;
-    let block_hash: Felt252Wrapper = block_header.hash::<H>();
     let chain_id = starknet.chain_id()?;
+    let block_hash: Felt252Wrapper = block_header.hash::<H>(chain_id.0.into());
 
     // computes the previous SUBSTRATE block hash and creates a block context
-    let previous_substrate_block_hash = previous_substrate_block_hash(starknet, substrate_block_hash)?;
-    let block_context = block_context(starknet.client.as_ref(), previous_substrate_block_hash)?;
+    let block_context = previous_block_context(starknet, substrate_block_hash)?;
 
     // retrieve all transaction hashes from the block in the cache or compute them
     let block_txs_hashes = if let Some(tx_hashes) = starknet.get_cached_transaction_hashes(block_hash.into()) {
@@ -82,12 +83,22 @@ where
 
     let receipts: Vec<TransactionReceipt> = execution_infos
         .iter()
-        .zip(transaction_with_hash)
+        .zip(&transaction_with_hash)
         .map(|(execution_info, (transaction, transaction_hash))| {
-            receipt(&transaction, execution_info, transaction_hash, block_number)
+            receipt(transaction, execution_info, *transaction_hash, block_number)
         })
         .collect::<Result<Vec<_>, _>>()?;
 
+    let revert_reasons_decoded = execution_infos
+        .iter()
+        .zip(&transaction_with_hash)
+        .filter(|_| starknet.decode_revert_reasons)
+        .filter_map(|(execution_info, (_, transaction_hash))| {
+            let revert_reason_decoded = decode_revert_reason(execution_info.revert_error.as_deref()?)?;
+            Some(DecodedRevertReason { transaction_hash: *transaction_hash, revert_reason_decoded })
+        })
+        .collect();
+
     let transactions_with_receipts = transactions_core
         .into_iter()
         .zip(receipts)
@@ -114,11 +125,11 @@ where
         };
 
         let pending_block = MaybePendingBlockWithReceipts::PendingBlock(pending_block_with_receipts);
-        Ok(pending_block)
+        Ok(WithDecodedRevertReasons { inner: pending_block, revert_reasons_decoded })
     } else {
         let block_with_receipts = BlockWithReceipts {
             status: status(starknet_block.header().block_number),
-            block_hash: starknet_block.header().hash::<H>().into(),
+            block_hash: block_hash.into(),
             parent_hash: parent_hash(&starknet_block),
             block_number: starknet_block.header().block_number,
             new_root: new_root(&starknet_block),
@@ -130,6 +141,7 @@ where
             starknet_version: starknet_version(&starknet_block),
             transactions: transactions_with_receipts,
         };
-        Ok(MaybePendingBlockWithReceipts::Block(block_with_receipts))
+        let block = MaybePendingBlockWithReceipts::Block(block_with_receipts);
+        Ok(WithDecodedRevertReasons { inner: block, revert_reasons_decoded })
     }
 }
@@ -8,6 +8,7 @@ use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{BlockId, BlockTag, MaybePendingBlockWithTxs};
 
+use crate::deoxys_backend_client::get_block_by_block_hash;
 use crate::errors::StarknetRpcApiError;
 use crate::{get_block_with_txs_finalized, get_block_with_txs_pending, Starknet};
 
@@ -41,13 +42,20 @@ where
     H: HasherT + Send + Sync + 'static,
 {
     let chain_id = starknet.chain_id()?;
+    if let Some(starknet_block) = starknet.backfilled_block(block_id) {
+        return get_block_with_txs_finalized(starknet, chain_id, starknet_block);
+    }
+
     let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
         log::error!("Block not found: '{e}'");
         StarknetRpcApiError::BlockNotFound
     })?;
 
     match block_id {
-        BlockId::Tag(BlockTag::Pending) => get_block_with_txs_pending::<H>(chain_id),
-        _ => get_block_with_txs_finalized(starknet, chain_id, substrate_block_hash),
+        BlockId::Tag(BlockTag::Pending) => get_block_with_txs_pending::<H>(starknet.pending_block(), chain_id),
+        _ => {
+            let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+            get_block_with_txs_finalized(starknet, chain_id, starknet_block)
+        }
     }
 }
@@ -2,6 +2,7 @@ use jsonrpsee::core::RpcResult;
 use mc_db::storage_handler::primitives::contract_class::{ContractClassWrapper, StorageContractClassData};
 use mc_db::storage_handler::{self, StorageView};
 use mp_felt::Felt252Wrapper;
+use serde_json::json;
 use starknet_core::types::{BlockId, ContractClass, FieldElement};
 
 use crate::errors::StarknetRpcApiError;
@@ -28,7 +29,7 @@ pub fn get_class(_block_id: BlockId, class_hash: FieldElement) -> RpcResult<Cont
             log::error!("Failed to retrieve contract class: {e}");
             Err(StarknetRpcApiError::InternalServerError.into())
         }
-        Ok(None) => Err(StarknetRpcApiError::ClassHashNotFound.into()),
+        Ok(None) => Err(StarknetRpcApiError::ClassHashNotFound.with_data(json!({ "class_hash": class_hash }))),
         Ok(Some(class)) => {
             let StorageContractClassData { contract_class, abi, sierra_program_length, abi_length } = class;
             Ok(ContractClassWrapper { contract: contract_class, abi, sierra_program_length, abi_length }
@@ -1,6 +1,8 @@
 use jsonrpsee::core::RpcResult;
 use mc_db::storage_handler::primitives::contract_class::{ContractClassWrapper, StorageContractClassData};
 use mc_db::storage_handler::{self, StorageView};
+use mc_sync::state::SyncState;
+use serde_json::json;
 use starknet_api::core::{ContractAddress, PatriciaKey};
 use starknet_api::hash::StarkFelt;
 use starknet_core::types::{BlockId, ContractClass, FieldElement};
@@ -27,8 +29,12 @@ use crate::methods::trace::utils::block_number_by_id;
 /// This method may return the following errors:
 /// * `BLOCK_NOT_FOUND` - If the specified block does not exist in the blockchain.
 /// * `CONTRACT_NOT_FOUND` - If the specified contract address does not exist.
-pub fn get_class_at(block_id: BlockId, contract_address: FieldElement) -> RpcResult<ContractClass> {
-    let block_number = block_number_by_id(block_id);
+pub fn get_class_at(
+    sync_state: &SyncState,
+    block_id: BlockId,
+    contract_address: FieldElement,
+) -> RpcResult<ContractClass> {
+    let block_number = block_number_by_id(sync_state, block_id);
     let key = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
 
     let class_hash = match storage_handler::contract_data().get_class_hash_at(&key, block_number) {
@@ -37,7 +43,8 @@ pub fn get_class_at(block_id: BlockId, contract_address: FieldElement) -> RpcRes
             return Err(StarknetRpcApiError::InternalServerError.into());
         }
         Ok(None) => {
-            return Err(StarknetRpcApiError::ContractNotFound.into());
+            let data = json!({ "contract_address": contract_address });
+            return Err(StarknetRpcApiError::ContractNotFound.with_data(data));
         }
         Ok(Some(val)) => val,
     };
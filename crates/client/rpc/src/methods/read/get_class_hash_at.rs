@@ -1,6 +1,8 @@
 use jsonrpsee::core::RpcResult;
 use mc_db::storage_handler;
+use mc_sync::state::SyncState;
 use mp_felt::Felt252Wrapper;
+use serde_json::json;
 use starknet_api::core::{ContractAddress, PatriciaKey};
 use starknet_api::hash::StarkFelt;
 use starknet_core::types::{BlockId, FieldElement};
@@ -21,13 +23,13 @@ use crate::Felt;
 /// ### Returns
 ///
 /// * `class_hash` - The class hash of the given contract
-pub fn get_class_hash_at(block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt> {
-    let block_number = block_number_by_id(block_id);
+pub fn get_class_hash_at(sync_state: &SyncState, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt> {
+    let block_number = block_number_by_id(sync_state, block_id);
     let key = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
 
     let Ok(Some(class_hash)) = storage_handler::contract_data().get_class_hash_at(&key, block_number) else {
         log::error!("Failed to retrieve contract class hash at '{contract_address:?}'");
-        return Err(StarknetRpcApiError::ContractNotFound.into());
+        return Err(StarknetRpcApiError::ContractNotFound.with_data(json!({ "contract_address": contract_address })));
     };
 
     Ok(Felt(Felt252Wrapper::from(class_hash).into()))
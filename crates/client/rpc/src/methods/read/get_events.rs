@@ -13,7 +13,7 @@ use starknet_ff::FieldElement;
 use crate::constants::{MAX_EVENTS_CHUNK_SIZE, MAX_EVENTS_KEYS};
 use crate::errors::StarknetRpcApiError;
 use crate::types::ContinuationToken;
-use crate::Starknet;
+use crate::{ExtendedBlockId, Starknet};
 
 /// Returns all events matching the given filter.
 ///
@@ -136,6 +136,10 @@ where
         log::error!("'{e}'");
         StarknetRpcApiError::BlockNotFound
     })?;
+    // the pending tag resolves to the latest block when the pending block is not served
+    let from_block =
+        from_block.map(|block_id| starknet.resolve_block_id(ExtendedBlockId::from(block_id))).transpose()?;
+    let to_block = to_block.map(|block_id| starknet.resolve_block_id(ExtendedBlockId::from(block_id))).transpose()?;
     let from = if from_block == Some(BlockId::Tag(BlockTag::Pending)) {
         latest + 1
     } else {
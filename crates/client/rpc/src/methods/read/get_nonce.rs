@@ -1,6 +1,8 @@
 use jsonrpsee::core::RpcResult;
 use mc_db::storage_handler;
+use mc_sync::state::SyncState;
 use mp_felt::Felt252Wrapper;
+use serde_json::json;
 use starknet_api::core::{ContractAddress, PatriciaKey};
 use starknet_api::hash::StarkFelt;
 use starknet_core::types::{BlockId, FieldElement};
@@ -25,13 +27,13 @@ use crate::Felt;
 /// count or other contract-specific operations. In case of errors, such as
 /// `BLOCK_NOT_FOUND` or `CONTRACT_NOT_FOUND`, returns a `StarknetRpcApiError` indicating the
 /// specific issue.
-pub fn get_nonce(block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt> {
+pub fn get_nonce(sync_state: &SyncState, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt> {
     let key = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
 
-    let block_number = block_number_by_id(block_id);
+    let block_number = block_number_by_id(sync_state, block_id);
     let Ok(Some(nonce)) = storage_handler::contract_data().get_nonce_at(&key, block_number) else {
         log::error!("Failed to get nonce at '{contract_address:?}'");
-        return Err(StarknetRpcApiError::ContractNotFound.into());
+        return Err(StarknetRpcApiError::ContractNotFound.with_data(json!({ "contract_address": contract_address })));
     };
 
     Ok(Felt(Felt252Wrapper::from(nonce).into()))
@@ -1,7 +1,6 @@
 use jsonrpsee::core::error::Error;
 use jsonrpsee::core::RpcResult;
 use mc_db::{storage_handler, DeoxysBackend};
-use mc_sync::l2::get_pending_state_update;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_types::block::{DBlockT, DHashT};
@@ -10,7 +9,7 @@ use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{BlockId, BlockTag, FieldElement, MaybePendingStateUpdate, StateUpdate};
+use starknet_core::types::{BlockId, BlockTag, FieldElement, MaybePendingStateUpdate, PendingStateUpdate, StateUpdate};
 
 use crate::deoxys_backend_client::get_block_by_block_hash;
 use crate::errors::StarknetRpcApiError;
@@ -29,7 +28,8 @@ where
 {
     let starknet_block = get_block_by_block_hash(server.client.as_ref(), substrate_block_hash)?;
 
-    let block_hash = starknet_block.header().hash::<H>().into();
+    let chain_id = server.chain_id()?.0.into();
+    let block_hash = starknet_block.header().hash::<H>(chain_id).into();
 
     let block_number = starknet_block.header().block_number;
 
@@ -55,8 +55,8 @@ where
     Ok(MaybePendingStateUpdate::Update(StateUpdate { block_hash, old_root, new_root, state_diff }))
 }
 
-fn get_state_update_pending() -> RpcResult<MaybePendingStateUpdate> {
-    match get_pending_state_update() {
+fn get_state_update_pending(pending_state_update: Option<PendingStateUpdate>) -> RpcResult<MaybePendingStateUpdate> {
+    match pending_state_update {
         Some(state_update) => Ok(MaybePendingStateUpdate::PendingUpdate(state_update)),
         None => Err(Error::Custom("Failed to retrieve pending state update, node not yet synchronized".to_string())),
     }
@@ -97,7 +97,7 @@ where
     })?;
 
     match block_id {
-        BlockId::Tag(BlockTag::Pending) => get_state_update_pending(),
+        BlockId::Tag(BlockTag::Pending) => get_state_update_pending(starknet.pending_state_update()),
         _ => get_state_update_finalized(starknet, substrate_block_hash),
     }
 }
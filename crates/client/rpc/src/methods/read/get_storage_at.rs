@@ -7,6 +7,7 @@ use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
+use serde_json::json;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_api::core::{ContractAddress, PatriciaKey};
@@ -64,13 +65,25 @@ where
         StarknetRpcApiError::BlockNotFound
     })?;
 
+    let data = json!({ "contract_address": contract_address, "key": key });
     let contract_address = ContractAddress(PatriciaKey(StarkFelt(contract_address.to_bytes_be())));
     let key = StorageKey(PatriciaKey(StarkFelt(key.to_bytes_be())));
 
-    let Ok(Some(value)) = storage_handler::contract_storage_trie().get_at(&contract_address, &key, block_number) else {
-        log::error!("Failed to retrieve storage at '{contract_address:?}' and '{key:?}'");
-        return Err(StarknetRpcApiError::ContractNotFound.into());
-    };
+    let value = storage_handler::versioned_storage().get_at(&(contract_address, key), block_number).map_err(|e| {
+        log::error!("Failed to retrieve storage at '{contract_address:?}' and '{key:?}': {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    if let Some(value) = value {
+        return Ok(Felt(Felt252Wrapper::from(value).into()));
+    }
 
-    Ok(Felt(Felt252Wrapper::from(value).into()))
+    // a key which was never set reads as zero, as long as the contract was deployed at that block
+    match storage_handler::contract_data().get_class_hash_at(&contract_address, block_number) {
+        Ok(Some(_)) => Ok(Felt(FieldElement::ZERO)),
+        Ok(None) => Err(StarknetRpcApiError::ContractNotFound.with_data(data)),
+        Err(e) => {
+            log::error!("Failed to retrieve the class hash of '{contract_address:?}': {e}");
+            Err(StarknetRpcApiError::InternalServerError.into())
+        }
+    }
 }
@@ -10,7 +10,7 @@ use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{BlockId, FieldElement, Transaction};
+use starknet_core::types::{BlockId, BlockTag, FieldElement, Transaction};
 
 use crate::deoxys_backend_client::get_block_by_block_hash;
 use crate::errors::StarknetRpcApiError;
@@ -19,8 +19,10 @@ use crate::Starknet;
 /// Get the details of a transaction by a given block id and index.
 ///
 /// This function fetches the details of a specific transaction in the StarkNet network by
-/// identifying it through its block and position (index) within that block. If no transaction
-/// is found at the specified index, null is returned.
+/// identifying it through its block and position (index) within that block. The transaction is
+/// read from the stored block, which is the pending block, a backfilled block or the block held
+/// in the digest of the substrate block. If no transaction is found at the specified index,
+/// `INVALID_TXN_INDEX` is returned.
 ///
 /// ### Arguments
 ///
@@ -48,18 +50,29 @@ where
     C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
     H: HasherT + Send + Sync + 'static,
 {
-    let substrate_block_hash = starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
-        log::error!("'{e}'");
-        StarknetRpcApiError::BlockNotFound
-    })?;
-
-    let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
+    let starknet_block = match block_id {
+        BlockId::Tag(BlockTag::Pending) => starknet.pending_block().ok_or(StarknetRpcApiError::BlockNotFound)?,
+        block_id => match starknet.backfilled_block(block_id) {
+            Some(starknet_block) => starknet_block,
+            None => {
+                let substrate_block_hash =
+                    starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|e| {
+                        log::error!("'{e}'");
+                        StarknetRpcApiError::BlockNotFound
+                    })?;
+                get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?
+            }
+        },
+    };
 
-    let transaction = starknet_block.transactions().get(index as usize).ok_or(StarknetRpcApiError::InvalidTxnIndex)?;
+    let transaction = usize::try_from(index)
+        .ok()
+        .and_then(|index| starknet_block.transactions().get(index))
+        .ok_or(StarknetRpcApiError::InvalidTxnIndex)?;
     let chain_id = starknet.chain_id()?;
 
     let opt_cached_transaction_hashes =
-        starknet.get_cached_transaction_hashes(starknet_block.header().hash::<H>().into());
+        starknet.get_cached_transaction_hashes(starknet_block.header().hash::<H>(chain_id.0.into()).into());
 
     let transaction_hash = if let Some(cached_tx_hashes) = opt_cached_transaction_hashes {
         cached_tx_hashes.get(index as usize).map(|&fe| FieldElement::from(Felt252Wrapper::from(fe))).ok_or(
@@ -8,6 +8,7 @@ use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
+use serde_json::json;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{FieldElement, Transaction};
@@ -63,30 +64,34 @@ where
 
     let substrate_block_hash = match substrate_block_hash_from_db {
         Some(block_hash) => block_hash,
-        None => return Err(StarknetRpcApiError::TxnHashNotFound.into()),
+        None => {
+            return Err(StarknetRpcApiError::TxnHashNotFound.with_data(json!({ "transaction_hash": transaction_hash })));
+        }
     };
 
     let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
 
     let chain_id = starknet.chain_id()?.0.into();
 
-    let find_tx =
-        if let Some(tx_hashes) = starknet.get_cached_transaction_hashes(starknet_block.header().hash::<H>().into()) {
-            tx_hashes
-                .into_iter()
-                .zip(starknet_block.transactions())
-                .find(|(tx_hash, _)| *tx_hash == Felt252Wrapper(transaction_hash).into())
-                .map(|(_, tx)| to_starknet_core_tx(tx.clone(), transaction_hash))
-        } else {
-            starknet_block
-                .transactions()
-                .iter()
-                .find(|tx| {
-                    tx.compute_hash::<H>(chain_id, false, Some(starknet_block.header().block_number)).0
-                        == Felt252Wrapper::from(transaction_hash).into()
-                })
-                .map(|tx| to_starknet_core_tx(tx.clone(), transaction_hash))
-        };
+    let block_hash = starknet_block.header().hash::<H>(chain_id);
 
-    find_tx.ok_or(StarknetRpcApiError::TxnHashNotFound.into())
+    let find_tx = if let Some(tx_hashes) = starknet.get_cached_transaction_hashes(block_hash.into()) {
+        tx_hashes
+            .into_iter()
+            .zip(starknet_block.transactions())
+            .find(|(tx_hash, _)| *tx_hash == Felt252Wrapper(transaction_hash).into())
+            .map(|(_, tx)| to_starknet_core_tx(tx.clone(), transaction_hash))
+    } else {
+        starknet_block
+            .transactions()
+            .iter()
+            .find(|tx| {
+                tx.compute_hash::<H>(chain_id, false, Some(starknet_block.header().block_number)).0
+                    == Felt252Wrapper::from(transaction_hash).into()
+            })
+            .map(|tx| to_starknet_core_tx(tx.clone(), transaction_hash))
+    };
+
+    find_tx
+        .ok_or_else(|| StarknetRpcApiError::TxnHashNotFound.with_data(json!({ "transaction_hash": transaction_hash })))
 }
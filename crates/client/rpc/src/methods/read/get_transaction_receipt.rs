@@ -3,12 +3,14 @@ use blockifier::transaction::objects::TransactionExecutionInfo;
 use blockifier::transaction::transaction_execution as btx;
 use jsonrpsee::core::RpcResult;
 use mc_db::DeoxysBackend;
+use mc_rpc_core::WithDecodedRevertReason;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_types::block::{DBlockT, DHashT};
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
+use serde_json::json;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_api::core::{calculate_contract_address, ContractAddress};
@@ -16,8 +18,7 @@ use starknet_api::transaction::Transaction;
 use starknet_core::types::{
     ComputationResources, DataAvailabilityResources, DataResources, DeclareTransactionReceipt,
     DeployAccountTransactionReceipt, ExecutionResources, ExecutionResult, FieldElement, Hash256,
-    InvokeTransactionReceipt, L1HandlerTransactionReceipt, TransactionFinalityStatus, TransactionReceipt,
-    TransactionReceiptWithBlockInfo,
+    InvokeTransactionReceipt, L1HandlerTransactionReceipt, TransactionReceipt, TransactionReceiptWithBlockInfo,
 };
 
 use crate::deoxys_backend_client::get_block_by_block_hash;
@@ -25,8 +26,9 @@ use crate::errors::StarknetRpcApiError;
 use crate::utils::call_info::{
     blockifier_call_info_to_starknet_resources, extract_events_from_call_info, extract_messages_from_call_info,
 };
-use crate::utils::execution::{block_context, re_execute_transactions};
-use crate::utils::helpers::{previous_substrate_block_hash, tx_hash_compute, tx_hash_retrieve};
+use crate::utils::execution::re_execute_transactions;
+use crate::utils::helpers::{finality_status, previous_block_context, tx_hash_compute, tx_hash_retrieve};
+use crate::utils::revert_reason::with_decoded_revert_reason;
 use crate::utils::transaction::blockifier_transactions;
 use crate::{Felt, Starknet};
 
@@ -55,7 +57,7 @@ use crate::{Felt, Starknet};
 pub async fn get_transaction_receipt<BE, C, H>(
     starknet: &Starknet<BE, C, H>,
     transaction_hash: FieldElement,
-) -> RpcResult<TransactionReceiptWithBlockInfo>
+) -> RpcResult<WithDecodedRevertReason<TransactionReceiptWithBlockInfo>>
 where
     BE: Backend<DBlockT> + 'static,
     C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
@@ -70,7 +72,9 @@ where
             log::error!("Failed to get transaction's substrate block hash from mapping_db: {e}");
             StarknetRpcApiError::InternalServerError
         })?
-        .ok_or(StarknetRpcApiError::TxnHashNotFound)?;
+        .ok_or_else(|| {
+            StarknetRpcApiError::TxnHashNotFound.with_data(json!({ "transaction_hash": transaction_hash }))
+        })?;
 
     let chain_id = starknet.chain_id()?;
 
@@ -82,7 +86,7 @@ pub fn get_transaction_receipt_finalized<BE, C, H>(
     chain_id: Felt,
     substrate_block_hash: DHashT,
     transaction_hash: FieldElement,
-) -> RpcResult<TransactionReceiptWithBlockInfo>
+) -> RpcResult<WithDecodedRevertReason<TransactionReceiptWithBlockInfo>>
 where
     BE: Backend<DBlockT> + 'static,
     C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
@@ -93,11 +97,10 @@ where
     let block = get_block_by_block_hash(client.client.as_ref(), substrate_block_hash)?;
     let block_header = block.header();
     let block_number = block_header.block_number;
-    let block_hash: Felt252Wrapper = block_header.hash::<H>();
+    let block_hash: Felt252Wrapper = block_header.hash::<H>(chain_id.0.into());
 
     // computes the previous SUBSTRATE block hash and creates a block context
-    let previous_substrate_block_hash = previous_substrate_block_hash(client, substrate_block_hash)?;
-    let block_context = block_context(client.client.as_ref(), previous_substrate_block_hash)?;
+    let block_context = previous_block_context(client, substrate_block_hash)?;
 
     // retrieve all transaction hashes from the block in the cache or compute them
     let block_txs_hashes = if let Some(tx_hashes) = client.get_cached_transaction_hashes(block_hash.into()) {
@@ -136,7 +139,11 @@ where
 
     let block_info = starknet_core::types::ReceiptBlock::Block { block_hash: block_hash.0, block_number };
 
-    Ok(TransactionReceiptWithBlockInfo { receipt, block: block_info })
+    Ok(with_decoded_revert_reason(
+        TransactionReceiptWithBlockInfo { receipt, block: block_info },
+        execution_infos.revert_error.as_deref(),
+        client.decode_revert_reasons,
+    ))
 }
 
 pub(crate) fn execution_infos(
@@ -175,11 +182,7 @@ pub fn receipt(
         unit: starknet_core::types::PriceUnit::Wei,
     };
 
-    let finality_status = if block_number <= mc_sync::l1::ETHEREUM_STATE_UPDATE.read().unwrap().block_number {
-        TransactionFinalityStatus::AcceptedOnL1
-    } else {
-        TransactionFinalityStatus::AcceptedOnL2
-    };
+    let finality_status = finality_status(block_number);
 
     let execution_result = match execution_infos.revert_error.clone() {
         Some(err) => ExecutionResult::Reverted { reason: err },
@@ -1,5 +1,6 @@
 use jsonrpsee::core::RpcResult;
 use mc_db::DeoxysBackend;
+use mc_sync::l1::{get_confirmation_status, ConfirmationStatus};
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::compute_hash::ComputeTransactionHash;
@@ -8,6 +9,7 @@ use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::backend::{Backend, StorageProvider};
 use sc_client_api::BlockBackend;
+use serde_json::json;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{FieldElement, TransactionExecutionStatus, TransactionStatus};
@@ -30,8 +32,8 @@ use crate::Starknet;
 /// ### Returns
 ///
 /// * `transaction_status` - An object containing the transaction status details:
-///   - `finality_status`: The finality status of the transaction, indicating whether it is
-///     confirmed, pending, or rejected.
+///   - `finality_status`: The finality status of the transaction, indicating whether its block has
+///     been confirmed on L1.
 ///   - `execution_status`: The execution status of the transaction, providing details on the
 ///     execution outcome if the transaction has been processed.
 pub fn get_transaction_status<BE, C, H>(
@@ -51,29 +53,32 @@ where
             log::error!("Failed to get transaction's substrate block hash from mapping_db: {e}");
             StarknetRpcApiError::TxnHashNotFound
         })?
-        .ok_or(StarknetRpcApiError::TxnHashNotFound)?;
+        .ok_or_else(|| {
+            StarknetRpcApiError::TxnHashNotFound.with_data(json!({ "transaction_hash": transaction_hash }))
+        })?;
 
     let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
 
     let chain_id = starknet.chain_id()?.0.into();
 
-    let _starknet_tx =
-        if let Some(tx_hashes) = starknet.get_cached_transaction_hashes(starknet_block.header().hash::<H>().into()) {
-            tx_hashes
-                .into_iter()
-                .zip(starknet_block.transactions())
-                .find(|(tx_hash, _)| *tx_hash == Felt252Wrapper(transaction_hash).into())
-                .map(|(_, tx)| to_starknet_core_tx(tx.clone(), transaction_hash))
-        } else {
-            starknet_block
-                .transactions()
-                .iter()
-                .find(|tx| {
-                    tx.compute_hash::<H>(chain_id, false, Some(starknet_block.header().block_number)).0
-                        == Felt252Wrapper::from(transaction_hash).into()
-                })
-                .map(|tx| to_starknet_core_tx(tx.clone(), transaction_hash))
-        };
+    let block_hash = starknet_block.header().hash::<H>(chain_id);
+
+    let _starknet_tx = if let Some(tx_hashes) = starknet.get_cached_transaction_hashes(block_hash.into()) {
+        tx_hashes
+            .into_iter()
+            .zip(starknet_block.transactions())
+            .find(|(tx_hash, _)| *tx_hash == Felt252Wrapper(transaction_hash).into())
+            .map(|(_, tx)| to_starknet_core_tx(tx.clone(), transaction_hash))
+    } else {
+        starknet_block
+            .transactions()
+            .iter()
+            .find(|tx| {
+                tx.compute_hash::<H>(chain_id, false, Some(starknet_block.header().block_number)).0
+                    == Felt252Wrapper::from(transaction_hash).into()
+            })
+            .map(|tx| to_starknet_core_tx(tx.clone(), transaction_hash))
+    };
 
     let execution_status = {
         let revert_error = starknet
@@ -95,5 +100,8 @@ where
         }
     };
 
-    Ok(TransactionStatus::AcceptedOnL2(execution_status))
+    match get_confirmation_status(starknet_block.header().block_number) {
+        ConfirmationStatus::AcceptedOnL1 => Ok(TransactionStatus::AcceptedOnL1(execution_status)),
+        ConfirmationStatus::AcceptedOnL2 => Ok(TransactionStatus::AcceptedOnL2(execution_status)),
+    }
 }
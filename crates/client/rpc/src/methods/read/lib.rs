@@ -7,7 +7,7 @@ use sc_client_api::BlockBackend;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{
-    BlockHashAndNumber, BlockId, BroadcastedTransaction, ContractClass, EventFilterWithPage, EventsPage, FeeEstimate,
+    BlockHashAndNumber, BroadcastedTransaction, ContractClass, EventFilterWithPage, EventsPage, FeeEstimate,
     FieldElement, FunctionCall, MaybePendingBlockWithReceipts, MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs,
     MaybePendingStateUpdate, MsgFromL1, SimulationFlagForEstimateFee, SyncStatusType, Transaction,
     TransactionReceiptWithBlockInfo, TransactionStatus,
@@ -33,7 +33,9 @@ use super::get_transaction_by_hash::*;
 use super::get_transaction_receipt::*;
 use super::get_transaction_status::*;
 use super::syncing::*;
-use crate::{Felt, Starknet, StarknetReadRpcApiServer};
+use crate::{
+    ExtendedBlockId, Felt, Starknet, StarknetReadRpcApiServer, WithDecodedRevertReason, WithDecodedRevertReasons,
+};
 
 #[async_trait]
 impl<BE, C, H> StarknetReadRpcApiServer for Starknet<BE, C, H>
@@ -56,69 +58,77 @@ where
         block_hash_and_number(self)
     }
 
-    fn call(&self, request: FunctionCall, block_id: BlockId) -> RpcResult<Vec<String>> {
-        call(self, request, block_id)
+    fn call(&self, request: FunctionCall, block_id: ExtendedBlockId) -> RpcResult<Vec<String>> {
+        self.pin_state(block_id)?.run(|block_id| call(self, request, block_id))
     }
 
     fn chain_id(&self) -> RpcResult<Felt> {
         self.chain_id()
     }
 
-    fn get_block_transaction_count(&self, block_id: BlockId) -> RpcResult<u128> {
-        get_block_transaction_count(self, block_id)
+    fn get_block_transaction_count(&self, block_id: ExtendedBlockId) -> RpcResult<u128> {
+        self.pin_block(block_id)?.run(|block_id| get_block_transaction_count(self, block_id))
     }
 
     async fn estimate_fee(
         &self,
         request: Vec<BroadcastedTransaction>,
         simulation_flags: Vec<SimulationFlagForEstimateFee>,
-        block_id: BlockId,
+        block_id: ExtendedBlockId,
     ) -> RpcResult<Vec<FeeEstimate>> {
-        estimate_fee(self, request, simulation_flags, block_id).await
+        self.pin_state(block_id)?.run_async(|block_id| estimate_fee(self, request, simulation_flags, block_id)).await
     }
 
-    async fn estimate_message_fee(&self, message: MsgFromL1, block_id: BlockId) -> RpcResult<FeeEstimate> {
-        estimate_message_fee(self, message, block_id).await
+    async fn estimate_message_fee(&self, message: MsgFromL1, block_id: ExtendedBlockId) -> RpcResult<FeeEstimate> {
+        self.pin_state(block_id)?.run_async(|block_id| estimate_message_fee(self, message, block_id)).await
     }
 
-    async fn get_block_with_receipts(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithReceipts> {
-        get_block_with_receipts(self, block_id)
+    async fn get_block_with_receipts(
+        &self,
+        block_id: ExtendedBlockId,
+    ) -> RpcResult<WithDecodedRevertReasons<MaybePendingBlockWithReceipts>> {
+        self.pin_block(block_id)?.run(|block_id| get_block_with_receipts(self, block_id))
     }
 
-    fn get_block_with_tx_hashes(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithTxHashes> {
-        get_block_with_tx_hashes(self, block_id)
+    fn get_block_with_tx_hashes(&self, block_id: ExtendedBlockId) -> RpcResult<MaybePendingBlockWithTxHashes> {
+        self.pin_block(block_id)?.run(|block_id| get_block_with_tx_hashes(self, block_id))
     }
 
-    fn get_block_with_txs(&self, block_id: BlockId) -> RpcResult<MaybePendingBlockWithTxs> {
-        get_block_with_txs(self, block_id)
+    fn get_block_with_txs(&self, block_id: ExtendedBlockId) -> RpcResult<MaybePendingBlockWithTxs> {
+        self.pin_block(block_id)?.run(|block_id| get_block_with_txs(self, block_id))
     }
 
-    fn get_class_at(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<ContractClass> {
-        get_class_at(block_id, contract_address)
+    fn get_class_at(&self, block_id: ExtendedBlockId, contract_address: FieldElement) -> RpcResult<ContractClass> {
+        self.pin_state(block_id)?.run(|block_id| get_class_at(&self.sync_state, block_id, contract_address))
     }
 
-    fn get_class_hash_at(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt> {
-        get_class_hash_at(block_id, contract_address)
+    fn get_class_hash_at(&self, block_id: ExtendedBlockId, contract_address: FieldElement) -> RpcResult<Felt> {
+        self.pin_state(block_id)?.run(|block_id| get_class_hash_at(&self.sync_state, block_id, contract_address))
     }
 
-    fn get_class(&self, block_id: BlockId, class_hash: FieldElement) -> RpcResult<ContractClass> {
-        get_class(block_id, class_hash)
+    fn get_class(&self, block_id: ExtendedBlockId, class_hash: FieldElement) -> RpcResult<ContractClass> {
+        self.pin_block(block_id)?.run(|block_id| get_class(block_id, class_hash))
     }
 
     async fn get_events(&self, filter: EventFilterWithPage) -> RpcResult<EventsPage> {
         get_events(self, filter).await
     }
 
-    fn get_nonce(&self, block_id: BlockId, contract_address: FieldElement) -> RpcResult<Felt> {
-        get_nonce(block_id, contract_address)
+    fn get_nonce(&self, block_id: ExtendedBlockId, contract_address: FieldElement) -> RpcResult<Felt> {
+        self.pin_state(block_id)?.run(|block_id| get_nonce(&self.sync_state, block_id, contract_address))
     }
 
-    fn get_storage_at(&self, contract_address: FieldElement, key: FieldElement, block_id: BlockId) -> RpcResult<Felt> {
-        get_storage_at(self, contract_address, key, block_id)
+    fn get_storage_at(
+        &self,
+        contract_address: FieldElement,
+        key: FieldElement,
+        block_id: ExtendedBlockId,
+    ) -> RpcResult<Felt> {
+        self.pin_state(block_id)?.run(|block_id| get_storage_at(self, contract_address, key, block_id))
     }
 
-    fn get_transaction_by_block_id_and_index(&self, block_id: BlockId, index: u64) -> RpcResult<Transaction> {
-        get_transaction_by_block_id_and_index(self, block_id, index)
+    fn get_transaction_by_block_id_and_index(&self, block_id: ExtendedBlockId, index: u64) -> RpcResult<Transaction> {
+        self.pin_block(block_id)?.run(|block_id| get_transaction_by_block_id_and_index(self, block_id, index))
     }
 
     fn get_transaction_by_hash(&self, transaction_hash: FieldElement) -> RpcResult<Transaction> {
@@ -128,7 +138,7 @@ where
     async fn get_transaction_receipt(
         &self,
         transaction_hash: FieldElement,
-    ) -> RpcResult<TransactionReceiptWithBlockInfo> {
+    ) -> RpcResult<WithDecodedRevertReason<TransactionReceiptWithBlockInfo>> {
         get_transaction_receipt(self, transaction_hash).await
     }
 
@@ -140,7 +150,7 @@ where
         syncing(self).await
     }
 
-    fn get_state_update(&self, block_id: BlockId) -> RpcResult<MaybePendingStateUpdate> {
-        get_state_update(self, block_id)
+    fn get_state_update(&self, block_id: ExtendedBlockId) -> RpcResult<MaybePendingStateUpdate> {
+        self.pin_state(block_id)?.run(|block_id| get_state_update(self, block_id))
     }
 }
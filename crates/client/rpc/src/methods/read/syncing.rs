@@ -1,5 +1,4 @@
 use jsonrpsee::core::RpcResult;
-use mc_sync::l2::get_highest_block_hash_and_number;
 use mp_hashers::HasherT;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
@@ -14,6 +13,8 @@ use crate::{deoxys_backend_client, Starknet};
 
 /// Returns an object about the sync status, or false if the node is not synching
 ///
+/// The node stops synching once it has applied the highest block known to the feeder gateway.
+///
 /// ### Arguments
 ///
 /// This function does not take any arguments.
@@ -34,8 +35,18 @@ where
     C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
     H: HasherT + Send + Sync + 'static,
 {
+    let progress = starknet.sync_state.sync_progress();
+    if progress.highest_block > 0 && progress.current_block >= progress.highest_block {
+        return Ok(SyncStatusType::NotSyncing);
+    }
+
+    // a node replaying requests offline has no network to sync from
+    let Some(sync_service) = &starknet.sync_service else {
+        return Ok(SyncStatusType::NotSyncing);
+    };
+
     // obtain best seen (highest) block number
-    match starknet.sync_service.best_seen_block().await {
+    match sync_service.best_seen_block().await {
         Ok(best_seen_block) => {
             let best_number = starknet.client.info().best_number;
             let highest_number = best_seen_block.unwrap_or(best_number);
@@ -56,14 +67,15 @@ where
 
             if starting_block.is_ok() && current_block.is_ok() && highest_block.is_ok() {
                 // Convert block numbers and hashes to the respective type required by the `syncing` endpoint.
+                let chain_id = starknet.chain_id()?.0.into();
                 let starting_block_num = UniqueSaturatedInto::<u64>::unique_saturated_into(starknet.starting_block);
-                let starting_block_hash = starting_block?.header().hash::<H>().0;
+                let starting_block_hash = starting_block?.header().hash::<H>(chain_id).0;
 
                 let current_block_num = UniqueSaturatedInto::<u64>::unique_saturated_into(best_number);
-                let current_block_hash = current_block?.header().hash::<H>().0;
+                let current_block_hash = current_block?.header().hash::<H>(chain_id).0;
 
                 // Get the highest block number and hash from the global variable update in l2 sync()
-                let (highest_block_hash, highest_block_num) = get_highest_block_hash_and_number();
+                let (highest_block_hash, highest_block_num) = starknet.sync_state.highest_block_hash_and_number();
 
                 // Build the `SyncStatus` struct with the respective syn information
                 Ok(SyncStatusType::Syncing(SyncStatus {
@@ -0,0 +1,107 @@
+//! Execution traces in the format read by `cairo-profiler`, as written by the `trace-data` crate.
+
+use std::collections::HashMap;
+
+use blockifier::execution::call_info::CallInfo;
+use blockifier::transaction::objects::TransactionExecutionInfo;
+use mc_rpc_core::{
+    CairoProfilerCallTrace, CairoProfilerCallTraceNode, CairoProfilerCallType, CairoProfilerEntryPoint,
+    CairoProfilerEntryPointType, CairoProfilerExecutionResources, CairoProfilerL1Resources,
+    CairoProfilerTransactionTrace, CairoProfilerVmResources,
+};
+use mp_felt::Felt252Wrapper;
+use starknet_api::core::ContractAddress;
+use starknet_ff::FieldElement;
+
+use super::lib::TryFuntionInvocationFromCallInfoError;
+use super::utils::call_info_class_hash;
+
+/// Converts the execution of a transaction to `cairo-profiler` traces.
+pub fn tx_execution_infos_to_cairo_profiler_trace(
+    transaction_hash: FieldElement,
+    tx_exec_info: &TransactionExecutionInfo,
+    block_number: u64,
+) -> Result<CairoProfilerTransactionTrace, TryFuntionInvocationFromCallInfoError> {
+    let mut class_hash_cache: HashMap<ContractAddress, FieldElement> = HashMap::new();
+    let mut convert = |call_info: &Option<CallInfo>| {
+        call_info
+            .as_ref()
+            .map(|call_info| call_info_to_cairo_profiler_trace(call_info, &mut class_hash_cache, block_number))
+            .transpose()
+    };
+
+    Ok(CairoProfilerTransactionTrace {
+        transaction_hash,
+        validate_invocation: convert(&tx_exec_info.validate_call_info)?,
+        execute_invocation: convert(&tx_exec_info.execute_call_info)?,
+        fee_transfer_invocation: convert(&tx_exec_info.fee_transfer_call_info)?,
+    })
+}
+
+fn call_info_to_cairo_profiler_trace(
+    call_info: &CallInfo,
+    class_hash_cache: &mut HashMap<ContractAddress, FieldElement>,
+    block_number: u64,
+) -> Result<CairoProfilerCallTrace, TryFuntionInvocationFromCallInfoError> {
+    let class_hash = call_info_class_hash(call_info, class_hash_cache, block_number)?;
+
+    let entry_point_type = match call_info.call.entry_point_type {
+        starknet_api::deprecated_contract_class::EntryPointType::Constructor => {
+            CairoProfilerEntryPointType::Constructor
+        }
+        starknet_api::deprecated_contract_class::EntryPointType::External => CairoProfilerEntryPointType::External,
+        starknet_api::deprecated_contract_class::EntryPointType::L1Handler => CairoProfilerEntryPointType::L1Handler,
+    };
+
+    let call_type = match call_info.call.call_type {
+        blockifier::execution::entry_point::CallType::Call => CairoProfilerCallType::Call,
+        blockifier::execution::entry_point::CallType::Delegate => CairoProfilerCallType::Delegate,
+    };
+
+    let nested_calls = call_info
+        .inner_calls
+        .iter()
+        .map(|call| {
+            call_info_to_cairo_profiler_trace(call, class_hash_cache, block_number)
+                .map(|trace| CairoProfilerCallTraceNode::EntryPointCall(Box::new(trace)))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mut l2_l1_message_sizes = Vec::new();
+    collect_l2_l1_message_sizes(call_info, &mut l2_l1_message_sizes);
+
+    let vm_resources = &call_info.vm_resources;
+    Ok(CairoProfilerCallTrace {
+        entry_point: CairoProfilerEntryPoint {
+            class_hash: Some(class_hash),
+            entry_point_type,
+            entry_point_selector: Felt252Wrapper::from(call_info.call.entry_point_selector.0).into(),
+            contract_address: Felt252Wrapper::from(call_info.call.storage_address.0.0).into(),
+            call_type,
+            contract_name: None,
+            function_name: None,
+        },
+        cumulative_resources: CairoProfilerExecutionResources {
+            vm_resources: CairoProfilerVmResources {
+                n_steps: vm_resources.n_steps,
+                n_memory_holes: vm_resources.n_memory_holes,
+                builtin_instance_counter: vm_resources
+                    .builtin_instance_counter
+                    .iter()
+                    .map(|(builtin, count)| (builtin.clone(), *count))
+                    .collect(),
+            },
+            gas_consumed: Some(call_info.execution.gas_consumed),
+        },
+        used_l1_resources: CairoProfilerL1Resources { l2_l1_message_sizes },
+        nested_calls,
+        vm_trace: None,
+    })
+}
+
+fn collect_l2_l1_message_sizes(call_info: &CallInfo, sizes: &mut Vec<usize>) {
+    sizes.extend(call_info.execution.l2_to_l1_messages.iter().map(|message| message.message.payload.0.len()));
+    for call in &call_info.inner_calls {
+        collect_l2_l1_message_sizes(call, sizes);
+    }
+}
@@ -1,14 +1,13 @@
 use blockifier::transaction::errors::TransactionExecutionError;
 use jsonrpsee::core::{async_trait, RpcResult};
+use mc_rpc_core::{TraceFormat, TransactionTraceOutput};
 use mp_hashers::HasherT;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::{Backend, BlockBackend, StorageProvider};
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use starknet_core::types::{
-    BlockId, BroadcastedTransaction, SimulatedTransaction, SimulationFlag, TransactionTraceWithHash,
-};
+use starknet_core::types::{BroadcastedTransaction, SimulatedTransaction, SimulationFlag, TransactionTraceWithHash};
 use starknet_ff::FieldElement;
 use thiserror::Error;
 
@@ -16,7 +15,7 @@ use super::simulate_transactions::simulate_transactions;
 use super::trace_block_transactions::trace_block_transactions;
 use super::trace_transaction::trace_transaction;
 use crate::errors::StarknetRpcApiError;
-use crate::{Starknet, StarknetTraceRpcApiServer};
+use crate::{ExtendedBlockId, Starknet, StarknetTraceRpcApiServer, WithDecodedRevertReason};
 
 #[async_trait]
 impl<BE, C, H> StarknetTraceRpcApiServer for Starknet<BE, C, H>
@@ -29,19 +28,28 @@ where
 {
     async fn simulate_transactions(
         &self,
-        block_id: BlockId,
+        block_id: ExtendedBlockId,
         transactions: Vec<BroadcastedTransaction>,
         simulation_flags: Vec<SimulationFlag>,
-    ) -> RpcResult<Vec<SimulatedTransaction>> {
-        simulate_transactions(self, block_id, transactions, simulation_flags).await
+    ) -> RpcResult<Vec<WithDecodedRevertReason<SimulatedTransaction>>> {
+        self.pin_state(block_id)?
+            .run_async(|block_id| simulate_transactions(self, block_id, transactions, simulation_flags))
+            .await
     }
 
-    async fn trace_block_transactions(&self, block_id: BlockId) -> RpcResult<Vec<TransactionTraceWithHash>> {
-        trace_block_transactions(self, block_id).await
+    async fn trace_block_transactions(
+        &self,
+        block_id: ExtendedBlockId,
+    ) -> RpcResult<Vec<WithDecodedRevertReason<TransactionTraceWithHash>>> {
+        self.pin_state(block_id)?.run_async(|block_id| trace_block_transactions(self, block_id)).await
     }
 
-    async fn trace_transaction(&self, transaction_hash: FieldElement) -> RpcResult<TransactionTraceWithHash> {
-        trace_transaction(self, transaction_hash).await
+    async fn trace_transaction(
+        &self,
+        transaction_hash: FieldElement,
+        format: Option<TraceFormat>,
+    ) -> RpcResult<TransactionTraceOutput> {
+        trace_transaction(self, transaction_hash, format.unwrap_or_default()).await
     }
 }
 
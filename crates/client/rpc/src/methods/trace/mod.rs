@@ -1,3 +1,4 @@
+pub mod cairo_profiler;
 pub mod lib;
 pub mod simulate_transactions;
 pub mod trace_block_transactions;
@@ -1,5 +1,9 @@
+use std::collections::HashSet;
+
+use blockifier::transaction::account_transaction::AccountTransaction;
 use blockifier::transaction::objects::{FeeType, HasRelatedFeeType, TransactionExecutionInfo};
 use jsonrpsee::core::RpcResult;
+use mc_rpc_core::WithDecodedRevertReason;
 use mp_hashers::HasherT;
 use mp_simulations::SimulationFlags;
 use mp_transactions::from_broadcasted_transactions::ToAccountTransaction;
@@ -7,6 +11,7 @@ use mp_transactions::TxType;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::{Backend, BlockBackend, StorageProvider};
+use serde_json::json;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_core::types::{
@@ -16,16 +21,22 @@ use starknet_core::types::{
 use super::lib::ConvertCallInfoToExecuteInvocationError;
 use super::utils::{block_number_by_id, tx_execution_infos_to_tx_trace};
 use crate::errors::StarknetRpcApiError;
-use crate::utils::execution::block_context;
-use crate::utils::helpers::previous_substrate_block_hash;
+use crate::utils::helpers::request_block_context;
+use crate::utils::revert_reason::with_decoded_revert_reason;
 use crate::{utils, Starknet};
 
+/// Simulates a bundle of transactions, each one executed on top of the state changes of the ones
+/// before it.
+///
+/// An account deployment may thus be followed by transactions sent from the account it deploys,
+/// as wallets do when onboarding a user: the deployment must come first in the bundle. With
+/// `SKIP_FEE_CHARGE`, the account does not need to be funded beforehand.
 pub async fn simulate_transactions<BE, C, H>(
     starknet: &Starknet<BE, C, H>,
     block_id: BlockId,
     transactions: Vec<BroadcastedTransaction>,
     simulation_flags: Vec<SimulationFlag>,
-) -> RpcResult<Vec<SimulatedTransaction>>
+) -> RpcResult<Vec<WithDecodedRevertReason<SimulatedTransaction>>>
 where
     BE: Backend<DBlockT> + 'static,
     C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
@@ -36,9 +47,8 @@ where
     let substrate_block_hash =
         starknet.substrate_block_hash_from_starknet_block(block_id).map_err(|_e| StarknetRpcApiError::BlockNotFound)?;
 
-    let previous_substrate_block_hash = previous_substrate_block_hash(starknet, substrate_block_hash)?;
-    let block_context = block_context(starknet.client.as_ref(), previous_substrate_block_hash)?;
-    let block_number = block_number_by_id(block_id);
+    let block_context = request_block_context(starknet, substrate_block_hash)?;
+    let block_number = block_number_by_id(&starknet.sync_state, block_id);
 
     let tx_type_and_tx_iterator = transactions.into_iter().map(|tx| match tx {
         BroadcastedTransaction::Invoke(_) => tx.to_account_transaction().map(|tx| (TxType::Invoke, tx)),
@@ -53,34 +63,79 @@ where
             },
         )?;
 
+    check_deployment_order(&user_transactions)?;
+
     let simulation_flags = SimulationFlags::from(simulation_flags);
 
     let fee_types = user_transactions.iter().map(|tx| tx.fee_type()).collect::<Vec<_>>();
-    let charge_fee = block_context.block_info().gas_prices.eth_l1_gas_price.get() != 1;
-
-    let res = utils::execution::simulate_transactions(user_transactions, &simulation_flags, &block_context, charge_fee)
-        .map_err(|e| {
-            log::error!("Failed to call function: {:#?}", e);
-            StarknetRpcApiError::ContractError
-        })?;
+    let charge_fee = simulation_flags.charge_fee && block_context.block_info().gas_prices.eth_l1_gas_price.get() != 1;
+
+    let policy = starknet.execution_policy.guard();
+    let res = utils::execution::simulate_transactions(
+        user_transactions,
+        &simulation_flags,
+        &block_context,
+        charge_fee,
+        &policy,
+    );
+    policy.check()?;
+    let res = res.map_err(|e| {
+        log::error!("Failed to call function: {:#?}", e);
+        StarknetRpcApiError::ContractError.with_data(json!({ "revert_error": e.to_string() }))
+    })?;
 
     if res.len() != fee_types.len() {
         log::error!("Failed to convert one or more transactions to simulated transactions: {:#?}", res);
         return Err(StarknetRpcApiError::InternalServerError.into());
     }
 
-    let simulated_transactions = tx_execution_infos_to_simulated_transactions(tx_types, res, block_number, fee_types)
-        .map_err(StarknetRpcApiError::from)?;
+    let simulated_transactions = tx_execution_infos_to_simulated_transactions(
+        tx_types,
+        res,
+        block_number,
+        fee_types,
+        starknet.decode_revert_reasons,
+    )
+    .map_err(StarknetRpcApiError::from)?;
 
     Ok(simulated_transactions)
 }
 
+/// Fails with [StarknetRpcApiError::ValidationFailure] if a transaction is sent from an account
+/// which is only deployed later in the bundle.
+fn check_deployment_order(transactions: &[AccountTransaction]) -> Result<(), StarknetRpcApiError> {
+    let mut deployed_later: HashSet<_> = transactions
+        .iter()
+        .filter_map(|tx| match tx {
+            AccountTransaction::DeployAccount(tx) => Some(tx.contract_address),
+            _ => None,
+        })
+        .collect();
+
+    for (index, tx) in transactions.iter().enumerate() {
+        let sender_address = match tx {
+            AccountTransaction::DeployAccount(tx) => {
+                deployed_later.remove(&tx.contract_address);
+                continue;
+            }
+            AccountTransaction::Declare(tx) => tx.tx.sender_address(),
+            AccountTransaction::Invoke(tx) => tx.tx.sender_address(),
+        };
+        if deployed_later.contains(&sender_address) {
+            log::error!("Transaction {index} is sent from an account deployed later in the bundle");
+            return Err(StarknetRpcApiError::ValidationFailure);
+        }
+    }
+    Ok(())
+}
+
 fn tx_execution_infos_to_simulated_transactions(
     tx_types: Vec<TxType>,
     transaction_execution_results: Vec<TransactionExecutionInfo>,
     block_number: u64,
     fee_types: Vec<FeeType>,
-) -> Result<Vec<SimulatedTransaction>, ConvertCallInfoToExecuteInvocationError> {
+    decode_revert_reasons: bool,
+) -> Result<Vec<WithDecodedRevertReason<SimulatedTransaction>>, ConvertCallInfoToExecuteInvocationError> {
     let mut results = vec![];
 
     for ((tx_type, res), fee_type) in
@@ -103,7 +158,7 @@ fn tx_execution_infos_to_simulated_transactions(
         let data_gas_consumed = res.da_gas.l1_data_gas.into();
         let data_gas_price = res.da_gas.l1_gas.into();
 
-        results.push(SimulatedTransaction {
+        let simulated_transaction = SimulatedTransaction {
             transaction_trace,
             fee_estimation: FeeEstimate {
                 gas_consumed,
@@ -113,7 +168,78 @@ fn tx_execution_infos_to_simulated_transactions(
                 overall_fee,
                 unit,
             },
-        });
+        };
+        results.push(with_decoded_revert_reason(
+            simulated_transaction,
+            res.revert_error.as_deref(),
+            decode_revert_reasons,
+        ));
     }
     Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+    use starknet_core::types::{
+        BroadcastedDeployAccountTransaction, BroadcastedDeployAccountTransactionV1, BroadcastedInvokeTransaction,
+        BroadcastedInvokeTransactionV1, FieldElement,
+    };
+    use starknet_core::utils::get_contract_address;
+
+    use super::*;
+
+    fn class_hash() -> FieldElement {
+        FieldElement::from(0x1234u64)
+    }
+
+    fn account_address() -> FieldElement {
+        get_contract_address(FieldElement::ONE, class_hash(), &[], FieldElement::ZERO)
+    }
+
+    fn deploy_account() -> AccountTransaction {
+        BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction::V1(
+            BroadcastedDeployAccountTransactionV1 {
+                max_fee: FieldElement::ZERO,
+                signature: vec![],
+                nonce: FieldElement::ZERO,
+                contract_address_salt: FieldElement::ONE,
+                constructor_calldata: vec![],
+                class_hash: class_hash(),
+                is_query: true,
+            },
+        ))
+        .to_account_transaction()
+        .expect("Failed to convert the account deployment")
+    }
+
+    fn invoke(sender_address: FieldElement) -> AccountTransaction {
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(BroadcastedInvokeTransactionV1 {
+            sender_address,
+            calldata: vec![],
+            max_fee: FieldElement::ZERO,
+            signature: vec![],
+            nonce: FieldElement::ONE,
+            is_query: true,
+        }))
+        .to_account_transaction()
+        .expect("Failed to convert the invoke transaction")
+    }
+
+    #[test]
+    fn test_deployment_followed_by_invokes_from_the_account() {
+        let transactions = [deploy_account(), invoke(account_address()), invoke(account_address())];
+        assert!(check_deployment_order(&transactions).is_ok());
+    }
+
+    #[test]
+    fn test_invoke_from_an_account_deployed_later() {
+        let transactions = [invoke(account_address()), deploy_account()];
+        assert!(matches!(check_deployment_order(&transactions), Err(StarknetRpcApiError::ValidationFailure)));
+    }
+
+    #[test]
+    fn test_invokes_from_other_accounts_are_unaffected() {
+        let transactions = [invoke(FieldElement::TWO), deploy_account(), invoke(FieldElement::TWO)];
+        assert!(check_deployment_order(&transactions).is_ok());
+    }
+}
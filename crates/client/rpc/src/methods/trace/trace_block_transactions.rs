@@ -1,4 +1,5 @@
 use jsonrpsee::core::RpcResult;
+use mc_rpc_core::WithDecodedRevertReason;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::TxType;
@@ -13,15 +14,16 @@ use starknet_core::types::{BlockId, TransactionTraceWithHash};
 use super::utils::tx_execution_infos_to_tx_trace;
 use crate::deoxys_backend_client::get_block_by_block_hash;
 use crate::errors::StarknetRpcApiError;
-use crate::utils::execution::{block_context, re_execute_transactions};
-use crate::utils::helpers::{previous_substrate_block_hash, tx_hash_compute, tx_hash_retrieve};
+use crate::utils::execution::re_execute_transactions;
+use crate::utils::helpers::{previous_block_context, tx_hash_compute, tx_hash_retrieve};
+use crate::utils::revert_reason::with_decoded_revert_reason;
 use crate::utils::transaction::blockifier_transactions;
 use crate::Starknet;
 
 pub async fn trace_block_transactions<BE, C, H>(
     starknet: &Starknet<BE, C, H>,
     block_id: BlockId,
-) -> RpcResult<Vec<TransactionTraceWithHash>>
+) -> RpcResult<Vec<WithDecodedRevertReason<TransactionTraceWithHash>>>
 where
     BE: Backend<DBlockT> + 'static,
     C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
@@ -40,10 +42,11 @@ where
     })?;
     let block_header = starknet_block.header();
     let block_number = block_header.block_number;
-    let block_hash: Felt252Wrapper = block_header.hash::<H>();
+    // the transactions are executed against the state of the previous block
+    starknet.ensure_state_available(block_number.saturating_sub(1))?;
     let chain_id = starknet.chain_id()?;
-    let previous_substrate_block_hash = previous_substrate_block_hash(starknet, substrate_block_hash)?;
-    let block_context = block_context(starknet.client.as_ref(), previous_substrate_block_hash)?;
+    let block_hash: Felt252Wrapper = block_header.hash::<H>(chain_id.0.into());
+    let block_context = previous_block_context(starknet, substrate_block_hash)?;
 
     let block_txs_hashes = if let Some(tx_hashes) = starknet.get_cached_transaction_hashes(block_hash.into()) {
         tx_hash_retrieve(tx_hashes)
@@ -83,10 +86,15 @@ where
             Transaction::Deploy(_) => unreachable!(),
         };
 
-        match tx_execution_infos_to_tx_trace(tx_type, &transactions_info[index], block_number) {
+        let execution_info = &transactions_info[index];
+        match tx_execution_infos_to_tx_trace(tx_type, execution_info, block_number) {
             Ok(trace) => {
                 let transaction_trace = TransactionTraceWithHash { trace_root: trace, transaction_hash: *tx_hash };
-                transactions_traces.push(transaction_trace);
+                transactions_traces.push(with_decoded_revert_reason(
+                    transaction_trace,
+                    execution_info.revert_error.as_deref(),
+                    starknet.decode_revert_reasons,
+                ));
             }
             Err(e) => {
                 log::error!("Failed to generate trace: {}", e);
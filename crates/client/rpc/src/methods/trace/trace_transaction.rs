@@ -1,12 +1,14 @@
 use blockifier::transaction::account_transaction::AccountTransaction;
 use jsonrpsee::core::RpcResult;
 use mc_db::DeoxysBackend;
+use mc_rpc_core::{TraceFormat, TransactionTraceOutput};
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
 use mp_transactions::TxType;
 use mp_types::block::DBlockT;
 use pallet_starknet_runtime_api::{ConvertTransactionRuntimeApi, StarknetRuntimeApi};
 use sc_client_api::{Backend, BlockBackend, StorageProvider};
+use serde_json::json;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_api::transaction::Transaction;
@@ -14,18 +16,20 @@ use starknet_core::types::TransactionTraceWithHash;
 use starknet_ff::FieldElement;
 
 use super::super::read::get_transaction_receipt::execution_infos;
+use super::cairo_profiler::tx_execution_infos_to_cairo_profiler_trace;
 use super::utils::tx_execution_infos_to_tx_trace;
 use crate::deoxys_backend_client::get_block_by_block_hash;
 use crate::errors::StarknetRpcApiError;
-use crate::utils::execution::block_context;
-use crate::utils::helpers::{previous_substrate_block_hash, tx_hash_compute, tx_hash_retrieve};
+use crate::utils::helpers::{previous_block_context, tx_hash_compute, tx_hash_retrieve};
+use crate::utils::revert_reason::with_decoded_revert_reason;
 use crate::utils::transaction::blockifier_transactions;
 use crate::Starknet;
 
 pub async fn trace_transaction<BE, C, H>(
     starknet: &Starknet<BE, C, H>,
     transaction_hash: FieldElement,
-) -> RpcResult<TransactionTraceWithHash>
+    format: TraceFormat,
+) -> RpcResult<TransactionTraceOutput>
 where
     BE: Backend<DBlockT> + 'static,
     C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
@@ -39,15 +43,18 @@ where
             log::error!("Failed to get transaction's substrate block hash from mapping_db: {e}");
             StarknetRpcApiError::TxnHashNotFound
         })?
-        .ok_or(StarknetRpcApiError::TxnHashNotFound)?;
+        .ok_or_else(|| {
+            StarknetRpcApiError::TxnHashNotFound.with_data(json!({ "transaction_hash": transaction_hash }))
+        })?;
 
     let starknet_block = get_block_by_block_hash(starknet.client.as_ref(), substrate_block_hash)?;
     let block_header = starknet_block.header();
-    let block_hash: Felt252Wrapper = block_header.hash::<H>();
-    let block_number = block_header.block_number;
     let chain_id = starknet.chain_id()?;
-    let previous_substrate_block_hash = previous_substrate_block_hash(starknet, substrate_block_hash)?;
-    let block_context = block_context(starknet.client.as_ref(), previous_substrate_block_hash)?;
+    let block_hash: Felt252Wrapper = block_header.hash::<H>(chain_id.0.into());
+    let block_number = block_header.block_number;
+    // the transactions are executed against the state of the previous block
+    starknet.ensure_state_available(block_number.saturating_sub(1))?;
+    let block_context = previous_block_context(starknet, substrate_block_hash)?;
 
     // retrieve all transaction hashes from the block in the cache or compute them
     // here we can optimize by computing only tx before the one that we want to trace (optimized if we
@@ -91,9 +98,22 @@ where
 
     let execution_infos = execution_infos(transactions_blockifier, &block_context)?;
 
+    if format == TraceFormat::CairoProfiler {
+        let trace = tx_execution_infos_to_cairo_profiler_trace(transaction_hash, &execution_infos, block_number)
+            .map_err(|e| {
+                log::error!("Failed to build the cairo-profiler trace of '{transaction_hash:#x}': {e}");
+                StarknetRpcApiError::InternalServerError
+            })?;
+        return Ok(TransactionTraceOutput::CairoProfiler(trace));
+    }
+
     let trace = tx_execution_infos_to_tx_trace(tx_type, &execution_infos, block_number).unwrap();
 
     let tx_trace = TransactionTraceWithHash { transaction_hash, trace_root: trace };
 
-    Ok(tx_trace)
+    Ok(TransactionTraceOutput::Starknet(with_decoded_revert_reason(
+        tx_trace,
+        execution_infos.revert_error.as_deref(),
+        starknet.decode_revert_reasons,
+    )))
 }
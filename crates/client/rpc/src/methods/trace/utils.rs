@@ -2,8 +2,13 @@ use std::collections::HashMap;
 
 use blockifier::execution::call_info::CallInfo;
 use blockifier::transaction::objects::TransactionExecutionInfo;
+use cairo_vm::vm::runners::builtin_runner::{
+    BITWISE_BUILTIN_NAME, EC_OP_BUILTIN_NAME, HASH_BUILTIN_NAME, KECCAK_BUILTIN_NAME, POSEIDON_BUILTIN_NAME,
+    RANGE_CHECK_BUILTIN_NAME, SEGMENT_ARENA_BUILTIN_NAME, SIGNATURE_BUILTIN_NAME,
+};
+use cairo_vm::vm::runners::cairo_runner::ExecutionResources as VmExecutionResources;
 use mc_db::storage_handler;
-use mc_sync::l2::get_highest_block_hash_and_number;
+use mc_sync::state::SyncState;
 use mp_felt::Felt252Wrapper;
 use mp_transactions::TxType;
 use starknet_api::core::ContractAddress;
@@ -63,7 +68,35 @@ fn blockifier_to_starknet_rs_ordered_events(
         .collect()
 }
 
-fn try_get_funtion_invocation_from_call_info(
+/// Returns the class hash of the contract executed by a call.
+pub fn call_info_class_hash(
+    call_info: &CallInfo,
+    class_hash_cache: &mut HashMap<ContractAddress, FieldElement>,
+    block_number: u64,
+) -> Result<FieldElement, TryFuntionInvocationFromCallInfoError> {
+    // Blockifier call info does not give use the class_hash "if it can be deducted from the storage
+    // address". We have to do this decution ourselves here
+    if let Some(class_hash) = call_info.call.class_hash {
+        let felt_wrapper: Felt252Wrapper = Felt252Wrapper::from(class_hash.0);
+        Ok(FieldElement::from(felt_wrapper))
+    } else if let Some(cached_hash) = class_hash_cache.get(&call_info.call.storage_address) {
+        Ok(*cached_hash)
+    } else {
+        // Compute and cache the class hash
+        let Ok(Some(class_hash)) =
+            storage_handler::contract_data().get_class_hash_at(&call_info.call.storage_address, block_number)
+        else {
+            return Err(TryFuntionInvocationFromCallInfoError::ContractNotFound);
+        };
+
+        let computed_hash = FieldElement::from_byte_slice_be(class_hash.0.bytes()).unwrap();
+        class_hash_cache.insert(call_info.call.storage_address, computed_hash);
+
+        Ok(computed_hash)
+    }
+}
+
+pub fn try_get_funtion_invocation_from_call_info(
     call_info: &CallInfo,
     class_hash_cache: &mut HashMap<ContractAddress, FieldElement>,
     block_number: u64,
@@ -97,40 +130,10 @@ fn try_get_funtion_invocation_from_call_info(
         blockifier::execution::entry_point::CallType::Delegate => starknet_core::types::CallType::Delegate,
     };
 
-    // Blockifier call info does not give use the class_hash "if it can be deducted from the storage
-    // address". We have to do this decution ourselves here
-    let class_hash = if let Some(class_hash) = call_info.call.class_hash {
-        let felt_wrapper: Felt252Wrapper = Felt252Wrapper::from(class_hash.0);
-        FieldElement::from(felt_wrapper)
-    } else if let Some(cached_hash) = class_hash_cache.get(&call_info.call.storage_address) {
-        *cached_hash
-    } else {
-        // Compute and cache the class hash
-        let Ok(Some(class_hash)) =
-            storage_handler::contract_data().get_class_hash_at(&call_info.call.storage_address, block_number)
-        else {
-            return Err(TryFuntionInvocationFromCallInfoError::ContractNotFound);
-        };
+    let class_hash = call_info_class_hash(call_info, class_hash_cache, block_number)?;
 
-        let computed_hash = FieldElement::from_byte_slice_be(class_hash.0.bytes()).unwrap();
-        class_hash_cache.insert(call_info.call.storage_address, computed_hash);
-
-        computed_hash
-    };
-
-    // TODO: Replace this with non default exec resources
-    let computation_resources = ComputationResources {
-        steps: 0,
-        memory_holes: None,
-        range_check_builtin_applications: None,
-        pedersen_builtin_applications: None,
-        poseidon_builtin_applications: None,
-        ec_op_builtin_applications: None,
-        ecdsa_builtin_applications: None,
-        bitwise_builtin_applications: None,
-        keccak_builtin_applications: None,
-        segment_arena_builtin: None,
-    };
+    // the resources of a call include those of its inner calls
+    let computation_resources = computation_resources([&call_info.vm_resources]);
 
     Ok(starknet_core::types::FunctionInvocation {
         contract_address: FieldElement::from(Felt252Wrapper::from(call_info.call.storage_address.0.0)),
@@ -148,6 +151,36 @@ fn try_get_funtion_invocation_from_call_info(
     })
 }
 
+/// Sums the VM resources used by calls, as the computation resources of a trace.
+pub fn computation_resources<'a>(
+    resources: impl IntoIterator<Item = &'a VmExecutionResources>,
+) -> ComputationResources {
+    let mut steps = 0;
+    let mut memory_holes = 0;
+    let mut builtins = HashMap::<&str, usize>::new();
+    for resources in resources {
+        steps += resources.n_steps;
+        memory_holes += resources.n_memory_holes;
+        for (builtin, count) in resources.builtin_instance_counter.iter() {
+            *builtins.entry(builtin.as_str()).or_default() += count;
+        }
+    }
+
+    let applications = |builtin: &str| builtins.get(builtin).map(|count| *count as u64).filter(|count| *count > 0);
+    ComputationResources {
+        steps: steps as u64,
+        memory_holes: Some(memory_holes as u64).filter(|memory_holes| *memory_holes > 0),
+        range_check_builtin_applications: applications(RANGE_CHECK_BUILTIN_NAME),
+        pedersen_builtin_applications: applications(HASH_BUILTIN_NAME),
+        poseidon_builtin_applications: applications(POSEIDON_BUILTIN_NAME),
+        ec_op_builtin_applications: applications(EC_OP_BUILTIN_NAME),
+        ecdsa_builtin_applications: applications(SIGNATURE_BUILTIN_NAME),
+        bitwise_builtin_applications: applications(BITWISE_BUILTIN_NAME),
+        keccak_builtin_applications: applications(KECCAK_BUILTIN_NAME),
+        segment_arena_builtin: applications(SEGMENT_ARENA_BUILTIN_NAME),
+    }
+}
+
 pub fn tx_execution_infos_to_tx_trace(
     tx_type: TxType,
     tx_exec_info: &TransactionExecutionInfo,
@@ -155,20 +188,13 @@ pub fn tx_execution_infos_to_tx_trace(
 ) -> Result<TransactionTrace, ConvertCallInfoToExecuteInvocationError> {
     let mut class_hash_cache: HashMap<ContractAddress, FieldElement> = HashMap::new();
 
-    // TODO: Replace this with non default exec resources
+    let call_infos =
+        [&tx_exec_info.validate_call_info, &tx_exec_info.execute_call_info, &tx_exec_info.fee_transfer_call_info];
+    // TODO: Replace this with non default data resources
     let execution_resources = ExecutionResources {
-        computation_resources: ComputationResources {
-            steps: 0,
-            memory_holes: None,
-            range_check_builtin_applications: None,
-            pedersen_builtin_applications: None,
-            poseidon_builtin_applications: None,
-            ec_op_builtin_applications: None,
-            ecdsa_builtin_applications: None,
-            bitwise_builtin_applications: None,
-            keccak_builtin_applications: None,
-            segment_arena_builtin: None,
-        },
+        computation_resources: computation_resources(
+            call_infos.into_iter().flatten().map(|call_info| &call_info.vm_resources),
+        ),
         data_resources: DataResources { data_availability: DataAvailabilityResources { l1_gas: 0, l1_data_gas: 0 } },
     };
 
@@ -243,13 +269,38 @@ pub fn tx_execution_infos_to_tx_trace(
 }
 
 // TODO: move to mod utils
-pub fn block_number_by_id(id: BlockId) -> u64 {
+pub fn block_number_by_id(sync_state: &SyncState, id: BlockId) -> u64 {
     match id {
         BlockId::Number(number) => number,
         BlockId::Hash(block_hash) => match storage_handler::block_number().get(&Felt252Wrapper(block_hash)) {
             Ok(Some(block_number)) => block_number,
-            _ => get_highest_block_hash_and_number().1,
+            _ => sync_state.highest_block_hash_and_number().1,
         },
-        BlockId::Tag(_) => get_highest_block_hash_and_number().1,
+        BlockId::Tag(_) => sync_state.highest_block_hash_and_number().1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_computation_resources() {
+        let resources = |n_steps, n_memory_holes, builtins: &[(&str, usize)]| VmExecutionResources {
+            n_steps,
+            n_memory_holes,
+            builtin_instance_counter: builtins.iter().map(|(name, count)| (name.to_string(), *count)).collect(),
+        };
+        let validate = resources(100, 2, &[(RANGE_CHECK_BUILTIN_NAME, 5)]);
+        let execute = resources(300, 0, &[(RANGE_CHECK_BUILTIN_NAME, 10), (HASH_BUILTIN_NAME, 3)]);
+
+        let total = computation_resources([&validate, &execute]);
+        assert_eq!(total.steps, 400);
+        assert_eq!(total.memory_holes, Some(2));
+        assert_eq!(total.range_check_builtin_applications, Some(15));
+        assert_eq!(total.pedersen_builtin_applications, Some(3));
+        assert_eq!(total.poseidon_builtin_applications, None);
+
+        assert_eq!(computation_resources([&execute]).memory_holes, None);
     }
 }
@@ -0,0 +1,416 @@
+//! Audit of the responses served against the Starknet OpenRPC specification.
+//!
+//! In audit mode, the result of every call to a method of the specification is validated against
+//! the schema of its result, and its errors against the errors the method declares. Violations are
+//! only logged, the responses are served as built, so that spec conformance regressions are caught
+//! in staging rather than by users.
+//!
+//! The specification is read from the `api` directory of a checkout of
+//! [starknet-specs](https://github.com/starkware-libs/starknet-specs) at the version served. Only
+//! the subset of JSON Schema used by the specification is validated, and `oneOf` is checked as
+//! `anyOf`, as some of its variants overlap.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use jsonrpsee::core::Error;
+use jsonrpsee::types::error::{CallError, ErrorObject};
+use jsonrpsee::RpcModule;
+use regex::Regex;
+use serde_json::Value;
+
+/// The documents of the specification covering the methods served by the node.
+const SPEC_DOCUMENTS: [&str; 3] =
+    ["starknet_api_openrpc.json", "starknet_write_api.json", "starknet_trace_api_openrpc.json"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum SpecAuditError {
+    #[error("failed to read specification document {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("invalid specification document {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+    #[error("invalid pattern {0} in the specification: {1}")]
+    Pattern(String, regex::Error),
+}
+
+/// A value of a response which does not conform to the specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The JSON pointer of the value in the response.
+    pub path: String,
+    pub message: String,
+}
+
+/// The result and errors of a method, as specified.
+struct MethodSpec {
+    /// The document the method is specified in, which its local references point into.
+    document: String,
+    result: Value,
+    errors: Vec<i64>,
+}
+
+/// The Starknet OpenRPC specification, which responses are validated against.
+pub struct SpecAudit {
+    version: String,
+    documents: HashMap<String, Value>,
+    methods: HashMap<String, MethodSpec>,
+    patterns: HashMap<String, Regex>,
+}
+
+impl SpecAudit {
+    /// Loads the specification from the `api` directory of a checkout of starknet-specs.
+    pub fn load(dir: &Path) -> Result<Self, SpecAuditError> {
+        let mut documents = HashMap::new();
+        for name in SPEC_DOCUMENTS {
+            let path = dir.join(name);
+            let content = std::fs::read_to_string(&path).map_err(|e| SpecAuditError::Io(path.clone(), e))?;
+            let document = serde_json::from_str(&content).map_err(|e| SpecAuditError::Parse(path, e))?;
+            documents.insert(name.to_string(), document);
+        }
+        Self::from_documents(documents)
+    }
+
+    fn from_documents(documents: HashMap<String, Value>) -> Result<Self, SpecAuditError> {
+        let version = documents
+            .values()
+            .find_map(|document| document.pointer("/info/version")?.as_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let mut patterns = HashMap::new();
+        for document in documents.values() {
+            collect_patterns(document, &mut patterns)?;
+        }
+
+        let mut audit = Self { version, documents, methods: HashMap::new(), patterns };
+        let mut methods = HashMap::new();
+        for (name, document) in &audit.documents {
+            for method in document["methods"].as_array().into_iter().flatten() {
+                let Some(method_name) = method["name"].as_str() else {
+                    continue;
+                };
+                let errors = method["errors"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|error| audit.resolve(name, error)?.1["code"].as_i64())
+                    .collect();
+                let result = method["result"]["schema"].clone();
+                methods.insert(method_name.to_string(), MethodSpec { document: name.clone(), result, errors });
+            }
+        }
+        audit.methods = methods;
+        Ok(audit)
+    }
+
+    /// The version of the specification, as stated by its documents.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Validates the response to a call of `method`. The methods which are not part of the
+    /// specification are not validated.
+    pub fn check_response(&self, method: &str, response: &Value) -> Vec<Violation> {
+        let Some(spec) = self.methods.get(method) else {
+            return Vec::new();
+        };
+
+        let mut violations = Vec::new();
+        if let Some(error) = response.get("error") {
+            // the errors of the json-rpc protocol itself, such as invalid params, are negative
+            match error.get("code").and_then(Value::as_i64) {
+                Some(code) if code >= 0 && !spec.errors.contains(&code) => violations.push(Violation {
+                    path: "/error/code".to_string(),
+                    message: format!("error {code} is not declared by the method"),
+                }),
+                _ => {}
+            }
+        } else if let Some(result) = response.get("result").filter(|_| !spec.result.is_null()) {
+            self.validate(&spec.document, &spec.result, result, "/result", &mut violations);
+        }
+        violations
+    }
+
+    /// Follows the references of a schema, returning the schema they lead to along with the
+    /// document it is in.
+    fn resolve<'a>(&'a self, document: &'a str, schema: &'a Value) -> Option<(&'a str, &'a Value)> {
+        let Some(reference) = schema.get("$ref").and_then(Value::as_str) else {
+            return Some((document, schema));
+        };
+        let (file, pointer) = reference.split_once('#')?;
+        // references to the other documents are relative to the root of the repository
+        let file = match file.rsplit('/').next() {
+            Some("") | None => document,
+            Some(file) => file,
+        };
+        let (document, target) = self.documents.get_key_value(file)?;
+        self.resolve(document, target.pointer(pointer)?)
+    }
+
+    fn validate(&self, document: &str, schema: &Value, value: &Value, path: &str, violations: &mut Vec<Violation>) {
+        let violation = |message: String| Violation { path: path.to_string(), message };
+        let Some((document, schema)) = self.resolve(document, schema) else {
+            violations.push(violation(format!("unresolved schema {schema}")));
+            return;
+        };
+
+        if let Some(schemas) = schema.get("allOf").and_then(Value::as_array) {
+            for schema in schemas {
+                self.validate(document, schema, value, path, violations);
+            }
+        }
+        let variants = schema.get("oneOf").or_else(|| schema.get("anyOf")).and_then(Value::as_array);
+        if let Some(variants) = variants {
+            let mut closest: Option<Vec<Violation>> = None;
+            for variant in variants {
+                let mut variant_violations = Vec::new();
+                self.validate(document, variant, value, path, &mut variant_violations);
+                if variant_violations.is_empty() {
+                    closest = None;
+                    break;
+                }
+                if closest.as_ref().map_or(true, |closest| variant_violations.len() < closest.len()) {
+                    closest = Some(variant_violations);
+                }
+            }
+            // the violations of the variant the value is the closest to are the most telling
+            violations.extend(closest.into_iter().flatten());
+        }
+        if let Some(schema) = schema.get("not") {
+            let mut not_violations = Vec::new();
+            self.validate(document, schema, value, path, &mut not_violations);
+            if not_violations.is_empty() {
+                violations.push(violation("matches a schema it must not match".to_string()));
+            }
+        }
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            if !values.contains(value) {
+                violations.push(violation(format!("{value} is not one of {}", Value::Array(values.clone()))));
+            }
+        }
+        if let Some(ty) = schema.get("type").and_then(Value::as_str) {
+            if !has_type(value, ty) {
+                violations.push(violation(format!("{value} is not of type {ty}")));
+                return;
+            }
+        }
+
+        match value {
+            Value::String(string) => {
+                let pattern = schema.get("pattern").and_then(Value::as_str);
+                if let Some((pattern, regex)) = pattern.and_then(|pattern| self.patterns.get_key_value(pattern)) {
+                    if !regex.is_match(string) {
+                        violations.push(violation(format!("\"{string}\" does not match {pattern}")));
+                    }
+                }
+            }
+            Value::Number(number) => {
+                let number = number.as_f64().unwrap_or_default();
+                match schema.get("minimum").and_then(Value::as_f64) {
+                    Some(minimum) if number < minimum => {
+                        violations.push(violation(format!("{number} is below the minimum {minimum}")))
+                    }
+                    _ => {}
+                }
+                match schema.get("maximum").and_then(Value::as_f64) {
+                    Some(maximum) if number > maximum => {
+                        violations.push(violation(format!("{number} is above the maximum {maximum}")))
+                    }
+                    _ => {}
+                }
+            }
+            Value::Object(object) => {
+                let required = schema.get("required").and_then(Value::as_array).into_iter().flatten();
+                for field in required.filter_map(Value::as_str).filter(|field| !object.contains_key(*field)) {
+                    violations.push(violation(format!("missing required field {field}")));
+                }
+                let properties = schema.get("properties").and_then(Value::as_object);
+                for (field, value) in object {
+                    let field_path = format!("{path}/{field}");
+                    let property = properties.and_then(|properties| properties.get(field));
+                    match (property, schema.get("additionalProperties")) {
+                        (Some(schema), _) => self.validate(document, schema, value, &field_path, violations),
+                        (None, Some(Value::Bool(false))) => {
+                            violations.push(Violation { path: field_path, message: "unexpected field".to_string() })
+                        }
+                        (None, Some(schema @ Value::Object(_))) => {
+                            self.validate(document, schema, value, &field_path, violations)
+                        }
+                        (None, _) => {}
+                    }
+                }
+            }
+            Value::Array(values) => {
+                if let Some(schema) = schema.get("items") {
+                    for (i, value) in values.iter().enumerate() {
+                        self.validate(document, schema, value, &format!("{path}/{i}"), violations);
+                    }
+                }
+            }
+            Value::Bool(_) | Value::Null => {}
+        }
+    }
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Compiles the patterns of every schema of a document.
+fn collect_patterns(value: &Value, patterns: &mut HashMap<String, Regex>) -> Result<(), SpecAuditError> {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(pattern)) = object.get("pattern") {
+                if !patterns.contains_key(pattern) {
+                    let regex = Regex::new(pattern).map_err(|e| SpecAuditError::Pattern(pattern.clone(), e))?;
+                    patterns.insert(pattern.clone(), regex);
+                }
+            }
+            object.values().try_for_each(|value| collect_patterns(value, patterns))
+        }
+        Value::Array(values) => values.iter().try_for_each(|value| collect_patterns(value, patterns)),
+        _ => Ok(()),
+    }
+}
+
+/// Audits the responses of the methods of `module` which are part of the specification.
+///
+/// The calls are forwarded to the methods as registered, and their responses are served unchanged
+/// once validated.
+pub fn audit_module(module: RpcModule<()>, audit: Arc<SpecAudit>) -> Result<RpcModule<()>, Error> {
+    let inner = module.clone();
+    let mut audited = module;
+
+    let method_names: Vec<&'static str> =
+        inner.method_names().filter(|name| audit.methods.contains_key(*name)).collect();
+    for method_name in method_names {
+        audited.remove_method(method_name);
+        let (inner, audit) = (inner.clone(), Arc::clone(&audit));
+        audited.register_async_method(method_name, move |params, _| {
+            let (inner, audit) = (inner.clone(), Arc::clone(&audit));
+            async move {
+                let request = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 0,
+                    "method": method_name,
+                    "params": params.parse::<Option<Value>>()?.unwrap_or(Value::Array(vec![])),
+                });
+                let (response, _) = inner.raw_json_request(&request.to_string()).await?;
+                let mut response: Value = serde_json::from_str(&response.result)?;
+
+                for Violation { path, message } in audit.check_response(method_name, &response) {
+                    log::warn!(
+                        "📋 Response to {method_name} does not conform to the {} specification at {path}: {message}",
+                        audit.version()
+                    );
+                }
+
+                match response.get_mut("error").map(Value::take) {
+                    Some(error) => {
+                        let code = error["code"].as_i64().unwrap_or_default() as i32;
+                        let message = error["message"].as_str().unwrap_or_default().to_string();
+                        let data = error.get("data").cloned();
+                        Err(Error::Call(CallError::Custom(ErrorObject::owned(code, message, data))))
+                    }
+                    None => Ok(response.get_mut("result").map(Value::take).unwrap_or_default()),
+                }
+            }
+        })?;
+    }
+    Ok(audited)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn audit() -> SpecAudit {
+        let api = json!({
+            "info": { "version": "0.7.1" },
+            "methods": [{
+                "name": "starknet_blockHashAndNumber",
+                "result": { "schema": { "$ref": "#/components/schemas/BLOCK_HASH_AND_NUMBER" } },
+                "errors": [{ "$ref": "#/components/errors/NO_BLOCKS" }],
+            }],
+            "components": {
+                "schemas": {
+                    "BLOCK_HASH_AND_NUMBER": {
+                        "type": "object",
+                        "properties": {
+                            "block_hash": { "$ref": "#/components/schemas/FELT" },
+                            "block_number": { "type": "integer", "minimum": 0 },
+                        },
+                        "required": ["block_hash", "block_number"],
+                    },
+                    "FELT": { "type": "string", "pattern": "^0x(0|[a-fA-F1-9]{1}[a-fA-F0-9]{0,62})$" },
+                },
+                "errors": { "NO_BLOCKS": { "code": 32, "message": "There are no blocks" } },
+            },
+        });
+        let trace_api = json!({
+            "methods": [{
+                "name": "starknet_traceBlockTransactions",
+                "result": {
+                    "schema": {
+                        "type": "array",
+                        "items": { "$ref": "./api/starknet_api_openrpc.json#/components/schemas/FELT" },
+                    },
+                },
+                "errors": [{ "$ref": "./api/starknet_api_openrpc.json#/components/errors/NO_BLOCKS" }],
+            }],
+        });
+        let documents = HashMap::from([
+            ("starknet_api_openrpc.json".to_string(), api),
+            ("starknet_trace_api_openrpc.json".to_string(), trace_api),
+        ]);
+        SpecAudit::from_documents(documents).unwrap()
+    }
+
+    #[test]
+    fn conforming_responses_pass() {
+        let audit = audit();
+        assert_eq!(audit.version(), "0.7.1");
+
+        let response = json!({ "result": { "block_hash": "0x1a", "block_number": 3 } });
+        assert!(audit.check_response("starknet_blockHashAndNumber", &response).is_empty());
+        let response = json!({ "error": { "code": 32, "message": "There are no blocks" } });
+        assert!(audit.check_response("starknet_blockHashAndNumber", &response).is_empty());
+        // the methods out of the specification are not audited
+        assert!(audit.check_response("deoxys_getBlockHeader", &json!({ "result": 1 })).is_empty());
+    }
+
+    #[test]
+    fn violations_are_located() {
+        let audit = audit();
+
+        let response = json!({ "result": { "block_hash": "0x01a" } });
+        let violations = audit.check_response("starknet_blockHashAndNumber", &response);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].path, "/result");
+        assert_eq!(violations[0].message, "missing required field block_number");
+        assert_eq!(violations[1].path, "/result/block_hash");
+
+        let response = json!({ "error": { "code": 24, "message": "Block not found" } });
+        let violations = audit.check_response("starknet_blockHashAndNumber", &response);
+        assert_eq!(violations[0].message, "error 24 is not declared by the method");
+
+        // references into the other documents are followed
+        let response = json!({ "result": ["0x1", 2] });
+        let violations = audit.check_response("starknet_traceBlockTransactions", &response);
+        let expected = Violation { path: "/result/1".to_string(), message: "2 is not of type string".to_string() };
+        assert_eq!(violations, vec![expected]);
+        let response = json!({ "error": { "code": 32, "message": "There are no blocks" } });
+        assert!(audit.check_response("starknet_traceBlockTransactions", &response).is_empty());
+    }
+}
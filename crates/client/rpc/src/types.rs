@@ -1,6 +1,13 @@
 use std::num::ParseIntError;
 use std::{fmt, u64};
 
+use mc_rpc_core::ExtendedBlockId;
+use mc_sync::l1::l1_head;
+use mc_sync::utility::get_config;
+use starknet_core::types::{BlockId, BlockTag};
+
+use crate::errors::StarknetRpcApiError;
+
 #[derive(PartialEq, Eq, Debug, Default)]
 pub struct ContinuationToken {
     pub block_n: u64,
@@ -32,6 +39,27 @@ impl ContinuationToken {
     }
 }
 
+/// Resolves the `l1_accepted` tag of `block_id` to the number of the current L1 head.
+///
+/// The `l1_accepted` tag designates no block until the first state update verified on L1 is known.
+/// When pending block tracking is disabled, the `pending` tag resolves to the latest block.
+pub fn resolve_extended_block_id(block_id: ExtendedBlockId) -> Result<BlockId, StarknetRpcApiError> {
+    Ok(match block_id {
+        ExtendedBlockId::L1Accepted => {
+            BlockId::Number(l1_head().ok_or(StarknetRpcApiError::BlockNotFound)?.block_number)
+        }
+        ExtendedBlockId::Starknet(BlockId::Tag(BlockTag::Pending)) if !pending_enabled() => {
+            BlockId::Tag(BlockTag::Latest)
+        }
+        ExtendedBlockId::Starknet(block_id) => block_id,
+    })
+}
+
+/// Whether the pending block is tracked by the sync.
+fn pending_enabled() -> bool {
+    get_config().map_or(true, |config| config.pending)
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
@@ -74,4 +102,13 @@ mod tests {
         let result = ContinuationToken::parse(string_token);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn l1_accepted_is_not_found_before_the_l1_head() {
+        assert!(matches!(
+            resolve_extended_block_id(ExtendedBlockId::L1Accepted),
+            Err(StarknetRpcApiError::BlockNotFound)
+        ));
+        assert_eq!(resolve_extended_block_id(BlockId::Number(3).into()).unwrap(), BlockId::Number(3));
+    }
 }
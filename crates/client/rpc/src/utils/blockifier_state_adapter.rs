@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
 
 use blockifier::execution::contract_class::ContractClass;
 use blockifier::state::errors::StateError;
@@ -8,6 +9,13 @@ use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce};
 use starknet_api::hash::StarkFelt;
 use starknet_api::state::StorageKey;
 
+use crate::execution_policy::PolicyGuard;
+use crate::utils::cache::{ClassCache, CLASS_CACHE_SIZE};
+
+/// The classes loaded by every execution, including the internal calls made during simulations and
+/// fee estimations, which blockifier offers no hook to memoize.
+static CLASS_CACHE: OnceLock<ClassCache> = OnceLock::new();
+
 /// `BlockifierStateAdapter` is only use to re-executing or simulate transactions.
 /// None of the setters should therefore change the storage persistently,
 /// all changes are temporary stored in the struct and are discarded after the execution
@@ -19,6 +27,8 @@ pub struct BlockifierStateAdapter {
     compiled_class_hash_update: HashMap<ClassHash, CompiledClassHash>,
     contract_class_update: HashMap<ClassHash, ContractClass>,
     visited_pcs: HashMap<ClassHash, HashSet<usize>>,
+    /// The execution policy checked whenever a class is loaded, if any.
+    policy: Option<Arc<PolicyGuard>>,
 }
 
 impl BlockifierStateAdapter {
@@ -31,15 +41,21 @@ impl BlockifierStateAdapter {
             compiled_class_hash_update: HashMap::default(),
             contract_class_update: HashMap::default(),
             visited_pcs: HashMap::default(),
+            policy: None,
         }
     }
+
+    pub(crate) fn with_policy(mut self, policy: Option<Arc<PolicyGuard>>) -> Self {
+        self.policy = policy;
+        self
+    }
 }
 
 impl StateReader for BlockifierStateAdapter {
     fn get_storage_at(&self, contract_address: ContractAddress, key: StorageKey) -> StateResult<StarkFelt> {
         match self.storage_update.get(&(contract_address, key)) {
             Some(value) => Ok(*value),
-            None => match storage_handler::contract_storage().get_at(&(contract_address, key), self.block_number) {
+            None => match storage_handler::versioned_storage().get_at(&(contract_address, key), self.block_number) {
                 Ok(Some(value)) => Ok(value),
                 Ok(None) => Ok(StarkFelt::default()),
                 Err(_) => Err(StateError::StateReadError(format!(
@@ -80,12 +96,28 @@ impl StateReader for BlockifierStateAdapter {
     }
 
     fn get_compiled_contract_class(&self, class_hash: ClassHash) -> StateResult<ContractClass> {
-        match self.contract_class_update.get(&class_hash) {
-            Some(contract_class) => Ok(contract_class.clone()),
-            None => match storage_handler::contract_class_data().get(&class_hash) {
-                Ok(Some(contract_class_data)) => Ok(contract_class_data.contract_class),
-                _ => Err(StateError::UndeclaredClassHash(class_hash)),
-            },
+        if let Some(policy) = &self.policy {
+            policy.check_class(class_hash)?;
+        }
+        let contract_class = match self.contract_class_update.get(&class_hash) {
+            Some(contract_class) => contract_class.clone(),
+            None => {
+                let class_cache = CLASS_CACHE.get_or_init(|| ClassCache::new(CLASS_CACHE_SIZE));
+                match class_cache.get(&class_hash) {
+                    Some(contract_class) => contract_class,
+                    None => match storage_handler::contract_class_data().get(&class_hash) {
+                        Ok(Some(contract_class_data)) => {
+                            class_cache.insert(class_hash, contract_class_data.contract_class.clone());
+                            contract_class_data.contract_class
+                        }
+                        _ => return Err(StateError::UndeclaredClassHash(class_hash)),
+                    },
+                }
+            }
+        };
+        match &self.policy {
+            Some(policy) => Ok(policy.restrict_class(class_hash, contract_class)),
+            None => Ok(contract_class),
         }
     }
 
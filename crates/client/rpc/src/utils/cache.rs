@@ -0,0 +1,179 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{Arc, RwLock};
+
+use blockifier::context::BlockContext;
+use blockifier::execution::call_info::CallInfo;
+use blockifier::execution::contract_class::ContractClass;
+use mp_types::block::DHashT;
+use starknet_api::core::{ClassHash, ContractAddress, EntryPointSelector};
+use starknet_api::hash::StarkFelt;
+use starknet_api::transaction::Calldata;
+
+/// Maximum number of block contexts kept in the [BlockContextCache].
+pub const BLOCK_CONTEXT_CACHE_SIZE: usize = 128;
+
+/// Maximum number of call results kept in the [CallCache].
+pub const CALL_CACHE_SIZE: usize = 1024;
+
+/// Maximum number of compiled classes kept in the [ClassCache].
+pub const CLASS_CACHE_SIZE: usize = 256;
+
+/// A bounded map evicting its oldest entries first.
+pub struct BoundedCache<K, V> {
+    capacity: usize,
+    inner: RwLock<(HashMap<K, V>, VecDeque<K>)>,
+}
+
+impl<K, V> BoundedCache<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, inner: RwLock::new((HashMap::with_capacity(capacity), VecDeque::with_capacity(capacity))) }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.inner.read().expect("Failed to acquire read lock on cache").0.get(key).cloned()
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut guard = self.inner.write().expect("Failed to acquire write lock on cache");
+        let (map, order) = &mut *guard;
+
+        if map.insert(key.clone(), value).is_none() {
+            order.push_back(key);
+        }
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                map.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Caches the execution context of the parent of a Substrate block, keyed by the hash of that
+/// block.
+///
+/// The parent of a block never changes, so entries never need to be invalidated: this lets
+/// high-QPS execution workloads skip resolving the previous block and rebuilding its context.
+pub type BlockContextCache = BoundedCache<DHashT, BlockContext>;
+
+/// A call made against the state of a block, under given execution limits.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CallKey {
+    block_hash: DHashT,
+    max_n_steps: u32,
+    max_recursion_depth: usize,
+    /// The class the contract is called with, which is its latest class rather than the one it had
+    /// at the block.
+    class_hash: Option<ClassHash>,
+    contract_address: ContractAddress,
+    entry_point_selector: EntryPointSelector,
+    calldata: Calldata,
+}
+
+impl CallKey {
+    pub fn new(
+        block_hash: DHashT,
+        block_context: &BlockContext,
+        class_hash: Option<ClassHash>,
+        contract_address: ContractAddress,
+        entry_point_selector: EntryPointSelector,
+        calldata: Calldata,
+    ) -> Self {
+        let constants = block_context.versioned_constants();
+        Self {
+            block_hash,
+            max_n_steps: constants.invoke_tx_max_n_steps,
+            max_recursion_depth: constants.max_recursion_depth,
+            class_hash,
+            contract_address,
+            entry_point_selector,
+            calldata,
+        }
+    }
+}
+
+/// The result of a call, as kept by the [CallCache].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedCall {
+    pub retdata: Vec<StarkFelt>,
+    /// The class and entry point of every call of its call tree, so that the execution policy,
+    /// which may change at runtime, is still enforced on the calls served from the cache.
+    pub entry_points: Arc<[(ClassHash, EntryPointSelector)]>,
+}
+
+impl CachedCall {
+    pub fn new(call_info: &CallInfo) -> Self {
+        fn collect(call_info: &CallInfo, entry_points: &mut Vec<(ClassHash, EntryPointSelector)>) {
+            if let Some(class_hash) = call_info.call.class_hash {
+                entry_points.push((class_hash, call_info.call.entry_point_selector));
+            }
+            call_info.inner_calls.iter().for_each(|call_info| collect(call_info, entry_points));
+        }
+
+        let mut entry_points = Vec::new();
+        collect(call_info, &mut entry_points);
+        entry_points.sort_unstable_by_key(|(class_hash, selector)| (class_hash.0, selector.0));
+        entry_points.dedup();
+        Self { retdata: call_info.execution.retdata.0.clone(), entry_points: entry_points.into() }
+    }
+}
+
+/// Caches the results of the calls made at a block, keyed by the hash of that block.
+///
+/// A call only depends on the state of the block and on the execution limits, so heavy batches of
+/// simulations repeating the same calls at a block execute each of them once.
+pub type CallCache = BoundedCache<CallKey, CachedCall>;
+
+/// Caches the compiled classes loaded for execution, keyed by their hash.
+///
+/// A class never changes once declared, so the classes loaded by the internal calls of an execution
+/// are shared with all the calls, fee estimations and simulations which follow.
+pub type ClassCache = BoundedCache<ClassHash, ContractClass>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_cache_evicts_oldest() {
+        let cache = BoundedCache::<u64, u64>::new(2);
+
+        cache.insert(0, 0);
+        cache.insert(1, 1);
+        cache.insert(0, 2);
+        assert_eq!(cache.get(&0), Some(2));
+        assert_eq!(cache.get(&1), Some(1));
+
+        cache.insert(2, 2);
+        assert_eq!(cache.get(&0), None);
+        assert_eq!(cache.get(&1), Some(1));
+        assert_eq!(cache.get(&2), Some(2));
+    }
+
+    #[test]
+    fn test_cached_call_keeps_the_entry_points_of_its_call_tree() {
+        let entry_point = |class_hash: u64, selector: u64| {
+            (ClassHash(StarkFelt::from(class_hash)), EntryPointSelector(StarkFelt::from(selector)))
+        };
+        let call = |(class_hash, selector): (ClassHash, EntryPointSelector), inner_calls: Vec<CallInfo>| {
+            let mut call_info = CallInfo { inner_calls, ..Default::default() };
+            call_info.call.class_hash = Some(class_hash);
+            call_info.call.entry_point_selector = selector;
+            call_info
+        };
+
+        let mut call_info = call(
+            entry_point(1, 1),
+            vec![call(entry_point(2, 1), vec![call(entry_point(1, 1), vec![])]), call(entry_point(2, 2), vec![])],
+        );
+        call_info.execution.retdata.0 = vec![StarkFelt::from(42u64)];
+
+        let cached = CachedCall::new(&call_info);
+        assert_eq!(cached.retdata, vec![StarkFelt::from(42u64)]);
+        assert_eq!(&*cached.entry_points, &[entry_point(1, 1), entry_point(2, 1), entry_point(2, 2)]);
+    }
+}
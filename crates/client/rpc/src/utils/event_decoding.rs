@@ -0,0 +1,402 @@
+//! Decoding of events with the ABI of the class of the contract which emitted them.
+//!
+//! Sierra classes describe their events in a JSON ABI, where the event of the contract is an enum
+//! whose variants are selected by the `starknet_keccak` of their name in the first key. Legacy
+//! classes list their events as entries of their ABI, selected the same way. Felts are decoded as
+//! hexadecimal strings, structs as objects and enums as `{"variant": name, "value": payload}`.
+//!
+//! ABIs are supplied by whoever declared the class, so types are only nested up to
+//! [MAX_DECODING_DEPTH] levels, and arrays are never longer than the felts left to decode them
+//! from.
+
+use std::collections::HashMap;
+use std::iter::Copied;
+use std::slice::Iter;
+
+use mc_db::storage_handler::primitives::contract_class::{
+    AbiEntryWrapper, AbiStructEntryWrapper, AbiTypedParameterWrapper, ContractAbi,
+};
+use serde_json::{json, Map, Value};
+use starknet_core::utils::starknet_keccak;
+use starknet_ff::FieldElement;
+
+type Felts<'a> = Copied<Iter<'a, FieldElement>>;
+
+/// The number of nested types and events an event is decoded through, beyond which it is not
+/// decoded, so that recursive types cannot exhaust the stack.
+const MAX_DECODING_DEPTH: usize = 32;
+
+/// Reads the length of an array, which cannot exceed the number of felts left.
+fn array_len(felts: &mut Felts) -> Option<u64> {
+    let len = u64::try_from(felts.next()?).ok()?;
+    (len <= felts.len() as u64).then_some(len)
+}
+
+/// The events declared in the ABI of a class, and the types they use.
+pub(crate) enum EventAbi {
+    Sierra(SierraAbi),
+    Legacy(Vec<AbiEntryWrapper>),
+}
+
+impl EventAbi {
+    /// Parses the ABI of a class, returning `None` if it cannot be parsed.
+    pub(crate) fn new(abi: ContractAbi) -> Option<Self> {
+        match abi {
+            ContractAbi::Sierra(abi) => SierraAbi::parse(&abi).map(EventAbi::Sierra),
+            ContractAbi::Cairo(entries) => Some(EventAbi::Legacy(entries.unwrap_or_default())),
+        }
+    }
+
+    /// Decodes an event, returning its name and fields, or `None` if the ABI does not describe it.
+    pub(crate) fn decode(&self, keys: &[FieldElement], data: &[FieldElement]) -> Option<(String, Value)> {
+        match self {
+            EventAbi::Sierra(abi) => abi.decode_event(keys, data),
+            EventAbi::Legacy(entries) => decode_legacy_event(entries, keys, data),
+        }
+    }
+}
+
+/// The types and events declared in a Sierra ABI, indexed by name.
+#[derive(Default)]
+pub(crate) struct SierraAbi {
+    structs: HashMap<String, Vec<(String, String)>>,
+    enums: HashMap<String, Vec<(String, String)>>,
+    /// Members of the struct events, along with whether they are a `key` or `data`.
+    struct_events: HashMap<String, Vec<(String, String, String)>>,
+    /// Variants of the enum events, along with whether they are `nested` or `flat`.
+    enum_events: HashMap<String, Vec<(String, String, String)>>,
+    /// Events of ABIs predating typed events, whose fields are all in the data.
+    untyped_events: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl SierraAbi {
+    fn parse(abi: &str) -> Option<Self> {
+        let entries: Vec<Value> = serde_json::from_str(abi).ok()?;
+
+        let mut parsed = Self::default();
+        for entry in entries {
+            let name = entry["name"].as_str().unwrap_or_default().to_string();
+            match (entry["type"].as_str(), entry["kind"].as_str()) {
+                (Some("struct"), _) => {
+                    parsed.structs.insert(name, pairs(&entry["members"]));
+                }
+                (Some("enum"), _) => {
+                    parsed.enums.insert(name, pairs(&entry["variants"]));
+                }
+                (Some("event"), Some("struct")) => {
+                    parsed.struct_events.insert(name, triples(&entry["members"]));
+                }
+                (Some("event"), Some("enum")) => {
+                    parsed.enum_events.insert(name, triples(&entry["variants"]));
+                }
+                (Some("event"), None) => parsed.untyped_events.push((name, pairs(&entry["inputs"]))),
+                _ => {}
+            }
+        }
+        Some(parsed)
+    }
+
+    fn decode_event(&self, keys: &[FieldElement], data: &[FieldElement]) -> Option<(String, Value)> {
+        let selector = *keys.first()?;
+        if let Some((name, inputs)) =
+            self.untyped_events.iter().find(|(name, _)| starknet_keccak(name.as_bytes()) == selector)
+        {
+            return Some((name.clone(), self.decode_members(inputs, &mut data.iter().copied(), 0)?));
+        }
+
+        // the event of the contract is the enum event which is not a variant of another event
+        let mut roots = self
+            .enum_events
+            .keys()
+            .filter(|name| !self.enum_events.values().flatten().any(|(_, variant_type, _)| variant_type == *name));
+        roots.find_map(|root| self.decode_enum_event(root, &mut keys.iter().copied(), &mut data.iter().copied(), 0))
+    }
+
+    fn decode_enum_event(
+        &self,
+        name: &str,
+        keys: &mut Felts,
+        data: &mut Felts,
+        depth: usize,
+    ) -> Option<(String, Value)> {
+        if depth > MAX_DECODING_DEPTH {
+            return None;
+        }
+        let selector = keys.clone().next()?;
+        for (variant, variant_type, kind) in self.enum_events.get(name)? {
+            if kind == "flat" {
+                // flat variants do not emit a key of their own, the variants of their enum are
+                // selected by the same key
+                let (mut flat_keys, mut flat_data) = (keys.clone(), data.clone());
+                if let Some(decoded) = self.decode_enum_event(variant_type, &mut flat_keys, &mut flat_data, depth + 1) {
+                    return Some(decoded);
+                }
+            } else if starknet_keccak(variant.as_bytes()) == selector {
+                keys.next();
+                if self.enum_events.contains_key(variant_type) {
+                    return self.decode_enum_event(variant_type, keys, data, depth + 1);
+                }
+                return Some((variant.clone(), self.decode_struct_event(variant_type, keys, data, depth + 1)?));
+            }
+        }
+        None
+    }
+
+    fn decode_struct_event(&self, name: &str, keys: &mut Felts, data: &mut Felts, depth: usize) -> Option<Value> {
+        let mut fields = Map::new();
+        for (member, member_type, kind) in self.struct_events.get(name)? {
+            let value = match kind.as_str() {
+                "key" => self.decode_type(member_type, keys, depth + 1)?,
+                "data" => self.decode_type(member_type, data, depth + 1)?,
+                _ => return None,
+            };
+            fields.insert(member.clone(), value);
+        }
+        Some(Value::Object(fields))
+    }
+
+    fn decode_members(&self, members: &[(String, String)], felts: &mut Felts, depth: usize) -> Option<Value> {
+        let mut fields = Map::new();
+        for (member, member_type) in members {
+            fields.insert(member.clone(), self.decode_type(member_type, felts, depth + 1)?);
+        }
+        Some(Value::Object(fields))
+    }
+
+    fn decode_type(&self, name: &str, felts: &mut Felts, depth: usize) -> Option<Value> {
+        if depth > MAX_DECODING_DEPTH {
+            return None;
+        }
+        if let Some(item_type) = array_item_type(name) {
+            let len = array_len(felts)?;
+            let items = (0..len).map(|_| self.decode_type(item_type, felts, depth + 1));
+            return items.collect::<Option<_>>().map(Value::Array);
+        }
+        if name == "()" {
+            return Some(Value::Null);
+        }
+        if name == "core::bool" {
+            return Some(Value::Bool(felts.next()? != FieldElement::ZERO));
+        }
+        if let Some(members) = self.structs.get(name) {
+            return self.decode_members(members, felts, depth);
+        }
+        if let Some(variants) = self.enums.get(name) {
+            let index = usize::try_from(u64::try_from(felts.next()?).ok()?).ok()?;
+            let (variant, payload_type) = variants.get(index)?;
+            return Some(json!({ "variant": variant, "value": self.decode_type(payload_type, felts, depth + 1)? }));
+        }
+        if is_single_felt(name) { felts.next().map(felt_value) } else { None }
+    }
+}
+
+/// Returns the `name` and `type` of each element of an array of ABI members or variants.
+fn pairs(members: &Value) -> Vec<(String, String)> {
+    triples(members).into_iter().map(|(name, member_type, _)| (name, member_type)).collect()
+}
+
+/// Returns the `name`, `type` and `kind` of each element of an array of ABI members or variants.
+fn triples(members: &Value) -> Vec<(String, String, String)> {
+    let field = |member: &Value, field: &str| member[field].as_str().unwrap_or_default().to_string();
+    members
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|member| (field(member, "name"), field(member, "type"), field(member, "kind")))
+        .collect()
+}
+
+/// Returns the type of the items of an array or span type.
+fn array_item_type(name: &str) -> Option<&str> {
+    name.strip_prefix("core::array::Array::<")
+        .or_else(|| name.strip_prefix("core::array::Span::<"))
+        .and_then(|item_type| item_type.strip_suffix('>'))
+}
+
+/// Whether a type which is neither a struct nor an enum of the ABI is serialized as a single felt.
+fn is_single_felt(name: &str) -> bool {
+    name == "core::felt252"
+        || name == "core::bytes_31::bytes31"
+        || name.starts_with("core::integer::")
+        || name.starts_with("core::starknet::")
+}
+
+fn felt_value(felt: FieldElement) -> Value {
+    Value::String(format!("{felt:#x}"))
+}
+
+fn decode_legacy_event(
+    entries: &[AbiEntryWrapper],
+    keys: &[FieldElement],
+    data: &[FieldElement],
+) -> Option<(String, Value)> {
+    let selector = *keys.first()?;
+    let event = entries.iter().find_map(|entry| match entry {
+        AbiEntryWrapper::Event(event) if starknet_keccak(event.name.as_bytes()) == selector => Some(event),
+        _ => None,
+    })?;
+    let structs: HashMap<&str, &AbiStructEntryWrapper> = entries
+        .iter()
+        .filter_map(|entry| match entry {
+            AbiEntryWrapper::Struct(entry) => Some((entry.name.as_str(), entry)),
+            _ => None,
+        })
+        .collect();
+
+    let mut fields = Map::new();
+    decode_legacy_parameters(&event.keys, &mut keys[1..].iter().copied(), &structs, &mut fields)?;
+    decode_legacy_parameters(&event.data, &mut data.iter().copied(), &structs, &mut fields)?;
+    Some((event.name.clone(), Value::Object(fields)))
+}
+
+fn decode_legacy_parameters(
+    parameters: &[AbiTypedParameterWrapper],
+    felts: &mut Felts,
+    structs: &HashMap<&str, &AbiStructEntryWrapper>,
+    fields: &mut Map<String, Value>,
+) -> Option<()> {
+    // arrays are preceded by their length
+    let mut last_felt = None;
+    for parameter in parameters {
+        let value = if parameter.r#type == "felt" {
+            let felt = felts.next()?;
+            last_felt = Some(felt);
+            felt_value(felt)
+        } else if let Some(item_type) = parameter.r#type.strip_suffix('*') {
+            let len = u64::try_from(last_felt?).ok().filter(|len| *len <= felts.len() as u64)?;
+            let items = (0..len).map(|_| decode_legacy_type(item_type, felts, structs, 0));
+            Value::Array(items.collect::<Option<_>>()?)
+        } else {
+            decode_legacy_type(&parameter.r#type, felts, structs, 0)?
+        };
+        fields.insert(parameter.name.clone(), value);
+    }
+    Some(())
+}
+
+fn decode_legacy_type(
+    name: &str,
+    felts: &mut Felts,
+    structs: &HashMap<&str, &AbiStructEntryWrapper>,
+    depth: usize,
+) -> Option<Value> {
+    if depth > MAX_DECODING_DEPTH {
+        return None;
+    }
+    if name == "felt" {
+        return felts.next().map(felt_value);
+    }
+
+    let mut fields = Map::new();
+    for member in &structs.get(name)?.members {
+        fields.insert(member.name.clone(), decode_legacy_type(&member.r#type, felts, structs, depth + 1)?);
+    }
+    Some(Value::Object(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use mc_db::storage_handler::primitives::contract_class::{AbiEventEntryWrapper, AbiEventTypeWrapper};
+
+    use super::*;
+
+    const ERC20_ABI: &str = r#"[
+        {"type": "struct", "name": "core::integer::u256", "members": [
+            {"name": "low", "type": "core::integer::u128"},
+            {"name": "high", "type": "core::integer::u128"}
+        ]},
+        {"type": "event", "name": "token::Transfer", "kind": "struct", "members": [
+            {"name": "from", "type": "core::starknet::contract_address::ContractAddress", "kind": "key"},
+            {"name": "to", "type": "core::starknet::contract_address::ContractAddress", "kind": "key"},
+            {"name": "value", "type": "core::integer::u256", "kind": "data"}
+        ]},
+        {"type": "event", "name": "token::Event", "kind": "enum", "variants": [
+            {"name": "Transfer", "type": "token::Transfer", "kind": "nested"}
+        ]}
+    ]"#;
+
+    fn felts(values: &[u64]) -> Vec<FieldElement> {
+        values.iter().map(|value| FieldElement::from(*value)).collect()
+    }
+
+    #[test]
+    fn test_decode_sierra_event() {
+        let abi = EventAbi::new(ContractAbi::Sierra(ERC20_ABI.to_string())).unwrap();
+        let selector = starknet_keccak(b"Transfer");
+
+        let (name, fields) = abi.decode(&[selector, 1u64.into(), 2u64.into()], &felts(&[3, 0])).unwrap();
+        assert_eq!(name, "Transfer");
+        assert_eq!(fields, json!({ "from": "0x1", "to": "0x2", "value": { "low": "0x3", "high": "0x0" } }));
+
+        assert!(abi.decode(&[starknet_keccak(b"Approval")], &[]).is_none());
+        // missing data
+        assert!(abi.decode(&[selector, 1u64.into(), 2u64.into()], &felts(&[3])).is_none());
+    }
+
+    #[test]
+    fn test_decode_legacy_event() {
+        let parameter =
+            |name: &str, r#type: &str| AbiTypedParameterWrapper { name: name.into(), r#type: r#type.into() };
+        let abi = EventAbi::new(ContractAbi::Cairo(Some(vec![AbiEntryWrapper::Event(AbiEventEntryWrapper {
+            r#type: AbiEventTypeWrapper::Event,
+            name: "Batch".into(),
+            keys: vec![],
+            data: vec![parameter("values_len", "felt"), parameter("values", "felt*")],
+        })])))
+        .unwrap();
+
+        let (name, fields) = abi.decode(&[starknet_keccak(b"Batch")], &felts(&[2, 7, 8])).unwrap();
+        assert_eq!(name, "Batch");
+        assert_eq!(fields, json!({ "values_len": "0x2", "values": ["0x7", "0x8"] }));
+
+        // the length exceeds the felts left
+        assert!(abi.decode(&[starknet_keccak(b"Batch")], &felts(&[u64::MAX, 7])).is_none());
+    }
+
+    #[test]
+    fn test_decode_rejects_oversized_arrays() {
+        let abi = r#"[
+            {"type": "event", "name": "Values", "kind": "struct", "members": [
+                {"name": "values", "type": "core::array::Array::<core::felt252>", "kind": "data"}
+            ]},
+            {"type": "event", "name": "Event", "kind": "enum", "variants": [
+                {"name": "Values", "type": "Values", "kind": "nested"}
+            ]}
+        ]"#;
+        let abi = EventAbi::new(ContractAbi::Sierra(abi.to_string())).unwrap();
+        let selector = starknet_keccak(b"Values");
+
+        let (_, fields) = abi.decode(&[selector], &felts(&[2, 7, 8])).unwrap();
+        assert_eq!(fields, json!({ "values": ["0x7", "0x8"] }));
+        assert!(abi.decode(&[selector], &felts(&[3, 7, 8])).is_none());
+        assert!(abi.decode(&[selector], &felts(&[u64::MAX])).is_none());
+    }
+
+    #[test]
+    fn test_decode_rejects_recursive_types() {
+        // a struct containing itself would be decoded forever
+        let abi = r#"[
+            {"type": "struct", "name": "Node", "members": [{"name": "next", "type": "Node"}]},
+            {"type": "event", "name": "Linked", "kind": "struct", "members": [
+                {"name": "node", "type": "Node", "kind": "data"}
+            ]},
+            {"type": "event", "name": "Event", "kind": "enum", "variants": [
+                {"name": "Linked", "type": "Linked", "kind": "nested"}
+            ]}
+        ]"#;
+        let abi = EventAbi::new(ContractAbi::Sierra(abi.to_string())).unwrap();
+        assert!(abi.decode(&[starknet_keccak(b"Linked")], &felts(&[1, 2, 3])).is_none());
+
+        // as would an event flattening itself
+        let abi = r#"[
+            {"type": "event", "name": "Event", "kind": "enum", "variants": [
+                {"name": "Event", "type": "Event", "kind": "flat"}
+            ]},
+            {"type": "event", "name": "Root", "kind": "enum", "variants": [
+                {"name": "Inner", "type": "Event", "kind": "nested"}
+            ]}
+        ]"#;
+        let abi = EventAbi::new(ContractAbi::Sierra(abi.to_string())).unwrap();
+        assert!(abi.decode(&[starknet_keccak(b"Inner"), starknet_keccak(b"Event")], &[]).is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use blockifier::context::{BlockContext, FeeTokenAddresses, TransactionContext};
+use blockifier::execution::call_info::CallInfo;
 use blockifier::execution::entry_point::{CallEntryPoint, CallType, EntryPointExecutionContext};
 use blockifier::execution::errors::EntryPointExecutionError;
 use blockifier::fee::gas_usage::estimate_minimal_gas_vector;
@@ -14,20 +15,23 @@ use blockifier::transaction::transaction_execution::Transaction;
 use blockifier::transaction::transactions::{ExecutableTransaction, L1HandlerTransaction};
 use blockifier::versioned_constants::VersionedConstants;
 use mc_db::storage_handler;
+use mc_sync::utility::get_config;
 use mp_felt::Felt252Wrapper;
 use mp_genesis_config::{ETH_TOKEN_ADDR, STRK_TOKEN_ADDR};
 use mp_simulations::{SimulationFlagForEstimateFee, SimulationFlags};
 use sp_blockchain::HeaderBackend;
 use sp_runtime::traits::Block as BlockT;
-use starknet_api::core::{ContractAddress, EntryPointSelector};
+use starknet_api::core::{ClassHash, ContractAddress, EntryPointSelector};
 use starknet_api::deprecated_contract_class::EntryPointType;
 use starknet_api::hash::StarkHash;
 use starknet_api::transaction::Calldata;
 use starknet_core::types::{FeeEstimate, PriceUnit};
+use starknet_core::utils::parse_cairo_short_string;
 use starknet_ff::FieldElement;
 
 use super::blockifier_state_adapter::BlockifierStateAdapter;
 use crate::errors::StarknetRpcApiError;
+use crate::execution_policy::PolicyGuard;
 use crate::get_block_by_block_hash;
 
 pub fn block_context<B, C>(
@@ -49,7 +53,14 @@ where
         strk_fee_token_address: StarkHash::new_unchecked(STRK_TOKEN_ADDR.0.to_bytes_be()).try_into().unwrap(),
         eth_fee_token_address: StarkHash::new_unchecked(ETH_TOKEN_ADDR.0.to_bytes_be()).try_into().unwrap(),
     };
-    let chain_id = starknet_api::core::ChainId("SN_MAIN".to_string());
+    let config = get_config().map_err(|e| {
+        log::error!("Failed to get config: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
+    let chain_id = parse_cairo_short_string(&config.chain_id).map(starknet_api::core::ChainId).map_err(|e| {
+        log::error!("Invalid chain id: {e}");
+        StarknetRpcApiError::InternalServerError
+    })?;
 
     Ok(block_header.into_block_context(fee_token_address, chain_id))
 }
@@ -60,7 +71,7 @@ pub fn re_execute_transactions(
     block_context: &BlockContext,
 ) -> Result<Vec<TransactionExecutionInfo>, TransactionExecutionError> {
     let charge_fee = block_context.block_info().gas_prices.eth_l1_gas_price.get() != 1;
-    let mut cached_state = init_cached_state(block_context);
+    let mut cached_state = init_cached_state(block_context, None);
 
     transactions_before
         .into_iter()
@@ -80,13 +91,19 @@ pub fn simulate_transactions(
     simulation_flags: &SimulationFlags,
     block_context: &BlockContext,
     charge_fee: bool,
+    policy: &Arc<PolicyGuard>,
 ) -> Result<Vec<TransactionExecutionInfo>, TransactionExecutionError> {
-    let mut cached_state = init_cached_state(block_context);
+    let mut cached_state = init_cached_state(block_context, Some(policy));
 
     let tx_execution_results = transactions
         .into_iter()
         .map(|tx| tx.execute(&mut cached_state, block_context, charge_fee, simulation_flags.validate))
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            policy.inspect_failure();
+            e
+        })?;
+    tx_execution_results.iter().for_each(|execution_info| policy.inspect_transaction(execution_info));
 
     Ok(tx_execution_results)
 }
@@ -97,9 +114,13 @@ pub fn call_contract(
     function_selector: EntryPointSelector,
     calldata: Calldata,
     block_context: &BlockContext,
-) -> Result<Vec<Felt252Wrapper>, ()> {
+    policy: &Arc<PolicyGuard>,
+) -> Result<CallInfo, ()> {
     // Get class hash
     let class_hash = storage_handler::contract_data().get_class_hash(&address).map_err(|_| ())?;
+    if let Some(class_hash) = class_hash {
+        policy.check_entry_point(class_hash, function_selector).map_err(|_| ())?;
+    }
 
     let entrypoint = CallEntryPoint {
         class_hash,
@@ -123,27 +144,88 @@ pub fn call_contract(
     )
     .map_err(|_| ())?;
 
-    match entrypoint.execute(
-        &mut BlockifierStateAdapter::new(block_context.block_info().block_number.0),
-        &mut resources,
-        &mut entry_point_execution_context,
-    ) {
+    let mut state_adapter =
+        BlockifierStateAdapter::new(block_context.block_info().block_number.0).with_policy(Some(Arc::clone(policy)));
+    match entrypoint.execute(&mut state_adapter, &mut resources, &mut entry_point_execution_context) {
         Ok(v) => {
             log::debug!("Successfully called a smart contract function: {:?}", v);
-            let result = v.execution.retdata.0.iter().map(|x| (*x).into()).collect();
-            Ok(result)
+            if v.execution.failed {
+                policy.inspect_failure();
+            }
+            policy.inspect_call(&v);
+            Ok(v)
         }
         Err(e) => {
             log::error!("failed to call smart contract {:?}", e);
+            policy.inspect_failure();
             Err(())
         }
     }
 }
 
+/// Executes a call like [call_contract], but returns its whole call info, with the inner calls,
+/// events and messages of the call tree.
+///
+/// Failures of Cairo 1 calls are not errors: they are reported through the `failed` flag of the
+/// call info, with the panic data as return data.
+pub fn trace_call(
+    address: ContractAddress,
+    class_hash: ClassHash,
+    function_selector: EntryPointSelector,
+    calldata: Calldata,
+    block_context: &BlockContext,
+    policy: &Arc<PolicyGuard>,
+) -> Result<CallInfo, TransactionExecutionError> {
+    if policy.check_entry_point(class_hash, function_selector).is_err() {
+        return Err(TransactionExecutionError::ExecutionError {
+            error: EntryPointExecutionError::InternalError("Entry point denied by the execution policy".to_string()),
+            storage_address: address,
+            selector: function_selector,
+        });
+    }
+
+    let entrypoint = CallEntryPoint {
+        class_hash: Some(class_hash),
+        code_address: None,
+        entry_point_type: EntryPointType::External,
+        entry_point_selector: function_selector,
+        calldata,
+        storage_address: address,
+        caller_address: ContractAddress::default(),
+        call_type: CallType::Call,
+        initial_gas: VersionedConstants::latest_constants().tx_initial_gas(),
+    };
+
+    let mut resources = cairo_vm::vm::runners::cairo_runner::ExecutionResources::default();
+    let mut entry_point_execution_context = EntryPointExecutionContext::new_invoke(
+        Arc::new(TransactionContext {
+            block_context: block_context.clone(),
+            tx_info: TransactionInfo::Deprecated(DeprecatedTransactionInfo::default()),
+        }),
+        false,
+    )?;
+
+    let mut state_adapter =
+        BlockifierStateAdapter::new(block_context.block_info().block_number.0).with_policy(Some(Arc::clone(policy)));
+    let call_info = entrypoint
+        .execute(&mut state_adapter, &mut resources, &mut entry_point_execution_context)
+        .map_err(|error| {
+            policy.inspect_failure();
+            TransactionExecutionError::ExecutionError { error, storage_address: address, selector: function_selector }
+        })?;
+    if call_info.execution.failed {
+        policy.inspect_failure();
+    }
+    policy.inspect_call(&call_info);
+
+    Ok(call_info)
+}
+
 pub fn estimate_fee(
     transactions: Vec<AccountTransaction>,
     simulation_flags: &[SimulationFlagForEstimateFee],
     block_context: &BlockContext,
+    policy: &Arc<PolicyGuard>,
 ) -> Result<Vec<FeeEstimate>, TransactionExecutionError> {
     let transactions_len = transactions.len();
 
@@ -152,7 +234,9 @@ pub fn estimate_fee(
     // TODO: the vector of flags should be for each transaction
     for tx in transactions {
         for flag in simulation_flags.iter() {
-            let execution_info = execute_fee_transaction(tx.clone(), flag.clone(), block_context)?;
+            let mut cached_state = init_cached_state(block_context, Some(policy));
+            let execution_info =
+                execute_fee_transaction(tx.clone(), flag.clone(), &mut cached_state, block_context, policy)?;
             fees.push(execution_info);
         }
     }
@@ -160,13 +244,40 @@ pub fn estimate_fee(
     Ok(fees)
 }
 
+/// Estimates the fees of transactions executed one after the other on a shared state, so that each
+/// transaction sees the state changes of the ones before it.
+///
+/// On failure, returns the index of the failing transaction along with its error.
+pub fn estimate_fee_sequence(
+    transactions: Vec<AccountTransaction>,
+    simulation_flags: SimulationFlagForEstimateFee,
+    block_context: &BlockContext,
+    policy: &Arc<PolicyGuard>,
+) -> Result<Vec<FeeEstimate>, (usize, TransactionExecutionError)> {
+    let mut cached_state = init_cached_state(block_context, Some(policy));
+
+    transactions
+        .into_iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            execute_fee_transaction(tx, simulation_flags.clone(), &mut cached_state, block_context, policy)
+                .map_err(|e| (index, e))
+        })
+        .collect()
+}
+
 pub fn estimate_message_fee(
     message: L1HandlerTransaction,
     block_context: &BlockContext,
+    policy: &Arc<PolicyGuard>,
 ) -> Result<FeeEstimate, TransactionExecutionError> {
-    let mut cached_state = init_cached_state(block_context);
+    let mut cached_state = init_cached_state(block_context, Some(policy));
 
-    let tx_execution_infos = message.clone().execute(&mut cached_state, block_context, true, true)?;
+    let tx_execution_infos = message.clone().execute(&mut cached_state, block_context, true, true).map_err(|e| {
+        policy.inspect_failure();
+        e
+    })?;
+    policy.inspect_transaction(&tx_execution_infos);
 
     // TODO: implement this
     // if !tx_execution_infos.is_reverted() {}
@@ -190,10 +301,10 @@ pub fn estimate_message_fee(
 fn execute_fee_transaction(
     transaction: AccountTransaction,
     simulation_flags: SimulationFlagForEstimateFee,
+    cached_state: &mut CachedState<BlockifierStateAdapter>,
     block_context: &BlockContext,
+    policy: &PolicyGuard,
 ) -> Result<FeeEstimate, TransactionExecutionError> {
-    let mut cached_state = init_cached_state(block_context);
-
     let fee_type = transaction.fee_type();
 
     let gas_price = block_context.block_info().gas_prices.get_gas_price_by_fee_type(&fee_type).get();
@@ -209,7 +320,7 @@ fn execute_fee_transaction(
     let tx_info: Result<
         blockifier::transaction::objects::TransactionExecutionInfo,
         blockifier::transaction::errors::TransactionExecutionError,
-    > = transaction.execute(&mut cached_state, block_context, false, simulation_flags.skip_validate).and_then(
+    > = transaction.execute(cached_state, block_context, false, simulation_flags.skip_validate).and_then(
         |mut tx_info| {
             if tx_info.actual_fee.0 == 0 {
                 tx_info.actual_fee =
@@ -222,6 +333,7 @@ fn execute_fee_transaction(
 
     match tx_info {
         Ok(tx_info) => {
+            policy.inspect_transaction(&tx_info);
             if let Some(_revert_error) = tx_info.revert_error {
                 return Err(TransactionExecutionError::ExecutionError {
                     error: EntryPointExecutionError::InternalError("Transaction reverted".to_string()),
@@ -239,11 +351,14 @@ fn execute_fee_transaction(
             );
             Ok(fee_estimate)
         }
-        Err(_error) => Err(TransactionExecutionError::ExecutionError {
-            error: EntryPointExecutionError::InternalError("Execution error".to_string()),
-            storage_address: ContractAddress::default(),
-            selector: EntryPointSelector::default(),
-        }),
+        Err(_error) => {
+            policy.inspect_failure();
+            Err(TransactionExecutionError::ExecutionError {
+                error: EntryPointExecutionError::InternalError("Execution error".to_string()),
+                storage_address: ContractAddress::default(),
+                selector: EntryPointSelector::default(),
+            })
+        }
     }
 }
 
@@ -276,7 +391,11 @@ pub fn from_tx_info_and_gas_price(
     }
 }
 
-fn init_cached_state(block_context: &BlockContext) -> CachedState<BlockifierStateAdapter> {
+fn init_cached_state(
+    block_context: &BlockContext,
+    policy: Option<&Arc<PolicyGuard>>,
+) -> CachedState<BlockifierStateAdapter> {
     let block_number = block_context.block_info().block_number.0;
-    CachedState::new(BlockifierStateAdapter::new(block_number), GlobalContractCache::new(10))
+    let state_adapter = BlockifierStateAdapter::new(block_number).with_policy(policy.cloned());
+    CachedState::new(state_adapter, GlobalContractCache::new(10))
 }
@@ -1,5 +1,6 @@
 use anyhow::Result;
-use mc_sync::l1::ETHEREUM_STATE_UPDATE;
+use blockifier::context::BlockContext;
+use mc_sync::l1::{get_confirmation_status, ConfirmationStatus};
 use mp_block::DeoxysBlock;
 use mp_hashers::HasherT;
 use mp_transactions::to_starknet_core_transaction::to_starknet_core_tx;
@@ -11,10 +12,11 @@ use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use starknet_api::hash::StarkFelt;
 use starknet_api::transaction as stx;
-use starknet_core::types::{BlockId, BlockStatus, FieldElement};
+use starknet_core::types::{BlockId, BlockStatus, FieldElement, TransactionFinalityStatus};
 
 use crate::deoxys_backend_client::get_block_by_block_hash;
 use crate::errors::StarknetRpcApiError;
+use crate::utils::execution::block_context;
 use crate::{Felt, Starknet};
 
 pub(crate) fn tx_hash_retrieve(tx_hashes: Vec<StarkFelt>) -> Vec<FieldElement> {
@@ -41,10 +43,17 @@ pub(crate) fn tx_conv(
 }
 
 pub(crate) fn status(block_number: u64) -> BlockStatus {
-    if block_number <= ETHEREUM_STATE_UPDATE.read().unwrap().block_number {
-        BlockStatus::AcceptedOnL1
-    } else {
-        BlockStatus::AcceptedOnL2
+    match get_confirmation_status(block_number) {
+        ConfirmationStatus::AcceptedOnL1 => BlockStatus::AcceptedOnL1,
+        ConfirmationStatus::AcceptedOnL2 => BlockStatus::AcceptedOnL2,
+    }
+}
+
+/// Returns the finality status of the transactions of a block.
+pub(crate) fn finality_status(block_number: u64) -> TransactionFinalityStatus {
+    match get_confirmation_status(block_number) {
+        ConfirmationStatus::AcceptedOnL1 => TransactionFinalityStatus::AcceptedOnL1,
+        ConfirmationStatus::AcceptedOnL2 => TransactionFinalityStatus::AcceptedOnL2,
     }
 }
 
@@ -76,3 +85,49 @@ where
 
     Ok(substrate_block_hash)
 }
+
+/// Returns the execution context of the block preceding `substrate_block_hash`.
+///
+/// This is the context every transaction of that block is executed against. Results are cached,
+/// since resolving the previous block and building its context dominates cheap execution calls.
+pub fn previous_block_context<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    substrate_block_hash: DHashT,
+) -> Result<BlockContext, StarknetRpcApiError>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    if let Some(block_context) = starknet.block_context_cache.get(&substrate_block_hash) {
+        return Ok(block_context);
+    }
+
+    let previous_substrate_block_hash = previous_substrate_block_hash(starknet, substrate_block_hash)?;
+    let block_context = block_context(starknet.client.as_ref(), previous_substrate_block_hash)?;
+    starknet.block_context_cache.insert(substrate_block_hash, block_context.clone());
+
+    Ok(block_context)
+}
+
+/// Returns the context the calls, fee estimations, simulations and traces requested at
+/// `substrate_block_hash` are executed against: the one of [previous_block_context], with the
+/// execution limits of the operator applied.
+///
+/// The transactions of the chain are executed against [previous_block_context] itself, so that the
+/// status and fees reported for them do not depend on these limits.
+pub fn request_block_context<BE, C, H>(
+    starknet: &Starknet<BE, C, H>,
+    substrate_block_hash: DHashT,
+) -> Result<BlockContext, StarknetRpcApiError>
+where
+    BE: Backend<DBlockT> + 'static,
+    C: HeaderBackend<DBlockT> + BlockBackend<DBlockT> + StorageProvider<DBlockT, BE> + 'static,
+    C: ProvideRuntimeApi<DBlockT>,
+    C::Api: StarknetRuntimeApi<DBlockT> + ConvertTransactionRuntimeApi<DBlockT>,
+    H: HasherT + Send + Sync + 'static,
+{
+    Ok(starknet.execution_constants.apply(previous_block_context(starknet, substrate_block_hash)?))
+}
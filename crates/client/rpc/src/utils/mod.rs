@@ -1,6 +1,10 @@
 pub(crate) mod block;
 pub(crate) mod blockifier_state_adapter;
+pub(crate) mod cache;
 pub(crate) mod call_info;
+pub(crate) mod event_decoding;
 pub(crate) mod execution;
 pub(crate) mod helpers;
+pub(crate) mod revert_reason;
+pub(crate) mod snapshot;
 pub(crate) mod transaction;
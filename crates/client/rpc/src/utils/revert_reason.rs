@@ -0,0 +1,154 @@
+//! Decoding of revert reasons into readable strings.
+//!
+//! Revert reasons hold the panic data of the reverted call as raw felts, which are most often short
+//! strings (`'Out of gas'`, `'u256_sub Overflow'`...) or, since Cairo 2.4, byte arrays. The decoded
+//! strings are served in a separate `revert_reason_decoded` field, as an extension to the RPC
+//! specification: the revert reasons themselves are left untouched.
+
+use mc_rpc_core::WithDecodedRevertReason;
+use starknet_ff::FieldElement;
+
+/// First felt of the panic data of a Cairo 1 `panic!` with a byte array message.
+const BYTE_ARRAY_MAGIC: &str = "0x46a6158a16a947e5916b2a2ca68501a45e93d7110e81aa2d6438b1c57c879a3";
+
+/// Number of bytes held in each full word of a byte array.
+const BYTES_IN_WORD: usize = 31;
+
+/// Attaches the readable form of `revert_reason` to `inner` if `decode` is set.
+pub(crate) fn with_decoded_revert_reason<T>(
+    inner: T,
+    revert_reason: Option<&str>,
+    decode: bool,
+) -> WithDecodedRevertReason<T> {
+    let revert_reason_decoded = revert_reason.filter(|_| decode).and_then(decode_revert_reason);
+    WithDecodedRevertReason { inner, revert_reason_decoded }
+}
+
+/// Decodes the felts of a revert reason into a readable string, if it holds any printable one.
+///
+/// A byte array is decoded into its message. Otherwise, the felts holding short strings are
+/// replaced by them, in place of the annotations blockifier may already have added.
+pub(crate) fn decode_revert_reason(reason: &str) -> Option<String> {
+    let felts = hex_felts(reason);
+
+    let panic_data: Vec<_> = felts.iter().map(|(_, _, felt)| *felt).collect();
+    if let Some(message) = decode_byte_array(&panic_data) {
+        return Some(message);
+    }
+
+    let mut decoded = String::with_capacity(reason.len());
+    let mut any_decoded = false;
+    let mut last = 0;
+    for (start, end, felt) in felts {
+        let Some(short_string) = decode_short_string(felt) else {
+            continue;
+        };
+        decoded.push_str(&reason[last..start]);
+        decoded.push_str(&format!("'{short_string}'"));
+        any_decoded = true;
+        last = end;
+        // blockifier may already have decoded short strings
+        let annotation = format!(" ('{short_string}')");
+        if reason[end..].starts_with(&annotation) {
+            last += annotation.len();
+        }
+    }
+    decoded.push_str(&reason[last..]);
+    any_decoded.then_some(decoded)
+}
+
+/// Returns the felts written in hexadecimal in `text`, along with the offsets at which they start
+/// and end.
+fn hex_felts(text: &str) -> Vec<(usize, usize, FieldElement)> {
+    let mut felts = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find("0x").map(|start| offset + start) {
+        let digits = text[start + 2..].find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(text.len() - start - 2);
+        let end = start + 2 + digits;
+        if let Ok(felt) = FieldElement::from_hex_be(&text[start..end]) {
+            felts.push((start, end, felt));
+        }
+        offset = end;
+    }
+    felts
+}
+
+fn is_printable(bytes: &[u8]) -> bool {
+    bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ')
+}
+
+/// Decodes a felt holding printable ascii characters.
+fn decode_short_string(felt: FieldElement) -> Option<String> {
+    let bytes = felt.to_bytes_be();
+    let bytes = &bytes[bytes.iter().position(|b| *b != 0)?..];
+    is_printable(bytes).then(|| String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Decodes panic data serialized as `[magic, n_full_words, full_words..., pending_word,
+/// pending_len]` holding printable ascii characters.
+fn decode_byte_array(felts: &[FieldElement]) -> Option<String> {
+    let magic = FieldElement::from_hex_be(BYTE_ARRAY_MAGIC).expect("valid byte array magic");
+    let (first, felts) = felts.split_first()?;
+    if *first != magic {
+        return None;
+    }
+
+    let (n_full_words, felts) = felts.split_first()?;
+    let n_full_words = usize::try_from(u64::try_from(*n_full_words).ok()?).ok()?;
+    // the full words are followed by the pending word and its length
+    if felts.len() != n_full_words.checked_add(2)? {
+        return None;
+    }
+    let (full_words, pending) = felts.split_at(n_full_words);
+
+    let mut bytes = Vec::with_capacity((n_full_words + 1) * BYTES_IN_WORD);
+    for word in full_words {
+        bytes.extend_from_slice(&word.to_bytes_be()[32 - BYTES_IN_WORD..]);
+    }
+    let pending_len = usize::try_from(u64::try_from(pending[1]).ok()?).ok()?;
+    if pending_len > BYTES_IN_WORD {
+        return None;
+    }
+    bytes.extend_from_slice(&pending[0].to_bytes_be()[32 - pending_len..]);
+
+    is_printable(&bytes).then(|| String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_revert_reason() {
+        assert_eq!(
+            decode_revert_reason("Execution failed. Failure reason: 0x4f7574206f6620676173.").as_deref(),
+            Some("Execution failed. Failure reason: 'Out of gas'.")
+        );
+        assert_eq!(
+            decode_revert_reason("Failure reason: 0x4f7574206f6620676173 ('Out of gas').").as_deref(),
+            Some("Failure reason: 'Out of gas'.")
+        );
+        assert_eq!(decode_revert_reason("Error at pc=0:1234"), None);
+        // not printable
+        assert_eq!(decode_revert_reason("Failure reason: 0x1f02."), None);
+
+        let byte_array = format!("Failure reason: ({BYTE_ARRAY_MAGIC}, 0x0, 0x48656c6c6f, 0x5).");
+        assert_eq!(decode_revert_reason(&byte_array).as_deref(), Some("Hello"));
+    }
+
+    #[test]
+    fn test_decode_byte_array_checks_its_length() {
+        let magic = FieldElement::from_hex_be(BYTE_ARRAY_MAGIC).unwrap();
+        let hello = FieldElement::from_hex_be("0x48656c6c6f").unwrap();
+        let felt = FieldElement::from;
+
+        assert_eq!(decode_byte_array(&[magic, felt(0u64), hello, felt(5u64)]).as_deref(), Some("Hello"));
+        // more full words announced than there are felts, which must not be allocated for
+        assert_eq!(decode_byte_array(&[magic, felt(u64::MAX), hello, felt(5u64)]), None);
+        assert_eq!(decode_byte_array(&[magic, felt(1u64), hello, felt(5u64)]), None);
+        // pending word longer than a word
+        assert_eq!(decode_byte_array(&[magic, felt(0u64), hello, felt(32u64)]), None);
+        // not printable
+        assert_eq!(decode_byte_array(&[magic, felt(0u64), felt(0x1f02u64), felt(2u64)]), None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use mc_db::{DbSnapshot, DeoxysBackend};
+use starknet_core::types::BlockId;
+use tokio::task::futures::TaskLocalFuture;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// A block id resolved once, along with the database snapshot its requests are served from.
+#[derive(Clone)]
+pub struct PinnedBlock {
+    pub block_id: BlockId,
+    snapshot: Arc<DbSnapshot>,
+}
+
+impl PinnedBlock {
+    /// Runs `f` against the resolved block id, with every database read served from the snapshot.
+    pub fn run<R>(&self, f: impl FnOnce(BlockId) -> R) -> R {
+        self.snapshot.pin(|| f(self.block_id))
+    }
+
+    /// Same as [PinnedBlock::run], for async requests: the snapshot is pinned on each poll.
+    pub fn run_async<F, Fut>(&self, f: F) -> PinnedFuture<Fut>
+    where
+        F: FnOnce(BlockId) -> Fut,
+        Fut: Future,
+    {
+        PinnedFuture { snapshot: Arc::clone(&self.snapshot), inner: Box::pin(f(self.block_id)) }
+    }
+}
+
+/// A future polled with a database snapshot pinned to the polling thread.
+pub struct PinnedFuture<Fut> {
+    snapshot: Arc<DbSnapshot>,
+    inner: Pin<Box<Fut>>,
+}
+
+impl<Fut: Future> Future for PinnedFuture<Fut> {
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let inner = &mut this.inner;
+        this.snapshot.pin(|| inner.as_mut().poll(cx))
+    }
+}
+
+/// A request served within [pin_request].
+pub type RequestPinned<F> = TaskLocalFuture<RefCell<Vec<(BlockId, PinnedBlock)>>, F>;
+
+tokio::task_local! {
+    /// The blocks pinned by the calls of the request being served, per requested block id.
+    static REQUEST_PINS: RefCell<Vec<(BlockId, PinnedBlock)>>;
+}
+
+/// Serves `request` sharing the pinned blocks between its calls.
+///
+/// The calls of the request made on the same block id, such as those of a JSON-RPC batch, share a
+/// single snapshot and a single resolution of the block tags: a block landing in the middle of the
+/// request is not observed by any of them. The pins are released once the request is served.
+pub fn pin_request<F: Future>(request: F) -> RequestPinned<F> {
+    REQUEST_PINS.scope(RefCell::new(Vec::new()), request)
+}
+
+/// Returns the block pinned for `block_id` by the request being served, pinning a new snapshot and
+/// resolving the block id with `resolve` if there is none.
+///
+/// Calls made outside of [pin_request], such as those of the requests spawned by the websocket
+/// connections, each pin their own snapshot.
+pub fn get_or_pin<E>(block_id: BlockId, resolve: impl FnOnce() -> Result<BlockId, E>) -> Result<PinnedBlock, E> {
+    let find = |pins: &RefCell<Vec<(BlockId, PinnedBlock)>>| {
+        pins.borrow().iter().find(|(id, _)| *id == block_id).map(|(_, pinned)| pinned.clone())
+    };
+    if let Ok(Some(pinned)) = REQUEST_PINS.try_with(find) {
+        return Ok(pinned);
+    }
+
+    // the snapshot is taken before resolving so that the resolved block is part of it
+    let snapshot = DeoxysBackend::snapshot();
+    let pinned = PinnedBlock { block_id: resolve()?, snapshot };
+    let _ = REQUEST_PINS.try_with(|pins| pins.borrow_mut().push((block_id, pinned.clone())));
+    Ok(pinned)
+}
+
+/// Middleware serving each request of a jsonrpsee server within [pin_request], installed with
+/// `ServerBuilder::set_middleware`.
+///
+/// jsonrpsee serves the calls of an HTTP batch within the request, so that they share their pins,
+/// while it spawns the calls made over a websocket connection, which then pin their own snapshots.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PinRequestLayer;
+
+impl<S> Layer<S> for PinRequestLayer {
+    type Service = PinRequest<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PinRequest { inner }
+    }
+}
+
+/// Service serving the requests of `S` within [pin_request].
+#[derive(Clone, Debug)]
+pub struct PinRequest<S> {
+    inner: S,
+}
+
+impl<S, Request> Service<Request> for PinRequest<S>
+where
+    S: Service<Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RequestPinned<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        pin_request(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use starknet_core::types::BlockTag;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn the_calls_of_a_request_share_their_pins() {
+        let _db = DeoxysBackend::open_for_testing();
+        let resolved = Cell::new(0);
+        let resolve = || {
+            resolved.set(resolved.get() + 1);
+            Ok::<_, ()>(BlockId::Number(2210 + resolved.get()))
+        };
+        let latest = BlockId::Tag(BlockTag::Latest);
+
+        let (first, second) =
+            pin_request(async { (get_or_pin(latest, resolve).unwrap(), get_or_pin(latest, resolve).unwrap()) }).await;
+        assert_eq!((first.block_id, second.block_id), (BlockId::Number(2211), BlockId::Number(2211)));
+        assert!(Arc::ptr_eq(&first.snapshot, &second.snapshot));
+
+        // outside of a request, and in the next one, the block is resolved again
+        assert_eq!(get_or_pin(latest, resolve).unwrap().block_id, BlockId::Number(2212));
+        assert_eq!(pin_request(async { get_or_pin(latest, resolve) }).await.unwrap().block_id, BlockId::Number(2213));
+    }
+}
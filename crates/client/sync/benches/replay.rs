@@ -0,0 +1,111 @@
+//! Deterministic block replay benchmarks.
+//!
+//! Replays the consecutive mainnet blocks stored in `resources/replay` as served by the feeder
+//! gateway, one `<block_number>.json` file per block, which `scripts/replay_blocks` fetches. Every
+//! block is converted into a [DeoxysBlock], then verified by recomputing its commitments and its
+//! hash against the ones served: a block which does not replay to the mainnet one fails the bench
+//! instead of being timed.
+
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use mc_sync::commitments::lib::calculate_commitments;
+use mc_sync::convert::convert_block_sync;
+use mc_sync::network::NetworkProfile;
+use mc_sync::utility::update_config;
+use mp_block::DeoxysBlock;
+use mp_felt::Felt252Wrapper;
+use mp_hashers::pedersen::PedersenHasher;
+use starknet_providers::sequencer::models as p;
+
+/// Reads the stored blocks, ordered by block number, checking that they form a chain.
+fn read_fixtures() -> Vec<String> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join("replay");
+    let mut fixtures = Vec::new();
+    for entry in read_dir(&dir).unwrap_or_else(|e| panic!("failed to read {}: {e}", dir.display())) {
+        let path = entry.expect("failed to read replay fixture entry").path();
+        if path.extension().is_some_and(|extension| extension == "json") {
+            let raw = read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+            fixtures.push((parse_fixture(&raw), raw));
+        }
+    }
+    assert!(!fixtures.is_empty(), "no blocks to replay in {}, fetch them with scripts/replay_blocks", dir.display());
+
+    fixtures.sort_by_key(|(block, _)| block.block_number);
+    for pair in fixtures.windows(2) {
+        let (parent, block) = (&pair[0].0, &pair[1].0);
+        assert_eq!(block.block_number, parent.block_number.map(|n| n + 1), "replayed blocks are not consecutive");
+        assert_eq!(Some(block.parent_block_hash), parent.block_hash, "replayed blocks do not form a chain");
+    }
+    fixtures.into_iter().map(|(_, raw)| raw).collect()
+}
+
+fn parse_fixture(raw: &str) -> p::Block {
+    serde_json::from_str(raw).expect("failed to parse replay fixture")
+}
+
+/// The conversion reads the chain id from the global sync configuration.
+fn set_mainnet_config() {
+    let mut config = NetworkProfile::mainnet().fetch_config();
+    config.workers = 1;
+    config.verify = false;
+    config.pending = false;
+    update_config(&config);
+}
+
+/// Recomputes the transaction commitment, the event commitment and the hash of a block.
+fn verify_block(block: &DeoxysBlock) -> (Felt252Wrapper, Felt252Wrapper, Felt252Wrapper) {
+    let header = block.header();
+    let chain_id = Felt252Wrapper(NetworkProfile::mainnet().chain_id);
+    let events: Vec<_> = block.events().iter().flat_map(|ordered| ordered.events.iter().cloned()).collect();
+    let (transaction_commitment, event_commitment) =
+        calculate_commitments(block.transactions(), &events, chain_id, header.block_number);
+    (transaction_commitment, event_commitment, header.hash::<PedersenHasher>(chain_id))
+}
+
+/// The transaction commitment, the event commitment and the hash of a block as served.
+fn served_block(block: &p::Block) -> (Felt252Wrapper, Felt252Wrapper, Felt252Wrapper) {
+    let served = |field: &str, value: Option<_>| {
+        value.map(Felt252Wrapper).unwrap_or_else(|| panic!("replay fixture {:?} has no {field}", block.block_number))
+    };
+    (
+        served("transaction commitment", block.transaction_commitment),
+        served("event commitment", block.event_commitment),
+        served("hash", block.block_hash),
+    )
+}
+
+fn replay(c: &mut Criterion) {
+    set_mainnet_config();
+    let fixtures = read_fixtures();
+
+    // a block which does not replay to the mainnet one is not worth timing
+    let mut blocks = Vec::with_capacity(fixtures.len());
+    for raw in &fixtures {
+        let served = parse_fixture(raw);
+        let (block_number, expected) = (served.block_number, served_block(&served));
+        let block =
+            convert_block_sync(served).unwrap_or_else(|e| panic!("failed to convert block {block_number:?}: {e}"));
+        assert_eq!(verify_block(&block), expected, "block {block_number:?} does not replay");
+        blocks.push(block);
+    }
+
+    let mut group = c.benchmark_group("replay");
+    group.throughput(Throughput::Elements(blocks.len() as u64));
+
+    group.bench_function("convert", |b| {
+        b.iter_batched(
+            || fixtures.iter().map(|raw| parse_fixture(raw)).collect::<Vec<_>>(),
+            |blocks| blocks.into_iter().map(convert_block_sync).collect::<Vec<_>>(),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("verify", |b| b.iter(|| blocks.iter().map(verify_block).collect::<Vec<_>>()));
+
+    group.finish();
+}
+
+criterion_group!(benches, replay);
+criterion_main!(benches);
@@ -0,0 +1,78 @@
+//! Background backfill of the historical blocks, when the node starts syncing mid-chain.
+//!
+//! The node serves the chain from the block it started syncing from, while the blocks below it are
+//! retrieved from the most recent to the oldest. Each backfilled block must hash to the parent hash
+//! of the block above it, and the range of backfilled blocks is exposed by the database so that
+//! queries on blocks which have not been backfilled yet can be told apart from unknown blocks.
+
+use std::time::Duration;
+
+use futures::prelude::*;
+use mc_db::DeoxysBackend;
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models as p;
+
+use crate::fetch::fetchers::fetch_block;
+use crate::fetch::provider_pool::ProviderPool;
+use crate::l2::L2SyncError;
+
+/// Number of historical blocks fetched in parallel.
+const BACKFILL_CONCURRENCY: usize = 8;
+
+/// Maximum number of attempts to fetch a historical block.
+const BACKFILL_MAX_ATTEMPTS: u32 = 8;
+
+/// How long to wait before retrying to fetch a block, doubled after each failed attempt.
+const BACKFILL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+async fn fetch_block_retrying(provider: &ProviderPool, block_number: u64) -> Result<p::Block, L2SyncError> {
+    let mut delay = BACKFILL_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match fetch_block(provider, block_number).await {
+            Ok(block) => return Ok(block),
+            Err(e) if attempt < BACKFILL_MAX_ATTEMPTS => {
+                log::warn!("Failed to fetch historical block #{block_number} (attempt {attempt}): {e}, retrying");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Backfills the blocks below `end` down to the genesis block, resuming a backfill previously
+/// interrupted.
+pub async fn backfill(provider: &ProviderPool, end: u64) -> Result<(), L2SyncError> {
+    let range = DeoxysBackend::backfill().start(end)?;
+    if range.is_complete() {
+        return Ok(());
+    }
+
+    // the block right above the backfilled range links the backfilled blocks to the synced chain
+    let mut expected_hash: FieldElement = fetch_block_retrying(provider, range.lowest).await?.parent_block_hash;
+    log::info!("⏪ Backfilling blocks #0..#{}", range.lowest - 1);
+
+    let mut blocks = stream::iter((0..range.lowest).rev())
+        .map(|block_number| fetch_block_retrying(provider, block_number))
+        .buffered(BACKFILL_CONCURRENCY);
+
+    while let Some(block) = blocks.next().await {
+        let block = block?;
+        let block_number = block.block_number.unwrap_or_default();
+        if block.block_hash != Some(expected_hash) {
+            return Err(L2SyncError::BackfillMismatch(block_number));
+        }
+        expected_hash = block.parent_block_hash;
+
+        let block = crate::convert::block(block).await?;
+        let range = DeoxysBackend::backfill().store_block(&block)?;
+        if range.lowest % 1000 == 0 {
+            log::info!("⏪ Backfilled blocks #{}..#{}", range.lowest, range.end - 1);
+        }
+    }
+
+    log::info!("⏪ Backfill complete");
+    Ok(())
+}
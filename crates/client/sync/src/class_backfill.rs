@@ -0,0 +1,162 @@
+//! Background backfill of the classes missing from the database.
+//!
+//! Nodes synced with older versions may have stored blocks without the definitions of the classes
+//! they declare or deploy. The state diffs of the blocks synced before the node started are scanned
+//! from the oldest, and the missing classes are fetched from the gateway at a throttled rate so
+//! that the head sync keeps most of the gateway's capacity. The scan progress is stored, so a
+//! restarted node resumes the scan where it stopped.
+
+use std::time::Duration;
+
+use itertools::Itertools;
+use mc_db::storage_handler::primitives::contract_class::{ClassUpdateWrapper, ContractClassData};
+use mc_db::storage_handler::{self, StorageView};
+use mc_db::storage_updates::store_class_update;
+use mc_db::DeoxysBackend;
+use starknet_api::core::ClassHash;
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::{DeclaredClassItem, DeployedContractItem, ReplacedClassItem};
+use starknet_ff::FieldElement;
+use tokio::time::MissedTickBehavior;
+
+use crate::fetch::fetchers::{fetch_class, is_broken_class};
+use crate::fetch::provider_pool::ProviderPool;
+use crate::l2::L2SyncError;
+use crate::metrics::class_metrics;
+
+/// The delay between two classes fetched by the backfill.
+const CLASS_BACKFILL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long to wait before retrying to fetch a class.
+const CLASS_BACKFILL_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How many times fetching a class is retried before the backfill gives up, to be resumed on the
+/// next start.
+const CLASS_BACKFILL_MAX_RETRY: u32 = 60;
+
+/// The number of blocks scanned between two writes of the scan progress.
+const CURSOR_INTERVAL: u64 = 1000;
+
+/// The classes declared or deployed in a block which are not stored.
+fn missing_classes(block_number: u64) -> Result<Vec<FieldElement>, L2SyncError> {
+    let Some(state_diff) =
+        storage_handler::block_state_diff().get(block_number).map_err(|e| L2SyncError::Storage(block_number, e))?
+    else {
+        // blocks preceding a trusted root have no state diff
+        return Ok(Vec::new());
+    };
+
+    let class_hashes = std::iter::empty()
+        .chain(state_diff.deprecated_declared_classes.iter())
+        .chain(state_diff.declared_classes.iter().map(|DeclaredClassItem { class_hash, .. }| class_hash))
+        .chain(state_diff.deployed_contracts.iter().map(|DeployedContractItem { class_hash, .. }| class_hash))
+        .chain(state_diff.replaced_classes.iter().map(|ReplacedClassItem { class_hash, .. }| class_hash))
+        .unique()
+        .filter(|class_hash| !is_broken_class(class_hash));
+
+    let mut missing = Vec::new();
+    for class_hash in class_hashes {
+        let stored = storage_handler::contract_class_data()
+            .contains(&ClassHash(StarkFelt(class_hash.to_bytes_be())))
+            .map_err(|e| L2SyncError::Storage(block_number, e))?;
+        if !stored {
+            missing.push(*class_hash);
+        }
+    }
+    Ok(missing)
+}
+
+async fn fetch_class_retrying(
+    provider: &ProviderPool,
+    class_hash: FieldElement,
+    block_number: u64,
+) -> Result<ContractClassData, L2SyncError> {
+    let mut attempt = 0;
+
+    loop {
+        match fetch_class(provider, class_hash, block_number).await {
+            Err(e) if e.is_retryable() => {
+                log::debug!("Failed to fetch class {class_hash:#x} of block #{block_number}: {e}");
+                attempt += 1;
+                if attempt >= CLASS_BACKFILL_MAX_RETRY {
+                    return Err(L2SyncError::FetchRetryLimit);
+                }
+                tokio::time::sleep(CLASS_BACKFILL_RETRY_DELAY).await;
+            }
+            fetched => return fetched,
+        }
+    }
+}
+
+/// Fetches the classes missing from the blocks up to `end`, resuming a scan previously
+/// interrupted.
+pub async fn backfill_classes(provider: &ProviderPool, end: u64) -> Result<(), L2SyncError> {
+    let start = DeoxysBackend::meta().class_backfill_cursor()?.unwrap_or_default();
+    if start > end {
+        return Ok(());
+    }
+    log::info!("🧩 Scanning blocks #{start}..#{end} for missing classes");
+
+    let mut throttle = tokio::time::interval(CLASS_BACKFILL_INTERVAL);
+    throttle.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut backfilled = 0;
+
+    for block_number in start..=end {
+        let missing = missing_classes(block_number)?;
+        let found = !missing.is_empty();
+        if found {
+            let mut classes = Vec::with_capacity(missing.len());
+            for class_hash in missing {
+                throttle.tick().await;
+                classes.push(fetch_class_retrying(provider, class_hash, block_number).await?);
+            }
+            backfilled += classes.len();
+            if let Some(metrics) = class_metrics() {
+                metrics.record_stored(&classes);
+            }
+            store_class_update(block_number, ClassUpdateWrapper(classes))
+                .await
+                .map_err(|e| L2SyncError::Storage(block_number, e))?;
+        }
+
+        if found || block_number % CURSOR_INTERVAL == 0 || block_number == end {
+            DeoxysBackend::meta().write_class_backfill_cursor(block_number + 1)?;
+        }
+    }
+
+    log::info!("🧩 Class backfill complete, {backfilled} missing classes were fetched");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_core::types::StateDiff;
+
+    use super::*;
+
+    #[test]
+    fn missing_classes_are_listed_once() {
+        let _db = DeoxysBackend::open_for_testing();
+        let class = |n: u64| FieldElement::from(0x2710_0000u64 + n);
+        let broken =
+            FieldElement::from_hex_be("0x024f092a79bdff4efa1ec86e28fa7aa7d60c89b30924ec4dab21dbfd4db73698").unwrap();
+        assert!(is_broken_class(&broken));
+
+        let state_diff = StateDiff {
+            storage_diffs: vec![],
+            deprecated_declared_classes: vec![class(1), broken],
+            declared_classes: vec![DeclaredClassItem { class_hash: class(2), compiled_class_hash: FieldElement::ONE }],
+            deployed_contracts: vec![
+                DeployedContractItem { address: FieldElement::ONE, class_hash: class(1) },
+                DeployedContractItem { address: FieldElement::TWO, class_hash: class(3) },
+            ],
+            replaced_classes: vec![ReplacedClassItem { contract_address: FieldElement::ONE, class_hash: class(2) }],
+            nonces: vec![],
+        };
+        storage_handler::block_state_diff().insert(2710, state_diff).unwrap();
+
+        assert_eq!(missing_classes(2710).unwrap(), vec![class(1), class(2), class(3)]);
+        // blocks without a state diff have no missing class
+        assert!(missing_classes(2711).unwrap().is_empty());
+    }
+}
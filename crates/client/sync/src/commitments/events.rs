@@ -12,6 +12,8 @@ use starknet_ff::FieldElement;
 use starknet_types_core::felt::Felt;
 use starknet_types_core::hash::Pedersen;
 
+use super::lib::ProofNode;
+
 /// Calculate the hash of the event.
 ///
 /// # Arguments
@@ -43,22 +45,8 @@ pub fn calculate_event_hash<H: HasherT>(event: &Event) -> FieldElement {
     H::compute_hash_on_elements(&[from_address, keys_hash, data_hash])
 }
 
-/// Calculate the event commitment in memory using HashMapDb (which is more efficient for this
-/// usecase).
-///
-/// # Arguments
-///
-/// * `events` - The events of the block
-///
-/// # Returns
-///
-/// The event commitment as `Felt252Wrapper`.
-pub fn memory_event_commitment(events: &[Event]) -> Result<Felt252Wrapper, String> {
-    // TODO @cchudant refacto/optimise this function
-    if events.is_empty() {
-        return Ok(Felt252Wrapper::ZERO);
-    }
-
+/// Builds the event trie of a block in memory, returning it along with its leaves.
+fn memory_event_trie(events: &[Event]) -> (BonsaiStorage<BasicId, HashMapDb<BasicId>, Pedersen>, Vec<FieldElement>) {
     let config = BonsaiStorageConfig::default();
     let bonsai_db = HashMapDb::<BasicId>::default();
     let mut bonsai_storage =
@@ -69,9 +57,9 @@ pub fn memory_event_commitment(events: &[Event]) -> Result<Felt252Wrapper, Strin
     let events = events.par_iter().map(calculate_event_hash::<PedersenHasher>).collect::<Vec<_>>();
 
     // once event hashes have finished computing, they are inserted into the local Bonsai db
-    for (i, event_hash) in events.into_iter().enumerate() {
+    for (i, event_hash) in events.iter().enumerate() {
         let key = BitVec::from_vec(i.to_be_bytes().to_vec());
-        let value = Felt::from(Felt252Wrapper::from(event_hash));
+        let value = Felt::from(Felt252Wrapper::from(*event_hash));
         bonsai_storage.insert(identifier, key.as_bitslice(), &value).expect("Failed to insert into bonsai storage");
     }
 
@@ -83,9 +71,56 @@ pub fn memory_event_commitment(events: &[Event]) -> Result<Felt252Wrapper, Strin
     let mut id_builder = BasicIdBuilder::new();
     let id = id_builder.new_id();
 
-    // run in a blocking-safe thread to avoid starving the thread pool
     bonsai_storage.commit(id).expect("Failed to commit to bonsai storage");
-    let root_hash = bonsai_storage.root_hash(identifier).expect("Failed to get root hash");
+
+    (bonsai_storage, events)
+}
+
+/// Calculate the event commitment in memory using HashMapDb (which is more efficient for this
+/// usecase).
+///
+/// # Arguments
+///
+/// * `events` - The events of the block
+///
+/// # Returns
+///
+/// The event commitment as `Felt252Wrapper`.
+pub fn memory_event_commitment(events: &[Event]) -> Result<Felt252Wrapper, String> {
+    // TODO @cchudant refacto/optimise this function
+    if events.is_empty() {
+        return Ok(Felt252Wrapper::ZERO);
+    }
+
+    let (bonsai_storage, _) = memory_event_trie(events);
+    let root_hash = bonsai_storage.root_hash(bonsai_identifier::EVENT).expect("Failed to get root hash");
 
     Ok(Felt252Wrapper::from(root_hash))
 }
+
+/// Computes the inclusion proof of an event in the event commitment of its block.
+///
+/// # Arguments
+///
+/// * `events` - The events of the block, in emission order
+/// * `index` - The index of the event in the block
+///
+/// # Returns
+///
+/// The event commitment, the hash of the event and the proof nodes from the root to that leaf.
+pub fn memory_event_proof(
+    events: &[Event],
+    index: usize,
+) -> Result<(Felt252Wrapper, FieldElement, Vec<ProofNode>), String> {
+    let (bonsai_storage, leaves) = memory_event_trie(events);
+    let leaf = *leaves.get(index).ok_or_else(|| format!("No event at index {index}"))?;
+
+    let identifier = bonsai_identifier::EVENT;
+    let root_hash = bonsai_storage.root_hash(identifier).map_err(|e| format!("Failed to get root hash: {e:?}"))?;
+    let key = BitVec::from_vec(index.to_be_bytes().to_vec());
+    let proof = bonsai_storage
+        .get_proof(identifier, key.as_bitslice())
+        .map_err(|e| format!("Failed to get event proof: {e:?}"))?;
+
+    Ok((Felt252Wrapper::from(root_hash), leaf, proof.into_iter().map(ProofNode::from).collect()))
+}
@@ -2,6 +2,7 @@ use blockifier::state::cached_state::CommitmentStateDiff;
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use mc_db::storage_handler::{self, DeoxysStorageError, StorageViewMut};
+use mc_db::DeoxysBackend;
 use mp_convert::field_element::FromFieldElement;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::pedersen::PedersenHasher;
@@ -21,6 +22,30 @@ use starknet_types_core::felt::Felt;
 use super::events::memory_event_commitment;
 use super::transactions::memory_transaction_commitment;
 
+/// A node of a Merkle-Patricia proof, ordered from the root down to the leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofNode {
+    Binary { left: FieldElement, right: FieldElement },
+    Edge { child: FieldElement, path: FieldElement, length: usize },
+}
+
+impl From<bonsai_trie::ProofNode> for ProofNode {
+    fn from(node: bonsai_trie::ProofNode) -> Self {
+        match node {
+            bonsai_trie::ProofNode::Binary { left, right } => {
+                ProofNode::Binary { left: Felt252Wrapper::from(left).into(), right: Felt252Wrapper::from(right).into() }
+            }
+            bonsai_trie::ProofNode::Edge { child, path } => ProofNode::Edge {
+                child: Felt252Wrapper::from(child).into(),
+                path: path.0.iter().fold(FieldElement::ZERO, |acc, bit| {
+                    acc + acc + if *bit { FieldElement::ONE } else { FieldElement::ZERO }
+                }),
+                length: path.0.len(),
+            },
+        }
+    }
+}
+
 /// Calculate the transaction and event commitment.
 ///
 /// # Arguments
@@ -150,6 +175,11 @@ pub fn update_state_root(csd: CommitmentStateDiff, block_number: u64) -> Felt252
         || contract_trie_root(&csd, block_number).expect("Failed to compute contract root"),
         || class_trie_root(&csd, block_number).expect("Failed to compute class root"),
     );
+    // the tries are read from this snapshot from now on, as they all committed the block
+    DeoxysBackend::snapshot_tries(block_number);
+    // a revert then knows the tries have to be reverted, even once state roots are no longer verified
+    DeoxysBackend::meta().write_tries_block(block_number).expect("Failed to record the block of the tries");
+
     calculate_state_root::<PoseidonHasher>(contract_trie_root, class_trie_root)
 }
 
@@ -206,11 +236,15 @@ fn contract_trie_root(csd: &CommitmentStateDiff, block_number: u64) -> Result<Fe
 
 fn contract_state_leaf_hash(csd: &CommitmentStateDiff, contract_address: &ContractAddress, storage_root: Felt) -> Felt {
     let class_hash = class_hash(csd, contract_address);
+    let nonce = csd.address_to_nonce.get(contract_address).copied().unwrap_or_default();
 
-    let storage_root = FieldElement::from_bytes_be(&storage_root.to_bytes_be()).unwrap();
+    contract_state_hash(class_hash, storage_root, nonce)
+}
 
-    let nonce_bytes = csd.address_to_nonce.get(contract_address).unwrap_or(&Nonce::default()).0.0;
-    let nonce = FieldElement::from_bytes_be(&nonce_bytes).unwrap();
+/// Computes the leaf of a contract in the contract trie.
+pub(crate) fn contract_state_hash(class_hash: FieldElement, storage_root: Felt, nonce: Nonce) -> Felt {
+    let storage_root = FieldElement::from_bytes_be(&storage_root.to_bytes_be()).unwrap();
+    let nonce = FieldElement::from_bytes_be(&nonce.0.0).unwrap();
 
     // computes the contract state leaf hash
     let contract_state_hash = PedersenHasher::hash_elements(class_hash, storage_root);
@@ -256,13 +290,7 @@ fn class_trie_root(csd: &CommitmentStateDiff, block_number: u64) -> Result<Felt2
         .class_hash_to_compiled_class_hash
         .iter()
         .par_bridge()
-        .map(|(class_hash, compiled_class_hash)| {
-            let compiled_class_hash = FieldElement::from_bytes_be(&compiled_class_hash.0.0).unwrap();
-
-            let hash = PoseidonHasher::hash_elements(*CONTRACT_CLASS_HASH_VERSION, compiled_class_hash);
-
-            (class_hash, hash)
-        })
+        .map(|(class_hash, compiled_class_hash)| (class_hash, class_leaf_hash(compiled_class_hash)))
         .collect::<Vec<_>>();
 
     handler_class.init()?;
@@ -271,3 +299,9 @@ fn class_trie_root(csd: &CommitmentStateDiff, block_number: u64) -> Result<Felt2
 
     Ok(handler_class.root()?.into())
 }
+
+/// Computes the leaf of a class in the class trie.
+pub(crate) fn class_leaf_hash(compiled_class_hash: &CompiledClassHash) -> FieldElement {
+    let compiled_class_hash = FieldElement::from_bytes_be(&compiled_class_hash.0.0).unwrap();
+    PoseidonHasher::hash_elements(*CONTRACT_CLASS_HASH_VERSION, compiled_class_hash)
+}
@@ -1,3 +1,4 @@
 pub mod events;
 pub mod lib;
+pub mod storage_proof;
 pub mod transactions;
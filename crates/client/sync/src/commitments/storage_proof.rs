@@ -0,0 +1,74 @@
+use mc_db::storage_handler::{self, DeoxysStorageError};
+use mp_felt::Felt252Wrapper;
+use mp_hashers::poseidon::PoseidonHasher;
+use starknet_api::core::ContractAddress;
+use starknet_api::state::StorageKey;
+use starknet_ff::FieldElement;
+
+use super::lib::{calculate_state_root, ProofNode};
+
+/// The proof of the storage values of a contract against the state commitment.
+#[derive(Debug, Clone)]
+pub struct StorageProof {
+    pub state_commitment: FieldElement,
+    pub contracts_trie_root: FieldElement,
+    pub classes_trie_root: FieldElement,
+    /// The proof nodes of the contract leaf, from the root of the contracts trie.
+    pub contract_proof: Vec<ProofNode>,
+    /// The root of the storage trie of the contract, committed to in its leaf.
+    pub storage_root: FieldElement,
+    /// The proof nodes of every requested key, from the root of the storage trie of the contract.
+    pub storage_proofs: Vec<Vec<ProofNode>>,
+}
+
+/// Computes the proof of the storage values of a contract against the state commitment at a block.
+///
+/// The proof is computed from the tries as they were once they all committed the block, which is
+/// only kept for the last blocks committed.
+///
+/// # Arguments
+///
+/// * `block_number` - The block at which the storage is proven.
+/// * `contract_address` - The contract whose storage is proven.
+/// * `keys` - The storage keys to prove.
+///
+/// # Returns
+///
+/// The state commitment, the roots it is computed from and the proof nodes from the roots down to
+/// the contract leaf and down to each storage leaf, or `None` if the tries are no longer available
+/// at `block_number`. The proof of a key which is not set, or of a contract which is not deployed,
+/// ends where its path leaves the trie.
+pub fn get_storage_proof(
+    block_number: u64,
+    contract_address: &ContractAddress,
+    keys: &[StorageKey],
+) -> Result<Option<StorageProof>, DeoxysStorageError> {
+    let Some(tries) = storage_handler::tries_at(block_number) else {
+        return Ok(None);
+    };
+    let contract_trie = tries.contract_trie()?;
+    let contract_storage_trie = tries.contract_storage_trie()?;
+
+    let contracts_trie_root = Felt252Wrapper::from(contract_trie.root()?);
+    let classes_trie_root = Felt252Wrapper::from(tries.class_trie()?.root()?);
+    let state_commitment = calculate_state_root::<PoseidonHasher>(contracts_trie_root, classes_trie_root);
+
+    let contract_proof = contract_trie.get_proof(contract_address)?.into_iter().map(ProofNode::from).collect();
+    let storage_root = Felt252Wrapper::from(contract_storage_trie.root(contract_address)?);
+    let storage_proofs = keys
+        .iter()
+        .map(|key| {
+            let proof = contract_storage_trie.get_proof(contract_address, key)?;
+            Ok(proof.into_iter().map(ProofNode::from).collect())
+        })
+        .collect::<Result<_, DeoxysStorageError>>()?;
+
+    Ok(Some(StorageProof {
+        state_commitment: state_commitment.into(),
+        contracts_trie_root: contracts_trie_root.into(),
+        classes_trie_root: classes_trie_root.into(),
+        contract_proof,
+        storage_root: storage_root.into(),
+        storage_proofs,
+    }))
+}
@@ -13,6 +13,10 @@ use starknet_ff::FieldElement;
 use starknet_types_core::felt::Felt;
 use starknet_types_core::hash::Pedersen;
 
+use super::lib::ProofNode;
+use crate::network::NetworkProfile;
+use crate::utility::get_config;
+
 /// Compute the combined hash of the transaction hash and the signature.
 ///
 /// Since the transaction hash doesn't take the signature values as its input
@@ -22,6 +26,8 @@ use starknet_types_core::hash::Pedersen;
 /// # Arguments
 ///
 /// * `transaction` - The transaction to compute the hash of.
+/// * `include_signature` - Whether the signature of non-invoke transactions is included, as per the
+///   [VersionSchedule](crate::network::VersionSchedule) of the network.
 ///
 /// # Returns
 ///
@@ -30,12 +36,11 @@ pub fn calculate_transaction_hash_with_signature<H: HasherT>(
     transaction: &Transaction,
     chain_id: Felt252Wrapper,
     block_number: u64,
+    include_signature: bool,
 ) -> FieldElement
 where
     H: HasherT,
 {
-    let include_signature = block_number >= 61394;
-
     let signature_hash = match transaction {
         Transaction::Invoke(invoke_tx) => {
             // Include signatures for Invoke transactions or for all transactions
@@ -80,40 +85,34 @@ where
     )
 }
 
-/// Calculate the transaction commitment in memory using HashMapDb (which is more efficient for this
-/// usecase).
-///
-/// # Arguments
-///
-/// * `transactions` - The transactions of the block
-/// * `chain_id` - The current chain id
-/// * `block_number` - The current block number
-///
-/// # Returns
-///
-/// The transaction commitment as `Felt252Wrapper`.
-pub fn memory_transaction_commitment(
+/// Builds the transaction trie of a block in memory, returning it along with its leaves.
+fn memory_transaction_trie(
     transactions: &[Transaction],
     chain_id: Felt252Wrapper,
     block_number: u64,
-) -> Result<Felt252Wrapper, String> {
-    // TODO @cchudant refacto/optimise this function
+) -> (BonsaiStorage<BasicId, HashMapDb<BasicId>, Pedersen>, Vec<FieldElement>) {
     let config = BonsaiStorageConfig::default();
     let bonsai_db = HashMapDb::<BasicId>::default();
     let mut bonsai_storage =
         BonsaiStorage::<_, _, Pedersen>::new(bonsai_db, config).expect("Failed to create bonsai storage");
     let identifier = bonsai_identifier::TRANSACTION;
 
+    // the mainnet schedule applies when no network is configured
+    let versions = get_config().map_or(NetworkProfile::mainnet().versions, |config| config.versions);
+    let include_signature = block_number >= versions.tx_commitment_signatures;
+
     // transaction hashes are computed in parallel
     let txs = transactions
         .par_iter()
-        .map(|tx| calculate_transaction_hash_with_signature::<PedersenHasher>(tx, chain_id, block_number))
+        .map(|tx| {
+            calculate_transaction_hash_with_signature::<PedersenHasher>(tx, chain_id, block_number, include_signature)
+        })
         .collect::<Vec<_>>();
 
     // once transaction hashes have finished computing, they are inserted into the local Bonsai db
-    for (i, tx_hash) in txs.into_iter().enumerate() {
+    for (i, tx_hash) in txs.iter().enumerate() {
         let key = BitVec::from_vec(i.to_be_bytes().to_vec());
-        let value = Felt::from(Felt252Wrapper::from(tx_hash));
+        let value = Felt::from(Felt252Wrapper::from(*tx_hash));
         bonsai_storage.insert(identifier, key.as_bitslice(), &value).expect("Failed to insert into bonsai storage");
     }
 
@@ -121,7 +120,62 @@ pub fn memory_transaction_commitment(
     let id = id_builder.new_id();
 
     bonsai_storage.commit(id).expect("Failed to commit to bonsai storage");
-    let root_hash = bonsai_storage.root_hash(identifier).expect("Failed to get root hash");
+
+    (bonsai_storage, txs)
+}
+
+/// Calculate the transaction commitment in memory using HashMapDb (which is more efficient for this
+/// usecase).
+///
+/// # Arguments
+///
+/// * `transactions` - The transactions of the block
+/// * `chain_id` - The current chain id
+/// * `block_number` - The current block number
+///
+/// # Returns
+///
+/// The transaction commitment as `Felt252Wrapper`.
+pub fn memory_transaction_commitment(
+    transactions: &[Transaction],
+    chain_id: Felt252Wrapper,
+    block_number: u64,
+) -> Result<Felt252Wrapper, String> {
+    // TODO @cchudant refacto/optimise this function
+    let (bonsai_storage, _) = memory_transaction_trie(transactions, chain_id, block_number);
+    let root_hash = bonsai_storage.root_hash(bonsai_identifier::TRANSACTION).expect("Failed to get root hash");
 
     Ok(Felt252Wrapper::from(root_hash))
 }
+
+/// Computes the inclusion proof of a transaction in the transaction commitment of its block.
+///
+/// # Arguments
+///
+/// * `transactions` - The transactions of the block
+/// * `chain_id` - The current chain id
+/// * `block_number` - The current block number
+/// * `index` - The index of the transaction in the block
+///
+/// # Returns
+///
+/// The transaction commitment, the leaf of the transaction (its hash combined with its signature)
+/// and the proof nodes from the root to that leaf.
+pub fn memory_transaction_proof(
+    transactions: &[Transaction],
+    chain_id: Felt252Wrapper,
+    block_number: u64,
+    index: usize,
+) -> Result<(Felt252Wrapper, FieldElement, Vec<ProofNode>), String> {
+    let (bonsai_storage, leaves) = memory_transaction_trie(transactions, chain_id, block_number);
+    let leaf = *leaves.get(index).ok_or_else(|| format!("No transaction at index {index}"))?;
+
+    let identifier = bonsai_identifier::TRANSACTION;
+    let root_hash = bonsai_storage.root_hash(identifier).map_err(|e| format!("Failed to get root hash: {e:?}"))?;
+    let key = BitVec::from_vec(index.to_be_bytes().to_vec());
+    let proof = bonsai_storage
+        .get_proof(identifier, key.as_bitslice())
+        .map_err(|e| format!("Failed to get transaction proof: {e:?}"))?;
+
+    Ok((Felt252Wrapper::from(root_hash), leaf, proof.into_iter().map(ProofNode::from).collect()))
+}
@@ -0,0 +1,671 @@
+//! Reconstruction of the Starknet state purely from the data published on L1.
+//!
+//! In this mode the feeder gateway is never queried: the state diffs published as data availability
+//! (calldata or blobs) for every L1 state update are decoded, applied to the database and checked
+//! against the state root verified on L1. The resulting state trails the finalized L1 head, only
+//! has the granularity of L1 state updates, and has no transaction bodies, receipts or events.
+//!
+//! Before EIP-4844, the state diff is registered as memory pages of the Starknet OS output, in
+//! `registerContinuousMemoryPage` transactions sent ahead of the state update. Since then, it is
+//! published as blobs of the state update transaction, which are retrieved from a beacon node.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ethers::abi::{self, ParamType, Token};
+use ethers::contract::parse_log;
+use ethers::providers::{Http, Middleware, Provider, ProviderError};
+use ethers::types::{Address, BlockNumber as EthBlockNumber, Filter, Log, TransactionReceipt, H256, U256, U64};
+use ethers::utils::keccak256;
+use lazy_static::lazy_static;
+use mc_db::storage_handler::primitives::contract_class::ClassUpdateWrapper;
+use mc_db::storage_handler::{self, DeoxysStorageError};
+use mc_db::storage_updates::store_block_artifacts;
+use mc_db::{DbError, DeoxysBackend};
+use mp_convert::field_element::FromFieldElement;
+use num_bigint::BigUint;
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use starknet_api::core::{ContractAddress, Nonce};
+use starknet_core::types::{
+    ContractStorageDiffItem, DeclaredClassItem, DeployedContractItem, NonceUpdate, ReplacedClassItem, StateDiff,
+    StateUpdate, StorageEntry,
+};
+use starknet_ff::FieldElement;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tokio::time::Duration;
+
+use crate::l1::LogStateUpdate;
+use crate::l2::{sync_checkpoint, verify_l2};
+use crate::state::SyncState;
+use crate::utility::convert_log_state_update;
+use crate::utils::constant::LOG_STATE_UPDTATE_TOPIC;
+
+/// The number of L1 blocks queried at once for logs.
+const LOG_RANGE: u64 = 2_000;
+/// How far before a state update its memory pages are searched for.
+const MEMORY_PAGES_LOOKBACK: u64 = 20_000;
+/// The EIP-2718 type of blob transactions.
+const BLOB_TX_TYPE: u64 = 3;
+/// The number of field elements in a blob.
+const BLOB_LEN: usize = 4096;
+const SECONDS_PER_SLOT: u64 = 12;
+
+lazy_static! {
+    /// The modulus of the BLS12-381 scalar field, over which blobs are encoded.
+    static ref BLS_MODULUS: BigUint = BigUint::parse_bytes(
+        b"73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001",
+        16
+    )
+    .expect("Failed to parse BLS modulus");
+    /// A primitive root of unity of order [BLOB_LEN], derived from the generator 7.
+    static ref BLOB_ROOT_OF_UNITY: BigUint =
+        BigUint::from(7u32).modpow(&((&*BLS_MODULUS - 1u32) / BLOB_LEN), &BLS_MODULUS);
+}
+
+#[derive(Error, Debug)]
+pub enum DaError {
+    #[error("L1 provider error: {0}")]
+    Provider(#[from] ProviderError),
+    #[error("beacon node error: {0}")]
+    Beacon(#[from] reqwest::Error),
+    #[error("failed to parse DA payload: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("failed to decode L1 call data: {0}")]
+    Abi(#[from] abi::Error),
+    #[error("{0} not found on L1")]
+    Missing(&'static str),
+    #[error("state update #{0} is published as blobs, but no beacon node url is set")]
+    NoBeaconNode(u64),
+    #[error("unexpected end of DA payload")]
+    UnexpectedEnd,
+    #[error("invalid {0} in DA payload")]
+    InvalidValue(&'static str),
+    #[error("blob decoding task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error(transparent)]
+    Storage(#[from] DeoxysStorageError),
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("reconstructed state root {computed:#x} of block #{block_number} doesn't match {expected:#x} on L1")]
+    StateRootMismatch { block_number: u64, computed: FieldElement, expected: FieldElement },
+}
+
+/// The data published on L1 for a single state update of the Starknet core contract.
+#[derive(Debug, Clone)]
+pub struct DaStateUpdate {
+    /// The last Starknet block covered by this state update.
+    pub block_number: u64,
+    pub block_hash: FieldElement,
+    pub global_root: FieldElement,
+    /// The state diff, in the Starknet DA encoding.
+    pub data: Vec<FieldElement>,
+}
+
+#[derive(Deserialize)]
+struct BeaconResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct BeaconGenesis {
+    genesis_time: String,
+}
+
+#[derive(Deserialize)]
+struct BlobSidecar {
+    blob: String,
+    kzg_commitment: String,
+}
+
+/// Retrieves the DA payloads of the state updates of the Starknet core contract from L1.
+pub struct L1DaFetcher {
+    provider: Arc<Provider<Http>>,
+    core_address: Address,
+    beacon_url: Option<Url>,
+    http: reqwest::Client,
+    genesis_time: OnceCell<u64>,
+}
+
+impl L1DaFetcher {
+    pub fn new(l1_url: Url, core_address: Address, beacon_url: Option<Url>) -> Result<Self, DaError> {
+        let provider = Provider::<Http>::try_from(l1_url.as_str()).map_err(|_| DaError::InvalidValue("L1 url"))?;
+        let http = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
+        Ok(Self { provider: Arc::new(provider), core_address, beacon_url, http, genesis_time: OnceCell::new() })
+    }
+
+    /// Returns the number of the last finalized L1 block.
+    async fn finalized_block(&self) -> Result<u64, DaError> {
+        let block =
+            self.provider.get_block(EthBlockNumber::Finalized).await?.ok_or(DaError::Missing("finalized block"))?;
+        Ok(block.number.ok_or(DaError::Missing("finalized block number"))?.as_u64())
+    }
+
+    /// Returns the `LogStateUpdate` events emitted between the L1 blocks `from` and `to`, in order.
+    async fn state_update_logs(&self, from: u64, to: u64) -> Result<Vec<Log>, DaError> {
+        let filter = Filter::new()
+            .from_block(from)
+            .to_block(to)
+            .address(self.core_address)
+            .topic0(H256::from_slice(&hex::decode(&LOG_STATE_UPDTATE_TOPIC[2..]).expect("Failed to decode topic")));
+        Ok(self.provider.get_logs(&filter).await?)
+    }
+
+    /// Retrieves the state update published by a `LogStateUpdate` event, along with its state diff.
+    async fn fetch_state_update(&self, log: Log) -> Result<DaStateUpdate, DaError> {
+        let tx_hash = log.transaction_hash.ok_or(DaError::Missing("state update transaction"))?;
+        let l1_block = log.block_number.ok_or(DaError::Missing("state update block"))?.as_u64();
+        let state_update = parse_log::<LogStateUpdate>(log)?;
+        let state_update = convert_log_state_update(state_update).map_err(DaError::InvalidValue)?;
+
+        let tx = self.provider.get_transaction(tx_hash).await?.ok_or(DaError::Missing("state update transaction"))?;
+        let data = if tx.transaction_type == Some(U64::from(BLOB_TX_TYPE)) {
+            let hashes: Vec<H256> = tx.other.get_deserialized("blobVersionedHashes").transpose()?.unwrap_or_default();
+            self.blob_data(state_update.block_number, l1_block, &hashes).await?
+        } else {
+            let receipt = self
+                .provider
+                .get_transaction_receipt(tx_hash)
+                .await?
+                .ok_or(DaError::Missing("state update receipt"))?;
+            self.memory_pages_data(l1_block, &receipt).await?
+        };
+
+        Ok(DaStateUpdate {
+            block_number: state_update.block_number,
+            block_hash: FieldElement::from_bytes_be(&state_update.block_hash.0)
+                .map_err(|_| DaError::InvalidValue("block hash"))?,
+            global_root: FieldElement::from_bytes_be(&state_update.global_root.0)
+                .map_err(|_| DaError::InvalidValue("global root"))?,
+            data,
+        })
+    }
+
+    /// Retrieves the state diff registered as memory pages ahead of the state update `receipt`.
+    ///
+    /// The state transition fact of the update identifies the `LogMemoryPagesHashes` event of its
+    /// proof, which lists the hashes of its memory pages. The first page holds the OS output
+    /// header, and the state diff is split over the following ones.
+    async fn memory_pages_data(
+        &self,
+        l1_block: u64,
+        receipt: &TransactionReceipt,
+    ) -> Result<Vec<FieldElement>, DaError> {
+        let fact_topic = event_topic("LogStateTransitionFact(bytes32)");
+        let fact = receipt
+            .logs
+            .iter()
+            .find(|log| log.topics.first() == Some(&fact_topic) && log.data.len() == 32)
+            .map(|log| H256::from_slice(&log.data))
+            .ok_or(DaError::Missing("state transition fact"))?;
+
+        let mut pages = None;
+        self.scan_logs_back(event_topic("LogMemoryPagesHashes(bytes32,bytes32[])"), l1_block, |log| {
+            let params = [ParamType::FixedBytes(32), ParamType::Array(Box::new(ParamType::FixedBytes(32)))];
+            if let Ok([Token::FixedBytes(fact_hash), Token::Array(hashes)]) = abi::decode(&params, &log.data).as_deref()
+            {
+                if fact_hash.as_slice() == fact.as_bytes() {
+                    let hashes = hashes.iter().filter_map(|hash| hash.clone().into_fixed_bytes());
+                    pages = Some(hashes.map(|hash| U256::from_big_endian(&hash)).collect::<Vec<_>>());
+                }
+            }
+            pages.is_some()
+        })
+        .await?;
+        let pages = pages.ok_or(DaError::Missing("memory pages of the state update"))?;
+        let pages = pages.get(1..).unwrap_or_default();
+
+        let mut page_txs = HashMap::new();
+        self.scan_logs_back(event_topic("LogMemoryPageFactContinuous(bytes32,uint256,uint256)"), l1_block, |log| {
+            if log.data.len() == 96 {
+                let memory_hash = U256::from_big_endian(&log.data[32..64]);
+                if pages.contains(&memory_hash) {
+                    if let Some(tx_hash) = log.transaction_hash {
+                        page_txs.entry(memory_hash).or_insert(tx_hash);
+                    }
+                }
+            }
+            page_txs.len() == pages.len()
+        })
+        .await?;
+
+        let mut data = Vec::new();
+        for page in pages {
+            let tx_hash = page_txs.get(page).ok_or(DaError::Missing("memory page"))?;
+            let tx = self.provider.get_transaction(*tx_hash).await?.ok_or(DaError::Missing("memory page"))?;
+
+            // registerContinuousMemoryPage(startAddr, values, z, alpha, prime), all uint256 but values
+            let params = [
+                ParamType::Uint(256),
+                ParamType::Array(Box::new(ParamType::Uint(256))),
+                ParamType::Uint(256),
+                ParamType::Uint(256),
+                ParamType::Uint(256),
+            ];
+            let input = tx.input.get(4..).ok_or(DaError::InvalidValue("memory page call data"))?;
+            let Some(Token::Array(values)) = abi::decode(&params, input)?.into_iter().nth(1) else {
+                return Err(DaError::InvalidValue("memory page call data"));
+            };
+            for value in values {
+                let value = value.into_uint().ok_or(DaError::InvalidValue("memory page value"))?;
+                let mut bytes = [0u8; 32];
+                value.to_big_endian(&mut bytes);
+                data.push(FieldElement::from_bytes_be(&bytes).map_err(|_| DaError::InvalidValue("memory page value"))?);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Visits the logs of `topic` from the L1 block `l1_block` backwards, at most
+    /// [MEMORY_PAGES_LOOKBACK] blocks back, until `visit` returns `true`.
+    async fn scan_logs_back(
+        &self,
+        topic: H256,
+        l1_block: u64,
+        mut visit: impl FnMut(&Log) -> bool,
+    ) -> Result<(), DaError> {
+        let lowest = l1_block.saturating_sub(MEMORY_PAGES_LOOKBACK);
+        let mut to = l1_block;
+        loop {
+            let from = to.saturating_sub(LOG_RANGE - 1).max(lowest);
+            let filter = Filter::new().from_block(from).to_block(to).topic0(topic);
+            for log in self.provider.get_logs(&filter).await?.iter().rev() {
+                if visit(log) {
+                    return Ok(());
+                }
+            }
+            if from == lowest {
+                return Ok(());
+            }
+            to = from - 1;
+        }
+    }
+
+    /// Retrieves the state diff published in the blobs `versioned_hashes` of the state update of
+    /// `block_number`, sent in the L1 block `l1_block`.
+    async fn blob_data(
+        &self,
+        block_number: u64,
+        l1_block: u64,
+        versioned_hashes: &[H256],
+    ) -> Result<Vec<FieldElement>, DaError> {
+        let beacon_url = self.beacon_url.as_ref().ok_or(DaError::NoBeaconNode(block_number))?;
+
+        let genesis_time = self
+            .genesis_time
+            .get_or_try_init(|| async {
+                let url = beacon_url.join("eth/v1/beacon/genesis").map_err(|_| DaError::InvalidValue("beacon url"))?;
+                let genesis: BeaconResponse<BeaconGenesis> =
+                    self.http.get(url).send().await?.error_for_status()?.json().await?;
+                genesis.data.genesis_time.parse::<u64>().map_err(|_| DaError::InvalidValue("beacon genesis time"))
+            })
+            .await?;
+        let timestamp =
+            self.provider.get_block(l1_block).await?.ok_or(DaError::Missing("state update block"))?.timestamp;
+        let slot = timestamp.as_u64().saturating_sub(*genesis_time) / SECONDS_PER_SLOT;
+
+        let url = beacon_url
+            .join(&format!("eth/v1/beacon/blob_sidecars/{slot}"))
+            .map_err(|_| DaError::InvalidValue("beacon url"))?;
+        let sidecars: BeaconResponse<Vec<BlobSidecar>> =
+            self.http.get(url).send().await?.error_for_status()?.json().await?;
+
+        let mut blobs = Vec::with_capacity(versioned_hashes.len());
+        for versioned_hash in versioned_hashes {
+            let sidecar = sidecars
+                .data
+                .iter()
+                .find(|sidecar| {
+                    decode_hex(&sidecar.kzg_commitment)
+                        .is_ok_and(|commitment| kzg_to_versioned_hash(&commitment) == *versioned_hash)
+                })
+                .ok_or(DaError::Missing("blob"))?;
+            blobs.push(decode_hex(&sidecar.blob)?);
+        }
+
+        // the inverse FFT of a blob is too heavy to run on the async workers
+        let decoded = tokio::task::spawn_blocking(move || {
+            blobs.iter().map(|blob| decode_blob(blob)).collect::<Result<Vec<_>, _>>()
+        })
+        .await??;
+        Ok(decoded.into_iter().flatten().collect())
+    }
+}
+
+fn event_topic(signature: &str) -> H256 {
+    H256::from(keccak256(signature.as_bytes()))
+}
+
+fn decode_hex(hex_str: &str) -> Result<Vec<u8>, DaError> {
+    hex::decode(hex_str.trim_start_matches("0x")).map_err(|_| DaError::InvalidValue("hex string"))
+}
+
+/// Computes the versioned hash of a blob from its KZG commitment, as defined by EIP-4844.
+fn kzg_to_versioned_hash(commitment: &[u8]) -> H256 {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = 0x01;
+    H256::from(hash)
+}
+
+/// Decodes the field elements published in a blob.
+///
+/// Starknet publishes its data as the coefficients of a polynomial, and a blob holds the
+/// evaluations of that polynomial over the roots of unity, in bit-reversed order. The data is
+/// recovered by reordering the evaluations and applying an inverse FFT.
+pub fn decode_blob(blob: &[u8]) -> Result<Vec<FieldElement>, DaError> {
+    if blob.len() != BLOB_LEN * 32 {
+        return Err(DaError::InvalidValue("blob length"));
+    }
+
+    let mut evaluations = vec![BigUint::default(); BLOB_LEN];
+    for (i, chunk) in blob.chunks_exact(32).enumerate() {
+        evaluations[bit_reverse(i)] = BigUint::from_bytes_be(chunk);
+    }
+
+    let modulus = &*BLS_MODULUS;
+    let inverse_root = BLOB_ROOT_OF_UNITY.modpow(&(modulus - 2u32), modulus);
+    let inverse_len = BigUint::from(BLOB_LEN).modpow(&(modulus - 2u32), modulus);
+
+    fft(&evaluations, &inverse_root, modulus)
+        .into_iter()
+        .map(|coefficient| {
+            let bytes = (coefficient * &inverse_len % modulus).to_bytes_be();
+            let mut padded = [0u8; 32];
+            padded[32 - bytes.len()..].copy_from_slice(&bytes);
+            FieldElement::from_bytes_be(&padded).map_err(|_| DaError::InvalidValue("blob element"))
+        })
+        .collect()
+}
+
+fn bit_reverse(i: usize) -> usize {
+    i.reverse_bits() >> (usize::BITS - BLOB_LEN.trailing_zeros())
+}
+
+/// Evaluates the polynomial of coefficients `values` over the powers of `root`, whose order is the
+/// number of values.
+fn fft(values: &[BigUint], root: &BigUint, modulus: &BigUint) -> Vec<BigUint> {
+    if values.len() == 1 {
+        return values.to_vec();
+    }
+
+    let root_squared = root * root % modulus;
+    let even = fft(&values.iter().step_by(2).cloned().collect::<Vec<_>>(), &root_squared, modulus);
+    let odd = fft(&values.iter().skip(1).step_by(2).cloned().collect::<Vec<_>>(), &root_squared, modulus);
+
+    let half = values.len() / 2;
+    let mut out = vec![BigUint::default(); values.len()];
+    let mut power = BigUint::from(1u32);
+    for i in 0..half {
+        let term = &power * &odd[i] % modulus;
+        out[i] = (&even[i] + &term) % modulus;
+        out[i + half] = (&even[i] + modulus - &term) % modulus;
+        power = power * root % modulus;
+    }
+    out
+}
+
+/// A contract update, as encoded in the DA payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaContractUpdate {
+    pub address: FieldElement,
+    pub nonce: FieldElement,
+    pub class_hash: Option<FieldElement>,
+    pub storage_updates: Vec<(FieldElement, FieldElement)>,
+}
+
+/// A state diff, as encoded in the DA payload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DaStateDiff {
+    pub contracts: Vec<DaContractUpdate>,
+    pub declared_classes: Vec<(FieldElement, FieldElement)>,
+}
+
+/// Decodes a state diff from the Starknet DA encoding.
+///
+/// The payload starts with the number of updated contracts. Each contract is encoded as its
+/// address, a header word packing its class flag (bit 128), new nonce (bits 64..128) and number of
+/// storage updates (bits 0..64), its new class hash if the class flag is set, and its storage
+/// updates as key/value pairs. It is followed by the number of declared classes, and their class
+/// hash/compiled class hash pairs.
+pub fn decode_state_diff(data: &[FieldElement]) -> Result<DaStateDiff, DaError> {
+    let mut words = data.iter().copied();
+    let mut next = || words.next().ok_or(DaError::UnexpectedEnd);
+
+    let n_contracts = to_usize(next()?, "contract count")?;
+    // the count is read from the blob, each contract taking at least two of its words
+    let mut contracts = Vec::with_capacity(n_contracts.min(data.len() / 2));
+    for _ in 0..n_contracts {
+        let address = next()?;
+        let header = next()?.to_bytes_be();
+
+        if header[..15].iter().any(|b| *b != 0) || header[15] > 1 {
+            return Err(DaError::InvalidValue("contract header"));
+        }
+        let has_class = header[15] == 1;
+        let nonce = FieldElement::from(u64::from_be_bytes(header[16..24].try_into().unwrap()));
+        let n_updates = u64::from_be_bytes(header[24..32].try_into().unwrap());
+
+        let class_hash = if has_class { Some(next()?) } else { None };
+        let storage_updates =
+            (0..n_updates).map(|_| -> Result<_, DaError> { Ok((next()?, next()?)) }).collect::<Result<_, _>>()?;
+
+        contracts.push(DaContractUpdate { address, nonce, class_hash, storage_updates });
+    }
+
+    let n_classes = to_usize(next()?, "declared class count")?;
+    let declared_classes =
+        (0..n_classes).map(|_| -> Result<_, DaError> { Ok((next()?, next()?)) }).collect::<Result<_, _>>()?;
+
+    Ok(DaStateDiff { contracts, declared_classes })
+}
+
+fn to_usize(word: FieldElement, what: &'static str) -> Result<usize, DaError> {
+    u64::try_from(word).ok().and_then(|n| usize::try_from(n).ok()).ok_or(DaError::InvalidValue(what))
+}
+
+/// Converts a decoded DA state diff into a [StateDiff], using the current state to tell deployed
+/// contracts from replaced classes, and to leave out the nonces that did not change.
+///
+/// The DA encoding does not distinguish Cairo 0 declarations, which therefore cannot be recovered.
+fn to_state_diff(diff: DaStateDiff) -> Result<StateDiff, DaError> {
+    let mut state_diff = StateDiff {
+        storage_diffs: Vec::new(),
+        deprecated_declared_classes: Vec::new(),
+        declared_classes: Vec::new(),
+        deployed_contracts: Vec::new(),
+        replaced_classes: Vec::new(),
+        nonces: Vec::new(),
+    };
+    let contract_data = storage_handler::contract_data();
+
+    for DaContractUpdate { address, nonce, class_hash, storage_updates } in diff.contracts {
+        let contract_address = ContractAddress::from_field_element(address);
+        if let Some(class_hash) = class_hash {
+            if contract_data.get_class_hash(&contract_address)?.is_some() {
+                state_diff.replaced_classes.push(ReplacedClassItem { contract_address: address, class_hash });
+            } else {
+                state_diff.deployed_contracts.push(DeployedContractItem { address, class_hash });
+            }
+        }
+        if contract_data.get_nonce(&contract_address)?.unwrap_or_default() != Nonce::from_field_element(nonce) {
+            state_diff.nonces.push(NonceUpdate { contract_address: address, nonce });
+        }
+        if !storage_updates.is_empty() {
+            let storage_entries = storage_updates.into_iter().map(|(key, value)| StorageEntry { key, value }).collect();
+            state_diff.storage_diffs.push(ContractStorageDiffItem { address, storage_entries });
+        }
+    }
+
+    state_diff.declared_classes = diff
+        .declared_classes
+        .into_iter()
+        .map(|(class_hash, compiled_class_hash)| DeclaredClassItem { class_hash, compiled_class_hash })
+        .collect();
+
+    Ok(state_diff)
+}
+
+/// Reconstructs the state from the DA payloads published on L1, starting at the L1 block
+/// `from_l1_block`, up to the finalized L1 head.
+///
+/// Every reconstructed state update is checkpointed, so that the reconstruction resumes after it on
+/// restart: the state updates at or before the checkpoint are skipped.
+pub async fn sync(fetcher: L1DaFetcher, from_l1_block: u64, sync_state: &SyncState) -> Result<(), DaError> {
+    log::info!("🛰️ Reconstructing state from L1 data availability only");
+
+    let mut interval = tokio::time::interval(Duration::from_secs(12));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    let checkpoint = DeoxysBackend::meta().sync_checkpoint()?;
+    let mut last_block = checkpoint.map(|checkpoint| checkpoint.block_number);
+    let mut old_root = match checkpoint {
+        Some(checkpoint) => FieldElement::from_bytes_be(&checkpoint.global_state_root.0)
+            .map_err(|_| DaError::InvalidValue("checkpoint state root"))?,
+        None => FieldElement::ZERO,
+    };
+    let mut next_l1_block = from_l1_block;
+
+    loop {
+        interval.tick().await;
+        let finalized = match fetcher.finalized_block().await {
+            Ok(finalized) => finalized,
+            Err(e) => {
+                log::warn!("Failed to get the finalized L1 block: {e}");
+                continue;
+            }
+        };
+
+        'range: while next_l1_block <= finalized {
+            let to = (next_l1_block + LOG_RANGE - 1).min(finalized);
+            let logs = match fetcher.state_update_logs(next_l1_block, to).await {
+                Ok(logs) => logs,
+                Err(e) => {
+                    log::warn!("Failed to get the L1 state updates: {e}");
+                    break;
+                }
+            };
+
+            for log in logs {
+                let update = match fetcher.fetch_state_update(log).await {
+                    Ok(update) => update,
+                    // the range is fetched again on the next tick, skipping the updates applied
+                    Err(e @ (DaError::Provider(_) | DaError::Beacon(_))) => {
+                        log::warn!("Failed to get DA state update: {e}");
+                        break 'range;
+                    }
+                    Err(e) => return Err(e),
+                };
+                let block_n = update.block_number;
+                if last_block.is_some_and(|last_block| block_n <= last_block) {
+                    continue;
+                }
+
+                let diff = decode_state_diff(&update.data)?;
+                let state_update = StateUpdate {
+                    block_hash: update.block_hash,
+                    old_root,
+                    new_root: update.global_root,
+                    state_diff: to_state_diff(diff)?,
+                };
+
+                let state_root = verify_l2(sync_state, block_n, &state_update);
+                let computed =
+                    FieldElement::from_bytes_be(&state_root.0).map_err(|_| DaError::InvalidValue("state root"))?;
+                if computed != update.global_root {
+                    return Err(DaError::StateRootMismatch {
+                        block_number: block_n,
+                        computed,
+                        expected: update.global_root,
+                    });
+                }
+
+                let checkpoint = sync_checkpoint(block_n, &state_update);
+                store_block_artifacts(block_n, state_update, ClassUpdateWrapper(Vec::new())).await?;
+                DeoxysBackend::meta().write_sync_checkpoint(checkpoint)?;
+
+                log::info!("🛰️ Reconstructed state up to block #{block_n} from L1 data");
+                old_root = update.global_root;
+                last_block = Some(block_n);
+            }
+            next_l1_block = to + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_state_diff() {
+        let header = |class_flag: u8, nonce: u64, n_updates: u64| {
+            let mut bytes = [0u8; 32];
+            bytes[15] = class_flag;
+            bytes[16..24].copy_from_slice(&nonce.to_be_bytes());
+            bytes[24..32].copy_from_slice(&n_updates.to_be_bytes());
+            FieldElement::from_bytes_be(&bytes).unwrap()
+        };
+        let f = FieldElement::from;
+
+        let data = [
+            f(2u64),
+            f(0x100u64),
+            header(1, 3, 1),
+            f(0xc1u64),
+            f(0x1u64),
+            f(0x2u64),
+            f(0x200u64),
+            header(0, 0, 0),
+            f(1u64),
+            f(0xc2u64),
+            f(0xcc2u64),
+        ];
+
+        let diff = decode_state_diff(&data).unwrap();
+        assert_eq!(
+            diff.contracts,
+            vec![
+                DaContractUpdate {
+                    address: f(0x100u64),
+                    nonce: f(3u64),
+                    class_hash: Some(f(0xc1u64)),
+                    storage_updates: vec![(f(0x1u64), f(0x2u64))]
+                },
+                DaContractUpdate { address: f(0x200u64), nonce: f(0u64), class_hash: None, storage_updates: vec![] },
+            ]
+        );
+        assert_eq!(diff.declared_classes, vec![(f(0xc2u64), f(0xcc2u64))]);
+
+        assert!(matches!(decode_state_diff(&data[..5]), Err(DaError::UnexpectedEnd)));
+        // a contract count larger than the blob is not allocated for
+        assert!(matches!(decode_state_diff(&[f(u64::MAX), f(0x100u64)]), Err(DaError::UnexpectedEnd)));
+    }
+
+    #[test]
+    fn test_decode_blob() {
+        let modulus = &*BLS_MODULUS;
+        let data: Vec<BigUint> = (0..BLOB_LEN as u64).map(|i| BigUint::from(i * i + 1)).collect();
+
+        // the blob holds the evaluations of the data, in bit-reversed order
+        let evaluations = fft(&data, &BLOB_ROOT_OF_UNITY, modulus);
+        let mut blob = vec![0u8; BLOB_LEN * 32];
+        for (i, chunk) in blob.chunks_exact_mut(32).enumerate() {
+            let bytes = evaluations[bit_reverse(i)].to_bytes_be();
+            chunk[32 - bytes.len()..].copy_from_slice(&bytes);
+        }
+
+        let decoded = decode_blob(&blob).unwrap();
+        let expected: Vec<FieldElement> = (0..BLOB_LEN as u64).map(|i| FieldElement::from(i * i + 1)).collect();
+        assert_eq!(decoded, expected);
+
+        assert!(matches!(decode_blob(&blob[..32]), Err(DaError::InvalidValue(_))));
+    }
+
+    #[test]
+    fn test_kzg_to_versioned_hash() {
+        let hash = kzg_to_versioned_hash(&[0u8; 48]);
+        assert_eq!(hash.as_bytes()[0], 0x01);
+        assert_eq!(&hash.as_bytes()[1..], &<[u8; 32]>::from(Sha256::digest([0u8; 48]))[1..]);
+    }
+}
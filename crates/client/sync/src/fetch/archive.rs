@@ -0,0 +1,400 @@
+//! Historical blocks fetched from compressed block range archives rather than from the gateway.
+//!
+//! Mirrors of the feeder gateway publish the blocks of the chain as archives of contiguous block
+//! ranges, which are much cheaper to download than one request per block, class and state update.
+//! An archive mirror serves:
+//!
+//! - `index.json`: the archives of the mirror, as `{"archives": [{"first_block": 0, "last_block":
+//!   9999, "file": "blocks-0-9999.jsonl.gz"}]}`.
+//! - the archives themselves, relative to the mirror: gzip compressed JSON lines, one per block, of
+//!   the form `{"block": .., "state_update": .., "classes": [{"class_hash": .., "class": ..}]}`, as
+//!   returned by the `get_block`, `get_state_update` and `get_class_by_hash` methods of the feeder
+//!   gateway.
+//!
+//! The lines of an archive are in block order. Archives are decoded as they are downloaded, and
+//! only a bounded number of their blocks are kept ahead of the sync.
+//!
+//! Blocks which are not archived, such as the recent tail of the chain, and classes missing from an
+//! archive are fetched from the gateway. So are the blocks of an archive which cannot be
+//! downloaded or decoded. Archived blocks go through the same checks as the blocks of the gateway,
+//! so a tampered archive fails the sync like a misbehaving gateway would.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use flate2::read::GzDecoder;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models as p;
+use thiserror::Error;
+use tokio::sync::{mpsc, Mutex as AsyncMutex, OnceCell};
+use url::Url;
+
+use crate::fetch::resumable::{download_resumable, ResumableDownloadError};
+
+/// The number of decoded blocks of an archive kept ahead of the sync, and of downloaded chunks of
+/// an archive kept ahead of its decoding.
+const ARCHIVE_BUFFER: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum BlockArchiveError {
+    #[error("failed to download {0}: {1}")]
+    Download(Url, ResumableDownloadError),
+    #[error("failed to download {0}: {1}")]
+    Request(Url, reqwest::Error),
+    #[error("invalid archive url {0}: {1}")]
+    Url(String, url::ParseError),
+    #[error("failed to decompress archive: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse archive: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Deserialize)]
+struct ArchiveIndex {
+    archives: Vec<ArchiveFile>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ArchiveFile {
+    first_block: u64,
+    last_block: u64,
+    file: String,
+}
+
+/// A block of an archive, along with its state update and the classes it declares or deploys.
+#[derive(Debug, Deserialize)]
+pub struct ArchivedBlock {
+    pub block: p::Block,
+    pub state_update: p::StateUpdate,
+    #[serde(default)]
+    pub classes: Vec<ArchivedClass>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArchivedClass {
+    pub class_hash: FieldElement,
+    pub class: p::DeployedClass,
+}
+
+/// The blocks of an archive, decoded as it is downloaded.
+struct ArchiveStream {
+    blocks: mpsc::Receiver<(u64, ArchivedBlock)>,
+    /// The blocks decoded ahead of the block taken, which the concurrent fetches take out of order.
+    pending: BTreeMap<u64, ArchivedBlock>,
+}
+
+impl ArchiveStream {
+    /// A stream without blocks, for archives which cannot be downloaded.
+    fn empty() -> Self {
+        let (_, blocks) = mpsc::channel(1);
+        Self { blocks, pending: BTreeMap::new() }
+    }
+
+    /// Takes `block_number` out of the archive, decoding the archive up to it.
+    async fn take(&mut self, block_number: u64) -> Option<ArchivedBlock> {
+        if let Some(archived) = self.pending.remove(&block_number) {
+            return Some(archived);
+        }
+        // the blocks are in order, so a block missing before the last one decoded is not archived
+        if self.pending.last_key_value().is_some_and(|(last, _)| *last > block_number) {
+            return None;
+        }
+
+        while let Some((number, archived)) = self.blocks.recv().await {
+            if number == block_number {
+                return Some(archived);
+            }
+            if self.pending.len() == ARCHIVE_BUFFER {
+                self.pending.pop_first();
+            }
+            self.pending.insert(number, archived);
+            if number > block_number {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+/// The block archives of a mirror, downloaded as the sync reaches them.
+pub struct BlockArchive {
+    /// The url of the mirror, ending with a slash.
+    base: Url,
+    client: reqwest::Client,
+    /// The archives of the mirror, `None` if its index could not be downloaded.
+    index: OnceCell<Option<Vec<ArchiveFile>>>,
+    /// The archives being synced, by position in the index. Only the last two archives reached are
+    /// kept, as blocks are fetched in order.
+    loaded: AsyncMutex<HashMap<usize, Arc<AsyncMutex<ArchiveStream>>>>,
+}
+
+impl BlockArchive {
+    /// The archives listed by the `index.json` of the mirror at `base`.
+    pub fn new(base: Url) -> Result<Self, BlockArchiveError> {
+        // a base without a trailing slash would have its last segment replaced when joined
+        let base = match base.path().ends_with('/') {
+            true => base,
+            false => Url::parse(&format!("{base}/")).map_err(|e| BlockArchiveError::Url(base.to_string(), e))?,
+        };
+        Ok(Self {
+            base,
+            client: reqwest::Client::new(),
+            index: OnceCell::new(),
+            loaded: AsyncMutex::new(HashMap::new()),
+        })
+    }
+
+    /// Takes `block_number` out of its archive, downloading the archive if needed.
+    ///
+    /// Returns `None` if the block is not archived, if it was already taken, or if its archive
+    /// cannot be downloaded, in which case it must be fetched from the gateway.
+    pub async fn take_block(&self, block_number: u64) -> Option<ArchivedBlock> {
+        let index = self.index().await?;
+        let position = index.iter().position(|file| (file.first_block..=file.last_block).contains(&block_number))?;
+
+        let stream = {
+            let mut loaded = self.loaded.lock().await;
+            match loaded.get(&position) {
+                Some(stream) => Arc::clone(stream),
+                None => {
+                    let file = &index[position];
+                    let stream = match self.stream(file).await {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            log::warn!("❗ Falling back to the gateway for blocks #{}..: {e}", file.first_block);
+                            ArchiveStream::empty()
+                        }
+                    };
+                    loaded.retain(|loaded_position, _| loaded_position + 1 >= position);
+                    Arc::clone(loaded.entry(position).or_insert_with(|| Arc::new(AsyncMutex::new(stream))))
+                }
+            }
+        };
+
+        let mut stream = stream.lock().await;
+        stream.take(block_number).await
+    }
+
+    async fn index(&self) -> Option<&Vec<ArchiveFile>> {
+        self.index
+            .get_or_init(|| async {
+                match self.download_index().await {
+                    Ok(index) => {
+                        log::info!("📚 Syncing historical blocks from {} block archives", index.len());
+                        Some(index)
+                    }
+                    Err(e) => {
+                        log::warn!("❗ Block archives unavailable, syncing from the gateway: {e}");
+                        None
+                    }
+                }
+            })
+            .await
+            .as_ref()
+    }
+
+    async fn download_index(&self) -> Result<Vec<ArchiveFile>, BlockArchiveError> {
+        let body = self.download_file("index.json").await?;
+        let index: ArchiveIndex = serde_json::from_slice(&body)?;
+        Ok(index.archives)
+    }
+
+    /// Starts downloading an archive, and decoding its blocks as they are downloaded.
+    async fn stream(&self, file: &ArchiveFile) -> Result<ArchiveStream, BlockArchiveError> {
+        log::debug!("Downloading block archive {}", file.file);
+        let url = self.base.join(&file.file).map_err(|e| BlockArchiveError::Url(file.file.clone(), e))?;
+        let mut response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| BlockArchiveError::Request(url, e))?;
+
+        let (chunk_sender, chunks) = mpsc::channel(ARCHIVE_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let chunk = match response.chunk().await {
+                    Ok(Some(chunk)) => Ok(chunk),
+                    Ok(None) => break,
+                    Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
+                };
+                let failed = chunk.is_err();
+                if chunk_sender.send(chunk).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let (block_sender, blocks) = mpsc::channel(ARCHIVE_BUFFER);
+        let (name, range) = (file.file.clone(), file.first_block..=file.last_block);
+        // a decoding which fails or panics closes the stream, and the blocks left are fetched from the
+        // gateway
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = decode_archive(ChunkReader::new(chunks), range, &block_sender) {
+                log::warn!("❗ Falling back to the gateway for the rest of block archive {name}: {e}");
+            }
+        });
+        Ok(ArchiveStream { blocks, pending: BTreeMap::new() })
+    }
+
+    async fn download_file(&self, path: &str) -> Result<Vec<u8>, BlockArchiveError> {
+        let url = self.base.join(path).map_err(|e| BlockArchiveError::Url(path.to_string(), e))?;
+        download_resumable(&self.client, url.clone(), HeaderMap::new())
+            .await
+            .map_err(|e| BlockArchiveError::Download(url, e))
+    }
+}
+
+/// Decodes the blocks of an archive within `range`, sending them as they are decoded.
+///
+/// Decoding stops once the blocks are no longer taken.
+fn decode_archive(
+    archive: impl Read,
+    range: RangeInclusive<u64>,
+    blocks: &mpsc::Sender<(u64, ArchivedBlock)>,
+) -> Result<(), BlockArchiveError> {
+    for line in BufReader::new(GzDecoder::new(archive)).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let archived: ArchivedBlock = serde_json::from_str(&line)?;
+        // blocks outside of the range of the archive are left to the gateway
+        match archived.block.block_number {
+            Some(block_number) if range.contains(&block_number) => {
+                if blocks.blocking_send((block_number, archived)).is_err() {
+                    return Ok(());
+                }
+            }
+            _ => continue,
+        }
+    }
+    Ok(())
+}
+
+/// Reads the chunks of a body downloaded by another task.
+struct ChunkReader<B> {
+    chunks: mpsc::Receiver<std::io::Result<B>>,
+    chunk: Option<B>,
+    offset: usize,
+}
+
+impl<B> ChunkReader<B> {
+    fn new(chunks: mpsc::Receiver<std::io::Result<B>>) -> Self {
+        Self { chunks, chunk: None, offset: 0 }
+    }
+}
+
+impl<B: AsRef<[u8]>> Read for ChunkReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if let Some(chunk) = &self.chunk {
+                let rest = &chunk.as_ref()[self.offset..];
+                if !rest.is_empty() {
+                    let read = rest.len().min(buf.len());
+                    buf[..read].copy_from_slice(&rest[..read]);
+                    self.offset += read;
+                    return Ok(read);
+                }
+            }
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = Some(chunk?);
+                    self.offset = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::Path;
+
+    use flate2::write::GzEncoder;
+
+    use super::*;
+
+    /// An archive line holding the bundled synthetic block as `block_number`.
+    fn archived_line(block_number: u64) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join("schema").join("block_0_13_1.json");
+        let mut block: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        block["block_number"] = block_number.into();
+        let state_update = serde_json::json!({
+            "block_hash": block["block_hash"],
+            "new_root": "0x2",
+            "old_root": "0x1",
+            "state_diff": {
+                "storage_diffs": {},
+                "deployed_contracts": [],
+                "old_declared_contracts": [],
+                "declared_classes": [],
+                "nonces": {},
+                "replaced_classes": []
+            }
+        });
+        serde_json::json!({ "block": block, "state_update": state_update }).to_string()
+    }
+
+    fn archived_block(block_number: u64) -> ArchivedBlock {
+        serde_json::from_str(&archived_line(block_number)).unwrap()
+    }
+
+    #[test]
+    fn archives_are_decoded_within_their_range() {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        for line in [archived_line(10), String::new(), archived_line(11), archived_line(20)] {
+            writeln!(encoder, "{line}").unwrap();
+        }
+        let archive = encoder.finish().unwrap();
+
+        let (sender, mut blocks) = mpsc::channel(ARCHIVE_BUFFER);
+        decode_archive(&archive[..], 10..=19, &sender).unwrap();
+        let mut decoded = Vec::new();
+        while let Ok((block_number, archived)) = blocks.try_recv() {
+            assert_eq!(archived.block.block_number, Some(block_number));
+            decoded.push(block_number);
+        }
+        assert_eq!(decoded, vec![10, 11]);
+
+        // a truncated archive fails once its complete lines are decoded
+        assert!(decode_archive(&archive[..archive.len() / 2], 10..=19, &sender).is_err());
+    }
+
+    #[tokio::test]
+    async fn blocks_are_taken_out_of_order_and_missing_ones_fall_back() {
+        let (sender, blocks) = mpsc::channel(ARCHIVE_BUFFER);
+        for block_number in [10, 11, 13] {
+            sender.send((block_number, archived_block(block_number))).await.unwrap();
+        }
+        drop(sender);
+
+        let mut stream = ArchiveStream { blocks, pending: BTreeMap::new() };
+        assert!(stream.take(11).await.is_some());
+        assert!(stream.take(10).await.is_some());
+        assert!(stream.take(12).await.is_none());
+        assert!(stream.take(13).await.is_some());
+        assert!(stream.take(10).await.is_none());
+        assert!(stream.take(14).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn unreachable_mirrors_fall_back_to_the_gateway() {
+        let archive = BlockArchive::new(Url::parse("http://127.0.0.1:1/mainnet").unwrap()).unwrap();
+        assert!(archive.take_block(0).await.is_none());
+    }
+
+    #[test]
+    fn archives_are_relative_to_the_mirror() {
+        let archive = BlockArchive::new(Url::parse("https://mirror.example/mainnet").unwrap()).unwrap();
+        let file = archive.base.join("blocks-0-9999.jsonl.gz").unwrap();
+        assert_eq!(file.as_str(), "https://mirror.example/mainnet/blocks-0-9999.jsonl.gz");
+    }
+}
@@ -1,25 +1,42 @@
 //! Contains the code required to fetch data from the network efficiently.
 use core::time::Duration;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use itertools::Itertools;
+use lazy_static::lazy_static;
 use mc_db::storage_handler;
 use mc_db::storage_handler::primitives::contract_class::{ContractClassData, ContractClassWrapper};
 use mc_db::storage_handler::StorageView;
 use mp_block::DeoxysBlock;
 use mp_convert::state_update::ToStateUpdateCore;
+use mp_transactions::from_broadcasted_transactions::{
+    decompress_program, legacy_class_hash, BroadcastedTransactionConversionError,
+};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use sp_core::H160;
 use starknet_api::core::ClassHash;
 use starknet_api::hash::StarkFelt;
-use starknet_core::types::{BlockId as BlockIdCore, DeclaredClassItem, DeployedContractItem, StateUpdate};
+use starknet_core::types::{ContractClass, DeclaredClassItem, DeployedContractItem, StarknetError, StateUpdate};
 use starknet_ff::FieldElement;
 use starknet_providers::sequencer::models as p;
 use starknet_providers::sequencer::models::BlockId;
-use starknet_providers::{Provider, ProviderError, SequencerGatewayProvider};
+use starknet_providers::ProviderError;
 use tokio::task::JoinSet;
 use url::Url;
 
-use crate::l2::L2SyncError;
+use crate::fetch::archive::{ArchivedBlock, ArchivedClass};
+use crate::fetch::provider_pool::{PooledProvider, ProviderFailure, ProviderPool};
+use crate::fetch::resumable::{download_resumable, ResumableDownloadError};
+use crate::fetch::schema;
+use crate::l2::{L2StateUpdate, L2SyncError};
+use crate::metrics::{class_metrics, gateway_metrics};
+use crate::network::VersionSchedule;
+use crate::soak::SoakConfig;
+use crate::state::SyncState;
 
 /// The configuration of the worker responsible for fetching new blocks and state updates from the
 /// feeder.
@@ -29,6 +46,8 @@ pub struct FetchConfig {
     pub gateway: Url,
     /// The URL of the feeder gateway.
     pub feeder_gateway: Url,
+    /// The feeder gateways to fall back to when the main one is rate limiting or down.
+    pub fallback_feeder_gateways: Vec<Url>,
     /// The ID of the chain served by the sequencer gateway.
     pub chain_id: starknet_ff::FieldElement,
     /// The number of tasks spawned to fetch blocks and state updates.
@@ -39,23 +58,120 @@ pub struct FetchConfig {
     pub l1_core_address: H160,
     /// Whether to check the root of the state update
     pub verify: bool,
+    /// The number of blocks ahead of the one being applied which are converted, and whose
+    /// commitment state diffs are built when verifying state roots, in parallel.
+    pub verify_lookahead: usize,
+    /// Whether to check the transaction commitment of the fetched blocks against their transactions
+    pub verify_tx_commitments: bool,
+    /// Whether to check the event commitment of the fetched blocks against their events
+    pub verify_event_commitments: bool,
+    /// The number of blocks fetched from the feeder gateway in parallel.
+    pub fetch_concurrency: usize,
+    /// The number of fetched blocks queued ahead of the one being applied.
+    pub buffer_size: usize,
     /// The optional API_KEY to avoid rate limiting from the sequencer gateway.
     pub api_key: Option<String>,
+    /// The last block to sync, the sync stops once it has been applied.
+    pub sync_until: Option<u64>,
+    /// How long the sync pipeline may go without applying a block before it is restarted.
+    pub stall_timeout: Option<Duration>,
+    /// When set, the state is reconstructed only from the L1 data availability payloads, scanning
+    /// L1 from this block, and the feeder gateway is not used for syncing.
+    pub da_from_l1_block: Option<u64>,
+    /// The beacon node serving the blobs, needed to reconstruct the state updates published as
+    /// blobs.
+    pub beacon_url: Option<Url>,
+    /// Whether the pending block is polled from the sequencer.
+    pub pending: bool,
+    /// How often the tip and the pending block are polled from the sequencer.
+    pub pending_poll_interval: Duration,
+    /// Whether the header chain is fetched up to the tip ahead of the full blocks.
+    pub headers_first: bool,
+    /// Whether the blocks below the starting block are backfilled in the background.
+    pub backfill: bool,
+    /// Whether the classes missing from the blocks below the starting block are fetched in the
+    /// background.
+    pub backfill_classes: bool,
+    /// The blocks from which the protocol changes affecting block verification apply.
+    pub versions: VersionSchedule,
+    /// The file where the per-block timings of the sync pipeline are recorded, if any.
+    pub profile_sync: Option<PathBuf>,
+    /// The state snapshot imported before syncing the blocks after it, if any.
+    pub state_snapshot: Option<PathBuf>,
+    /// Whether the memory of the node is sampled to catch it creeping up over long runs.
+    pub soak: Option<SoakConfig>,
+    /// The mirror whose block archives the historical blocks are fetched from, if any.
+    pub block_archive: Option<Url>,
+    /// The classes stored without checking that they hash to their class hash, for historical
+    /// classes whose hash cannot be reproduced.
+    pub unverified_classes: Vec<FieldElement>,
 }
 
-pub async fn fetch_block(client: &SequencerGatewayProvider, block_number: u64) -> Result<p::Block, L2SyncError> {
-    let block = client.get_block(BlockId::Number(block_number)).await?;
+pub async fn fetch_block(pool: &ProviderPool, block_number: u64) -> Result<p::Block, L2SyncError> {
+    let block = fetch_feeder(pool, "get_block", &[("blockNumber", block_number.to_string())]).await?;
+    Ok(pool.served_block(block))
+}
+
+lazy_static! {
+    static ref DOWNLOAD_CLIENT: reqwest::Client = reqwest::Client::new();
+}
+
+/// Downloads the response of a feeder gateway method, resuming the transfer if the connection
+/// drops midway so that large blocks and classes are not downloaded again from the start.
+pub(crate) async fn fetch_feeder<T: DeserializeOwned>(
+    pool: &ProviderPool,
+    method: &str,
+    query: &[(&str, String)],
+) -> Result<T, L2SyncError> {
+    pool.request(|provider| fetch_feeder_from(provider, method, query)).await
+}
+
+async fn fetch_feeder_from<T: DeserializeOwned>(
+    provider: &PooledProvider,
+    method: &str,
+    query: &[(&str, String)],
+) -> Result<T, L2SyncError> {
+    let mut url = provider.feeder_gateway.clone();
+    url.path_segments_mut().expect("feeder gateway url cannot be a base").pop_if_empty().push(method);
+    url.query_pairs_mut().extend_pairs(query);
+
+    let mut headers = HeaderMap::new();
+    if let Some(api_key) = &provider.api_key {
+        headers.insert("X-Throttling-Bypass", HeaderValue::from_str(api_key).map_err(|_| L2SyncError::InvalidApiKey)?);
+    }
 
-    Ok(block)
+    let body = download_resumable(&DOWNLOAD_CLIENT, url, headers).await.map_err(|err| match err {
+        // overloaded gateways shed load with 503s, which are backed off like rate limits
+        ResumableDownloadError::Status(StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE, _) => {
+            L2SyncError::Provider(ProviderError::RateLimited)
+        }
+        ResumableDownloadError::Status(_, message) if message.contains("BLOCK_NOT_FOUND") => {
+            L2SyncError::Provider(ProviderError::StarknetError(StarknetError::BlockNotFound))
+        }
+        err => L2SyncError::Download(err),
+    })?;
+    if let Some(metrics) = gateway_metrics() {
+        metrics.bytes_downloaded.with_label_values(&[provider.label.as_str()]).inc_by(body.len() as f64);
+    }
+
+    schema::decode(method, &body).map_err(L2SyncError::Decode)
 }
 
 pub async fn fetch_block_and_updates(
     block_n: u64,
-    provider: Arc<SequencerGatewayProvider>,
+    provider: Arc<ProviderPool>,
 ) -> Result<(p::Block, StateUpdate, Vec<ContractClassData>), L2SyncError> {
     const MAX_RETRY: u32 = 15;
     let mut attempt = 0;
-    let base_delay = Duration::from_secs(1);
+
+    // historical blocks are taken from the block archives when they are archived
+    let archived = match provider.archive() {
+        Some(archive) => archive.take_block(block_n).await,
+        None => None,
+    };
+    if let Some(archived) = archived {
+        return archived_block_and_updates(&provider, archived, block_n).await;
+    }
 
     loop {
         log::debug!("fetch_block_and_updates {}", block_n);
@@ -65,16 +181,13 @@ pub async fn fetch_block_and_updates(
         log::debug!("fetch_block_and_updates: done {block_n}");
 
         match block.as_ref().err().or(state_update.as_ref().err()) {
-            Some(L2SyncError::Provider(ProviderError::RateLimited)) => {
-                log::info!("The fetching process has been rate limited");
-                log::debug!("The fetching process has been rate limited, retrying in {:?} seconds", base_delay);
+            // the rate limiters of the gateways back them off before the request is retried
+            Some(err) if err.is_rate_limited() => {
+                log::debug!("The fetching process of block #{block_n} has been rate limited, retrying");
                 attempt += 1;
                 if attempt >= MAX_RETRY {
                     return Err(L2SyncError::FetchRetryLimit);
                 }
-                // Exponential backoff with a cap on the delay
-                let delay = base_delay * 2_u32.pow(attempt - 1).min(6); // Cap to prevent overly long delays
-                tokio::time::sleep(delay).await;
             }
             _ => {
                 let (block, (state_update, class_update)) = (block?, state_update?);
@@ -84,45 +197,85 @@ pub async fn fetch_block_and_updates(
     }
 }
 
+/// Converts a block taken from a block archive, fetching the classes missing from the archive from
+/// the gateway.
+async fn archived_block_and_updates(
+    provider: &Arc<ProviderPool>,
+    archived: ArchivedBlock,
+    block_n: u64,
+) -> Result<(p::Block, StateUpdate, Vec<ContractClassData>), L2SyncError> {
+    let state_update = archived.state_update.to_state_update_core();
+    let archived_classes =
+        archived.classes.into_iter().map(|ArchivedClass { class_hash, class }| (class_hash, class)).collect();
+    let class_update = fetch_class_update(provider, &state_update, block_n, archived_classes).await?;
+    Ok((archived.block, state_update, class_update))
+}
+
 pub async fn fetch_apply_genesis_block(config: FetchConfig) -> Result<DeoxysBlock, String> {
-    let client = SequencerGatewayProvider::new(config.gateway.clone(), config.feeder_gateway.clone(), config.chain_id);
-    let client = match &config.api_key {
-        Some(api_key) => client.with_header("X-Throttling-Bypass".to_string(), api_key.clone()),
-        None => client,
-    };
+    let client = ProviderPool::from_config(&config);
     let block = client.get_block(BlockId::Number(0)).await.map_err(|e| format!("failed to get block: {e}"))?;
 
-    Ok(crate::convert::block(block).await)
+    crate::convert::block(block).await.map_err(|e| format!("failed to convert {e}"))
+}
+
+/// Anchors the L2 state on a trusted state root, so that syncing can start right after
+/// `block_number` without the blocks preceding it.
+///
+/// The trusted root must be the state root of `block_number` according to the gateway.
+pub async fn apply_trusted_root(
+    config: &FetchConfig,
+    sync_state: &SyncState,
+    block_number: u64,
+    trusted_root: FieldElement,
+) -> Result<(), String> {
+    let client = ProviderPool::from_config(config);
+    let block =
+        client.get_block(BlockId::Number(block_number)).await.map_err(|e| format!("failed to get block: {e}"))?;
+
+    let state_root = block.state_root.ok_or("no state root provided")?;
+    if state_root != trusted_root {
+        return Err(format!(
+            "trusted root {trusted_root:#x} does not match the state root {state_root:#x} of block {block_number}"
+        ));
+    }
+    let block_hash = block.block_hash.ok_or("no block hash provided")?;
+
+    sync_state.update_l2(L2StateUpdate {
+        block_number,
+        global_root: StarkFelt(state_root.to_bytes_be()),
+        block_hash: StarkFelt(block_hash.to_bytes_be()),
+    });
+
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
 async fn fetch_state_and_class_update(
-    provider: &SequencerGatewayProvider,
+    provider: &Arc<ProviderPool>,
     block_number: u64,
 ) -> Result<(StateUpdate, Vec<ContractClassData>), L2SyncError> {
     // Children tasks need StateUpdate as an Arc, because of task spawn 'static requirement
     // We make an Arc, and then unwrap the StateUpdate out of the Arc
     let state_update = fetch_state_update(provider, block_number).await?;
-    let class_update = fetch_class_update(provider, &state_update, block_number).await?;
+    let class_update = fetch_class_update(provider, &state_update, block_number, HashMap::new()).await?;
 
     Ok((state_update, class_update))
 }
 
 /// retrieves state update from Starknet sequencer
-async fn fetch_state_update(
-    provider: &SequencerGatewayProvider,
-    block_number: u64,
-) -> Result<StateUpdate, L2SyncError> {
-    let state_update = provider.get_state_update(BlockId::Number(block_number)).await?;
+async fn fetch_state_update(provider: &ProviderPool, block_number: u64) -> Result<StateUpdate, L2SyncError> {
+    let state_update: p::StateUpdate =
+        fetch_feeder(provider, "get_state_update", &[("blockNumber", block_number.to_string())]).await?;
 
     Ok(state_update.to_state_update_core())
 }
 
-/// retrieves class updates from Starknet sequencer
+/// retrieves class updates from Starknet sequencer, unless they are among `archived_classes`
 async fn fetch_class_update(
-    provider: &SequencerGatewayProvider,
+    provider: &Arc<ProviderPool>,
     state_update: &StateUpdate,
     block_number: u64,
+    mut archived_classes: HashMap<FieldElement, p::DeployedClass>,
 ) -> Result<Vec<ContractClassData>, L2SyncError> {
     let missing_classes: Vec<&FieldElement> = std::iter::empty()
         .chain(
@@ -143,22 +296,30 @@ async fn fetch_class_update(
         .filter(|class_hash| is_missing_class(class_hash))
         .collect();
 
-    let arc_provider = Arc::new(provider.clone());
-
-    let mut task_set = missing_classes.into_iter().fold(JoinSet::new(), |mut set, class_hash| {
-        let provider = Arc::clone(&arc_provider);
+    let mut classes = vec![];
+    let mut task_set = JoinSet::new();
+    for class_hash in missing_classes {
         let class_hash = *class_hash;
-        // Skip what appears to be a broken Sierra class definition (quick fix)
-        if class_hash
-            != FieldElement::from_hex_be("0x024f092a79bdff4efa1ec86e28fa7aa7d60c89b30924ec4dab21dbfd4db73698").unwrap()
-        {
-            set.spawn(async move { fetch_class(class_hash, block_number, &provider).await });
+        if is_broken_class(&class_hash) {
+            continue;
         }
-        set
-    });
+        match archived_classes.remove(&class_hash) {
+            Some(deployed_class) => {
+                let provider = Arc::clone(provider);
+                task_set.spawn(async move { decode_class(&provider, class_hash, deployed_class).await });
+            }
+            None => {
+                let provider = Arc::clone(provider);
+                let queued = class_metrics().map(|metrics| metrics.compile_queued());
+                task_set.spawn(async move {
+                    let _queued = queued;
+                    fetch_class(&provider, class_hash, block_number).await
+                });
+            }
+        }
+    }
 
     // WARNING: all class downloads will abort if even a single class fails to download.
-    let mut classes = vec![];
     while let Some(res) = task_set.join_next().await {
         classes.push(res.expect("Join error")?);
     }
@@ -168,16 +329,74 @@ async fn fetch_class_update(
 
 /// Downloads a class definition from the Starknet sequencer. Note that because
 /// of the current type hell this needs to be converted into a blockifier equivalent
-async fn fetch_class(
+pub(crate) async fn fetch_class(
+    provider: &ProviderPool,
     class_hash: FieldElement,
     block_number: u64,
-    provider: &SequencerGatewayProvider,
 ) -> Result<ContractClassData, L2SyncError> {
-    let core_class = provider.get_class(BlockIdCore::Number(block_number), class_hash).await?;
-    Ok(ContractClassData {
-        hash: ClassHash(StarkFelt(class_hash.to_bytes_be())),
-        contract_class: ContractClassWrapper::try_from(core_class).expect("converting contract class"),
-    })
+    let deployed_class: p::DeployedClass = fetch_feeder(
+        provider,
+        "get_class_by_hash",
+        &[("classHash", format!("{class_hash:#x}")), ("blockNumber", block_number.to_string())],
+    )
+    .await?;
+    decode_class(provider, class_hash, deployed_class).await
+}
+
+/// Converts a class fetched from the gateway on the blocking threads, as hashing and compiling it
+/// is heavy.
+async fn decode_class(
+    provider: &ProviderPool,
+    class_hash: FieldElement,
+    deployed_class: p::DeployedClass,
+) -> Result<ContractClassData, L2SyncError> {
+    let verify = !provider.is_unverified_class(&class_hash);
+    tokio::task::spawn_blocking(move || convert_class(class_hash, deployed_class, verify))
+        .await
+        .expect("tokio join error")
+}
+
+/// Converts a class fetched from the gateway, rejecting it if it does not hash to `class_hash`
+/// when `verify` is set.
+fn convert_class(
+    class_hash: FieldElement,
+    deployed_class: p::DeployedClass,
+    verify: bool,
+) -> Result<ContractClassData, L2SyncError> {
+    let core_class = ContractClass::try_from(deployed_class)
+        .map_err(|_| L2SyncError::ClassConversion { class_hash, reason: "invalid class definition".to_string() })?;
+    if verify {
+        verify_class_hash(class_hash, &core_class)?;
+    } else {
+        log::debug!("Storing class {class_hash:#x} without checking its hash");
+    }
+    let contract_class = ContractClassWrapper::try_from(core_class)
+        .map_err(|e| L2SyncError::ClassConversion { class_hash, reason: e.to_string() })?;
+    Ok(ContractClassData { hash: ClassHash(StarkFelt(class_hash.to_bytes_be())), contract_class })
+}
+
+/// Checks that a class hashes to `class_hash`, as the gateway serving it is not trusted.
+fn verify_class_hash(class_hash: FieldElement, class: &ContractClass) -> Result<(), L2SyncError> {
+    let computed =
+        compute_class_hash(class).map_err(|e| L2SyncError::ClassConversion { class_hash, reason: e.to_string() })?;
+    if computed != class_hash {
+        return Err(L2SyncError::ClassHashMismatch { class_hash, computed });
+    }
+    Ok(())
+}
+
+/// Recomputes the hash of a class.
+fn compute_class_hash(class: &ContractClass) -> Result<FieldElement, BroadcastedTransactionConversionError> {
+    match class {
+        ContractClass::Sierra(class) => Ok(class.class_hash()),
+        ContractClass::Legacy(class) => legacy_class_hash(class, &decompress_program(&class.program)?),
+    }
+}
+
+/// Whether a class is skipped, as it appears to have a broken Sierra class definition (quick fix).
+pub(crate) fn is_broken_class(class_hash: &FieldElement) -> bool {
+    *class_hash
+        == FieldElement::from_hex_be("0x024f092a79bdff4efa1ec86e28fa7aa7d60c89b30924ec4dab21dbfd4db73698").unwrap()
 }
 
 /// Check if a class is stored in the local Substrate db.
@@ -188,3 +407,38 @@ fn is_missing_class(class_hash: &FieldElement) -> bool {
     let class_hash = ClassHash(StarkFelt(class_hash.to_bytes_be()));
     storage_handler::contract_class_data().contains(&class_hash).is_ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use starknet_core::types::{EntryPointsByType, FlattenedSierraClass};
+
+    use super::*;
+
+    #[test]
+    fn classes_must_hash_to_their_class_hash() {
+        let class = ContractClass::Sierra(FlattenedSierraClass {
+            sierra_program: vec![FieldElement::ONE],
+            contract_class_version: "0.1.0".to_string(),
+            entry_points_by_type: EntryPointsByType { constructor: vec![], external: vec![], l1_handler: vec![] },
+            abi: "[]".to_string(),
+        });
+        let class_hash = compute_class_hash(&class).unwrap();
+
+        assert!(verify_class_hash(class_hash, &class).is_ok());
+        let forged = class_hash + FieldElement::ONE;
+        assert!(matches!(
+            verify_class_hash(forged, &class),
+            Err(L2SyncError::ClassHashMismatch { class_hash, computed }) if class_hash == forged && computed != forged
+        ));
+    }
+
+    #[test]
+    fn unverified_classes_are_configured_on_the_pool() {
+        let url = Url::parse("http://gateway.test/").unwrap();
+        let provider = PooledProvider::new(url.clone(), url, FieldElement::ZERO, None);
+        let pool = ProviderPool::new(vec![provider]).with_unverified_classes([FieldElement::TWO]);
+
+        assert!(pool.is_unverified_class(&FieldElement::TWO));
+        assert!(!pool.is_unverified_class(&FieldElement::ONE));
+    }
+}
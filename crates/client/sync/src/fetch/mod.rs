@@ -1 +1,8 @@
+pub mod archive;
 pub mod fetchers;
+pub mod provider_pool;
+pub mod rate_limit;
+pub mod resumable;
+pub mod schema;
+#[cfg(feature = "testing")]
+pub mod simulation;
@@ -0,0 +1,160 @@
+//! Adaptive rate limiting of the requests sent to a feeder gateway.
+//!
+//! A gateway is not limited until it rate limits the node (`429` or `503` responses). From then
+//! on, its request rate is halved with each rate limited response, and increased step by step with
+//! each successful one, until the limit is lifted. Rate limited responses also back the gateway off
+//! for a while, doubling with each of them in a row, with some jitter so that the concurrent
+//! fetches do not all retry at once.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+/// The request rate a gateway is limited to after its first rate limited response, per second.
+const INITIAL_RATE: f64 = 32.0;
+/// The lowest request rate a gateway is limited to, per second.
+const MIN_RATE: f64 = 0.5;
+/// The request rate above which a gateway is not limited anymore, per second.
+const MAX_RATE: f64 = 256.0;
+/// The increase of the request rate of a limited gateway with each successful request, per second.
+const RATE_STEP: f64 = 0.5;
+/// The backoff of a gateway after one rate limited response.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+/// The longest backoff of a gateway rate limiting repeatedly.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// The window over which the effective request rate is measured.
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+struct LimiterState {
+    /// The requests per second the gateway is limited to, `None` if it is not limited.
+    rate: Option<f64>,
+    /// When the next request may be sent at the current rate.
+    next_slot: Instant,
+    /// Until when no request is sent to the gateway.
+    backoff_until: Option<Instant>,
+    consecutive_limits: u32,
+    window_start: Instant,
+    window_requests: u64,
+    effective_rate: f64,
+}
+
+/// The rate limiter of a gateway.
+#[derive(Debug)]
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            state: Mutex::new(LimiterState {
+                rate: None,
+                next_slot: now,
+                backoff_until: None,
+                consecutive_limits: 0,
+                window_start: now,
+                window_requests: 0,
+                effective_rate: 0.0,
+            }),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Waits until a request may be sent to the gateway, and records it.
+    pub async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Reserves the next slot of the gateway, returning how long to wait for it.
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut state = self.state.lock().expect("Failed to acquire lock on rate limiter");
+
+        let elapsed = now.saturating_duration_since(state.window_start);
+        if elapsed >= RATE_WINDOW {
+            state.effective_rate = state.window_requests as f64 / elapsed.as_secs_f64();
+            state.window_start = now;
+            state.window_requests = 0;
+        }
+        state.window_requests += 1;
+
+        let slot = now.max(state.next_slot).max(state.backoff_until.unwrap_or(now));
+        if let Some(rate) = state.rate {
+            state.next_slot = slot + Duration::from_secs_f64(1.0 / rate);
+        }
+        slot - now
+    }
+
+    /// Raises the rate of a limited gateway after a successful request.
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().expect("Failed to acquire lock on rate limiter");
+        state.consecutive_limits = 0;
+        state.rate = state.rate.map(|rate| rate + RATE_STEP).filter(|rate| *rate < MAX_RATE);
+    }
+
+    /// Halves the rate of the gateway and backs it off after a rate limited response.
+    pub fn record_rate_limited(&self, now: Instant) {
+        let mut state = self.state.lock().expect("Failed to acquire lock on rate limiter");
+        state.consecutive_limits += 1;
+        state.rate = Some(state.rate.map_or(INITIAL_RATE, |rate| (rate / 2.0).max(MIN_RATE)));
+
+        let backoff = BASE_BACKOFF.saturating_mul(2u32.saturating_pow(state.consecutive_limits - 1)).min(MAX_BACKOFF);
+        let jitter = rand::thread_rng().gen_range(0.5..1.5);
+        state.backoff_until = Some(now + backoff.mul_f64(jitter));
+    }
+
+    /// The requests per second the gateway is limited to, `None` if it is not limited.
+    pub fn rate_limit(&self) -> Option<f64> {
+        self.state.lock().expect("Failed to acquire lock on rate limiter").rate
+    }
+
+    /// The requests per second sent to the gateway, as measured over the last complete window.
+    pub fn effective_rate(&self) -> f64 {
+        self.state.lock().expect("Failed to acquire lock on rate limiter").effective_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_is_halved_when_limited_and_raised_back() {
+        let limiter = RateLimiter::default();
+        assert_eq!(limiter.rate_limit(), None);
+        assert!(limiter.reserve(Instant::now()).is_zero());
+
+        let now = Instant::now();
+        limiter.record_rate_limited(now);
+        assert_eq!(limiter.rate_limit(), Some(INITIAL_RATE));
+        limiter.record_rate_limited(now);
+        assert_eq!(limiter.rate_limit(), Some(INITIAL_RATE / 2.0));
+
+        // the gateway is backed off for at least half of the base backoff, doubled
+        assert!(limiter.reserve(now) >= BASE_BACKOFF);
+
+        limiter.record_success();
+        assert_eq!(limiter.rate_limit(), Some(INITIAL_RATE / 2.0 + RATE_STEP));
+        for _ in 0..1000 {
+            limiter.record_success();
+        }
+        assert_eq!(limiter.rate_limit(), None);
+    }
+
+    #[test]
+    fn requests_are_spaced_at_the_limited_rate() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        limiter.record_rate_limited(now);
+
+        let after_backoff = now + MAX_BACKOFF;
+        assert!(limiter.reserve(after_backoff).is_zero());
+        assert_eq!(limiter.reserve(after_backoff), Duration::from_secs_f64(1.0 / INITIAL_RATE));
+    }
+}
@@ -0,0 +1,184 @@
+//! Gateway downloads which resume where they left off when the connection drops.
+//!
+//! Large class definitions can weigh tens of megabytes: rather than restarting such a download
+//! from scratch when the connection is lost, the missing bytes are requested with an HTTP range
+//! request. The resource is pinned with `If-Range` so that a resource changing between two
+//! attempts is downloaded again in full, and the length of the completed body is checked against
+//! the one announced by the gateway.
+//!
+//! The end of what was already received is requested again along with the missing bytes, and the
+//! digests of both copies must match: a resumed body which does not follow the bytes received
+//! before is an error rather than being stitched together.
+
+use reqwest::header::{HeaderMap, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use reqwest::{Client, StatusCode};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use url::Url;
+
+/// Maximum number of times a download is resumed after its connection dropped.
+pub const MAX_RESUMES: u32 = 5;
+
+/// Number of bytes already received which are requested again when a download is resumed, to check
+/// that the resumed body follows them.
+const RESUME_OVERLAP: usize = 4096;
+
+#[derive(Error, Debug)]
+pub enum ResumableDownloadError {
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("unexpected status {0}: {1}")]
+    Status(StatusCode, String),
+    #[error("download still incomplete after {0} resumes")]
+    ResumeLimit(u32),
+    #[error("invalid content range {0:?}")]
+    ContentRange(String),
+    #[error("downloaded {received} bytes, expected {expected}")]
+    Length { received: u64, expected: u64 },
+    #[error("the resumed body does not match the bytes received before, from byte {0}")]
+    Integrity(usize),
+}
+
+/// Downloads the body at `url`, resuming the transfer with range requests if it is interrupted.
+pub async fn download_resumable(
+    client: &Client,
+    url: Url,
+    headers: HeaderMap,
+) -> Result<Vec<u8>, ResumableDownloadError> {
+    let mut body = Vec::new();
+    let mut expected_len = None;
+    let mut etag = None;
+    let mut resumes = 0;
+
+    loop {
+        let resume_from = body.len().saturating_sub(RESUME_OVERLAP);
+        // the bytes received again, checked against the ones received before
+        let mut overlap = Vec::new();
+        let mut overlap_len = 0;
+
+        let mut request = client.get(url.clone()).headers(headers.clone());
+        if !body.is_empty() {
+            request = request.header(RANGE, format!("bytes={resume_from}-"));
+            if let Some(etag) = &etag {
+                request = request.header(IF_RANGE, etag);
+            }
+        }
+
+        let interrupted = match request.send().await {
+            Ok(mut response) => {
+                match response.status() {
+                    StatusCode::PARTIAL_CONTENT if !body.is_empty() => {
+                        let content_range = response
+                            .headers()
+                            .get(CONTENT_RANGE)
+                            .and_then(|value| value.to_str().ok())
+                            .unwrap_or_default();
+                        let (start, total) = parse_content_range(content_range)?;
+                        if start != resume_from as u64 {
+                            return Err(ResumableDownloadError::ContentRange(content_range.to_string()));
+                        }
+                        expected_len = total.or(expected_len);
+                        overlap_len = body.len() - resume_from;
+                    }
+                    // the gateway ignored the range or the resource changed: start over
+                    status if status.is_success() => {
+                        body.clear();
+                        expected_len = response.content_length();
+                        etag = response.headers().get(ETAG).cloned();
+                    }
+                    status => {
+                        let message = response.text().await.unwrap_or_default();
+                        return Err(ResumableDownloadError::Status(status, message));
+                    }
+                }
+
+                loop {
+                    match response.chunk().await {
+                        Ok(Some(chunk)) => {
+                            let mut chunk = &chunk[..];
+                            if overlap.len() < overlap_len {
+                                let (resent, rest) = chunk.split_at((overlap_len - overlap.len()).min(chunk.len()));
+                                overlap.extend_from_slice(resent);
+                                if overlap.len() == overlap_len {
+                                    check_overlap(&body[resume_from..], &overlap, resume_from)?;
+                                }
+                                chunk = rest;
+                            }
+                            body.extend_from_slice(chunk);
+                        }
+                        Ok(None) => break false,
+                        Err(e) => {
+                            log::debug!("Download of {url} interrupted after {} bytes: {e}", body.len());
+                            break true;
+                        }
+                    }
+                }
+            }
+            // nothing was received yet, there is nothing to resume
+            Err(e) if body.is_empty() => return Err(e.into()),
+            Err(e) => {
+                log::debug!("Failed to resume download of {url}: {e}");
+                true
+            }
+        };
+
+        if !interrupted {
+            return match expected_len {
+                Some(expected) if expected != body.len() as u64 => {
+                    Err(ResumableDownloadError::Length { received: body.len() as u64, expected })
+                }
+                _ => Ok(body),
+            };
+        }
+
+        resumes += 1;
+        if resumes > MAX_RESUMES {
+            return Err(ResumableDownloadError::ResumeLimit(MAX_RESUMES));
+        }
+        log::debug!("Resuming download of {url} at byte {}", body.len());
+    }
+}
+
+/// Checks that the bytes received again from `offset` when resuming a download are the ones
+/// received before.
+fn check_overlap(received: &[u8], resent: &[u8], offset: usize) -> Result<(), ResumableDownloadError> {
+    if Sha256::digest(received) != Sha256::digest(resent) {
+        return Err(ResumableDownloadError::Integrity(offset));
+    }
+    Ok(())
+}
+
+/// Parses a `Content-Range: bytes <start>-<end>/<total>` header into its start and total length.
+fn parse_content_range(content_range: &str) -> Result<(u64, Option<u64>), ResumableDownloadError> {
+    let invalid = || ResumableDownloadError::ContentRange(content_range.to_string());
+
+    let (range, total) =
+        content_range.strip_prefix("bytes ").and_then(|range| range.split_once('/')).ok_or_else(invalid)?;
+    let (start, _end) = range.split_once('-').ok_or_else(invalid)?;
+    let start = start.parse().map_err(|_| invalid())?;
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().map_err(|_| invalid())?),
+    };
+
+    Ok((start, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range("bytes 100-199/200").unwrap(), (100, Some(200)));
+        assert_eq!(parse_content_range("bytes 0-49/*").unwrap(), (0, None));
+        assert!(parse_content_range("bytes */200").is_err());
+        assert!(parse_content_range("items 0-1/2").is_err());
+    }
+
+    #[test]
+    fn test_check_overlap() {
+        assert!(check_overlap(b"abcd", b"abcd", 10).is_ok());
+        assert!(matches!(check_overlap(b"abcd", b"abce", 10), Err(ResumableDownloadError::Integrity(10))));
+    }
+}
@@ -0,0 +1,202 @@
+//! Tolerance to the additions made to the schema of the feeder gateway responses.
+//!
+//! The models the responses are decoded into follow the protocol version supported by this
+//! release. Newer protocol versions mostly add fields to the responses, which the sync has no use
+//! for until a release supporting them is cut, but which models denying unknown fields reject.
+//!
+//! Each protocol version after the supported one is described by the fields it added to the
+//! responses of each method, at their exact place in the response. A response which does not
+//! decode as is has the fields added by the protocol version it was served with, and by the ones
+//! before it, dropped from those places only. Responses which still do not decode, such as those
+//! with new enum variants or fields added by an unknown protocol version, are rejected as before.
+
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// The protocol version the models of the responses follow.
+pub const SUPPORTED_PROTOCOL_VERSION: [u64; 3] = [0, 13, 1];
+
+/// A field added to the responses of a feeder gateway method.
+struct AddedField {
+    method: &'static str,
+    /// The path to the field from the root of the response, `*` standing for every element of an
+    /// array.
+    path: &'static [&'static str],
+}
+
+/// The fields a protocol version added to the responses.
+struct ProtocolModel {
+    version: [u64; 3],
+    added_fields: &'static [AddedField],
+}
+
+/// The models of the protocol versions after the supported one, oldest first.
+const MODELS: &[ProtocolModel] = &[
+    ProtocolModel {
+        version: [0, 13, 2],
+        added_fields: &[
+            AddedField { method: "get_block", path: &["state_diff_commitment"] },
+            AddedField { method: "get_block", path: &["state_diff_length"] },
+            AddedField { method: "get_block", path: &["receipt_commitment"] },
+            AddedField {
+                method: "get_block",
+                path: &["transaction_receipts", "*", "execution_resources", "total_gas_consumed"],
+            },
+        ],
+    },
+    ProtocolModel {
+        version: [0, 13, 3],
+        added_fields: &[
+            AddedField { method: "get_block", path: &["l2_gas_price"] },
+            AddedField { method: "get_block", path: &["transaction_receipts", "*", "execution_resources", "l2_gas"] },
+        ],
+    },
+];
+
+/// The methods and protocol versions which were already decoded leniently, warned about once.
+static LENIENT_DECODES: Mutex<BTreeSet<(String, String)>> = Mutex::new(BTreeSet::new());
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+/// Removes the field at `path` from `value`, returning whether it was found.
+fn drop_field(value: &mut Value, path: &[&str]) -> bool {
+    match (path, value) {
+        ([field], Value::Object(object)) => object.remove(*field).is_some(),
+        (["*", rest @ ..], Value::Array(values)) => {
+            values.iter_mut().fold(false, |dropped, value| drop_field(value, rest) | dropped)
+        }
+        ([field, rest @ ..], Value::Object(object)) => {
+            object.get_mut(*field).is_some_and(|value| drop_field(value, rest))
+        }
+        _ => false,
+    }
+}
+
+/// Decodes the response of a feeder gateway method, tolerating the fields added by newer protocol
+/// versions.
+pub(crate) fn decode<T: DeserializeOwned>(method: &str, body: &[u8]) -> Result<T, String> {
+    let strict_error = match serde_json::from_slice(body) {
+        Ok(decoded) => return Ok(decoded),
+        Err(e) => e,
+    };
+    let Ok(mut value) = serde_json::from_slice::<Value>(body) else {
+        return Err(strict_error.to_string());
+    };
+
+    let version = value.get("starknet_version").and_then(Value::as_str).unwrap_or("unknown").to_string();
+    let Some(parsed) = parse_version(&version) else {
+        return Err(format!("{strict_error} (protocol version {version})"));
+    };
+    let mut dropped = Vec::new();
+    for model in MODELS.iter().filter(|model| parsed.as_slice() >= model.version.as_slice()) {
+        for added in model.added_fields.iter().filter(|added| added.method == method) {
+            if drop_field(&mut value, added.path) {
+                dropped.push(added.path.join("."));
+            }
+        }
+    }
+    if dropped.is_empty() {
+        return Err(format!("{strict_error} (protocol version {version})"));
+    }
+
+    let decoded = T::deserialize(&value).map_err(|_| format!("{strict_error} (protocol version {version})"))?;
+    let first = LENIENT_DECODES
+        .lock()
+        .expect("Failed to acquire lock on the lenient decodes")
+        .insert((method.to_string(), version.clone()));
+    if first {
+        log::warn!(
+            "❗ Decoded {method} leniently, as served with protocol version {version} while this release supports {}. \
+             Ignored fields: {}",
+            SUPPORTED_PROTOCOL_VERSION.map(|part| part.to_string()).join("."),
+            dropped.join(", ")
+        );
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_providers::sequencer::models as p;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct Block {
+        starknet_version: String,
+        transactions: Vec<Transaction>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct Transaction {
+        #[serde(rename = "type")]
+        kind: Kind,
+        #[serde(default)]
+        l2_gas_price: Option<u64>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Kind {
+        #[serde(rename = "INVOKE_FUNCTION")]
+        Invoke,
+        #[serde(rename = "NEW")]
+        New,
+    }
+
+    #[test]
+    fn added_fields_are_dropped_where_they_were_added() {
+        let block: Block = decode(
+            "get_block",
+            br#"{"starknet_version": "0.13.3", "l2_gas_price": 3,
+                 "transactions": [{"type": "INVOKE_FUNCTION", "l2_gas_price": 4}]}"#,
+        )
+        .unwrap();
+        assert_eq!(block.starknet_version, "0.13.3");
+        assert_eq!(block.transactions, vec![Transaction { kind: Kind::Invoke, l2_gas_price: Some(4) }]);
+    }
+
+    #[test]
+    fn fields_added_by_later_versions_are_rejected() {
+        let result = decode::<Block>(
+            "get_block",
+            br#"{"starknet_version": "0.13.2", "l2_gas_price": 3, "transactions": [{"type": "NEW"}]}"#,
+        );
+        assert!(result.unwrap_err().contains("protocol version 0.13.2"));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let result = decode::<Block>(
+            "get_block",
+            br#"{"starknet_version": "0.13.2", "transactions": [{"type": "NEW", "new_field": 1}]}"#,
+        );
+        assert!(result.unwrap_err().contains("protocol version 0.13.2"));
+    }
+
+    #[test]
+    fn blocks_served_with_a_newer_version_are_decoded() {
+        let raw = include_str!("../../resources/schema/block_0_13_1.json");
+        let mut block: Value = serde_json::from_str(raw).unwrap();
+        block["starknet_version"] = "0.13.2".into();
+        block["state_diff_commitment"] = "0x276".into();
+        block["state_diff_length"] = 3.into();
+        block["receipt_commitment"] = "0x2760".into();
+        for receipt in block["transaction_receipts"].as_array_mut().unwrap() {
+            receipt["execution_resources"]["total_gas_consumed"] =
+                serde_json::json!({ "l1_gas": 1000, "l1_data_gas": 128 });
+        }
+
+        let decoded: p::Block = decode("get_block", &serde_json::to_vec(&block).unwrap()).unwrap();
+        assert_eq!(decoded.block_number, Some(400000));
+        assert_eq!(decoded.starknet_version.as_deref(), Some("0.13.2"));
+        assert_eq!(decoded.transaction_receipts.len(), block["transaction_receipts"].as_array().unwrap().len());
+    }
+}
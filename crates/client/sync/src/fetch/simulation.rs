@@ -0,0 +1,157 @@
+//! Simulated gateway faults, to exercise the reorg and retry paths of the sync deterministically.
+//!
+//! A [Simulation] attached to a [ProviderPool](crate::fetch::provider_pool::ProviderPool) sits
+//! between the sync and the gateways of the pool:
+//!
+//! - an error burst makes the next requests fail as if the gateways were rate limiting the node,
+//!   without them being sent;
+//! - a reorg makes the gateways serve a fork of the chain, whose blocks up to the one it forks at
+//!   have other hashes, until the sync fetches the block the fork starts from, which is the common
+//!   ancestor of both chains. The real chain is served again from then on.
+//!
+//! The pools built from the fetch config pick up the simulation [installed](install) beforehand,
+//! so that a whole sync run can be driven by a test.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models as p;
+
+static INSTALLED: OnceLock<Arc<Simulation>> = OnceLock::new();
+
+/// Attaches `simulation` to the provider pools built from the fetch config from now on.
+pub fn install(simulation: Arc<Simulation>) -> Result<(), Arc<Simulation>> {
+    INSTALLED.set(simulation)
+}
+
+pub(crate) fn installed() -> Option<Arc<Simulation>> {
+    INSTALLED.get().cloned()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SimulatedReorg {
+    /// The first block of the fork whose parent is not on the local chain.
+    at: u64,
+    /// The number of blocks before `at` replaced by the fork.
+    depth: u64,
+}
+
+impl SimulatedReorg {
+    fn ancestor(&self) -> u64 {
+        self.at - self.depth - 1
+    }
+
+    fn is_forked(&self, block_number: u64) -> bool {
+        (self.at - self.depth..self.at).contains(&block_number)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    failing_requests: u64,
+    reorg: Option<SimulatedReorg>,
+    failures_injected: u64,
+    reorgs_resolved: u64,
+}
+
+/// The faults injected into the requests to the gateways.
+#[derive(Debug, Default)]
+pub struct Simulation {
+    faults: Mutex<Faults>,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn faults(&self) -> std::sync::MutexGuard<'_, Faults> {
+        self.faults.lock().expect("Failed to acquire lock on the simulated faults")
+    }
+
+    /// Makes the next `requests` requests to the gateways fail as rate limited.
+    pub fn inject_error_burst(&self, requests: u64) {
+        self.faults().failing_requests += requests;
+    }
+
+    /// Makes the gateways serve a fork replacing the `depth` blocks before `at`, so that block `at`
+    /// no longer extends the local chain and the sync reverts to block `at - depth - 1`.
+    ///
+    /// # Panics
+    ///
+    /// When the fork would replace the genesis block.
+    pub fn inject_reorg(&self, at: u64, depth: u64) {
+        assert!(depth < at, "a simulated reorg cannot replace the genesis block");
+        self.faults().reorg = Some(SimulatedReorg { at, depth });
+    }
+
+    /// The number of requests failed by an error burst so far.
+    pub fn failures_injected(&self) -> u64 {
+        self.faults().failures_injected
+    }
+
+    /// The number of simulated reorgs whose common ancestor was fetched so far.
+    pub fn reorgs_resolved(&self) -> u64 {
+        self.faults().reorgs_resolved
+    }
+
+    /// Whether a simulated reorg is still being served.
+    pub fn is_reorg_pending(&self) -> bool {
+        self.faults().reorg.is_some()
+    }
+
+    /// Whether the next request fails, consuming one request of the error burst.
+    pub(crate) fn take_failure(&self) -> bool {
+        let mut faults = self.faults();
+        if faults.failing_requests == 0 {
+            return false;
+        }
+        faults.failing_requests -= 1;
+        faults.failures_injected += 1;
+        true
+    }
+
+    /// The hash and parent hash of block `block_number` as served by the gateways.
+    pub fn served_hashes(
+        &self,
+        block_number: u64,
+        block_hash: FieldElement,
+        parent_block_hash: FieldElement,
+    ) -> (FieldElement, FieldElement) {
+        let mut faults = self.faults();
+        let Some(reorg) = faults.reorg else {
+            return (block_hash, parent_block_hash);
+        };
+
+        if block_number == reorg.ancestor() {
+            faults.reorg = None;
+            faults.reorgs_resolved += 1;
+            log::debug!("Simulated reorg at block #{} resolved", reorg.at);
+            return (block_hash, parent_block_hash);
+        }
+
+        let block_hash = if reorg.is_forked(block_number) { fork(block_hash) } else { block_hash };
+        let parent_block_hash = match block_number.checked_sub(1) {
+            Some(parent) if reorg.is_forked(parent) => fork(parent_block_hash),
+            _ => parent_block_hash,
+        };
+        (block_hash, parent_block_hash)
+    }
+
+    /// Rewrites a block fetched from the gateways as served by the simulated chain.
+    pub(crate) fn serve_block(&self, mut block: p::Block) -> p::Block {
+        let Some(block_number) = block.block_number else {
+            return block;
+        };
+        let (block_hash, parent_block_hash) =
+            self.served_hashes(block_number, block.block_hash.unwrap_or(FieldElement::ZERO), block.parent_block_hash);
+        block.block_hash = block.block_hash.map(|_| block_hash);
+        block.parent_block_hash = parent_block_hash;
+        block
+    }
+}
+
+/// The hash of a block of the fork, derived from the hash of the block it replaces.
+fn fork(block_hash: FieldElement) -> FieldElement {
+    block_hash + FieldElement::ONE
+}
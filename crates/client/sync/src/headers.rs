@@ -0,0 +1,221 @@
+//! Headers-first sync: the header chain is fetched up to the tip ahead of the full blocks, which
+//! are backfilled behind it by the regular sync pipeline.
+//!
+//! Only the header of each block is downloaded, which holds its number and hash. The header of the
+//! block last verified on L1 is checked against the block hash of the core contract, and every full
+//! block is checked against its header when it is applied: a block which does not match its header
+//! fails the sync pipeline, and the headers from that block onwards are fetched again. Reorgs at
+//! the tip are detected by fetching the headers at the tip again once the chain has caught up with
+//! it.
+//!
+//! The header chain is held by the [SyncState], and only holds the headers of the blocks which have
+//! not been applied yet, at most [MAX_HEADERS_AHEAD] of them.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+use starknet_api::hash::StarkFelt;
+use starknet_core::types::StarknetError;
+use starknet_ff::FieldElement;
+use starknet_providers::ProviderError;
+
+use crate::fetch::fetchers::fetch_feeder;
+use crate::fetch::provider_pool::ProviderPool;
+use crate::l1::l1_head;
+use crate::l2::L2SyncError;
+use crate::state::SyncState;
+
+/// Number of headers fetched in parallel.
+const HEADER_FETCH_CONCURRENCY: usize = 32;
+
+/// Minimum number of full blocks fetched in parallel behind the header chain.
+pub(crate) const BODY_FETCH_CONCURRENCY: usize = 32;
+
+/// Maximum number of headers held ahead of the last applied block.
+pub const MAX_HEADERS_AHEAD: u64 = 100_000;
+
+/// How long to wait before polling the gateway again once the header chain has reached the tip.
+const HEADER_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The header of a block, as returned by the feeder gateway.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub block_number: u64,
+    pub block_hash: FieldElement,
+}
+
+/// The header of a block did not extend the header chain, as it is not the successor of its tip.
+#[derive(Debug, PartialEq, Eq)]
+pub struct HeaderGap {
+    pub expected: u64,
+    pub got: u64,
+}
+
+/// Headers fetched ahead of the full blocks, by block number.
+#[derive(Default)]
+pub struct HeaderChain {
+    hashes: BTreeMap<u64, FieldElement>,
+}
+
+impl HeaderChain {
+    pub fn get(&self, block_number: u64) -> Option<BlockHeader> {
+        self.hashes.get(&block_number).map(|&block_hash| BlockHeader { block_number, block_hash })
+    }
+
+    /// Returns the numbers of the lowest and highest headers of the chain.
+    pub fn range(&self) -> Option<(u64, u64)> {
+        Some((*self.hashes.keys().next()?, *self.hashes.keys().next_back()?))
+    }
+
+    /// Appends a header to the chain, after checking that it is the successor of the tip.
+    fn link(&mut self, header: BlockHeader) -> Result<(), HeaderGap> {
+        if let Some(&tip) = self.hashes.keys().next_back()
+            && header.block_number != tip + 1
+        {
+            return Err(HeaderGap { expected: tip + 1, got: header.block_number });
+        }
+        self.hashes.insert(header.block_number, header.block_hash);
+        Ok(())
+    }
+
+    /// Drops the headers from `block_number` onwards, so that they are fetched again.
+    pub(crate) fn drop_from(&mut self, block_number: u64) {
+        self.hashes.split_off(&block_number);
+    }
+
+    /// Drops the headers of the applied blocks, which are served from the database.
+    fn drop_applied(&mut self, last_applied: u64) {
+        self.hashes = self.hashes.split_off(&(last_applied + 1));
+    }
+}
+
+async fn fetch_header(provider: &ProviderPool, block_number: u64) -> Result<BlockHeader, L2SyncError> {
+    fetch_feeder(
+        provider,
+        "get_block",
+        &[("blockNumber", block_number.to_string()), ("headerOnly", "true".to_string())],
+    )
+    .await
+}
+
+/// Returns the block last verified on L1, if its header does not match the block hash verified on
+/// L1.
+fn l1_mismatch(sync_state: &SyncState) -> Option<u64> {
+    let l1_head = l1_head()?;
+    let header = sync_state.header(l1_head.block_number)?;
+    (StarkFelt(header.block_hash.to_bytes_be()) != l1_head.block_hash).then_some(l1_head.block_number)
+}
+
+/// Returns the lowest header at the tip of the chain which no longer matches the gateway, if any.
+async fn first_stale_header(provider: &ProviderPool, sync_state: &SyncState) -> Option<u64> {
+    let (first, tip) = sync_state.header_range()?;
+    let mut stale = None;
+    for block_number in (first..=tip).rev() {
+        let header = sync_state.header(block_number)?;
+        match fetch_header(provider, block_number).await {
+            Ok(fetched) if fetched.block_hash == header.block_hash => break,
+            Ok(_) | Err(L2SyncError::Provider(ProviderError::StarknetError(StarknetError::BlockNotFound))) => {
+                stale = Some(block_number)
+            }
+            Err(e) => {
+                log::debug!("Failed to fetch header #{block_number} again: {e}");
+                break;
+            }
+        }
+    }
+    stale
+}
+
+/// Fetches the header chain from `first_block` up to the tip, then keeps following the tip.
+pub async fn sync_headers(provider: &ProviderPool, sync_state: &SyncState, first_block: u64) {
+    let mut next_block = first_block;
+
+    loop {
+        let last_applied = sync_state.state_update().block_number;
+        {
+            let mut chain = sync_state.header_chain().write().expect("Failed to acquire write lock on header chain");
+            chain.drop_applied(last_applied);
+            // the full blocks may have been applied past the header chain
+            if chain.range().is_none() {
+                next_block = next_block.max(last_applied + 1);
+            }
+        }
+
+        let mut headers = stream::iter(next_block..last_applied.max(first_block) + MAX_HEADERS_AHEAD)
+            .map(|block_number| fetch_header(provider, block_number))
+            .buffered(HEADER_FETCH_CONCURRENCY);
+
+        while let Some(header) = headers.next().await {
+            let header = match header {
+                Ok(header) => header,
+                Err(L2SyncError::Provider(ProviderError::StarknetError(StarknetError::BlockNotFound))) => break,
+                Err(e) => {
+                    log::debug!("Failed to fetch header #{next_block}: {e}");
+                    break;
+                }
+            };
+
+            let (block_hash, block_number) = (header.block_hash, header.block_number);
+            let mut chain = sync_state.header_chain().write().expect("Failed to acquire write lock on header chain");
+            match chain.link(header) {
+                Ok(()) => {
+                    next_block += 1;
+                    drop(chain);
+                    // the tip of the header chain is reported as the highest block in the sync status
+                    sync_state.raise_highest_block(block_hash, block_number);
+                }
+                Err(HeaderGap { expected, .. }) => {
+                    next_block = expected;
+                    break;
+                }
+            }
+        }
+
+        if let Some(block_number) = l1_mismatch(sync_state) {
+            log::error!("❗ Header #{block_number} doesn't match the block hash verified on L1, fetching it again");
+            sync_state.drop_headers_from(block_number);
+            next_block = block_number;
+        } else if let Some(block_number) = first_stale_header(provider, sync_state).await {
+            log::warn!("🔀 Header #{block_number} changed at the tip, fetching the headers from it again");
+            sync_state.drop_headers_from(block_number);
+            next_block = block_number;
+        }
+
+        tokio::time::sleep(HEADER_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(block_number: u64) -> BlockHeader {
+        BlockHeader { block_number, block_hash: FieldElement::from(block_number + 100) }
+    }
+
+    #[test]
+    fn test_link_header() {
+        let mut chain = HeaderChain::default();
+
+        assert_eq!(chain.link(header(5)), Ok(()));
+        assert_eq!(chain.link(header(6)), Ok(()));
+        assert_eq!(chain.link(header(8)), Err(HeaderGap { expected: 7, got: 8 }));
+        assert_eq!(chain.range(), Some((5, 6)));
+    }
+
+    #[test]
+    fn test_applied_headers_are_dropped() {
+        let mut chain = HeaderChain::default();
+        (5..10).for_each(|block_number| chain.link(header(block_number)).unwrap());
+
+        chain.drop_applied(6);
+        assert_eq!(chain.range(), Some((7, 9)));
+        assert_eq!(chain.get(6), None);
+        assert_eq!(chain.get(7), Some(header(7)));
+
+        chain.drop_from(8);
+        assert_eq!(chain.range(), Some((7, 7)));
+    }
+}
@@ -1,15 +1,16 @@
 //! Contains the necessaries to perform an L1 verification of the state
 
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, PoisonError, RwLock};
 
 use anyhow::Result;
-use ethers::contract::{abigen, EthEvent};
+use ethers::contract::{abigen, parse_log, EthEvent};
 use ethers::providers::{Http, Middleware, Provider};
 use ethers::types::transaction::eip2718::TypedTransaction;
 use ethers::types::{Address, BlockNumber as EthBlockNumber, Filter, TransactionRequest, I256, U256, U64};
 use ethers::utils::hex::decode;
 use futures::stream::StreamExt;
 use lazy_static::lazy_static;
+use mc_db::{DeoxysBackend, L1Confirmation};
 use mp_felt::Felt252Wrapper;
 use primitive_types::H256;
 use reqwest::Url;
@@ -17,7 +18,7 @@ use serde::Deserialize;
 use serde_json::Value;
 use starknet_api::hash::StarkHash;
 
-use crate::l2::STARKNET_STATE_UPDATE;
+use crate::state::SyncState;
 use crate::utility::{convert_log_state_update, get_config, get_state_update_at};
 use crate::utils::constant::LOG_STATE_UPDTATE_TOPIC;
 
@@ -38,6 +39,30 @@ pub struct L1StateUpdate {
     pub block_hash: StarkHash,
 }
 
+/// Returns the latest state update verified on L1, or `None` until the first one is known, either
+/// restored from the database or received from L1.
+pub fn l1_head() -> Option<L1StateUpdate> {
+    // the state update is replaced as a whole, so it is still consistent if a writer panicked
+    let l1_head = ETHEREUM_STATE_UPDATE.read().unwrap_or_else(PoisonError::into_inner);
+    (l1_head.global_root != StarkHash::default()).then(|| l1_head.clone())
+}
+
+/// Whether a block has been confirmed by a state update posted to L1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationStatus {
+    AcceptedOnL2,
+    AcceptedOnL1,
+}
+
+/// Returns whether `block_n` is covered by the latest state update verified on L1.
+///
+/// The L1 head is restored from the database when the node starts, before L1 is queried again, so
+/// that blocks already confirmed are not reported as only accepted on L2 in the meantime.
+pub fn get_confirmation_status(block_n: u64) -> ConfirmationStatus {
+    let l1_head = ETHEREUM_STATE_UPDATE.read().expect("Failed to acquire read lock on ETHEREUM_STATE_UPDATE");
+    if block_n <= l1_head.block_number { ConfirmationStatus::AcceptedOnL1 } else { ConfirmationStatus::AcceptedOnL2 }
+}
+
 /// Starknet core LogStateUpdate event
 #[derive(Clone, Debug, EthEvent, Deserialize)]
 pub struct LogStateUpdate {
@@ -135,6 +160,24 @@ impl EthereumClient {
         Ok(StarkHash::from(Felt252Wrapper::from_hex_be(&result.to_string()).expect("Failed to parse block hash")))
     }
 
+    /// Get the state update of the Starknet block `block_number`, from the `LogStateUpdate` events
+    /// emitted in the Ethereum block `l1_block_number`, if it was posted in that block.
+    pub async fn get_state_update_in(&self, l1_block_number: u64, block_number: u64) -> Result<Option<L1StateUpdate>> {
+        let topic = H256::from_slice(&hex::decode(&LOG_STATE_UPDTATE_TOPIC[2..])?);
+        let address = get_config().expect("Failed to get config").l1_core_address;
+        let filter =
+            Filter::new().from_block(l1_block_number).to_block(l1_block_number).address(vec![address]).topic0(topic);
+
+        for log in self.provider.get_logs(&filter).await? {
+            let state_update =
+                convert_log_state_update(parse_log::<LogStateUpdate>(log)?).map_err(anyhow::Error::msg)?;
+            if state_update.block_number == block_number {
+                return Ok(Some(state_update));
+            }
+        }
+        Ok(None)
+    }
+
     /// Get the last Starknet state update verified on the L1
     pub async fn get_initial_state(client: &EthereumClient) -> Result<L1StateUpdate, ()> {
         let block_number = client.get_last_block_number().await.map_err(|e| {
@@ -164,14 +207,14 @@ impl EthereumClient {
 
         let event_filter = contract.event::<LogStateUpdate>().from_block(start_block).to_block(EthBlockNumber::Latest);
 
-        let mut event_stream = event_filter.stream().await.expect("Failed to initiate event stream");
+        let mut event_stream = event_filter.stream_with_meta().await.expect("Failed to initiate event stream");
 
         while let Some(event_result) = event_stream.next().await {
             match event_result {
-                Ok(log) => {
+                Ok((log, meta)) => {
                     let format_event =
                         convert_log_state_update(log.clone()).expect("Failed to format event into an L1StateUpdate");
-                    update_l1(format_event);
+                    update_l1(format_event, Some(meta.block_number.as_u64()));
                 }
                 Err(e) => log::error!("Error while listening for events: {:?}", e),
             }
@@ -181,8 +224,9 @@ impl EthereumClient {
     }
 }
 
-/// Update the L1 state with the latest data
-pub fn update_l1(state_update: L1StateUpdate) {
+/// Update the L1 state with the latest data, posted in the Ethereum block `l1_block_number` if it
+/// is known
+pub fn update_l1(state_update: L1StateUpdate, l1_block_number: Option<u64>) {
     log::info!(
         "🔄 Updated L1 head: Number: #{}, Hash: {}, Root: {}",
         state_update.block_number,
@@ -190,6 +234,12 @@ pub fn update_l1(state_update: L1StateUpdate) {
         state_update.global_root
     );
 
+    let confirmation =
+        L1Confirmation { l1_block_number, block_hash: state_update.block_hash, global_root: state_update.global_root };
+    if let Err(e) = DeoxysBackend::l1().store_state_update(state_update.block_number, &confirmation) {
+        log::error!("Failed to store the L1 state update of block #{}: {e}", state_update.block_number);
+    }
+
     {
         let last_state_update = ETHEREUM_STATE_UPDATE.clone();
         let mut new_state_update =
@@ -199,8 +249,8 @@ pub fn update_l1(state_update: L1StateUpdate) {
 }
 
 /// Verify the L1 state with the latest data
-pub async fn verify_l1(state_update: L1StateUpdate, rpc_port: u16) -> Result<(), String> {
-    let starknet_state_block_number = STARKNET_STATE_UPDATE.read().map_err(|e| e.to_string())?.block_number;
+pub async fn verify_l1(sync_state: &SyncState, state_update: L1StateUpdate, rpc_port: u16) -> Result<(), String> {
+    let starknet_state_block_number = sync_state.state_update().block_number;
 
     // Check if the node reached the latest verified state on Ethereum
     if state_update.block_number > starknet_state_block_number {
@@ -237,12 +287,26 @@ pub async fn sync(l1_url: Url) {
 
     log::info!("🚀 Subscribed to L1 state verification");
 
+    // Restore the L1 head confirmed before the node restarted
+    match DeoxysBackend::l1().last_confirmed() {
+        Ok(Some((block_number, confirmation))) => {
+            let l1_head = L1StateUpdate {
+                block_number,
+                global_root: confirmation.global_root,
+                block_hash: confirmation.block_hash,
+            };
+            *ETHEREUM_STATE_UPDATE.write().expect("Failed to acquire write lock on ETHEREUM_STATE_UPDATE") = l1_head;
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("Failed to restore the L1 head: {e}"),
+    }
+
     // Get and store the latest verified state
     let initial_state = match EthereumClient::get_initial_state(&client).await {
         Ok(state) => state,
         Err(_) => return,
     };
-    update_l1(initial_state);
+    update_l1(initial_state, None);
 
     // Listen to LogStateUpdate (0x77552641) update and send changes continusly
     let start_block =
@@ -1,33 +1,40 @@
 //! Contains the code required to sync data from the feeder efficiently.
-use std::pin::pin;
-use std::str::FromStr;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
+use blockifier::state::cached_state::CommitmentStateDiff;
 use futures::prelude::*;
-use lazy_static::lazy_static;
-use mc_db::storage_handler::primitives::contract_class::ClassUpdateWrapper;
-use mc_db::storage_updates::{store_class_update, store_state_update};
-use mc_db::DeoxysBackend;
+use mc_db::storage_handler::primitives::contract_class::{ClassUpdateWrapper, ContractClassData};
+use mc_db::storage_handler::DeoxysStorageError;
+use mc_db::storage_updates::{revert_block, revert_tries_to, store_block_artifacts};
+use mc_db::{ApplyJournal, DbError, DeoxysBackend, SyncCheckpoint};
 use mp_block::DeoxysBlock;
 use mp_felt::Felt252Wrapper;
-use mp_types::block::{DBlockT, DHashT};
-use serde::Deserialize;
-use sp_blockchain::HeaderBackend;
+use serde::{Deserialize, Serialize};
 use sp_core::H256;
 use starknet_api::hash::{StarkFelt, StarkHash};
-use starknet_core::types::{PendingStateUpdate, StarknetError, StateUpdate};
+use starknet_core::types::{StarknetError, StateUpdate};
 use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models as p;
 use starknet_providers::sequencer::models::BlockId;
-use starknet_providers::{ProviderError, SequencerGatewayProvider};
+use starknet_providers::ProviderError;
 use thiserror::Error;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
 use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 use crate::commitments::lib::{build_commitment_state_diff, update_state_root};
+use crate::convert::ConversionError;
 use crate::fetch::fetchers::fetch_block_and_updates;
-use crate::l1::ETHEREUM_STATE_UPDATE;
-use crate::CommandSink;
+use crate::fetch::provider_pool::ProviderPool;
+use crate::fetch::resumable::ResumableDownloadError;
+use crate::metrics::{class_metrics, pool_metrics, PoolMetrics};
+use crate::profile::{self, Stage};
+use crate::reorder::{Rejected, ReorderBuffer};
+use crate::reorgs::lib::is_reorg;
+use crate::state::SyncState;
+use crate::watchdog::{watchdog, PipelineProbe, PipelineStage};
+use crate::{soak, CommandSink};
 
 async fn spawn_compute<F, R>(func: F) -> R
 where
@@ -36,20 +43,99 @@ where
 {
     let (tx, rx) = tokio::sync::oneshot::channel();
 
+    let metrics = pool_metrics();
+    let queued_at = metrics.map(PoolMetrics::compute_queued);
     rayon::spawn(move || {
-        let _result = tx.send(func());
+        let started_at = metrics.zip(queued_at).map(|(metrics, queued_at)| metrics.compute_started(queued_at));
+        let result = func();
+        if let Some((metrics, started_at)) = metrics.zip(started_at) {
+            metrics.compute_finished(started_at);
+        }
+        let _result = tx.send(result);
     });
 
     rx.await.expect("tokio channel closed")
 }
 
-// TODO: add more error variants, which are more explicit
 #[derive(Error, Debug)]
 pub enum L2SyncError {
     #[error("provider error")]
     Provider(#[from] ProviderError),
     #[error("fetch retry limit exceeded")]
     FetchRetryLimit,
+    #[error("sync pipeline stalled after block {0}")]
+    Stalled(u64),
+    #[error("the gateway api key is not a valid http header value")]
+    InvalidApiKey,
+    #[error("gateway download failed: {0}")]
+    Download(#[from] ResumableDownloadError),
+    #[error("failed to decode gateway response: {0}")]
+    Decode(String),
+    #[error("block {0} does not extend the local chain")]
+    Reorg(u64),
+    #[error("fetched block {fetched:?} in place of block {expected}")]
+    OutOfSequence { expected: u64, fetched: Option<u64> },
+    #[error("the local chain shares no block with the sequencer")]
+    NoCommonAncestor,
+    #[error("block {block_number} hash {block_hash:#x} doesn't match its header: {header:#x}")]
+    HeaderMismatch { block_number: u64, header: FieldElement, block_hash: FieldElement },
+    #[error("historical block {0} does not hash to the parent hash of its successor")]
+    BackfillMismatch(u64),
+    #[error("failed to convert class {class_hash:#x}: {reason}")]
+    ClassConversion { class_hash: FieldElement, reason: String },
+    #[error("failed to convert {0}")]
+    BlockConversion(#[from] ConversionError),
+    #[error("state root {computed} of block {block_number} doesn't match the fetched state root {fetched}")]
+    StateRootMismatch { block_number: u64, computed: StarkHash, fetched: StarkHash },
+    #[error("class {class_hash:#x} fetched from the gateway hashes to {computed:#x}")]
+    ClassHashMismatch { class_hash: FieldElement, computed: FieldElement },
+    #[error("{commitment} commitment {computed} of block {block_number} doesn't match the fetched one {fetched}")]
+    CommitmentMismatch { block_number: u64, commitment: &'static str, computed: StarkHash, fetched: StarkHash },
+    #[error("failed to store block {0}: {1}")]
+    Storage(u64, #[source] DeoxysStorageError),
+    #[error("failed to write sync metadata: {0}")]
+    Db(#[from] DbError),
+    #[error("{0} channel closed")]
+    ChannelClosed(&'static str),
+    #[error("failed to seal block {0}: {1}")]
+    Seal(u64, String),
+    #[error("failed to revert the chain to block {0}: {1}")]
+    ChainRevert(u64, #[source] sp_blockchain::Error),
+    #[error("sync cancelled")]
+    Cancelled,
+}
+
+impl L2SyncError {
+    /// Whether syncing again from the last applied block may get past the error.
+    ///
+    /// The other errors are deterministic or leave the node unable to apply blocks, so the sync
+    /// halts on them rather than applying the same block again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            L2SyncError::Provider(err) => !matches!(err, ProviderError::StarknetError(_)),
+            L2SyncError::FetchRetryLimit
+            | L2SyncError::Stalled(_)
+            | L2SyncError::Download(_)
+            | L2SyncError::Decode(_)
+            | L2SyncError::Reorg(_)
+            | L2SyncError::HeaderMismatch { .. }
+            | L2SyncError::OutOfSequence { .. } => true,
+            L2SyncError::NoCommonAncestor
+            | L2SyncError::InvalidApiKey
+            | L2SyncError::BackfillMismatch(_)
+            | L2SyncError::ClassConversion { .. }
+            | L2SyncError::BlockConversion(_)
+            | L2SyncError::StateRootMismatch { .. }
+            | L2SyncError::CommitmentMismatch { .. }
+            | L2SyncError::ClassHashMismatch { .. }
+            | L2SyncError::Storage(..)
+            | L2SyncError::Db(_)
+            | L2SyncError::ChannelClosed(_)
+            | L2SyncError::Seal(..)
+            | L2SyncError::ChainRevert(..)
+            | L2SyncError::Cancelled => false,
+        }
+    }
 }
 
 /// Contains the latest Starknet verified state on L2
@@ -67,55 +153,14 @@ pub struct L2StateUpdate {
 /// - SyncPendingState: the node is fully synced and now syncing Pending blocks
 ///
 /// This is used to determine the current state of the syncing process
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SyncStatus {
     SyncVerifiedState,
     SyncUnverifiedState,
     SyncPendingState,
 }
 
-lazy_static! {
-    /// Shared current syncing status, either verified, unverified or pending
-    pub static ref SYNC_STATUS: RwLock<SyncStatus> = RwLock::new(SyncStatus::SyncVerifiedState);
-}
-
-lazy_static! {
-    /// Shared latest L2 state update verified on L2
-    pub static ref STARKNET_STATE_UPDATE: RwLock<L2StateUpdate> = RwLock::new(L2StateUpdate {
-        block_number: u64::default(),
-        global_root: StarkHash::default(),
-        block_hash: StarkHash::default(),
-    });
-}
-
-lazy_static! {
-    /// Shared latest block number and hash of chain, using a RwLock to allow for concurrent reads and exclusive writes
-    pub static ref STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER: RwLock<(FieldElement, u64)> = RwLock::new((FieldElement::default(), 0));
-}
-
-lazy_static! {
-    /// Shared pending block data, using a RwLock to allow for concurrent reads and exclusive writes
-    static ref STARKNET_PENDING_BLOCK: RwLock<Option<DeoxysBlock>> = RwLock::new(None);
-}
-
-lazy_static! {
-    /// Shared pending state update, using RwLock to allow for concurrent reads and exclusive writes
-    static ref STARKNET_PENDING_STATE_UPDATE: RwLock<Option<PendingStateUpdate>> = RwLock::new(None);
-}
-
-pub fn get_highest_block_hash_and_number() -> (FieldElement, u64) {
-    *STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER
-        .read()
-        .expect("Failed to acquire read lock on STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER")
-}
-
-pub fn get_pending_block() -> Option<DeoxysBlock> {
-    STARKNET_PENDING_BLOCK.read().expect("Failed to acquire read lock on STARKNET_PENDING_BLOCK").clone()
-}
-
-pub fn get_pending_state_update() -> Option<PendingStateUpdate> {
-    STARKNET_PENDING_STATE_UPDATE.read().expect("Failed to acquire read lock on STARKNET_PENDING_BLOCK").clone()
-}
-
 /// The configuration of the senders responsible for sending blocks and state
 /// updates from the feeder.
 pub struct SenderConfig {
@@ -126,147 +171,538 @@ pub struct SenderConfig {
     pub command_sink: CommandSink,
 }
 
+/// How long the block being applied is waited for once the pipeline has stalled, before it is
+/// abandoned half applied.
+pub const STALLED_BLOCK_GRACE: Duration = Duration::from_secs(30);
+
 /// Spawns workers to fetch blocks and state updates from the feeder.
-/// `n_blocks` is optionally the total number of blocks to sync, for debugging/benchmark purposes.
-pub async fn sync<C>(
+///
+/// When `sync_until` is set, no block past it is fetched, and the pipeline returns once it has been
+/// applied and flushed to disk, for benchmarking and testing purposes.
+///
+/// When `stall_timeout` is set, the pipeline is torn down with [`L2SyncError::Stalled`] if no
+/// block could be applied for that long while the gateway head is advancing. The block being
+/// applied, if any, is completed first, unless it is itself stuck for [STALLED_BLOCK_GRACE]: it is
+/// then abandoned, left in the apply journal to be rolled back before the pipeline is restarted.
+///
+/// The next `verify_lookahead` blocks are converted, and their commitment state diffs built when
+/// `verify` is set, in parallel, while the state root is updated one block at a time as they are
+/// applied.
+///
+/// When `verify_tx_commitments` or `verify_event_commitments` is set, the transaction or event
+/// commitment computed while converting each block is checked against the one of the fetched block.
+///
+/// Up to `fetch_concurrency` blocks are fetched in parallel, and up to `buffer_size` fetched blocks
+/// are queued ahead of the apply loop.
+/// Fetched blocks arriving out of order are held until their turn, up to `buffer_size` blocks
+/// ahead of the one being applied, and blocks arriving more than once are only applied once. The
+/// blocks skipped by the provider are requested again from the fetcher.
+///
+/// When a fetched block does not extend the last applied one, the pipeline is torn down with
+/// [`L2SyncError::Reorg`].
+///
+/// Once `cancel` is cancelled, the pipeline stops after the block being applied, if any, and
+/// returns once its writes are flushed to disk. The tasks of the pipeline are cancelled whenever it
+/// ends, including when it fails.
+#[allow(clippy::too_many_arguments)]
+pub async fn sync(
     block_sender: Sender<DeoxysBlock>,
     mut command_sink: CommandSink,
-    provider: SequencerGatewayProvider,
+    provider: Arc<ProviderPool>,
     first_block: u64,
     verify: bool,
-    client: Arc<C>,
-) where
-    C: HeaderBackend<DBlockT> + 'static,
-{
-    let provider = Arc::new(provider);
+    verify_lookahead: usize,
+    verify_tx_commitments: bool,
+    verify_event_commitments: bool,
+    fetch_concurrency: usize,
+    buffer_size: usize,
+    sync_until: Option<u64>,
+    stall_timeout: Option<Duration>,
+    sync_state: Arc<SyncState>,
+    cancel: CancellationToken,
+) -> Result<(), L2SyncError> {
+    let cancel = cancel.child_token();
+    // spawned fetches do not outlive the pipeline, whichever way it ends
+    let _cancel_on_drop = cancel.clone().drop_guard();
+    // stops the apply loop in between blocks, on cancellation or once the watchdog fires
+    let stop = cancel.child_token();
     let mut last_block_hash = None;
+    let probe = PipelineProbe::new(first_block.saturating_sub(1));
+    sync_state.reset_progress(first_block.saturating_sub(1));
 
     // Fetch blocks and updates in parallel one time before looping
-    let fetch_stream = (first_block..).map(|block_n| {
+    let fetch_stream = (first_block..=sync_until.unwrap_or(u64::MAX)).map(|block_n| {
         let provider = Arc::clone(&provider);
-        async move { tokio::spawn(fetch_block_and_updates(block_n, provider)).await.expect("tokio join error") }
+        let cancel = cancel.clone();
+        async move {
+            let _span = profile::span(Stage::Fetch, block_n);
+            let fetch = async move {
+                tokio::select! {
+                    _ = cancel.cancelled() => Err(L2SyncError::Cancelled),
+                    fetched = fetch_block_and_updates(block_n, provider) => fetched,
+                }
+            };
+            tokio::spawn(fetch).await.expect("tokio join error")
+        }
     });
-
-    // Have 10 fetches in parallel at once, using futures Buffered
-    let fetch_stream = stream::iter(fetch_stream).buffered(10);
-    let (fetch_stream_sender, mut fetch_stream_receiver) = mpsc::channel(10);
+    // the blocks the provider skipped are requested again by the apply loop
+    let (refetch_sender, mut refetch_receiver) = mpsc::unbounded_channel::<u64>();
+
+    // Have `fetch_concurrency` fetches in parallel at once, using futures Buffered
+    let fetch_stream = stream::iter(fetch_stream).buffered(fetch_concurrency.max(1));
+
+    // Convert the next blocks and build their commitment state diffs in parallel, only the state
+    // root update has to follow the chain order
+    let fetch_stream = fetch_stream
+        .map(|val| async move {
+            let (block, state_update, class_update) = val?;
+            let commitments = (verify_tx_commitments, verify_event_commitments);
+            let prepared =
+                spawn_compute(move || prepare_block(block, state_update, class_update, verify, commitments)).await;
+            Ok::<_, L2SyncError>(prepared)
+        })
+        .buffered(verify_lookahead.max(1));
+    let (fetch_stream_sender, mut fetch_stream_receiver) = mpsc::channel(buffer_size.max(1));
+    let fetch_queue = fetch_stream_sender.downgrade();
+    let refetch_queue = fetch_stream_sender.downgrade();
+    soak::register_queue("fetch_queue", fetch_queue.clone());
+    soak::register_queue("block_import_queue", block_sender.downgrade());
 
     tokio::select!(
-        // update highest block hash and number, update pending block and state update
-        _ = async {
-            let mut interval = tokio::time::interval(Duration::from_secs(5));
-            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-            loop {
-                interval.tick().await;
-                if let Err(e) = update_starknet_data(&provider, client.as_ref()).await {
-                    log::error!("Failed to update highest block hash and number: {}", e);
-                }
-            }
-        } => {},
         // fetch blocks and updates in parallel
         _ = async {
-            fetch_stream.for_each(|val| async {
-                fetch_stream_sender.send(val).await.expect("receiver is closed");
-            }).await;
-
-            drop(fetch_stream_sender); // dropping the channel makes the recieving task stop once the queue is empty.
+            let fetch = async {
+                fetch_stream.for_each(|val| async {
+                    fetch_stream_sender.send(val).await.expect("receiver is closed");
+                }).await;
+
+                // dropping the channel makes the recieving task stop once the queue is empty.
+                drop(fetch_stream_sender);
+            };
+            // the skipped blocks are fetched again one at a time, and queued along the others
+            let refetch = async {
+                while let Some(block_n) = refetch_receiver.recv().await {
+                    let fetched = tokio::select! {
+                        _ = cancel.cancelled() => Err(L2SyncError::Cancelled),
+                        fetched = fetch_block_in_sequence(block_n, Arc::clone(&provider)) => fetched,
+                    };
+                    let prepared = match fetched {
+                        Ok((block, state_update, class_update)) => {
+                            let commitments = (verify_tx_commitments, verify_event_commitments);
+                            let prepare = move || prepare_block(block, state_update, class_update, verify, commitments);
+                            Ok(spawn_compute(prepare).await)
+                        }
+                        Err(err) => Err(err),
+                    };
+                    let Some(sender) = refetch_queue.upgrade() else { break };
+                    if sender.send(prepared).await.is_err() {
+                        break;
+                    }
+                }
+            };
+            future::join(fetch, refetch).await;
 
             std::future::pending().await
         } => {},
         // apply blocks and updates sequentially
-        _ = async {
-            let mut block_n = first_block;
-            let block_sender = Arc::new(block_sender);
-
-            while let Some(val) = pin!(fetch_stream_receiver.recv()).await {
-                if matches!(val, Err(L2SyncError::Provider(ProviderError::StarknetError(StarknetError::BlockNotFound)))) {
-                    break;
-                }
-
-                let (block, state_update, class_update) = val.expect("fetching block");
-
-                let (state_update, block_conv) = {
-                    let state_update = Arc::new(state_update);
-                    let state_update_1 = Arc::clone(&state_update);
-
-                    let block_conv = spawn_compute(move || {
-                        let convert_block = |block| {
-                            let start = std::time::Instant::now();
-                            let block_conv = crate::convert::convert_block_sync(block);
-                            log::debug!("convert::convert_block_sync: {:?}", std::time::Instant::now() - start);
-                            block_conv
+        stalled = async {
+            let apply = async {
+                let mut block_n = first_block;
+                let block_sender = Arc::new(block_sender);
+                let mut reorder = ReorderBuffer::new(first_block, buffer_size);
+                let mut refetching = std::collections::BTreeSet::new();
+
+                loop {
+                    let Some(prepared) = reorder.pop() else {
+                        // the pipeline is only stopped in between blocks, a block is never left half applied
+                        let val = tokio::select! {
+                            biased;
+                            _ = stop.cancelled() => {
+                                DeoxysBackend::flush()?;
+                                log::info!("🛑 Sync stopped, next block to apply is #{block_n}");
+                                break;
+                            }
+                            val = fetch_stream_receiver.recv() => val,
                         };
-                        let ver_l2 = || {
+                        let Some(val) = val else { break };
+                        if matches!(
+                            val,
+                            Err(L2SyncError::Provider(ProviderError::StarknetError(StarknetError::BlockNotFound)))
+                        ) {
+                            break;
+                        }
+
+                        // blocks arriving out of order are held until their turn, duplicates are dropped
+                        let fetched = val?;
+                        let fetched_n = fetched.block_number.unwrap_or(block_n);
+                        match reorder.insert(fetched_n, fetched) {
+                            Ok(()) => {}
+                            Err(Rejected::Duplicate) => log::debug!("Dropping block #{fetched_n}, fetched twice"),
+                            Err(Rejected::OutOfWindow) => {
+                                return Err(L2SyncError::OutOfSequence { expected: block_n, fetched: Some(fetched_n) });
+                            }
+                        }
+                        // a block arriving ahead of its turn means the provider skipped the ones before
+                        refetching.retain(|refetched| *refetched >= reorder.next());
+                        for missing in reorder.missing() {
+                            if refetching.insert(missing) {
+                                log::warn!("❗ Block #{missing} was skipped by the provider, fetching it again");
+                                refetch_sender.send(missing).map_err(|_| L2SyncError::ChannelClosed("refetch"))?;
+                            }
+                        }
+                        continue;
+                    };
+                    let PreparedBlock {
+                        parent_block_hash,
+                        block_hash,
+                        block_conv,
+                        state_update,
+                        class_update,
+                        commitment_state_diff,
+                        ..
+                    } = prepared;
+                    if is_reorg(block_n, parent_block_hash)? {
+                        return Err(L2SyncError::Reorg(block_n));
+                    }
+                    if let (Some(header), Some(block_hash)) = (sync_state.header(block_n), block_hash)
+                        && header.block_hash != block_hash
+                    {
+                        // the headers are fetched again from the block
+                        sync_state.drop_headers_from(block_n);
+                        let header = header.block_hash;
+                        return Err(L2SyncError::HeaderMismatch { block_number: block_n, header, block_hash });
+                    }
+                    // the tries are updated while verifying the state root, so the block is journaled before
+                    DeoxysBackend::meta().write_apply_journal(ApplyJournal::Started(block_n))?;
+                    probe.enter(PipelineStage::Converting);
+
+                    // the conversion error, if any, is only reported for a block extending the local chain
+                    let block_conv = block_conv?;
+                    if let Some(commitment_state_diff) = commitment_state_diff {
+                        let sync_state = Arc::clone(&sync_state);
+                        let block_hash = state_update.block_hash;
+                        let fetched = block_conv.header().global_state_root;
+
+                        spawn_compute(move || {
+                            let _span = profile::span(Stage::Verify, block_n);
                             let start = std::time::Instant::now();
-                            let state_root = verify_l2(block_n, &state_update);
+                            let state_root = verify_l2_diff(&sync_state, block_n, block_hash, commitment_state_diff);
                             log::debug!("verify_l2: {:?}", std::time::Instant::now() - start);
-                            state_root
-                        };
+                            if fetched != state_root {
+                                return Err(L2SyncError::StateRootMismatch {
+                                    block_number: block_n,
+                                    computed: state_root,
+                                    fetched,
+                                });
+                            }
+                            Ok(())
+                        })
+                        .await?;
+                    }
 
-                        if verify {
-                            let (state_root, block_conv) = rayon::join(ver_l2, || convert_block(block));
-                            if (block_conv.header().global_state_root) != state_root {
-                                log::info!(
-                                    "❗ Verified state: {} doesn't match fetched state: {}",
-                                    state_root,
-                                    block_conv.header().global_state_root
-                                );
+                    let checkpoint = sync_checkpoint(block_n, &state_update);
+                    let header = block_conv.header().clone();
+
+                    probe.enter(PipelineStage::Applying);
+                    let block_sender = Arc::clone(&block_sender);
+                    // a block failing to apply is left in the apply journal, and rolled back on restart. The
+                    // block is only sealed once its state is stored, so no sealed block lacks its state
+                    let (sent, stored) = tokio::join!(
+                        async move {
+                            block_sender.send(block_conv).await.map_err(|_| L2SyncError::ChannelClosed("block"))
+                        },
+                        async {
+                            let _span = profile::span(Stage::Store, block_n);
+                            if let Some(metrics) = class_metrics() {
+                                metrics.record_stored(&class_update);
                             }
-                            block_conv
-                        } else {
-                            convert_block(block)
+                            store_block_artifacts(block_n, state_update, ClassUpdateWrapper(class_update))
+                                .await
+                                .map_err(|e| L2SyncError::Storage(block_n, e))
                         }
-                    })
-                    .await;
-
-                    (Arc::try_unwrap(state_update_1).expect("arc should not be aliased"), block_conv)
-                };
-
-                let block_sender = Arc::clone(&block_sender);
-                tokio::join!(
-                    async move {
-                        block_sender.send(block_conv).await.expect("block reciever channel is closed");
-                    },
-                    async {
-                        if store_state_update(block_n, state_update).await.is_err() {
-                            log::info!("❗ Failed to store state update for block {block_n}");
-                        };
-                    },
-                    async {
-                        if store_class_update(block_n, ClassUpdateWrapper(class_update)).await.is_err() {
-                            log::info!("❗ Failed to store class update for block {block_n}");
-                        };
-                    },
-                    async {
+                    );
+                    sent.and(stored)?;
+                    {
+                        let _span = profile::span(Stage::CreateBlock, block_n);
                         let start = std::time::Instant::now();
-                        create_block(&mut command_sink, &mut last_block_hash).await.expect("creating block");
+                        let sealed = create_block(&mut command_sink, &mut last_block_hash).await;
                         log::debug!("end create_block: {:?}", std::time::Instant::now() - start);
+                        sealed.map_err(|e| L2SyncError::Seal(block_n, e))?;
+                    }
+                    let block_hash = Felt252Wrapper::from(checkpoint.block_hash).into();
+                    DeoxysBackend::meta().write_sync_checkpoint(checkpoint)?;
+                    mc_db::pruning::block_applied(block_n);
+                    mc_db::cold_storage::block_applied(block_n);
+                    probe.applied(block_n);
+                    sync_state.record_applied(block_n);
+                    sync_state.cache_header(block_hash, header);
+                    // the pending block built on top of the previous block has been closed
+                    if crate::pending::drop_superseded_pending(&sync_state, block_hash) {
+                        log::debug!("Pending block superseded by block #{block_n}");
+                    }
+
+                    block_n += 1;
+
+                    // the database is compacted by its own scheduler, in the background
+                    if block_n % 1000 == 0
+                        && let Some(metrics) = class_metrics()
+                    {
+                        metrics.record_stats();
+                    }
+
+                    if sync_until.is_some_and(|last_block| block_n > last_block) {
+                        DeoxysBackend::flush()?;
+                        break;
                     }
-                );
-                block_n += 1;
+                }
 
-                // compact DB every 1k blocks
-                if block_n % 1000 == 0 {
-                    DeoxysBackend::compact();
+                Ok::<_, L2SyncError>(())
+            };
+            tokio::pin!(apply);
+
+            // restart the pipeline if it stalls
+            let stalled = async {
+                match stall_timeout {
+                    Some(timeout) => watchdog(&probe, &sync_state, fetch_queue, timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                applied = &mut apply => applied.map(|()| false),
+                _ = stalled => {
+                    // the apply loop stops in between blocks, unless the stall is in the block being applied
+                    stop.cancel();
+                    match tokio::time::timeout(STALLED_BLOCK_GRACE, &mut apply).await {
+                        Ok(applied) => applied.map(|()| true),
+                        Err(_) => {
+                            log::warn!("❗ Block #{} is stuck, abandoning it", probe.last_applied() + 1);
+                            Ok(true)
+                        }
+                    }
                 }
             }
-        } => {},
+        } => if stalled? {
+            return Err(L2SyncError::Stalled(probe.last_applied()));
+        },
     );
 
     log::debug!("L2 sync finished :)");
+    Ok(())
+}
+
+/// Checks a commitment recomputed from the body of a block against the one of the fetched block.
+///
+/// Blocks old enough not to carry the commitment are not checked.
+fn verify_commitment(
+    block_number: u64,
+    commitment: &'static str,
+    computed: StarkHash,
+    fetched: Option<FieldElement>,
+) -> Result<(), L2SyncError> {
+    match fetched.map(|fetched| StarkHash::from(Felt252Wrapper::from(fetched))) {
+        Some(fetched) if fetched != computed => {
+            Err(L2SyncError::CommitmentMismatch { block_number, commitment, computed, fetched })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Recovers from a block whose apply phase was interrupted, as recorded in the apply journal.
+///
+/// The partially stored state and classes of that block are always rolled back, along with the
+/// tries when state roots are verified. If the block had already been sealed, the sync will resume
+/// after it, so its state and class updates are fetched and stored again. Otherwise the block is
+/// simply applied again by the sync.
+pub async fn recover_apply_journal(
+    provider: Arc<ProviderPool>,
+    last_sealed_block: u64,
+    verify: bool,
+    sync_state: &SyncState,
+) -> Result<(), L2SyncError> {
+    let Some(ApplyJournal::Started(block_n)) = DeoxysBackend::meta().apply_journal()? else {
+        return Ok(());
+    };
+
+    log::warn!("🩹 Block #{block_n} was not fully applied, rolling it back");
+    revert_block(block_n).await.map_err(|e| L2SyncError::Storage(block_n, e))?;
+    // the block is only journaled once the previous one is checkpointed, with its tries committed
+    if verify && block_n > 0 {
+        revert_tries_to(block_n - 1).map_err(|e| L2SyncError::Storage(block_n, e))?;
+    }
+
+    if block_n <= last_sealed_block {
+        restore_block(block_n, provider, verify, sync_state).await
+    } else {
+        DeoxysBackend::meta().write_apply_journal(ApplyJournal::Applied(block_n))?;
+        Ok(())
+    }
 }
 
-/// Notifies the consensus engine that a new block should be created.
+/// Returns the first block to sync, resuming after the sync checkpoint.
+///
+/// The sync resumes after the checkpoint, unless a later `first_block` is requested. Blocks sealed
+/// after the checkpoint have not been fully applied, so their state is rolled back and stored again
+/// first. Databases synced before checkpoints were recorded resume from `first_block`.
+pub async fn resume_from_checkpoint(
+    provider: Arc<ProviderPool>,
+    first_block: u64,
+    last_sealed_block: u64,
+    verify: bool,
+    sync_state: &SyncState,
+) -> Result<u64, L2SyncError> {
+    let Some(checkpoint) = DeoxysBackend::meta().sync_checkpoint()? else {
+        return Ok(first_block);
+    };
+
+    if checkpoint.block_number < last_sealed_block {
+        let first_unchecked = checkpoint.block_number + 1;
+        log::warn!("🩹 Blocks #{first_unchecked} to #{last_sealed_block} are not checkpointed, storing them again");
+        for block_n in (first_unchecked..=last_sealed_block).rev() {
+            revert_block(block_n).await.map_err(|e| L2SyncError::Storage(block_n, e))?;
+        }
+        if verify {
+            revert_tries_to(checkpoint.block_number).map_err(|e| L2SyncError::Storage(first_unchecked, e))?;
+        }
+        for block_n in first_unchecked..=last_sealed_block {
+            restore_block(block_n, Arc::clone(&provider), verify, sync_state).await?;
+        }
+    }
+
+    let resume_block = first_block.max(checkpoint.block_number + 1);
+    log::info!("⏩ Resuming the sync at block #{resume_block}, checkpoint is block #{}", checkpoint.block_number);
+    Ok(resume_block)
+}
+
+/// Fetches and stores again the state and class updates of a sealed block, then checkpoints it.
+///
+/// When `verify` is set, the tries are updated with the block and checked against its state root.
+async fn restore_block(
+    block_n: u64,
+    provider: Arc<ProviderPool>,
+    verify: bool,
+    sync_state: &SyncState,
+) -> Result<(), L2SyncError> {
+    let (block, state_update, class_update) = fetch_block_and_updates(block_n, provider).await?;
+    if verify {
+        let computed = verify_l2(sync_state, block_n, &state_update);
+        let fetched: Option<StarkHash> = block.state_root.map(|root| Felt252Wrapper::from(root).into());
+        if let Some(fetched) = fetched.filter(|fetched| *fetched != computed) {
+            return Err(L2SyncError::StateRootMismatch { block_number: block_n, computed, fetched });
+        }
+    }
+    let checkpoint = sync_checkpoint(block_n, &state_update);
+    store_block_artifacts(block_n, state_update, ClassUpdateWrapper(class_update))
+        .await
+        .map_err(|e| L2SyncError::Storage(block_n, e))?;
+
+    DeoxysBackend::meta().write_sync_checkpoint(checkpoint)?;
+    Ok(())
+}
+
+/// Fetches `block_n`, re-fetching it when the provider answers with another block.
+///
+/// Used to fetch the blocks the provider skipped, which must not be skipped again.
+async fn fetch_block_in_sequence(
+    block_n: u64,
+    provider: Arc<ProviderPool>,
+) -> Result<(p::Block, StateUpdate, Vec<ContractClassData>), L2SyncError> {
+    const MAX_REFETCH: u32 = 3;
+    let mut attempt = 0;
+
+    loop {
+        let fetched = fetch_block_and_updates(block_n, Arc::clone(&provider)).await?;
+        let block_number = fetched.0.block_number;
+        if block_number == Some(block_n) {
+            return Ok(fetched);
+        }
+
+        attempt += 1;
+        if attempt >= MAX_REFETCH {
+            return Err(L2SyncError::OutOfSequence { expected: block_n, fetched: block_number });
+        }
+        log::warn!("❗ Fetched block {block_number:?} in place of block #{block_n}, fetching it again");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Builds the checkpoint recording that `block_n` has been applied.
+pub(crate) fn sync_checkpoint(block_n: u64, state_update: &StateUpdate) -> SyncCheckpoint {
+    SyncCheckpoint {
+        block_number: block_n,
+        block_hash: Felt252Wrapper::from(state_update.block_hash).into(),
+        global_state_root: Felt252Wrapper::from(state_update.new_root).into(),
+    }
+}
+
+/// A fetched block, prepared for the apply loop ahead of its turn.
+struct PreparedBlock {
+    block_number: Option<u64>,
+    block_hash: Option<FieldElement>,
+    parent_block_hash: FieldElement,
+    block_conv: Result<DeoxysBlock, L2SyncError>,
+    state_update: StateUpdate,
+    class_update: Vec<ContractClassData>,
+    /// Only built when state roots are verified.
+    commitment_state_diff: Option<CommitmentStateDiff>,
+}
+
+/// Converts a fetched block and checks its commitments, and builds its commitment state diff when
+/// `verify` is set.
+///
+/// None of this depends on the blocks before it, so that the blocks ahead of the one being applied
+/// are prepared in parallel.
+fn prepare_block(
+    block: p::Block,
+    state_update: StateUpdate,
+    class_update: Vec<ContractClassData>,
+    verify: bool,
+    (verify_tx_commitments, verify_event_commitments): (bool, bool),
+) -> PreparedBlock {
+    let block_n = block.block_number.unwrap_or_default();
+    let block_number = block.block_number;
+    let block_hash = block.block_hash;
+    let parent_block_hash = block.parent_block_hash;
+    let tx_commitment = block.transaction_commitment.filter(|_| verify_tx_commitments);
+    let event_commitment = block.event_commitment.filter(|_| verify_event_commitments);
+
+    let convert_block = || -> Result<DeoxysBlock, L2SyncError> {
+        let _span = profile::span(Stage::Convert, block_n);
+        let start = std::time::Instant::now();
+        let block_conv = crate::convert::convert_block_sync(block);
+        log::debug!("convert::convert_block_sync: {:?}", std::time::Instant::now() - start);
+
+        let block_conv = block_conv?;
+        let header = block_conv.header();
+        verify_commitment(block_n, "transaction", header.transaction_commitment, tx_commitment)?;
+        verify_commitment(block_n, "event", header.event_commitment, event_commitment)?;
+        Ok(block_conv)
+    };
+    let (block_conv, commitment_state_diff) =
+        rayon::join(convert_block, || verify.then(|| build_commitment_state_diff(&state_update)));
+
+    PreparedBlock {
+        block_number,
+        block_hash,
+        parent_block_hash,
+        block_conv,
+        state_update,
+        class_update,
+        commitment_state_diff,
+    }
+}
+
+/// Notifies the consensus engine that a new block should be created on top of `parent_hash`, or of
+/// the best block if it is not set.
 async fn create_block(cmds: &mut CommandSink, parent_hash: &mut Option<H256>) -> Result<(), String> {
     let (sender, receiver) = futures::channel::oneshot::channel();
 
     cmds.try_send(sc_consensus_manual_seal::rpc::EngineCommand::SealNewBlock {
         create_empty: true,
         finalize: false,
-        parent_hash: None,
+        parent_hash: *parent_hash,
         sender: Some(sender),
     })
-    .unwrap();
+    .map_err(|err| format!("failed to send seal command: {err}"))?;
 
     let create_block_info = receiver
         .await
@@ -277,25 +713,22 @@ async fn create_block(cmds: &mut CommandSink, parent_hash: &mut Option<H256>) ->
     Ok(())
 }
 
-/// Update the L2 state with the latest data
-pub fn update_l2(state_update: L2StateUpdate) {
-    *STARKNET_STATE_UPDATE.write().expect("Failed to acquire write lock on STARKNET_STATE_UPDATE") =
-        state_update.clone();
-
-    let last_l1_state_update_block =
-        ETHEREUM_STATE_UPDATE.read().expect("Failed to acquire read lock on ETHEREUM_STATE_UPDATE").block_number;
-    if state_update.block_number >= last_l1_state_update_block {
-        *SYNC_STATUS.write().expect("Failed to acquire write lock on SYNC_STATUS") = SyncStatus::SyncUnverifiedState;
-    }
-}
-
 /// Verify and update the L2 state according to the latest state update
-pub fn verify_l2(block_number: u64, state_update: &StateUpdate) -> StarkFelt {
+pub fn verify_l2(sync_state: &SyncState, block_number: u64, state_update: &StateUpdate) -> StarkFelt {
     let csd = build_commitment_state_diff(state_update);
+    verify_l2_diff(sync_state, block_number, state_update.block_hash, csd)
+}
+
+/// Verify and update the L2 state according to a commitment state diff built ahead of time
+pub fn verify_l2_diff(
+    sync_state: &SyncState,
+    block_number: u64,
+    block_hash: FieldElement,
+    csd: CommitmentStateDiff,
+) -> StarkFelt {
     let state_root = update_state_root(csd, block_number);
-    let block_hash = state_update.block_hash;
 
-    update_l2(L2StateUpdate {
+    sync_state.update_l2(L2StateUpdate {
         block_number,
         global_root: state_root.into(),
         block_hash: Felt252Wrapper::from(block_hash).into(),
@@ -303,43 +736,3 @@ pub fn verify_l2(block_number: u64, state_update: &StateUpdate) -> StarkFelt {
 
     state_root.into()
 }
-
-async fn update_starknet_data<C>(provider: &SequencerGatewayProvider, client: &C) -> Result<(), String>
-where
-    C: HeaderBackend<DBlockT>,
-{
-    let block = provider.get_block(BlockId::Pending).await.map_err(|e| format!("Failed to get pending block: {e}"))?;
-
-    let hash_best = client.info().best_hash;
-    let hash_current = block.parent_block_hash;
-    let number = provider
-        .get_block_id_by_hash(hash_current)
-        .await
-        .map_err(|e| format!("Failed to get block id by hash: {e}"))?;
-    let tmp = DHashT::from_str(&hash_current.to_string()).unwrap_or(Default::default());
-
-    if hash_best == tmp {
-        let state_update = provider
-            .get_state_update(BlockId::Pending)
-            .await
-            .map_err(|e| format!("Failed to get pending state update: {e}"))?;
-
-        *STARKNET_PENDING_BLOCK.write().expect("Failed to acquire write lock on STARKNET_PENDING_BLOCK") =
-            Some(crate::convert::block(block).await);
-
-        *STARKNET_PENDING_STATE_UPDATE.write().expect("Failed to aquire write lock on STARKNET_PENDING_STATE_UPDATE") =
-            Some(crate::convert::state_update(state_update));
-    }
-
-    *STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER
-        .write()
-        .expect("Failed to acquire write lock on STARKNET_HIGHEST_BLOCK_HASH_AND_NUMBER") = (hash_current, number);
-
-    log::debug!(
-        "update_starknet_data: latest_block_number: {}, latest_block_hash: 0x{:x}, best_hash: {}",
-        number,
-        hash_current,
-        hash_best
-    );
-    Ok(())
-}
@@ -5,13 +5,27 @@
 // use sp_runtime::traits::Block as BlockT;
 // use reqwest::Url;
 
+pub mod backfill;
+pub mod class_backfill;
 pub mod commitments;
+pub mod da;
 pub mod fetch;
+pub mod headers;
 pub mod l1;
 pub mod l2;
+pub mod metrics;
+pub mod network;
+pub mod pending;
+pub mod profile;
+pub mod progress;
+pub mod reorder;
 pub mod reorgs;
+pub mod soak;
+pub mod state;
+pub mod state_snapshot;
 pub mod types;
 pub mod utils;
+pub mod watchdog;
 
 pub use l2::SenderConfig;
 pub use mp_types::block::{DBlockT, DHashT};
@@ -23,40 +37,83 @@ type CommandSink = futures::channel::mpsc::Sender<sc_consensus_manual_seal::rpc:
 
 pub mod starknet_sync_worker {
     use std::sync::Arc;
+    use std::time::{Duration, Instant};
 
+    use mc_db::DeoxysBackend;
     use mp_block::DeoxysBlock;
     use mp_convert::state_update::ToStateUpdateCore;
+    use prometheus_endpoint::Registry;
     use reqwest::Url;
     use sp_blockchain::HeaderBackend;
     use starknet_providers::sequencer::models::BlockId;
-    use starknet_providers::SequencerGatewayProvider;
     use tokio::sync::mpsc::Sender;
+    use tokio_util::sync::CancellationToken;
 
     use self::fetch::fetchers::FetchConfig;
+    use self::fetch::provider_pool::ProviderPool;
+    use self::state::SyncState;
     use super::*;
-    use crate::l2::verify_l2;
+    use crate::l2::{verify_l2, L2SyncError};
+    use crate::metrics::{
+        ClassMetrics, ConversionMetrics, DbMetrics, GatewayMetrics, PendingDataMetrics, PoolMetrics,
+        SyncProgressMetrics,
+    };
 
-    pub async fn sync<C>(
+    /// How long to wait before restarting the sync pipeline after a retryable error.
+    const SYNC_RESTART_DELAY: Duration = Duration::from_secs(5);
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn sync<C, BE>(
         fetch_config: FetchConfig,
         block_sender: Sender<DeoxysBlock>,
         command_sink: CommandSink,
         l1_url: Url,
         client: Arc<C>,
+        backend: Arc<BE>,
         starting_block: u32,
+        prometheus_registry: Option<Registry>,
+        sync_state: Arc<SyncState>,
+        cancel: CancellationToken,
     ) where
         C: HeaderBackend<DBlockT> + 'static,
+        BE: sc_client_api::Backend<DBlockT>,
     {
-        let starting_block = starting_block + 1;
+        let last_sealed_block = u64::from(client.info().best_number);
+        let starting_block = u64::from(starting_block) + 1;
 
-        let provider = SequencerGatewayProvider::new(
-            fetch_config.gateway.clone(),
-            fetch_config.feeder_gateway.clone(),
-            fetch_config.chain_id,
-        );
-        let provider = match &fetch_config.api_key {
-            Some(api_key) => provider.with_header("X-Throttling-Bypass".to_string(), api_key.clone()),
-            None => provider,
-        };
+        if let Some(from_l1_block) = fetch_config.da_from_l1_block {
+            let fetcher =
+                da::L1DaFetcher::new(l1_url.clone(), fetch_config.l1_core_address, fetch_config.beacon_url.clone())
+                    .expect("Failed to create the L1 DA fetcher");
+            let reconstruct = async {
+                if let Err(e) = da::sync(fetcher, from_l1_block, &sync_state).await {
+                    log::error!("❗ Failed to reconstruct the state from L1: {e}");
+                }
+            };
+            tokio::join!(l1::sync(l1_url.clone()), reconstruct);
+            return;
+        }
+
+        let provider = Arc::new(ProviderPool::from_config(&fetch_config));
+
+        if let Some(path) = &fetch_config.state_snapshot {
+            state_snapshot::import_state_snapshot(path, l1_url.clone(), &sync_state)
+                .await
+                .expect("importing the state snapshot");
+        }
+        l2::recover_apply_journal(Arc::clone(&provider), starting_block - 1, fetch_config.verify, &sync_state)
+            .await
+            .expect("recovering interrupted block");
+        let starting_block = l2::resume_from_checkpoint(
+            Arc::clone(&provider),
+            starting_block,
+            last_sealed_block,
+            fetch_config.verify,
+            &sync_state,
+        )
+        .await
+        .expect("resuming from the sync checkpoint");
+        pending::restore_pending(&sync_state, starting_block - 1);
 
         if starting_block == 1 {
             let state_update = provider
@@ -64,12 +121,212 @@ pub mod starknet_sync_worker {
                 .await
                 .expect("getting state update for genesis block")
                 .to_state_update_core();
-            verify_l2(0, &state_update);
+            verify_l2(&sync_state, 0, &state_update);
         }
 
+        // behind the header chain, the full blocks are known to exist and are fetched with more concurrency
+        let fetch_concurrency = if fetch_config.headers_first {
+            fetch_config.fetch_concurrency.max(headers::BODY_FETCH_CONCURRENCY)
+        } else {
+            fetch_config.fetch_concurrency
+        };
+
+        if let Some(path) = &fetch_config.profile_sync
+            && let Err(e) = profile::init(path, fetch_concurrency)
+        {
+            log::error!("❗ Failed to start recording the sync profile to {}: {e}", path.display());
+        }
+
+        let metrics = prometheus_registry.as_ref().and_then(|registry| PendingDataMetrics::register(registry).ok());
+        let pool_metrics = prometheus_registry.as_ref().and_then(|registry| PoolMetrics::register(registry).ok());
+        let db_metrics = prometheus_registry.as_ref().and_then(|registry| DbMetrics::register(registry).ok());
+        let progress_metrics =
+            prometheus_registry.as_ref().and_then(|registry| SyncProgressMetrics::register(registry).ok());
+        // the gateway metrics are recorded by the provider pool
+        prometheus_registry.as_ref().and_then(|registry| GatewayMetrics::register(registry).ok());
+        prometheus_registry.as_ref().and_then(|registry| ClassMetrics::register(registry).ok());
+        prometheus_registry.as_ref().and_then(|registry| ConversionMetrics::register(registry).ok());
+
+        // once `cancel` is cancelled by the shutdown of the node, the sync stops after the block being
+        // applied
+        let shutdown = async {
+            cancel.cancelled().await;
+            log::info!("🛑 Stopping the sync after the block being applied");
+        };
+
+        let l2_sync = async {
+            let started_at = Instant::now();
+            let mut first_block = starting_block;
+            loop {
+                let result = l2::sync(
+                    block_sender.clone(),
+                    command_sink.clone(),
+                    Arc::clone(&provider),
+                    first_block,
+                    fetch_config.verify,
+                    fetch_config.verify_lookahead,
+                    fetch_config.verify_tx_commitments,
+                    fetch_config.verify_event_commitments,
+                    fetch_concurrency,
+                    fetch_config.buffer_size,
+                    fetch_config.sync_until,
+                    fetch_config.stall_timeout,
+                    Arc::clone(&sync_state),
+                    cancel.clone(),
+                )
+                .await;
+
+                match result {
+                    // the pipeline is restarted from the last applied block whenever the watchdog detects a stall,
+                    // once the block it abandoned half applied, if any, is rolled back
+                    Err(L2SyncError::Stalled(last_applied)) => {
+                        let last_sealed_block = u64::from(client.info().best_number);
+                        let recovered = l2::recover_apply_journal(
+                            Arc::clone(&provider),
+                            last_sealed_block,
+                            fetch_config.verify,
+                            &sync_state,
+                        )
+                        .await;
+                        if let Err(e) = recovered {
+                            log::error!("❗ Sync halted: failed to roll back the abandoned block: {e}");
+                            break;
+                        }
+                        let checkpoint = DeoxysBackend::meta().sync_checkpoint().expect("reading sync checkpoint");
+                        first_block = checkpoint.map_or(last_applied + 1, |checkpoint| checkpoint.block_number + 1);
+                    }
+                    // after a reorg, the chain is synced again from the last common ancestor
+                    Err(L2SyncError::Reorg(block_n)) => {
+                        match reorgs::lib::revert_to_common_ancestor(&provider, &*backend, &*client, block_n).await {
+                            Ok(ancestor) => {
+                                sync_state.drop_cached_headers_after(ancestor);
+                                first_block = ancestor + 1;
+                            }
+                            Err(e) => {
+                                log::error!("❗ Sync halted: failed to revert the reorged blocks: {e}");
+                                break;
+                            }
+                        }
+                    }
+                    Ok(()) => {
+                        if let Some(last_block) = fetch_config.sync_until {
+                            report_throughput(&sync_state, starting_block, last_block, started_at.elapsed());
+                        }
+                        break;
+                    }
+                    Err(L2SyncError::Cancelled) => break,
+                    Err(e) if e.is_retryable() => {
+                        log::warn!("❗ Sync pipeline failed: {e}, restarting it from the last applied block");
+                        tokio::select! {
+                            _ = cancel.cancelled() => break,
+                            _ = tokio::time::sleep(SYNC_RESTART_DELAY) => {}
+                        }
+                        let checkpoint = DeoxysBackend::meta().sync_checkpoint().expect("reading sync checkpoint");
+                        first_block = checkpoint.map_or(first_block, |checkpoint| checkpoint.block_number + 1);
+                    }
+                    Err(e) => {
+                        log::error!("❗ Sync halted: {e}");
+                        break;
+                    }
+                }
+            }
+        };
+
+        // in headers-first mode, the full blocks are backfilled behind the header chain
+        let header_sync = async {
+            if fetch_config.headers_first {
+                headers::sync_headers(&provider, &sync_state, starting_block).await;
+            }
+        };
+
+        let backfill = async {
+            if fetch_config.backfill
+                && let Err(e) = backfill::backfill(&provider, starting_block).await
+            {
+                log::error!("❗ Backfill stopped: {e}");
+            }
+        };
+
+        // the classes missing from the blocks synced by older versions are fetched behind the head sync
+        let class_backfill = async {
+            if fetch_config.backfill_classes && starting_block > 0 {
+                tokio::select! {
+                    _ = cancel.cancelled() => {}
+                    result = class_backfill::backfill_classes(&provider, starting_block - 1) => {
+                        if let Err(e) = result {
+                            log::error!("❗ Class backfill stopped: {e}");
+                        }
+                    }
+                }
+            }
+        };
+
+        // the pending block is tracked independently of the pipeline, which is restarted on failures
+        let pending_tracker = async {
+            tokio::select! {
+                _ = cancel.cancelled() => {}
+                _ = pending::track_pending(
+                    Arc::clone(&provider),
+                    Arc::clone(&sync_state),
+                    Arc::clone(&client),
+                    fetch_config.pending_poll_interval,
+                    fetch_config.pending,
+                    metrics,
+                ) => {}
+            }
+        };
+
+        let soak = async {
+            if let Some(config) = &fetch_config.soak {
+                tokio::select! {
+                    _ = cancel.cancelled() => {}
+                    _ = soak::soak(config) => {}
+                }
+            }
+        };
+
+        let pool_probe = async {
+            if let Some(pool_metrics) = pool_metrics {
+                pool_metrics.probe().await;
+            }
+        };
+
+        let db_probe = async {
+            if let Some(db_metrics) = &db_metrics {
+                db_metrics.probe().await;
+            }
+        };
+
+        let progress_probe = async {
+            if let Some(progress_metrics) = &progress_metrics {
+                progress_metrics.probe(&sync_state).await;
+            }
+        };
+
         let _ = tokio::join!(
             l1::sync(l1_url.clone()),
-            l2::sync(block_sender, command_sink, provider, starting_block.into(), fetch_config.verify, client)
+            header_sync,
+            l2_sync,
+            pending_tracker,
+            backfill,
+            class_backfill,
+            pool_probe,
+            db_probe,
+            progress_probe,
+            soak,
+            shutdown
+        );
+    }
+
+    /// Logs the throughput of a sync bounded by `--sync-until`, from `starting_block` on.
+    fn report_throughput(sync_state: &SyncState, starting_block: u64, last_block: u64, elapsed: Duration) {
+        let current_block = sync_state.sync_progress().current_block;
+        let synced = (current_block + 1).saturating_sub(starting_block);
+        let blocks_per_second = if elapsed.is_zero() { 0.0 } else { synced as f64 / elapsed.as_secs_f64() };
+        log::info!(
+            "🏁 Synced up to block #{last_block}: {synced} blocks in {:.1}s ({blocks_per_second:.2} blocks/s), the \
+             node keeps serving the synced state",
+            elapsed.as_secs_f64()
         );
     }
 }
@@ -0,0 +1,596 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use blockifier::execution::contract_class::ContractClass as ContractClassBlockifier;
+use mc_db::storage_handler::primitives::contract_class::ContractClassData;
+use mc_db::storage_handler::{self, ClassDataStats};
+use mc_db::{DbStats, DeoxysBackend};
+use parity_scale_codec::Encode;
+use prometheus_endpoint::prometheus::{
+    Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts,
+};
+use prometheus_endpoint::{register, PrometheusError, Registry};
+
+use crate::progress::SyncProgress;
+use crate::state::SyncState;
+
+#[derive(Clone, Debug)]
+pub struct PendingDataMetrics {
+    pub gateway_timeouts: Counter,
+    pub rate_limits: Counter,
+    pub decode_errors: Counter,
+    pub hash_mismatches: Counter,
+    pub other_errors: Counter,
+}
+
+impl PendingDataMetrics {
+    pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            gateway_timeouts: register(
+                Counter::new("deoxys_pending_gateway_timeouts", "Counter for pending data gateway timeouts")?,
+                registry,
+            )?,
+            rate_limits: register(
+                Counter::new("deoxys_pending_rate_limits", "Counter for pending data gateway rate limits")?,
+                registry,
+            )?,
+            decode_errors: register(
+                Counter::new("deoxys_pending_decode_errors", "Counter for pending data decode errors")?,
+                registry,
+            )?,
+            hash_mismatches: register(
+                Counter::new(
+                    "deoxys_pending_hash_mismatches",
+                    "Counter for pending blocks not built on top of the local best block",
+                )?,
+                registry,
+            )?,
+            other_errors: register(
+                Counter::new("deoxys_pending_other_errors", "Counter for other pending data errors")?,
+                registry,
+            )?,
+        })
+    }
+}
+
+static GATEWAY_METRICS: OnceLock<GatewayMetrics> = OnceLock::new();
+
+/// Returns the gateway metrics, if they were registered.
+pub fn gateway_metrics() -> Option<&'static GatewayMetrics> {
+    GATEWAY_METRICS.get()
+}
+
+/// Requests sent to each feeder gateway and their rate limiting, labelled by its url.
+///
+/// The error rate of a gateway is `rate(deoxys_gateway_request_errors) /
+/// rate(deoxys_gateway_requests)`.
+#[derive(Clone, Debug)]
+pub struct GatewayMetrics {
+    pub request_rate: GaugeVec,
+    pub rate_limit: GaugeVec,
+    pub rate_limited: CounterVec,
+    pub requests: CounterVec,
+    pub request_errors: CounterVec,
+    pub request_duration: HistogramVec,
+    /// The bytes of the blocks, state updates and classes downloaded by the sync.
+    pub bytes_downloaded: CounterVec,
+}
+
+impl GatewayMetrics {
+    /// Registers the gateway metrics, which are then recorded globally.
+    pub fn register(registry: &Registry) -> Result<&'static Self, PrometheusError> {
+        let metrics = Self {
+            request_rate: register(
+                GaugeVec::new(
+                    Opts::new("deoxys_gateway_request_rate", "Requests per second sent to the feeder gateway"),
+                    &["feeder_gateway"],
+                )?,
+                registry,
+            )?,
+            rate_limit: register(
+                GaugeVec::new(
+                    Opts::new(
+                        "deoxys_gateway_rate_limit",
+                        "Requests per second the feeder gateway is limited to, 0 when it is not limited",
+                    ),
+                    &["feeder_gateway"],
+                )?,
+                registry,
+            )?,
+            rate_limited: register(
+                CounterVec::new(
+                    Opts::new("deoxys_gateway_rate_limited", "Counter for rate limited feeder gateway responses"),
+                    &["feeder_gateway"],
+                )?,
+                registry,
+            )?,
+            requests: register(
+                CounterVec::new(
+                    Opts::new("deoxys_gateway_requests", "Counter for requests sent to the feeder gateway"),
+                    &["feeder_gateway"],
+                )?,
+                registry,
+            )?,
+            request_errors: register(
+                CounterVec::new(
+                    Opts::new("deoxys_gateway_request_errors", "Counter for failed feeder gateway requests"),
+                    &["feeder_gateway"],
+                )?,
+                registry,
+            )?,
+            request_duration: register(
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "deoxys_gateway_request_duration_seconds",
+                        "Time taken by the feeder gateway to answer a request",
+                    ),
+                    &["feeder_gateway"],
+                )?,
+                registry,
+            )?,
+            bytes_downloaded: register(
+                CounterVec::new(
+                    Opts::new(
+                        "deoxys_gateway_bytes_downloaded",
+                        "Bytes of responses downloaded from the feeder gateway",
+                    ),
+                    &["feeder_gateway"],
+                )?,
+                registry,
+            )?,
+        };
+
+        Ok(GATEWAY_METRICS.get_or_init(|| metrics))
+    }
+
+    /// Records a request answered by a gateway, or which failed.
+    pub fn record_request(&self, feeder_gateway: &str, duration: Duration, failed: bool) {
+        self.requests.with_label_values(&[feeder_gateway]).inc();
+        if failed {
+            self.request_errors.with_label_values(&[feeder_gateway]).inc();
+        }
+        self.request_duration.with_label_values(&[feeder_gateway]).observe(duration.as_secs_f64());
+    }
+
+    /// Records the request rates of a gateway.
+    pub fn record_rates(&self, feeder_gateway: &str, request_rate: f64, rate_limit: Option<f64>) {
+        self.request_rate.with_label_values(&[feeder_gateway]).set(request_rate);
+        self.rate_limit.with_label_values(&[feeder_gateway]).set(rate_limit.unwrap_or_default());
+    }
+}
+
+static CLASS_METRICS: OnceLock<ClassMetrics> = OnceLock::new();
+
+/// Returns the class metrics, if they were registered.
+pub fn class_metrics() -> Option<&'static ClassMetrics> {
+    CLASS_METRICS.get()
+}
+
+/// Classes stored by the node, to attribute the growth of the database.
+///
+/// The stored classes are labelled by kind, `sierra` or `legacy`.
+#[derive(Clone, Debug)]
+pub struct ClassMetrics {
+    pub classes_stored: IntCounterVec,
+    /// The size of the stored classes, before they are compressed by the database.
+    pub class_raw_bytes: IntCounterVec,
+    pub classes: IntGauge,
+    pub class_disk_bytes: IntGauge,
+    pub compile_backlog: IntGauge,
+}
+
+impl ClassMetrics {
+    /// Registers the class metrics, which are then recorded globally.
+    pub fn register(registry: &Registry) -> Result<&'static Self, PrometheusError> {
+        let metrics = Self {
+            classes_stored: register(
+                IntCounterVec::new(
+                    Opts::new("deoxys_classes_stored", "Counter for classes stored since the node started"),
+                    &["kind"],
+                )?,
+                registry,
+            )?,
+            class_raw_bytes: register(
+                IntCounterVec::new(
+                    Opts::new("deoxys_class_raw_bytes", "Bytes of classes stored since the node started, uncompressed"),
+                    &["kind"],
+                )?,
+                registry,
+            )?,
+            classes: register(
+                IntGauge::new("deoxys_classes", "Estimated number of classes in the database")?,
+                registry,
+            )?,
+            class_disk_bytes: register(
+                IntGauge::new("deoxys_class_disk_bytes", "Estimated size of the classes on disk, compressed")?,
+                registry,
+            )?,
+            compile_backlog: register(
+                IntGauge::new("deoxys_class_compile_backlog", "Number of classes being downloaded and compiled")?,
+                registry,
+            )?,
+        };
+        metrics.record_stats();
+
+        Ok(CLASS_METRICS.get_or_init(|| metrics))
+    }
+
+    /// Records classes about to be stored.
+    pub fn record_stored(&self, classes: &[ContractClassData]) {
+        for class in classes {
+            let kind = match class.contract_class.contract {
+                ContractClassBlockifier::V0(_) => "legacy",
+                ContractClassBlockifier::V1(_) => "sierra",
+            };
+            self.classes_stored.with_label_values(&[kind]).inc();
+            self.class_raw_bytes.with_label_values(&[kind]).inc_by(class.contract_class.encoded_size() as u64);
+        }
+    }
+
+    /// Records the estimates of the database about the stored classes.
+    pub fn record_stats(&self) {
+        match storage_handler::contract_class_data().stats() {
+            Ok(ClassDataStats { classes, disk_bytes }) => {
+                self.classes.set(classes as i64);
+                self.class_disk_bytes.set(disk_bytes as i64);
+            }
+            Err(e) => log::debug!("Failed to read the class statistics: {e}"),
+        }
+    }
+
+    /// Records a class entering the compile backlog, until the returned guard is dropped.
+    pub fn compile_queued(&'static self) -> CompileBacklogGuard {
+        self.compile_backlog.inc();
+        CompileBacklogGuard(&self.compile_backlog)
+    }
+}
+
+/// A class in the compile backlog, which leaves it when dropped.
+pub struct CompileBacklogGuard(&'static IntGauge);
+
+impl Drop for CompileBacklogGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+static CONVERSION_METRICS: OnceLock<ConversionMetrics> = OnceLock::new();
+
+/// Returns the conversion metrics, if they were registered.
+pub fn conversion_metrics() -> Option<&'static ConversionMetrics> {
+    CONVERSION_METRICS.get()
+}
+
+/// Blocks served by the gateway which could not be converted, labelled by the offending field.
+#[derive(Clone, Debug)]
+pub struct ConversionMetrics {
+    pub conversion_errors: IntCounterVec,
+}
+
+impl ConversionMetrics {
+    /// Registers the conversion metrics, which are then recorded globally.
+    pub fn register(registry: &Registry) -> Result<&'static Self, PrometheusError> {
+        let metrics = Self {
+            conversion_errors: register(
+                IntCounterVec::new(
+                    Opts::new("deoxys_conversion_errors", "Counter for blocks which failed to convert"),
+                    &["field"],
+                )?,
+                registry,
+            )?,
+        };
+
+        Ok(CONVERSION_METRICS.get_or_init(|| metrics))
+    }
+}
+
+/// How often the statistics of the database are recorded.
+const DB_STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The statistics of the columns of the database, labelled by column, to spot storage blowups
+/// before the disk fills up.
+#[derive(Clone, Debug)]
+pub struct DbMetrics {
+    pub column_keys: IntGaugeVec,
+    pub column_disk_bytes: IntGaugeVec,
+    pub column_mem_table_bytes: IntGaugeVec,
+    pub column_l0_files: IntGaugeVec,
+    pub column_pending_compaction_bytes: IntGaugeVec,
+    /// Only recorded when the statistics of the database are enabled.
+    pub block_cache_hit_rate: Gauge,
+}
+
+impl DbMetrics {
+    pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        let column_gauge = |name: &str, help: &str| -> Result<IntGaugeVec, PrometheusError> {
+            register(IntGaugeVec::new(Opts::new(name, help), &["column"])?, registry)
+        };
+        Ok(Self {
+            column_keys: column_gauge("deoxys_db_column_keys", "Estimated number of keys of each column")?,
+            column_disk_bytes: column_gauge("deoxys_db_column_disk_bytes", "Size of each column on disk, compressed")?,
+            column_mem_table_bytes: column_gauge(
+                "deoxys_db_column_mem_table_bytes",
+                "Size of the writes to each column not flushed to disk yet",
+            )?,
+            column_l0_files: column_gauge("deoxys_db_column_l0_files", "Number of files in level 0 of each column")?,
+            column_pending_compaction_bytes: column_gauge(
+                "deoxys_db_column_pending_compaction_bytes",
+                "Estimated bytes to compact to bring the levels of each column back to their target size",
+            )?,
+            block_cache_hit_rate: register(
+                Gauge::new("deoxys_db_block_cache_hit_rate", "Share of the blocks read found in the block cache")?,
+                registry,
+            )?,
+        })
+    }
+
+    pub fn record(&self, stats: &DbStats) {
+        for column in &stats.columns {
+            let labels = [column.column.as_str()];
+            self.column_keys.with_label_values(&labels).set(column.estimated_keys as i64);
+            self.column_disk_bytes.with_label_values(&labels).set(column.disk_bytes as i64);
+            self.column_mem_table_bytes.with_label_values(&labels).set(column.mem_table_bytes as i64);
+            self.column_l0_files.with_label_values(&labels).set(column.l0_files as i64);
+            self.column_pending_compaction_bytes.with_label_values(&labels).set(column.pending_compaction_bytes as i64);
+        }
+        if let Some(hit_rate) = stats.block_cache_hit_rate {
+            self.block_cache_hit_rate.set(hit_rate);
+        }
+    }
+
+    /// Periodically records the statistics of the database.
+    pub async fn probe(&self) {
+        let mut interval = tokio::time::interval(DB_STATS_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.record(&DeoxysBackend::db_stats());
+        }
+    }
+}
+
+/// How often the progress of the sync is recorded.
+const SYNC_PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The progress of the sync, as served by `deoxys_getSyncProgress`.
+#[derive(Clone, Debug)]
+pub struct SyncProgressMetrics {
+    pub current_block: IntGauge,
+    pub highest_block: IntGauge,
+    pub blocks_per_second: Gauge,
+    /// NaN while the time left is unknown.
+    pub eta_seconds: Gauge,
+}
+
+impl SyncProgressMetrics {
+    pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            current_block: register(
+                IntGauge::new("deoxys_sync_current_block", "Last block applied by the sync")?,
+                registry,
+            )?,
+            highest_block: register(
+                IntGauge::new("deoxys_sync_highest_block", "Highest block known to the feeder gateway")?,
+                registry,
+            )?,
+            blocks_per_second: register(
+                Gauge::new("deoxys_sync_blocks_per_second", "Blocks applied per second over the last minute")?,
+                registry,
+            )?,
+            eta_seconds: register(
+                Gauge::new("deoxys_sync_eta_seconds", "Estimated seconds left to reach the highest block")?,
+                registry,
+            )?,
+        })
+    }
+
+    pub fn record(&self, progress: &SyncProgress) {
+        self.current_block.set(progress.current_block as i64);
+        self.highest_block.set(progress.highest_block as i64);
+        self.blocks_per_second.set(progress.blocks_per_second);
+        self.eta_seconds.set(progress.eta_seconds.map_or(f64::NAN, |eta| eta as f64));
+    }
+
+    /// Periodically records the progress of the sync.
+    pub async fn probe(&self, sync_state: &SyncState) {
+        let mut interval = tokio::time::interval(SYNC_PROGRESS_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.record(&sync_state.sync_progress());
+        }
+    }
+}
+
+/// How often the busy ratios of the pools are sampled and the tokio scheduling latency is probed.
+const POOL_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+static POOL_METRICS: OnceLock<PoolMetrics> = OnceLock::new();
+
+/// Returns the pool metrics, if they were registered.
+pub fn pool_metrics() -> Option<&'static PoolMetrics> {
+    POOL_METRICS.get()
+}
+
+/// Saturation metrics of the rayon compute pool and of the tokio runtime.
+///
+/// The compute pool is instrumented by `spawn_compute`: the work rayon steals from its tasks is
+/// accounted to them. The queue depths and busy ratio of the tokio runtime are only exposed by
+/// tokio when the node is built with `RUSTFLAGS="--cfg tokio_unstable"`, otherwise only its
+/// scheduling latency is measured.
+#[derive(Clone, Debug)]
+pub struct PoolMetrics {
+    pub compute_threads: IntGauge,
+    pub compute_queue_depth: IntGauge,
+    pub compute_running: IntGauge,
+    pub compute_queue_latency: Histogram,
+    pub compute_task_duration: Histogram,
+    pub compute_busy_seconds: Counter,
+    pub compute_busy_ratio: Gauge,
+    pub tokio_scheduling_latency: Histogram,
+    #[cfg(tokio_unstable)]
+    pub tokio_runtime: TokioRuntimeMetrics,
+}
+
+impl PoolMetrics {
+    /// Registers the pool metrics, which are then recorded globally.
+    pub fn register(registry: &Registry) -> Result<&'static Self, PrometheusError> {
+        let metrics = Self {
+            compute_threads: register(
+                IntGauge::new("deoxys_compute_threads", "Number of threads of the compute pool")?,
+                registry,
+            )?,
+            compute_queue_depth: register(
+                IntGauge::new("deoxys_compute_queue_depth", "Number of compute tasks waiting for a thread")?,
+                registry,
+            )?,
+            compute_running: register(
+                IntGauge::new("deoxys_compute_running", "Number of compute tasks currently running")?,
+                registry,
+            )?,
+            compute_queue_latency: register(
+                Histogram::with_opts(HistogramOpts::new(
+                    "deoxys_compute_queue_latency_seconds",
+                    "Time spent by compute tasks waiting for a thread",
+                ))?,
+                registry,
+            )?,
+            compute_task_duration: register(
+                Histogram::with_opts(HistogramOpts::new(
+                    "deoxys_compute_task_duration_seconds",
+                    "Time spent running compute tasks",
+                ))?,
+                registry,
+            )?,
+            compute_busy_seconds: register(
+                Counter::new("deoxys_compute_busy_seconds", "Total time spent by the compute pool running tasks")?,
+                registry,
+            )?,
+            compute_busy_ratio: register(
+                Gauge::new("deoxys_compute_busy_ratio", "Share of the time of the compute pool spent running tasks")?,
+                registry,
+            )?,
+            tokio_scheduling_latency: register(
+                Histogram::with_opts(HistogramOpts::new(
+                    "deoxys_tokio_scheduling_latency_seconds",
+                    "Time between the spawn of a task on the tokio runtime and its first poll",
+                ))?,
+                registry,
+            )?,
+            #[cfg(tokio_unstable)]
+            tokio_runtime: TokioRuntimeMetrics::register(registry)?,
+        };
+        metrics.compute_threads.set(rayon::current_num_threads() as i64);
+
+        Ok(POOL_METRICS.get_or_init(|| metrics))
+    }
+
+    /// Records a task entering the queue of the compute pool.
+    pub fn compute_queued(&self) -> Instant {
+        self.compute_queue_depth.inc();
+        Instant::now()
+    }
+
+    /// Records a task leaving the queue of the compute pool to start running.
+    pub fn compute_started(&self, queued_at: Instant) -> Instant {
+        self.compute_queue_depth.dec();
+        self.compute_running.inc();
+        self.compute_queue_latency.observe(queued_at.elapsed().as_secs_f64());
+        Instant::now()
+    }
+
+    /// Records a task of the compute pool completing.
+    pub fn compute_finished(&self, started_at: Instant) {
+        let busy = started_at.elapsed().as_secs_f64();
+        self.compute_running.dec();
+        self.compute_task_duration.observe(busy);
+        self.compute_busy_seconds.inc_by(busy);
+    }
+
+    /// Periodically samples the busy ratios of the pools, and measures how long a task spawned on
+    /// the tokio runtime waits before it is polled, which grows as the runtime saturates.
+    pub async fn probe(&self) {
+        let mut interval = tokio::time::interval(POOL_PROBE_INTERVAL);
+        let mut sampled_at = Instant::now();
+        let mut compute_busy = self.compute_busy_seconds.get();
+        #[cfg(tokio_unstable)]
+        let mut tokio_busy = Duration::ZERO;
+        loop {
+            interval.tick().await;
+
+            let elapsed = sampled_at.elapsed().as_secs_f64();
+            sampled_at = Instant::now();
+            let busy = self.compute_busy_seconds.get();
+            self.compute_busy_ratio.set(busy_ratio(busy - compute_busy, elapsed, rayon::current_num_threads()));
+            compute_busy = busy;
+            #[cfg(tokio_unstable)]
+            {
+                tokio_busy = self.tokio_runtime.sample(tokio_busy, elapsed);
+            }
+
+            let spawned_at = Instant::now();
+            if let Ok(latency) = tokio::spawn(async move { spawned_at.elapsed() }).await {
+                self.tokio_scheduling_latency.observe(latency.as_secs_f64());
+            }
+        }
+    }
+}
+
+/// The share of the time of `threads` threads spent busy over `elapsed` seconds.
+fn busy_ratio(busy: f64, elapsed: f64, threads: usize) -> f64 {
+    if elapsed <= 0.0 || threads == 0 { 0.0 } else { (busy / (elapsed * threads as f64)).min(1.0) }
+}
+
+/// Queue depths and busy ratio of the tokio runtime, as instrumented by tokio itself.
+#[cfg(tokio_unstable)]
+#[derive(Clone, Debug)]
+pub struct TokioRuntimeMetrics {
+    pub workers: IntGauge,
+    pub queue_depth: IntGauge,
+    pub blocking_queue_depth: IntGauge,
+    pub alive_tasks: IntGauge,
+    pub busy_ratio: Gauge,
+}
+
+#[cfg(tokio_unstable)]
+impl TokioRuntimeMetrics {
+    fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            workers: register(
+                IntGauge::new("deoxys_tokio_workers", "Number of worker threads of the tokio runtime")?,
+                registry,
+            )?,
+            queue_depth: register(
+                IntGauge::new("deoxys_tokio_queue_depth", "Number of tasks waiting in the tokio worker queues")?,
+                registry,
+            )?,
+            blocking_queue_depth: register(
+                IntGauge::new("deoxys_tokio_blocking_queue_depth", "Number of blocking tasks waiting for a thread")?,
+                registry,
+            )?,
+            alive_tasks: register(
+                IntGauge::new("deoxys_tokio_alive_tasks", "Number of tasks alive on the tokio runtime")?,
+                registry,
+            )?,
+            busy_ratio: register(
+                Gauge::new("deoxys_tokio_busy_ratio", "Share of the time of the tokio workers spent polling tasks")?,
+                registry,
+            )?,
+        })
+    }
+
+    /// Samples the metrics of the current runtime, given the total busy time of its workers at the
+    /// previous sample `elapsed` seconds ago, and returns their current total busy time.
+    fn sample(&self, previous_busy: Duration, elapsed: f64) -> Duration {
+        let metrics = tokio::runtime::Handle::current().metrics();
+        let workers = metrics.num_workers();
+        let busy: Duration = (0..workers).map(|worker| metrics.worker_total_busy_duration(worker)).sum();
+        let local_queue_depth: usize = (0..workers).map(|worker| metrics.worker_local_queue_depth(worker)).sum();
+
+        self.workers.set(workers as i64);
+        self.queue_depth.set((metrics.injection_queue_depth() + local_queue_depth) as i64);
+        self.blocking_queue_depth.set(metrics.blocking_queue_depth() as i64);
+        self.alive_tasks.set(metrics.active_tasks_count() as i64);
+        self.busy_ratio.set(busy_ratio(busy.saturating_sub(previous_busy).as_secs_f64(), elapsed, workers));
+        busy
+    }
+}
@@ -0,0 +1,173 @@
+//! Network profiles.
+//!
+//! A profile gathers everything which differs between the Starknet networks the node can sync:
+//! the gateway urls, the chain id, the address of the core contract on L1, and the blocks at which
+//! the way blocks are committed to changed. Mainnet, Sepolia and integration are built in, and any
+//! other network can be described in a toml file passed as `custom:<path>`.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+use sp_core::H160;
+use starknet_ff::FieldElement;
+use url::Url;
+
+use crate::fetch::fetchers::FetchConfig;
+use crate::pending::DEFAULT_PENDING_POLL_INTERVAL;
+use crate::utils::constant::starknet_core_address;
+
+/// The blocks from which the protocol changes affecting block verification apply.
+///
+/// Networks started after those changes use the default schedule, where they apply from genesis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct VersionSchedule {
+    /// First block whose transaction commitment includes the signature of every transaction, and
+    /// not only of invoke transactions.
+    pub tx_commitment_signatures: u64,
+}
+
+/// The parameters of a Starknet network.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct NetworkProfile {
+    pub name: String,
+    pub gateway: Url,
+    pub feeder_gateway: Url,
+    /// The chain id, written as a short string such as `SN_MAIN` in profile files.
+    #[serde(deserialize_with = "deserialize_chain_id")]
+    pub chain_id: FieldElement,
+    pub l1_core_address: H160,
+    #[serde(default)]
+    pub versions: VersionSchedule,
+}
+
+fn deserialize_chain_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FieldElement, D::Error> {
+    let chain_id = String::deserialize(deserializer)?;
+    FieldElement::from_byte_slice_be(chain_id.as_bytes()).map_err(serde::de::Error::custom)
+}
+
+impl NetworkProfile {
+    fn builtin(name: &str, uri: &str, chain_id: &[u8], l1_core_address: &str, versions: VersionSchedule) -> Self {
+        Self {
+            name: name.to_string(),
+            gateway: format!("{uri}/gateway").parse().unwrap(),
+            feeder_gateway: format!("{uri}/feeder_gateway").parse().unwrap(),
+            chain_id: FieldElement::from_byte_slice_be(chain_id).unwrap(),
+            l1_core_address: l1_core_address.parse().unwrap(),
+            versions,
+        }
+    }
+
+    pub fn mainnet() -> Self {
+        Self::builtin(
+            "mainnet",
+            "https://alpha-mainnet.starknet.io",
+            b"SN_MAIN",
+            starknet_core_address::MAINNET,
+            VersionSchedule { tx_commitment_signatures: 61394 },
+        )
+    }
+
+    pub fn sepolia() -> Self {
+        Self::builtin(
+            "sepolia",
+            "https://alpha-sepolia.starknet.io",
+            b"SN_SEPOLIA",
+            starknet_core_address::SEPOLIA_TESTNET,
+            VersionSchedule::default(),
+        )
+    }
+
+    pub fn integration() -> Self {
+        Self::builtin(
+            "integration",
+            "https://external.integration.starknet.io",
+            b"SN_INTE",
+            starknet_core_address::SEPOLIA_INTEGRATION,
+            VersionSchedule::default(),
+        )
+    }
+
+    /// Reads a profile from a toml file.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        toml::from_str(&content).map_err(|e| format!("invalid network profile {}: {e}", path.display()))
+    }
+
+    /// The default configuration of the sync when following this network.
+    pub fn fetch_config(&self) -> FetchConfig {
+        FetchConfig {
+            gateway: self.gateway.clone(),
+            feeder_gateway: self.feeder_gateway.clone(),
+            fallback_feeder_gateways: Vec::new(),
+            chain_id: self.chain_id,
+            workers: 5,
+            sound: false,
+            l1_core_address: self.l1_core_address,
+            verify: true,
+            verify_lookahead: 16,
+            verify_tx_commitments: false,
+            verify_event_commitments: false,
+            fetch_concurrency: 10,
+            buffer_size: 10,
+            api_key: None,
+            sync_until: None,
+            stall_timeout: None,
+            da_from_l1_block: None,
+            beacon_url: None,
+            pending: true,
+            pending_poll_interval: DEFAULT_PENDING_POLL_INTERVAL,
+            headers_first: false,
+            backfill: false,
+            backfill_classes: false,
+            versions: self.versions,
+            profile_sync: None,
+            state_snapshot: None,
+            soak: None,
+            block_archive: None,
+            unverified_classes: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for NetworkProfile {
+    type Err = String;
+
+    /// Parses `mainnet`, `sepolia`, `integration` or `custom:<path to a toml profile>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "main" => Ok(Self::mainnet()),
+            "sepolia" | "test" => Ok(Self::sepolia()),
+            "integration" => Ok(Self::integration()),
+            s => match s.strip_prefix("custom:") {
+                Some(path) => Self::from_file(Path::new(path)),
+                None => Err(format!("unknown network {s:?}, expected mainnet, sepolia, integration or custom:<path>")),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        let profile: NetworkProfile = toml::from_str(
+            r#"
+            name = "devnet"
+            gateway = "http://localhost:5050/gateway"
+            feeder_gateway = "http://localhost:5050/feeder_gateway"
+            chain_id = "SN_DEVNET"
+            l1_core_address = "0x0000000000000000000000000000000000000001"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(profile.chain_id, FieldElement::from_byte_slice_be(b"SN_DEVNET").unwrap());
+        assert_eq!(profile.versions, VersionSchedule::default());
+        assert_eq!("main".parse::<NetworkProfile>().unwrap(), NetworkProfile::mainnet());
+        assert!("goerli".parse::<NetworkProfile>().is_err());
+    }
+}
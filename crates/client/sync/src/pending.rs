@@ -0,0 +1,367 @@
+//! Tracks the tip of the chain and the pending block built on top of it.
+//!
+//! The tracker polls the head of the feeder gateway to report the highest block of the chain. Once
+//! the node has caught up with the tip, it also downloads the pending block, which is broadcast to
+//! the rpc layer through the [`SyncState`]. A pending block is dropped as soon as the block it was
+//! built on top of is no longer the tip, as it has been superseded by a closed block: either when
+//! the tip is polled, or when the sync applies the next block, whichever comes first.
+//!
+//! The last pending block is also stored in the database, so that it is served again right after a
+//! restart rather than once the tip has been polled.
+use std::sync::Arc;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use mc_db::{storage_handler, DeoxysBackend};
+use mp_block::DeoxysBlock;
+use mp_types::block::DBlockT;
+use serde::Deserialize;
+use sp_blockchain::HeaderBackend;
+use starknet_core::types::PendingStateUpdate;
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models::BlockId;
+use starknet_providers::sequencer::GatewayClientError;
+use starknet_providers::ProviderError;
+use thiserror::Error;
+use tokio::sync::watch;
+
+use crate::convert::ConversionError;
+use crate::fetch::provider_pool::{gateway_client_error, is_rate_limit_status, PooledProvider, ProviderPool};
+use crate::metrics::PendingDataMetrics;
+use crate::state::SyncState;
+
+/// How often the tip and the pending block are polled by default, when the gateway is healthy.
+pub const DEFAULT_PENDING_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The pending block, along with the state update it makes on top of the tip.
+#[derive(Clone, Debug)]
+pub struct PendingBlock {
+    /// The hash of the closed block the pending block is built on top of.
+    pub parent_hash: FieldElement,
+    pub block: DeoxysBlock,
+    pub state_update: PendingStateUpdate,
+}
+
+/// A subscription to the pending block, which sees every new pending block as soon as it is
+/// downloaded.
+pub type PendingSubscription = watch::Receiver<Option<Arc<PendingBlock>>>;
+
+/// Errors raised while updating the highest block and the pending data.
+#[derive(Error, Debug)]
+pub enum PendingDataError {
+    #[error("gateway timed out: {0}")]
+    GatewayTimeout(String),
+    #[error("gateway rate limit reached")]
+    RateLimited,
+    #[error("failed to decode gateway response: {0}")]
+    Decode(String),
+    #[error("provider error: {0}")]
+    Provider(ProviderError),
+    #[error("http error: {0}")]
+    Http(reqwest::Error),
+    #[error("failed to convert {0}")]
+    Conversion(#[from] ConversionError),
+}
+
+impl From<ProviderError> for PendingDataError {
+    fn from(err: ProviderError) -> Self {
+        if matches!(err, ProviderError::RateLimited) {
+            return PendingDataError::RateLimited;
+        }
+        let classified = match gateway_client_error(&err) {
+            Some(GatewayClientError::Network(inner)) if inner.is_timeout() => {
+                Some(PendingDataError::GatewayTimeout(inner.to_string()))
+            }
+            Some(GatewayClientError::Network(inner)) if is_rate_limit_status(inner.status()) => {
+                Some(PendingDataError::RateLimited)
+            }
+            Some(GatewayClientError::Serde(inner)) => Some(PendingDataError::Decode(inner.to_string())),
+            _ => None,
+        };
+        classified.unwrap_or(PendingDataError::Provider(err))
+    }
+}
+
+impl From<reqwest::Error> for PendingDataError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            PendingDataError::GatewayTimeout(err.to_string())
+        } else if err.is_decode() {
+            PendingDataError::Decode(err.to_string())
+        } else if is_rate_limit_status(err.status()) {
+            PendingDataError::RateLimited
+        } else {
+            PendingDataError::Http(err)
+        }
+    }
+}
+
+impl PendingDataError {
+    fn record(&self, metrics: &PendingDataMetrics) {
+        match self {
+            PendingDataError::GatewayTimeout(_) => metrics.gateway_timeouts.inc(),
+            PendingDataError::RateLimited => metrics.rate_limits.inc(),
+            PendingDataError::Decode(_) => metrics.decode_errors.inc(),
+            PendingDataError::Provider(_) | PendingDataError::Http(_) | PendingDataError::Conversion(_) => {
+                metrics.other_errors.inc()
+            }
+        }
+    }
+}
+
+/// Serves the pending block stored before the node was stopped, if it was built on top of
+/// `last_block`, the last block of the node, until the tip is polled again.
+pub fn restore_pending(sync_state: &SyncState, last_block: u64) {
+    let (parent_hash, block, state_update) = match DeoxysBackend::pending().get() {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("❗ Failed to read the stored pending block: {e}");
+            return;
+        }
+    };
+
+    let last_block_hash = storage_handler::block_hash().get(last_block).ok().flatten().map(|hash| hash.0);
+    if last_block_hash != Some(parent_hash) {
+        log::debug!("Stored pending block is not built on top of block #{last_block}, dropping it");
+        if let Err(e) = DeoxysBackend::pending().clear() {
+            log::warn!("❗ Failed to remove the stored pending block: {e}");
+        }
+        return;
+    }
+    sync_state.set_pending(PendingBlock { parent_hash, block, state_update });
+}
+
+/// Drops the pending block, along with its stored copy, if it was not built on top of `tip_hash`,
+/// the new tip of the chain, so that the subscribers stop serving it right away. Returns whether it
+/// was dropped.
+pub(crate) fn drop_superseded_pending(sync_state: &SyncState, tip_hash: FieldElement) -> bool {
+    if !sync_state.drop_superseded_pending(tip_hash) {
+        return false;
+    }
+    if let Err(e) = DeoxysBackend::pending().clear() {
+        log::warn!("❗ Failed to remove the stored pending block: {e}");
+    }
+    true
+}
+
+/// Polls the tip every `poll_interval`, and the pending block too when `fetch_pending` is set.
+///
+/// The interval is doubled after each consecutive failure, so that gateway incidents are not
+/// hammered.
+pub async fn track_pending<C>(
+    provider: Arc<ProviderPool>,
+    sync_state: Arc<SyncState>,
+    client: Arc<C>,
+    poll_interval: Duration,
+    fetch_pending: bool,
+    metrics: Option<PendingDataMetrics>,
+) where
+    C: HeaderBackend<DBlockT>,
+{
+    let mut failures = 0u32;
+    loop {
+        tokio::time::sleep(poll_interval * 2u32.pow(failures.min(6))).await;
+        let best_block = u64::from(client.info().best_number);
+        match update_starknet_data(&provider, &sync_state, best_block, fetch_pending, metrics.as_ref()).await {
+            Ok(()) => {
+                if failures > 0 {
+                    log::info!("Pending data updates recovered after {failures} failed attempts");
+                }
+                failures = 0;
+            }
+            Err(e) => {
+                if let Some(metrics) = &metrics {
+                    e.record(metrics);
+                }
+                if failures == 0 {
+                    log::warn!("Failed to update highest block hash and number: {e}, backing off");
+                } else {
+                    log::debug!("Failed to update highest block hash and number: {e}");
+                }
+                failures = failures.saturating_add(1);
+            }
+        }
+    }
+}
+
+/// The hash and number of a block, as returned by the feeder gateway when only the header is
+/// requested.
+#[derive(Deserialize)]
+struct BlockHead {
+    block_hash: FieldElement,
+    block_number: u64,
+}
+
+/// Fetches the hash and number of the latest block, without downloading its body.
+async fn fetch_head(provider: &PooledProvider) -> Result<(FieldElement, u64), PendingDataError> {
+    let mut url = provider.feeder_gateway.clone();
+    url.path_segments_mut().expect("feeder gateway url cannot be a base").pop_if_empty().push("get_block");
+    url.query_pairs_mut().append_pair("blockNumber", "latest").append_pair("headerOnly", "true");
+
+    let mut request = HEAD_CLIENT.get(url);
+    if let Some(api_key) = &provider.api_key {
+        request = request.header("X-Throttling-Bypass", api_key);
+    }
+    let head: BlockHead = request.send().await?.error_for_status()?.json().await?;
+
+    Ok((head.block_hash, head.block_number))
+}
+
+lazy_static! {
+    static ref HEAD_CLIENT: reqwest::Client =
+        reqwest::Client::builder().timeout(Duration::from_secs(10)).build().expect("Failed to build http client");
+}
+
+/// Reports the tip of the chain, and fetches the pending block built on top of it once it is
+/// `best_block`, the last block of the node.
+async fn update_starknet_data(
+    provider: &ProviderPool,
+    sync_state: &SyncState,
+    best_block: u64,
+    fetch_pending: bool,
+    metrics: Option<&PendingDataMetrics>,
+) -> Result<(), PendingDataError> {
+    let (hash_current, number) = provider.request(fetch_head).await?;
+    if drop_superseded_pending(sync_state, hash_current) {
+        log::debug!("Pending block superseded by block #{number}");
+    }
+
+    let hash_best = storage_handler::block_hash().get(best_block).ok().flatten().map(|hash| hash.0);
+
+    if hash_best != Some(hash_current) {
+        // the tip is not our best block, which is expected while catching up
+        if let Some(metrics) = metrics {
+            metrics.hash_mismatches.inc();
+        }
+    } else if fetch_pending {
+        // the full pending block is only downloaded once we have caught up with the tip
+        let mut block = provider.get_block(BlockId::Pending).await?;
+        // the tip may have moved since its head was fetched
+        if block.parent_block_hash == hash_current {
+            // the gateway neither numbers the pending block nor commits to its state yet
+            block.block_number.get_or_insert(number + 1);
+            block.state_root.get_or_insert(FieldElement::ZERO);
+            let state_update = provider.get_state_update(BlockId::Pending).await?;
+
+            let pending = PendingBlock {
+                parent_hash: hash_current,
+                block: crate::convert::block(block).await?,
+                state_update: crate::convert::state_update(state_update),
+            };
+            if let Err(e) = DeoxysBackend::pending().store(pending.parent_hash, &pending.block, &pending.state_update) {
+                log::warn!("❗ Failed to store the pending block: {e}");
+            }
+            sync_state.set_pending(pending);
+        }
+    }
+
+    sync_state.set_highest_block(hash_current, number);
+
+    log::debug!(
+        "update_starknet_data: latest_block_number: {}, latest_block_hash: 0x{:x}, best_block_number: {}",
+        number,
+        hash_current,
+        best_block
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_felt::Felt252Wrapper;
+    use serde_json::json;
+    use url::Url;
+
+    use super::*;
+
+    /// Serves the responses of a feeder gateway, each picked by a pattern of the requested url, and
+    /// returns its url.
+    fn feeder_gateway(responses: Vec<(&'static str, String)>) -> Url {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/feeder_gateway", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            use std::io::{BufRead, BufReader, Write};
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let (_, body) = responses.iter().find(|(pattern, _)| request.contains(pattern)).unwrap();
+                write!(stream, "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}", body.len())
+                    .unwrap();
+            }
+        });
+        Url::parse(&url).unwrap()
+    }
+
+    #[tokio::test]
+    async fn pending_block_reaches_the_subscribers_once_caught_up() {
+        let _db = DeoxysBackend::open_for_testing();
+        let tip = FieldElement::from(0x2600u64);
+        storage_handler::block_hash().insert(2600, &Felt252Wrapper(tip)).unwrap();
+
+        // the synthetic block, as the gateway serves a pending block
+        let mut pending: serde_json::Value =
+            serde_json::from_str(include_str!("../resources/schema/block_0_13_1.json")).unwrap();
+        for field in ["block_hash", "block_number", "state_root", "transaction_commitment", "event_commitment"] {
+            pending.as_object_mut().unwrap().remove(field);
+        }
+        pending["parent_block_hash"] = "0x2600".into();
+        pending["status"] = "PENDING".into();
+        let state_update = json!({
+            "block_hash": null,
+            "new_root": null,
+            "old_root": "0x2601",
+            "state_diff": {
+                "storage_diffs": {},
+                "deployed_contracts": [],
+                "old_declared_contracts": [],
+                "declared_classes": [],
+                "nonces": {},
+                "replaced_classes": []
+            }
+        });
+        let feeder_gateway = feeder_gateway(vec![
+            ("headerOnly=true", json!({ "block_hash": "0x2600", "block_number": 2600 }).to_string()),
+            ("get_block?blockNumber=pending", pending.to_string()),
+            ("get_state_update?blockNumber=pending", state_update.to_string()),
+        ]);
+        let gateway = Url::parse("http://gateway.test/gateway").unwrap();
+        let provider = ProviderPool::new(vec![PooledProvider::new(gateway, feeder_gateway, FieldElement::ZERO, None)]);
+
+        let sync_state = SyncState::default();
+        let mut subscription = sync_state.subscribe_pending();
+
+        // the pending block is not fetched while catching up
+        update_starknet_data(&provider, &sync_state, 2599, true, None).await.unwrap();
+        assert!(!subscription.has_changed().unwrap());
+        assert_eq!(sync_state.highest_block_hash_and_number(), (tip, 2600));
+
+        update_starknet_data(&provider, &sync_state, 2600, true, None).await.unwrap();
+        assert!(subscription.has_changed().unwrap());
+        let pending = subscription.borrow_and_update().clone().unwrap();
+        assert_eq!(pending.parent_hash, tip);
+        assert_eq!(pending.block.header().block_number, 2601);
+        assert_eq!(pending.block.transactions().len(), 3);
+        assert_eq!(pending.state_update.old_root, FieldElement::from(0x2601u64));
+    }
+
+    #[test]
+    fn provider_errors_are_classified_on_their_type() {
+        let decode = serde_json::from_str::<u64>("<html>").unwrap_err();
+        let err = PendingDataError::from(ProviderError::Other(Box::new(GatewayClientError::Serde(decode))));
+        assert!(matches!(err, PendingDataError::Decode(_)));
+
+        assert!(matches!(PendingDataError::from(ProviderError::RateLimited), PendingDataError::RateLimited));
+
+        // the other gateway client errors are left unclassified
+        let err = PendingDataError::from(ProviderError::Other(Box::new(GatewayClientError::MethodNotSupported)));
+        assert!(matches!(err, PendingDataError::Provider(_)));
+    }
+}
@@ -0,0 +1,133 @@
+//! Per-block timings of the sync pipeline, recorded as a chrome trace.
+//!
+//! When enabled with `--profile-sync`, every stage of the import of every block is written as a
+//! complete event of the [trace event format], which can be opened in Perfetto or
+//! `chrome://tracing`. Each stage gets its own track, and the events of a block carry its number.
+//! Events are appended one per line as they complete, without the closing bracket of the array,
+//! which trace viewers accept: a trace is thus valid even when the node is killed.
+//!
+//! [trace event format]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use serde_json::json;
+
+static PROFILER: OnceLock<SyncProfiler> = OnceLock::new();
+
+/// A stage of the import of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Fetching the block, state update and classes from the feeder gateway.
+    Fetch,
+    /// Converting the block.
+    Convert,
+    /// Computing the state root.
+    Verify,
+    /// Storing the state and class updates.
+    Store,
+    /// Sealing the block.
+    CreateBlock,
+}
+
+impl Stage {
+    const ALL: [Stage; 5] = [Stage::Fetch, Stage::Convert, Stage::Verify, Stage::Store, Stage::CreateBlock];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Fetch => "fetch",
+            Stage::Convert => "convert",
+            Stage::Verify => "verify",
+            Stage::Store => "store",
+            Stage::CreateBlock => "create_block",
+        }
+    }
+
+    /// The first track of the stage. Stages which run concurrently for several blocks use several
+    /// tracks, as complete events on a single track must not overlap: the fetches are spread over
+    /// `fetch_tracks` tracks, one per block fetched concurrently.
+    fn track(self, fetch_tracks: u64) -> u64 {
+        match self {
+            Stage::Fetch => 1,
+            Stage::Convert => 1 + fetch_tracks,
+            Stage::Verify => 2 + fetch_tracks,
+            Stage::Store => 3 + fetch_tracks,
+            Stage::CreateBlock => 4 + fetch_tracks,
+        }
+    }
+
+    fn tracks(self, fetch_tracks: u64) -> u64 {
+        if self == Stage::Fetch { fetch_tracks } else { 1 }
+    }
+}
+
+struct SyncProfiler {
+    start: Instant,
+    fetch_tracks: u64,
+    output: Mutex<LineWriter<File>>,
+}
+
+impl SyncProfiler {
+    fn write(&self, event: serde_json::Value) {
+        let mut output = self.output.lock().expect("Failed to acquire lock on sync profile");
+        if let Err(e) = writeln!(output, "{event},") {
+            log::debug!("Failed to write sync profile event: {e}");
+        }
+    }
+}
+
+/// Starts recording the sync profile to `path`, which is truncated, for a sync fetching up to
+/// `fetch_concurrency` blocks concurrently.
+pub fn init(path: &Path, fetch_concurrency: usize) -> std::io::Result<()> {
+    let mut output = LineWriter::new(File::create(path)?);
+    writeln!(output, "[")?;
+
+    let fetch_tracks = fetch_concurrency.max(1) as u64;
+    let profiler = SyncProfiler { start: Instant::now(), fetch_tracks, output: Mutex::new(output) };
+    profiler.write(json!({ "name": "process_name", "ph": "M", "pid": 1, "args": { "name": "deoxys sync" } }));
+    for stage in Stage::ALL {
+        let track = stage.track(fetch_tracks);
+        for tid in track..track + stage.tracks(fetch_tracks) {
+            let name = json!({ "name": stage.name() });
+            profiler.write(json!({ "name": "thread_name", "ph": "M", "pid": 1, "tid": tid, "args": name }));
+        }
+    }
+
+    PROFILER.set(profiler).map_err(|_| std::io::Error::other("sync profile already initialized"))
+}
+
+/// A stage being timed, recorded once dropped.
+pub struct Span {
+    stage: Stage,
+    block_n: u64,
+    start: Instant,
+}
+
+/// Times `stage` of the import of `block_n` until the returned span is dropped, if profiling is
+/// enabled.
+pub fn span(stage: Stage, block_n: u64) -> Option<Span> {
+    PROFILER.get().map(|_| Span { stage, block_n, start: Instant::now() })
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(profiler) = PROFILER.get() else {
+            return;
+        };
+        let ts = self.start.duration_since(profiler.start).as_secs_f64() * 1e6;
+        let dur = self.start.elapsed().as_secs_f64() * 1e6;
+        profiler.write(json!({
+            "name": format!("{} #{}", self.stage.name(), self.block_n),
+            "cat": self.stage.name(),
+            "ph": "X",
+            "ts": ts,
+            "dur": dur,
+            "pid": 1,
+            "tid": self.stage.track(profiler.fetch_tracks) + self.block_n % self.stage.tracks(profiler.fetch_tracks),
+            "args": { "block_number": self.block_n },
+        }));
+    }
+}
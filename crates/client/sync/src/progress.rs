@@ -0,0 +1,107 @@
+//! Reports the progress of the L2 sync.
+//!
+//! The apply task records every block it applies in the [`SyncState`](crate::state::SyncState).
+//! The speed of the sync is averaged over the blocks applied during the last [`SPEED_WINDOW`], and
+//! the time left to reach the highest known block is estimated from it.
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::l2::SyncStatus;
+
+/// The period over which the speed of the sync is averaged.
+pub const SPEED_WINDOW: Duration = Duration::from_secs(60);
+
+/// A snapshot of the progress of the sync.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SyncProgress {
+    /// The last block applied by the sync.
+    pub current_block: u64,
+    /// The highest block known to the feeder gateway.
+    pub highest_block: u64,
+    /// The number of blocks applied per second, averaged over the last [`SPEED_WINDOW`].
+    pub blocks_per_second: f64,
+    /// The estimated number of seconds left to reach the highest block, unknown while no block is
+    /// being applied.
+    pub eta_seconds: Option<u64>,
+    pub status: SyncStatus,
+}
+
+/// Tracks the blocks applied by the sync, held by the [`SyncState`](crate::state::SyncState).
+pub(crate) struct ProgressTracker {
+    current_block: u64,
+    started_at: Instant,
+    /// When the blocks of the last [`SPEED_WINDOW`] were applied, oldest first.
+    applied_at: VecDeque<Instant>,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(current_block: u64, now: Instant) -> Self {
+        Self { current_block, started_at: now, applied_at: VecDeque::new() }
+    }
+
+    pub(crate) fn record(&mut self, block_n: u64, now: Instant) {
+        self.current_block = block_n;
+        self.applied_at.push_back(now);
+        while self.applied_at.front().is_some_and(|at| now.duration_since(*at) > SPEED_WINDOW) {
+            self.applied_at.pop_front();
+        }
+    }
+
+    /// The speed over the window, or over the time since the start when it is shorter, so that the
+    /// speed drops to zero once the sync stops applying blocks.
+    fn blocks_per_second(&self, now: Instant) -> f64 {
+        let window = now.duration_since(self.started_at).min(SPEED_WINDOW);
+        if window.is_zero() {
+            return 0.0;
+        }
+        let applied = self.applied_at.iter().filter(|at| now.duration_since(**at) <= window).count();
+        applied as f64 / window.as_secs_f64()
+    }
+
+    pub(crate) fn snapshot(&self, highest_block: u64, status: SyncStatus, now: Instant) -> SyncProgress {
+        let blocks_per_second = self.blocks_per_second(now);
+        let remaining = highest_block.saturating_sub(self.current_block);
+        let eta_seconds = match remaining {
+            0 => Some(0),
+            _ if blocks_per_second > 0.0 => Some((remaining as f64 / blocks_per_second).ceil() as u64),
+            _ => None,
+        };
+
+        SyncProgress { current_block: self.current_block, highest_block, blocks_per_second, eta_seconds, status }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_the_time_left_from_the_recent_speed() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::new(100, start);
+        for i in 1..=20 {
+            tracker.record(100 + i, start + Duration::from_secs(i));
+        }
+
+        let progress = tracker.snapshot(220, SyncStatus::SyncUnverifiedState, start + Duration::from_secs(20));
+        assert_eq!(progress.current_block, 120);
+        assert_eq!(progress.blocks_per_second, 1.0);
+        assert_eq!(progress.eta_seconds, Some(100));
+    }
+
+    #[test]
+    fn speed_drops_once_blocks_stop_being_applied() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::new(0, start);
+        tracker.record(1, start + Duration::from_secs(1));
+
+        let stalled = start + Duration::from_secs(1) + SPEED_WINDOW * 2;
+        let progress = tracker.snapshot(10, SyncStatus::SyncUnverifiedState, stalled);
+        assert_eq!(progress.blocks_per_second, 0.0);
+        assert_eq!(progress.eta_seconds, None);
+
+        assert_eq!(tracker.snapshot(1, SyncStatus::SyncUnverifiedState, stalled).eta_seconds, Some(0));
+    }
+}
@@ -0,0 +1,114 @@
+//! Reorders the fetched blocks before they are applied.
+//!
+//! The apply task applies the blocks one at a time in chain order, while the blocks may arrive out
+//! of order or more than once when they are fetched from several sources. A [`ReorderBuffer`] holds
+//! the blocks arriving ahead of the next one to apply, within a bounded window, and hands them back
+//! in order.
+use std::collections::BTreeMap;
+
+/// Why a block could not be buffered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejected {
+    /// The block was already handed back, or is already buffered.
+    Duplicate,
+    /// The block is too far ahead of the next one to apply to fit in the window.
+    OutOfWindow,
+}
+
+/// The blocks arriving ahead of the next one to apply, keyed by block number.
+#[derive(Debug)]
+pub struct ReorderBuffer<T> {
+    next: u64,
+    window: usize,
+    pending: BTreeMap<u64, T>,
+}
+
+impl<T> ReorderBuffer<T> {
+    /// Creates a buffer handing back the blocks from `next`, holding the `window` blocks after it.
+    pub fn new(next: u64, window: usize) -> Self {
+        Self { next, window: window.max(1), pending: BTreeMap::new() }
+    }
+
+    /// Buffers a block until it is the next one to apply.
+    pub fn insert(&mut self, block_n: u64, block: T) -> Result<(), Rejected> {
+        if block_n < self.next || self.pending.contains_key(&block_n) {
+            return Err(Rejected::Duplicate);
+        }
+        if block_n - self.next >= self.window as u64 {
+            return Err(Rejected::OutOfWindow);
+        }
+        self.pending.insert(block_n, block);
+        Ok(())
+    }
+
+    /// Hands back the next block to apply, if it has arrived.
+    pub fn pop(&mut self) -> Option<T> {
+        let block = self.pending.remove(&self.next)?;
+        self.next += 1;
+        Some(block)
+    }
+
+    /// The number of the next block to apply.
+    pub fn next(&self) -> u64 {
+        self.next
+    }
+
+    /// The blocks missing in between the next one to apply and the last buffered one.
+    ///
+    /// The blocks are fetched in order, so these were skipped rather than still being fetched.
+    pub fn missing(&self) -> Vec<u64> {
+        let Some((&last, _)) = self.pending.last_key_value() else {
+            return Vec::new();
+        };
+        (self.next..last).filter(|block_n| !self.pending.contains_key(block_n)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_are_handed_back_in_order() {
+        let mut buffer = ReorderBuffer::new(10, 4);
+        assert_eq!(buffer.insert(12, "c"), Ok(()));
+        assert_eq!(buffer.insert(11, "b"), Ok(()));
+        assert_eq!(buffer.pop(), None);
+
+        assert_eq!(buffer.insert(10, "a"), Ok(()));
+        assert_eq!(buffer.pop(), Some("a"));
+        assert_eq!(buffer.pop(), Some("b"));
+        assert_eq!(buffer.pop(), Some("c"));
+        assert_eq!(buffer.pop(), None);
+        assert_eq!(buffer.next(), 13);
+    }
+
+    #[test]
+    fn duplicates_and_blocks_past_the_window_are_rejected() {
+        let mut buffer = ReorderBuffer::new(10, 4);
+        assert_eq!(buffer.insert(11, ()), Ok(()));
+        assert_eq!(buffer.insert(11, ()), Err(Rejected::Duplicate));
+        assert_eq!(buffer.insert(14, ()), Err(Rejected::OutOfWindow));
+
+        assert_eq!(buffer.insert(10, ()), Ok(()));
+        assert_eq!(buffer.pop(), Some(()));
+        assert_eq!(buffer.insert(10, ()), Err(Rejected::Duplicate));
+        assert_eq!(buffer.insert(14, ()), Ok(()));
+    }
+
+    #[test]
+    fn skipped_blocks_are_missing() {
+        let mut buffer = ReorderBuffer::new(10, 8);
+        assert!(buffer.missing().is_empty());
+
+        assert_eq!(buffer.insert(10, ()), Ok(()));
+        assert!(buffer.missing().is_empty());
+        assert_eq!(buffer.insert(12, ()), Ok(()));
+        assert_eq!(buffer.insert(14, ()), Ok(()));
+        assert_eq!(buffer.missing(), vec![11, 13]);
+
+        assert_eq!(buffer.pop(), Some(()));
+        assert_eq!(buffer.insert(11, ()), Ok(()));
+        assert_eq!(buffer.missing(), vec![13]);
+    }
+}
@@ -1,35 +1,110 @@
-use starknet_providers::sequencer::models::Block as StarknetBlock;
+use mc_db::{storage_handler, DeoxysBackend};
+use mp_convert::state_update::ToStateUpdateCore;
+use mp_felt::Felt252Wrapper;
+use mp_types::block::DBlockT;
+use sp_blockchain::HeaderBackend;
+use starknet_ff::FieldElement;
+use starknet_providers::sequencer::models::BlockId;
 
-use crate::l2::get_highest_block_hash_and_number;
+use crate::fetch::provider_pool::ProviderPool;
+use crate::l2::{sync_checkpoint, L2SyncError};
 
-/// Check for a reorg on Starknet and fix the current state if detected.
+/// Check whether a fetched block does not extend the local chain.
 ///
 /// On Starknet with the current system relying on a single sequencer it's rare to detect a reorg,
-/// but if the L1 reorgs we must handle it the following way:
+/// but if the L1 reorgs, or if a gateway serves an inconsistent chain, the parent hash of the next
+/// block fetched from the sequencer is no longer the hash of the last block applied by Deoxys.
 ///
-/// 1. The last fetched block parent hash is not equal to the last synced block by Deoxys: a reorg
-///    is detected.
-/// 2. We remove the last synced substrate digest and the associated classes/state_update we stored
-///    until we reach the last common ancestor.
+/// The parent hash is checked against the sync checkpoint, and against the stored hash of the
+/// parent block when the checkpoint is not at the parent, so that the fetch order is never trusted.
 ///
 /// ### Arguments
 ///
-/// * `block` - The last fetched block from the sequencer (before beeing converted).
+/// * `block_n` - The number of the fetched block.
+/// * `parent_block_hash` - The parent hash of the fetched block.
 ///
 /// ### Returns
-/// This function will return a `Bool` returning `true` if a reorg was detected and `false` if not.
-pub async fn reorg(block: StarknetBlock) -> bool {
-    let last_synced_block_hash = get_highest_block_hash_and_number().0;
-    if block.parent_block_hash != last_synced_block_hash {
-        let mut new_lsbh = last_synced_block_hash;
-        while block.parent_block_hash != new_lsbh {
-            // 1. Remove the last synced block in the digest
-            // 2. Remove all the downloaded stuff from the state updates
-            new_lsbh = get_highest_block_hash_and_number().0;
+///
+/// `true` if a reorg was detected and `false` if not, or if the parent block is not known locally.
+pub fn is_reorg(block_n: u64, parent_block_hash: FieldElement) -> Result<bool, L2SyncError> {
+    let Some(parent_block_n) = block_n.checked_sub(1) else {
+        return Ok(false);
+    };
+
+    let local_parent_hash = match DeoxysBackend::meta().sync_checkpoint()? {
+        Some(checkpoint) if checkpoint.block_number == parent_block_n => {
+            Some(FieldElement::from(Felt252Wrapper::from(checkpoint.block_hash)))
+        }
+        _ => storage_handler::block_hash()
+            .get(parent_block_n)
+            .map_err(|e| L2SyncError::Storage(parent_block_n, e))?
+            .map(FieldElement::from),
+    };
+
+    match local_parent_hash {
+        Some(local_parent_hash) if local_parent_hash != parent_block_hash => {
+            log::warn!(
+                "❗ Block #{block_n} has parent hash {parent_block_hash:#x}, but block #{parent_block_n} is \
+                 {local_parent_hash:#x} locally"
+            );
+            Ok(true)
         }
-        // 3. Revert the state commitment tries to the correct block number
-        true
-    } else {
-        false
+        _ => Ok(false),
+    }
+}
+
+/// Fix the current state after a reorg was detected at `block_n`.
+///
+/// 1. Walk back from the parent of `block_n` until the last common ancestor of the local chain and
+///    of the chain of the sequencer is reached.
+/// 2. Remove the state updates, classes, state commitment tries and block mappings stored for the
+///    blocks after it, see [`DeoxysBackend::revert_to`].
+/// 3. Rewind the substrate chain to it, so that the next block is sealed on top of it.
+/// 4. Move the sync checkpoint back to it, so the chain is synced again from there.
+///
+/// ### Returns
+///
+/// The number of the last common ancestor.
+pub async fn revert_to_common_ancestor<BE, C>(
+    provider: &ProviderPool,
+    backend: &BE,
+    client: &C,
+    block_n: u64,
+) -> Result<u64, L2SyncError>
+where
+    BE: sc_client_api::Backend<DBlockT>,
+    C: HeaderBackend<DBlockT>,
+{
+    let ancestor = find_common_ancestor(provider, block_n.saturating_sub(1)).await?;
+    log::warn!("🔀 Reorg detected at block #{block_n}, reverting to block #{ancestor}");
+
+    DeoxysBackend::revert_to(ancestor).await.map_err(|e| L2SyncError::Storage(ancestor, e))?;
+
+    // the sealed blocks are never finalized, so they can all be reverted
+    let best = u64::from(client.info().best_number);
+    if best > ancestor {
+        backend.revert((best - ancestor) as _, false).map_err(|e| L2SyncError::ChainRevert(ancestor, e))?;
+    }
+
+    let state_update = provider.get_state_update(BlockId::Number(ancestor)).await?.to_state_update_core();
+    DeoxysBackend::meta().write_sync_checkpoint(sync_checkpoint(ancestor, &state_update))?;
+
+    Ok(ancestor)
+}
+
+/// Returns the last block, from `block_n` down, whose hash is the same locally and on the
+/// sequencer.
+async fn find_common_ancestor(provider: &ProviderPool, mut block_n: u64) -> Result<u64, L2SyncError> {
+    loop {
+        let local_hash = storage_handler::block_hash().get(block_n).map_err(|e| L2SyncError::Storage(block_n, e))?;
+        let remote_hash = provider.get_block(BlockId::Number(block_n)).await?.block_hash;
+
+        if let (Some(local_hash), Some(remote_hash)) = (local_hash, remote_hash)
+            && FieldElement::from(local_hash) == remote_hash
+        {
+            return Ok(block_n);
+        }
+
+        block_n = block_n.checked_sub(1).ok_or(L2SyncError::NoCommonAncestor)?;
     }
 }
@@ -0,0 +1,171 @@
+//! Soak mode, catching the memory of the node creeping up over long runs.
+//!
+//! Every interval, the resident memory of the node, the memory held by the database, and the
+//! occupancy of the queues of the sync are sampled. A series which kept growing over its last
+//! samples is flagged, and all the series are written to a report after every sample, which can be
+//! attached to a bug report about the memory of the node.
+//!
+//! The components of the sync [register](register) the queues they own, which are sampled on top of
+//! the built-in series.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use mc_db::DeoxysBackend;
+use tokio::sync::mpsc::WeakSender;
+use tokio::time::MissedTickBehavior;
+
+use crate::metrics::{class_metrics, pool_metrics};
+
+/// The number of last samples of a series checked for growth.
+const GROWTH_WINDOW: usize = 6;
+
+/// How much a series must grow over the window to be flagged, in percent of its first sample.
+const GROWTH_THRESHOLD_PERCENT: u64 = 10;
+
+/// The number of last samples of every series written to the report.
+const REPORTED_SAMPLES: usize = 4 * GROWTH_WINDOW;
+
+#[derive(Debug, Clone)]
+pub struct SoakConfig {
+    /// How often the series are sampled.
+    pub interval: Duration,
+    /// The file the report is written to.
+    pub report: PathBuf,
+}
+
+type Source = Box<dyn Fn() -> Option<u64> + Send>;
+
+static SOURCES: OnceLock<Mutex<BTreeMap<&'static str, Source>>> = OnceLock::new();
+
+fn sources() -> &'static Mutex<BTreeMap<&'static str, Source>> {
+    SOURCES.get_or_init(Default::default)
+}
+
+/// Registers a series sampled by the soak mode, replacing the one registered under the same name.
+///
+/// The source returns `None` while there is nothing to sample, such as when a queue is closed.
+pub fn register(name: &'static str, source: impl Fn() -> Option<u64> + Send + 'static) {
+    sources().lock().expect("Failed to acquire lock on the soak sources").insert(name, Box::new(source));
+}
+
+/// Registers the number of messages waiting in a queue as a series sampled by the soak mode.
+pub fn register_queue<T: Send + 'static>(name: &'static str, queue: WeakSender<T>) {
+    register(name, move || queue.upgrade().map(|sender| (sender.max_capacity() - sender.capacity()) as u64));
+}
+
+/// The resident memory of the node, in bytes.
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let rss = status.lines().find_map(|line| line.strip_prefix("VmRSS:"))?;
+    let kib: u64 = rss.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+fn sample() -> Vec<(&'static str, Option<u64>)> {
+    let db_memory = DeoxysBackend::memory_usage();
+    let mut sample = vec![
+        ("rss_bytes", resident_memory()),
+        ("db_mem_tables_bytes", Some(db_memory.mem_tables)),
+        ("db_table_readers_bytes", Some(db_memory.table_readers)),
+        ("db_block_cache_bytes", Some(db_memory.block_cache)),
+        ("class_compile_backlog", class_metrics().and_then(|metrics| metrics.compile_backlog.get().try_into().ok())),
+        ("compute_queue_depth", pool_metrics().and_then(|metrics| metrics.compute_queue_depth.get().try_into().ok())),
+    ];
+    let sources = sources().lock().expect("Failed to acquire lock on the soak sources");
+    sample.extend(sources.iter().map(|(name, source)| (*name, source())));
+    sample
+}
+
+/// Whether a series never decreased over its last [GROWTH_WINDOW] samples, growing by more than
+/// [GROWTH_THRESHOLD_PERCENT] over them.
+fn is_growing(samples: &[u64]) -> bool {
+    let Some(window) = samples.len().checked_sub(GROWTH_WINDOW).map(|start| &samples[start..]) else {
+        return false;
+    };
+    let (first, last) = (window[0], window[GROWTH_WINDOW - 1]);
+    window.windows(2).all(|pair| pair[0] <= pair[1]) && (last - first) * 100 > first * GROWTH_THRESHOLD_PERCENT
+}
+
+fn report(series: &BTreeMap<&'static str, Vec<u64>>, uptime: Duration, interval: Duration) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "deoxys soak report");
+    let _ = writeln!(report, "uptime: {}s, sampled every {}s", uptime.as_secs(), interval.as_secs());
+    let _ = writeln!(report);
+
+    let width = series.keys().map(|name| name.len()).max().unwrap_or_default();
+    let _ = writeln!(
+        report,
+        "{:<width$}  {:>8}  {:>14}  {:>14}  {:>14}  growing",
+        "series", "samples", "first", "last", "max"
+    );
+    for (name, samples) in series {
+        let first = samples.first().copied().unwrap_or_default();
+        let last = samples.last().copied().unwrap_or_default();
+        let max = samples.iter().copied().max().unwrap_or_default();
+        let growing = if is_growing(samples) { "yes" } else { "no" };
+        let _ = writeln!(report, "{name:<width$}  {:>8}  {first:>14}  {last:>14}  {max:>14}  {growing}", samples.len());
+    }
+    let _ = writeln!(report);
+
+    let _ = writeln!(report, "last {REPORTED_SAMPLES} samples, oldest first:");
+    for (name, samples) in series {
+        let recent = &samples[samples.len().saturating_sub(REPORTED_SAMPLES)..];
+        let recent = recent.iter().map(u64::to_string).collect::<Vec<_>>().join(" ");
+        let _ = writeln!(report, "{name:<width$}  {recent}");
+    }
+    report
+}
+
+/// Samples the series every interval and rewrites the report after every sample, forever.
+pub async fn soak(config: &SoakConfig) {
+    log::info!("🧪 Soak mode: sampling the memory every {:?} into {}", config.interval, config.report.display());
+    let started_at = Instant::now();
+    let mut series: BTreeMap<&'static str, Vec<u64>> = BTreeMap::new();
+    let mut flagged = BTreeSet::new();
+
+    let mut interval = tokio::time::interval(config.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        for (name, value) in sample() {
+            if let Some(value) = value {
+                series.entry(name).or_default().push(value);
+            }
+        }
+
+        for (name, samples) in &series {
+            if !is_growing(samples) {
+                flagged.remove(name);
+            } else if flagged.insert(*name) {
+                log::warn!(
+                    "🧪 {name} kept growing over the last {GROWTH_WINDOW} samples, up to {}",
+                    samples.last().copied().unwrap_or_default()
+                );
+            }
+        }
+
+        if let Err(e) = std::fs::write(&config.report, report(&series, started_at.elapsed(), config.interval)) {
+            log::error!("❗ Failed to write the soak report to {}: {e}", config.report.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_steady_growth_is_flagged() {
+        assert!(!is_growing(&[100, 110, 120, 130, 140]));
+        assert!(is_growing(&[100, 110, 120, 130, 140, 150]));
+        assert!(is_growing(&[500, 100, 100, 105, 105, 110, 111]));
+        // a single drop in the window is enough to clear the series
+        assert!(!is_growing(&[100, 110, 120, 115, 140, 150]));
+        // growth within the threshold is noise
+        assert!(!is_growing(&[1000, 1001, 1002, 1003, 1004, 1005]));
+        assert!(!is_growing(&[0, 0, 0, 0, 0, 0]));
+    }
+}
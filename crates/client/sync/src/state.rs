@@ -0,0 +1,265 @@
+//! The state of the sync shared with the rest of the node.
+//!
+//! A [`SyncState`] is created along with the node and handed by `Arc` to the sync tasks, which
+//! update it, and to the rpc layer, which reads it, so that tests and several nodes in one process
+//! each get their own. The L1 head (`ETHEREUM_STATE_UPDATE`) and the fetch config (`CONFIG`) are
+//! still kept in globals, and so are shared by every node of the process.
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+use mp_block::Header;
+use starknet_api::hash::StarkHash;
+use starknet_ff::FieldElement;
+use tokio::sync::watch;
+
+use crate::headers::{BlockHeader, HeaderChain};
+use crate::l1::ETHEREUM_STATE_UPDATE;
+use crate::l2::{L2StateUpdate, SyncStatus};
+use crate::pending::{PendingBlock, PendingSubscription};
+use crate::progress::{ProgressTracker, SyncProgress};
+
+/// The number of headers of the last applied blocks kept in memory.
+pub const HEADER_CACHE_SIZE: usize = 64;
+
+/// The header of a recently applied block, served to the rpc without reading the database.
+#[derive(Clone, Debug)]
+pub struct CachedHeader {
+    /// The hash of the block, as served by the gateway.
+    pub block_hash: FieldElement,
+    pub header: Header,
+}
+
+pub struct SyncState {
+    /// Current syncing status, either verified, unverified or pending
+    status: RwLock<SyncStatus>,
+    /// Latest L2 state update verified on L2
+    state_update: RwLock<L2StateUpdate>,
+    /// Latest block hash and number of the chain
+    highest_block: RwLock<(FieldElement, u64)>,
+    /// Pending block, broadcast to the subscribers whenever it changes
+    pending: watch::Sender<Option<Arc<PendingBlock>>>,
+    /// Progress of the apply task, using a Mutex as every applied block updates it
+    progress: Mutex<ProgressTracker>,
+    /// Headers of the last applied blocks, by block number
+    headers: RwLock<BTreeMap<u64, CachedHeader>>,
+    /// Headers fetched ahead of the full blocks in headers-first sync
+    header_chain: RwLock<HeaderChain>,
+}
+
+impl Default for SyncState {
+    fn default() -> Self {
+        Self {
+            status: RwLock::new(SyncStatus::SyncVerifiedState),
+            state_update: RwLock::new(L2StateUpdate {
+                block_number: u64::default(),
+                global_root: StarkHash::default(),
+                block_hash: StarkHash::default(),
+            }),
+            highest_block: RwLock::new((FieldElement::default(), 0)),
+            // the pending block is kept by the sender, whether or not it has subscribers
+            pending: watch::channel(None).0,
+            progress: Mutex::new(ProgressTracker::new(0, Instant::now())),
+            headers: RwLock::new(BTreeMap::new()),
+            header_chain: RwLock::new(HeaderChain::default()),
+        }
+    }
+}
+
+impl SyncState {
+    pub fn status(&self) -> SyncStatus {
+        *self.status.read().expect("Failed to acquire read lock on sync status")
+    }
+
+    pub fn state_update(&self) -> L2StateUpdate {
+        self.state_update.read().expect("Failed to acquire read lock on L2 state update").clone()
+    }
+
+    pub fn highest_block_hash_and_number(&self) -> (FieldElement, u64) {
+        *self.highest_block.read().expect("Failed to acquire read lock on highest block")
+    }
+
+    /// Subscribes to the pending block, which is `None` until the node has caught up with the tip,
+    /// and while the block it was built on top of is being applied.
+    pub fn subscribe_pending(&self) -> PendingSubscription {
+        self.pending.subscribe()
+    }
+
+    /// Returns the header of a block, if it is one of the last [HEADER_CACHE_SIZE] applied blocks.
+    pub fn cached_header(&self, block_number: u64) -> Option<CachedHeader> {
+        self.headers.read().expect("Failed to acquire read lock on header cache").get(&block_number).cloned()
+    }
+
+    /// Returns the header of a block from its hash, if it is one of the last [HEADER_CACHE_SIZE]
+    /// applied blocks.
+    pub fn cached_header_by_hash(&self, block_hash: FieldElement) -> Option<CachedHeader> {
+        let headers = self.headers.read().expect("Failed to acquire read lock on header cache");
+        headers.values().rev().find(|cached| cached.block_hash == block_hash).cloned()
+    }
+
+    /// Returns the header of a block fetched ahead of its full block in headers-first sync, if it
+    /// has not been applied yet.
+    pub fn header(&self, block_number: u64) -> Option<BlockHeader> {
+        self.header_chain.read().expect("Failed to acquire read lock on header chain").get(block_number)
+    }
+
+    /// Returns the numbers of the lowest and highest headers fetched ahead of their full blocks.
+    pub fn header_range(&self) -> Option<(u64, u64)> {
+        self.header_chain.read().expect("Failed to acquire read lock on header chain").range()
+    }
+
+    pub(crate) fn header_chain(&self) -> &RwLock<HeaderChain> {
+        &self.header_chain
+    }
+
+    /// Drops the headers from `block_number` onwards, so that they are fetched again.
+    pub(crate) fn drop_headers_from(&self, block_number: u64) {
+        self.header_chain.write().expect("Failed to acquire write lock on header chain").drop_from(block_number);
+    }
+
+    /// Returns the current progress of the sync.
+    pub fn sync_progress(&self) -> SyncProgress {
+        let (_, highest_block) = self.highest_block_hash_and_number();
+        let status = self.status();
+        let progress = self.progress.lock().expect("Failed to acquire lock on sync progress");
+        progress.snapshot(highest_block, status, Instant::now())
+    }
+
+    /// Update the L2 state with the latest data
+    pub fn update_l2(&self, state_update: L2StateUpdate) {
+        let block_number = state_update.block_number;
+        *self.state_update.write().expect("Failed to acquire write lock on L2 state update") = state_update;
+
+        let last_l1_state_update_block =
+            ETHEREUM_STATE_UPDATE.read().expect("Failed to acquire read lock on ETHEREUM_STATE_UPDATE").block_number;
+        if block_number >= last_l1_state_update_block {
+            *self.status.write().expect("Failed to acquire write lock on sync status") =
+                SyncStatus::SyncUnverifiedState;
+        }
+    }
+
+    pub(crate) fn set_highest_block(&self, block_hash: FieldElement, block_number: u64) {
+        *self.highest_block.write().expect("Failed to acquire write lock on highest block") =
+            (block_hash, block_number);
+    }
+
+    /// Sets the highest block unless a higher one is already known.
+    pub(crate) fn raise_highest_block(&self, block_hash: FieldElement, block_number: u64) {
+        let mut highest = self.highest_block.write().expect("Failed to acquire write lock on highest block");
+        if block_number >= highest.1 {
+            *highest = (block_hash, block_number);
+        }
+    }
+
+    pub(crate) fn set_pending(&self, pending: PendingBlock) {
+        self.pending.send_replace(Some(Arc::new(pending)));
+    }
+
+    /// Drops the pending block if it was not built on top of `tip_hash`, as it has been closed
+    /// since. Returns whether it was dropped.
+    pub(crate) fn drop_superseded_pending(&self, tip_hash: FieldElement) -> bool {
+        self.pending.send_if_modified(|pending| match pending {
+            Some(block) if block.parent_hash != tip_hash => {
+                *pending = None;
+                true
+            }
+            _ => false,
+        })
+    }
+
+    /// Restarts the progress report from `last_applied`, when the sync pipeline is (re)started.
+    pub(crate) fn reset_progress(&self, last_applied: u64) {
+        *self.progress.lock().expect("Failed to acquire lock on sync progress") =
+            ProgressTracker::new(last_applied, Instant::now());
+    }
+
+    /// Caches the header of a block which has just been applied, replacing the headers of the
+    /// blocks it reverted.
+    pub(crate) fn cache_header(&self, block_hash: FieldElement, header: Header) {
+        let mut headers = self.headers.write().expect("Failed to acquire write lock on header cache");
+        headers.split_off(&header.block_number);
+        headers.insert(header.block_number, CachedHeader { block_hash, header });
+        while headers.len() > HEADER_CACHE_SIZE {
+            headers.pop_first();
+        }
+    }
+
+    /// Drops the headers of the blocks after `block_number`, once they have been reverted.
+    pub(crate) fn drop_cached_headers_after(&self, block_number: u64) {
+        self.headers.write().expect("Failed to acquire write lock on header cache").split_off(&(block_number + 1));
+    }
+
+    /// Records that `block_n` has been fully applied.
+    pub(crate) fn record_applied(&self, block_n: u64) {
+        self.progress.lock().expect("Failed to acquire lock on sync progress").record(block_n, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mp_block::DeoxysBlock;
+    use starknet_core::types::{PendingStateUpdate, StateDiff};
+
+    use super::*;
+
+    #[test]
+    fn highest_block_is_only_raised() {
+        let sync_state = SyncState::default();
+        sync_state.raise_highest_block(FieldElement::ONE, 10);
+        sync_state.raise_highest_block(FieldElement::TWO, 5);
+        assert_eq!(sync_state.highest_block_hash_and_number(), (FieldElement::ONE, 10));
+
+        // the gateway head is always reported as is, as the chain may have been reorganized
+        sync_state.set_highest_block(FieldElement::TWO, 5);
+        assert_eq!(sync_state.highest_block_hash_and_number(), (FieldElement::TWO, 5));
+
+        // each node gets its own state
+        assert_eq!(SyncState::default().highest_block_hash_and_number(), (FieldElement::ZERO, 0));
+    }
+
+    #[test]
+    fn header_cache_keeps_the_last_applied_blocks() {
+        let sync_state = SyncState::default();
+        let header = |block_number| Header { block_number, ..Default::default() };
+        for block_number in 0..HEADER_CACHE_SIZE as u64 + 10 {
+            sync_state.cache_header(FieldElement::from(block_number), header(block_number));
+        }
+        assert!(sync_state.cached_header(9).is_none());
+        assert_eq!(sync_state.cached_header(10).map(|cached| cached.block_hash), Some(FieldElement::from(10u64)));
+        assert_eq!(sync_state.cached_header_by_hash(FieldElement::from(42u64)).unwrap().header.block_number, 42);
+
+        // applying a block again after a reorg replaces the blocks above it
+        sync_state.cache_header(FieldElement::ONE, header(20));
+        assert!(sync_state.cached_header(21).is_none());
+        assert_eq!(sync_state.cached_header(20).unwrap().block_hash, FieldElement::ONE);
+
+        sync_state.drop_cached_headers_after(15);
+        assert!(sync_state.cached_header(16).is_none());
+        assert!(sync_state.cached_header(15).is_some());
+    }
+
+    #[test]
+    fn pending_block_is_dropped_once_superseded() {
+        let sync_state = SyncState::default();
+        let subscription = sync_state.subscribe_pending();
+        let state_diff = StateDiff {
+            storage_diffs: vec![],
+            deprecated_declared_classes: vec![],
+            declared_classes: vec![],
+            deployed_contracts: vec![],
+            replaced_classes: vec![],
+            nonces: vec![],
+        };
+        sync_state.set_pending(PendingBlock {
+            parent_hash: FieldElement::ONE,
+            block: DeoxysBlock::default(),
+            state_update: PendingStateUpdate { old_root: FieldElement::ZERO, state_diff },
+        });
+
+        assert!(!sync_state.drop_superseded_pending(FieldElement::ONE));
+        assert!(subscription.borrow().is_some());
+
+        assert!(sync_state.drop_superseded_pending(FieldElement::TWO));
+        assert!(subscription.borrow().is_none());
+    }
+}
@@ -0,0 +1,325 @@
+//! Fast sync from a trusted state snapshot.
+//!
+//! Syncing from the genesis block replays every block of the chain, which takes days. Instead, the
+//! node can import a [state snapshot](mc_db::state_snapshot) of some block and only sync the blocks
+//! after it. The state root of the snapshot is checked against the one posted to the Starknet core
+//! contract for that block before anything is written, and the root of the tries rebuilt from the
+//! snapshot is then checked against it, so that the node never syncs on top of a tampered state.
+//!
+//! The snapshot is stored and the tries are rebuilt from it in bounded batches, so that the state
+//! is never held in memory as a whole.
+
+use std::path::Path;
+
+use mc_db::state_snapshot::{for_each_contract, StateSnapshotEntry, StateSnapshotError, StateSnapshotReader};
+use mc_db::storage_handler::primitives::contract_class::{
+    ContractClassData, ContractClassWrapper, StorageContractClassData,
+};
+use mc_db::storage_handler::{self, DeoxysStorageError, StorageViewMut};
+use mc_db::{ApplyJournal, DbError, DeoxysBackend, SyncCheckpoint};
+use mp_hashers::poseidon::PoseidonHasher;
+use reqwest::Url;
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce};
+use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::state::StorageKey;
+use starknet_ff::FieldElement;
+use thiserror::Error;
+
+use crate::commitments::lib::{calculate_state_root, class_leaf_hash, contract_state_hash};
+use crate::l1::EthereumClient;
+use crate::l2::L2StateUpdate;
+use crate::state::SyncState;
+
+/// The number of entries of the snapshot stored at once.
+const IMPORT_BATCH: usize = 100_000;
+
+/// The number of classes of the snapshot stored at once, as classes are much larger than the other
+/// entries.
+const IMPORT_CLASS_BATCH: usize = 256;
+
+#[derive(Error, Debug)]
+pub enum StateSnapshotImportError {
+    #[error(transparent)]
+    Snapshot(#[from] StateSnapshotError),
+    #[error("failed to query the state update from L1: {0}")]
+    L1(anyhow::Error),
+    #[error("no state update of block {block_number} was posted to L1 in Ethereum block {l1_block_number}")]
+    NotOnL1 { block_number: u64, l1_block_number: u64 },
+    #[error("state snapshot of block {0} doesn't match the state update verified on L1")]
+    L1Mismatch(u64),
+    #[error(
+        "state root {computed} rebuilt from the snapshot doesn't match its state root {expected}, the database must \
+         be purged"
+    )]
+    StateRootMismatch { computed: StarkHash, expected: StarkHash },
+    #[error("the database already holds the state up to block {0}, it must be purged to import a snapshot")]
+    AlreadySynced(u64),
+    #[error("a previous import of the snapshot of block {0} was interrupted, the database must be purged")]
+    Interrupted(u64),
+    #[error("failed to store the state snapshot: {0}")]
+    Storage(#[from] DeoxysStorageError),
+    #[error("state snapshot import task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("failed to write sync metadata: {0}")]
+    Db(#[from] DbError),
+}
+
+/// Imports the state snapshot at `path`, after checking it against the state update verified on L1
+/// for its block, and checkpoints its block so that the sync resumes right after it.
+///
+/// Returns the block of the snapshot. A node which already imported the snapshot, or synced past
+/// it, is left untouched.
+pub async fn import_state_snapshot(
+    path: &Path,
+    l1_url: Url,
+    sync_state: &SyncState,
+) -> Result<u64, StateSnapshotImportError> {
+    let header = *StateSnapshotReader::open(path)?.header();
+    let block_number = header.block_number;
+
+    if let Some(checkpoint) = DeoxysBackend::meta().sync_checkpoint()? {
+        if checkpoint.block_number < block_number {
+            return Err(StateSnapshotImportError::AlreadySynced(checkpoint.block_number));
+        }
+        log::info!("📦 State snapshot of block #{block_number} already imported");
+        return Ok(block_number);
+    }
+    if let Some(ApplyJournal::Started(block_n)) = DeoxysBackend::meta().apply_journal()? {
+        return Err(StateSnapshotImportError::Interrupted(block_n));
+    }
+
+    // the snapshot is checked against L1 before anything is written
+    let l1_client = EthereumClient::new(l1_url).await.map_err(StateSnapshotImportError::L1)?;
+    let l1_state_update = l1_client
+        .get_state_update_in(header.l1_block_number, block_number)
+        .await
+        .map_err(StateSnapshotImportError::L1)?
+        .ok_or(StateSnapshotImportError::NotOnL1 { block_number, l1_block_number: header.l1_block_number })?;
+    if l1_state_update.global_root != header.global_state_root || l1_state_update.block_hash != header.block_hash {
+        return Err(StateSnapshotImportError::L1Mismatch(block_number));
+    }
+    log::info!("📦 Importing the state snapshot of block #{block_number}, verified on L1");
+
+    // an import failing from now on leaves a partial state, which the journal reports on restart
+    DeoxysBackend::meta().write_apply_journal(ApplyJournal::Started(block_number))?;
+    let path = path.to_owned();
+    let imported = tokio::task::spawn_blocking(move || import_state(&path, block_number, IMPORT_BATCH)).await??;
+    if imported.state_root != header.global_state_root {
+        return Err(StateSnapshotImportError::StateRootMismatch {
+            computed: imported.state_root,
+            expected: header.global_state_root,
+        });
+    }
+
+    DeoxysBackend::meta().write_sync_checkpoint(SyncCheckpoint {
+        block_number,
+        block_hash: header.block_hash,
+        global_state_root: header.global_state_root,
+    })?;
+    sync_state.update_l2(L2StateUpdate {
+        block_number,
+        global_root: header.global_state_root,
+        block_hash: header.block_hash,
+    });
+
+    let ImportedState { contracts, classes, .. } = imported;
+    log::info!("📦 Imported the state of block #{block_number}: {contracts} contracts, {classes} classes");
+    Ok(block_number)
+}
+
+/// The state stored from a snapshot.
+struct ImportedState {
+    state_root: StarkHash,
+    contracts: u64,
+    classes: u64,
+}
+
+/// Stores the state held by the snapshot at `path` at `block_number`, `batch_size` entries at a
+/// time, and rebuilds the tries from it.
+///
+/// The tries commit every batch at `block_number`, which they can as they keep no change log.
+fn import_state(path: &Path, block_number: u64, batch_size: usize) -> Result<ImportedState, StateSnapshotImportError> {
+    let mut batch = ImportBatch::default();
+    let (mut contracts, mut classes) = (0, 0);
+
+    for entry in StateSnapshotReader::open(path)? {
+        match entry? {
+            StateSnapshotEntry::Contract { address, class_hash, nonce } => {
+                batch.contracts.push((address, class_hash, nonce));
+                contracts += 1;
+            }
+            StateSnapshotEntry::Storage { address, key, value } => batch.storage.push((address, key, value)),
+            StateSnapshotEntry::Class { class, compiled_class_hash } => {
+                batch.classes.push((class, compiled_class_hash));
+                classes += 1;
+            }
+            StateSnapshotEntry::End => {}
+        }
+        if batch.len() >= batch_size || batch.classes.len() >= IMPORT_CLASS_BATCH {
+            batch.store(block_number)?;
+        }
+    }
+    batch.store(block_number)?;
+
+    // every contract has a leaf in the contract trie, which can only be computed once all of its
+    // storage is stored
+    let mut contract_trie = storage_handler::contract_trie_mut();
+    let mut storage_trie = storage_handler::contract_storage_trie_mut();
+    let mut leaves = Vec::new();
+    let mut commit_leaves = |leaves: &mut Vec<(ContractAddress, _)>| -> Result<(), DeoxysStorageError> {
+        contract_trie.update(leaves.iter().map(|(address, leaf)| (address, *leaf)).collect())?;
+        contract_trie.commit(block_number)?;
+        leaves.clear();
+        Ok(())
+    };
+    for_each_contract(block_number, |address, class_hash, nonce| {
+        storage_trie.init(&address)?;
+        let storage_root = storage_trie.root(&address)?;
+        let class_hash = FieldElement::from_bytes_be(&class_hash.0.0).unwrap();
+        leaves.push((address, contract_state_hash(class_hash, storage_root, nonce)));
+        if leaves.len() >= batch_size {
+            commit_leaves(&mut leaves)?;
+        }
+        Ok::<_, DeoxysStorageError>(())
+    })?;
+    commit_leaves(&mut leaves)?;
+    storage_trie.commit(block_number)?;
+
+    let contract_trie_root = contract_trie.root()?;
+    let class_trie_root = storage_handler::class_trie_mut().root()?;
+    drop((contract_trie, storage_trie));
+    // the tries are read from this snapshot from now on, as they all committed the block
+    DeoxysBackend::snapshot_tries(block_number);
+    DeoxysBackend::meta().write_tries_block(block_number)?;
+
+    let state_root = calculate_state_root::<PoseidonHasher>(contract_trie_root.into(), class_trie_root.into());
+    Ok(ImportedState { state_root: state_root.into(), contracts, classes })
+}
+
+/// Entries of a snapshot waiting to be stored.
+#[derive(Default)]
+struct ImportBatch {
+    contracts: Vec<(ContractAddress, ClassHash, Nonce)>,
+    storage: Vec<(ContractAddress, StorageKey, StarkFelt)>,
+    classes: Vec<(ContractClassData, Option<CompiledClassHash>)>,
+}
+
+impl ImportBatch {
+    fn len(&self) -> usize {
+        self.contracts.len() + self.storage.len() + self.classes.len()
+    }
+
+    /// Stores the entries of the batch at `block_number` and adds them to the storage and class
+    /// tries.
+    fn store(&mut self, block_number: u64) -> Result<(), DeoxysStorageError> {
+        let contract_data = storage_handler::contract_data_mut();
+        for (address, class_hash, nonce) in self.contracts.drain(..) {
+            contract_data.insert(address, (Some(class_hash), Some(nonce)))?;
+        }
+        contract_data.commit(block_number)?;
+
+        let contract_storage = storage_handler::contract_storage_mut();
+        let mut storage_trie = storage_handler::contract_storage_trie_mut();
+        for (address, key, value) in self.storage.drain(..) {
+            storage_trie.init(&address)?;
+            storage_trie.insert(address, key, value)?;
+            contract_storage.insert((address, key), value)?;
+        }
+        storage_trie.commit(block_number)?;
+        contract_storage.commit(block_number)?;
+
+        let class_data = storage_handler::contract_class_data_mut();
+        let class_hashes = storage_handler::contract_class_hashes_mut();
+        let mut class_leaves = Vec::new();
+        for (ContractClassData { hash, contract_class }, compiled_class_hash) in self.classes.drain(..) {
+            let ContractClassWrapper { contract, abi, sierra_program_length, abi_length } = contract_class;
+            class_data.insert(
+                hash,
+                StorageContractClassData { contract_class: contract, abi, sierra_program_length, abi_length },
+            )?;
+            if let Some(compiled_class_hash) = compiled_class_hash {
+                class_hashes.insert(hash, compiled_class_hash)?;
+                class_leaves.push((hash, class_leaf_hash(&compiled_class_hash)));
+            }
+        }
+        class_data.commit(block_number)?;
+        class_hashes.commit(block_number)?;
+
+        let mut class_trie = storage_handler::class_trie_mut();
+        class_trie.init()?;
+        class_trie.update(class_leaves.iter().map(|(class_hash, leaf)| (class_hash, *leaf)).collect())?;
+        class_trie.commit(block_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::state::cached_state::CommitmentStateDiff;
+    use indexmap::IndexMap;
+    use mc_db::state_snapshot::{StateSnapshotHeader, StateSnapshotWriter, STATE_SNAPSHOT_VERSION};
+    use mc_db::storage_handler::StorageView;
+    use starknet_api::core::PatriciaKey;
+
+    use super::*;
+    use crate::commitments::lib::update_state_root;
+
+    #[test]
+    fn imported_state_root_does_not_depend_on_batches() {
+        let _db = DeoxysBackend::open_for_testing();
+        let dir = std::env::temp_dir().join(format!("deoxys-state-snapshot-import-{}", std::process::id()));
+        let felt = |n: u64| StarkFelt::from(n);
+        let address = |n: u64| ContractAddress(PatriciaKey(felt(n)));
+        let key = |n: u64| StorageKey(PatriciaKey(felt(n)));
+
+        // two contracts, one of them without storage, and the block hash contract, which has no class
+        let contracts = [
+            (address(0x100), ClassHash(felt(0x10)), Nonce(felt(1))),
+            (address(0x200), ClassHash(felt(0x20)), Nonce::default()),
+        ];
+        let storage =
+            [(address(0x100), key(1), felt(5)), (address(0x100), key(2), felt(6)), (address(1), key(9), felt(0x99))];
+
+        let header = StateSnapshotHeader {
+            version: STATE_SNAPSHOT_VERSION,
+            block_number: 10,
+            block_hash: felt(1),
+            global_state_root: felt(2),
+            l1_block_number: 100,
+        };
+        let mut writer = StateSnapshotWriter::create(&dir, header).unwrap();
+        for (address, class_hash, nonce) in contracts {
+            writer.write(&StateSnapshotEntry::Contract { address, class_hash, nonce }).unwrap();
+        }
+        for (address, key, value) in storage {
+            writer.write(&StateSnapshotEntry::Storage { address, key, value }).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let batched = import_state(&dir, 10, 1).unwrap();
+        let whole = import_state(&dir, 11, IMPORT_BATCH).unwrap();
+        assert_eq!(batched.state_root, whole.state_root);
+        assert_eq!((whole.contracts, whole.classes), (2, 0));
+        let contract_storage = storage_handler::contract_storage();
+        assert_eq!(contract_storage.get(&(address(1), key(9))).unwrap(), Some(felt(0x99)));
+
+        // the sync yields the same root for the same state
+        let mut csd = CommitmentStateDiff {
+            address_to_class_hash: IndexMap::new(),
+            address_to_nonce: IndexMap::new(),
+            storage_updates: IndexMap::new(),
+            class_hash_to_compiled_class_hash: IndexMap::new(),
+        };
+        for (address, class_hash, nonce) in contracts {
+            csd.address_to_class_hash.insert(address, class_hash);
+            csd.address_to_nonce.insert(address, nonce);
+            csd.storage_updates.entry(address).or_default();
+        }
+        for (address, key, value) in storage {
+            csd.storage_updates.entry(address).or_default().insert(key, value);
+        }
+        let state_root: StarkHash = update_state_root(csd, 12).into();
+        assert_eq!(state_root, whole.state_root);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,14 @@
 //! Converts types from [`starknet_providers`] to deoxys's expected types.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::num::NonZeroU128;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use blockifier::blockifier::block::GasPrices;
 use mp_block::DeoxysBlock;
 use mp_felt::Felt252Wrapper;
+use serde::{Deserialize, Serialize};
 use starknet_api::hash::StarkFelt;
 use starknet_api::transaction::{
     DeclareTransaction, DeployAccountTransaction, DeployAccountTransactionV1, DeployTransaction, Event,
@@ -23,28 +25,129 @@ use starknet_providers::sequencer::models::state_update::{
 use starknet_providers::sequencer::models::{self as p, StateUpdate as StateUpdateProvider};
 
 use crate::commitments::lib::calculate_commitments;
+use crate::metrics::conversion_metrics;
 use crate::utility::get_config;
 
-pub async fn block(block: p::Block) -> DeoxysBlock {
+/// The number of conversion errors kept for the admin diagnostics.
+pub const CONVERSION_ERRORS_KEPT: usize = 32;
+
+/// The last conversion errors, oldest first.
+static CONVERSION_ERRORS: Mutex<VecDeque<ConversionError>> = Mutex::new(VecDeque::new());
+
+/// A block served by the gateway which could not be converted, located down to the offending
+/// field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversionError {
+    /// `None` if the block number itself is missing.
+    pub block_number: Option<u64>,
+    /// The index of the offending transaction in the block, if the field belongs to a transaction.
+    pub transaction_index: Option<usize>,
+    pub field: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "field `{}`", self.field)?;
+        if let Some(transaction_index) = self.transaction_index {
+            write!(f, " of transaction {transaction_index}")?;
+        }
+        match self.block_number {
+            Some(block_number) => write!(f, " of block {block_number}")?,
+            None => write!(f, " of a block")?,
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// A field which could not be converted, before it is located in its block.
+#[derive(Debug)]
+struct FieldError {
+    transaction_index: Option<usize>,
+    field: &'static str,
+    reason: String,
+}
+
+impl FieldError {
+    fn new(field: &'static str, reason: impl Into<String>) -> Self {
+        Self { transaction_index: None, field, reason: reason.into() }
+    }
+
+    fn in_transaction(self, transaction_index: usize) -> Self {
+        Self { transaction_index: Some(transaction_index), ..self }
+    }
+
+    fn in_block(self, block_number: Option<u64>) -> ConversionError {
+        ConversionError {
+            block_number,
+            transaction_index: self.transaction_index,
+            field: self.field.to_string(),
+            reason: self.reason,
+        }
+    }
+}
+
+/// Unwraps a field the gateway may omit, but which the conversion requires.
+fn required<T>(value: Option<T>, field: &'static str) -> Result<T, FieldError> {
+    value.ok_or_else(|| FieldError::new(field, "missing"))
+}
+
+/// Returns the last conversion errors, oldest first, at most [CONVERSION_ERRORS_KEPT].
+pub fn recent_conversion_errors() -> Vec<ConversionError> {
+    CONVERSION_ERRORS.lock().expect("Failed to acquire lock on the conversion errors").iter().cloned().collect()
+}
+
+/// Logs, counts and keeps a conversion error for the admin diagnostics.
+///
+/// An error which is already kept is not recorded again, as the pending block is converted again
+/// on every poll until the next block is closed.
+fn record(error: &ConversionError) {
+    let mut errors = CONVERSION_ERRORS.lock().expect("Failed to acquire lock on the conversion errors");
+    if errors.contains(error) {
+        return;
+    }
+
+    log::warn!("❗ Failed to convert {error}");
+    if let Some(metrics) = conversion_metrics() {
+        metrics.conversion_errors.with_label_values(&[&error.field]).inc();
+    }
+    if errors.len() == CONVERSION_ERRORS_KEPT {
+        errors.pop_front();
+    }
+    errors.push_back(error.clone());
+}
+
+pub async fn block(block: p::Block) -> Result<DeoxysBlock, ConversionError> {
     tokio::task::spawn_blocking(|| convert_block_sync(block)).await.expect("join error")
 }
 
-pub fn convert_block_sync(block: p::Block) -> DeoxysBlock {
+pub fn convert_block_sync(block: p::Block) -> Result<DeoxysBlock, ConversionError> {
+    let block_number = block.block_number;
+    convert_block_fields(block).map_err(|e| {
+        let error = e.in_block(block_number);
+        record(&error);
+        error
+    })
+}
+
+fn convert_block_fields(block: p::Block) -> Result<DeoxysBlock, FieldError> {
     // converts starknet_provider transactions and events to mp_transactions and starknet_api events
-    let transactions = transactions(block.transactions);
+    let transactions = transactions(block.transactions)?;
     let events = events(&block.transaction_receipts);
     let parent_block_hash = felt(block.parent_block_hash);
-    let block_number = block.block_number.expect("no block number provided");
+    let block_number = required(block.block_number, "block_number")?;
     let block_timestamp = block.timestamp;
-    let global_state_root = felt(block.state_root.expect("no state root provided"));
+    let global_state_root = felt(required(block.state_root, "state_root")?);
     let sequencer_address = block.sequencer_address.map_or(contract_address(FieldElement::ZERO), contract_address);
     let transaction_count = transactions.len() as u128;
     let event_count = events.len() as u128;
 
     let (transaction_commitment, event_commitment) = commitments(&transactions, &events, block_number);
 
-    let protocol_version = starknet_version(&block.starknet_version);
-    let l1_gas_price = resource_price(block.l1_gas_price, block.l1_data_gas_price);
+    let protocol_version = starknet_version(&block.starknet_version)?;
+    let l1_gas_price = resource_price(block.l1_gas_price, block.l1_data_gas_price)?;
     let l1_da_mode = l1_da_mode(block.l1_da_mode);
     let extra_data = block.block_hash.map(|h| sp_core::U256::from_big_endian(&h.to_bytes_be()));
 
@@ -72,27 +175,27 @@ pub fn convert_block_sync(block: p::Block) -> DeoxysBlock {
         .map(|(i, r)| mp_block::OrderedEvents::new(i as u128, r.events.iter().map(event).collect()))
         .collect();
 
-    DeoxysBlock::new(header, transactions, ordered_events)
+    Ok(DeoxysBlock::new(header, transactions, ordered_events))
 }
 
-fn transactions(txs: Vec<p::TransactionType>) -> Vec<Transaction> {
-    txs.into_iter().map(transaction).collect()
+fn transactions(txs: Vec<p::TransactionType>) -> Result<Vec<Transaction>, FieldError> {
+    txs.into_iter().enumerate().map(|(i, tx)| transaction(tx).map_err(|e| e.in_transaction(i))).collect()
 }
 
-fn transaction(transaction: p::TransactionType) -> Transaction {
-    match transaction {
-        p::TransactionType::Declare(tx) => Transaction::Declare(declare_transaction(tx)),
+fn transaction(transaction: p::TransactionType) -> Result<Transaction, FieldError> {
+    Ok(match transaction {
+        p::TransactionType::Declare(tx) => Transaction::Declare(declare_transaction(tx)?),
         p::TransactionType::Deploy(tx) => Transaction::Deploy(deploy_transaction(tx)),
-        p::TransactionType::DeployAccount(tx) => Transaction::DeployAccount(deploy_account_transaction(tx)),
-        p::TransactionType::InvokeFunction(tx) => Transaction::Invoke(invoke_transaction(tx)),
+        p::TransactionType::DeployAccount(tx) => Transaction::DeployAccount(deploy_account_transaction(tx)?),
+        p::TransactionType::InvokeFunction(tx) => Transaction::Invoke(invoke_transaction(tx)?),
         p::TransactionType::L1Handler(tx) => Transaction::L1Handler(l1_handler_transaction(tx)),
-    }
+    })
 }
 
-fn declare_transaction(tx: p::DeclareTransaction) -> DeclareTransaction {
-    if tx.version == FieldElement::ZERO || tx.version == FieldElement::ONE {
+fn declare_transaction(tx: p::DeclareTransaction) -> Result<DeclareTransaction, FieldError> {
+    Ok(if tx.version == FieldElement::ZERO || tx.version == FieldElement::ONE {
         DeclareTransaction::V1(starknet_api::transaction::DeclareTransactionV0V1 {
-            max_fee: fee(tx.max_fee.expect("no max fee provided")),
+            max_fee: fee(required(tx.max_fee, "max_fee")?)?,
             signature: signature(tx.signature),
             nonce: nonce(tx.nonce),
             class_hash: class_hash(tx.class_hash),
@@ -100,36 +203,39 @@ fn declare_transaction(tx: p::DeclareTransaction) -> DeclareTransaction {
         })
     } else if tx.version == FieldElement::TWO {
         DeclareTransaction::V2(starknet_api::transaction::DeclareTransactionV2 {
-            max_fee: fee(tx.max_fee.expect("no max fee provided")),
+            max_fee: fee(required(tx.max_fee, "max_fee")?)?,
             signature: signature(tx.signature),
             nonce: nonce(tx.nonce),
             class_hash: class_hash(tx.class_hash),
-            compiled_class_hash: compiled_class_hash(tx.compiled_class_hash.expect("no compiled class hash provided")),
+            compiled_class_hash: compiled_class_hash(required(tx.compiled_class_hash, "compiled_class_hash")?),
             sender_address: contract_address(tx.sender_address),
         })
     } else if tx.version == FieldElement::THREE {
         DeclareTransaction::V3(starknet_api::transaction::DeclareTransactionV3 {
-            resource_bounds: resource_bounds(tx.resource_bounds.expect("no resource bounds provided")),
-            tip: tip(tx.tip.expect("no tip provided")),
+            resource_bounds: resource_bounds(required(tx.resource_bounds, "resource_bounds")?)?,
+            tip: tip(required(tx.tip, "tip")?),
             signature: signature(tx.signature),
             nonce: nonce(tx.nonce),
             class_hash: class_hash(tx.class_hash),
-            compiled_class_hash: compiled_class_hash(tx.compiled_class_hash.expect("no compiled class hash provided")),
+            compiled_class_hash: compiled_class_hash(required(tx.compiled_class_hash, "compiled_class_hash")?),
             sender_address: contract_address(tx.sender_address),
-            nonce_data_availability_mode: data_availability_mode(
-                tx.nonce_data_availability_mode.expect("no nonce_data_availability_mode provided"),
-            ),
-            fee_data_availability_mode: data_availability_mode(
-                tx.fee_data_availability_mode.expect("no fee_data_availability_mode provided"),
-            ),
-            paymaster_data: paymaster_data(tx.paymaster_data.expect("no paymaster_data provided")),
-            account_deployment_data: account_deployment_data(
-                tx.account_deployment_data.expect("no account_deployment_data provided"),
-            ),
+            nonce_data_availability_mode: data_availability_mode(required(
+                tx.nonce_data_availability_mode,
+                "nonce_data_availability_mode",
+            )?),
+            fee_data_availability_mode: data_availability_mode(required(
+                tx.fee_data_availability_mode,
+                "fee_data_availability_mode",
+            )?),
+            paymaster_data: paymaster_data(required(tx.paymaster_data, "paymaster_data")?),
+            account_deployment_data: account_deployment_data(required(
+                tx.account_deployment_data,
+                "account_deployment_data",
+            )?),
         })
     } else {
-        panic!("declare transaction version not supported");
-    }
+        return Err(unsupported_version("declare", tx.version));
+    })
 }
 
 fn deploy_transaction(tx: p::DeployTransaction) -> DeployTransaction {
@@ -141,10 +247,10 @@ fn deploy_transaction(tx: p::DeployTransaction) -> DeployTransaction {
     }
 }
 
-fn deploy_account_transaction(tx: p::DeployAccountTransaction) -> DeployAccountTransaction {
-    match deploy_account_transaction_version(&tx) {
+fn deploy_account_transaction(tx: p::DeployAccountTransaction) -> Result<DeployAccountTransaction, FieldError> {
+    Ok(match deploy_account_transaction_version(&tx) {
         1 => DeployAccountTransaction::V1(DeployAccountTransactionV1 {
-            max_fee: fee(tx.max_fee.expect("no max fee provided")),
+            max_fee: fee(required(tx.max_fee, "max_fee")?)?,
             signature: signature(tx.signature),
             nonce: nonce(tx.nonce),
             class_hash: class_hash(tx.class_hash),
@@ -153,24 +259,26 @@ fn deploy_account_transaction(tx: p::DeployAccountTransaction) -> DeployAccountT
         }),
 
         3 => DeployAccountTransaction::V3(starknet_api::transaction::DeployAccountTransactionV3 {
-            resource_bounds: resource_bounds(tx.resource_bounds.expect("no resource bounds provided")),
-            tip: tip(tx.tip.expect("no tip provided")),
+            resource_bounds: resource_bounds(required(tx.resource_bounds, "resource_bounds")?)?,
+            tip: tip(required(tx.tip, "tip")?),
             signature: signature(tx.signature),
             nonce: nonce(tx.nonce),
             class_hash: class_hash(tx.class_hash),
             contract_address_salt: contract_address_salt(tx.contract_address_salt),
             constructor_calldata: call_data(tx.constructor_calldata),
-            nonce_data_availability_mode: data_availability_mode(
-                tx.nonce_data_availability_mode.expect("no nonce_data_availability_mode provided"),
-            ),
-            fee_data_availability_mode: data_availability_mode(
-                tx.fee_data_availability_mode.expect("no fee_data_availability_mode provided"),
-            ),
-            paymaster_data: paymaster_data(tx.paymaster_data.expect("no paymaster_data provided")),
+            nonce_data_availability_mode: data_availability_mode(required(
+                tx.nonce_data_availability_mode,
+                "nonce_data_availability_mode",
+            )?),
+            fee_data_availability_mode: data_availability_mode(required(
+                tx.fee_data_availability_mode,
+                "fee_data_availability_mode",
+            )?),
+            paymaster_data: paymaster_data(required(tx.paymaster_data, "paymaster_data")?),
         }),
 
-        _ => panic!("deploy account transaction version not supported"),
-    }
+        _ => return Err(unsupported_version("deploy account", tx.version)),
+    })
 }
 
 // TODO: implement something better than this
@@ -178,45 +286,52 @@ fn deploy_account_transaction_version(tx: &p::DeployAccountTransaction) -> u8 {
     if tx.resource_bounds.is_some() { 3 } else { 1 }
 }
 
-fn invoke_transaction(tx: p::InvokeFunctionTransaction) -> InvokeTransaction {
-    if tx.version == FieldElement::ZERO {
+fn invoke_transaction(tx: p::InvokeFunctionTransaction) -> Result<InvokeTransaction, FieldError> {
+    Ok(if tx.version == FieldElement::ZERO {
         InvokeTransaction::V0(starknet_api::transaction::InvokeTransactionV0 {
-            max_fee: fee(tx.max_fee.expect("no max fee provided")),
+            max_fee: fee(required(tx.max_fee, "max_fee")?)?,
             signature: signature(tx.signature),
             contract_address: contract_address(tx.sender_address),
-            entry_point_selector: entry_point(tx.entry_point_selector.expect("no entry_point_selector provided")),
+            entry_point_selector: entry_point(required(tx.entry_point_selector, "entry_point_selector")?),
             calldata: call_data(tx.calldata),
         })
     } else if tx.version == FieldElement::ONE {
         InvokeTransaction::V1(starknet_api::transaction::InvokeTransactionV1 {
-            max_fee: fee(tx.max_fee.expect("no max fee provided")),
+            max_fee: fee(required(tx.max_fee, "max_fee")?)?,
             signature: signature(tx.signature),
-            nonce: nonce(tx.nonce.expect("no nonce provided")),
+            nonce: nonce(required(tx.nonce, "nonce")?),
             sender_address: contract_address(tx.sender_address),
             calldata: call_data(tx.calldata),
         })
     } else if tx.version == FieldElement::THREE {
         InvokeTransaction::V3(starknet_api::transaction::InvokeTransactionV3 {
-            resource_bounds: resource_bounds(tx.resource_bounds.expect("no resource bounds provided")),
-            tip: tip(tx.tip.expect("no tip provided")),
+            resource_bounds: resource_bounds(required(tx.resource_bounds, "resource_bounds")?)?,
+            tip: tip(required(tx.tip, "tip")?),
             signature: signature(tx.signature),
-            nonce: nonce(tx.nonce.expect("no nonce provided")),
+            nonce: nonce(required(tx.nonce, "nonce")?),
             sender_address: contract_address(tx.sender_address),
             calldata: call_data(tx.calldata),
-            nonce_data_availability_mode: data_availability_mode(
-                tx.nonce_data_availability_mode.expect("no nonce_data_availability_mode provided"),
-            ),
-            fee_data_availability_mode: data_availability_mode(
-                tx.fee_data_availability_mode.expect("no fee_data_availability_mode provided"),
-            ),
-            paymaster_data: paymaster_data(tx.paymaster_data.expect("no paymaster_data provided")),
-            account_deployment_data: account_deployment_data(
-                tx.account_deployment_data.expect("no account_deployment_data provided"),
-            ),
+            nonce_data_availability_mode: data_availability_mode(required(
+                tx.nonce_data_availability_mode,
+                "nonce_data_availability_mode",
+            )?),
+            fee_data_availability_mode: data_availability_mode(required(
+                tx.fee_data_availability_mode,
+                "fee_data_availability_mode",
+            )?),
+            paymaster_data: paymaster_data(required(tx.paymaster_data, "paymaster_data")?),
+            account_deployment_data: account_deployment_data(required(
+                tx.account_deployment_data,
+                "account_deployment_data",
+            )?),
         })
     } else {
-        panic!("invoke transaction version not supported");
-    }
+        return Err(unsupported_version("invoke", tx.version));
+    })
+}
+
+fn unsupported_version(kind: &str, version: FieldElement) -> FieldError {
+    FieldError::new("version", format!("{kind} transaction version {version} is not supported"))
 }
 
 fn l1_handler_transaction(tx: p::L1HandlerTransaction) -> L1HandlerTransaction {
@@ -230,18 +345,18 @@ fn l1_handler_transaction(tx: p::L1HandlerTransaction) -> L1HandlerTransaction {
 }
 
 /// Converts a starknet version string to a felt value.
-/// If the string contains more than 31 bytes, the function panics.
-fn starknet_version(version: &Option<String>) -> Felt252Wrapper {
+/// If the string contains more than 31 bytes, the conversion fails.
+fn starknet_version(version: &Option<String>) -> Result<Felt252Wrapper, FieldError> {
     match version {
-        Some(version) => {
-            Felt252Wrapper::try_from(version.as_bytes()).expect("Failed to convert version to felt: string is too long")
-        }
-        None => Felt252Wrapper::ZERO,
+        Some(version) => Felt252Wrapper::try_from(version.as_bytes())
+            .map_err(|_| FieldError::new("starknet_version", format!("'{version}' is longer than 31 bytes"))),
+        None => Ok(Felt252Wrapper::ZERO),
     }
 }
 
-fn fee(felt: starknet_ff::FieldElement) -> starknet_api::transaction::Fee {
-    starknet_api::transaction::Fee(felt.try_into().expect("Value out of range for u128"))
+fn fee(felt: starknet_ff::FieldElement) -> Result<starknet_api::transaction::Fee, FieldError> {
+    let fee = felt.try_into().map_err(|_| FieldError::new("max_fee", format!("{felt:#x} is out of range for u128")))?;
+    Ok(starknet_api::transaction::Fee(fee))
 }
 
 fn signature(signature: Vec<starknet_ff::FieldElement>) -> starknet_api::transaction::TransactionSignature {
@@ -284,7 +399,7 @@ fn transaction_version(version: starknet_ff::FieldElement) -> starknet_api::tran
 
 fn resource_bounds(
     ressource_bounds: starknet_providers::sequencer::models::ResourceBoundsMapping,
-) -> starknet_api::transaction::ResourceBoundsMapping {
+) -> Result<starknet_api::transaction::ResourceBoundsMapping, FieldError> {
     starknet_api::transaction::ResourceBoundsMapping::try_from(vec![
         (
             starknet_api::transaction::Resource::L1Gas,
@@ -301,7 +416,7 @@ fn resource_bounds(
             },
         ),
     ])
-    .expect("Failed to convert resource bounds")
+    .map_err(|e| FieldError::new("resource_bounds", e.to_string()))
 }
 
 fn tip(tip: u64) -> starknet_api::transaction::Tip {
@@ -337,26 +452,37 @@ fn account_deployment_data(
 fn resource_price(
     l1_gas_price: starknet_core::types::ResourcePrice,
     l1_data_gas_price: starknet_core::types::ResourcePrice,
-) -> Option<GasPrices> {
+) -> Result<Option<GasPrices>, FieldError> {
     /// Converts a FieldElement to a NonZeroU128, with 0 being converted to 1.
-    fn field_element_to_non_zero_u128(field_element: FieldElement) -> NonZeroU128 {
+    fn field_element_to_non_zero_u128(
+        field_element: FieldElement,
+        field: &'static str,
+    ) -> Result<NonZeroU128, FieldError> {
         let value: u128 = if field_element == FieldElement::ZERO {
             1
         } else {
-            field_element.try_into().expect("FieldElement is more than u128")
+            field_element
+                .try_into()
+                .map_err(|_| FieldError::new(field, format!("{field_element:#x} is out of range for u128")))?
         };
-        NonZeroU128::new(value).expect("Failed to convert field_element to NonZeroU128")
+        Ok(NonZeroU128::new(value).expect("Failed to convert field_element to NonZeroU128"))
     }
 
     if l1_gas_price.price_in_wei == FieldElement::ZERO {
-        None
+        Ok(None)
     } else {
-        Some(GasPrices {
-            eth_l1_gas_price: field_element_to_non_zero_u128(l1_gas_price.price_in_wei),
-            strk_l1_gas_price: field_element_to_non_zero_u128(l1_gas_price.price_in_fri),
-            eth_l1_data_gas_price: field_element_to_non_zero_u128(l1_data_gas_price.price_in_wei),
-            strk_l1_data_gas_price: field_element_to_non_zero_u128(l1_data_gas_price.price_in_fri),
-        })
+        Ok(Some(GasPrices {
+            eth_l1_gas_price: field_element_to_non_zero_u128(l1_gas_price.price_in_wei, "l1_gas_price.price_in_wei")?,
+            strk_l1_gas_price: field_element_to_non_zero_u128(l1_gas_price.price_in_fri, "l1_gas_price.price_in_fri")?,
+            eth_l1_data_gas_price: field_element_to_non_zero_u128(
+                l1_data_gas_price.price_in_wei,
+                "l1_data_gas_price.price_in_wei",
+            )?,
+            strk_l1_data_gas_price: field_element_to_non_zero_u128(
+                l1_data_gas_price.price_in_fri,
+                "l1_data_gas_price.price_in_fri",
+            )?,
+        }))
     }
 }
 
@@ -481,3 +607,25 @@ fn nonces(nonces: HashMap<FieldElement, FieldElement>) -> Vec<NonceUpdate> {
     // and not `nonce` -> `contract_address`
     nonces.into_iter().map(|(contract_address, nonce)| NonceUpdate { contract_address, nonce }).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_locate_the_offending_field() {
+        let error = required::<FieldElement>(None, "max_fee").unwrap_err().in_transaction(3).in_block(Some(12));
+        assert_eq!(error.to_string(), "field `max_fee` of transaction 3 of block 12: missing");
+
+        let error = required::<u64>(None, "block_number").unwrap_err().in_block(None);
+        assert_eq!(error.to_string(), "field `block_number` of a block: missing");
+    }
+
+    #[test]
+    fn errors_are_recorded_once() {
+        let error = FieldError::new("l1_gas_price", "overflow").in_block(Some(2772));
+        record(&error);
+        record(&error);
+        assert_eq!(recent_conversion_errors().iter().filter(|recorded| **recorded == error).count(), 1);
+    }
+}
@@ -0,0 +1,119 @@
+//! Detects stalls in the L2 sync pipeline.
+//!
+//! The apply task reports its progress to a [`PipelineProbe`]. A [`watchdog`] polls this probe and
+//! resolves once no block has been applied for longer than the configured timeout while the
+//! gateway head keeps moving forward, at which point the pipeline is torn down and restarted from
+//! the last applied block.
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc::WeakSender;
+
+use crate::state::SyncState;
+
+/// The stage of the apply task currently being executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PipelineStage {
+    /// Waiting for the next fetched block.
+    Waiting = 0,
+    /// Converting the block and verifying the state root.
+    Converting = 1,
+    /// Storing the state and class updates, and sealing the block.
+    Applying = 2,
+}
+
+impl From<u8> for PipelineStage {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => PipelineStage::Converting,
+            2 => PipelineStage::Applying,
+            _ => PipelineStage::Waiting,
+        }
+    }
+}
+
+/// Shared progress report of the apply task.
+pub struct PipelineProbe {
+    last_applied: AtomicU64,
+    last_applied_at: Mutex<Instant>,
+    stage: AtomicU8,
+}
+
+impl PipelineProbe {
+    pub fn new(last_applied: u64) -> Self {
+        Self {
+            last_applied: AtomicU64::new(last_applied),
+            last_applied_at: Mutex::new(Instant::now()),
+            stage: AtomicU8::new(PipelineStage::Waiting as u8),
+        }
+    }
+
+    /// Marks the apply task as having entered `stage`.
+    pub fn enter(&self, stage: PipelineStage) {
+        self.stage.store(stage as u8, Ordering::Relaxed);
+    }
+
+    /// Records that `block_n` has been fully applied.
+    pub fn applied(&self, block_n: u64) {
+        self.last_applied.store(block_n, Ordering::Relaxed);
+        *self.last_applied_at.lock().expect("Failed to acquire lock on last_applied_at") = Instant::now();
+        self.enter(PipelineStage::Waiting);
+    }
+
+    pub fn last_applied(&self) -> u64 {
+        self.last_applied.load(Ordering::Relaxed)
+    }
+
+    pub fn stage(&self) -> PipelineStage {
+        self.stage.load(Ordering::Relaxed).into()
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.last_applied_at.lock().expect("Failed to acquire lock on last_applied_at").elapsed()
+    }
+}
+
+/// Resolves once the pipeline is considered stalled, after having logged diagnostics.
+///
+/// The pipeline is stalled when no block was applied for `timeout` while the gateway head is
+/// ahead of the last applied block. A node which is simply caught up with the tip is never
+/// considered stalled.
+///
+/// * `fetch_queue`: handle on the fetched blocks queue, used to report its occupancy.
+pub async fn watchdog<T>(
+    probe: &PipelineProbe,
+    sync_state: &SyncState,
+    fetch_queue: WeakSender<T>,
+    timeout: Duration,
+) -> u64 {
+    let mut interval = tokio::time::interval(timeout.min(Duration::from_secs(10)));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        let last_applied = probe.last_applied();
+        let (_, highest_block) = sync_state.highest_block_hash_and_number();
+        if probe.elapsed() < timeout || highest_block <= last_applied {
+            continue;
+        }
+
+        let occupancy = match fetch_queue.upgrade() {
+            Some(sender) => format!("{}/{}", sender.max_capacity() - sender.capacity(), sender.max_capacity()),
+            None => "closed".to_string(),
+        };
+        log::warn!(
+            "🐕 Sync stalled: no block applied for {:?} (last applied: #{}, gateway head: #{}, stage: {:?}, fetch \
+             queue: {}), restarting pipeline",
+            probe.elapsed(),
+            last_applied,
+            highest_block,
+            probe.stage(),
+            occupancy
+        );
+
+        return last_applied;
+    }
+}
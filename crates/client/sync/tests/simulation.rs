@@ -0,0 +1,66 @@
+//! The fault paths of the fetch layer, driven deterministically by a simulation.
+
+use std::sync::Arc;
+
+use mc_sync::fetch::provider_pool::{PooledProvider, ProviderPool};
+use mc_sync::fetch::simulation::Simulation;
+use starknet_ff::FieldElement;
+use starknet_providers::ProviderError;
+use url::Url;
+
+fn pool(simulation: &Arc<Simulation>, feeder_gateways: &[&str]) -> ProviderPool {
+    let gateway = Url::parse("http://gateway.test/gateway").unwrap();
+    ProviderPool::new(
+        feeder_gateways
+            .iter()
+            .map(|url| PooledProvider::new(gateway.clone(), Url::parse(url).unwrap(), FieldElement::ZERO, None))
+            .collect(),
+    )
+    .with_simulation(Arc::clone(simulation))
+}
+
+/// The hash of a block of the real chain.
+fn hash(block_number: u64) -> FieldElement {
+    FieldElement::from(block_number + 100)
+}
+
+#[tokio::test]
+async fn error_bursts_fall_back_and_recover() {
+    let simulation = Arc::new(Simulation::new());
+    let pool = pool(&simulation, &["http://a.test/", "http://b.test/"]);
+    let request = |_: &PooledProvider| async { Ok::<_, ProviderError>(()) };
+
+    simulation.inject_error_burst(3);
+    // every gateway of the pool fails
+    assert!(matches!(pool.request(request).await, Err(ProviderError::RateLimited)));
+    assert_eq!(simulation.failures_injected(), 2);
+
+    // the request falls back to the next gateway once the first one fails
+    assert!(pool.request(request).await.is_ok());
+    assert_eq!(simulation.failures_injected(), 3);
+    assert_eq!(pool.health().iter().map(|(_, health)| health.failures).sum::<u64>(), 3);
+
+    // the burst is over
+    assert!(pool.request(request).await.is_ok());
+    assert_eq!(simulation.failures_injected(), 3);
+}
+
+#[test]
+fn reorgs_are_served_until_the_common_ancestor_is_fetched() {
+    let simulation = Simulation::new();
+    let served = |block_number| simulation.served_hashes(block_number, hash(block_number), hash(block_number - 1));
+
+    simulation.inject_reorg(10, 3);
+    // block #10 no longer extends the local chain
+    assert_ne!(served(10).1, hash(9));
+    assert!(simulation.is_reorg_pending());
+
+    // walking back, the blocks of the fork differ from the local ones down to the common ancestor
+    let ancestor = (1..10).rev().find(|&block_number| served(block_number).0 == hash(block_number));
+    assert_eq!(ancestor, Some(6));
+    assert_eq!(simulation.reorgs_resolved(), 1);
+
+    // the real chain is served again while syncing from the common ancestor
+    assert_eq!(served(7), (hash(7), hash(6)));
+    assert_eq!(served(10), (hash(10), hash(9)));
+}
@@ -1,14 +1,24 @@
 use deoxys_runtime::{AuraConfig, GrandpaConfig, RuntimeGenesisConfig, SealingMode, SystemConfig, WASM_BINARY};
+use mc_sync::network::NetworkProfile;
 use pallet_starknet::genesis_loader::GenesisData;
 use pallet_starknet::GenesisConfig;
-use sc_service::ChainType;
+use sc_service::{ChainType, Properties};
 use serde::{Deserialize, Serialize};
 use sp_core::storage::Storage;
 use sp_state_machine::BasicExternalities;
+use starknet_core::types::FieldElement;
+use starknet_core::utils::parse_cairo_short_string;
 use starknet_providers::sequencer::models::BlockId;
 use starknet_providers::SequencerGatewayProvider;
 use tokio::runtime::Runtime;
 
+/// Chain spec property holding the chain id of the chain, as a short string such as `SN_MAIN`. It
+/// must match the chain id of the selected network.
+pub const CHAIN_ID_PROPERTY: &str = "starknetChainId";
+
+/// Chain spec property holding the version of the Starknet RPC specification served by the node.
+pub const SPEC_VERSION_PROPERTY: &str = "starknetSpecVersion";
+
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = sc_service::GenericChainSpec<RuntimeGenesisConfig>;
 
@@ -35,10 +45,42 @@ impl sp_runtime::BuildStorage for DevGenesisExt {
     }
 }
 
-pub fn deoxys_config(sealing: SealingMode, chain_id: &str) -> Result<DevChainSpec, String> {
+/// The Starknet parameters found in the properties of a chain spec.
+#[derive(Debug, Default)]
+pub struct StarknetProperties {
+    pub chain_id: Option<FieldElement>,
+    pub spec_version: Option<String>,
+}
+
+impl StarknetProperties {
+    pub fn from_chain_spec(chain_spec: &dyn sc_service::ChainSpec) -> Result<Self, String> {
+        let properties = chain_spec.properties();
+        let string_property = |key: &str| {
+            properties
+                .get(key)
+                .map(|value| value.as_str().map(str::to_string).ok_or_else(|| format!("{key} must be a string")))
+                .transpose()
+        };
+
+        let chain_id = string_property(CHAIN_ID_PROPERTY)?
+            .map(|chain_id| FieldElement::from_byte_slice_be(chain_id.as_bytes()))
+            .transpose()
+            .map_err(|e| format!("invalid {CHAIN_ID_PROPERTY}: {e}"))?;
+        let spec_version = string_property(SPEC_VERSION_PROPERTY)?;
+
+        Ok(Self { chain_id, spec_version })
+    }
+}
+
+pub fn deoxys_config(sealing: SealingMode, chain_id: &str, network: &NetworkProfile) -> Result<DevChainSpec, String> {
     let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
     let genesis_loader = load_genesis_state()?;
 
+    let mut properties = Properties::new();
+    let starknet_chain_id = parse_cairo_short_string(&network.chain_id).map_err(|e| e.to_string())?;
+    properties.insert(CHAIN_ID_PROPERTY.into(), starknet_chain_id.into());
+    properties.insert(SPEC_VERSION_PROPERTY.into(), mc_rpc::SPEC_VERSION.into());
+
     Ok(DevChainSpec::from_genesis(
         // Name
         "Starknet",
@@ -58,7 +100,7 @@ pub fn deoxys_config(sealing: SealingMode, chain_id: &str) -> Result<DevChainSpe
         Some("Starknet"),
         None,
         // Properties
-        None,
+        Some(properties),
         // Extensions
         None,
     ))
@@ -1,4 +1,4 @@
-use crate::commands::ExtendedRunCmd;
+use crate::commands::{DbCmd, ExportContractCmd, ExtendedRunCmd, SnapshotCmd};
 
 #[derive(Debug, clap::Parser)]
 pub struct Cli {
@@ -22,6 +22,18 @@ pub enum Subcommand {
     /// Db meta columns information.
     ChainInfo(sc_cli::ChainInfoCmd),
 
+    /// Inspect the databases of the node, which must be stopped.
+    #[command(subcommand)]
+    Db(DbCmd),
+
+    /// Check the database, the feeder gateways, the L1 endpoint, the clock, the disk space and the
+    /// file descriptor limit with the options the node is to be started with, before a long sync.
+    Doctor,
+
+    /// Print the effective configuration resolved from the config file, the command line and the
+    /// defaults.
+    PrintConfig,
+
     /// Validate blocks.
     CheckBlock(sc_cli::CheckBlockCmd),
 
@@ -31,6 +43,9 @@ pub enum Subcommand {
     /// Export the state of a given block into a chain spec.
     ExportState(sc_cli::ExportStateCmd),
 
+    /// Export the class hash, nonce, storage and class of a contract at a given block as JSON.
+    ExportContract(ExportContractCmd),
+
     /// Import blocks.
     ImportBlocks(sc_cli::ImportBlocksCmd),
 
@@ -44,6 +59,10 @@ pub enum Subcommand {
     /// Revert the chain to a previous state.
     Revert(sc_cli::RevertCmd),
 
+    /// Publish snapshots of the databases to object storage, or bootstrap the node from one.
+    #[command(subcommand)]
+    Snapshot(SnapshotCmd),
+
     /// Try some command against runtime state.
     #[cfg(feature = "try-runtime")]
     TryRuntime(try_runtime_cli::TryRuntimeCmd),
@@ -4,7 +4,7 @@ use sc_cli::{ChainSpec, SubstrateCli};
 
 use crate::benchmarking::{inherent_benchmark_data, RemarkBuilder};
 use crate::cli::{Cli, Subcommand};
-use crate::commands::run_node;
+use crate::commands::{expand_args, print_config, revert, run_doctor, run_node, DbCmd, SnapshotCmd};
 use crate::{chain_spec, service};
 
 impl SubstrateCli for Cli {
@@ -36,7 +36,7 @@ impl SubstrateCli for Cli {
         Ok(match id {
             "starknet" => {
                 let sealing = self.run.sealing.map(Into::into).unwrap_or_default();
-                Box::new(chain_spec::deoxys_config(sealing, id)?)
+                Box::new(chain_spec::deoxys_config(sealing, id, &self.run.network)?)
             }
             path_or_url => Box::new(chain_spec::ChainSpec::from_json_file(std::path::PathBuf::from(path_or_url))?),
         })
@@ -45,7 +45,8 @@ impl SubstrateCli for Cli {
 
 /// Parse and run command line arguments
 pub fn run() -> sc_cli::Result<()> {
-    let cli = Cli::from_args();
+    let args = expand_args(std::env::args_os().collect()).map_err(sc_cli::Error::Input)?;
+    let cli = Cli::from_iter(args.clone());
 
     match cli.subcommand {
         Some(Subcommand::Key(ref cmd)) => cmd.run(&cli),
@@ -56,28 +57,41 @@ pub fn run() -> sc_cli::Result<()> {
         Some(Subcommand::CheckBlock(ref cmd)) => {
             let runner = cli.create_runner(cmd)?;
             runner.async_run(|mut config| {
-                let (client, _, import_queue, task_manager, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                let (client, _, import_queue, task_manager, _) =
+                    service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
                 Ok((cmd.run(client, import_queue), task_manager))
             })
         }
         Some(Subcommand::ExportBlocks(ref cmd)) => {
             let runner = cli.create_runner(cmd)?;
             runner.async_run(|mut config| {
-                let (client, _, _, task_manager, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                let (client, _, _, task_manager, _) =
+                    service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
                 Ok((cmd.run(client, config.database), task_manager))
             })
         }
         Some(Subcommand::ExportState(ref cmd)) => {
             let runner = cli.create_runner(cmd)?;
             runner.async_run(|mut config| {
-                let (client, _, _, task_manager, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                let (client, _, _, task_manager, _) =
+                    service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
                 Ok((cmd.run(client, config.chain_spec), task_manager))
             })
         }
+        Some(Subcommand::ExportContract(ref cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.async_run(|mut config| {
+                // the databases are opened along with the chain client
+                let (_, _, _, task_manager, _) =
+                    service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
+                Ok((async move { cmd.run().map_err(sc_cli::Error::Input) }, task_manager))
+            })
+        }
         Some(Subcommand::ImportBlocks(ref cmd)) => {
             let runner = cli.create_runner(cmd)?;
             runner.async_run(|mut config| {
-                let (client, _, import_queue, task_manager, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                let (client, _, import_queue, task_manager, _) =
+                    service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
                 Ok((cmd.run(client, import_queue), task_manager))
             })
         }
@@ -85,8 +99,40 @@ pub fn run() -> sc_cli::Result<()> {
             let runner = cli.create_runner(cmd)?;
             runner.sync_run(|config| cmd.run(config.database))
         }
-        // TODO: This does not handle reverts correctly
-        Some(Subcommand::Revert(ref _cmd)) => Err("Subcommand Revert is not implemented.".into()),
+        Some(Subcommand::Db(DbCmd::Profile(ref cmd))) => {
+            let runner = cli.create_runner(cmd)?;
+            // the lookups per level are only counted by the statistics of the database
+            mc_db::perf::enable_statistics();
+            runner.async_run(|mut config| {
+                let (client, _, _, task_manager, _) =
+                    service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
+                Ok((async move { cmd.run(client).await.map_err(sc_cli::Error::Input) }, task_manager))
+            })
+        }
+        Some(Subcommand::Snapshot(SnapshotCmd::Export(ref cmd))) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.async_run(|mut config| {
+                let (client, _, _, task_manager, _) =
+                    service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
+                Ok((async move { cmd.run(client.as_ref()).map_err(sc_cli::Error::Input) }, task_manager))
+            })
+        }
+        Some(Subcommand::Snapshot(SnapshotCmd::Publish(ref cmd))) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|config| cmd.run(config).map_err(sc_cli::Error::Input))
+        }
+        Some(Subcommand::Snapshot(SnapshotCmd::Bootstrap(ref cmd))) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.sync_run(|config| cmd.run(config).map_err(sc_cli::Error::Input))
+        }
+        Some(Subcommand::Revert(ref cmd)) => {
+            let runner = cli.create_runner(cmd)?;
+            runner.async_run(|mut config| {
+                let (client, backend, _, task_manager, _) =
+                    service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
+                Ok((revert(cmd, client, backend), task_manager))
+            })
+        }
         Some(Subcommand::Benchmark(ref cmd)) => {
             let runner = cli.create_runner(cmd)?;
 
@@ -104,7 +150,8 @@ pub fn run() -> sc_cli::Result<()> {
                         cmd.run::<Block, sp_statement_store::runtime_api::HostFunctions>(config)
                     }
                     BenchmarkCmd::Block(cmd) => {
-                        let (client, _, _, _, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                        let (client, _, _, _, _) =
+                            service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
                         cmd.run(client)
                     }
                     #[cfg(not(feature = "runtime-benchmarks"))]
@@ -113,20 +160,23 @@ pub fn run() -> sc_cli::Result<()> {
                     }
                     #[cfg(feature = "runtime-benchmarks")]
                     BenchmarkCmd::Storage(cmd) => {
-                        let (client, backend, _, _, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                        let (client, backend, _, _, _) =
+                            service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
                         let db = backend.expose_db();
                         let storage = backend.expose_storage();
 
                         cmd.run(config, client, db, storage)
                     }
                     BenchmarkCmd::Overhead(cmd) => {
-                        let (client, _, _, _, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                        let (client, _, _, _, _) =
+                            service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
                         let ext_builder = RemarkBuilder::new(client.clone());
 
                         cmd.run(config, client, inherent_benchmark_data()?, Vec::new(), &ext_builder)
                     }
                     BenchmarkCmd::Extrinsic(cmd) => {
-                        let (client, _, _, _, _) = service::new_chain_ops(&mut config, cli.run.cache)?;
+                        let (client, _, _, _, _) =
+                            service::new_chain_ops(&mut config, cli.run.cache, cli.run.cold_storage())?;
                         // Register the *Remark* builder.
                         let ext_factory = ExtrinsicFactory(vec![Box::new(RemarkBuilder::new(client.clone()))]);
 
@@ -156,6 +206,8 @@ pub fn run() -> sc_cli::Result<()> {
             let runner = cli.create_runner(cmd)?;
             runner.sync_run(|config| cmd.run::<Block>(&config))
         }
+        Some(Subcommand::Doctor) => run_doctor(cli),
+        Some(Subcommand::PrintConfig) => print_config(args).map_err(sc_cli::Error::Input),
         None => run_node(cli),
     }
 }
@@ -0,0 +1,246 @@
+//! `--config` file support.
+//!
+//! A config file is a toml file whose keys are the long names of the command line options, such as
+//! `l1-endpoint` or `rpc-port`. Keys may be grouped in tables (`[sync]`, `[rpc]`, `[db]`, `[l1]`,
+//! `[telemetry]`...) for readability: the table names are ignored, and an option may only be set
+//! once across the tables. Flags are set with booleans (`cache = true`). The options of the file
+//! are expanded into command line arguments, and options also passed on the command line take
+//! precedence over the file.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command, CommandFactory};
+use toml::{Table, Value};
+
+use crate::cli::Cli;
+
+/// Options which may hold secrets: their literal values are redacted from the printed config,
+/// while `env:` and `file:` references are kept.
+const SECRET_OPTIONS: &[&str] = &["gateway-key", "l1-endpoint"];
+
+/// Expands the options of the `--config` file, if any, into command line arguments.
+pub fn expand_args(args: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    let Some(path) = config_path(&args) else {
+        return Ok(args);
+    };
+
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let table: Table = content.parse().map_err(|e| format!("invalid config file {}: {e}", path.display()))?;
+
+    let command = Cli::command();
+    let cli_matches = command.clone().ignore_errors(true).get_matches_from(&args);
+
+    let options = flatten(table).map_err(|e| format!("{e} in config file {}", path.display()))?;
+
+    let mut file_args = Vec::new();
+    for (key, value) in options {
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key.as_str()))
+            .ok_or_else(|| format!("unknown option {key:?} in config file {}", path.display()))?;
+
+        // the command line takes precedence over the config file
+        if cli_matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+
+        match (arg.get_action(), value) {
+            (ArgAction::SetTrue, Value::Boolean(true)) => file_args.push(format!("--{key}")),
+            (ArgAction::SetTrue, Value::Boolean(false)) => {}
+            // flags take no value, `--{key}=true` would be rejected
+            (action, value) if !action.takes_values() => {
+                return Err(format!(
+                    "option {key:?} is a flag and expects true or false, found {value} in config file {}",
+                    path.display()
+                ));
+            }
+            (_, Value::Array(values)) => {
+                for value in values {
+                    file_args.push(format!("--{key}={}", scalar(&key, value)?));
+                }
+            }
+            (_, value) => file_args.push(format!("--{key}={}", scalar(&key, value)?)),
+        }
+    }
+
+    // the file options are inserted right after the binary name, before any subcommand
+    let mut args = args.into_iter();
+    Ok(args.next().into_iter().chain(file_args.into_iter().map(OsString::from)).chain(args).collect())
+}
+
+/// Returns the path passed with `--config <path>` or `--config=<path>`.
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(Into::into);
+        }
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(path.into());
+        }
+    }
+    None
+}
+
+/// Lifts the keys of the tables of the config file to the top level.
+///
+/// Fails on an option set in several tables, as the tables are only there for readability and one
+/// of the values would silently override the other.
+fn flatten(table: Table) -> Result<Vec<(String, Value)>, String> {
+    let mut options = Vec::new();
+    flatten_into(table, "", &mut options, &mut HashMap::new())?;
+    Ok(options)
+}
+
+fn flatten_into(
+    table: Table,
+    path: &str,
+    options: &mut Vec<(String, Value)>,
+    tables: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    for (key, value) in table {
+        match value {
+            Value::Table(table) => {
+                let path = if path.is_empty() { key } else { format!("{path}.{key}") };
+                flatten_into(table, &path, options, tables)?;
+            }
+            value => {
+                if let Some(other) = tables.insert(key.clone(), path.to_string()) {
+                    return Err(format!(
+                        "option {key:?} is set both in {} and in {}",
+                        table_name(&other),
+                        table_name(path)
+                    ));
+                }
+                options.push((key, value));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn table_name(path: &str) -> String {
+    if path.is_empty() { "the top level table".to_string() } else { format!("[{path}]") }
+}
+
+fn scalar(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        Value::Integer(value) => Ok(value.to_string()),
+        Value::Float(value) => Ok(value.to_string()),
+        Value::Boolean(value) => Ok(value.to_string()),
+        value => Err(format!("unsupported value {value} for option {key:?} in config file")),
+    }
+}
+
+/// Prints the effective configuration resolved from the config file, the command line and the
+/// defaults, in the format of a config file.
+pub fn print_config(args: Vec<OsString>) -> Result<(), String> {
+    let command = Cli::command();
+    let matches = command.clone().try_get_matches_from(args).map_err(|e| e.to_string())?;
+    print!("{}", effective_config(&command, &matches));
+    Ok(())
+}
+
+fn is_secret_reference(value: &str) -> bool {
+    value.starts_with("env:") || value.starts_with("file:")
+}
+
+fn effective_config(command: &Command, matches: &ArgMatches) -> Table {
+    let mut table = Table::new();
+    for arg in command.get_arguments() {
+        let Some(key) = arg.get_long().filter(|key| !matches!(*key, "help" | "version" | "config")) else {
+            continue;
+        };
+        let Some(raw) = matches.get_raw(arg.get_id().as_str()) else {
+            continue;
+        };
+
+        let values: Vec<String> = raw
+            .map(|value| value.to_string_lossy().into_owned())
+            .map(|value| {
+                if SECRET_OPTIONS.contains(&key) && !is_secret_reference(&value) {
+                    "<redacted>".to_string()
+                } else {
+                    value
+                }
+            })
+            .collect();
+        let value = match (arg.get_action(), values.as_slice()) {
+            (action, [value]) if !action.takes_values() => Value::Boolean(value == "true"),
+            (ArgAction::Append, _) => Value::Array(values.into_iter().map(Value::String).collect()),
+            (_, [value]) => Value::String(value.clone()),
+            (_, _) => Value::Array(values.into_iter().map(Value::String).collect()),
+        };
+        table.insert(key.to_string(), value);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn expand(name: &str, content: &str, args: &[&str]) -> Result<Vec<OsString>, String> {
+        let path = std::env::temp_dir().join(format!("deoxys-config-{name}.toml"));
+        std::fs::write(&path, content).expect("Failed to write the config file");
+        let config = format!("--config={}", path.display());
+        expand_args(["deoxys", config.as_str()].iter().chain(args).map(OsString::from).collect())
+    }
+
+    #[test]
+    fn test_flags_are_passed_without_value() {
+        let args = expand("flags", "[sync]\ncache = true\nsound = false\nsync-fetch-concurrency = 4\n", &[]).unwrap();
+        assert!(args.contains(&OsString::from("--cache")));
+        assert!(!args.iter().any(|arg| arg.to_string_lossy().starts_with("--sound")));
+
+        let cli = Cli::try_parse_from(args).expect("Failed to parse the expanded arguments");
+        assert!(cli.run.cache);
+        assert!(!cli.run.sound);
+        assert_eq!(cli.run.sync_fetch_concurrency, 4);
+    }
+
+    #[test]
+    fn test_flags_expect_a_boolean() {
+        assert!(expand("flag-string", "cache = \"true\"\n", &[]).is_err());
+    }
+
+    #[test]
+    fn test_options_set_in_several_tables_are_rejected() {
+        let error = expand("collision", "[sync]\ncache = true\n[rpc]\ncache = false\n", &[]).unwrap_err();
+        assert!(error.contains("\"cache\""), "{error}");
+    }
+
+    #[test]
+    fn test_command_line_takes_precedence() {
+        let args = expand("precedence", "sync-fetch-concurrency = 4\n", &["--sync-fetch-concurrency", "8"]).unwrap();
+        let cli = Cli::try_parse_from(args).expect("Failed to parse the expanded arguments");
+        assert_eq!(cli.run.sync_fetch_concurrency, 8);
+    }
+
+    #[test]
+    fn test_effective_config_renders_flags_as_booleans() {
+        let command = Cli::command();
+        let matches = command.clone().try_get_matches_from(["deoxys", "--cache"]).unwrap();
+        let config = effective_config(&command, &matches);
+        assert_eq!(config.get("cache"), Some(&Value::Boolean(true)));
+        assert_eq!(config.get("sound"), Some(&Value::Boolean(false)));
+        assert_eq!(config.get("sync-fetch-concurrency"), Some(&Value::String("10".to_string())));
+    }
+
+    #[test]
+    fn test_effective_config_redacts_literal_secrets() {
+        let command = Cli::command();
+        let args = ["deoxys", "--gateway-key", "literal-key", "--l1-endpoint", "env:DEOXYS_TEST_ENDPOINT"];
+        std::env::set_var("DEOXYS_TEST_ENDPOINT", "https://eth.example.com/v2/key");
+        let matches = command.clone().try_get_matches_from(args).unwrap();
+        let config = effective_config(&command, &matches);
+        assert_eq!(config.get("gateway-key"), Some(&Value::String("<redacted>".to_string())));
+        assert_eq!(config.get("l1-endpoint"), Some(&Value::String("env:DEOXYS_TEST_ENDPOINT".to_string())));
+    }
+}
@@ -0,0 +1,94 @@
+//! `deoxys db` subcommands, inspecting the databases of a stopped node.
+
+use std::sync::Arc;
+
+use jsonrpsee::RpcModule;
+use mc_db::perf::ReadProfiler;
+use mc_rpc::execution_constants::ExecutionConstants;
+use mc_rpc::execution_policy::ExecutionPolicy;
+use mc_rpc::{DeoxysRpcApiServer, Starknet, StarknetReadRpcApiServer, StarknetTraceRpcApiServer};
+use mc_sync::state::SyncState;
+use mp_types::block::DHasherT;
+use sc_cli::{CliConfiguration, SharedParams};
+
+use crate::service::{FullBackend, FullClient};
+
+#[derive(Debug, clap::Subcommand)]
+pub enum DbCmd {
+    /// Replay an RPC request against the local databases and report the reads it made, such as
+    /// the lookups per level and the bloom filter hit rate.
+    Profile(ProfileCmd),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ProfileCmd {
+    /// The RPC method to replay, such as `getEvents`. Methods without a namespace are looked up in
+    /// the `starknet` namespace.
+    #[clap(long, value_name = "METHOD")]
+    pub method: String,
+
+    /// The parameters of the request, as a JSON array or object.
+    #[clap(long, value_name = "JSON", default_value = "[]")]
+    pub params: String,
+
+    #[clap(flatten)]
+    pub shared_params: SharedParams,
+}
+
+impl CliConfiguration for DbCmd {
+    fn shared_params(&self) -> &SharedParams {
+        match self {
+            DbCmd::Profile(cmd) => &cmd.shared_params,
+        }
+    }
+}
+
+impl CliConfiguration for ProfileCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+}
+
+impl ProfileCmd {
+    /// Replays the request on the current thread, whose reads are the ones counted.
+    pub async fn run(&self, client: Arc<FullClient>) -> Result<(), String> {
+        let params: serde_json::Value =
+            serde_json::from_str(&self.params).map_err(|e| format!("invalid params {}: {e}", self.params))?;
+        let method = match self.method.contains('_') {
+            true => self.method.clone(),
+            false => format!("starknet_{}", self.method),
+        };
+        let request = serde_json::json!({ "jsonrpc": "2.0", "id": 0, "method": method, "params": params });
+
+        let starknet = || {
+            Starknet::<FullBackend, _, DHasherT>::offline(
+                Arc::clone(&client),
+                mc_rpc::SPEC_VERSION.to_string(),
+                Arc::new(SyncState::default()),
+                Arc::new(ExecutionConstants::default()),
+                Arc::new(ExecutionPolicy::default()),
+            )
+        };
+        let mut module = RpcModule::new(());
+        module.merge(StarknetReadRpcApiServer::into_rpc(starknet())).map_err(|e| e.to_string())?;
+        module.merge(StarknetTraceRpcApiServer::into_rpc(starknet())).map_err(|e| e.to_string())?;
+        module.merge(DeoxysRpcApiServer::into_rpc(starknet())).map_err(|e| e.to_string())?;
+        if module.method(&method).is_none() {
+            return Err(format!("unknown method {method}"));
+        }
+
+        let profiler = ReadProfiler::start();
+        let started_at = std::time::Instant::now();
+        let (response, _) = module.raw_json_request(&request.to_string()).await.map_err(|e| e.to_string())?;
+        let elapsed = started_at.elapsed();
+        let profile = profiler.finish();
+
+        let status = if response.success { "succeeded" } else { "failed" };
+        println!("{method} {status} in {elapsed:?}, returning {} bytes", response.result.len());
+        println!("{profile}");
+        if !response.success {
+            println!("{}", response.result);
+        }
+        Ok(())
+    }
+}
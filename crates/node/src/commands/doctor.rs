@@ -0,0 +1,364 @@
+//! `deoxys doctor`, checking the environment of the node before a long sync.
+//!
+//! The checks read the same options as the node, which are passed before the subcommand or through
+//! the config file: `deoxys --network sepolia --l1-endpoint <URL> doctor`. Every check is run even
+//! when an earlier one fails, and the command fails if any of them did.
+
+use std::ffi::CString;
+use std::fmt;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+use mc_db::{starknet_database_dir, DB_SCHEMA_VERSION};
+use reqwest::header::DATE;
+use reqwest::{Client, Url};
+use sc_cli::{Result, SubstrateCli};
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+
+use crate::cli::Cli;
+use crate::commands::apply_environment;
+use crate::configs::db_config_dir;
+
+/// How long to wait for the answer of a gateway or of the L1 endpoint.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The latency above which a gateway or the L1 endpoint is reported as slow.
+const SLOW_LATENCY: Duration = Duration::from_secs(2);
+
+/// The clock skew with the gateway above which the clock is reported, and above which it fails.
+const CLOCK_SKEW_WARN: Duration = Duration::from_secs(5);
+const CLOCK_SKEW_FAIL: Duration = Duration::from_secs(60);
+
+/// The free disk space below which the disk is reported, and below which it fails.
+const DISK_SPACE_WARN: u64 = 500 * 1024 * 1024 * 1024;
+const DISK_SPACE_FAIL: u64 = 50 * 1024 * 1024 * 1024;
+
+/// The file descriptor limit below which it is reported, and below which it fails. The database
+/// keeps a file descriptor open for each of its files.
+const OPEN_FILES_WARN: u64 = 65536;
+const OPEN_FILES_FAIL: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Pass => write!(f, "✅ pass"),
+            Status::Warn => write!(f, "⚠️  warn"),
+            Status::Fail => write!(f, "❌ fail"),
+        }
+    }
+}
+
+struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status, detail: detail.into() }
+    }
+}
+
+pub fn run_doctor(mut cli: Cli) -> Result<()> {
+    apply_environment(&mut cli.run);
+    let runner = cli.create_runner(&cli.run.base)?;
+    runner.sync_run(|config| {
+        let runtime = Runtime::new().map_err(|e| sc_cli::Error::Input(format!("failed to start the runtime: {e}")))?;
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| sc_cli::Error::Input(format!("failed to build the http client: {e}")))?;
+        let chain_dir = db_config_dir(&config);
+
+        let mut checks = vec![check_database(&chain_dir)];
+        let (gateway_checks, gateway_date) = runtime.block_on(check_gateways(&client, &cli));
+        checks.extend(gateway_checks);
+        checks.push(runtime.block_on(check_l1_endpoint(&client, &cli)));
+        checks.push(check_clock(gateway_date));
+        checks.push(check_disk_space(&chain_dir));
+        checks.push(check_open_files());
+
+        let width = checks.iter().map(|check| check.name.len()).max().unwrap_or_default();
+        for Check { name, status, detail } in &checks {
+            println!("{status}  {name:<width$}  {detail}");
+        }
+
+        let failed = checks.iter().filter(|check| check.status == Status::Fail).count();
+        let warned = checks.iter().filter(|check| check.status == Status::Warn).count();
+        match failed {
+            0 => {
+                println!("{} checks passed, {warned} with warnings", checks.len());
+                Ok(())
+            }
+            _ => Err(sc_cli::Error::Input(format!("{failed} of {} checks failed", checks.len()))),
+        }
+    })
+}
+
+fn check_database(chain_dir: &Path) -> Check {
+    let path = starknet_database_dir(chain_dir, "rockdb");
+    if !path.exists() {
+        return Check::new("database", Status::Pass, format!("no database in {}, it will be created", path.display()));
+    }
+
+    match mc_db::read_schema_version(chain_dir) {
+        Ok(version) => schema_version_check(version),
+        Err(e) => Check::new("database", Status::Fail, format!("failed to open {}: {e:#}", path.display())),
+    }
+}
+
+fn schema_version_check(version: Option<u32>) -> Check {
+    match version {
+        Some(DB_SCHEMA_VERSION) => Check::new("database", Status::Pass, format!("schema version {DB_SCHEMA_VERSION}")),
+        // older layouts are migrated when the database is opened
+        Some(version) if version < DB_SCHEMA_VERSION => Check::new(
+            "database",
+            Status::Warn,
+            format!("schema version {version}, it will be migrated to version {DB_SCHEMA_VERSION} on startup"),
+        ),
+        Some(version) => Check::new(
+            "database",
+            Status::Fail,
+            format!("schema version {version}, this node supports version {DB_SCHEMA_VERSION}"),
+        ),
+        None => Check::new(
+            "database",
+            Status::Pass,
+            format!("schema version not recorded, it will be migrated to version {DB_SCHEMA_VERSION} on startup"),
+        ),
+    }
+}
+
+/// Checks the feeder gateway of the network and the fallback feeder gateways, returning the date
+/// given by the first one answering.
+async fn check_gateways(client: &Client, cli: &Cli) -> (Vec<Check>, Option<SystemTime>) {
+    let gateways = std::iter::once((&cli.run.network.feeder_gateway, cli.run.gateway_key.as_ref()))
+        .chain(cli.run.fallback_feeder_gateways.iter().map(|gateway| (gateway, None)));
+
+    let mut checks = Vec::new();
+    let mut date = None;
+    for (gateway, api_key) in gateways {
+        let name = format!("gateway {}", gateway.host_str().unwrap_or_default());
+        match latest_block(client, gateway, api_key).await {
+            Ok((block_number, latency, gateway_date)) => {
+                date = date.or(gateway_date);
+                let status = latency_status(latency);
+                checks.push(Check::new(name, status, format!("block #{block_number} in {latency:?}")));
+            }
+            Err(e) => checks.push(Check::new(name, Status::Fail, e)),
+        }
+    }
+    (checks, date)
+}
+
+async fn latest_block(
+    client: &Client,
+    gateway: &Url,
+    api_key: Option<&String>,
+) -> std::result::Result<(u64, Duration, Option<SystemTime>), String> {
+    let url = format!("{}/get_block?blockNumber=latest", gateway.as_str().trim_end_matches('/'));
+    let mut request = client.get(url);
+    if let Some(api_key) = api_key {
+        request = request.header("X-Throttling-Bypass", api_key);
+    }
+
+    let start = Instant::now();
+    let response = request.send().await.map_err(|e| format!("unreachable: {e}"))?;
+    let latency = start.elapsed();
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("answered {status}"));
+    }
+
+    let date = response
+        .headers()
+        .get(DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| httpdate::parse_http_date(date).ok());
+    let body = response.text().await.map_err(|e| format!("failed to read the block: {e}"))?;
+    let block_number = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|block| block["block_number"].as_u64())
+        .ok_or_else(|| "answered an invalid block".to_string())?;
+    Ok((block_number, latency, date))
+}
+
+async fn check_l1_endpoint(client: &Client, cli: &Cli) -> Check {
+    let Some(l1_endpoint) = &cli.run.l1_endpoint else {
+        return Check::new("l1 endpoint", Status::Fail, "missing --l1-endpoint");
+    };
+
+    let start = Instant::now();
+    let chain_id = match eth_call(client, l1_endpoint, "eth_chainId", json!([])).await {
+        Ok(chain_id) => chain_id,
+        Err(e) => return Check::new("l1 endpoint", Status::Fail, e),
+    };
+    let latency = start.elapsed();
+
+    // the core contract is only deployed on the Ethereum network the Starknet network settles on
+    let core_address = format!("{:#x}", cli.run.network.l1_core_address);
+    let code = match eth_call(client, l1_endpoint, "eth_getCode", json!([core_address, "latest"])).await {
+        Ok(code) => code,
+        Err(e) => return Check::new("l1 endpoint", Status::Fail, e),
+    };
+    if code.trim_start_matches("0x").is_empty() {
+        return Check::new(
+            "l1 endpoint",
+            Status::Fail,
+            format!("chain {chain_id} has no core contract at {core_address}, is it the right network?"),
+        );
+    }
+
+    let status = latency_status(latency);
+    Check::new("l1 endpoint", status, format!("chain {chain_id} in {latency:?}"))
+}
+
+/// Calls an Ethereum json-rpc method returning a string.
+async fn eth_call(client: &Client, endpoint: &Url, method: &str, params: Value) -> std::result::Result<String, String> {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let response = client
+        .post(endpoint.clone())
+        .header("Content-Type", "application/json")
+        .body(request.to_string())
+        .send()
+        .await
+        // the endpoint is left out of the error as it usually embeds an api key
+        .map_err(|e| format!("unreachable: {}", e.without_url()))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("{method} answered {status}"));
+    }
+
+    let body = response.text().await.map_err(|e| format!("failed to read the answer to {method}: {e}"))?;
+    let answer: Value = serde_json::from_str(&body).map_err(|_| format!("{method} answered invalid json"))?;
+    match answer["result"].as_str() {
+        Some(result) => Ok(result.to_string()),
+        None => Err(format!("{method} failed: {}", answer["error"])),
+    }
+}
+
+fn check_clock(gateway_date: Option<SystemTime>) -> Check {
+    let Some(gateway_date) = gateway_date else {
+        return Check::new("clock", Status::Warn, "no gateway gave its date to compare the clock with");
+    };
+
+    let now = SystemTime::now();
+    let skew = now.duration_since(gateway_date).or_else(|_| gateway_date.duration_since(now)).unwrap_or_default();
+    Check::new("clock", clock_skew_status(skew), format!("{}s off the gateway", skew.as_secs()))
+}
+
+fn check_disk_space(chain_dir: &Path) -> Check {
+    // the chain directory does not exist before the first run
+    let Some(dir) = chain_dir.ancestors().find(|dir| dir.exists()) else {
+        return Check::new("disk space", Status::Fail, format!("{} is not reachable", chain_dir.display()));
+    };
+
+    match available_space(dir) {
+        Ok(available) => {
+            let status = disk_space_status(available);
+            let available_gib = available / (1024 * 1024 * 1024);
+            Check::new("disk space", status, format!("{available_gib} GiB available in {}", dir.display()))
+        }
+        Err(e) => Check::new("disk space", Status::Fail, format!("failed to read the space of {}: {e}", dir.display())),
+    }
+}
+
+fn available_space(path: &Path) -> std::io::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid nul-terminated string and `stat` is only read once initialized
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+fn check_open_files() -> Check {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    // SAFETY: `limit` is a valid rlimit to write the limit to
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        let e = std::io::Error::last_os_error();
+        return Check::new("open files", Status::Fail, format!("failed to read the file descriptor limit: {e}"));
+    }
+
+    let open_files = limit.rlim_cur as u64;
+    let status = open_files_status(open_files);
+    Check::new("open files", status, format!("limit of {open_files} file descriptors, {OPEN_FILES_WARN} recommended"))
+}
+
+fn latency_status(latency: Duration) -> Status {
+    if latency > SLOW_LATENCY { Status::Warn } else { Status::Pass }
+}
+
+fn clock_skew_status(skew: Duration) -> Status {
+    // the date of the gateway is only precise to the second
+    match skew {
+        skew if skew > CLOCK_SKEW_FAIL => Status::Fail,
+        skew if skew > CLOCK_SKEW_WARN => Status::Warn,
+        _ => Status::Pass,
+    }
+}
+
+fn disk_space_status(available: u64) -> Status {
+    match available {
+        available if available < DISK_SPACE_FAIL => Status::Fail,
+        available if available < DISK_SPACE_WARN => Status::Warn,
+        _ => Status::Pass,
+    }
+}
+
+fn open_files_status(open_files: u64) -> Status {
+    match open_files {
+        open_files if open_files < OPEN_FILES_FAIL => Status::Fail,
+        open_files if open_files < OPEN_FILES_WARN => Status::Warn,
+        _ => Status::Pass,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(latency_status(Duration::from_millis(200)), Status::Pass);
+        assert_eq!(latency_status(SLOW_LATENCY), Status::Pass);
+        assert_eq!(latency_status(Duration::from_secs(3)), Status::Warn);
+
+        assert_eq!(clock_skew_status(Duration::from_secs(1)), Status::Pass);
+        assert_eq!(clock_skew_status(CLOCK_SKEW_WARN), Status::Pass);
+        assert_eq!(clock_skew_status(Duration::from_secs(30)), Status::Warn);
+        assert_eq!(clock_skew_status(Duration::from_secs(61)), Status::Fail);
+
+        assert_eq!(disk_space_status(2048 * GIB), Status::Pass);
+        assert_eq!(disk_space_status(DISK_SPACE_WARN), Status::Pass);
+        assert_eq!(disk_space_status(100 * GIB), Status::Warn);
+        assert_eq!(disk_space_status(10 * GIB), Status::Fail);
+
+        assert_eq!(open_files_status(1 << 20), Status::Pass);
+        assert_eq!(open_files_status(OPEN_FILES_WARN), Status::Pass);
+        assert_eq!(open_files_status(4096), Status::Warn);
+        assert_eq!(open_files_status(256), Status::Fail);
+    }
+
+    #[test]
+    fn schema_versions() {
+        assert_eq!(schema_version_check(Some(DB_SCHEMA_VERSION)).status, Status::Pass);
+        assert_eq!(schema_version_check(None).status, Status::Pass);
+        assert_eq!(schema_version_check(Some(DB_SCHEMA_VERSION - 1)).status, Status::Warn);
+        assert_eq!(schema_version_check(Some(DB_SCHEMA_VERSION + 1)).status, Status::Fail);
+    }
+}
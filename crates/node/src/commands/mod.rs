@@ -1,3 +1,15 @@
+mod config_file;
+mod db;
+mod doctor;
+mod export_contract;
+mod revert;
 mod run;
+mod snapshot;
 
+pub use config_file::{expand_args, print_config};
+pub use db::DbCmd;
+pub use doctor::run_doctor;
+pub use export_contract::ExportContractCmd;
+pub use revert::revert;
 pub use run::*;
+pub use snapshot::SnapshotCmd;
@@ -0,0 +1,48 @@
+//! `deoxys revert`, rewinding the node to an older block.
+//!
+//! The state updates, classes, state commitment tries and block mappings of the reverted blocks are
+//! removed along with the substrate blocks, and the sync checkpoint is moved back to the new head,
+//! so that the chain is synced again from there.
+
+use std::sync::Arc;
+
+use mc_db::{storage_handler, DeoxysBackend, SyncCheckpoint, TRIE_LOG_DEPTH};
+use mc_rpc::deoxys_backend_client::get_block_by_block_hash;
+use sc_cli::RevertCmd;
+use sp_blockchain::HeaderBackend;
+
+use crate::service::{FullBackend, FullClient};
+
+/// Reverts the last `cmd.num` blocks of the chain.
+pub async fn revert(cmd: &RevertCmd, client: Arc<FullClient>, backend: Arc<FullBackend>) -> sc_cli::Result<()> {
+    let reverted: u32 = cmd.num.parse().map_err(sc_cli::Error::Input)?;
+    let block_number = client.info().best_number.saturating_sub(reverted);
+
+    let substrate_block_hash =
+        client.hash(block_number)?.ok_or_else(|| sc_cli::Error::Input(format!("block #{block_number} not found")))?;
+    let block = get_block_by_block_hash(client.as_ref(), substrate_block_hash)
+        .map_err(|e| sc_cli::Error::Application(e.into()))?;
+    let block_hash = storage_handler::block_hash()
+        .get(block_number.into())
+        .map_err(|e| sc_cli::Error::Application(e.into()))?
+        .ok_or_else(|| sc_cli::Error::Input(format!("hash of block #{block_number} not found")))?;
+
+    // the tries would be left past the blocks reverted
+    let tries_block = DeoxysBackend::meta().tries_block().map_err(|e| sc_cli::Error::Application(e.into()))?;
+    if tries_block.is_some_and(|tries_block| tries_block > u64::from(block_number) + TRIE_LOG_DEPTH as u64) {
+        return Err(sc_cli::Error::Input(format!("the state tries can only be reverted by {TRIE_LOG_DEPTH} blocks")));
+    }
+
+    log::info!("⏪ Reverting the Starknet state to block #{block_number}");
+    DeoxysBackend::revert_to(block_number.into()).await.map_err(|e| sc_cli::Error::Application(e.into()))?;
+    DeoxysBackend::meta()
+        .write_sync_checkpoint(SyncCheckpoint {
+            block_number: block_number.into(),
+            block_hash: block_hash.into(),
+            global_state_root: block.header().global_state_root,
+        })
+        .map_err(|e| sc_cli::Error::Application(e.into()))?;
+
+    // the substrate blocks go last, so that an interrupted revert is picked up again by the sync
+    cmd.run(client, backend, None)
+}
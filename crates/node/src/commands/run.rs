@@ -1,15 +1,28 @@
 use std::path::PathBuf;
 use std::result::Result as StdResult;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use deoxys_runtime::SealingMode;
-use mc_sync::fetch::fetchers::{fetch_apply_genesis_block, FetchConfig};
+use mc_db::compaction::CompactionPolicy;
+use mc_db::pruning::StatePruning;
+use mc_db::state_snapshot::StateSnapshotReader;
+use mc_db::ColdStorage;
+use mc_rpc::execution_constants::ExecutionConstants;
+use mc_rpc::spec_audit::SpecAudit;
+use mc_sync::fetch::fetchers::{apply_trusted_root, fetch_apply_genesis_block};
+use mc_sync::network::NetworkProfile;
+use mc_sync::soak::SoakConfig;
+use mc_sync::state::SyncState;
 use mc_sync::utility::update_config;
-use mc_sync::utils::constant::starknet_core_address;
 use reqwest::Url;
 use sc_cli::{Result, RpcMethods, RunCmd, SubstrateCli};
 use serde::{Deserialize, Serialize};
-use sp_core::H160;
+use starknet_core::types::FieldElement;
+use starknet_core::utils::parse_cairo_short_string;
 
+use crate::chain_spec::StarknetProperties;
 use crate::cli::Cli;
 use crate::service;
 
@@ -36,66 +49,37 @@ impl From<Sealing> for SealingMode {
     }
 }
 
-/// Starknet network types.
-#[derive(Debug, Clone, Copy, clap::ValueEnum)]
-pub enum NetworkType {
-    /// The main network (mainnet).
-    Main,
-    /// The test network (testnet).
-    Test,
-    /// The integration network.
-    Integration,
-}
-
-/// Starknet network configuration.
-impl NetworkType {
-    pub fn uri(&self) -> &'static str {
-        match self {
-            NetworkType::Main => "https://alpha-mainnet.starknet.io",
-            NetworkType::Test => "https://alpha-sepolia.starknet.io",
-            NetworkType::Integration => "https://external.integration.starknet.io",
-        }
+/// Resolves a secret passed as `env:<VARIABLE>` or `file:<PATH>`, so that it appears neither in
+/// process listings nor in config files. Any other value is taken literally.
+pub fn resolve_secret(s: &str) -> StdResult<String, String> {
+    if let Some(var) = s.strip_prefix("env:") {
+        std::env::var(var).map_err(|e| format!("failed to read environment variable {var}: {e}"))
+    } else if let Some(path) = s.strip_prefix("file:") {
+        let secret = std::fs::read_to_string(path).map_err(|e| format!("failed to read secret file {path}: {e}"))?;
+        Ok(secret.trim_end().to_string())
+    } else {
+        Ok(s.to_string())
     }
+}
 
-    pub fn chain_id(&self) -> starknet_core::types::FieldElement {
-        match self {
-            NetworkType::Main => starknet_core::types::FieldElement::from_byte_slice_be(b"SN_MAIN").unwrap(),
-            NetworkType::Test => starknet_core::types::FieldElement::from_byte_slice_be(b"SN_SEPOLIA").unwrap(),
-            NetworkType::Integration => starknet_core::types::FieldElement::from_byte_slice_be(b"SN_INTE").unwrap(),
-        }
-    }
+/// Resolves a gateway api key, which is sent as an http header.
+fn parse_api_key(s: &str) -> StdResult<String, String> {
+    let api_key = resolve_secret(s)?;
+    reqwest::header::HeaderValue::from_str(&api_key).map_err(|_| "the api key is not a valid http header value")?;
+    Ok(api_key)
+}
 
-    pub fn l1_core_address(&self) -> H160 {
-        match self {
-            NetworkType::Main => starknet_core_address::MAINNET.parse().unwrap(),
-            NetworkType::Test => starknet_core_address::SEPOLIA_TESTNET.parse().unwrap(),
-            NetworkType::Integration => starknet_core_address::SEPOLIA_INTEGRATION.parse().unwrap(),
-        }
-    }
+fn parse_url(s: &str) -> StdResult<Url, String> {
+    resolve_secret(s)?.parse().map_err(|e: url::ParseError| e.to_string())
+}
 
-    pub fn block_fetch_config(&self) -> FetchConfig {
-        let uri = self.uri();
-        let chain_id = self.chain_id();
-
-        let gateway = format!("{uri}/gateway").parse().unwrap();
-        let feeder_gateway = format!("{uri}/feeder_gateway").parse().unwrap();
-        let l1_core_address = self.l1_core_address();
-
-        FetchConfig {
-            gateway,
-            feeder_gateway,
-            chain_id,
-            workers: 5,
-            sound: false,
-            l1_core_address,
-            verify: true,
-            api_key: None,
-        }
-    }
+pub(crate) fn parse_felt(s: &str) -> StdResult<FieldElement, String> {
+    FieldElement::from_hex_be(s).map_err(|e| e.to_string())
 }
 
-fn parse_url(s: &str) -> StdResult<Url, url::ParseError> {
-    s.parse()
+/// Formats a chain id as its short string, such as `SN_MAIN`.
+fn short_string(felt: FieldElement) -> String {
+    parse_cairo_short_string(&felt).unwrap_or_else(|_| format!("{felt:#x}"))
 }
 
 #[derive(Clone, Debug, clap::Args)]
@@ -103,30 +87,70 @@ pub struct ExtendedRunCmd {
     #[clap(flatten)]
     pub base: RunCmd,
 
+    /// Read options from this toml file, keyed by their long name (`l1-endpoint = "..."`) and
+    /// optionally grouped in tables. Options passed on the command line take precedence.
+    #[clap(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
     /// Choose sealing method.
     #[clap(long, value_enum, ignore_case = true)]
     pub sealing: Option<Sealing>,
 
-    /// The L1 rpc endpoint url for state verification
+    /// The L1 rpc endpoint url for state verification. As it usually embeds an api key, it may be
+    /// read from `env:<VARIABLE>` or `file:<PATH>`.
     #[clap(long, value_parser = parse_url)]
     pub l1_endpoint: Option<Url>,
 
-    /// The block you want to start syncing from.
-    #[clap(long)]
+    /// The block you want to start syncing from. A restarted node resumes after the last block it
+    /// fully applied, unless a later block is given.
+    #[clap(long, alias = "start-block")]
     pub starting_block: Option<u32>,
 
-    /// The network type to connect to.
-    #[clap(long, short, default_value = "integration")]
-    pub network: NetworkType,
+    /// Trust this state root for `--starting-block` instead of syncing the blocks before it. The
+    /// root is checked against the gateway. The tries are not initialized from it: they only hold
+    /// the state changed after the starting block, so state roots cannot be verified locally and
+    /// `--disable-root` is required. Use `--import-state-snapshot` to start from a verified state
+    /// with complete tries instead.
+    #[clap(long, value_name = "ROOT", value_parser = parse_felt, requires_all = ["starting_block", "disable_root"])]
+    pub trusted_root: Option<FieldElement>,
+
+    /// Retrieve the blocks preceding `--trusted-root` in the background, from the most recent to
+    /// the oldest. Queries on blocks not backfilled yet fail with a dedicated error.
+    #[clap(long, requires = "trusted_root")]
+    pub backfill: bool,
+
+    /// Fetch in the background the classes missing from the blocks already synced, such as by an
+    /// older version of the node, at a throttled rate which leaves the gateway to the head sync.
+    #[clap(long)]
+    pub backfill_classes: bool,
+
+    /// Import the state of a block from this state snapshot directory, as written by `snapshot
+    /// export`, and sync the blocks after it instead of syncing from the genesis block. The state
+    /// root of the snapshot is checked against the one verified on L1 for its block. Once imported,
+    /// the snapshot is ignored on restart.
+    #[clap(long, value_name = "PATH", conflicts_with_all = ["starting_block", "trusted_root"])]
+    pub import_state_snapshot: Option<PathBuf>,
+
+    /// The network to connect to: `mainnet`, `sepolia`, `integration`, or `custom:<path>` to read
+    /// the gateway urls, chain id, core contract and version schedule from a toml profile.
+    #[clap(long, short, value_name = "NETWORK", default_value = "integration", value_parser = NetworkProfile::from_str)]
+    pub network: NetworkProfile,
 
     /// When enabled, more information about the blocks and their transaction is cached and stored
     /// in the database.
     ///
     /// This may improve response times for RPCs that require that information, but it also
-    /// increases the memory footprint of the node.
+    /// increases the memory footprint of the node. Transactions are also indexed by sender, which
+    /// `deoxys_getTransactionsBySender` requires.
     #[clap(long)]
     pub cache: bool,
 
+    /// Index the ERC-20 transfers by account as blocks are stored, which
+    /// `deoxys_getTokenTransfers` requires. Blocks stored before the flag was enabled are not
+    /// indexed.
+    #[clap(long)]
+    pub index_transfers: bool,
+
     /// This will invoke sound interpreted from the block hashes.
     #[clap(long)]
     pub sound: bool,
@@ -139,16 +163,183 @@ pub struct ExtendedRunCmd {
     #[clap(long)]
     pub disable_root: bool,
 
-    /// Gateway api key to avoid rate limiting (optional)
+    /// The number of upcoming blocks converted, and whose state diffs are prepared for root
+    /// verification, in parallel, while the state root itself is still updated one block at a time.
+    #[clap(long, value_name = "BLOCKS", default_value_t = 16)]
+    pub verify_lookahead: usize,
+
+    /// Recompute the transaction commitment of every synced block from its transactions, and stop
+    /// the sync if it does not match the one in the block header served by the gateway.
     #[clap(long)]
+    pub verify_tx_commitments: bool,
+
+    /// Recompute the event commitment of every synced block from its events, and stop the sync if
+    /// it does not match the one in the block header served by the gateway.
+    #[clap(long)]
+    pub verify_event_commitments: bool,
+
+    /// The number of blocks fetched from the feeder gateway in parallel. Raising it speeds up the
+    /// sync on fast links, at the cost of more requests in flight.
+    #[clap(long, value_name = "BLOCKS", default_value_t = 10)]
+    pub sync_fetch_concurrency: usize,
+
+    /// The number of fetched blocks kept in memory while waiting to be applied. Lowering it reduces
+    /// the memory used by the sync on constrained machines.
+    #[clap(long, value_name = "BLOCKS", default_value_t = 10)]
+    pub sync_buffer_size: usize,
+
+    /// Gateway api key to avoid rate limiting (optional). May be read from `env:<VARIABLE>` or
+    /// `file:<PATH>`.
+    #[clap(long, value_name = "KEY", value_parser = parse_api_key)]
     pub gateway_key: Option<String>,
 
+    /// A feeder gateway to fall back to when the one of the network is rate limiting or down. May
+    /// be given several times, and read from `env:<VARIABLE>` or `file:<PATH>` when its url
+    /// holds a key: only its origin is shown in the logs and metrics. The gateway api key is
+    /// only sent to the feeder gateway of the network.
+    #[clap(long = "fallback-feeder-gateway", value_name = "URL", value_parser = parse_url)]
+    pub fallback_feeder_gateways: Vec<Url>,
+
+    /// Fetch the historical blocks from the compressed block archives published by this mirror of
+    /// the feeder gateway, listed in its `index.json`. The blocks which are not archived are
+    /// fetched from the gateway.
+    #[clap(long, value_name = "URL", value_parser = parse_url)]
+    pub block_archive: Option<Url>,
+
+    /// Store this class without checking that it hashes to its class hash, for historical classes
+    /// whose hash cannot be reproduced and would otherwise halt the sync. May be given several
+    /// times.
+    #[clap(long = "unverified-class", value_name = "CLASS HASH", value_parser = parse_felt)]
+    pub unverified_classes: Vec<FieldElement>,
+
+    /// Restart the sync pipeline when no block has been applied for this many seconds while the
+    /// gateway head is advancing. Set to 0 to disable the watchdog.
+    #[clap(long, value_name = "SECONDS", default_value_t = 300)]
+    pub sync_stall_timeout: u64,
+
+    /// Stop the sync once this block has been applied, and report its throughput. The node keeps
+    /// serving the synced state, which is meant for benchmarking and testing.
+    #[clap(long, value_name = "BLOCK")]
+    pub sync_until: Option<u64>,
+
+    /// Only serve blocks covered by a state update verified on L1. The latest and pending block
+    /// tags then resolve to the L1 head, and more recent blocks are reported as not found.
+    #[clap(long)]
+    pub l1_accepted_only: bool,
+
+    /// Attach a readable form of the revert reasons (short strings, Cairo 1 byte array panics) to
+    /// traces, simulations and receipts, in a separate `revert_reason_decoded` field which is an
+    /// extension to the RPC specification. The revert reasons themselves are left untouched.
+    #[clap(long)]
+    pub rpc_decode_revert_reasons: bool,
+
+    /// Override the execution limits of the RPC (`invoke_tx_max_n_steps`, `validate_max_n_steps`,
+    /// `max_recursion_depth`) with the values of this toml file. The file is watched, and changes
+    /// are applied without restarting the node.
+    #[clap(long, value_name = "PATH")]
+    pub rpc_execution_constants: Option<PathBuf>,
+
+    /// Validate every response to a method of the Starknet RPC specification against the schemas
+    /// of the `api` directory of a starknet-specs checkout, logging the responses which do not
+    /// conform. The responses are served unchanged. Meant for staging, as it slows the RPC down.
+    #[clap(long, value_name = "DIR")]
+    pub rpc_spec_audit: Option<PathBuf>,
+
+    /// Disable polling of the pending block. Queries on the pending block then resolve to the
+    /// latest block, which saves gateway quota when sub-block latency is not needed.
+    #[clap(long)]
+    pub no_pending: bool,
+
+    /// How often the tip of the chain and the pending block are polled from the gateway. The
+    /// interval is doubled after each failed poll, up to 64 times this value.
+    #[clap(long, value_name = "SECONDS", default_value_t = 5)]
+    pub pending_poll_interval: u64,
+
+    /// Fetch the block headers up to the tip before the full blocks, which are then backfilled
+    /// behind them with at least 32 fetches in parallel, each checked against its header. Header
+    /// and sync status queries are answered from the header chain long before the full sync
+    /// completes.
+    #[clap(long)]
+    pub headers_first: bool,
+
+    /// Record the timings of every stage of the import of every block to this file, as a chrome
+    /// trace which can be opened in Perfetto.
+    #[clap(long, value_name = "PATH")]
+    pub profile_sync: Option<PathBuf>,
+
+    /// Sample the resident memory of the node, the memory held by the database and the occupancy
+    /// of the sync queues every this many minutes, flagging the series which keep growing. Meant
+    /// for long runs, the samples are written to `--soak-report`.
+    #[clap(long, value_name = "MINUTES")]
+    pub soak: Option<u64>,
+
+    /// The file the soak report is written to, rewritten after every sample.
+    #[clap(long, value_name = "PATH", default_value = "soak-report.txt", requires = "soak")]
+    pub soak_report: PathBuf,
+
+    /// Move the state diffs and backfilled blocks of the oldest blocks to a database in this
+    /// directory, which may be on cheaper and slower storage. Reads go through both databases
+    /// transparently, and the node must then always be started with this directory.
+    #[clap(long, value_name = "PATH")]
+    pub cold_db_path: Option<PathBuf>,
+
+    /// The number of last blocks whose data is kept in the main database when `--cold-db-path` is
+    /// set. The data of older blocks is moved to the cold database.
+    #[clap(long, value_name = "BLOCKS", default_value_t = 100_000, requires = "cold_db_path")]
+    pub cold_db_after: u64,
+
+    /// Compact a column of the Starknet database once its level 0 holds more than this many files.
+    /// The columns are checked in the background, so compacting them never blocks the sync.
+    #[clap(long, value_name = "FILES", default_value_t = 20)]
+    pub db_compaction_l0_files: u64,
+
+    /// Compact a column of the Starknet database once the data to rewrite to bring its levels back
+    /// to their target size is estimated above this size, in GiB.
+    #[clap(long, value_name = "GIB", default_value_t = 64)]
+    pub db_compaction_pending_gib: u64,
+
+    /// Leave the compaction of the Starknet database entirely to RocksDB.
+    #[clap(long, conflicts_with_all = ["db_compaction_l0_files", "db_compaction_pending_gib"])]
+    pub disable_db_compaction: bool,
+
+    /// How much of the state history is kept: `archive` keeps the state readable at every block,
+    /// while a number of blocks only keeps it readable at the last blocks, pruning the older
+    /// history in the background. The tries of the latest state are never pruned.
+    #[clap(long, value_name = "archive|BLOCKS", default_value = "archive")]
+    pub state_pruning: StatePruning,
+
+    /// Reconstruct the state only from the data availability payloads published on L1, scanning
+    /// L1 for state updates from this block, without syncing from the feeder gateway. The state
+    /// then trails the finalized L1 head and has no transaction bodies. The genesis block is still
+    /// fetched from the gateway at startup.
+    #[clap(long, value_name = "L1_BLOCK", requires = "l1_endpoint")]
+    pub da_only: Option<u64>,
+
+    /// The beacon node api url, from which the blobs of the state updates are retrieved in
+    /// `--da-only` mode. It may be read from `env:<VARIABLE>` or `file:<PATH>`.
+    #[clap(long, value_parser = parse_url, requires = "da_only")]
+    pub beacon_url: Option<Url>,
+
     /// A flag to run the TUI dashboard
     #[cfg(feature = "tui")]
     #[clap(long)]
     pub tui: bool,
 }
 
+impl ExtendedRunCmd {
+    pub fn cold_storage(&self) -> Option<ColdStorage> {
+        self.cold_db_path.clone().map(|path| ColdStorage { path, after_blocks: self.cold_db_after })
+    }
+
+    pub fn compaction_policy(&self) -> Option<CompactionPolicy> {
+        (!self.disable_db_compaction).then(|| CompactionPolicy {
+            max_l0_files: self.db_compaction_l0_files,
+            max_pending_bytes: self.db_compaction_pending_gib.saturating_mul(1024 * 1024 * 1024),
+            ..Default::default()
+        })
+    }
+}
+
 pub fn run_node(mut cli: Cli) -> Result<()> {
     #[cfg(feature = "tui")]
     {
@@ -162,11 +353,7 @@ pub fn run_node(mut cli: Cli) -> Result<()> {
             });
         }
     }
-    if cli.run.base.shared_params.dev {
-        override_dev_environment(&mut cli.run);
-    } else if cli.run.deoxys {
-        deoxys_environment(&mut cli.run);
-    }
+    apply_environment(&mut cli.run);
 
     let runner = cli.create_runner(&cli.run.base)?;
 
@@ -182,20 +369,118 @@ pub fn run_node(mut cli: Cli) -> Result<()> {
     runner.run_node_until_exit(|config| async move {
         let sealing = cli.run.sealing.map(Into::into).unwrap_or_default();
         let cache = cli.run.cache;
-        let starting_block = cli.run.starting_block;
-        let mut fetch_block_config = cli.run.network.block_fetch_config();
+        // the sync starts right after the block of the state snapshot
+        let starting_block =
+            match &cli.run.import_state_snapshot {
+                Some(path) => {
+                    let reader = StateSnapshotReader::open(path)
+                        .map_err(|e| sc_cli::Error::Input(format!("{}: {e}", path.display())))?;
+                    let block_number = reader.header().block_number;
+                    Some(u32::try_from(block_number).map_err(|_| {
+                        sc_cli::Error::Input(format!("invalid state snapshot block number {block_number}"))
+                    })?)
+                }
+                None => cli.run.starting_block,
+            };
+        let mut fetch_block_config = cli.run.network.fetch_config();
+        // the blocks synced from the network would not belong to the chain of the chain spec
+        let properties =
+            StarknetProperties::from_chain_spec(config.chain_spec.as_ref()).map_err(sc_cli::Error::Input)?;
+        if let Some(chain_id) = properties.chain_id.filter(|chain_id| *chain_id != fetch_block_config.chain_id) {
+            return Err(sc_cli::Error::Input(format!(
+                "The chain spec is for chain id {}, but the network {} is selected: pass --network with a profile of \
+                 this chain, such as custom:<path>",
+                short_string(chain_id),
+                short_string(fetch_block_config.chain_id),
+            )));
+        }
+        let spec_version = properties.spec_version.unwrap_or_else(|| mc_rpc::SPEC_VERSION.to_string());
         fetch_block_config.sound = cli.run.sound;
         fetch_block_config.verify = !cli.run.disable_root;
+        fetch_block_config.verify_lookahead = cli.run.verify_lookahead;
+        fetch_block_config.verify_tx_commitments = cli.run.verify_tx_commitments;
+        fetch_block_config.verify_event_commitments = cli.run.verify_event_commitments;
+        fetch_block_config.fetch_concurrency = cli.run.sync_fetch_concurrency;
+        fetch_block_config.buffer_size = cli.run.sync_buffer_size;
         fetch_block_config.api_key = cli.run.gateway_key.clone();
+        fetch_block_config.fallback_feeder_gateways = cli.run.fallback_feeder_gateways.clone();
+        fetch_block_config.block_archive = cli.run.block_archive.clone();
+        fetch_block_config.unverified_classes = cli.run.unverified_classes.clone();
+        fetch_block_config.sync_until = cli.run.sync_until;
+        fetch_block_config.stall_timeout =
+            Some(cli.run.sync_stall_timeout).filter(|secs| *secs > 0).map(Duration::from_secs);
+        fetch_block_config.da_from_l1_block = cli.run.da_only;
+        fetch_block_config.beacon_url = cli.run.beacon_url.clone();
+        fetch_block_config.pending = !cli.run.no_pending;
+        fetch_block_config.pending_poll_interval = Duration::from_secs(cli.run.pending_poll_interval.max(1));
+        fetch_block_config.headers_first = cli.run.headers_first;
+        fetch_block_config.backfill = cli.run.backfill;
+        fetch_block_config.backfill_classes = cli.run.backfill_classes;
+        fetch_block_config.profile_sync = cli.run.profile_sync.clone();
+        fetch_block_config.state_snapshot = cli.run.import_state_snapshot.clone();
+        fetch_block_config.soak = cli.run.soak.map(|minutes| SoakConfig {
+            interval: Duration::from_secs(minutes.max(1) * 60),
+            report: cli.run.soak_report.clone(),
+        });
         update_config(&fetch_block_config);
 
+        let sync_state = Arc::new(SyncState::default());
+        let execution_constants = match cli.run.rpc_execution_constants.clone() {
+            Some(path) => ExecutionConstants::from_file(path).map_err(|e| sc_cli::Error::Input(e.to_string()))?,
+            None => ExecutionConstants::default(),
+        };
+        let spec_audit = match &cli.run.rpc_spec_audit {
+            Some(dir) => {
+                let audit = SpecAudit::load(dir).map_err(|e| sc_cli::Error::Input(e.to_string()))?;
+                if audit.version() != spec_version {
+                    log::warn!(
+                        "⚠️ Auditing the RPC against the {} specification while it serves the {spec_version} one",
+                        audit.version()
+                    );
+                }
+                Some(Arc::new(audit))
+            }
+            None => None,
+        };
         let genesis_block = fetch_apply_genesis_block(fetch_block_config.clone()).await.unwrap();
+        if let (Some(block_number), Some(trusted_root)) = (starting_block, cli.run.trusted_root) {
+            apply_trusted_root(&fetch_block_config, &sync_state, block_number.into(), trusted_root)
+                .await
+                .map_err(sc_cli::Error::Input)?;
+        }
 
-        service::new_full(config, sealing, l1_endpoint, cache, fetch_block_config, genesis_block, starting_block)
-            .map_err(sc_cli::Error::Service)
+        service::new_full(
+            config,
+            sealing,
+            l1_endpoint,
+            cache,
+            cli.run.cold_storage(),
+            cli.run.compaction_policy(),
+            cli.run.state_pruning,
+            cli.run.index_transfers,
+            fetch_block_config,
+            genesis_block,
+            starting_block,
+            cli.run.l1_accepted_only,
+            spec_version,
+            cli.run.rpc_decode_revert_reasons,
+            sync_state,
+            Arc::new(execution_constants),
+            spec_audit,
+        )
+        .map_err(sc_cli::Error::Service)
     })
 }
 
+/// Applies the presets of `--dev` and `--deoxys` to the options.
+pub(crate) fn apply_environment(cmd: &mut ExtendedRunCmd) {
+    if cmd.base.shared_params.dev {
+        override_dev_environment(cmd);
+    } else if cmd.deoxys {
+        deoxys_environment(cmd);
+    }
+}
+
 fn override_dev_environment(cmd: &mut ExtendedRunCmd) {
     // create a reproducible dev environment
     // by disabling the default substrate `dev` behaviour
@@ -234,3 +519,35 @@ fn deoxys_environment(cmd: &mut ExtendedRunCmd) {
     cmd.base.no_grandpa = true;
     cmd.sealing = Some(Sealing::Manual);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_secret() {
+        assert_eq!(resolve_secret("literal").unwrap(), "literal");
+
+        std::env::set_var("DEOXYS_TEST_SECRET", "from-env");
+        assert_eq!(resolve_secret("env:DEOXYS_TEST_SECRET").unwrap(), "from-env");
+        assert!(resolve_secret("env:DEOXYS_TEST_SECRET_UNSET").is_err());
+
+        let path = std::env::temp_dir().join("deoxys-test-secret");
+        std::fs::write(&path, "from-file\n").expect("Failed to write the secret file");
+        assert_eq!(resolve_secret(&format!("file:{}", path.display())).unwrap(), "from-file");
+        assert!(resolve_secret("file:/nonexistent/deoxys-test-secret").is_err());
+    }
+
+    #[test]
+    fn test_parse_url_resolves_secrets() {
+        std::env::set_var("DEOXYS_TEST_URL", "https://eth.example.com/v2/key");
+        assert_eq!(parse_url("env:DEOXYS_TEST_URL").unwrap().as_str(), "https://eth.example.com/v2/key");
+    }
+
+    #[test]
+    fn test_parse_api_key() {
+        std::env::set_var("DEOXYS_TEST_API_KEY", "key");
+        assert_eq!(parse_api_key("env:DEOXYS_TEST_API_KEY").unwrap(), "key");
+        assert!(parse_api_key("key\nwith a line break").is_err());
+    }
+}
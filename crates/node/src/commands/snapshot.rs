@@ -0,0 +1,714 @@
+//! `deoxys snapshot` subcommands, sharing sync bootstraps through object storage.
+//!
+//! A snapshot is a RocksDB checkpoint of the substrate and Starknet databases of a stopped node,
+//! uploaded to an object store (`s3://bucket/prefix`, `gs://bucket/prefix`, or a local `file:///`
+//! directory) as:
+//!
+//! - `<snapshot>/files/<path>`: the database files, relative to the chain directory.
+//! - `<snapshot>/manifest.json`: the list of the files along with their size and sha3-256
+//!   checksums, of the whole file and of each of its chunks.
+//! - `<snapshot>/manifest.json.sig`: the ed25519 signature of the manifest by the publisher, if a
+//!   signing key was given.
+//! - `latest`: the name of the latest snapshot published.
+//!
+//! Bootstrapping checks the manifest against its checksum or the keys of trusted publishers, and
+//! every chunk against the manifest as it is downloaded, so that tampered mirrors are caught
+//! before anything is imported.
+//!
+//! The object stores are configured from their own environment variables, such as
+//! `AWS_ACCESS_KEY_ID` or `GOOGLE_SERVICE_ACCOUNT`, and from the `--store-option` flags. Publishing
+//! periodically is a matter of running `snapshot publish` on a schedule while the node is stopped.
+//!
+//! `snapshot export` writes a [state snapshot](mc_db::state_snapshot) of a block instead, which
+//! other nodes import with `--import-state-snapshot` whatever their database layout.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::StreamExt;
+use mc_db::state_snapshot::{export_state_snapshot, StateSnapshotHeader, STATE_SNAPSHOT_VERSION};
+use mc_db::{storage_handler, DeoxysBackend, L1Confirmation};
+use mc_rpc::deoxys_backend_client::{get_block_by_block_hash, load_hash};
+use mp_types::block::DBlockT;
+use object_store::aws::{AmazonS3Builder, AmazonS3ConfigKey};
+use object_store::gcp::{GoogleCloudStorageBuilder, GoogleConfigKey};
+use object_store::local::LocalFileSystem;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use sc_cli::{CliConfiguration, SharedParams};
+use sc_service::Configuration;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use sp_blockchain::HeaderBackend;
+use sp_core::{ed25519, Pair};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::Runtime;
+use url::Url;
+
+use crate::commands::resolve_secret;
+use crate::configs::db_config_dir;
+
+/// The directories of the chain directory holding the databases: the node keys and keystore are
+/// never part of a snapshot.
+const SNAPSHOT_DIRS: &[&str] = &["db", "starknet"];
+
+/// Name of the object holding the name of the latest snapshot.
+const LATEST: &str = "latest";
+
+/// Directory of the chain directory the databases are checkpointed to before being uploaded.
+const CHECKPOINT_DIR: &str = "snapshot.checkpoint";
+
+/// Size of the chunks of the files which are checked one by one.
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SnapshotCmd {
+    /// Upload a snapshot of the databases of the node, which must be stopped, to object storage.
+    Publish(PublishCmd),
+
+    /// Download a snapshot from object storage, verify it, and install it as the databases of the
+    /// node.
+    Bootstrap(BootstrapCmd),
+
+    /// Export the state of a block from the databases of the node, which must be stopped, as a
+    /// state snapshot.
+    Export(ExportCmd),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct PublishCmd {
+    /// Where to upload the snapshot, such as `s3://bucket/deoxys` or `gs://bucket/deoxys`.
+    #[clap(long, value_name = "URL")]
+    pub to: Url,
+
+    /// Sign the manifest with this ed25519 secret seed, as a hex seed or a secret phrase. May be
+    /// read from `env:<VARIABLE>` or `file:<PATH>`.
+    #[clap(long, value_name = "SEED", value_parser = resolve_secret)]
+    pub signing_key: Option<String>,
+
+    /// An option of the object store, such as `region=eu-west-1` or `endpoint=https://...`, on top
+    /// of the ones read from its environment variables.
+    #[clap(long = "store-option", value_name = "KEY=VALUE", value_parser = parse_store_option)]
+    pub store_options: Vec<(String, String)>,
+
+    #[clap(flatten)]
+    pub shared_params: SharedParams,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BootstrapCmd {
+    /// Where to download the snapshot from, such as `s3://bucket/deoxys` or `gs://bucket/deoxys`.
+    #[clap(long, value_name = "URL")]
+    pub from: Url,
+
+    /// The name of the snapshot to download. Defaults to the latest snapshot published.
+    #[clap(long, value_name = "NAME")]
+    pub snapshot: Option<String>,
+
+    /// The sha3-256 checksum of the manifest of the snapshot, as printed when it was published.
+    /// Required unless the manifest is checked against `--trusted-key`.
+    #[clap(long, value_name = "HEX", required_unless_present = "trusted_keys")]
+    pub checksum: Option<String>,
+
+    /// Only accept a snapshot whose manifest is signed by one of these ed25519 public keys, in hex.
+    #[clap(long = "trusted-key", value_name = "HEX")]
+    pub trusted_keys: Vec<String>,
+
+    /// An option of the object store, such as `region=eu-west-1` or `endpoint=https://...`, on top
+    /// of the ones read from its environment variables.
+    #[clap(long = "store-option", value_name = "KEY=VALUE", value_parser = parse_store_option)]
+    pub store_options: Vec<(String, String)>,
+
+    /// The number of files downloaded and verified in parallel. Each of them holds at most a chunk
+    /// of the snapshot in memory. Defaults to the number of cores.
+    #[clap(long, value_name = "COUNT")]
+    pub jobs: Option<usize>,
+
+    #[clap(flatten)]
+    pub shared_params: SharedParams,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ExportCmd {
+    /// The block to export the state of, at most the last block synced by the node.
+    #[clap(long, value_name = "BLOCK NUMBER")]
+    pub block: u64,
+
+    /// The Ethereum block in which the state update of the block was posted to the Starknet core
+    /// contract, which importing nodes check the snapshot against. Defaults to the one recorded by
+    /// the L1 sync of the node.
+    #[clap(long, value_name = "BLOCK NUMBER")]
+    pub l1_block: Option<u64>,
+
+    /// The directory to write the snapshot to.
+    #[clap(long, value_name = "PATH")]
+    pub out: PathBuf,
+
+    #[clap(flatten)]
+    pub shared_params: SharedParams,
+}
+
+/// The content of a snapshot.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub chain_id: String,
+    /// The unix time at which the snapshot was taken, in seconds.
+    pub timestamp: u64,
+    /// The size of the chunks of the files, in bytes.
+    pub chunk_size: u64,
+    pub files: Vec<SnapshotFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// The path of the file, relative to the chain directory.
+    pub path: String,
+    pub size: u64,
+    pub sha3_256: String,
+    /// The sha3-256 checksums of the chunks of the file, in order.
+    pub chunks: Vec<String>,
+}
+
+impl CliConfiguration for PublishCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+}
+
+impl CliConfiguration for BootstrapCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+}
+
+impl CliConfiguration for ExportCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+}
+
+/// Runs a command against object storage to completion.
+fn block_on(command: impl std::future::Future<Output = Result<(), String>>) -> Result<(), String> {
+    Runtime::new().map_err(|e| format!("failed to start the runtime: {e}"))?.block_on(command)
+}
+
+impl PublishCmd {
+    pub fn run(&self, config: Configuration) -> Result<(), String> {
+        block_on(self.checkpoint_and_publish(&config))
+    }
+
+    async fn checkpoint_and_publish(&self, config: &Configuration) -> Result<(), String> {
+        let chain_dir = db_config_dir(config);
+        let checkpoint_dir = chain_dir.join(CHECKPOINT_DIR);
+        // a checkpoint left by an interrupted publish is stale
+        if checkpoint_dir.exists() {
+            std::fs::remove_dir_all(&checkpoint_dir)
+                .map_err(|e| format!("failed to remove {}: {e}", checkpoint_dir.display()))?;
+        }
+
+        let result = self.publish(config, &chain_dir, &checkpoint_dir).await;
+        std::fs::remove_dir_all(&checkpoint_dir)
+            .map_err(|e| format!("failed to remove {}: {e}", checkpoint_dir.display()))?;
+        result
+    }
+
+    async fn publish(&self, config: &Configuration, chain_dir: &Path, checkpoint_dir: &Path) -> Result<(), String> {
+        let (store, prefix) = object_store(&self.to, &self.store_options)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs();
+        let chain_id = config.chain_spec.id().to_string();
+        let name = format!("{chain_id}-{timestamp}");
+
+        let substrate_db = config.database.path().ok_or("only rocksdb databases can be published")?;
+        let starknet_db = mc_db::starknet_database_dir(chain_dir, "rockdb");
+        for db_path in [substrate_db, starknet_db.as_path()] {
+            let relative = db_path
+                .strip_prefix(chain_dir)
+                .map_err(|_| format!("{} is not in {}", db_path.display(), chain_dir.display()))?;
+            log::info!("📸 Checkpointing {}", relative.display());
+            mc_db::create_checkpoint(db_path, &checkpoint_dir.join(relative))
+                .map_err(|e| format!("failed to checkpoint {}, is the node stopped? {e:#}", db_path.display()))?;
+        }
+
+        let mut paths = Vec::new();
+        list_files(checkpoint_dir, checkpoint_dir, &mut paths)?;
+
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let local_path = checkpoint_dir.join(&path);
+            let (size, sha3_256, chunks) = {
+                let local_path = local_path.clone();
+                tokio::task::spawn_blocking(move || checksum(&local_path))
+                    .await
+                    .map_err(|e| format!("checksum task failed: {e}"))??
+            };
+            log::info!("📤 Uploading {path} ({size} bytes)");
+            upload(store.as_ref(), &object_path(&prefix, &format!("{name}/files/{path}"))?, &local_path).await?;
+            files.push(SnapshotFile { path, size, sha3_256, chunks });
+        }
+
+        let manifest = SnapshotManifest { chain_id, timestamp, chunk_size: CHUNK_SIZE as u64, files };
+        let manifest =
+            serde_json::to_vec_pretty(&manifest).map_err(|e| format!("failed to serialize the manifest: {e}"))?;
+        let manifest_checksum = hex::encode(Sha3_256::digest(&manifest));
+        if let Some(seed) = &self.signing_key {
+            let pair = ed25519::Pair::from_string(seed, None).map_err(|e| format!("invalid signing key: {e:?}"))?;
+            let signature = hex::encode(pair.sign(&manifest));
+            put(store.as_ref(), &object_path(&prefix, &format!("{name}/manifest.json.sig"))?, signature.into_bytes())
+                .await?;
+            log::info!("🔏 Signed the manifest with key {}", hex::encode(pair.public()));
+        }
+        put(store.as_ref(), &object_path(&prefix, &format!("{name}/manifest.json"))?, manifest).await?;
+        // the latest snapshot is only updated once the snapshot is complete
+        put(store.as_ref(), &object_path(&prefix, LATEST)?, name.clone().into_bytes()).await?;
+
+        log::info!("✅ Published snapshot {name} with manifest checksum {manifest_checksum}");
+        Ok(())
+    }
+}
+
+impl BootstrapCmd {
+    pub fn run(&self, config: Configuration) -> Result<(), String> {
+        block_on(self.bootstrap(&config))
+    }
+
+    async fn bootstrap(&self, config: &Configuration) -> Result<(), String> {
+        let chain_dir = db_config_dir(config);
+        if let Some(dir) = SNAPSHOT_DIRS.iter().map(|dir| chain_dir.join(dir)).find(|dir| dir.exists()) {
+            return Err(format!("{} already exists, purge the chain before bootstrapping", dir.display()));
+        }
+        let (store, prefix) = object_store(&self.from, &self.store_options)?;
+
+        let name = match &self.snapshot {
+            Some(name) => name.clone(),
+            None => String::from_utf8(get(store.as_ref(), &object_path(&prefix, LATEST)?).await?)
+                .map_err(|e| format!("invalid latest snapshot name: {e}"))?,
+        };
+
+        let manifest = get(store.as_ref(), &object_path(&prefix, &format!("{name}/manifest.json"))?).await?;
+        let manifest_checksum = hex::encode(Sha3_256::digest(&manifest));
+        // clap requires the checksum when there are no trusted keys
+        if let Some(checksum) = &self.checksum {
+            if !checksum.trim_start_matches("0x").eq_ignore_ascii_case(&manifest_checksum) {
+                return Err(format!("checksum mismatch for the manifest of {name}: got {manifest_checksum}"));
+            }
+        }
+        if !self.trusted_keys.is_empty() {
+            let signature = get(store.as_ref(), &object_path(&prefix, &format!("{name}/manifest.json.sig"))?).await?;
+            verify_signature(&manifest, &signature, &self.trusted_keys)
+                .map_err(|e| format!("invalid signature for the manifest of {name}: {e}"))?;
+        }
+        let manifest: SnapshotManifest =
+            serde_json::from_slice(&manifest).map_err(|e| format!("invalid manifest for {name}: {e}"))?;
+        if manifest.chain_id != config.chain_spec.id() {
+            return Err(format!("snapshot {name} is for chain {}, not {}", manifest.chain_id, config.chain_spec.id()));
+        }
+        let chunk_size = usize::try_from(manifest.chunk_size)
+            .ok()
+            .filter(|size| *size > 0)
+            .ok_or_else(|| format!("invalid chunk size in the manifest of {name}"))?;
+
+        // files are downloaded aside and only moved in place once they are all verified, a download
+        // left by an interrupted bootstrap is started over
+        let partial_dir = chain_dir.join("snapshot.partial");
+        if partial_dir.exists() {
+            tokio::fs::remove_dir_all(&partial_dir)
+                .await
+                .map_err(|e| format!("failed to remove {}: {e}", partial_dir.display()))?;
+        }
+        let result = self.download_all(&store, &prefix, &name, &manifest, &partial_dir, chunk_size).await;
+        if let Err(e) = result {
+            // a failed download is not resumed, so it is not worth keeping
+            let _ = tokio::fs::remove_dir_all(&partial_dir).await;
+            return Err(e);
+        }
+
+        for dir in SNAPSHOT_DIRS {
+            let from = partial_dir.join(dir);
+            if from.exists() {
+                tokio::fs::rename(&from, chain_dir.join(dir))
+                    .await
+                    .map_err(|e| format!("failed to install {dir}: {e}"))?;
+            }
+        }
+        tokio::fs::remove_dir_all(&partial_dir)
+            .await
+            .map_err(|e| format!("failed to remove {}: {e}", partial_dir.display()))?;
+
+        log::info!("✅ Bootstrapped from snapshot {name}");
+        Ok(())
+    }
+
+    /// Downloads and verifies the files of the snapshot `name` to `partial_dir`.
+    async fn download_all(
+        &self,
+        store: &Arc<dyn ObjectStore>,
+        prefix: &ObjectPath,
+        name: &str,
+        manifest: &SnapshotManifest,
+        partial_dir: &Path,
+        chunk_size: usize,
+    ) -> Result<(), String> {
+        let downloads = manifest
+            .files
+            .iter()
+            .map(|file| {
+                let local_path = safe_join(partial_dir, &file.path)?;
+                let location = object_path(prefix, &format!("{name}/files/{}", file.path))?;
+                let (store, file) = (Arc::clone(store), file.clone());
+                Ok(async move {
+                    log::info!("📥 Downloading {} ({} bytes)", file.path, file.size);
+                    let task = tokio::spawn(async move {
+                        download(store.as_ref(), &location, &local_path, &file, chunk_size).await
+                    });
+                    task.await.map_err(|e| format!("download task failed: {e}"))?
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let jobs = self.jobs.or_else(|| std::thread::available_parallelism().ok().map(usize::from)).unwrap_or(1);
+        let mut downloads = futures::stream::iter(downloads).buffer_unordered(jobs.max(1));
+        while let Some(result) = downloads.next().await {
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl ExportCmd {
+    pub fn run<C: HeaderBackend<DBlockT> + 'static>(&self, client: &C) -> Result<(), String> {
+        let block_number = self.block;
+        let block_hash = storage_handler::block_hash()
+            .get(block_number)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("hash of block #{block_number} not found"))?;
+        // the substrate blocks are not numbered after the Starknet blocks on nodes started from a
+        // state snapshot or a trusted root
+        let substrate_block_hash = load_hash(client, block_hash.into())
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("block #{block_number} not found"))?;
+        let block = get_block_by_block_hash(client, substrate_block_hash).map_err(|e| e.to_string())?;
+
+        let l1_block_number = match self.l1_block {
+            Some(l1_block_number) => l1_block_number,
+            None => match DeoxysBackend::l1().confirmation_of(block_number).map_err(|e| e.to_string())? {
+                Some((confirmed, L1Confirmation { l1_block_number: Some(l1_block_number), .. }))
+                    if confirmed == block_number =>
+                {
+                    l1_block_number
+                }
+                _ => {
+                    return Err(format!(
+                        "no state update of block #{block_number} was recorded from L1, pass its Ethereum block with \
+                         --l1-block"
+                    ));
+                }
+            },
+        };
+
+        let header = StateSnapshotHeader {
+            version: STATE_SNAPSHOT_VERSION,
+            block_number,
+            block_hash: block_hash.into(),
+            global_state_root: block.header().global_state_root,
+            l1_block_number,
+        };
+        log::info!("📦 Exporting the state of block #{block_number} to {}", self.out.display());
+        let counts = export_state_snapshot(&self.out, header).map_err(|e| e.to_string())?;
+
+        log::info!(
+            "✅ Exported the state of block #{block_number}: {} contracts, {} storage entries, {} classes, with state \
+             root {}",
+            counts.contracts,
+            counts.storage_entries,
+            counts.classes,
+            header.global_state_root
+        );
+        Ok(())
+    }
+}
+
+/// Parses a `KEY=VALUE` object store option.
+fn parse_store_option(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid object store option {s:?}, expected `KEY=VALUE`"))
+}
+
+/// Builds the object store of `url`, configured from its environment variables and `options`, along
+/// with the prefix of the snapshots in it.
+fn object_store(url: &Url, options: &[(String, String)]) -> Result<(Arc<dyn ObjectStore>, ObjectPath), String> {
+    let invalid = |e: &dyn std::fmt::Display| format!("invalid object store url {url}: {e}");
+    let store: Arc<dyn ObjectStore> = match url.scheme() {
+        "s3" => {
+            let mut builder = AmazonS3Builder::from_env().with_url(url.as_str());
+            for (key, value) in options {
+                let key: AmazonS3ConfigKey = key.parse().map_err(|e| format!("invalid s3 option {key}: {e}"))?;
+                builder = builder.with_config(key, value);
+            }
+            Arc::new(builder.build().map_err(|e| invalid(&e))?)
+        }
+        "gs" => {
+            let mut builder = GoogleCloudStorageBuilder::from_env().with_url(url.as_str());
+            for (key, value) in options {
+                let key: GoogleConfigKey = key.parse().map_err(|e| format!("invalid gcs option {key}: {e}"))?;
+                builder = builder.with_config(key, value);
+            }
+            Arc::new(builder.build().map_err(|e| invalid(&e))?)
+        }
+        "file" => {
+            if !options.is_empty() {
+                return Err("local directories take no object store option".to_string());
+            }
+            let dir = url.to_file_path().map_err(|_| invalid(&"not a local path"))?;
+            std::fs::create_dir_all(&dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+            let store = LocalFileSystem::new_with_prefix(&dir).map_err(|e| invalid(&e))?;
+            return Ok((Arc::new(store), ObjectPath::default()));
+        }
+        scheme => return Err(invalid(&format!("unsupported scheme {scheme}"))),
+    };
+    let prefix = ObjectPath::from_url_path(url.path()).map_err(|e| invalid(&e))?;
+    Ok((store, prefix))
+}
+
+fn object_path(prefix: &ObjectPath, path: &str) -> Result<ObjectPath, String> {
+    let path = if prefix.as_ref().is_empty() { path.to_string() } else { format!("{prefix}/{path}") };
+    ObjectPath::parse(&path).map_err(|e| format!("invalid object path {path}: {e}"))
+}
+
+/// Joins a path read from a manifest to `dir`, rejecting paths escaping it.
+fn safe_join(dir: &Path, path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    if path.components().any(|component| !matches!(component, std::path::Component::Normal(_))) {
+        return Err(format!("invalid path in manifest: {}", path.display()));
+    }
+    Ok(dir.join(path))
+}
+
+/// Lists the files under `dir`, relative to `base`.
+fn list_files(base: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), String> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir).map_err(|e| format!("failed to read {}: {e}", dir.display()))? {
+        let path = entry.map_err(|e| format!("failed to read {}: {e}", dir.display()))?.path();
+        if path.is_dir() {
+            list_files(base, &path, files)?;
+        } else if let Some(relative) = path.strip_prefix(base).ok().and_then(Path::to_str) {
+            files.push(relative.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Checks the signature of a manifest against the keys of the trusted publishers.
+fn verify_signature(manifest: &[u8], signature: &[u8], trusted_keys: &[String]) -> Result<(), String> {
+    let signature = std::str::from_utf8(signature).map_err(|e| e.to_string())?;
+    let signature: [u8; 64] = hex::decode(signature.trim())
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| "signature is not 64 bytes long".to_string())?;
+    let signature = ed25519::Signature::from_raw(signature);
+
+    for key in trusted_keys {
+        let key: [u8; 32] = hex::decode(key.trim_start_matches("0x"))
+            .ok()
+            .and_then(|key| key.try_into().ok())
+            .ok_or_else(|| format!("invalid trusted key {key}"))?;
+        if ed25519::Pair::verify(&signature, manifest, &ed25519::Public::from_raw(key)) {
+            return Ok(());
+        }
+    }
+    Err("not signed by any trusted key".to_string())
+}
+
+/// Returns the size, sha3-256 checksum, and sha3-256 checksums of the chunks of a file.
+fn checksum(path: &Path) -> Result<(u64, String, Vec<String>), String> {
+    let mut file = File::open(path).map_err(|e| format!("failed to open {}: {e}", path.display()))?;
+    let mut hasher = Sha3_256::new();
+    let mut chunks = Vec::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut size = 0;
+    loop {
+        let read = read_chunk(&mut file, &mut buffer).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        chunks.push(hex::encode(Sha3_256::digest(&buffer[..read])));
+        size += read as u64;
+    }
+    Ok((size, hex::encode(hasher.finalize()), chunks))
+}
+
+/// Fills `buffer` from `file`, only returning less than a full buffer at the end of the file.
+fn read_chunk(file: &mut File, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+async fn upload(store: &dyn ObjectStore, location: &ObjectPath, path: &Path) -> Result<(), String> {
+    let mut file = tokio::fs::File::open(path).await.map_err(|e| format!("failed to open {}: {e}", path.display()))?;
+    let (_, mut writer) =
+        store.put_multipart(location).await.map_err(|e| format!("failed to upload {location}: {e}"))?;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer).await.map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read]).await.map_err(|e| format!("failed to upload {location}: {e}"))?;
+    }
+    writer.shutdown().await.map_err(|e| format!("failed to upload {location}: {e}"))
+}
+
+async fn put(store: &dyn ObjectStore, location: &ObjectPath, bytes: Vec<u8>) -> Result<(), String> {
+    store.put(location, bytes.into()).await.map(|_| ()).map_err(|e| format!("failed to upload {location}: {e}"))
+}
+
+async fn get(store: &dyn ObjectStore, location: &ObjectPath) -> Result<Vec<u8>, String> {
+    let result = store.get(location).await.map_err(|e| format!("failed to download {location}: {e}"))?;
+    result.bytes().await.map(|bytes| bytes.to_vec()).map_err(|e| format!("failed to download {location}: {e}"))
+}
+
+/// Downloads a file of a snapshot, checking each of its chunks as they are received, and the whole
+/// file once complete, against the manifest.
+async fn download(
+    store: &dyn ObjectStore,
+    location: &ObjectPath,
+    path: &Path,
+    expected: &SnapshotFile,
+    chunk_size: usize,
+) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let mut file =
+        tokio::fs::File::create(path).await.map_err(|e| format!("failed to create {}: {e}", path.display()))?;
+
+    let mut stream =
+        store.get(location).await.map_err(|e| format!("failed to download {location}: {e}"))?.into_stream();
+    let mut hasher = Sha3_256::new();
+    let mut size = 0;
+    let mut chunks = expected.chunks.iter();
+    let mut buffer = Vec::with_capacity(chunk_size);
+    let mut check_chunk = |buffer: &mut Vec<u8>| {
+        let index = expected.chunks.len() - chunks.len();
+        match chunks.next() {
+            Some(chunk) if *chunk == hex::encode(Sha3_256::digest(&buffer[..])) => {
+                buffer.clear();
+                Ok(())
+            }
+            _ => Err(format!("chunk {index} of {} does not match the manifest", expected.path)),
+        }
+    };
+
+    while let Some(bytes) = stream.next().await {
+        let mut bytes = &bytes.map_err(|e| format!("failed to download {location}: {e}"))?[..];
+        hasher.update(bytes);
+        size += bytes.len() as u64;
+        file.write_all(bytes).await.map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+
+        while !bytes.is_empty() {
+            let take = bytes.len().min(chunk_size - buffer.len());
+            buffer.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+            if buffer.len() == chunk_size {
+                check_chunk(&mut buffer)?;
+            }
+        }
+    }
+    if !buffer.is_empty() {
+        check_chunk(&mut buffer)?;
+    }
+    if chunks.next().is_some() {
+        return Err(format!("{} is missing chunks", expected.path));
+    }
+    file.flush().await.map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+
+    let sha3_256 = hex::encode(hasher.finalize());
+    if size != expected.size || sha3_256 != expected.sha3_256 {
+        return Err(format!(
+            "{} does not match the manifest: got {size} bytes with checksum {sha3_256}",
+            expected.path
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    fn signed_manifest() -> (Vec<u8>, Vec<u8>, String) {
+        let pair = ed25519::Pair::from_seed(&[1; 32]);
+        let manifest = br#"{"chain_id":"SN_MAIN"}"#.to_vec();
+        let signature = hex::encode(pair.sign(&manifest)).into_bytes();
+        (manifest, signature, hex::encode(pair.public()))
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let (manifest, signature, key) = signed_manifest();
+        let other_key = hex::encode(ed25519::Pair::from_seed(&[2; 32]).public());
+
+        assert!(verify_signature(&manifest, &signature, &[key.clone()]).is_ok());
+        assert!(verify_signature(&manifest, &signature, &[other_key.clone(), format!("0x{key}")]).is_ok());
+        assert!(verify_signature(&manifest, &signature, &[other_key]).is_err());
+        assert!(verify_signature(&manifest, &signature, &[]).is_err());
+        assert!(verify_signature(b"tampered", &signature, &[key.clone()]).is_err());
+    }
+
+    #[test]
+    fn test_verify_signature_rejects_malformed_input() {
+        let (manifest, signature, key) = signed_manifest();
+
+        assert!(verify_signature(&manifest, b"not hex", &[key.clone()]).is_err());
+        assert!(verify_signature(&manifest, &signature[..64], &[key.clone()]).is_err());
+        assert!(verify_signature(&manifest, &signature, &["00".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_download_checks_chunks() {
+        let content = b"0123456789".to_vec();
+        let chunk_size = 4;
+        let expected = SnapshotFile {
+            path: "db/file".to_string(),
+            size: content.len() as u64,
+            sha3_256: hex::encode(Sha3_256::digest(&content)),
+            chunks: content.chunks(chunk_size).map(|chunk| hex::encode(Sha3_256::digest(chunk))).collect(),
+        };
+
+        let store = InMemory::new();
+        let location = ObjectPath::from("snapshot/files/db/file");
+        let path = std::env::temp_dir().join("deoxys-test-snapshot-download");
+        let runtime = Runtime::new().expect("Failed to create the runtime");
+
+        runtime.block_on(async {
+            put(&store, &location, content.clone()).await.unwrap();
+            download(&store, &location, &path, &expected, chunk_size).await.unwrap();
+            assert_eq!(std::fs::read(&path).expect("Failed to read the downloaded file"), content);
+
+            let mut tampered = content.clone();
+            tampered[5] = b'x';
+            put(&store, &location, tampered).await.unwrap();
+            let err = download(&store, &location, &path, &expected, chunk_size).await.unwrap_err();
+            assert!(err.contains("chunk 1"), "{err}");
+
+            put(&store, &location, content[..8].to_vec()).await.unwrap();
+            let err = download(&store, &location, &path, &expected, chunk_size).await.unwrap_err();
+            assert!(err.contains("missing chunks"), "{err}");
+        });
+        let _ = std::fs::remove_file(&path);
+    }
+}
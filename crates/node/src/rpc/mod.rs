@@ -61,29 +61,87 @@ where
     P: TransactionPool<Block = DBlockT> + 'static,
     BE: Backend<DBlockT> + 'static,
 {
-    use mc_rpc::{Starknet, StarknetReadRpcApiServer, StarknetTraceRpcApiServer, StarknetWriteRpcApiServer};
+    use mc_rpc::{
+        DeoxysAdminRpcApiServer, DeoxysRpcApiServer, PathfinderRpcApiServer, Starknet, StarknetReadRpcApiServer,
+        StarknetTraceRpcApiServer, StarknetWriteRpcApiServer,
+    };
     use sc_consensus_manual_seal::rpc::{ManualSeal, ManualSealApiServer};
     use substrate_frame_rpc_system::{System, SystemApiServer};
 
     let mut module = RpcModule::new(());
     let FullDeps { client, pool, deny_unsafe, starknet: starknet_params, command_sink, .. } = deps;
+    let spec_audit = starknet_params.spec_audit.clone();
 
     module.merge(System::new(client.clone(), pool.clone(), deny_unsafe).into_rpc())?;
     module.merge(StarknetReadRpcApiServer::into_rpc(Starknet::<_, _, DHasherT>::new(
         client.clone(),
         starknet_params.sync_service.clone(),
         starknet_params.starting_block,
+        starknet_params.l1_accepted_only,
+        starknet_params.spec_version.clone(),
+        starknet_params.decode_revert_reasons,
+        starknet_params.sync_state.clone(),
+        starknet_params.execution_constants.clone(),
+        starknet_params.execution_policy.clone(),
     )))?;
     module.merge(StarknetWriteRpcApiServer::into_rpc(Starknet::<_, _, DHasherT>::new(
         client.clone(),
         starknet_params.sync_service.clone(),
         starknet_params.starting_block,
+        starknet_params.l1_accepted_only,
+        starknet_params.spec_version.clone(),
+        starknet_params.decode_revert_reasons,
+        starknet_params.sync_state.clone(),
+        starknet_params.execution_constants.clone(),
+        starknet_params.execution_policy.clone(),
     )))?;
     module.merge(StarknetTraceRpcApiServer::into_rpc(Starknet::<_, _, DHasherT>::new(
-        client,
-        starknet_params.sync_service,
+        client.clone(),
+        starknet_params.sync_service.clone(),
+        starknet_params.starting_block,
+        starknet_params.l1_accepted_only,
+        starknet_params.spec_version.clone(),
+        starknet_params.decode_revert_reasons,
+        starknet_params.sync_state.clone(),
+        starknet_params.execution_constants.clone(),
+        starknet_params.execution_policy.clone(),
+    )))?;
+    module.merge(DeoxysRpcApiServer::into_rpc(Starknet::<_, _, DHasherT>::new(
+        client.clone(),
+        starknet_params.sync_service.clone(),
         starknet_params.starting_block,
+        starknet_params.l1_accepted_only,
+        starknet_params.spec_version.clone(),
+        starknet_params.decode_revert_reasons,
+        starknet_params.sync_state.clone(),
+        starknet_params.execution_constants.clone(),
+        starknet_params.execution_policy.clone(),
     )))?;
+    module.merge(PathfinderRpcApiServer::into_rpc(Starknet::<_, _, DHasherT>::new(
+        client.clone(),
+        starknet_params.sync_service.clone(),
+        starknet_params.starting_block,
+        starknet_params.l1_accepted_only,
+        starknet_params.spec_version.clone(),
+        starknet_params.decode_revert_reasons,
+        starknet_params.sync_state.clone(),
+        starknet_params.execution_constants.clone(),
+        starknet_params.execution_policy.clone(),
+    )))?;
+    // the administration methods change the behavior of the node for every user
+    if deny_unsafe.check_if_safe().is_ok() {
+        module.merge(DeoxysAdminRpcApiServer::into_rpc(Starknet::<_, _, DHasherT>::new(
+            client,
+            starknet_params.sync_service,
+            starknet_params.starting_block,
+            starknet_params.l1_accepted_only,
+            starknet_params.spec_version,
+            starknet_params.decode_revert_reasons,
+            starknet_params.sync_state,
+            starknet_params.execution_constants,
+            starknet_params.execution_policy,
+        )))?;
+    }
 
     if let Some(command_sink) = command_sink {
         module.merge(
@@ -93,5 +151,8 @@ where
         )?;
     }
 
-    Ok(module)
+    match spec_audit {
+        Some(audit) => Ok(mc_rpc::spec_audit::audit_module(module, audit)?),
+        None => Ok(module),
+    }
 }
@@ -2,6 +2,10 @@ use std::sync::Arc;
 
 use mc_db::DeoxysBackend;
 use mc_genesis_data_provider::GenesisProvider;
+use mc_rpc::execution_constants::ExecutionConstants;
+use mc_rpc::execution_policy::ExecutionPolicy;
+use mc_rpc::spec_audit::SpecAudit;
+use mc_sync::state::SyncState;
 use sc_network_sync::SyncingService;
 use sp_api::BlockT;
 use sp_runtime::traits::Header as HeaderT;
@@ -18,6 +22,20 @@ pub struct StarknetDeps<C, G: GenesisProvider, B: BlockT> {
     pub starting_block: <<B>::Header as HeaderT>::Number,
     /// The genesis state data provider
     pub genesis_provider: Arc<G>,
+    /// Whether only blocks covered by a state update verified on L1 are served.
+    pub l1_accepted_only: bool,
+    /// The version of the Starknet RPC specification reported by the node.
+    pub spec_version: String,
+    /// Whether a readable form of the revert reasons is attached to traces and receipts.
+    pub decode_revert_reasons: bool,
+    /// The state of the sync, such as its progress and the pending block.
+    pub sync_state: Arc<SyncState>,
+    /// The limits used to execute transactions and calls.
+    pub execution_constants: Arc<ExecutionConstants>,
+    /// The classes and entry points the RPC refuses to execute.
+    pub execution_policy: Arc<ExecutionPolicy>,
+    /// The specification the responses are audited against, if any.
+    pub spec_audit: Option<Arc<SpecAudit>>,
 }
 
 impl<C, G: GenesisProvider, B: BlockT> Clone for StarknetDeps<C, G, B> {
@@ -28,6 +46,13 @@ impl<C, G: GenesisProvider, B: BlockT> Clone for StarknetDeps<C, G, B> {
             sync_service: self.sync_service.clone(),
             starting_block: self.starting_block,
             genesis_provider: self.genesis_provider.clone(),
+            l1_accepted_only: self.l1_accepted_only,
+            spec_version: self.spec_version.clone(),
+            decode_revert_reasons: self.decode_revert_reasons,
+            sync_state: self.sync_state.clone(),
+            execution_constants: self.execution_constants.clone(),
+            execution_policy: self.execution_policy.clone(),
+            spec_audit: self.spec_audit.clone(),
         }
     }
 }
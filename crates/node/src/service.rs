@@ -10,11 +10,17 @@ use futures::channel::mpsc;
 use futures::future;
 use futures::future::BoxFuture;
 use futures::prelude::*;
-use mc_db::DeoxysBackend;
+use mc_db::compaction::CompactionPolicy;
+use mc_db::pruning::StatePruning;
+use mc_db::{ColdStorage, DeoxysBackend};
 use mc_genesis_data_provider::OnDiskGenesisConfig;
 use mc_mapping_sync::MappingSyncWorker;
+use mc_rpc::execution_constants::{ExecutionConstants, CONSTANTS_POLL_INTERVAL};
+use mc_rpc::execution_policy::ExecutionPolicy;
+use mc_rpc::spec_audit::SpecAudit;
 use mc_sync::fetch::fetchers::FetchConfig;
 use mc_sync::starknet_sync_worker;
+use mc_sync::state::SyncState;
 use mp_block::DeoxysBlock;
 use mp_types::block::{DBlockT, DHashT, DHasherT};
 use parity_scale_codec::Encode;
@@ -34,6 +40,7 @@ use sp_inherents::InherentData;
 use sp_runtime::testing::Digest;
 use sp_runtime::traits::Block as BlockT;
 use sp_runtime::DigestItem;
+use tokio_util::sync::CancellationToken;
 
 use crate::configs::db_config_dir;
 use crate::genesis_block::DeoxysGenesisBlockBuilder;
@@ -62,7 +69,7 @@ impl sc_executor::NativeExecutionDispatch for ExecutorDispatch {
 }
 
 pub type FullClient = sc_service::TFullClient<DBlockT, RuntimeApi, NativeElseWasmExecutor<ExecutorDispatch>>;
-type FullBackend = sc_service::TFullBackend<DBlockT>;
+pub type FullBackend = sc_service::TFullBackend<DBlockT>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, DBlockT>;
 
 type BasicImportQueue = sc_consensus::DefaultImportQueue<DBlockT>;
@@ -73,6 +80,7 @@ pub fn new_partial<BIQ>(
     config: &Configuration,
     build_import_queue: BIQ,
     cache_more_things: bool,
+    cold_storage: Option<ColdStorage>,
     genesis_block: DeoxysBlock,
 ) -> Result<
     sc_service::PartialComponents<
@@ -94,7 +102,8 @@ where
         &TaskManager,
     ) -> Result<(BasicImportQueue, BoxBlockImport), ServiceError>,
 {
-    let deoxys_backend = DeoxysBackend::open(&config.database, &db_config_dir(config), cache_more_things).unwrap();
+    let deoxys_backend =
+        DeoxysBackend::open(&config.database, &db_config_dir(config), cache_more_things, cold_storage).unwrap();
 
     let telemetry = config
         .telemetry_endpoints
@@ -189,14 +198,38 @@ where
 /// # Arguments
 ///
 /// - `cache`: whether more information should be cached when storing the block in the database.
+/// - `cold_storage`: the secondary database the data of the oldest blocks is moved to, if any.
+/// - `state_pruning`: how much of the state history is kept, the older history being pruned in the
+///   background.
+/// - `index_transfers`: whether the ERC-20 transfers are indexed by account as blocks are stored.
+/// - `l1_accepted_only`: whether the RPC only serves blocks covered by a state update verified on
+///   L1.
+/// - `spec_version`: the version of the Starknet RPC specification reported by the RPC.
+/// - `decode_revert_reasons`: whether the RPC attaches a readable form of the revert reasons to
+///   traces and receipts.
+/// - `sync_state`: the state of the sync, updated by the sync worker and read by the RPC.
+/// - `execution_constants`: the limits used by the RPC to execute transactions and calls, reloaded
+///   whenever their override file changes.
+/// - `spec_audit`: the specification the responses of the RPC are audited against, if any.
+#[allow(clippy::too_many_arguments)]
 pub fn new_full(
     config: Configuration,
     sealing: SealingMode,
     l1_url: Url,
     cache_more_things: bool,
+    cold_storage: Option<ColdStorage>,
+    compaction: Option<CompactionPolicy>,
+    state_pruning: StatePruning,
+    index_transfers: bool,
     fetch_config: FetchConfig,
     genesis_block: DeoxysBlock,
     starting_block: Option<u32>,
+    l1_accepted_only: bool,
+    spec_version: String,
+    decode_revert_reasons: bool,
+    sync_state: Arc<SyncState>,
+    execution_constants: Arc<ExecutionConstants>,
+    spec_audit: Option<Arc<SpecAudit>>,
 ) -> Result<TaskManager, ServiceError> {
     let build_import_queue = build_manual_seal_import_queue;
 
@@ -209,7 +242,16 @@ pub fn new_full(
         select_chain,
         transaction_pool,
         other: (block_import, mut telemetry, deoxys_backend),
-    } = new_partial(&config, build_import_queue, cache_more_things, genesis_block)?;
+    } = new_partial(&config, build_import_queue, cache_more_things, cold_storage.clone(), genesis_block)?;
+
+    if let Some(policy) = compaction {
+        mc_db::compaction::start(policy);
+    }
+    mc_db::pruning::start(state_pruning);
+    mc_db::class_index::start();
+    if let Some(cold_storage) = cold_storage {
+        mc_db::cold_storage::start(cold_storage.after_blocks);
+    }
 
     let net_config = sc_network::config::FullNetworkConfiguration::new(&config.network);
 
@@ -249,6 +291,17 @@ pub fn new_full(
         sync_service: sync_service.clone(),
         starting_block: on_block.unwrap(),
         genesis_provider: genesis_data.into(),
+        l1_accepted_only,
+        spec_version,
+        decode_revert_reasons,
+        sync_state: Arc::clone(&sync_state),
+        execution_constants: Arc::clone(&execution_constants),
+        // shared by all the rpc servers, so that the policy set through one applies to all
+        execution_policy: Arc::new(
+            ExecutionPolicy::restore()
+                .map_err(|_| ServiceError::Other("Failed to restore the execution policy".into()))?,
+        ),
+        spec_audit,
     };
 
     let rpc_extensions_builder = {
@@ -296,25 +349,40 @@ pub fn new_full(
             3,
             0,
             prometheus_registry.clone(),
+            index_transfers,
         )
         .for_each(|()| future::ready(())),
     );
 
-    let (block_sender, block_receiver) = tokio::sync::mpsc::channel::<DeoxysBlock>(100);
-
-    task_manager.spawn_essential_handle().spawn(
-        "starknet-sync-worker",
+    task_manager.spawn_handle().spawn(
+        "execution-constants-watcher",
         Some(DEOXYS_TASK_GROUP),
-        starknet_sync_worker::sync(
-            fetch_config,
-            block_sender,
-            command_sink.unwrap().clone(),
-            l1_url,
-            Arc::clone(&client),
-            on_block.unwrap(),
-        ),
+        execution_constants.watch(CONSTANTS_POLL_INTERVAL),
     );
 
+    let (block_sender, block_receiver) = tokio::sync::mpsc::channel::<DeoxysBlock>(100);
+
+    // the sync runs outside of the task manager, which drops its tasks on shutdown: the sync is
+    // cancelled instead, so that it stops after the block being applied rather than in the middle
+    // of it
+    let cancel_sync = CancellationToken::new();
+    let sync = tokio::spawn(starknet_sync_worker::sync(
+        fetch_config,
+        block_sender,
+        command_sink.unwrap().clone(),
+        l1_url,
+        Arc::clone(&client),
+        backend.clone(),
+        on_block.unwrap(),
+        prometheus_registry.clone(),
+        sync_state,
+        cancel_sync.clone(),
+    ));
+    task_manager.spawn_essential_handle().spawn("starknet-sync-worker", Some(DEOXYS_TASK_GROUP), async move {
+        let _cancel_on_shutdown = cancel_sync.drop_guard();
+        let _ = sync.await;
+    });
+
     // manual-seal authorship
     if !sealing.is_default() {
         run_manual_seal_authorship(
@@ -472,9 +540,18 @@ where
 type ChainOpsResult =
     Result<(Arc<FullClient>, Arc<FullBackend>, BasicQueue<DBlockT>, TaskManager, Arc<DeoxysBackend>), ServiceError>;
 
-pub fn new_chain_ops(config: &mut Configuration, cache_more_things: bool) -> ChainOpsResult {
+pub fn new_chain_ops(
+    config: &mut Configuration,
+    cache_more_things: bool,
+    cold_storage: Option<ColdStorage>,
+) -> ChainOpsResult {
     config.keystore = sc_service::config::KeystoreConfig::InMemory;
-    let sc_service::PartialComponents { client, backend, import_queue, task_manager, other, .. } =
-        new_partial::<_>(config, build_manual_seal_import_queue, cache_more_things, DeoxysBlock::default())?;
+    let sc_service::PartialComponents { client, backend, import_queue, task_manager, other, .. } = new_partial::<_>(
+        config,
+        build_manual_seal_import_queue,
+        cache_more_things,
+        cold_storage,
+        DeoxysBlock::default(),
+    )?;
     Ok((client, backend, import_queue, task_manager, other.2))
 }
@@ -463,7 +463,7 @@ impl<T: Config> Pallet<T> {
     /// Returns a storage keys and values of a given contract
     pub fn get_storage_from(contract_address: ContractAddress) -> Result<Vec<(StorageKey, StarkFelt)>, DispatchError> {
         Ok(storage_handler::contract_storage_trie()
-            .get_storage(&contract_address)
+            .and_then(|trie| trie.get_storage(&contract_address))
             .map_err(|_| Error::<T>::ContractNotFound)?)
     }
 
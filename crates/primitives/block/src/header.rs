@@ -5,12 +5,12 @@ use blockifier::context::{BlockContext, ChainInfo, FeeTokenAddresses};
 use blockifier::versioned_constants::VersionedConstants;
 use mp_felt::Felt252Wrapper;
 use mp_hashers::HasherT;
+use mp_transactions::LegacyHashes;
 use sp_core::U256;
 use starknet_api::block::{BlockNumber, BlockTimestamp};
 use starknet_api::core::{ChainId, ContractAddress};
 use starknet_api::data_availability::L1DataAvailabilityMode;
 use starknet_api::hash::StarkHash;
-use starknet_core::types::FieldElement;
 
 /// Block status.
 ///
@@ -135,9 +135,9 @@ impl Header {
         )
     }
 
-    /// Compute the hash of the header.
-    pub fn hash<H: HasherT>(&self) -> Felt252Wrapper {
-        if self.block_number >= 833 {
+    /// Compute the hash of the header, on the network with this chain id.
+    pub fn hash<H: HasherT>(&self, chain_id: Felt252Wrapper) -> Felt252Wrapper {
+        if !LegacyHashes::of_chain(chain_id).is_some_and(|legacy| self.block_number < legacy.block) {
             // Computes the block hash for blocks generated after Cairo 0.7.0
             let data: &[Felt252Wrapper] = &[
                 self.block_number.into(),           // block number
@@ -167,7 +167,7 @@ impl Header {
                 Felt252Wrapper::ZERO,
                 Felt252Wrapper::ZERO,
                 Felt252Wrapper::ZERO,
-                Felt252Wrapper(FieldElement::from_byte_slice_be(b"SN_MAIN").unwrap()),
+                chain_id,
                 self.parent_block_hash.into(),
             ];
 
@@ -104,3 +104,20 @@ fn test_to_block_context() {
         &fee_token_addresses.strk_fee_token_address
     );
 }
+
+#[test]
+fn test_legacy_header_hash_per_network() {
+    let mainnet = Felt252Wrapper::from_hex_be("0x534e5f4d41494e").unwrap(); // SN_MAIN
+    let sepolia = Felt252Wrapper::from_hex_be("0x534e5f5345504f4c4941").unwrap(); // SN_SEPOLIA
+    let sequencer_address = ContractAddress(PatriciaKey(StarkFelt::try_from("0xFF").unwrap()));
+    let header = Header { block_number: 1, block_timestamp: 1, sequencer_address, ..Default::default() };
+
+    // the first blocks of mainnet do not commit to their sequencer address nor their timestamp
+    let legacy_hash = header.hash::<PedersenHasher>(mainnet);
+    assert_eq!(legacy_hash, Header { block_timestamp: 2, ..header.clone() }.hash::<PedersenHasher>(mainnet));
+
+    // the other networks hash every block with the current format
+    let hash = header.hash::<PedersenHasher>(sepolia);
+    assert_ne!(hash, legacy_hash);
+    assert_ne!(hash, Header { block_timestamp: 2, ..header }.hash::<PedersenHasher>(sepolia));
+}
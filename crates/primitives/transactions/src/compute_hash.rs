@@ -15,7 +15,7 @@ use starknet_core::utils::starknet_keccak;
 use starknet_crypto::FieldElement;
 
 use super::SIMULATE_TX_VERSION_OFFSET;
-use crate::{LEGACY_BLOCK_NUMBER, LEGACY_L1_HANDLER_BLOCK};
+use crate::LegacyHashes;
 
 const DECLARE_PREFIX: &[u8] = b"declare";
 const DEPLOY_ACCOUNT_PREFIX: &[u8] = b"deploy_account";
@@ -98,7 +98,7 @@ impl ComputeTransactionHash for InvokeTransactionV0 {
         let max_fee = FieldElement::from(self.max_fee.0);
 
         // Check for deprecated environment
-        if block_number > Some(LEGACY_BLOCK_NUMBER) {
+        if !is_legacy_transaction(chain_id, block_number) {
             Felt252Wrapper(H::compute_hash_on_elements(&[
                 prefix,
                 version,
@@ -495,9 +495,10 @@ impl ComputeTransactionHash for L1HandlerTransaction {
         let entrypoint_selector = Felt252Wrapper::from(self.entry_point_selector).into();
         let calldata_hash = compute_hash_on_elements(&convert_calldata(self.calldata.clone()));
         let nonce = Felt252Wrapper::from(self.nonce).into();
+        let legacy = LegacyHashes::of_chain(chain_id);
         let chain_id = chain_id.into();
 
-        if block_number < Some(LEGACY_L1_HANDLER_BLOCK) && block_number.is_some() {
+        if legacy.zip(block_number).is_some_and(|(legacy, block_number)| block_number < legacy.l1_handler) {
             Felt252Wrapper::from(H::compute_hash_on_elements(&[
                 invoke_prefix,
                 contract_address,
@@ -506,7 +507,7 @@ impl ComputeTransactionHash for L1HandlerTransaction {
                 chain_id,
             ]))
             .into()
-        } else if block_number < Some(LEGACY_BLOCK_NUMBER) && block_number.is_some() {
+        } else if legacy.zip(block_number).is_some_and(|(legacy, block_number)| block_number < legacy.transaction) {
             Felt252Wrapper::from(H::compute_hash_on_elements(&[
                 prefix,
                 contract_address,
@@ -532,6 +533,12 @@ impl ComputeTransactionHash for L1HandlerTransaction {
     }
 }
 
+/// Whether the transactions of `block_number` are hashed without their version on the network with
+/// this chain id, which is also the case of the transactions outside of a block on such a network.
+fn is_legacy_transaction(chain_id: Felt252Wrapper, block_number: Option<u64>) -> bool {
+    LegacyHashes::of_chain(chain_id).is_some_and(|legacy| block_number <= Some(legacy.transaction))
+}
+
 pub fn compute_hash_given_contract_address<H: HasherT>(
     transaction: DeployTransaction,
     chain_id: FieldElement,
@@ -546,7 +553,7 @@ pub fn compute_hash_given_contract_address<H: HasherT>(
 
     let constructor = starknet_keccak(b"constructor");
 
-    if block_number > Some(LEGACY_BLOCK_NUMBER) {
+    if !is_legacy_transaction(Felt252Wrapper(chain_id), block_number) {
         Felt252Wrapper(H::compute_hash_on_elements(&[
             prefix,
             version,
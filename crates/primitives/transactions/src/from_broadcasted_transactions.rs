@@ -86,29 +86,8 @@ fn declare_to_account_transaction(
             contract_class,
             is_query: _,
         }) => {
-            // Create a GzipDecoder to decompress the bytes
-            let mut gz = GzDecoder::new(&contract_class.program[..]);
-
-            // Read the decompressed bytes into a Vec<u8>
-            let mut decompressed_bytes = Vec::new();
-            std::io::Read::read_to_end(&mut gz, &mut decompressed_bytes)
-                .map_err(|_| BroadcastedTransactionConversionError::ProgramDecompressionFailed)?;
-
-            let class_hash = {
-                let legacy_contract_class = LegacyContractClass {
-                    program: serde_json::from_slice(decompressed_bytes.as_slice())
-                        .map_err(|_| BroadcastedTransactionConversionError::ProgramDeserializationFailed)?,
-                    abi: match contract_class.abi.as_ref() {
-                        Some(abi) => Some(abi.iter().cloned().map(|entry| entry.into()).collect::<Vec<_>>()),
-                        None => vec![].into(),
-                    },
-                    entry_points_by_type: to_raw_legacy_entry_points(contract_class.entry_points_by_type.clone()),
-                };
-
-                legacy_contract_class
-                    .class_hash()
-                    .map_err(|_| BroadcastedTransactionConversionError::ClassHashComputationFailed)?
-            };
+            let decompressed_bytes = decompress_program(&contract_class.program)?;
+            let class_hash = legacy_class_hash(&contract_class, &decompressed_bytes)?;
 
             let blockifier_contract_class = instantiate_blockifier_contract_class(&contract_class, decompressed_bytes)?;
 
@@ -496,6 +475,33 @@ fn instantiate_blockifier_contract_class(
     Ok(contract_class)
 }
 
+/// Decompresses the program of a [CompressedLegacyContractClass] into its json bytes
+pub fn decompress_program(program: &[u8]) -> Result<Vec<u8>, BroadcastedTransactionConversionError> {
+    let mut gz = GzDecoder::new(program);
+    let mut decompressed_bytes = Vec::new();
+    std::io::Read::read_to_end(&mut gz, &mut decompressed_bytes)
+        .map_err(|_| BroadcastedTransactionConversionError::ProgramDecompressionFailed)?;
+    Ok(decompressed_bytes)
+}
+
+/// Computes the hash of a [CompressedLegacyContractClass], given its decompressed program
+pub fn legacy_class_hash(
+    contract_class: &CompressedLegacyContractClass,
+    decompressed_program: &[u8],
+) -> Result<FieldElement, BroadcastedTransactionConversionError> {
+    let legacy_contract_class = LegacyContractClass {
+        program: serde_json::from_slice(decompressed_program)
+            .map_err(|_| BroadcastedTransactionConversionError::ProgramDeserializationFailed)?,
+        abi: match contract_class.abi.as_ref() {
+            Some(abi) => Some(abi.iter().cloned().map(|entry| entry.into()).collect::<Vec<_>>()),
+            None => vec![].into(),
+        },
+        entry_points_by_type: to_raw_legacy_entry_points(contract_class.entry_points_by_type.clone()),
+    };
+
+    legacy_contract_class.class_hash().map_err(|_| BroadcastedTransactionConversionError::ClassHashComputationFailed)
+}
+
 fn to_raw_legacy_entry_point(entry_point: LegacyContractEntryPoint) -> RawLegacyEntryPoint {
     RawLegacyEntryPoint { offset: LegacyEntrypointOffset::U64AsInt(entry_point.offset), selector: entry_point.selector }
 }
@@ -16,6 +16,7 @@ pub mod utils;
 use blockifier::transaction::account_transaction::AccountTransaction;
 use blockifier::transaction::transaction_execution::Transaction;
 use blockifier::transaction::transaction_types::TransactionType;
+use mp_felt::Felt252Wrapper;
 use starknet_ff::FieldElement;
 
 const SIMULATE_TX_VERSION_OFFSET: FieldElement =
@@ -26,6 +27,32 @@ const SIMULATE_TX_VERSION_OFFSET: FieldElement =
 
 pub const LEGACY_BLOCK_NUMBER: u64 = 1470;
 pub const LEGACY_L1_HANDLER_BLOCK: u64 = 854;
+pub const LEGACY_BLOCK_HASH_BLOCK: u64 = 833;
+
+/// The blocks from which the current hash formats apply, on a network whose first blocks are
+/// hashed with legacy formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LegacyHashes {
+    /// Last block whose transactions are hashed without their version.
+    pub transaction: u64,
+    /// First block whose L1 handler transactions are hashed with their nonce.
+    pub l1_handler: u64,
+    /// First block whose hash commits to its sequencer address, timestamp and events.
+    pub block: u64,
+}
+
+impl LegacyHashes {
+    pub const MAINNET: Self =
+        Self { transaction: LEGACY_BLOCK_NUMBER, l1_handler: LEGACY_L1_HANDLER_BLOCK, block: LEGACY_BLOCK_HASH_BLOCK };
+
+    /// The legacy hash formats of the network with this chain id, if it has any.
+    ///
+    /// Mainnet is the only live network old enough to have blocks hashed with the legacy formats:
+    /// the other networks hash every block with the current ones.
+    pub fn of_chain(chain_id: Felt252Wrapper) -> Option<Self> {
+        (chain_id.0 == FieldElement::from_byte_slice_be(b"SN_MAIN").unwrap()).then_some(Self::MAINNET)
+    }
+}
 
 /// Wrapper type for transaction execution error.
 /// Different tx types.